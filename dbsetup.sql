@@ -40,6 +40,11 @@ BEGIN
     sch||'.trades', 'time', p_chunk_trades
   );
 
+  -- Conflict target of the writer's idempotent trade inserts (ON CONFLICT DO NOTHING);
+  -- required, not gated by p_create_indexes. Rows with a NULL trade_id never conflict.
+  -- Existing databases: migrations/0001_trades_conflict_index.sql (de-dups, then adds it).
+  EXECUTE format('CREATE UNIQUE INDEX IF NOT EXISTS %I ON %I.trades (time, symbol, trade_id);', sch||'_trades_uq', sch);
+
   IF p_create_indexes THEN
     EXECUTE format('CREATE INDEX IF NOT EXISTS %I ON %I.trades (symbol, time DESC);', sch||'_trades_sym_time', sch);
   END IF;
@@ -67,6 +72,96 @@ BEGIN
     EXECUTE format('CREATE INDEX IF NOT EXISTS %I ON %I.depth_deltas (symbol, time DESC);', sch||'_depth_sym_time', sch);
   END IF;
 
+  ---------------------------------------------------------------------------
+  -- DEPTH SNAPSHOTS (row per level of a full L2 book snapshot)
+  ---------------------------------------------------------------------------
+  EXECUTE format($SQL$
+    CREATE TABLE IF NOT EXISTS %I.depth_snapshots (
+      time        TIMESTAMPTZ NOT NULL,
+      symbol      TEXT        NOT NULL,
+      side        SMALLINT    NOT NULL,   -- 0=bid, 1=ask
+      price_i     BIGINT      NOT NULL,   -- scaled integer price
+      size_i      BIGINT      NOT NULL,   -- scaled size at price
+      level_idx   INTEGER     NOT NULL,   -- 0 = best level of the side
+      snapshot_id BIGINT      NOT NULL    -- shared by every level of one snapshot
+    );
+  $SQL$, sch);
+
+  EXECUTE format(
+    'SELECT create_hypertable(%L, %L, chunk_time_interval => %L::interval, if_not_exists => TRUE);',
+    sch||'.depth_snapshots', 'time', p_chunk_depth
+  );
+
+  IF p_create_indexes THEN
+    EXECUTE format('CREATE INDEX IF NOT EXISTS %I ON %I.depth_snapshots (symbol, snapshot_id DESC);', sch||'_depth_snap_sym_id', sch);
+  END IF;
+
+  ---------------------------------------------------------------------------
+  -- BBO (best bid / best ask, derived from depth when emit_bbo is on)
+  ---------------------------------------------------------------------------
+  EXECUTE format($SQL$
+    CREATE TABLE IF NOT EXISTS %I.bbo (
+      time        TIMESTAMPTZ NOT NULL,
+      symbol      TEXT        NOT NULL,
+      bid_px_i    BIGINT      NULL,       -- NULL = empty side
+      bid_sz_i    BIGINT      NULL,
+      ask_px_i    BIGINT      NULL,
+      ask_sz_i    BIGINT      NULL
+    );
+  $SQL$, sch);
+
+  EXECUTE format(
+    'SELECT create_hypertable(%L, %L, chunk_time_interval => %L::interval, if_not_exists => TRUE);',
+    sch||'.bbo', 'time', p_chunk_depth
+  );
+
+  IF p_create_indexes THEN
+    EXECUTE format('CREATE INDEX IF NOT EXISTS %I ON %I.bbo (symbol, time DESC);', sch||'_bbo_sym_time', sch);
+  END IF;
+
+  ---------------------------------------------------------------------------
+  -- MICROPRICE (size-weighted mid of each BBO row, when emit_microprice is on)
+  ---------------------------------------------------------------------------
+  EXECUTE format($SQL$
+    CREATE TABLE IF NOT EXISTS %I.microprice (
+      time          TIMESTAMPTZ NOT NULL,
+      symbol        TEXT        NOT NULL,
+      microprice_i  BIGINT      NOT NULL    -- scaled like the prices
+    );
+  $SQL$, sch);
+
+  EXECUTE format(
+    'SELECT create_hypertable(%L, %L, chunk_time_interval => %L::interval, if_not_exists => TRUE);',
+    sch||'.microprice', 'time', p_chunk_depth
+  );
+
+  IF p_create_indexes THEN
+    EXECUTE format('CREATE INDEX IF NOT EXISTS %I ON %I.microprice (symbol, time DESC);', sch||'_microprice_sym_time', sch);
+  END IF;
+
+  ---------------------------------------------------------------------------
+  -- TRADE IMBALANCE (buy/sell volume per window, derived from trades when
+  -- writer.trade_imbalance_window_ms > 0)
+  ---------------------------------------------------------------------------
+  EXECUTE format($SQL$
+    CREATE TABLE IF NOT EXISTS %I.trade_imbalance (
+      time        TIMESTAMPTZ NOT NULL,   -- window start
+      symbol      TEXT        NOT NULL,
+      buy_vol_i   BIGINT      NOT NULL,   -- scaled, sum of buy qty_i
+      sell_vol_i  BIGINT      NOT NULL,   -- scaled, sum of sell qty_i
+      count       BIGINT      NOT NULL
+    );
+  $SQL$, sch);
+
+  EXECUTE format(
+    'SELECT create_hypertable(%L, %L, chunk_time_interval => %L::interval, if_not_exists => TRUE);',
+    sch||'.trade_imbalance', 'time', p_chunk_trades
+  );
+
+  IF p_create_indexes THEN
+    EXECUTE format('CREATE INDEX IF NOT EXISTS %I ON %I.trade_imbalance (symbol, time DESC);', sch||'_trade_imb_sym_time', sch);
+  END IF;
+
   ---------------------------------------------------------------------------
   -- OPEN INTEREST
   ---------------------------------------------------------------------------
@@ -151,6 +246,30 @@ BEGIN
     );
   $SQL$, sch);
 
+  EXECUTE format($SQL$
+    ALTER TABLE %I.depth_snapshots SET (
+      timescaledb.compress,
+      timescaledb.compress_segmentby = 'symbol,side',
+      timescaledb.compress_orderby   = 'time DESC'
+    );
+  $SQL$, sch);
+
+  EXECUTE format($SQL$
+    ALTER TABLE %I.bbo SET (
+      timescaledb.compress,
+      timescaledb.compress_segmentby = 'symbol',
+      timescaledb.compress_orderby   = 'time DESC'
+    );
+  $SQL$, sch);
+
+  EXECUTE format($SQL$
+    ALTER TABLE %I.microprice SET (
+      timescaledb.compress,
+      timescaledb.compress_segmentby = 'symbol',
+      timescaledb.compress_orderby   = 'time DESC'
+    );
+  $SQL$, sch);
+
   EXECUTE format($SQL$
     ALTER TABLE %I.open_interest SET (
       timescaledb.compress,
@@ -197,6 +316,33 @@ BEGIN
     EXECUTE format('SELECT add_compression_policy(%L, %L::interval);', sch||'.depth_deltas', p_compress_after);
   END IF;
 
+  IF NOT EXISTS (
+    SELECT 1 FROM timescaledb_information.jobs
+    WHERE proc_name = 'policy_compression'
+      AND hypertable_schema = sch
+      AND hypertable_name = 'depth_snapshots'
+  ) THEN
+    EXECUTE format('SELECT add_compression_policy(%L, %L::interval);', sch||'.depth_snapshots', p_compress_after);
+  END IF;
+
+  IF NOT EXISTS (
+    SELECT 1 FROM timescaledb_information.jobs
+    WHERE proc_name = 'policy_compression'
+      AND hypertable_schema = sch
+      AND hypertable_name = 'bbo'
+  ) THEN
+    EXECUTE format('SELECT add_compression_policy(%L, %L::interval);', sch||'.bbo', p_compress_after);
+  END IF;
+
+  IF NOT EXISTS (
+    SELECT 1 FROM timescaledb_information.jobs
+    WHERE proc_name = 'policy_compression'
+      AND hypertable_schema = sch
+      AND hypertable_name = 'microprice'
+  ) THEN
+    EXECUTE format('SELECT add_compression_policy(%L, %L::interval);', sch||'.microprice', p_compress_after);
+  END IF;
+
   IF NOT EXISTS (
     SELECT 1 FROM timescaledb_information.jobs
     WHERE proc_name = 'policy_compression'
@@ -243,6 +389,33 @@ BEGIN
     EXECUTE format('SELECT add_retention_policy(%L, %L::interval);', sch||'.depth_deltas', p_retention);
   END IF;
 
+  IF NOT EXISTS (
+    SELECT 1 FROM timescaledb_information.jobs
+    WHERE proc_name = 'policy_retention'
+      AND hypertable_schema = sch
+      AND hypertable_name = 'depth_snapshots'
+  ) THEN
+    EXECUTE format('SELECT add_retention_policy(%L, %L::interval);', sch||'.depth_snapshots', p_retention);
+  END IF;
+
+  IF NOT EXISTS (
+    SELECT 1 FROM timescaledb_information.jobs
+    WHERE proc_name = 'policy_retention'
+      AND hypertable_schema = sch
+      AND hypertable_name = 'bbo'
+  ) THEN
+    EXECUTE format('SELECT add_retention_policy(%L, %L::interval);', sch||'.bbo', p_retention);
+  END IF;
+
+  IF NOT EXISTS (
+    SELECT 1 FROM timescaledb_information.jobs
+    WHERE proc_name = 'policy_retention'
+      AND hypertable_schema = sch
+      AND hypertable_name = 'microprice'
+  ) THEN
+    EXECUTE format('SELECT add_retention_policy(%L, %L::interval);', sch||'.microprice', p_retention);
+  END IF;
+
   IF NOT EXISTS (
     SELECT 1 FROM timescaledb_information.jobs
     WHERE proc_name = 'policy_retention'
@@ -0,0 +1,46 @@
+-- Unique index behind the writer's idempotent trade inserts
+-- (TradeDBRow::CONFLICT_TARGET = time, symbol, trade_id), for databases set up with a
+-- dbsetup.sql older than the index. Until it runs, trades are written with a plain INSERT
+-- (the startup check warns once per table) and replayed trades may be stored twice.
+--
+-- Per ex_* schema with a trades table: blocks writes to it for the rest of the transaction,
+-- deletes repeated (time, symbol, trade_id) rows keeping one, then creates the index.
+-- Rows with a NULL trade_id are left alone (they never conflict). Idempotent.
+-- Compressed chunks must accept DELETE (TimescaleDB >= 2.11) or be decompressed first.
+--
+--   psql "$DATABASE_URL" -v ON_ERROR_STOP=1 -f migrations/0001_trades_conflict_index.sql
+--
+-- Run it on every shard, then restart (or wait for the next start) to leave the fallback.
+
+DO $$
+DECLARE
+  sch     TEXT;
+  removed BIGINT;
+BEGIN
+  FOR sch IN
+    SELECT n.nspname
+    FROM pg_class c
+    JOIN pg_namespace n ON n.oid = c.relnamespace
+    WHERE c.relname = 'trades'
+      AND c.relkind IN ('r', 'p')
+      AND n.nspname LIKE 'ex\_%'
+  LOOP
+    EXECUTE format('LOCK TABLE %I.trades IN SHARE ROW EXCLUSIVE MODE', sch);
+
+    EXECUTE format($SQL$
+      DELETE FROM %1$I.trades a
+      USING %1$I.trades b
+      WHERE a.trade_id IS NOT NULL
+        AND a.time = b.time
+        AND a.symbol = b.symbol
+        AND a.trade_id = b.trade_id
+        AND a.tableoid = b.tableoid
+        AND a.ctid > b.ctid
+    $SQL$, sch);
+    GET DIAGNOSTICS removed = ROW_COUNT;
+    RAISE NOTICE '%.trades: % duplicate rows removed', sch, removed;
+
+    EXECUTE format('CREATE UNIQUE INDEX IF NOT EXISTS %I ON %I.trades (time, symbol, trade_id);', sch||'_trades_uq', sch);
+  END LOOP;
+END
+$$;
@@ -20,6 +20,8 @@ CREATE TABLE IF NOT EXISTS mini_fintickstreams.stream_registry (
   flush_interval_ms       bigint  NOT NULL DEFAULT 1000 CHECK (flush_interval_ms > 0),
   chunk_rows              integer NOT NULL DEFAULT 1000 CHECK (chunk_rows > 0),
   hard_cap_rows           integer NOT NULL DEFAULT 5000 CHECK (hard_cap_rows > 0),
+  emit_bbo                boolean NOT NULL DEFAULT false,
+  emit_microprice         boolean NOT NULL DEFAULT false,
 
   created_at  timestamptz NOT NULL DEFAULT now(),
   updated_at  timestamptz NOT NULL DEFAULT now(),
@@ -38,3 +40,8 @@ CREATE INDEX IF NOT EXISTS stream_registry_enabled_idx
 ON mini_fintickstreams.stream_registry (enabled)
 WHERE enabled = true;
 
+
+-- Upgrade path for registries created before a knob column existed
+ALTER TABLE mini_fintickstreams.stream_registry
+  ADD COLUMN IF NOT EXISTS emit_bbo boolean NOT NULL DEFAULT false,
+  ADD COLUMN IF NOT EXISTS emit_microprice boolean NOT NULL DEFAULT false;
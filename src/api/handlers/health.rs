@@ -1,7 +1,8 @@
-use axum::{Json, extract::State};
+use axum::{Json, extract::State, http::StatusCode};
 
-use crate::api::types::HealthResp;
+use crate::api::types::{DbShardsResp, HealthResp};
 use crate::app::AppRuntime;
+use crate::app::health::AppHealth;
 
 pub async fn runtime(State(app): State<AppRuntime>) -> Json<HealthResp> {
     Json(HealthResp {
@@ -9,6 +10,30 @@ pub async fn runtime(State(app): State<AppRuntime>) -> Json<HealthResp> {
     })
 }
 
+/// GET /readyz
+/// 200 when runtime is GREEN and enough DB shards are healthy to write, 503 otherwise.
+pub async fn readyz(State(app): State<AppRuntime>) -> (StatusCode, Json<HealthResp>) {
+    let ok = app.is_ready().await;
+    let code = if ok {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (code, Json(HealthResp { ok }))
+}
+
+/// GET /health
+/// Composite DB + Redis status; 503 when unhealthy (a degraded pipeline still writes).
+pub async fn overall(State(app): State<AppRuntime>) -> (StatusCode, Json<AppHealth>) {
+    let health = app.app_health().await;
+    let code = if health.is_unhealthy() {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::OK
+    };
+    (code, Json(health))
+}
+
 pub async fn db(State(app): State<AppRuntime>) -> Json<HealthResp> {
     // "ok" means: DB gate enabled AND DB initialized AND health says we can admit.
     let ok = app.deps.is_db_enabled()
@@ -21,6 +46,28 @@ pub async fn db(State(app): State<AppRuntime>) -> Json<HealthResp> {
     Json(HealthResp { ok })
 }
 
+/// GET /health/db/shards
+/// Shard probes vs. `health.min_healthy_shards` and each shard's breaker state; 503 when not
+/// enough shards are healthy to write.
+pub async fn db_shards(State(app): State<AppRuntime>) -> (StatusCode, Json<DbShardsResp>) {
+    let report = match app.deps.db.as_ref() {
+        Some(db) if app.deps.is_db_enabled() => Some(db.handler.shard_health_report().await),
+        _ => None,
+    };
+    let code = if report.as_ref().is_some_and(|r| !r.is_write_ready()) {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::OK
+    };
+    (
+        code,
+        Json(DbShardsResp {
+            enabled: report.is_some(),
+            report,
+        }),
+    )
+}
+
 pub async fn redis(State(app): State<AppRuntime>) -> Json<HealthResp> {
     // "ok" means: redis gate enabled AND redis initialized AND manager can publish
     let ok = app.deps.is_redis_enabled()
@@ -6,7 +6,7 @@ use axum::{
 use crate::api::error::ApiError;
 use crate::api::types::{KnobsResp, PatchKnobsRequest};
 use crate::app::AppRuntime;
-use crate::app::stream_types::{ExchangeId, StreamId, StreamKind, StreamTransport};
+use crate::app::stream_types::{ExchangeId, StreamKind, StreamTransport};
 use crate::error::AppError;
 
 type KnobsPath = (String, String, StreamKind, StreamTransport);
@@ -29,7 +29,7 @@ pub async fn get(
     Path((exchange_str, symbol, kind, transport)): Path<KnobsPath>,
 ) -> Result<Json<KnobsResp>, ApiError> {
     let exchange = parse_exchange_id(&exchange_str)?;
-    let id = StreamId::new(exchange.as_str(), symbol.as_str(), kind, transport);
+    let id = app.stream_id(exchange, &symbol, kind, transport);
 
     let Some(knobs) = app.stream_knobs_snapshot(&id).await else {
         return Err(ApiError(AppError::StreamNotFound(
@@ -46,7 +46,7 @@ pub async fn patch(
     Json(req): Json<PatchKnobsRequest>,
 ) -> Result<Json<&'static str>, ApiError> {
     let exchange = parse_exchange_id(&exchange_str)?;
-    let id = StreamId::new(exchange.as_str(), symbol.as_str(), kind, transport);
+    let id = app.stream_id(exchange, &symbol, kind, transport);
 
     // Apply only the fields provided
     if let Some(disable) = req.disable_db_writes {
@@ -69,6 +69,12 @@ pub async fn patch(
     if let Some(v) = req.hard_cap_rows {
         app.set_stream_hard_cap_rows(&id, v).await?;
     }
+    if let Some(v) = req.emit_bbo {
+        app.set_stream_emit_bbo(&id, v).await?;
+    }
+    if let Some(v) = req.emit_microprice {
+        app.set_stream_emit_microprice(&id, v).await?;
+    }
 
     Ok(Json("ok"))
 }
@@ -2,7 +2,9 @@ pub mod health;
 pub mod instruments_axum;
 pub mod knobs;
 pub mod limiters;
+pub mod pause;
 pub mod streams;
+pub mod ws_subscriptions;
 
 pub use health::*;
 pub use instruments_axum::*;
@@ -0,0 +1,32 @@
+use axum::{Json, extract::State};
+
+use crate::api::types::IngestPauseResp;
+use crate::app::AppRuntime;
+
+fn status(app: &AppRuntime, changed: bool) -> Json<IngestPauseResp> {
+    let pause = &app.deps.ingest_pause;
+    Json(IngestPauseResp {
+        paused: pause.is_paused(),
+        changed,
+        mode: pause.mode(),
+        dropped_rows: pause.dropped_rows(),
+    })
+}
+
+/// GET /pause
+pub async fn get(State(app): State<AppRuntime>) -> Json<IngestPauseResp> {
+    status(&app, false)
+}
+
+/// POST /pause
+/// Stop all DB writes and Redis publishes; WS connections stay up.
+pub async fn pause(State(app): State<AppRuntime>) -> Json<IngestPauseResp> {
+    let changed = app.pause_ingest();
+    status(&app, changed)
+}
+
+/// POST /resume
+pub async fn resume(State(app): State<AppRuntime>) -> Json<IngestPauseResp> {
+    let changed = app.resume_ingest();
+    status(&app, changed)
+}
@@ -6,9 +6,9 @@ use axum::{
 use crate::api::error::ApiError;
 use crate::api::types::{
     AddStreamRequest, AvailableStreamDto, ListStreamsQuery, RemoveStreamRequest, StreamCountResp,
-    StreamRow, StreamSpecDto, StreamStatusResp,
+    StreamGcResp, StreamRow, StreamSpecDto, StreamStatusResp,
 };
-use crate::app::stream_types::{ExchangeId, StreamId, StreamKind, StreamTransport};
+use crate::app::stream_types::{ExchangeId, StreamKind, StreamTransport};
 use crate::app::{AppRuntime, StartStreamParams};
 use crate::error::AppError;
 
@@ -172,6 +172,15 @@ pub async fn count(State(app): State<AppRuntime>) -> Json<StreamCountResp> {
     Json(StreamCountResp { count })
 }
 
+/// POST /streams/gc
+/// Disable registry streams whose instrument has been delisted beyond the grace period.
+pub async fn gc(State(app): State<AppRuntime>) -> Result<Json<StreamGcResp>, ApiError> {
+    let disabled = app.gc_dead_streams().await?;
+    Ok(Json(StreamGcResp {
+        disabled: disabled.into_iter().map(|id| id.0).collect(),
+    }))
+}
+
 /// GET /streams/:exchange/:symbol/:kind/:transport
 pub async fn get_one(
     State(app): State<AppRuntime>,
@@ -181,7 +190,7 @@ pub async fn get_one(
     let kind = parse_kind(&kind_str)?;
     let transport = parse_transport(&transport_str)?;
 
-    let id = StreamId::new(exchange.as_str(), symbol.as_str(), kind, transport);
+    let id = app.stream_id(exchange, &symbol, kind, transport);
 
     let rows = app.list_streams().await;
     let found = rows.into_iter().find(|(sid, _, _)| *sid == id);
@@ -0,0 +1,29 @@
+use axum::{
+    Json,
+    extract::{Query, State},
+};
+use serde::Deserialize;
+
+use crate::api::types::WsSubscriptionsResp;
+use crate::app::AppRuntime;
+
+#[derive(Debug, Deserialize)]
+pub struct WsSubscriptionsQuery {
+    /// Optional exchange filter: "binance_linear" | "hyperliquid_perp"
+    pub exchange: Option<String>,
+}
+
+/// GET /ws/subscriptions
+/// Streams each live WS connection is subscribed to, and since when.
+pub async fn list(
+    State(app): State<AppRuntime>,
+    Query(q): Query<WsSubscriptionsQuery>,
+) -> Json<WsSubscriptionsResp> {
+    let subs = app.deps.ws_subscriptions.as_ref();
+    Json(WsSubscriptionsResp {
+        tracking: subs.is_some(),
+        connections: subs
+            .map(|s| s.snapshot(q.exchange.as_deref()))
+            .unwrap_or_default(),
+    })
+}
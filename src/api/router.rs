@@ -5,16 +5,21 @@ use axum::{
 
 use crate::app::AppRuntime;
 
-use super::handlers::{health, instruments_axum, knobs, limiters, streams};
+use super::handlers::{
+    health, instruments_axum, knobs, limiters, pause, streams, ws_subscriptions,
+};
 
 pub fn build_router(app: AppRuntime) -> Router {
     Router::new()
         // -----------------------
         // Health
         // -----------------------
+        .route("/health", get(health::overall))
         .route("/health/runtime", get(health::runtime))
         .route("/health/db", get(health::db))
+        .route("/health/db/shards", get(health::db_shards))
         .route("/health/redis", get(health::redis))
+        .route("/readyz", get(health::readyz))
         // -----------------------
         // Capabilities
         // -----------------------
@@ -28,6 +33,7 @@ pub fn build_router(app: AppRuntime) -> Router {
         .route("/streams", post(streams::add))
         .route("/streams", delete(streams::remove))
         .route("/streams/count", get(streams::count))
+        .route("/streams/gc", post(streams::gc))
         .route(
             "/streams/{exchange}/{symbol}/{kind}/{transport}",
             get(streams::get_one),
@@ -54,6 +60,16 @@ pub fn build_router(app: AppRuntime) -> Router {
         // Limiters (NEW)
         // -----------------------
         .route("/limiters", get(limiters::get_limiters))
+        // -----------------------
+        // Global ingest pause (kill switch)
+        // -----------------------
+        .route("/pause", get(pause::get))
+        .route("/pause", post(pause::pause))
+        .route("/resume", post(pause::resume))
+        // -----------------------
+        // Live WS subscriptions
+        // -----------------------
+        .route("/ws/subscriptions", get(ws_subscriptions::list))
         // ✅ ALWAYS last
         .with_state(app)
 }
@@ -1,6 +1,9 @@
+use crate::app::pause::PauseMode;
 use crate::app::state::StreamKnobs;
 use crate::app::stream_types::{ExchangeId, StreamKind, StreamTransport};
+use crate::db::health::ShardHealthReport;
 use crate::ingest::instruments::spec::InstrumentKind;
+use crate::ingest::ws::subscriptions::WsConnectionSubscriptions;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Deserialize)]
@@ -43,6 +46,13 @@ pub struct HealthResp {
     pub ok: bool,
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct DbShardsResp {
+    /// False when the DB is disabled or not initialized (`report` is then absent).
+    pub enabled: bool,
+    pub report: Option<ShardHealthReport>,
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct KnobsResp {
     pub knobs: StreamKnobs,
@@ -57,6 +67,8 @@ pub struct PatchKnobsRequest {
     pub flush_interval_ms: Option<u64>,
     pub chunk_rows: Option<usize>,
     pub hard_cap_rows: Option<usize>,
+    pub emit_bbo: Option<bool>,
+    pub emit_microprice: Option<bool>,
 }
 
 // --------------------
@@ -83,6 +95,29 @@ pub struct StreamCountResp {
     pub count: usize,
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct IngestPauseResp {
+    pub paused: bool,
+    /// False when the request found the switch already in that position.
+    pub changed: bool,
+    pub mode: PauseMode,
+    /// Rows discarded while paused since startup.
+    pub dropped_rows: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WsSubscriptionsResp {
+    /// False when `streams.ws_track_subscriptions` is off (`connections` is then empty).
+    pub tracking: bool,
+    pub connections: Vec<WsConnectionSubscriptions>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamGcResp {
+    /// Stream ids disabled in the registry by this GC pass.
+    pub disabled: Vec<String>,
+}
+
 // Path params: /streams/:exchange/:symbol/:kind/:transport
 #[derive(Debug, Clone, Deserialize)]
 pub struct StreamPath {
@@ -1,5 +1,8 @@
+use crate::app::metrics::SERIES_LABEL_NAMES;
+use crate::app::stream_types::ExchangeId;
 use crate::error::{AppError, AppResult};
 use serde::Deserialize;
+use std::collections::BTreeMap;
 use std::fs;
 
 #[derive(Debug, Deserialize)]
@@ -21,6 +24,18 @@ pub struct AppConfig {
 
     // NEW
     pub health: HealthConfig,
+
+    #[serde(default)]
+    pub stream_gc: StreamGcConfig,
+
+    #[serde(default)]
+    pub instruments: InstrumentsConfig,
+
+    #[serde(default)]
+    pub shutdown_report: ShutdownReportConfig,
+
+    #[serde(default)]
+    pub ingest_pause: IngestPauseConfig,
 }
 
 #[derive(Debug, Deserialize)]
@@ -40,12 +55,94 @@ pub struct ScalesConfig {
     pub qty: i64,
     pub open_interest: i64,
     pub funding: i64,
+    // Startup check: warn when an instrument declares more decimals than price/qty
+    // scales hold, beyond this many digits of slack (0 = any shortfall warns)
+    #[serde(default)]
+    pub max_decimal_mismatch: u32,
+    // Startup check that one price tick / qty step of every instrument scales to an exact
+    // i64 and back (off | warn | error); `error` refuses to start on a lossy instrument
+    #[serde(default = "default_round_trip_check")]
+    pub round_trip_check: CheckMode,
+    // Per-exchange overrides, keyed by exchange name (`[scales.exchanges.hyperliquid_perp]`).
+    // Each exchange writes to its own `ex_<name>` schema, so its rows carry its own scales.
+    #[serde(default)]
+    pub exchanges: BTreeMap<String, ScaleOverride>,
+}
+
+/// Scales one exchange uses instead of the global ones; unset fields fall back.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ScaleOverride {
+    pub price: Option<i64>,
+    pub qty: Option<i64>,
+    pub open_interest: Option<i64>,
+    pub funding: Option<i64>,
+}
+
+fn default_round_trip_check() -> CheckMode {
+    CheckMode::Off
+}
+
+/// What a startup consistency check (naming, scales round trip, ...) does with its findings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckMode {
+    Off,
+    /// Log findings and keep going.
+    #[default]
+    Warn,
+    /// Refuse to start on any finding.
+    Error,
+}
+
+impl AppConfig {
+    /// Labels every metrics registry puts on its series (`[metrics]` instance/const labels).
+    pub fn metric_const_labels(&self) -> BTreeMap<String, String> {
+        let mut labels = self.metrics.const_labels.clone();
+        if self.metrics.instance_labels {
+            labels.insert("instance_id".into(), self.id.clone());
+            labels.insert("env".into(), self.env.clone());
+        }
+        labels
+    }
+}
+
+/// Fixed-point scales in effect for one exchange.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExchangeScales {
+    pub price: i64,
+    pub qty: i64,
+    pub open_interest: i64,
+    pub funding: i64,
+}
+
+impl ScalesConfig {
+    /// Fractional digits a fixed-point scale represents exactly (1e8 -> 8).
+    pub fn decimals(scale: i64) -> u32 {
+        scale.checked_ilog10().unwrap_or(0)
+    }
+
+    /// Global scales with the overrides of `exchange` applied.
+    pub fn for_exchange(&self, exchange: &str) -> ExchangeScales {
+        let o = self.exchanges.get(exchange);
+        ExchangeScales {
+            price: o.and_then(|o| o.price).unwrap_or(self.price),
+            qty: o.and_then(|o| o.qty).unwrap_or(self.qty),
+            open_interest: o
+                .and_then(|o| o.open_interest)
+                .unwrap_or(self.open_interest),
+            funding: o.and_then(|o| o.funding).unwrap_or(self.funding),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ExchangeToggles {
     pub binance_linear: bool,
     pub hyperliquid_perp: bool,
+
+    /// Startup check that every exchange client is named after an `ExchangeId`.
+    #[serde(default)]
+    pub name_check: CheckMode,
 }
 
 #[derive(Debug, Deserialize)]
@@ -55,6 +152,66 @@ pub struct StreamsConfig {
     pub ws_reconnect_backoff_max_ms: u64,
     pub ws_reconnect_trip_after_failures: u32,
     pub ws_reconnect_cooldown_seconds: u64,
+    /// Reconnect fast path: connections up at least this long skip backoff/limiter (0 = off).
+    #[serde(default)]
+    pub ws_stable_connection_threshold_ms: u64,
+
+    // --- REST snapshot resyncs (shared by all depth streams) ---
+    /// Snapshot fetches running at once.
+    #[serde(default = "default_resync_max_concurrent")]
+    pub resync_max_concurrent: usize,
+    /// Minimum spacing between snapshot fetch starts.
+    #[serde(default = "default_resync_min_interval_ms")]
+    pub resync_min_interval_ms: u64,
+
+    /// Local order books held in memory (one per depth stream); the least recently updated is
+    /// evicted over the cap and re-seeded on its next update. 0 = unbounded.
+    #[serde(default = "default_max_books")]
+    pub max_books: usize,
+
+    /// Record live subscriptions per WS connection (`GET /ws/subscriptions`).
+    #[serde(default = "default_ws_track_subscriptions")]
+    pub ws_track_subscriptions: bool,
+
+    // --- failure dumps ---
+    /// Last raw frames kept per WS stream, logged when its handler errors or panics (0 = off).
+    #[serde(default)]
+    pub ws_frame_ring_size: usize,
+    /// Also write those dumps here (`<exchange>-<unix_ms>.frames`).
+    #[serde(default)]
+    pub ws_frame_ring_dump_dir: Option<String>,
+
+    // --- spec resolution ---
+    /// Streams whose subscribe/spec is still rendering after this long are logged (0 = never;
+    /// only batches of `RESOLVE_PARALLEL_MIN_ITEMS` streams or more are watched).
+    #[serde(default)]
+    pub resolve_budget_ms: u64,
+
+    // --- shutdown ---
+    /// Shutdown waits this long for stream tasks to end (their final batch flushes) before
+    /// the DB pools close.
+    #[serde(default = "default_stop_timeout_ms")]
+    pub stop_timeout_ms: u64,
+}
+
+fn default_ws_track_subscriptions() -> bool {
+    true
+}
+
+fn default_resync_max_concurrent() -> usize {
+    2
+}
+
+fn default_resync_min_interval_ms() -> u64 {
+    250
+}
+
+fn default_max_books() -> usize {
+    2000
+}
+
+fn default_stop_timeout_ms() -> u64 {
+    10_000
 }
 
 #[derive(Debug, Deserialize)]
@@ -66,11 +223,127 @@ pub struct LimitsConfig {
 #[derive(Debug, Deserialize)]
 pub struct LoggingConfig {
     pub level: String,
+    /// Repeated errors (ws read errors, failed publishes, ...) log once per window per
+    /// category, then with the count suppressed meanwhile. 0 = log every occurrence.
+    #[serde(default = "default_repeat_window_secs")]
+    pub repeat_window_secs: u64,
+    /// Payload fields never logged or quoted in errors (authenticated streams): field names
+    /// (any depth) or JSON pointers (`/data/0/user`). See `telemetry::redact`.
+    #[serde(default)]
+    pub redact_fields: Vec<String>,
+    /// Replacement for redacted values.
+    #[serde(default = "default_redact_placeholder")]
+    pub redact_placeholder: String,
+}
+
+fn default_repeat_window_secs() -> u64 {
+    crate::telemetry::throttle::DEFAULT_REPEAT_WINDOW_SECS
+}
+
+fn default_redact_placeholder() -> String {
+    crate::telemetry::redact::DEFAULT_REDACT_PLACEHOLDER.to_string()
 }
 
 #[derive(Debug, Deserialize)]
 pub struct MetricsConfig {
     pub enabled: bool,
+    /// Cap on distinct `stream` label values of per-stream gauges (0 = limits.max_active_streams).
+    #[serde(default)]
+    pub max_stream_labels: usize,
+    /// Keep each `ingest_lag_seconds` observation with probability 1/N (1 = all).
+    #[serde(default = "default_lag_sample_every")]
+    pub lag_sample_every: u64,
+    /// Label `ws_ping_rtt_seconds` by exchange; false aggregates all venues into one series.
+    #[serde(default = "default_true")]
+    pub ws_ping_rtt_by_exchange: bool,
+    /// Put `instance_id` (= `id`) and `env` on every series of every registry.
+    #[serde(default)]
+    pub instance_labels: bool,
+    /// More labels for every series (e.g. `deploy_version`), set once at startup.
+    #[serde(default)]
+    pub const_labels: BTreeMap<String, String>,
+}
+
+fn default_lag_sample_every() -> u64 {
+    1
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Dead-stream GC: disables registry streams whose instrument was delisted.
+/// `enabled` only controls the periodic run; the manual trigger always works.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct StreamGcConfig {
+    pub enabled: bool,
+    pub interval_sec: u64,
+    /// How long an instrument must stay delisted before its streams are disabled.
+    pub grace_sec: u64,
+}
+
+impl Default for StreamGcConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_sec: 3600,
+            grace_sec: 86400,
+        }
+    }
+}
+
+/// Instrument registry freshness and startup load.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct InstrumentsConfig {
+    /// Streams are refused when the registry is older than this and a refresh fails (0 = off).
+    pub max_registry_age_sec: u64,
+    /// Retries of the startup instrument load after a transient error (network, 5xx, 429).
+    /// Permanent errors (bad config, unparsable response) fail at once. 0 = no retry.
+    pub load_retry_attempts: u32,
+    /// First retry delay; doubles per attempt up to `load_retry_max_backoff_ms`.
+    pub load_retry_initial_backoff_ms: u64,
+    pub load_retry_max_backoff_ms: u64,
+}
+
+impl Default for InstrumentsConfig {
+    fn default() -> Self {
+        Self {
+            max_registry_age_sec: 0,
+            load_retry_attempts: 3,
+            load_retry_initial_backoff_ms: 500,
+            load_retry_max_backoff_ms: 8000,
+        }
+    }
+}
+
+/// Structured shutdown report (see `ShutdownReport`).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ShutdownReportConfig {
+    pub enabled: bool,
+    /// Also write the report as JSON to this file ("" = log only).
+    pub path: String,
+}
+
+impl Default for ShutdownReportConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            path: String::new(),
+        }
+    }
+}
+
+/// Global ingest pause (see `app::pause`). The process always starts unpaused.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct IngestPauseConfig {
+    /// Rows arriving while paused: `drop` or `buffer` (kept in their batch up to its hard cap).
+    pub mode: crate::app::pause::PauseMode,
+    /// SIGUSR1 pauses, SIGUSR2 resumes (unix only).
+    pub signals: bool,
 }
 
 // ==================================================
@@ -81,6 +354,44 @@ pub struct MetricsConfig {
 pub struct HealthConfig {
     pub enabled: bool,
     pub runtime: RuntimeHealthConfig,
+    #[serde(default)]
+    pub stall: StallConfig,
+    /// Count Redis being unavailable as unhealthy instead of degraded (`AppHealth`).
+    #[serde(default)]
+    pub redis_required: bool,
+}
+
+/// Stream stall watchdog (`ingest_stream_stalled{stream}`).
+/// Time while the instrument's market is closed never counts towards a stall.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct StallConfig {
+    pub enabled: bool,
+    pub interval_sec: u64,
+    /// No event for this long during an open session = stalled.
+    pub stall_after_sec: u64,
+    /// `stall_after_sec` per stream kind (`Liquidations`, `Funding`, ...); 0 = never stalled
+    /// (event-driven kinds may be quiet for hours). Other kinds use `stall_after_sec`.
+    pub stall_after_sec_by_kind: BTreeMap<String, u64>,
+    /// Weekly UTC sessions of instruments that do not trade 24/7 (`"Sun 23:00-Fri 22:00"`),
+    /// keyed by exchange (`binance_linear`) or `exchange:SYMBOL`; the symbol entry wins.
+    /// Instruments without one use their registry schedule (crypto: always open).
+    pub sessions: BTreeMap<String, Vec<String>>,
+}
+
+impl Default for StallConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_sec: 10,
+            stall_after_sec: 120,
+            stall_after_sec_by_kind: BTreeMap::from([
+                ("Liquidations".to_string(), 0),
+                ("Funding".to_string(), 3000),
+            ]),
+            sessions: BTreeMap::new(),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -161,12 +472,31 @@ fn validate_config(cfg: &AppConfig) -> AppResult<()> {
     // --------------------------------------------------
     let scales = &cfg.scales;
 
-    for (name, value) in [
-        ("price", scales.price),
-        ("qty", scales.qty),
-        ("open_interest", scales.open_interest),
-        ("funding", scales.funding),
-    ] {
+    let mut checked = vec![
+        ("price".to_string(), scales.price),
+        ("qty".to_string(), scales.qty),
+        ("open_interest".to_string(), scales.open_interest),
+        ("funding".to_string(), scales.funding),
+    ];
+    for (exchange, o) in &scales.exchanges {
+        if ExchangeId::from_name(exchange).is_none() {
+            return Err(AppError::InvalidConfig(format!(
+                "scales.exchanges: unknown exchange '{exchange}'"
+            )));
+        }
+        for (field, value) in [
+            ("price", o.price),
+            ("qty", o.qty),
+            ("open_interest", o.open_interest),
+            ("funding", o.funding),
+        ] {
+            if let Some(v) = value {
+                checked.push((format!("exchanges.{exchange}.{field}"), v));
+            }
+        }
+    }
+
+    for (name, value) in checked {
         if value <= 0 {
             return Err(AppError::InvalidConfig(format!(
                 "scale '{name}' must be > 0"
@@ -238,6 +568,30 @@ fn validate_config(cfg: &AppConfig) -> AppResult<()> {
     // --------------------------------------------------
     validate_health_config(&cfg.health)?;
 
+    for name in cfg.metrics.const_labels.keys() {
+        if !is_metric_label_name(name) {
+            return Err(AppError::InvalidConfig(format!(
+                "metrics.const_labels: invalid label name {name:?}"
+            )));
+        }
+        if cfg.metrics.instance_labels && matches!(name.as_str(), "instance_id" | "env") {
+            return Err(AppError::InvalidConfig(format!(
+                "metrics.const_labels: {name:?} is set by metrics.instance_labels"
+            )));
+        }
+        if SERIES_LABEL_NAMES.contains(&name.as_str()) {
+            return Err(AppError::InvalidConfig(format!(
+                "metrics.const_labels: {name:?} is a label of the metrics themselves"
+            )));
+        }
+    }
+
+    if cfg.stream_gc.enabled && cfg.stream_gc.interval_sec == 0 {
+        return Err(AppError::InvalidConfig(
+            "stream_gc.interval_sec must be > 0 when stream_gc.enabled is true".into(),
+        ));
+    }
+
     Ok(())
 }
 
@@ -348,9 +702,22 @@ fn validate_health_config(health: &HealthConfig) -> AppResult<()> {
         }
     }
 
+    // Stall watchdog: kinds and sessions parse
+    crate::app::health::stall::StallWatchdog::from_config(&health.stall)?;
+
     Ok(())
 }
 
+/// Prometheus label name (`[a-zA-Z_][a-zA-Z0-9_]*`, `__` prefix reserved).
+fn is_metric_label_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !name.starts_with("__")
+}
+
 fn is_power_of_ten(mut v: i64) -> bool {
     if v <= 0 {
         return false;
@@ -508,4 +875,46 @@ mod tests {
             cfg.health.runtime.poll_interval_ms, cfg.health.runtime.hold_down_ms
         );
     }
+
+    #[test]
+    fn exchange_scale_overrides_are_validated() {
+        let mut cfg = load_app_config(false, 0).expect("failed to load app config");
+        cfg.scales.exchanges.insert(
+            "hyperliquid_perp".into(),
+            ScaleOverride {
+                qty: Some(10_000_000_000),
+                ..Default::default()
+            },
+        );
+        assert!(validate_config(&cfg).is_ok());
+
+        cfg.scales
+            .exchanges
+            .get_mut("hyperliquid_perp")
+            .unwrap()
+            .qty = Some(250);
+        assert!(validate_config(&cfg).is_err());
+
+        cfg.scales.exchanges.clear();
+        cfg.scales
+            .exchanges
+            .insert("kraken".into(), ScaleOverride::default());
+        assert!(validate_config(&cfg).is_err());
+    }
+
+    #[test]
+    fn const_labels_must_not_shadow_metric_labels() {
+        let mut cfg = load_app_config(false, 0).expect("failed to load app config");
+        cfg.metrics
+            .const_labels
+            .insert("deploy_version".into(), "2024.06.1".into());
+        assert!(validate_config(&cfg).is_ok());
+
+        for name in ["stream", "exchange", "le"] {
+            cfg.metrics.const_labels.insert(name.into(), "x".into());
+            let err = validate_config(&cfg).unwrap_err().to_string();
+            assert!(err.contains(name), "{err}");
+            cfg.metrics.const_labels.remove(name);
+        }
+    }
 }
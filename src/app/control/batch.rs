@@ -1,11 +1,19 @@
 // crate::db::writer::batch_helpers.rs (or crate::db::writer::mod.rs)
+use crate::app::dependencies::AppDeps;
+use crate::app::ports::AnyDbBatch;
 use crate::app::stream_types::{ExchangeId, StreamKind, StreamTransport};
 use crate::db::WriterConfig;
 use crate::db::{Batch, BatchKey};
 use crate::error::AppResult;
+use crate::ingest::datamap::change_only::{ChangeOnlyFilter, ChangeOnlyRow};
+use crate::ingest::metrics::IngestMetrics;
+use crate::redis::fields::PersistedRedisPublish;
+use std::future::Future;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::{Mutex, watch};
 use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
 use tokio_util::sync::CancellationToken;
 
 use crate::app::state::StreamKnobs;
@@ -20,8 +28,11 @@ pub fn make_batch_key(
     let stream = kind.endpoint_key(exchange, transport)?; // <-- your mapping
     Ok(BatchKey {
         exchange: exchange.as_str().to_string(),
+        transport,
+        kind,
         stream: stream.to_string(),
         symbol: symbol.as_ref().to_string(),
+        derived: None,
     })
 }
 
@@ -37,6 +48,37 @@ pub fn make_empty_batch<T>(
     Ok(Batch::new(key, vec![], &writer_cfg))
 }
 
+/// Empty batch for a series derived from a stream (`derived`: "bbo", "microprice", ...):
+/// its own key, routed like the source stream.
+#[inline]
+pub fn make_empty_derived_batch<T>(
+    exchange: ExchangeId,
+    transport: StreamTransport,
+    kind: StreamKind,
+    derived: &'static str,
+    symbol: impl AsRef<str>,
+    writer_cfg: WriterConfig,
+) -> AppResult<Batch<T>> {
+    let mut key = make_batch_key(exchange, transport, kind, symbol)?;
+    key.derived = Some(derived);
+    Ok(Batch::new(key, vec![], &writer_cfg))
+}
+
+/// Store-on-change: keep the rows `filter` admits, count the dropped ones in ingest metrics.
+pub fn retain_changed<R: ChangeOnlyRow>(
+    filter: &std::sync::Mutex<ChangeOnlyFilter>,
+    rows: Vec<R>,
+    metrics: Option<&IngestMetrics>,
+) -> Vec<R> {
+    let mut filter = filter.lock().unwrap_or_else(|e| e.into_inner());
+    let before = filter.suppressed_total();
+    let kept = filter.retain(rows);
+    if let Some(m) = metrics {
+        m.add_unchanged_suppressed(filter.suppressed_total() - before);
+    }
+    kept
+}
+
 /// Spawns a task that listens for StreamKnobs changes and:
 /// - updates the batch settings (flush_rows/interval/hard_cap)
 /// - triggers an immediate DB flush if the batch becomes flushable
@@ -74,3 +116,202 @@ where
         }
     })
 }
+
+/// Spawns a task that runs `tick` every `period` until `cancel` (timer-driven flushes of a
+/// stream's derived rows; the final flush on stop is up to the stream task). A zero `period`
+/// never ticks.
+pub fn spawn_periodic_task<F, Fut>(
+    period: Duration,
+    cancel: CancellationToken,
+    mut tick: F,
+) -> JoinHandle<()>
+where
+    F: FnMut() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    tokio::spawn(async move {
+        if period.is_zero() {
+            return;
+        }
+        let mut every = tokio::time::interval(period);
+        every.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        every.tick().await; // the first tick completes immediately
+        loop {
+            tokio::select! {
+                _ = cancel.cancelled() => break,
+                _ = every.tick() => tick().await,
+            }
+        }
+    })
+}
+
+/// Write whatever a stream's `batch` still holds once the stream has stopped (rows below the
+/// size/interval threshold would be lost otherwise), publishing its committed rows. Dropped
+/// with the stream's DB writes off, like the rows of the hot path.
+pub async fn flush_stream_batch<T>(
+    deps: &AppDeps,
+    exchange: ExchangeId,
+    batch: &Mutex<Batch<T>>,
+    knobs: StreamKnobs,
+) where
+    T: PersistedRedisPublish + Send + Sync,
+    for<'a> AnyDbBatch<'a>: From<&'a mut Batch<T>>,
+{
+    if knobs.disable_db_writes {
+        return;
+    }
+    if let Err(e) = deps
+        .db_write_and_publish(
+            exchange.as_str(),
+            batch,
+            Vec::new(),
+            true,
+            !knobs.disable_redis_publishes,
+        )
+        .await
+    {
+        tracing::warn!(error = ?e, "batch flush on stop failed");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{CommitLedger, PendingBatchBudget};
+
+    #[test]
+    fn kinds_of_one_symbol_keep_separate_batches() -> AppResult<()> {
+        use ExchangeId::BinanceLinear;
+        use StreamTransport::{HttpPoll, Ws};
+
+        let trades = make_batch_key(BinanceLinear, Ws, StreamKind::Trades, "BTCUSDT")?;
+        let depth = make_batch_key(BinanceLinear, Ws, StreamKind::L2Book, "BTCUSDT")?;
+        let funding = make_batch_key(BinanceLinear, HttpPoll, StreamKind::Funding, "BTCUSDT")?;
+
+        // Full key: exchange + transport + kind + symbol
+        assert_eq!((trades.transport, trades.kind), (Ws, StreamKind::Trades));
+        assert_eq!(
+            (funding.transport, funding.kind),
+            (HttpPoll, StreamKind::Funding)
+        );
+        assert_ne!(trades, depth);
+        assert_ne!(trades, funding);
+        assert_ne!(depth, funding);
+
+        let cfg = WriterConfig {
+            batch_size: 3,
+            hard_batch_size: 100,
+            flush_interval_ms: 60_000,
+            ..WriterConfig::default()
+        };
+        let budget = Arc::new(PendingBatchBudget::new(0, None));
+        let mut trades_batch = make_empty_batch::<u64>(
+            BinanceLinear,
+            Ws,
+            StreamKind::Trades,
+            "BTCUSDT",
+            cfg.clone(),
+        )?;
+        let mut funding_batch =
+            make_empty_batch::<u64>(BinanceLinear, HttpPoll, StreamKind::Funding, "BTCUSDT", cfg)?;
+        trades_batch.attach_budget(Arc::clone(&budget));
+        funding_batch.attach_budget(Arc::clone(&budget));
+
+        // Trades reach their flush threshold; funding stays on its own schedule
+        trades_batch.extend(vec![1, 2, 3]);
+        funding_batch.extend(vec![1]);
+        assert!(trades_batch.should_flush());
+        assert!(!funding_batch.should_flush());
+        assert_eq!(budget.tracked_batches(), 2);
+
+        trades_batch.take_rows();
+        assert!(trades_batch.is_empty());
+        assert_eq!(funding_batch.len(), 1);
+        assert_eq!(budget.total_bytes(), funding_batch.approx_bytes());
+
+        Ok(())
+    }
+
+    #[test]
+    fn derived_series_get_their_own_key_but_route_like_the_source() -> AppResult<()> {
+        use ExchangeId::BinanceLinear;
+        use StreamTransport::Ws;
+
+        let cfg = WriterConfig::default();
+        let budget = Arc::new(PendingBatchBudget::new(0, None));
+        let mut depth = make_empty_batch::<u64>(
+            BinanceLinear,
+            Ws,
+            StreamKind::L2Book,
+            "BTCUSDT",
+            cfg.clone(),
+        )?;
+        let mut bbo = make_empty_derived_batch::<u64>(
+            BinanceLinear,
+            Ws,
+            StreamKind::L2Book,
+            "bbo",
+            "BTCUSDT",
+            cfg.clone(),
+        )?;
+        let microprice = make_empty_derived_batch::<u64>(
+            BinanceLinear,
+            Ws,
+            StreamKind::L2Book,
+            "microprice",
+            "BTCUSDT",
+            cfg,
+        )?;
+        depth.attach_budget(Arc::clone(&budget));
+        bbo.attach_budget(Arc::clone(&budget));
+        depth.extend(vec![1, 2]);
+        bbo.extend(vec![1]);
+
+        // Separate budget entries and watermarks...
+        assert_ne!(depth.key, bbo.key);
+        assert_ne!(bbo.key, microprice.key);
+        assert_eq!(budget.tracked_batches(), 2);
+        assert_eq!(
+            budget.total_bytes(),
+            depth.approx_bytes() + bbo.approx_bytes()
+        );
+        assert_ne!(
+            CommitLedger::stream_key(&depth.key),
+            CommitLedger::stream_key(&bbo.key)
+        );
+
+        // ...same route as the source
+        assert_eq!(
+            (&bbo.key.exchange, &bbo.key.stream, &bbo.key.symbol),
+            (&depth.key.exchange, &depth.key.stream, &depth.key.symbol)
+        );
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn periodic_task_ticks_until_cancelled() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let cancel = CancellationToken::new();
+        let task = {
+            let ticks = Arc::clone(&ticks);
+            spawn_periodic_task(Duration::from_millis(100), cancel.clone(), move || {
+                ticks.fetch_add(1, Ordering::SeqCst);
+                async {}
+            })
+        };
+
+        tokio::time::sleep(Duration::from_millis(350)).await;
+        assert_eq!(ticks.load(Ordering::SeqCst), 3);
+
+        cancel.cancel();
+        task.await.unwrap();
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert_eq!(ticks.load(Ordering::SeqCst), 3);
+
+        // Zero period: off
+        let idle = spawn_periodic_task(Duration::ZERO, CancellationToken::new(), || async {});
+        idle.await.unwrap();
+    }
+}
@@ -1,7 +1,13 @@
-use crate::app::stream_types::{ExchangeId, StreamKind, StreamTransport};
+use crate::app::control::stream::{RenderedSpecs, StartStreamParams};
+use crate::app::stream_types::{ExchangeId, StreamId, StreamKind, StreamTransport};
 use crate::error::{AppError, AppResult};
 use crate::ingest::config::{ApiEndpoint, ExchangeConfigs, WsStream};
+use crate::ingest::spec::resolve::{
+    log_resolve_progress, resolve_http_request, resolve_parallel, resolve_ws_control_batches,
+    seed_ws_stream_ctx,
+};
 use crate::ingest::spec::{Ctx, ParamPlacement};
+use std::time::Duration;
 use std::{fmt, str::FromStr};
 
 impl StreamKind {
@@ -127,15 +133,6 @@ impl StreamKind {
     }
 }
 
-impl ExchangeId {
-    pub fn as_str(&self) -> &'static str {
-        match self {
-            ExchangeId::BinanceLinear => "binance_linear",
-            ExchangeId::HyperliquidPerp => "hyperliquid_perp",
-        }
-    }
-}
-
 impl fmt::Display for ExchangeId {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.write_str(self.as_str())
@@ -146,13 +143,8 @@ impl FromStr for ExchangeId {
     type Err = AppError;
 
     fn from_str(s: &str) -> AppResult<Self> {
-        match s {
-            "binance_linear" => Ok(Self::BinanceLinear),
-            "hyperliquid_perp" => Ok(Self::HyperliquidPerp),
-            _ => Err(AppError::InvalidArgument(format!(
-                "invalid ExchangeId: {s}"
-            ))),
-        }
+        Self::from_name(s)
+            .ok_or_else(|| AppError::InvalidArgument(format!("invalid ExchangeId: {s}")))
     }
 }
 
@@ -205,8 +197,6 @@ impl ParamPlacement {
 }
 
 pub fn ctx_with_symbol(exchange: ExchangeId, transport: StreamTransport, symbol: &str) -> Ctx {
-    let mut ctx = Ctx::new();
-
     let normalized_symbol = match (exchange, transport) {
         // WS Binance → lowercase
         (ExchangeId::BinanceLinear, StreamTransport::Ws) => symbol.to_lowercase(),
@@ -215,12 +205,33 @@ pub fn ctx_with_symbol(exchange: ExchangeId, transport: StreamTransport, symbol:
         _ => symbol.to_uppercase(),
     };
 
+    symbol_ctx(exchange, normalized_symbol)
+}
+
+/// Same as `ctx_with_symbol`, but casing follows the exchange config
+/// (`symbol_case` / `ws_symbol_case`). Falls back to `ctx_with_symbol` if the
+/// exchange is not loaded.
+pub fn ctx_with_symbol_cased(
+    configs: &ExchangeConfigs,
+    exchange: ExchangeId,
+    transport: StreamTransport,
+    symbol: &str,
+) -> Ctx {
+    match configs.get(exchange) {
+        Some(cfg) => symbol_ctx(exchange, cfg.template_symbol(transport, symbol)),
+        None => ctx_with_symbol(exchange, transport, symbol),
+    }
+}
+
+fn symbol_ctx(exchange: ExchangeId, symbol: String) -> Ctx {
+    let mut ctx = Ctx::new();
+
     match exchange {
         ExchangeId::BinanceLinear => {
-            ctx.insert("symbol".into(), normalized_symbol);
+            ctx.insert("symbol".into(), symbol);
         }
         ExchangeId::HyperliquidPerp => {
-            ctx.insert("coin".into(), normalized_symbol);
+            ctx.insert("coin".into(), symbol);
         }
     }
 
@@ -321,6 +332,50 @@ pub fn resolve_ws_stream_str(
     resolve_ws_stream(configs, exchange_id, kind)
 }
 
+/// Render the specs of every stream in `streams` (the startup restore) up front, in parallel
+/// (`resolve_parallel`: progress logs, a report of what is still rendering after `budget`):
+/// the subscribe batches of WS streams and the REST request of polled streams and of depth
+/// snapshots, rendered from the ctx their start builds. Blocking: run it off the async
+/// workers. The first error in order wins.
+pub fn render_stream_specs(
+    configs: &ExchangeConfigs,
+    streams: &[StartStreamParams],
+    budget: Option<Duration>,
+) -> AppResult<Vec<RenderedSpecs>> {
+    resolve_parallel(
+        streams,
+        |p| format!("{}:{}:{}", p.exchange.as_str(), p.symbol, p.kind),
+        |p| {
+            let cfg = configs.get(p.exchange).ok_or_else(|| {
+                AppError::InvalidConfig(format!("missing {} config", p.exchange.as_str()))
+            })?;
+            let ctx = ctx_with_symbol_cased(configs, p.exchange, p.transport, &p.symbol);
+            let mut rendered = RenderedSpecs::default();
+            if p.transport == StreamTransport::HttpPoll || p.kind == StreamKind::L2Book {
+                let ep = resolve_api_endpoint(configs, p.exchange, p.kind)?;
+                let placement = ParamPlacement::for_exchange(p.exchange);
+                rendered.http = Some(resolve_http_request(&ep, &ctx, placement)?);
+            }
+            if p.transport == StreamTransport::Ws {
+                let stream = resolve_ws_stream(configs, p.exchange, p.kind)?;
+                let mut ctx = ctx;
+                // As the stream task builds it: Binance requests carry the stream id
+                if p.exchange == ExchangeId::BinanceLinear {
+                    let id = StreamId::new(p.exchange.as_str(), &p.symbol, p.kind, p.transport);
+                    ctx.insert("stream_id".into(), binance_ws_request_id(&id.to_string()));
+                }
+                seed_ws_stream_ctx(&stream, &mut ctx)?;
+                ctx.entry("stream_id".to_string())
+                    .or_insert_with(|| "1".to_string());
+                rendered.ws = Some(resolve_ws_control_batches(cfg, &[ctx])?);
+            }
+            Ok(rendered)
+        },
+        budget,
+        &log_resolve_progress,
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -603,4 +658,109 @@ mod tests {
 
         println!("\n================= DONE =================");
     }
+
+    #[test]
+    fn ctx_with_symbol_cased_follows_exchange_policy() {
+        use crate::ingest::config::SymbolCase;
+
+        let app_cfgs = load_app_config(false, 0).unwrap();
+        let mut cfgs = ExchangeConfigs::new(&app_cfgs, false, 0).unwrap();
+        if cfgs.binance_linear.is_none() {
+            cfgs.binance_linear = Some(
+                crate::ingest::config::load_exchange_config("binance_linear", false, 0).unwrap(),
+            );
+        }
+        let binance = cfgs.get_mut(ExchangeId::BinanceLinear).unwrap();
+
+        // upper + ws override lower (shipped config)
+        binance.symbol_case = SymbolCase::Upper;
+        binance.ws_symbol_case = Some(SymbolCase::Lower);
+        let ctx = ctx_with_symbol_cased(
+            &cfgs,
+            ExchangeId::BinanceLinear,
+            StreamTransport::Ws,
+            "BtcUsdt",
+        );
+        assert_eq!(ctx.get("symbol").map(|s| s.as_str()), Some("btcusdt"));
+        let ctx = ctx_with_symbol_cased(
+            &cfgs,
+            ExchangeId::BinanceLinear,
+            StreamTransport::HttpPoll,
+            "BtcUsdt",
+        );
+        assert_eq!(ctx.get("symbol").map(|s| s.as_str()), Some("BTCUSDT"));
+
+        // lower everywhere
+        let binance = cfgs.get_mut(ExchangeId::BinanceLinear).unwrap();
+        binance.symbol_case = SymbolCase::Lower;
+        binance.ws_symbol_case = None;
+        for tr in [StreamTransport::Ws, StreamTransport::HttpPoll] {
+            let ctx = ctx_with_symbol_cased(&cfgs, ExchangeId::BinanceLinear, tr, "BtcUsdt");
+            assert_eq!(ctx.get("symbol").map(|s| s.as_str()), Some("btcusdt"));
+        }
+
+        // preserve everywhere
+        let binance = cfgs.get_mut(ExchangeId::BinanceLinear).unwrap();
+        binance.symbol_case = SymbolCase::Preserve;
+        for tr in [StreamTransport::Ws, StreamTransport::HttpPoll] {
+            let ctx = ctx_with_symbol_cased(&cfgs, ExchangeId::BinanceLinear, tr, "BtcUsdt");
+            assert_eq!(ctx.get("symbol").map(|s| s.as_str()), Some("BtcUsdt"));
+        }
+
+        // exchange not loaded => legacy ctx_with_symbol behavior
+        cfgs.hyperliquid_perp = None;
+        let ctx = ctx_with_symbol_cased(
+            &cfgs,
+            ExchangeId::HyperliquidPerp,
+            StreamTransport::Ws,
+            "kPepe",
+        );
+        assert_eq!(ctx.get("coin").map(|s| s.as_str()), Some("KPEPE"));
+    }
+
+    #[test]
+    fn restore_renders_ws_and_http_specs_as_the_start_would() {
+        let app_cfgs = load_app_config(false, 0).unwrap();
+        let mut cfgs = ExchangeConfigs::new(&app_cfgs, false, 0).unwrap();
+        if cfgs.binance_linear.is_none() {
+            cfgs.binance_linear = Some(
+                crate::ingest::config::load_exchange_config("binance_linear", false, 0).unwrap(),
+            );
+        }
+        let params = |transport, kind| StartStreamParams {
+            exchange: ExchangeId::BinanceLinear,
+            transport,
+            kind,
+            symbol: "BTCUSDT".into(),
+        };
+        let streams = [
+            params(StreamTransport::Ws, StreamKind::Trades),
+            params(StreamTransport::Ws, StreamKind::L2Book),
+            params(StreamTransport::HttpPoll, StreamKind::OpenInterest),
+        ];
+        let rendered = render_stream_specs(&cfgs, &streams, None).unwrap();
+
+        // WS: the subscribe the stream task would send (its request id is the stream id)
+        let ws = rendered[0].ws.as_ref().unwrap();
+        let id = StreamId::new(
+            "binance_linear",
+            "BTCUSDT",
+            StreamKind::Trades,
+            StreamTransport::Ws,
+        );
+        assert_eq!(ws.len(), 1);
+        assert_eq!(
+            ws[0].subscribe[0]["id"],
+            binance_ws_request_id(&id.to_string())
+        );
+        assert!(rendered[0].http.is_none());
+
+        // Depth: the snapshot request too; polled streams only their request
+        assert!(rendered[1].ws.is_some() && rendered[1].http.is_some());
+        assert!(rendered[2].ws.is_none() && rendered[2].http.is_some());
+
+        // A stream that does not render fails the whole restore
+        let bad = [params(StreamTransport::HttpPoll, StreamKind::Trades)];
+        assert!(render_stream_specs(&cfgs, &bad, None).is_err());
+    }
 }
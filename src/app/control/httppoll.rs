@@ -1,4 +1,4 @@
-use crate::app::control::batch::make_empty_batch;
+use crate::app::control::batch::{flush_stream_batch, make_empty_batch, retain_changed};
 use crate::app::runtime::AppRuntime;
 use crate::app::state::StreamHandle;
 use crate::app::state::StreamKnobs;
@@ -7,9 +7,10 @@ use crate::app::stream_types::{StreamId, StreamSpec, StreamStatus};
 use crate::db::WriterConfig;
 use crate::db::rows::{DepthDeltaDBRow, FundingDBRow, OpenInterestDBRow};
 use crate::error::{AppError, AppResult};
+use crate::ingest::datamap::change_only::ChangeOnlyFilter;
 use crate::ingest::datamap::ctx::MapCtx;
 use crate::ingest::datamap::event::MapEnvelope;
-use crate::ingest::datamap::event::MarketEvent;
+use crate::ingest::datamap::event::{DepthDeltaRow, MarketEvent};
 use crate::ingest::datamap::sources::binance_linear::types::{
     BinanceLinearDepthSnapshot, BinanceLinearFundingRateSnapshot, BinanceLinearOpenInterestSnapshot,
 };
@@ -17,6 +18,7 @@ use crate::ingest::datamap::sources::hyperliquid_perp::types::HyperliquidPerpDep
 use crate::ingest::spec::types::HttpRequestSpec;
 use crate::ingest::traits::MapToEvents;
 use crate::redis::fields::as_publish_fields;
+use chrono::{DateTime, Utc};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::task::JoinHandle;
@@ -67,13 +69,18 @@ pub async fn http_poll_binancelinear_oi(
         Some(db) => db.cfg.writer.clone(),
         None => WriterConfig::default(),
     };
-    let db_batch = make_empty_batch::<OpenInterestDBRow>(
+    let change_only = Arc::new(std::sync::Mutex::new(
+        ChangeOnlyFilter::open_interest(&writer_cfg)
+            .with_metrics(runtime.deps.ingest_metrics.as_deref()),
+    ));
+    let mut db_batch = make_empty_batch::<OpenInterestDBRow>(
         exchange,
         transport,
         kind,
         symbol_for_task.clone(),
         writer_cfg,
     )?;
+    db_batch.track_persisted = runtime.deps.publish_persisted_only();
 
     let knobs = StreamKnobs::from_deps(deps.clone());
     // Runtime knobs for redis / db
@@ -108,33 +115,50 @@ pub async fn http_poll_binancelinear_oi(
         let mut backoff = Duration::from_millis(250);
         let max_backoff = Duration::from_secs(10);
 
+        let deps_for_stop = deps.clone();
+        let batch_for_stop = Arc::clone(&batch);
+        let knobs_rx_for_stop = knobs_rx.clone();
+
         // Define on_item; it captures &mut batch for each call
-        let mut on_item = move |item: BinanceLinearOpenInterestSnapshot| {
+        // Liveness gauge label (one per stream)
+        let stream_label: Arc<str> = Arc::from(stream_id_for_task.to_string());
+        let mut on_item = move |item: BinanceLinearOpenInterestSnapshot,
+                                venue_time: Option<DateTime<Utc>>| {
+            let stream_label = Arc::clone(&stream_label);
             let cancel_for_item = cancel_for_test.clone();
             {
                 let knobs: StreamKnobs = *knobs_rx.borrow_and_update();
                 let batch = Arc::clone(&batch);
+                let change_only = Arc::clone(&change_only);
                 let map_ctx = Arc::clone(&map_ctx_for_task);
                 let map_envelope = Arc::clone(&map_envelope_for_task);
                 let deps = deps.clone();
 
                 async move {
                     // let res = BinanceLinearOpenInterestSnapshot::from_json_str(item.as_str())?;
-                    let events = item.map_to_events(&map_ctx, Some((*map_envelope).clone()))?;
+                    let mut events = item.map_to_events(&map_ctx, Some((*map_envelope).clone()))?;
+                    if let Some(t) = venue_time {
+                        events.iter_mut().for_each(|e| e.set_time(t));
+                    }
 
-                    // 1) Convert to DB rows (no lock yet)
-                    let oi_db_rows: Vec<OpenInterestDBRow> = events
+                    // 1) Convert to DB rows (no lock yet); store-on-change drops unchanged values
+                    let oi_rows = events
                         .iter()
                         .filter_map(|e| match e {
-                            MarketEvent::OpenInterest(oi) => {
-                                Some(OpenInterestDBRow::from(oi.clone()))
-                            }
+                            MarketEvent::OpenInterest(oi) => Some(oi.clone()),
                             _ => None,
                         })
                         .collect();
-
-                    // 2) Publish to redis (no lock). If you want "latest only", publish only last().
-                    if !knobs.disable_redis_publishes {
+                    let oi_db_rows: Vec<OpenInterestDBRow> =
+                        retain_changed(&change_only, oi_rows, deps.ingest_metrics.as_deref())
+                            .into_iter()
+                            .map(OpenInterestDBRow::from)
+                            .collect();
+
+                    // 2) Publish to redis (no lock), unless only committed rows are published.
+                    // If you want "latest only", publish only last().
+                    let persisted_only = deps.publish_persisted_only() && !knobs.disable_db_writes;
+                    if !knobs.disable_redis_publishes && !persisted_only {
                         for e in &events {
                             if let Some((kind, ex, sym, fields)) = e.as_redis_publish() {
                                 let fields_ref = as_publish_fields(&fields);
@@ -145,9 +169,19 @@ pub async fn http_poll_binancelinear_oi(
 
                     // 3) Now lock batch and extend + write
                     if !knobs.disable_db_writes {
-                        let mut guard = batch.lock().await;
-                        guard.rows.extend(oi_db_rows);
-                        deps.db_write((&mut *guard).into()).await?;
+                        deps.db_write_and_publish(
+                            exchange.as_str(),
+                            &batch,
+                            oi_db_rows,
+                            false,
+                            !knobs.disable_redis_publishes,
+                        )
+                        .await?;
+                    }
+
+                    // Liveness: unix time of the last processed event
+                    if let Some(m) = deps.ingest_metrics.as_deref() {
+                        m.mark_stream_event(&stream_label);
                     }
 
                     // 4) TEST ESCAPE HATCH
@@ -201,6 +235,10 @@ pub async fn http_poll_binancelinear_oi(
                 }
             }
         }
+
+        // Stream stopped: the rows the batch still holds
+        let knobs = *knobs_rx_for_stop.borrow();
+        flush_stream_batch(&deps_for_stop, exchange, &batch_for_stop, knobs).await;
     });
 
     // Build handle + register in state
@@ -262,13 +300,17 @@ pub async fn http_poll_binancelinear_funding(
         Some(db) => db.cfg.writer.clone(),
         None => WriterConfig::default(),
     };
-    let db_batch = make_empty_batch::<FundingDBRow>(
+    let change_only = Arc::new(std::sync::Mutex::new(
+        ChangeOnlyFilter::funding(&writer_cfg).with_metrics(runtime.deps.ingest_metrics.as_deref()),
+    ));
+    let mut db_batch = make_empty_batch::<FundingDBRow>(
         exchange,
         transport,
         kind,
         symbol_for_task.clone(),
         writer_cfg,
     )?;
+    db_batch.track_persisted = runtime.deps.publish_persisted_only();
 
     // Runtime knobs for redis / db
     let (knobs_tx, mut knobs_rx) =
@@ -303,33 +345,50 @@ pub async fn http_poll_binancelinear_funding(
         let mut backoff = Duration::from_millis(250);
         let max_backoff = Duration::from_secs(10);
 
+        let deps_for_stop = deps.clone();
+        let batch_for_stop = Arc::clone(&batch);
+        let knobs_rx_for_stop = knobs_rx.clone();
+
         // Define on_item; it captures &mut batch for each call
-        let mut on_item = move |item: Vec<BinanceLinearFundingRateSnapshot>| {
+        // Liveness gauge label (one per stream)
+        let stream_label: Arc<str> = Arc::from(stream_id_for_task.to_string());
+        let mut on_item = move |item: Vec<BinanceLinearFundingRateSnapshot>,
+                                venue_time: Option<DateTime<Utc>>| {
+            let stream_label = Arc::clone(&stream_label);
             let cancel_for_item = cancel_for_test.clone();
             {
                 let knobs: StreamKnobs = *knobs_rx.borrow_and_update();
                 let batch = Arc::clone(&batch);
+                let change_only = Arc::clone(&change_only);
                 let map_ctx = Arc::clone(&map_ctx_for_task);
                 let map_envelope = Arc::clone(&map_envelope_for_task);
                 let deps = deps.clone();
 
                 async move {
                     // let res = BinanceLinearOpenInterestSnapshot::from_json_str(item.as_str())?;
-                    let events = item.map_to_events(&map_ctx, Some((*map_envelope).clone()))?;
+                    let mut events = item.map_to_events(&map_ctx, Some((*map_envelope).clone()))?;
+                    if let Some(t) = venue_time {
+                        events.iter_mut().for_each(|e| e.set_time(t));
+                    }
 
-                    // 1) Convert to DB rows (no lock yet)
-                    let funding_db_rows: Vec<FundingDBRow> = events
+                    // 1) Convert to DB rows (no lock yet); store-on-change drops unchanged values
+                    let funding_rows = events
                         .iter()
                         .filter_map(|e| match e {
-                            MarketEvent::Funding(funding) => {
-                                Some(FundingDBRow::from(funding.clone()))
-                            }
+                            MarketEvent::Funding(funding) => Some(funding.clone()),
                             _ => None,
                         })
                         .collect();
-
-                    // 2) Publish to redis (no lock). If you want "latest only", publish only last().
-                    if !knobs.disable_redis_publishes {
+                    let funding_db_rows: Vec<FundingDBRow> =
+                        retain_changed(&change_only, funding_rows, deps.ingest_metrics.as_deref())
+                            .into_iter()
+                            .map(FundingDBRow::from)
+                            .collect();
+
+                    // 2) Publish to redis (no lock), unless only committed rows are published.
+                    // If you want "latest only", publish only last().
+                    let persisted_only = deps.publish_persisted_only() && !knobs.disable_db_writes;
+                    if !knobs.disable_redis_publishes && !persisted_only {
                         for e in &events {
                             if let Some((kind, ex, sym, fields)) = e.as_redis_publish() {
                                 let fields_ref = as_publish_fields(&fields);
@@ -340,9 +399,19 @@ pub async fn http_poll_binancelinear_funding(
 
                     // 3) Now lock batch and extend + write
                     if !knobs.disable_db_writes {
-                        let mut guard = batch.lock().await;
-                        guard.rows.extend(funding_db_rows);
-                        deps.db_write((&mut *guard).into()).await?;
+                        deps.db_write_and_publish(
+                            exchange.as_str(),
+                            &batch,
+                            funding_db_rows,
+                            false,
+                            !knobs.disable_redis_publishes,
+                        )
+                        .await?;
+                    }
+
+                    // Liveness: unix time of the last processed event
+                    if let Some(m) = deps.ingest_metrics.as_deref() {
+                        m.mark_stream_event(&stream_label);
                     }
 
                     // 4) TEST ESCAPE HATCH
@@ -396,6 +465,10 @@ pub async fn http_poll_binancelinear_funding(
                 }
             }
         }
+
+        // Stream stopped: the rows the batch still holds
+        let knobs = *knobs_rx_for_stop.borrow();
+        flush_stream_batch(&deps_for_stop, exchange, &batch_for_stop, knobs).await;
     });
 
     // Build handle + register in state
@@ -413,13 +486,15 @@ pub async fn http_poll_binancelinear_funding(
     Ok(())
 }
 
+/// One-shot full depth snapshot. Returns the snapshot levels so the WS depth stream can
+/// seed its local book (`BookState`).
 pub async fn http_binance_linear_depth_snap(
     runtime: &AppRuntime,
     http_spec: HttpRequestSpec,
     map_ctx: MapCtx,
     map_envelope: MapEnvelope,
     symbol: String,
-) -> AppResult<()> {
+) -> AppResult<Vec<DepthDeltaRow>> {
     let exchange = ExchangeId::BinanceLinear;
     let transport = StreamTransport::HttpPoll;
     let kind = StreamKind::L2Book;
@@ -435,27 +510,39 @@ pub async fn http_binance_linear_depth_snap(
 
     let knobs: StreamKnobs = crate::app::StreamKnobs::from_deps(deps.clone());
 
-    // 1) Fetch once
-    let snap: BinanceLinearDepthSnapshot = api_client.execute_json(&http_spec).await?;
+    // 1) Fetch once (behind the shared resync throttle)
+    let (snap, venue_time): (BinanceLinearDepthSnapshot, _) = deps
+        .resync_throttle
+        .run(api_client.execute_json_timed(&http_spec))
+        .await?;
 
     // 2) Map to events
-    let events = snap.map_to_events(&map_ctx, Some(map_envelope))?;
+    let mut events = snap.map_to_events(&map_ctx, Some(map_envelope))?;
+    if let Some(t) = venue_time {
+        events.iter_mut().for_each(|e| e.set_time(t));
+    }
     let batch_size = events.len();
 
     // 3) Convert events -> DB rows (no locks; one-shot)
     //
     // Adjust the MarketEvent variant + DB row type to match your actual depth event model.
     // Examples people commonly use: MarketEvent::Depth, MarketEvent::OrderBook, etc.
-    let depth_db_rows: Vec<DepthDeltaDBRow> = events
+    let snapshot_rows: Vec<DepthDeltaRow> = events
         .iter()
         .filter_map(|e| match e {
-            MarketEvent::DepthDelta(d) => Some(DepthDeltaDBRow::from(d.clone())),
+            MarketEvent::DepthDelta(d) => Some(d.clone()),
             _ => None,
         })
         .collect();
+    let depth_db_rows: Vec<DepthDeltaDBRow> = snapshot_rows
+        .iter()
+        .cloned()
+        .map(DepthDeltaDBRow::from)
+        .collect();
 
-    // 4) Publish to redis (optional)
-    if !knobs.disable_redis_publishes {
+    // 4) Publish to redis (optional), unless only committed rows are published
+    let persisted_only = deps.publish_persisted_only() && !knobs.disable_db_writes;
+    if !knobs.disable_redis_publishes && !persisted_only {
         for e in &events {
             if let Some((kind, ex, sym, fields)) = e.as_redis_publish() {
                 let fields_ref = as_publish_fields(&fields);
@@ -475,25 +562,34 @@ pub async fn http_binance_linear_depth_snap(
             make_empty_batch::<DepthDeltaDBRow>(exchange, transport, kind, symbol, writer_cfg)?;
         batch.flush_rows = batch_size; // just insert everything on the full depth first snapshot
         batch.hard_cap_rows = batch_size * 5;
-
-        batch.rows.extend(depth_db_rows);
-        deps.db_write((&mut batch).into()).await?;
+        batch.track_persisted = persisted_only;
+
+        deps.db_write_and_publish(
+            exchange.as_str(),
+            &tokio::sync::Mutex::new(batch),
+            depth_db_rows,
+            false,
+            !knobs.disable_redis_publishes,
+        )
+        .await?;
     }
     if std::env::var_os("APP_TEST_ONESHOT").is_some() {
         println!("{:?}", events.iter().take(5).collect::<Vec<_>>());
         tracing::info!("APP_TEST_ONESHOT set: The test worked fine");
     }
 
-    Ok(())
+    Ok(snapshot_rows)
 }
 
+/// One-shot full depth snapshot. Returns the snapshot levels so the WS depth stream can
+/// seed its local book (`BookState`).
 pub async fn http_hyperliquid_perp_depth_snap(
     runtime: &AppRuntime,
     http_spec: HttpRequestSpec,
     map_ctx: MapCtx,
     map_envelope: MapEnvelope,
     symbol: String,
-) -> AppResult<()> {
+) -> AppResult<Vec<DepthDeltaRow>> {
     let exchange = ExchangeId::HyperliquidPerp;
     let transport = StreamTransport::HttpPoll;
     let kind = StreamKind::L2Book;
@@ -509,27 +605,39 @@ pub async fn http_hyperliquid_perp_depth_snap(
 
     let knobs: StreamKnobs = crate::app::StreamKnobs::from_deps(deps.clone());
 
-    // 1) Fetch once
-    let snap: HyperliquidPerpDepthSnapshot = api_client.execute_json(&http_spec).await?;
+    // 1) Fetch once (behind the shared resync throttle)
+    let (snap, venue_time): (HyperliquidPerpDepthSnapshot, _) = deps
+        .resync_throttle
+        .run(api_client.execute_json_timed(&http_spec))
+        .await?;
 
     // 2) Map to events
-    let events = snap.map_to_events(&map_ctx, Some(map_envelope))?;
+    let mut events = snap.map_to_events(&map_ctx, Some(map_envelope))?;
+    if let Some(t) = venue_time {
+        events.iter_mut().for_each(|e| e.set_time(t));
+    }
     let batch_size = events.len();
 
     // 3) Convert events -> DB rows (no locks; one-shot)
     //
     // Adjust the MarketEvent variant + DB row type to match your actual depth event model.
     // Examples people commonly use: MarketEvent::Depth, MarketEvent::OrderBook, etc.
-    let depth_db_rows: Vec<DepthDeltaDBRow> = events
+    let snapshot_rows: Vec<DepthDeltaRow> = events
         .iter()
         .filter_map(|e| match e {
-            MarketEvent::DepthDelta(d) => Some(DepthDeltaDBRow::from(d.clone())),
+            MarketEvent::DepthDelta(d) => Some(d.clone()),
             _ => None,
         })
         .collect();
+    let depth_db_rows: Vec<DepthDeltaDBRow> = snapshot_rows
+        .iter()
+        .cloned()
+        .map(DepthDeltaDBRow::from)
+        .collect();
 
-    // 4) Publish to redis (optional)
-    if !knobs.disable_redis_publishes {
+    // 4) Publish to redis (optional), unless only committed rows are published
+    let persisted_only = deps.publish_persisted_only() && !knobs.disable_db_writes;
+    if !knobs.disable_redis_publishes && !persisted_only {
         for e in &events {
             if let Some((kind, ex, sym, fields)) = e.as_redis_publish() {
                 let fields_ref = as_publish_fields(&fields);
@@ -549,9 +657,16 @@ pub async fn http_hyperliquid_perp_depth_snap(
             make_empty_batch::<DepthDeltaDBRow>(exchange, transport, kind, symbol, writer_cfg)?;
         batch.flush_rows = batch_size; // just insert everything on the full depth first snapshot
         batch.hard_cap_rows = batch_size * 5;
-
-        batch.rows.extend(depth_db_rows);
-        deps.db_write((&mut batch).into()).await?;
+        batch.track_persisted = persisted_only;
+
+        deps.db_write_and_publish(
+            exchange.as_str(),
+            &tokio::sync::Mutex::new(batch),
+            depth_db_rows,
+            false,
+            !knobs.disable_redis_publishes,
+        )
+        .await?;
     }
 
     if std::env::var_os("APP_TEST_ONESHOT").is_some() {
@@ -559,5 +674,5 @@ pub async fn http_hyperliquid_perp_depth_snap(
         tracing::info!("APP_TEST_ONESHOT set: The test worked fine");
     }
 
-    Ok(())
+    Ok(snapshot_rows)
 }
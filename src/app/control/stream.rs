@@ -1,13 +1,16 @@
 use crate::app::AppRuntime;
 use crate::app::StreamKnobs;
-use crate::app::control::helpers::{ctx_with_symbol, resolve_api_endpoint};
+use crate::app::control::helpers::{ctx_with_symbol_cased, resolve_api_endpoint};
 use crate::app::control::httppoll::http_poll_binancelinear_oi;
 use crate::app::stream_types::{ExchangeId, StreamId, StreamKind, StreamSpec, StreamTransport};
+use crate::db::RegistryUpsert;
 use crate::error::{AppError, AppResult};
+use crate::ingest::config::ExchangeConfigs;
 use crate::ingest::datamap::ctx::MapCtx;
 use crate::ingest::datamap::event::MapEnvelope;
-use crate::ingest::spec::ParamPlacement;
 use crate::ingest::spec::resolve::resolve_http_request;
+use crate::ingest::spec::types::{HttpRequestSpec, WsControlSpec};
+use crate::ingest::spec::{Ctx, ParamPlacement};
 
 /// Parameters to start a stream.
 #[derive(Debug, Clone)]
@@ -18,6 +21,16 @@ pub struct StartStreamParams {
     pub symbol: String,
 }
 
+/// Specs of one stream rendered ahead of its start (the startup restore renders every stream
+/// at once, see `render_stream_specs`). Whatever is None is rendered by the start.
+#[derive(Debug, Clone, Default)]
+pub struct RenderedSpecs {
+    /// Subscribe batches of its WS connection.
+    pub ws: Option<Vec<WsControlSpec>>,
+    /// Its REST request: the poll, or the depth snapshot taken before the WS diffs.
+    pub http: Option<HttpRequestSpec>,
+}
+
 /// Start a stream:
 /// - Builds StreamId + StreamSpec
 /// - Spawns worker task
@@ -27,10 +40,39 @@ pub async fn start_stream(
     p: StartStreamParams,
     add_to_db_registry: bool,
 ) -> AppResult<()> {
+    let entry = start_stream_rendered(app, p, RenderedSpecs::default()).await?;
+
+    if add_to_db_registry {
+        match app.deps.as_ref().db.as_ref() {
+            Some(db) => {
+                db.handler
+                    .as_ref()
+                    .upsert_stream_registry(&entry.spec, &entry.knobs, entry.enabled)
+                    .await
+            }
+            None => Ok(()),
+        }?;
+    }
+
+    Ok(())
+}
+
+/// `start_stream` with specs rendered beforehand, leaving the registry write to the caller
+/// (e.g. one `upsert_stream_registry_many` for a whole restore). Returns its registry entry.
+pub async fn start_stream_rendered(
+    app: &AppRuntime,
+    mut p: StartStreamParams,
+    rendered: RenderedSpecs,
+) -> AppResult<RegistryUpsert> {
     // 1) Build StreamSpec / StreamId
     let deps = app.deps.clone();
 
     app.ensure_runtime_ok_for_admission()?;
+    app.ensure_db_write_ready().await?;
+    app.ensure_registry_fresh().await?;
+
+    // One canonical casing per symbol: ids, registry lookups, rows and Redis keys
+    p.symbol = app.canonical_symbol(p.exchange, &p.symbol);
 
     let exchange_str = p.exchange.as_str();
     let spec = StreamSpec {
@@ -41,10 +83,8 @@ pub async fn start_stream(
     };
     let id = StreamId::new(exchange_str, p.symbol.as_str(), p.kind, p.transport);
 
-    // 2) Ensure not already running
-    if app.state.contains(&id).await {
-        return Err(AppError::StreamAlreadyExists(id.to_string()));
-    }
+    // 2) Ensure not already running (or being started): held until the handle is inserted
+    let _reservation = app.state.reserve_start(&id).await?;
 
     let knobs = StreamKnobs::from_deps(deps.clone());
 
@@ -53,19 +93,19 @@ pub async fn start_stream(
     let map_envelope = build_map_envelope(&p)?;
 
     // 5) Build Symbol CTX
-    let mut ctx = ctx_with_symbol(p.exchange, p.transport, &p.symbol);
+    let mut ctx = ctx_with_symbol_cased(&deps.exchange_cfgs, p.exchange, p.transport, &p.symbol);
 
     // 6) Resolve Param Placement for http
     let http_placement = ParamPlacement::for_exchange(p.exchange);
+    let RenderedSpecs { ws, http } = rendered;
 
     match spec.transport {
         StreamTransport::HttpPoll => {
-            let ep = resolve_api_endpoint(&deps.exchange_cfgs, p.exchange, spec.kind)?;
-            let reqspec = resolve_http_request(&ep, &ctx, http_placement)?;
+            let reqspec = rendered_http(&deps.exchange_cfgs, &p, &ctx, http_placement, http)?;
 
             match spec.kind {
-                StreamKind::OpenInterest => match p.exchange.as_str() {
-                    "binance_linear" => {
+                StreamKind::OpenInterest => match p.exchange {
+                    ExchangeId::BinanceLinear => {
                         http_poll_binancelinear_oi(
                             app,
                             reqspec,
@@ -80,8 +120,8 @@ pub async fn start_stream(
                     _ => Err(AppError::Internal("Unsupported Exchange".to_string())),
                 },
 
-                StreamKind::Funding => match p.exchange.as_str() {
-                    "binance_linear" => {
+                StreamKind::Funding => match p.exchange {
+                    ExchangeId::BinanceLinear => {
                         crate::app::control::httppoll::http_poll_binancelinear_funding(
                             app,
                             reqspec,
@@ -105,8 +145,8 @@ pub async fn start_stream(
                 // -------------------------
                 // BINANCE LINEAR
                 // -------------------------
-                StreamKind::Trades => match p.exchange.as_str() {
-                    "binance_linear" => {
+                StreamKind::Trades => match p.exchange {
+                    ExchangeId::BinanceLinear => {
                         crate::app::control::ws::ws_binancelinear_aggtrades(
                             app,
                             ctx,
@@ -115,10 +155,11 @@ pub async fn start_stream(
                             spec.clone(),
                             p.symbol.clone(),
                             id,
+                            ws,
                         )
                         .await
                     }
-                    "hyperliquid_perp" => {
+                    ExchangeId::HyperliquidPerp => {
                         crate::app::control::ws::ws_hyperliquidperp_trades(
                             app,
                             ctx,
@@ -127,25 +168,26 @@ pub async fn start_stream(
                             spec.clone(),
                             p.symbol.clone(),
                             id,
+                            ws,
                         )
                         .await
                     }
-                    _ => Err(AppError::Internal("Unsupported Exchange".to_string())),
                 },
 
-                StreamKind::L2Book => match p.exchange.as_str() {
-                    "binance_linear" => {
-                        let ep = resolve_api_endpoint(&deps.exchange_cfgs, p.exchange, spec.kind)?;
-                        let reqspec = resolve_http_request(&ep, &ctx, http_placement)?;
+                StreamKind::L2Book => match p.exchange {
+                    ExchangeId::BinanceLinear => {
+                        let reqspec =
+                            rendered_http(&deps.exchange_cfgs, &p, &ctx, http_placement, http)?;
                         // Snap the whole orderbook at the current moment via API
-                        crate::app::control::httppoll::http_binance_linear_depth_snap(
-                            app,
-                            reqspec,
-                            map_ctx.clone(),
-                            map_envelope.clone(),
-                            p.symbol.clone(),
-                        )
-                        .await?;
+                        let snapshot =
+                            crate::app::control::httppoll::http_binance_linear_depth_snap(
+                                app,
+                                reqspec,
+                                map_ctx.clone(),
+                                map_envelope.clone(),
+                                p.symbol.clone(),
+                            )
+                            .await?;
                         crate::app::control::ws::ws_binancelinear_depth(
                             app,
                             ctx,
@@ -154,21 +196,24 @@ pub async fn start_stream(
                             spec.clone(),
                             p.symbol.clone(),
                             id,
+                            snapshot,
+                            ws,
                         )
                         .await
                     }
-                    "hyperliquid_perp" => {
-                        let ep = resolve_api_endpoint(&deps.exchange_cfgs, p.exchange, spec.kind)?;
-                        let reqspec = resolve_http_request(&ep, &ctx, http_placement)?;
+                    ExchangeId::HyperliquidPerp => {
+                        let reqspec =
+                            rendered_http(&deps.exchange_cfgs, &p, &ctx, http_placement, http)?;
                         // Snap the whole orderbook at the current moment via API
-                        crate::app::control::httppoll::http_hyperliquid_perp_depth_snap(
-                            app,
-                            reqspec,
-                            map_ctx.clone(),
-                            map_envelope.clone(),
-                            p.symbol.clone(),
-                        )
-                        .await?;
+                        let snapshot =
+                            crate::app::control::httppoll::http_hyperliquid_perp_depth_snap(
+                                app,
+                                reqspec,
+                                map_ctx.clone(),
+                                map_envelope.clone(),
+                                p.symbol.clone(),
+                            )
+                            .await?;
                         crate::app::control::ws::ws_hyperliquidperp_depth(
                             app,
                             ctx,
@@ -177,14 +222,15 @@ pub async fn start_stream(
                             spec.clone(),
                             p.symbol.clone(),
                             id,
+                            snapshot,
+                            ws,
                         )
                         .await
                     }
-                    _ => Err(AppError::Internal("Unsupported Exchange".to_string())),
                 },
 
-                StreamKind::Liquidations => match p.exchange.as_str() {
-                    "binance_linear" => {
+                StreamKind::Liquidations => match p.exchange {
+                    ExchangeId::BinanceLinear => {
                         crate::app::control::ws::ws_binancelinear_liquidation(
                             app,
                             ctx,
@@ -193,6 +239,7 @@ pub async fn start_stream(
                             spec.clone(),
                             p.symbol.clone(),
                             id,
+                            ws,
                         )
                         .await
                     }
@@ -202,8 +249,8 @@ pub async fn start_stream(
                 // -------------------------
                 // HYPERLIQUID PERP (combined OI + Funding stream)
                 // -------------------------
-                StreamKind::FundingOpenInterest => match p.exchange.as_str() {
-                    "hyperliquid_perp" => {
+                StreamKind::FundingOpenInterest => match p.exchange {
+                    ExchangeId::HyperliquidPerp => {
                         crate::app::control::ws::ws_hyperliquidperp_oifunding(
                             app,
                             ctx,
@@ -212,6 +259,7 @@ pub async fn start_stream(
                             spec.clone(),
                             p.symbol.clone(),
                             id,
+                            ws,
                         )
                         .await
                     }
@@ -222,8 +270,8 @@ pub async fn start_stream(
                 // you can either:
                 //  - error out (strict), OR
                 //  - route both to the combined stream (loose).
-                StreamKind::OpenInterest | StreamKind::Funding => match p.exchange.as_str() {
-                    "hyperliquid_perp" => Err(AppError::Internal(
+                StreamKind::OpenInterest | StreamKind::Funding => match p.exchange {
+                    ExchangeId::HyperliquidPerp => Err(AppError::Internal(
                         "hyperliquid_perp: use FundingOpenInterest (combined) stream kind"
                             .to_string(),
                     )),
@@ -235,19 +283,28 @@ pub async fn start_stream(
         }
     }
 
-    if add_to_db_registry {
-        match app.deps.as_ref().db.as_ref() {
-            Some(db) => {
-                db.handler
-                    .as_ref()
-                    .upsert_stream_registry(&spec, &knobs, true)
-                    .await
-            }
-            None => Ok(()),
-        }?;
-    }
+    Ok(RegistryUpsert {
+        spec,
+        knobs,
+        enabled: true,
+    })
+}
 
-    Ok(())
+/// The REST request of `p`: `rendered` if the caller rendered it, else resolved here.
+fn rendered_http(
+    configs: &ExchangeConfigs,
+    p: &StartStreamParams,
+    ctx: &Ctx,
+    placement: ParamPlacement,
+    rendered: Option<HttpRequestSpec>,
+) -> AppResult<HttpRequestSpec> {
+    match rendered {
+        Some(reqspec) => Ok(reqspec),
+        None => {
+            let ep = resolve_api_endpoint(configs, p.exchange, p.kind)?;
+            resolve_http_request(&ep, ctx, placement)
+        }
+    }
 }
 // =====================================================================================
 // Map ctx/envelope placeholders (replace with real mapping build)
@@ -258,7 +315,20 @@ fn build_map_ctx(app: &AppRuntime, spec: &StreamSpec) -> AppResult<MapCtx> {
     let deps = app.deps.clone(); // Arc<AppDeps> lives for function scope
     let app_cfgs = deps.app_cfgs.clone(); // whatever smart ptr this is
     let cfgs = app_cfgs.as_ref(); // borrow tied to `app_cfgs` lifetime
-    MapCtx::new(registry, cfgs, spec.exchange, &spec.instrument)
+    let exchange = spec.exchange.parse::<ExchangeId>().ok();
+    let symbol_case = exchange
+        .map(|ex| deps.exchange_cfgs.symbol_case(ex))
+        .unwrap_or_default();
+    let funding_bound = exchange.and_then(|ex| deps.exchange_cfgs.max_abs_funding_rate_pct(ex));
+    let qty_precision = exchange
+        .map(|ex| deps.exchange_cfgs.qty_precision(ex))
+        .unwrap_or_default();
+    Ok(
+        MapCtx::new_with_symbol_case(registry, cfgs, spec.exchange, &spec.instrument, symbol_case)?
+            .with_metrics(deps.ingest_metrics.clone())
+            .with_max_abs_funding_rate_pct(funding_bound)
+            .with_qty_precision(qty_precision),
+    )
 }
 
 fn build_map_envelope(par: &StartStreamParams) -> AppResult<MapEnvelope> {
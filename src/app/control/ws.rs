@@ -1,29 +1,46 @@
-use super::helpers::binance_ws_request_id;
-use crate::app::control::batch::make_empty_batch;
+use super::helpers::{binance_ws_request_id, resolve_api_endpoint};
+use crate::app::control::batch::{
+    flush_stream_batch, make_empty_batch, make_empty_derived_batch, retain_changed,
+    spawn_periodic_task,
+};
+use crate::app::dependencies::AppDeps;
 use crate::app::runtime::AppRuntime;
 use crate::app::state::StreamHandle;
 use crate::app::state::StreamKnobs;
 use crate::app::stream_types::{ExchangeId, StreamKind, StreamTransport};
 use crate::app::stream_types::{StreamId, StreamSpec, StreamStatus};
+use crate::db::Batch;
 use crate::db::WriterConfig;
 use crate::db::rows::{
-    DepthDeltaDBRow, FundingDBRow, LiquidationDBRow, OpenInterestDBRow, TradeDBRow,
+    BboRow, DepthDeltaDBRow, FundingDBRow, LiquidationDBRow, MicropriceRow, OpenInterestDBRow,
+    TradeDBRow, TradeImbalanceRow,
 };
 use crate::error::{AppError, AppResult};
 use crate::ingest::Ctx;
+use crate::ingest::datamap::book::{BookCache, BookSlot, BookState, BookUpdate, microprice_row};
+use crate::ingest::datamap::change_only::ChangeOnlyFilter;
+use crate::ingest::datamap::coalesce::DepthCoalescer;
 use crate::ingest::datamap::ctx::MapCtx;
 use crate::ingest::datamap::event::MapEnvelope;
-use crate::ingest::datamap::event::MarketEvent;
+use crate::ingest::datamap::event::{DepthDeltaRow, MarketEvent};
+use crate::ingest::datamap::frame::{apply_venue_time, decode_payload, map_frame};
+use crate::ingest::datamap::imbalance::TradeImbalance;
 use crate::ingest::datamap::sources::binance_linear::types::{
-    BinanceLinearWsAggTrade, BinanceLinearWsDepthUpdate, BinanceLinearWsForceOrder,
+    BinanceLinearDepthSnapshot, BinanceLinearWsAggTrade, BinanceLinearWsDepthUpdate,
+    BinanceLinearWsForceOrder,
 };
 use crate::ingest::datamap::sources::hyperliquid_perp::types::{
     HyperliquidPerpWsDepthUpdate, HyperliquidPerpWsOIFundingUpdate, HyperliquidPerpWsTrade,
 };
+use crate::ingest::http::ApiClient;
+use crate::ingest::spec::ParamPlacement;
+use crate::ingest::spec::resolve::resolve_http_request;
+use crate::ingest::spec::types::{HttpRequestSpec, WsControlSpec};
 use crate::ingest::traits::MapToEvents;
 use crate::ingest::ws::WsEvent;
 use crate::redis::StreamKind as RedisStreamKind;
 use crate::redis::fields::as_publish_fields;
+use crate::telemetry::throttle::log_throttle;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Duration;
@@ -31,6 +48,11 @@ use tokio::task::JoinHandle;
 use tokio_util::sync::CancellationToken;
 use tracing;
 
+/// Snapshots fetched per book re-seed before it is given up (each one that does not connect to
+/// the buffered deltas is fetched again).
+const RESEED_MAX_ATTEMPTS: usize = 3;
+
+#[allow(clippy::too_many_arguments)]
 pub async fn ws_binancelinear_aggtrades(
     runtime: &AppRuntime,
     mut ctx: Ctx,
@@ -39,6 +61,7 @@ pub async fn ws_binancelinear_aggtrades(
     stream_spec: StreamSpec,
     symbol: String,
     stream_id: StreamId,
+    rendered: Option<Vec<WsControlSpec>>,
 ) -> AppResult<()> {
     let exchange = ExchangeId::BinanceLinear;
     let transport = StreamTransport::Ws;
@@ -74,13 +97,32 @@ pub async fn ws_binancelinear_aggtrades(
         None => WriterConfig::default(),
     };
 
-    let db_batch = make_empty_batch::<TradeDBRow>(
+    // Buy/sell volume per window (`trade_imbalance_window_ms`, 0 = off); own batch key
+    // ("trade_imbalance"), routed like the trades -> lands on the same shard as its source
+    let imbalance = Arc::new(std::sync::Mutex::new(TradeImbalance::from_config(
+        symbol_for_task.clone(),
+        &writer_cfg,
+    )));
+    let imbalance_window_ms = writer_cfg.trade_imbalance_window_ms;
+    let imbalance_batch = Arc::new(tokio::sync::Mutex::new(make_empty_derived_batch::<
+        TradeImbalanceRow,
+    >(
+        exchange,
+        transport,
+        kind,
+        "trade_imbalance",
+        symbol_for_task.clone(),
+        writer_cfg.clone(),
+    )?));
+
+    let mut db_batch = make_empty_batch::<TradeDBRow>(
         exchange,
         transport,
         kind,
         symbol_for_task.clone(),
         writer_cfg,
     )?;
+    db_batch.track_persisted = runtime.deps.publish_persisted_only();
 
     // Runtime knobs for redis / db
     let (knobs_tx, mut knobs_rx) =
@@ -94,6 +136,36 @@ pub async fn ws_binancelinear_aggtrades(
         cancel_for_task.clone(),
     );
 
+    // Imbalance windows close on the clock too, so a quiet symbol still emits its last one
+    let imbalance_task = {
+        let deps = deps.clone();
+        let imbalance = Arc::clone(&imbalance);
+        let imbalance_batch = Arc::clone(&imbalance_batch);
+        let knobs_rx = knobs_rx.clone();
+        spawn_periodic_task(
+            Duration::from_millis(imbalance_window_ms),
+            cancel_for_task.clone(),
+            move || {
+                let deps = deps.clone();
+                let imbalance = Arc::clone(&imbalance);
+                let imbalance_batch = Arc::clone(&imbalance_batch);
+                let knobs = *knobs_rx.borrow();
+                async move {
+                    if let Err(e) = write_due_trade_imbalance(
+                        &deps,
+                        &imbalance,
+                        &imbalance_batch,
+                        !knobs.disable_db_writes,
+                    )
+                    .await
+                    {
+                        tracing::warn!(error = ?e, "trade imbalance write failed");
+                    }
+                }
+            },
+        )
+    };
+
     let task: JoinHandle<()> = tokio::spawn(async move {
         let deps = deps.clone();
 
@@ -115,6 +187,10 @@ pub async fn ws_binancelinear_aggtrades(
         // ------------------------------------------------------------
 
         let deps_for_closure = deps.clone();
+        let imbalance_for_stop = Arc::clone(&imbalance);
+        let imbalance_batch_for_stop = Arc::clone(&imbalance_batch);
+        let batch_for_stop = Arc::clone(&batch);
+        let knobs_rx_for_stop = knobs_rx.clone();
 
         // borrow configs from a local Arc, not from deps
         let exchange_cfgs = deps.exchange_cfgs.clone();
@@ -127,6 +203,8 @@ pub async fn ws_binancelinear_aggtrades(
             .ws
             .get("trades")
             .expect("missing [ws.trades] in binance config");
+        let time_field = stream.time_field.clone();
+        let array_frames = cfg.ws_array_frames;
 
         // Build WS ctx (minimum fields typically used by control templates)
         // Adjust keys if your templates expect different names.
@@ -145,7 +223,11 @@ pub async fn ws_binancelinear_aggtrades(
         // ------------------------------------------------------------
         // on_event: parse -> map_to_events -> redis/db (same logic)
         // ------------------------------------------------------------
+        // Liveness gauge label (one per stream)
+        let stream_label: Arc<str> = Arc::from(stream_id_for_task.to_string());
         let mut on_event = move |ev: WsEvent| {
+            let time_field = time_field.clone();
+            let stream_label = Arc::clone(&stream_label);
             // clone for this invocation (moved into async block)
             let test_counter = Arc::clone(&test_counter);
 
@@ -153,6 +235,8 @@ pub async fn ws_binancelinear_aggtrades(
             let cancel_for_item = cancel_for_test.clone();
             let knobs: StreamKnobs = *knobs_rx.borrow_and_update();
             let batch = Arc::clone(&batch);
+            let imbalance = Arc::clone(&imbalance);
+            let imbalance_batch = Arc::clone(&imbalance_batch);
             let map_ctx = Arc::clone(&map_ctx_for_task);
             let map_envelope = Arc::clone(&map_envelope_for_task);
             let deps = deps.clone();
@@ -162,7 +246,9 @@ pub async fn ws_binancelinear_aggtrades(
                     WsEvent::Text(s) => s,
                     WsEvent::Binary(_) => return Ok(()), // ignore (or add gzip handling if needed)
                     WsEvent::Ping(_) | WsEvent::Pong(_) => return Ok(()),
-                    WsEvent::Close(_) => return Ok(()),
+                    WsEvent::Close(_)
+                    | WsEvent::Connected { .. }
+                    | WsEvent::Disconnected { .. } => return Ok(()),
                 };
 
                 let v: serde_json::Value = match serde_json::from_str(&text) {
@@ -170,25 +256,30 @@ pub async fn ws_binancelinear_aggtrades(
                     Err(_) => return Ok(()), // ignore non-json
                 };
 
-                // 1) Ignore subscribe/unsubscribe acks: {"result":null,"id":...}
-                if v.get("result").is_some() && v.get("id").is_some() {
-                    return Ok(());
-                }
-
-                // 2) Handle combined stream wrapper: {"stream": "...", "data": {...}}
-                let payload = v.get("data").cloned().unwrap_or_else(|| v.clone());
-
-                // 3) Only attempt aggTrade
-                if payload.get("e").and_then(|x| x.as_str()) != Some("aggTrade") {
+                // Every aggTrade of the frame (one message, or an array of them)
+                let events = map_frame::<BinanceLinearWsAggTrade>(
+                    v,
+                    array_frames,
+                    "ws trades",
+                    |v| {
+                        // Ignore subscribe/unsubscribe acks: {"result":null,"id":...}
+                        if v.get("result").is_some() && v.get("id").is_some() {
+                            return None;
+                        }
+                        // Handle combined stream wrapper: {"stream": "...", "data": {...}}
+                        let payload = v.get("data").cloned().unwrap_or(v);
+                        // Only attempt aggTrade
+                        (payload.get("e").and_then(|x| x.as_str()) == Some("aggTrade"))
+                            .then_some(payload)
+                    },
+                    &map_ctx,
+                    Some(&map_envelope),
+                    time_field.as_ref(),
+                )?;
+                if events.is_empty() {
                     return Ok(());
                 }
 
-                // 4) Now typed deserialization is safe-ish
-                let item: BinanceLinearWsAggTrade = serde_json::from_value(payload)
-                    .map_err(|e| AppError::Internal(format!("ws trades deserialize error: {e}")))?;
-
-                let events = item.map_to_events(&map_ctx, Some((*map_envelope).clone()))?;
-
                 // 1) Convert to DB rows (no lock yet)
                 let trade_db_rows: Vec<TradeDBRow> = events
                     .iter()
@@ -198,8 +289,9 @@ pub async fn ws_binancelinear_aggtrades(
                     })
                     .collect();
 
-                // 2) Publish to redis (no lock)
-                if !knobs.disable_redis_publishes {
+                // 2) Publish to redis (no lock), unless only committed rows are published
+                let persisted_only = deps.publish_persisted_only() && !knobs.disable_db_writes;
+                if !knobs.disable_redis_publishes && !persisted_only {
                     for e in &events {
                         if let Some((kind, ex, sym, fields)) = e.as_redis_publish() {
                             let fields_ref = as_publish_fields(&fields);
@@ -210,9 +302,29 @@ pub async fn ws_binancelinear_aggtrades(
 
                 // 3) Now lock batch and extend + write
                 if !knobs.disable_db_writes {
-                    let mut guard = batch.lock().await;
-                    guard.rows.extend(trade_db_rows);
-                    deps.db_write((&mut *guard).into()).await?;
+                    deps.db_write_and_publish(
+                        exchange.as_str(),
+                        &batch,
+                        trade_db_rows,
+                        false,
+                        !knobs.disable_redis_publishes,
+                    )
+                    .await?;
+                }
+
+                // 3b) Derived trade imbalance: windows closed by these trades
+                write_trade_imbalance(
+                    &deps,
+                    &imbalance,
+                    &imbalance_batch,
+                    &events,
+                    !knobs.disable_db_writes,
+                )
+                .await?;
+
+                // Liveness: unix time of the last processed event
+                if let Some(m) = deps.ingest_metrics.as_deref() {
+                    m.mark_stream_event(&stream_label);
                 }
 
                 // 4) TEST ESCAPE HATCH
@@ -236,10 +348,11 @@ pub async fn ws_binancelinear_aggtrades(
         // One call runs the reconnect/breaker loop internally.
         // Cancellation is handled by passing our stream cancel token through.
         if let Err(e) = ws_client
-            .run_stream(
+            .run_stream_rendered(
                 ws_limiters,
                 stream,
                 ctx,
+                rendered,
                 &mut on_event,
                 None, // no test hook in production
                 Some(cancel_for_task.clone()),
@@ -251,6 +364,12 @@ pub async fn ws_binancelinear_aggtrades(
                 tracing::warn!(error=?e, "ws stream exited with error");
             }
         }
+
+        // Stream stopped: the trades the batch still holds, its partial imbalance window,
+        // and any window still pending
+        let knobs = *knobs_rx_for_stop.borrow();
+        flush_stream_batch(&deps, exchange, &batch_for_stop, knobs).await;
+        flush_trade_imbalance(&deps, &imbalance_for_stop, &imbalance_batch_for_stop).await;
     });
 
     // Build handle + register in state
@@ -260,13 +379,14 @@ pub async fn ws_binancelinear_aggtrades(
         cancel,
         task,
         knobs_tx,
-        vec![knobs_task],
+        vec![knobs_task, imbalance_task],
     );
 
     runtime.state.insert(stream_id, handle).await?;
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn ws_binancelinear_depth(
     runtime: &AppRuntime,
     mut ctx: Ctx,
@@ -275,6 +395,8 @@ pub async fn ws_binancelinear_depth(
     stream_spec: StreamSpec,
     symbol: String,
     stream_id: StreamId,
+    snapshot: Vec<DepthDeltaRow>,
+    rendered: Option<Vec<WsControlSpec>>,
 ) -> AppResult<()> {
     let exchange = ExchangeId::BinanceLinear;
     let transport = StreamTransport::Ws;
@@ -310,13 +432,58 @@ pub async fn ws_binancelinear_depth(
         None => WriterConfig::default(),
     };
 
-    let db_batch = make_empty_batch::<DepthDeltaDBRow>(
+    // Depth write-amplification guard (pass-through when window is 0)
+    let coalescer = Arc::new(std::sync::Mutex::new(DepthCoalescer::from_config(
+        &writer_cfg,
+    )));
+    let coalesce_window_ms = writer_cfg.depth_coalesce_window_ms;
+
+    // Local book (seeded from the REST snapshot) -> BBO / microprice rows when `emit_bbo` /
+    // `emit_microprice` is on.
+    // The book is maintained even when the knob is off so it is correct once enabled.
+    // It lives in the shared (bounded) book cache; once evicted, the next update starts a
+    // re-seed from a fresh REST snapshot, off the read loop.
+    let book_cache = Arc::clone(&deps.book_cache);
+    let bbo_min_interval_ms = writer_cfg.bbo_min_interval_ms;
+    let mut book = BookState::from_config(symbol_for_task.clone(), &writer_cfg);
+    book.seed(&snapshot);
+    let book_slot = book_cache.register(&stream_id_for_task.to_string(), book);
+    let api_client = deps.binance_linear_client.clone();
+    let resync_spec = Arc::new(resolve_http_request(
+        &resolve_api_endpoint(&deps.exchange_cfgs, exchange, kind)?,
+        &ctx,
+        ParamPlacement::for_exchange(exchange),
+    )?);
+
+    // Own batch keys ("bbo", "microprice"), routed like the depth rows -> land on the same
+    // shard as their source
+    let bbo_batch = Arc::new(tokio::sync::Mutex::new(make_empty_derived_batch::<BboRow>(
+        exchange,
+        transport,
+        kind,
+        "bbo",
+        symbol_for_task.clone(),
+        writer_cfg.clone(),
+    )?));
+    let microprice_batch = Arc::new(tokio::sync::Mutex::new(make_empty_derived_batch::<
+        MicropriceRow,
+    >(
+        exchange,
+        transport,
+        kind,
+        "microprice",
+        symbol_for_task.clone(),
+        writer_cfg.clone(),
+    )?));
+
+    let mut db_batch = make_empty_batch::<DepthDeltaDBRow>(
         exchange,
         transport,
         kind,
         symbol_for_task.clone(),
         writer_cfg,
     )?;
+    db_batch.track_persisted = runtime.deps.publish_persisted_only();
 
     // Runtime knobs for redis / db
     let (knobs_tx, mut knobs_rx) =
@@ -330,6 +497,70 @@ pub async fn ws_binancelinear_depth(
         cancel_for_task.clone(),
     );
 
+    // A coalesced window is released by the clock too, not only by the next delta
+    let coalesce_task = {
+        let deps = deps.clone();
+        let coalescer = Arc::clone(&coalescer);
+        let batch = Arc::clone(&batch);
+        let knobs_rx = knobs_rx.clone();
+        spawn_periodic_task(
+            Duration::from_millis(coalesce_window_ms),
+            cancel_for_task.clone(),
+            move || {
+                let deps = deps.clone();
+                let coalescer = Arc::clone(&coalescer);
+                let batch = Arc::clone(&batch);
+                let knobs = *knobs_rx.borrow();
+                async move {
+                    if let Err(e) =
+                        write_coalesced_depth(&deps, exchange, &coalescer, &batch, knobs, false)
+                            .await
+                    {
+                        tracing::warn!(error = ?e, "coalesced depth write failed");
+                    }
+                }
+            },
+        )
+    };
+
+    // Trailing edge of the BBO debounce: the last top of a burst (and its microprice) is
+    // written without waiting for the next update
+    let held_bbo_task = {
+        let deps = deps.clone();
+        let book_cache = Arc::clone(&book_cache);
+        let book_slot = Arc::clone(&book_slot);
+        let bbo_batch = Arc::clone(&bbo_batch);
+        let microprice_batch = Arc::clone(&microprice_batch);
+        let knobs_rx = knobs_rx.clone();
+        spawn_periodic_task(
+            Duration::from_millis(bbo_min_interval_ms),
+            cancel_for_task.clone(),
+            move || {
+                let deps = deps.clone();
+                let book_cache = Arc::clone(&book_cache);
+                let book_slot = Arc::clone(&book_slot);
+                let bbo_batch = Arc::clone(&bbo_batch);
+                let microprice_batch = Arc::clone(&microprice_batch);
+                let knobs = *knobs_rx.borrow();
+                async move {
+                    if let Err(e) = write_held_bbo(
+                        &deps,
+                        &book_cache,
+                        &book_slot,
+                        &bbo_batch,
+                        &microprice_batch,
+                        knobs,
+                        false,
+                    )
+                    .await
+                    {
+                        tracing::warn!(error = ?e, "held bbo write failed");
+                    }
+                }
+            },
+        )
+    };
+
     let task: JoinHandle<()> = tokio::spawn(async move {
         let deps = deps.clone();
 
@@ -351,6 +582,13 @@ pub async fn ws_binancelinear_depth(
         // ------------------------------------------------------------
 
         let deps_for_closure = deps.clone();
+        let coalescer_for_stop = Arc::clone(&coalescer);
+        let batch_for_stop = Arc::clone(&batch);
+        let book_cache_for_stop = Arc::clone(&book_cache);
+        let book_slot_for_stop = Arc::clone(&book_slot);
+        let bbo_batch_for_stop = Arc::clone(&bbo_batch);
+        let microprice_batch_for_stop = Arc::clone(&microprice_batch);
+        let knobs_rx_for_stop = knobs_rx.clone();
 
         // borrow configs from a local Arc, not from deps
         let exchange_cfgs = deps.exchange_cfgs.clone();
@@ -363,6 +601,7 @@ pub async fn ws_binancelinear_depth(
             .ws
             .get("depth_update")
             .expect("missing [ws.trades] in binance config");
+        let time_field = stream.time_field.clone();
 
         // Build WS ctx (minimum fields typically used by control templates)
         // Adjust keys if your templates expect different names.
@@ -381,7 +620,11 @@ pub async fn ws_binancelinear_depth(
         // ------------------------------------------------------------
         // on_event: parse -> map_to_events -> redis/db (same logic)
         // ------------------------------------------------------------
+        // Liveness gauge label (one per stream)
+        let stream_label: Arc<str> = Arc::from(stream_id_for_task.to_string());
         let mut on_event = move |ev: WsEvent| {
+            let time_field = time_field.clone();
+            let stream_label = Arc::clone(&stream_label);
             // clone for this invocation (moved into async block)
             let test_counter = Arc::clone(&test_counter);
 
@@ -389,6 +632,14 @@ pub async fn ws_binancelinear_depth(
             let cancel_for_item = cancel_for_test.clone();
             let knobs: StreamKnobs = *knobs_rx.borrow_and_update();
             let batch = Arc::clone(&batch);
+            let coalescer = Arc::clone(&coalescer);
+            let book_cache = Arc::clone(&book_cache);
+            let book_slot = Arc::clone(&book_slot);
+            let api_client = api_client.clone();
+            let resync_spec = Arc::clone(&resync_spec);
+            let book_symbol = symbol_for_task.clone();
+            let bbo_batch = Arc::clone(&bbo_batch);
+            let microprice_batch = Arc::clone(&microprice_batch);
             let map_ctx = Arc::clone(&map_ctx_for_task);
             let map_envelope = Arc::clone(&map_envelope_for_task);
             let deps = deps.clone();
@@ -398,7 +649,9 @@ pub async fn ws_binancelinear_depth(
                     WsEvent::Text(s) => s,
                     WsEvent::Binary(_) => return Ok(()), // ignore (or add gzip handling if needed)
                     WsEvent::Ping(_) | WsEvent::Pong(_) => return Ok(()),
-                    WsEvent::Close(_) => return Ok(()),
+                    WsEvent::Close(_)
+                    | WsEvent::Connected { .. }
+                    | WsEvent::Disconnected { .. } => return Ok(()),
                 };
 
                 let v: serde_json::Value = match serde_json::from_str(&text) {
@@ -420,24 +673,59 @@ pub async fn ws_binancelinear_depth(
                 }
 
                 // 4) Now typed deserialization is safe-ish
-                let item: BinanceLinearWsDepthUpdate =
-                    serde_json::from_value(payload).map_err(|e| {
-                        AppError::Internal(format!("ws depth update deserialize error: {e}"))
-                    })?;
-
-                let events = item.map_to_events(&map_ctx, Some((*map_envelope).clone()))?;
-
-                // 1) Convert to DB rows (no lock yet)
-                let trade_db_rows: Vec<DepthDeltaDBRow> = events
+                let item: BinanceLinearWsDepthUpdate = decode_payload(&payload, "ws depth update")?;
+                let first_seq = Some(item.first_update_id as i64);
+
+                let mut events = item.map_to_events(&map_ctx, Some((*map_envelope).clone()))?;
+                apply_venue_time(
+                    time_field.as_ref(),
+                    &payload,
+                    &mut events,
+                    "ws depth update",
+                );
+
+                // 1) Convert to DB rows (no lock yet), coalescing per level if enabled
+                let depth_rows: Vec<DepthDeltaRow> = events
                     .iter()
                     .filter_map(|e| match e {
-                        MarketEvent::DepthDelta(t) => Some(DepthDeltaDBRow::from(t.clone())),
+                        MarketEvent::DepthDelta(t) => Some(t.clone()),
                         _ => None,
                     })
                     .collect();
+                let bbo = match book_cache.apply_deltas(&book_slot, first_seq, &depth_rows) {
+                    BookUpdate::Applied(bbo) => bbo,
+                    BookUpdate::Buffered => None,
+                    BookUpdate::Reseed => {
+                        // Evicted while cold: re-seed from a fresh snapshot off the read loop;
+                        // the deltas received meanwhile are buffered and replayed
+                        let client = api_client.clone().ok_or_else(|| {
+                            AppError::Disabled("Binance Linear exchange is disabled!".into())
+                        })?;
+                        spawn_binance_book_reseed(
+                            deps.clone(),
+                            client,
+                            Arc::clone(&resync_spec),
+                            Arc::clone(&map_ctx),
+                            Arc::clone(&map_envelope),
+                            Arc::clone(&book_slot),
+                            book_symbol,
+                            bbo_min_interval_ms,
+                            cancel_for_item.clone(),
+                        );
+                        None
+                    }
+                };
+                let trade_db_rows: Vec<DepthDeltaDBRow> = coalescer
+                    .lock()
+                    .expect("depth coalescer mutex poisoned")
+                    .push(depth_rows)
+                    .into_iter()
+                    .map(DepthDeltaDBRow::from)
+                    .collect();
 
-                // 2) Publish to redis (no lock)
-                if !knobs.disable_redis_publishes {
+                // 2) Publish to redis (no lock), unless only committed rows are published
+                let persisted_only = deps.publish_persisted_only() && !knobs.disable_db_writes;
+                if !knobs.disable_redis_publishes && !persisted_only {
                     for e in &events {
                         if let Some((kind, ex, sym, fields)) = e.as_redis_publish() {
                             let fields_ref = as_publish_fields(&fields);
@@ -448,11 +736,40 @@ pub async fn ws_binancelinear_depth(
 
                 // 3) Now lock batch and extend + write
                 if !knobs.disable_db_writes {
-                    let mut guard = batch.lock().await;
-                    guard.rows.extend(trade_db_rows);
+                    deps.db_write_and_publish(
+                        exchange.as_str(),
+                        &batch,
+                        trade_db_rows,
+                        false,
+                        !knobs.disable_redis_publishes,
+                    )
+                    .await?;
+                }
+
+                // 3b) Derived best bid/ask, and its microprice (same debounce)
+                let microprice = bbo.as_ref().and_then(microprice_row);
+                if knobs.emit_bbo
+                    && !knobs.disable_db_writes
+                    && let Some(row) = bbo
+                {
+                    let mut guard = bbo_batch.lock().await;
+                    guard.extend(vec![row]);
+                    deps.db_write((&mut *guard).into()).await?;
+                }
+                if knobs.emit_microprice
+                    && !knobs.disable_db_writes
+                    && let Some(row) = microprice
+                {
+                    let mut guard = microprice_batch.lock().await;
+                    guard.extend(vec![row]);
                     deps.db_write((&mut *guard).into()).await?;
                 }
 
+                // Liveness: unix time of the last processed event
+                if let Some(m) = deps.ingest_metrics.as_deref() {
+                    m.mark_stream_event(&stream_label);
+                }
+
                 // 4) TEST ESCAPE HATCH
                 // 4) TEST ESCAPE HATCH (after N processed messages)
                 if std::env::var_os("APP_TEST_ONESHOT").is_some() {
@@ -474,10 +791,11 @@ pub async fn ws_binancelinear_depth(
         // One call runs the reconnect/breaker loop internally.
         // Cancellation is handled by passing our stream cancel token through.
         if let Err(e) = ws_client
-            .run_stream(
+            .run_stream_rendered(
                 ws_limiters,
                 stream,
                 ctx,
+                rendered,
                 &mut on_event,
                 None, // no test hook in production
                 Some(cancel_for_task.clone()),
@@ -489,6 +807,35 @@ pub async fn ws_binancelinear_depth(
                 tracing::warn!(error=?e, "ws stream exited with error");
             }
         }
+
+        // Stream stopped: the top the debounce still holds
+        let knobs = *knobs_rx_for_stop.borrow();
+        if let Err(e) = write_held_bbo(
+            &deps,
+            &book_cache_for_stop,
+            &book_slot_for_stop,
+            &bbo_batch_for_stop,
+            &microprice_batch_for_stop,
+            knobs,
+            true,
+        )
+        .await
+        {
+            tracing::warn!(error = ?e, "held bbo flush on stop failed");
+        }
+        // ...and the levels the coalescer still holds
+        if let Err(e) = write_coalesced_depth(
+            &deps,
+            exchange,
+            &coalescer_for_stop,
+            &batch_for_stop,
+            knobs,
+            true,
+        )
+        .await
+        {
+            tracing::warn!(error = ?e, "coalesced depth flush on stop failed");
+        }
     });
 
     // Build handle + register in state
@@ -498,13 +845,242 @@ pub async fn ws_binancelinear_depth(
         cancel,
         task,
         knobs_tx,
-        vec![knobs_task],
+        vec![knobs_task, coalesce_task, held_bbo_task],
     );
 
     runtime.state.insert(stream_id, handle).await?;
     Ok(())
 }
 
+/// Feed the trades of `events` to the imbalance aggregator; the windows they close are written
+/// (`write`) or dropped with the stream's DB writes off.
+async fn write_trade_imbalance(
+    deps: &AppDeps,
+    imbalance: &std::sync::Mutex<TradeImbalance>,
+    batch: &tokio::sync::Mutex<Batch<TradeImbalanceRow>>,
+    events: &[MarketEvent],
+    write: bool,
+) -> AppResult<()> {
+    let closed: Vec<TradeImbalanceRow> = {
+        let mut agg = imbalance.lock().unwrap_or_else(|e| e.into_inner());
+        if !agg.is_enabled() {
+            return Ok(());
+        }
+        events
+            .iter()
+            .filter_map(|e| match e {
+                MarketEvent::Trade(t) => agg.push(t),
+                _ => None,
+            })
+            .collect()
+    };
+    if write && !closed.is_empty() {
+        let mut guard = batch.lock().await;
+        guard.extend(closed);
+        deps.db_write((&mut *guard).into()).await?;
+    }
+    Ok(())
+}
+
+/// Close the open imbalance window once the clock is past its end (stream timer); written
+/// (`write`) or dropped with the stream's DB writes off.
+async fn write_due_trade_imbalance(
+    deps: &AppDeps,
+    imbalance: &std::sync::Mutex<TradeImbalance>,
+    batch: &tokio::sync::Mutex<Batch<TradeImbalanceRow>>,
+    write: bool,
+) -> AppResult<()> {
+    let closed = imbalance
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .close_due(chrono::Utc::now());
+    if write && let Some(row) = closed {
+        let mut guard = batch.lock().await;
+        guard.extend(vec![row]);
+        deps.db_write((&mut *guard).into()).await?;
+    }
+    Ok(())
+}
+
+/// Write the partial imbalance window and whatever the batch still holds, when a trade
+/// stream stops.
+async fn flush_trade_imbalance(
+    deps: &AppDeps,
+    imbalance: &std::sync::Mutex<TradeImbalance>,
+    batch: &tokio::sync::Mutex<Batch<TradeImbalanceRow>>,
+) {
+    let partial = imbalance.lock().unwrap_or_else(|e| e.into_inner()).flush();
+    let mut guard = batch.lock().await;
+    guard.extend(partial.into_iter().collect());
+    if let Err(e) = deps.db_flush((&mut *guard).into()).await {
+        tracing::warn!(error = ?e, "trade imbalance flush on stop failed");
+    }
+}
+
+/// Write the depth rows the coalescer still holds: its window once the clock is past it
+/// (`stop` = false, stream timer), or all of them with a flush (`stop` = true). Dropped with
+/// the stream's DB writes off, like the rows of a closed window.
+async fn write_coalesced_depth(
+    deps: &AppDeps,
+    exchange: ExchangeId,
+    coalescer: &std::sync::Mutex<DepthCoalescer>,
+    batch: &tokio::sync::Mutex<Batch<DepthDeltaDBRow>>,
+    knobs: StreamKnobs,
+    stop: bool,
+) -> AppResult<()> {
+    let rows = {
+        let mut c = coalescer.lock().expect("depth coalescer mutex poisoned");
+        if stop {
+            c.flush()
+        } else {
+            c.flush_due(chrono::Utc::now())
+        }
+    };
+    if knobs.disable_db_writes || (rows.is_empty() && !stop) {
+        return Ok(());
+    }
+    deps.db_write_and_publish(
+        exchange.as_str(),
+        batch,
+        rows.into_iter().map(DepthDeltaDBRow::from).collect(),
+        stop,
+        !knobs.disable_redis_publishes,
+    )
+    .await?;
+    Ok(())
+}
+
+/// Write the BBO row the debounce of the book `book_slot` holds back, and its microprice: once
+/// its interval has passed (`stop` = false, stream timer) or unconditionally with a flush of
+/// both batches (`stop` = true).
+async fn write_held_bbo(
+    deps: &AppDeps,
+    book_cache: &BookCache,
+    book_slot: &BookSlot,
+    bbo_batch: &tokio::sync::Mutex<Batch<BboRow>>,
+    microprice_batch: &tokio::sync::Mutex<Batch<MicropriceRow>>,
+    knobs: StreamKnobs,
+    stop: bool,
+) -> AppResult<()> {
+    if !(knobs.emit_bbo || knobs.emit_microprice) || knobs.disable_db_writes {
+        return Ok(());
+    }
+    let held = book_cache.peek_book(book_slot, |b| {
+        if stop {
+            b.flush_held()
+        } else {
+            b.flush_due(chrono::Utc::now())
+        }
+    });
+    let bbo = held.flatten();
+    if bbo.is_none() && !stop {
+        return Ok(());
+    }
+    let microprice = bbo.as_ref().and_then(microprice_row);
+    if knobs.emit_microprice && (microprice.is_some() || stop) {
+        let mut guard = microprice_batch.lock().await;
+        guard.extend(microprice.into_iter().collect());
+        if stop {
+            deps.db_flush((&mut *guard).into()).await?;
+        } else {
+            deps.db_write((&mut *guard).into()).await?;
+        }
+    }
+    if knobs.emit_bbo {
+        let mut guard = bbo_batch.lock().await;
+        guard.extend(bbo.into_iter().collect());
+        if stop {
+            deps.db_flush((&mut *guard).into()).await?;
+        } else {
+            deps.db_write((&mut *guard).into()).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Re-seed the evicted Binance book `slot` in the background: fetch a snapshot (queued behind
+/// the shared resync throttle), seed a fresh book with it and replay the buffered deltas past
+/// its `lastUpdateId`. A snapshot that does not connect to the buffered deltas is fetched
+/// again (up to `RESEED_MAX_ATTEMPTS`). On failure the buffer is dropped and the next update
+/// tries again. The task ends with the stream (`cancel`).
+#[allow(clippy::too_many_arguments)]
+fn spawn_binance_book_reseed(
+    deps: Arc<AppDeps>,
+    client: Arc<ApiClient>,
+    spec: Arc<HttpRequestSpec>,
+    map_ctx: Arc<MapCtx>,
+    map_envelope: Arc<MapEnvelope>,
+    slot: Arc<BookSlot>,
+    symbol: String,
+    bbo_min_interval_ms: u64,
+    cancel: CancellationToken,
+) {
+    tokio::spawn(async move {
+        let reseed = async {
+            for _ in 0..RESEED_MAX_ATTEMPTS {
+                let snapshot = deps
+                    .resync_throttle
+                    .run(binance_depth_snapshot_rows(
+                        &client,
+                        &spec,
+                        &map_ctx,
+                        &map_envelope,
+                    ))
+                    .await?;
+                let mut book = BookState::new(symbol.clone(), bbo_min_interval_ms);
+                book.seed(&snapshot);
+                if deps.book_cache.finish_reseed(&slot, book) {
+                    return Ok(());
+                }
+            }
+            Err(AppError::Internal(format!(
+                "depth snapshot did not connect to the buffered deltas after {RESEED_MAX_ATTEMPTS} attempts"
+            )))
+        };
+        let result = tokio::select! {
+            _ = cancel.cancelled() => return,
+            result = reseed => result,
+        };
+        if let Err(e) = result {
+            deps.book_cache.abort_reseed(&slot);
+            if let Some(suppressed) = log_throttle().allow("book re-seed failed") {
+                tracing::warn!(
+                    component = "book",
+                    book = slot.key(),
+                    suppressed,
+                    error = ?e,
+                    "book re-seed failed; retried on the next update"
+                );
+            }
+        }
+    });
+}
+
+/// Fresh REST depth snapshot, for re-seeding an evicted Binance book.
+async fn binance_depth_snapshot_rows(
+    client: &ApiClient,
+    spec: &HttpRequestSpec,
+    map_ctx: &MapCtx,
+    map_envelope: &MapEnvelope,
+) -> AppResult<Vec<DepthDeltaRow>> {
+    let (snap, venue_time): (BinanceLinearDepthSnapshot, _) =
+        client.execute_json_timed(spec).await?;
+    Ok(snap
+        .map_to_events(map_ctx, Some(map_envelope.clone()))?
+        .into_iter()
+        .filter_map(|e| match e {
+            MarketEvent::DepthDelta(mut d) => {
+                if let Some(t) = venue_time {
+                    d.time = t;
+                }
+                Some(d)
+            }
+            _ => None,
+        })
+        .collect())
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn ws_binancelinear_liquidation(
     runtime: &AppRuntime,
     mut ctx: Ctx,
@@ -513,6 +1089,7 @@ pub async fn ws_binancelinear_liquidation(
     stream_spec: StreamSpec,
     symbol: String,
     stream_id: StreamId,
+    rendered: Option<Vec<WsControlSpec>>,
 ) -> AppResult<()> {
     let exchange = ExchangeId::BinanceLinear;
     let transport = StreamTransport::Ws;
@@ -548,13 +1125,14 @@ pub async fn ws_binancelinear_liquidation(
         None => WriterConfig::default(),
     };
 
-    let db_batch = make_empty_batch::<LiquidationDBRow>(
+    let mut db_batch = make_empty_batch::<LiquidationDBRow>(
         exchange,
         transport,
         kind,
         symbol_for_task.clone(),
         writer_cfg,
     )?;
+    db_batch.track_persisted = runtime.deps.publish_persisted_only();
 
     // Runtime knobs for redis / db
     let (knobs_tx, mut knobs_rx) =
@@ -570,6 +1148,8 @@ pub async fn ws_binancelinear_liquidation(
 
     let task: JoinHandle<()> = tokio::spawn(async move {
         let deps = deps.clone();
+        let batch_for_stop = Arc::clone(&batch);
+        let knobs_rx_for_stop = knobs_rx.clone();
 
         // ✅ create counter first
         let test_msg_counter = Arc::new(AtomicUsize::new(0));
@@ -601,6 +1181,7 @@ pub async fn ws_binancelinear_liquidation(
             .ws
             .get("liquidations")
             .expect("missing [ws.liquidations] in binance config");
+        let time_field = stream.time_field.clone();
 
         // Build WS ctx (minimum fields typically used by control templates)
         // Adjust keys if your templates expect different names.
@@ -619,7 +1200,11 @@ pub async fn ws_binancelinear_liquidation(
         // ------------------------------------------------------------
         // on_event: parse -> map_to_events -> redis/db (same logic)
         // ------------------------------------------------------------
+        // Liveness gauge label (one per stream)
+        let stream_label: Arc<str> = Arc::from(stream_id_for_task.to_string());
         let mut on_event = move |ev: WsEvent| {
+            let time_field = time_field.clone();
+            let stream_label = Arc::clone(&stream_label);
             // clone for this invocation (moved into async block)
             let test_counter = Arc::clone(&test_counter);
 
@@ -636,7 +1221,9 @@ pub async fn ws_binancelinear_liquidation(
                     WsEvent::Text(s) => s,
                     WsEvent::Binary(_) => return Ok(()), // ignore (or add gzip handling if needed)
                     WsEvent::Ping(_) | WsEvent::Pong(_) => return Ok(()),
-                    WsEvent::Close(_) => return Ok(()),
+                    WsEvent::Close(_)
+                    | WsEvent::Connected { .. }
+                    | WsEvent::Disconnected { .. } => return Ok(()),
                 };
 
                 let v: serde_json::Value = match serde_json::from_str(&text) {
@@ -658,12 +1245,10 @@ pub async fn ws_binancelinear_liquidation(
                 }
 
                 // 4) Now typed deserialization is safe-ish
-                let item: BinanceLinearWsForceOrder =
-                    serde_json::from_value(payload).map_err(|e| {
-                        AppError::Internal(format!("ws force order deserialize error: {e}"))
-                    })?;
+                let item: BinanceLinearWsForceOrder = decode_payload(&payload, "ws force order")?;
 
-                let events = item.map_to_events(&map_ctx, Some((*map_envelope).clone()))?;
+                let mut events = item.map_to_events(&map_ctx, Some((*map_envelope).clone()))?;
+                apply_venue_time(time_field.as_ref(), &payload, &mut events, "ws force order");
 
                 // 1) Convert to DB rows (no lock yet)
                 let trade_db_rows: Vec<LiquidationDBRow> = events
@@ -674,8 +1259,9 @@ pub async fn ws_binancelinear_liquidation(
                     })
                     .collect();
 
-                // 2) Publish to redis (no lock)
-                if !knobs.disable_redis_publishes {
+                // 2) Publish to redis (no lock), unless only committed rows are published
+                let persisted_only = deps.publish_persisted_only() && !knobs.disable_db_writes;
+                if !knobs.disable_redis_publishes && !persisted_only {
                     for e in &events {
                         if let Some((kind, ex, sym, fields)) = e.as_redis_publish() {
                             let fields_ref = as_publish_fields(&fields);
@@ -686,9 +1272,19 @@ pub async fn ws_binancelinear_liquidation(
 
                 // 3) Now lock batch and extend + write
                 if !knobs.disable_db_writes {
-                    let mut guard = batch.lock().await;
-                    guard.rows.extend(trade_db_rows);
-                    deps.db_write((&mut *guard).into()).await?;
+                    deps.db_write_and_publish(
+                        exchange.as_str(),
+                        &batch,
+                        trade_db_rows,
+                        false,
+                        !knobs.disable_redis_publishes,
+                    )
+                    .await?;
+                }
+
+                // Liveness: unix time of the last processed event
+                if let Some(m) = deps.ingest_metrics.as_deref() {
+                    m.mark_stream_event(&stream_label);
                 }
 
                 // 4) TEST ESCAPE HATCH
@@ -712,10 +1308,11 @@ pub async fn ws_binancelinear_liquidation(
         // One call runs the reconnect/breaker loop internally.
         // Cancellation is handled by passing our stream cancel token through.
         if let Err(e) = ws_client
-            .run_stream(
+            .run_stream_rendered(
                 ws_limiters,
                 stream,
                 ctx,
+                rendered,
                 &mut on_event,
                 None, // no test hook in production
                 Some(cancel_for_task.clone()),
@@ -727,6 +1324,10 @@ pub async fn ws_binancelinear_liquidation(
                 tracing::warn!(error=?e, "ws stream exited with error");
             }
         }
+
+        // Stream stopped: the liquidations the batch still holds
+        let knobs = *knobs_rx_for_stop.borrow();
+        flush_stream_batch(&deps, exchange, &batch_for_stop, knobs).await;
     });
 
     // Build handle + register in state
@@ -743,6 +1344,7 @@ pub async fn ws_binancelinear_liquidation(
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn ws_hyperliquidperp_depth(
     runtime: &AppRuntime,
     mut ctx: Ctx,
@@ -751,6 +1353,8 @@ pub async fn ws_hyperliquidperp_depth(
     stream_spec: StreamSpec,
     symbol: String,
     stream_id: StreamId,
+    snapshot: Vec<DepthDeltaRow>,
+    rendered: Option<Vec<WsControlSpec>>,
 ) -> AppResult<()> {
     let exchange = ExchangeId::HyperliquidPerp;
     let transport = StreamTransport::Ws;
@@ -785,13 +1389,52 @@ pub async fn ws_hyperliquidperp_depth(
         None => WriterConfig::default(),
     };
 
-    let db_batch = make_empty_batch::<DepthDeltaDBRow>(
+    // Depth write-amplification guard (pass-through when window is 0)
+    let coalescer = Arc::new(std::sync::Mutex::new(DepthCoalescer::from_config(
+        &writer_cfg,
+    )));
+    let coalesce_window_ms = writer_cfg.depth_coalesce_window_ms;
+
+    // Local book (seeded from the REST snapshot) -> BBO / microprice rows when `emit_bbo` /
+    // `emit_microprice` is on.
+    // The book is maintained even when the knob is off so it is correct once enabled.
+    // It lives in the shared (bounded) book cache; once evicted, the next full-book message
+    // re-seeds it.
+    let book_cache = Arc::clone(&deps.book_cache);
+    let bbo_min_interval_ms = writer_cfg.bbo_min_interval_ms;
+    let mut book = BookState::from_config(symbol_for_task.clone(), &writer_cfg);
+    book.seed(&snapshot);
+    let book_slot = book_cache.register(&stream_id_for_task.to_string(), book);
+
+    // Own batch keys ("bbo", "microprice"), routed like the depth rows -> land on the same
+    // shard as their source
+    let bbo_batch = Arc::new(tokio::sync::Mutex::new(make_empty_derived_batch::<BboRow>(
+        exchange,
+        transport,
+        kind,
+        "bbo",
+        symbol_for_task.clone(),
+        writer_cfg.clone(),
+    )?));
+    let microprice_batch = Arc::new(tokio::sync::Mutex::new(make_empty_derived_batch::<
+        MicropriceRow,
+    >(
+        exchange,
+        transport,
+        kind,
+        "microprice",
+        symbol_for_task.clone(),
+        writer_cfg.clone(),
+    )?));
+
+    let mut db_batch = make_empty_batch::<DepthDeltaDBRow>(
         exchange,
         transport,
         kind,
         symbol_for_task.clone(),
         writer_cfg,
     )?;
+    db_batch.track_persisted = runtime.deps.publish_persisted_only();
 
     // Runtime knobs for redis / db
     let (knobs_tx, mut knobs_rx) =
@@ -805,6 +1448,70 @@ pub async fn ws_hyperliquidperp_depth(
         cancel_for_task.clone(),
     );
 
+    // A coalesced window is released by the clock too, not only by the next delta
+    let coalesce_task = {
+        let deps = deps.clone();
+        let coalescer = Arc::clone(&coalescer);
+        let batch = Arc::clone(&batch);
+        let knobs_rx = knobs_rx.clone();
+        spawn_periodic_task(
+            Duration::from_millis(coalesce_window_ms),
+            cancel_for_task.clone(),
+            move || {
+                let deps = deps.clone();
+                let coalescer = Arc::clone(&coalescer);
+                let batch = Arc::clone(&batch);
+                let knobs = *knobs_rx.borrow();
+                async move {
+                    if let Err(e) =
+                        write_coalesced_depth(&deps, exchange, &coalescer, &batch, knobs, false)
+                            .await
+                    {
+                        tracing::warn!(error = ?e, "coalesced depth write failed");
+                    }
+                }
+            },
+        )
+    };
+
+    // Trailing edge of the BBO debounce: the last top of a burst (and its microprice) is
+    // written without waiting for the next update
+    let held_bbo_task = {
+        let deps = deps.clone();
+        let book_cache = Arc::clone(&book_cache);
+        let book_slot = Arc::clone(&book_slot);
+        let bbo_batch = Arc::clone(&bbo_batch);
+        let microprice_batch = Arc::clone(&microprice_batch);
+        let knobs_rx = knobs_rx.clone();
+        spawn_periodic_task(
+            Duration::from_millis(bbo_min_interval_ms),
+            cancel_for_task.clone(),
+            move || {
+                let deps = deps.clone();
+                let book_cache = Arc::clone(&book_cache);
+                let book_slot = Arc::clone(&book_slot);
+                let bbo_batch = Arc::clone(&bbo_batch);
+                let microprice_batch = Arc::clone(&microprice_batch);
+                let knobs = *knobs_rx.borrow();
+                async move {
+                    if let Err(e) = write_held_bbo(
+                        &deps,
+                        &book_cache,
+                        &book_slot,
+                        &bbo_batch,
+                        &microprice_batch,
+                        knobs,
+                        false,
+                    )
+                    .await
+                    {
+                        tracing::warn!(error = ?e, "held bbo write failed");
+                    }
+                }
+            },
+        )
+    };
+
     let task: JoinHandle<()> = tokio::spawn(async move {
         let deps = deps.clone();
 
@@ -826,6 +1533,13 @@ pub async fn ws_hyperliquidperp_depth(
         // ------------------------------------------------------------
 
         let deps_for_closure = deps.clone();
+        let coalescer_for_stop = Arc::clone(&coalescer);
+        let batch_for_stop = Arc::clone(&batch);
+        let book_cache_for_stop = Arc::clone(&book_cache);
+        let book_slot_for_stop = Arc::clone(&book_slot);
+        let bbo_batch_for_stop = Arc::clone(&bbo_batch);
+        let microprice_batch_for_stop = Arc::clone(&microprice_batch);
+        let knobs_rx_for_stop = knobs_rx.clone();
 
         // borrow configs from a local Arc, not from deps
         let exchange_cfgs = deps.exchange_cfgs.clone();
@@ -838,6 +1552,7 @@ pub async fn ws_hyperliquidperp_depth(
             .ws
             .get("depth_update")
             .expect("missing [ws.depth_update] in hyperliquid perp config");
+        let time_field = stream.time_field.clone();
 
         // Build WS ctx (minimum fields typically used by control templates)
         // Adjust keys if your templates expect different names.
@@ -855,7 +1570,11 @@ pub async fn ws_hyperliquidperp_depth(
         // ------------------------------------------------------------
         // on_event: parse -> map_to_events -> redis/db (same logic)
         // ------------------------------------------------------------
+        // Liveness gauge label (one per stream)
+        let stream_label: Arc<str> = Arc::from(stream_id_for_task.to_string());
         let mut on_event = move |ev: WsEvent| {
+            let time_field = time_field.clone();
+            let stream_label = Arc::clone(&stream_label);
             // clone for this invocation (moved into async block)
             let test_counter = Arc::clone(&test_counter);
 
@@ -863,6 +1582,12 @@ pub async fn ws_hyperliquidperp_depth(
             let cancel_for_item = cancel_for_test.clone();
             let knobs: StreamKnobs = *knobs_rx.borrow_and_update();
             let batch = Arc::clone(&batch);
+            let coalescer = Arc::clone(&coalescer);
+            let book_cache = Arc::clone(&book_cache);
+            let book_slot = Arc::clone(&book_slot);
+            let book_symbol = symbol_for_task.clone();
+            let bbo_batch = Arc::clone(&bbo_batch);
+            let microprice_batch = Arc::clone(&microprice_batch);
             let map_ctx = Arc::clone(&map_ctx_for_task);
             let map_envelope = Arc::clone(&map_envelope_for_task);
             let deps = deps.clone();
@@ -872,7 +1597,9 @@ pub async fn ws_hyperliquidperp_depth(
                     WsEvent::Text(s) => s,
                     WsEvent::Binary(_) => return Ok(()), // ignore (or add gzip handling if needed)
                     WsEvent::Ping(_) | WsEvent::Pong(_) => return Ok(()),
-                    WsEvent::Close(_) => return Ok(()),
+                    WsEvent::Close(_)
+                    | WsEvent::Connected { .. }
+                    | WsEvent::Disconnected { .. } => return Ok(()),
                 };
 
                 let v: serde_json::Value = match serde_json::from_str(&text) {
@@ -891,24 +1618,42 @@ pub async fn ws_hyperliquidperp_depth(
                 }
 
                 // 3) Now typed deserialization is safe-ish
-                let item: HyperliquidPerpWsDepthUpdate =
-                    serde_json::from_value(v).map_err(|e| {
-                        AppError::Internal(format!("ws force order deserialize error: {e}"))
-                    })?;
+                let item: HyperliquidPerpWsDepthUpdate = decode_payload(&v, "ws depth update")?;
 
-                let events = item.map_to_events(&map_ctx, Some((*map_envelope).clone()))?;
+                let mut events = item.map_to_events(&map_ctx, Some((*map_envelope).clone()))?;
+                apply_venue_time(time_field.as_ref(), &v, &mut events, "ws depth update");
 
-                // 1) Convert to DB rows (no lock yet)
-                let trade_db_rows: Vec<DepthDeltaDBRow> = events
+                // 1) Convert to DB rows (no lock yet), coalescing per level if enabled
+                let depth_rows: Vec<DepthDeltaRow> = events
                     .iter()
                     .filter_map(|e| match e {
-                        MarketEvent::DepthDelta(t) => Some(DepthDeltaDBRow::from(t.clone())),
+                        MarketEvent::DepthDelta(t) => Some(t.clone()),
                         _ => None,
                     })
                     .collect();
+                // l2Book messages carry the full (top-N) book
+                let bbo = match book_cache.with_book(&book_slot, |b| b.apply_snapshot(&depth_rows))
+                {
+                    Some(bbo) => bbo,
+                    None => {
+                        // Evicted while cold: this full book re-seeds it
+                        let mut book = BookState::new(book_symbol, bbo_min_interval_ms);
+                        let bbo = book.apply_snapshot(&depth_rows);
+                        book_cache.seed(&book_slot, book);
+                        bbo
+                    }
+                };
+                let trade_db_rows: Vec<DepthDeltaDBRow> = coalescer
+                    .lock()
+                    .expect("depth coalescer mutex poisoned")
+                    .push(depth_rows)
+                    .into_iter()
+                    .map(DepthDeltaDBRow::from)
+                    .collect();
 
-                // 2) Publish to redis (no lock)
-                if !knobs.disable_redis_publishes {
+                // 2) Publish to redis (no lock), unless only committed rows are published
+                let persisted_only = deps.publish_persisted_only() && !knobs.disable_db_writes;
+                if !knobs.disable_redis_publishes && !persisted_only {
                     for e in &events {
                         if let Some((kind, ex, sym, fields)) = e.as_redis_publish() {
                             let fields_ref = as_publish_fields(&fields);
@@ -919,11 +1664,40 @@ pub async fn ws_hyperliquidperp_depth(
 
                 // 3) Now lock batch and extend + write
                 if !knobs.disable_db_writes {
-                    let mut guard = batch.lock().await;
-                    guard.rows.extend(trade_db_rows);
+                    deps.db_write_and_publish(
+                        exchange.as_str(),
+                        &batch,
+                        trade_db_rows,
+                        false,
+                        !knobs.disable_redis_publishes,
+                    )
+                    .await?;
+                }
+
+                // 3b) Derived best bid/ask, and its microprice (same debounce)
+                let microprice = bbo.as_ref().and_then(microprice_row);
+                if knobs.emit_bbo
+                    && !knobs.disable_db_writes
+                    && let Some(row) = bbo
+                {
+                    let mut guard = bbo_batch.lock().await;
+                    guard.extend(vec![row]);
+                    deps.db_write((&mut *guard).into()).await?;
+                }
+                if knobs.emit_microprice
+                    && !knobs.disable_db_writes
+                    && let Some(row) = microprice
+                {
+                    let mut guard = microprice_batch.lock().await;
+                    guard.extend(vec![row]);
                     deps.db_write((&mut *guard).into()).await?;
                 }
 
+                // Liveness: unix time of the last processed event
+                if let Some(m) = deps.ingest_metrics.as_deref() {
+                    m.mark_stream_event(&stream_label);
+                }
+
                 // 4) TEST ESCAPE HATCH
                 // 4) TEST ESCAPE HATCH (after N processed messages)
                 if std::env::var_os("APP_TEST_ONESHOT").is_some() {
@@ -945,10 +1719,11 @@ pub async fn ws_hyperliquidperp_depth(
         // One call runs the reconnect/breaker loop internally.
         // Cancellation is handled by passing our stream cancel token through.
         if let Err(e) = ws_client
-            .run_stream(
+            .run_stream_rendered(
                 ws_limiters,
                 stream,
                 ctx,
+                rendered,
                 &mut on_event,
                 None, // no test hook in production
                 Some(cancel_for_task.clone()),
@@ -960,6 +1735,35 @@ pub async fn ws_hyperliquidperp_depth(
                 tracing::warn!(error=?e, "ws stream exited with error");
             }
         }
+
+        // Stream stopped: the top the debounce still holds
+        let knobs = *knobs_rx_for_stop.borrow();
+        if let Err(e) = write_held_bbo(
+            &deps,
+            &book_cache_for_stop,
+            &book_slot_for_stop,
+            &bbo_batch_for_stop,
+            &microprice_batch_for_stop,
+            knobs,
+            true,
+        )
+        .await
+        {
+            tracing::warn!(error = ?e, "held bbo flush on stop failed");
+        }
+        // ...and the levels the coalescer still holds
+        if let Err(e) = write_coalesced_depth(
+            &deps,
+            exchange,
+            &coalescer_for_stop,
+            &batch_for_stop,
+            knobs,
+            true,
+        )
+        .await
+        {
+            tracing::warn!(error = ?e, "coalesced depth flush on stop failed");
+        }
     });
 
     // Build handle + register in state
@@ -969,13 +1773,14 @@ pub async fn ws_hyperliquidperp_depth(
         cancel,
         task,
         knobs_tx,
-        vec![knobs_task],
+        vec![knobs_task, coalesce_task, held_bbo_task],
     );
 
     runtime.state.insert(stream_id, handle).await?;
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn ws_hyperliquidperp_trades(
     runtime: &AppRuntime,
     mut ctx: Ctx,
@@ -984,6 +1789,7 @@ pub async fn ws_hyperliquidperp_trades(
     stream_spec: StreamSpec,
     symbol: String,
     stream_id: StreamId,
+    rendered: Option<Vec<WsControlSpec>>,
 ) -> AppResult<()> {
     let exchange = ExchangeId::HyperliquidPerp;
     let transport = StreamTransport::Ws;
@@ -1018,13 +1824,32 @@ pub async fn ws_hyperliquidperp_trades(
         None => WriterConfig::default(),
     };
 
-    let db_batch = make_empty_batch::<TradeDBRow>(
+    // Buy/sell volume per window (`trade_imbalance_window_ms`, 0 = off); own batch key
+    // ("trade_imbalance"), routed like the trades -> lands on the same shard as its source
+    let imbalance = Arc::new(std::sync::Mutex::new(TradeImbalance::from_config(
+        symbol_for_task.clone(),
+        &writer_cfg,
+    )));
+    let imbalance_window_ms = writer_cfg.trade_imbalance_window_ms;
+    let imbalance_batch = Arc::new(tokio::sync::Mutex::new(make_empty_derived_batch::<
+        TradeImbalanceRow,
+    >(
+        exchange,
+        transport,
+        kind,
+        "trade_imbalance",
+        symbol_for_task.clone(),
+        writer_cfg.clone(),
+    )?));
+
+    let mut db_batch = make_empty_batch::<TradeDBRow>(
         exchange,
         transport,
         kind,
         symbol_for_task.clone(),
         writer_cfg,
     )?;
+    db_batch.track_persisted = runtime.deps.publish_persisted_only();
 
     // Runtime knobs for redis / db
     let (knobs_tx, mut knobs_rx) =
@@ -1038,6 +1863,36 @@ pub async fn ws_hyperliquidperp_trades(
         cancel_for_task.clone(),
     );
 
+    // Imbalance windows close on the clock too, so a quiet symbol still emits its last one
+    let imbalance_task = {
+        let deps = deps.clone();
+        let imbalance = Arc::clone(&imbalance);
+        let imbalance_batch = Arc::clone(&imbalance_batch);
+        let knobs_rx = knobs_rx.clone();
+        spawn_periodic_task(
+            Duration::from_millis(imbalance_window_ms),
+            cancel_for_task.clone(),
+            move || {
+                let deps = deps.clone();
+                let imbalance = Arc::clone(&imbalance);
+                let imbalance_batch = Arc::clone(&imbalance_batch);
+                let knobs = *knobs_rx.borrow();
+                async move {
+                    if let Err(e) = write_due_trade_imbalance(
+                        &deps,
+                        &imbalance,
+                        &imbalance_batch,
+                        !knobs.disable_db_writes,
+                    )
+                    .await
+                    {
+                        tracing::warn!(error = ?e, "trade imbalance write failed");
+                    }
+                }
+            },
+        )
+    };
+
     let task: JoinHandle<()> = tokio::spawn(async move {
         let deps = deps.clone();
 
@@ -1059,6 +1914,10 @@ pub async fn ws_hyperliquidperp_trades(
         // ------------------------------------------------------------
 
         let deps_for_closure = deps.clone();
+        let imbalance_for_stop = Arc::clone(&imbalance);
+        let imbalance_batch_for_stop = Arc::clone(&imbalance_batch);
+        let batch_for_stop = Arc::clone(&batch);
+        let knobs_rx_for_stop = knobs_rx.clone();
 
         // borrow configs from a local Arc, not from deps
         let exchange_cfgs = deps.exchange_cfgs.clone();
@@ -1071,6 +1930,8 @@ pub async fn ws_hyperliquidperp_trades(
             .ws
             .get("trades")
             .expect("missing [ws.trades] in hyperliquid perp config");
+        let time_field = stream.time_field.clone();
+        let array_frames = cfg.ws_array_frames;
 
         // Build WS ctx (minimum fields typically used by control templates)
         // Adjust keys if your templates expect different names.
@@ -1088,7 +1949,11 @@ pub async fn ws_hyperliquidperp_trades(
         // ------------------------------------------------------------
         // on_event: parse -> map_to_events -> redis/db (same logic)
         // ------------------------------------------------------------
+        // Liveness gauge label (one per stream)
+        let stream_label: Arc<str> = Arc::from(stream_id_for_task.to_string());
         let mut on_event = move |ev: WsEvent| {
+            let time_field = time_field.clone();
+            let stream_label = Arc::clone(&stream_label);
             // clone for this invocation (moved into async block)
             let test_counter = Arc::clone(&test_counter);
 
@@ -1096,6 +1961,8 @@ pub async fn ws_hyperliquidperp_trades(
             let cancel_for_item = cancel_for_test.clone();
             let knobs: StreamKnobs = *knobs_rx.borrow_and_update();
             let batch = Arc::clone(&batch);
+            let imbalance = Arc::clone(&imbalance);
+            let imbalance_batch = Arc::clone(&imbalance_batch);
             let map_ctx = Arc::clone(&map_ctx_for_task);
             let map_envelope = Arc::clone(&map_envelope_for_task);
             let deps = deps.clone();
@@ -1105,7 +1972,9 @@ pub async fn ws_hyperliquidperp_trades(
                     WsEvent::Text(s) => s,
                     WsEvent::Binary(_) => return Ok(()), // ignore (or add gzip handling if needed)
                     WsEvent::Ping(_) | WsEvent::Pong(_) => return Ok(()),
-                    WsEvent::Close(_) => return Ok(()),
+                    WsEvent::Close(_)
+                    | WsEvent::Connected { .. }
+                    | WsEvent::Disconnected { .. } => return Ok(()),
                 };
 
                 let v: serde_json::Value = match serde_json::from_str(&text) {
@@ -1113,22 +1982,21 @@ pub async fn ws_hyperliquidperp_trades(
                     Err(_) => return Ok(()), // ignore non-json
                 };
 
-                // 1) Ignore subscription response (and any non-l2Book channels)
-                let channel = match v.get("channel").and_then(|x| x.as_str()) {
-                    Some(c) => c,
-                    None => return Ok(()),
-                };
-
-                if channel != "trades" {
+                // Every trade of the frame: `data` holds several trades, and an array
+                // frame several such messages. Subscription responses are skipped.
+                let events = map_frame::<HyperliquidPerpWsTrade>(
+                    v,
+                    array_frames,
+                    "ws trades",
+                    |v| (v.get("channel").and_then(|x| x.as_str()) == Some("trades")).then_some(v),
+                    &map_ctx,
+                    Some(&map_envelope),
+                    time_field.as_ref(),
+                )?;
+                if events.is_empty() {
                     return Ok(());
                 }
 
-                // 3) Now typed deserialization is safe-ish
-                let item: HyperliquidPerpWsTrade = serde_json::from_value(v)
-                    .map_err(|e| AppError::Internal(format!("ws trades deserialize error: {e}")))?;
-
-                let events = item.map_to_events(&map_ctx, Some((*map_envelope).clone()))?;
-
                 // 1) Convert to DB rows (no lock yet)
                 let trade_db_rows: Vec<TradeDBRow> = events
                     .iter()
@@ -1138,8 +2006,9 @@ pub async fn ws_hyperliquidperp_trades(
                     })
                     .collect();
 
-                // 2) Publish to redis (no lock)
-                if !knobs.disable_redis_publishes {
+                // 2) Publish to redis (no lock), unless only committed rows are published
+                let persisted_only = deps.publish_persisted_only() && !knobs.disable_db_writes;
+                if !knobs.disable_redis_publishes && !persisted_only {
                     for e in &events {
                         if let Some((kind, ex, sym, fields)) = e.as_redis_publish() {
                             let fields_ref = as_publish_fields(&fields);
@@ -1150,9 +2019,29 @@ pub async fn ws_hyperliquidperp_trades(
 
                 // 3) Now lock batch and extend + write
                 if !knobs.disable_db_writes {
-                    let mut guard = batch.lock().await;
-                    guard.rows.extend(trade_db_rows);
-                    deps.db_write((&mut *guard).into()).await?;
+                    deps.db_write_and_publish(
+                        exchange.as_str(),
+                        &batch,
+                        trade_db_rows,
+                        false,
+                        !knobs.disable_redis_publishes,
+                    )
+                    .await?;
+                }
+
+                // 3b) Derived trade imbalance: windows closed by these trades
+                write_trade_imbalance(
+                    &deps,
+                    &imbalance,
+                    &imbalance_batch,
+                    &events,
+                    !knobs.disable_db_writes,
+                )
+                .await?;
+
+                // Liveness: unix time of the last processed event
+                if let Some(m) = deps.ingest_metrics.as_deref() {
+                    m.mark_stream_event(&stream_label);
                 }
 
                 // 4) TEST ESCAPE HATCH
@@ -1176,10 +2065,11 @@ pub async fn ws_hyperliquidperp_trades(
         // One call runs the reconnect/breaker loop internally.
         // Cancellation is handled by passing our stream cancel token through.
         if let Err(e) = ws_client
-            .run_stream(
+            .run_stream_rendered(
                 ws_limiters,
                 stream,
                 ctx,
+                rendered,
                 &mut on_event,
                 None, // no test hook in production
                 Some(cancel_for_task.clone()),
@@ -1191,6 +2081,12 @@ pub async fn ws_hyperliquidperp_trades(
                 tracing::warn!(error=?e, "ws stream exited with error");
             }
         }
+
+        // Stream stopped: the trades the batch still holds, its partial imbalance window,
+        // and any window still pending
+        let knobs = *knobs_rx_for_stop.borrow();
+        flush_stream_batch(&deps, exchange, &batch_for_stop, knobs).await;
+        flush_trade_imbalance(&deps, &imbalance_for_stop, &imbalance_batch_for_stop).await;
     });
 
     // Build handle + register in state
@@ -1200,13 +2096,14 @@ pub async fn ws_hyperliquidperp_trades(
         cancel,
         task,
         knobs_tx,
-        vec![knobs_task],
+        vec![knobs_task, imbalance_task],
     );
 
     runtime.state.insert(stream_id, handle).await?;
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn ws_hyperliquidperp_oifunding(
     runtime: &AppRuntime,
     mut ctx: Ctx,
@@ -1215,6 +2112,7 @@ pub async fn ws_hyperliquidperp_oifunding(
     stream_spec: StreamSpec,
     symbol: String,
     stream_id: StreamId,
+    rendered: Option<Vec<WsControlSpec>>,
 ) -> AppResult<()> {
     let exchange = ExchangeId::HyperliquidPerp;
     let transport = StreamTransport::Ws;
@@ -1249,20 +2147,30 @@ pub async fn ws_hyperliquidperp_oifunding(
         None => WriterConfig::default(),
     };
 
-    let db_batch_oi = make_empty_batch::<OpenInterestDBRow>(
+    let change_only_oi = Arc::new(std::sync::Mutex::new(
+        ChangeOnlyFilter::open_interest(&writer_cfg)
+            .with_metrics(runtime.deps.ingest_metrics.as_deref()),
+    ));
+    let change_only_funding = Arc::new(std::sync::Mutex::new(
+        ChangeOnlyFilter::funding(&writer_cfg).with_metrics(runtime.deps.ingest_metrics.as_deref()),
+    ));
+
+    let mut db_batch_oi = make_empty_batch::<OpenInterestDBRow>(
         exchange,
         transport,
         kind,
         symbol_for_task.clone(),
         writer_cfg.clone(),
     )?;
-    let db_batch_funding = make_empty_batch::<FundingDBRow>(
+    db_batch_oi.track_persisted = runtime.deps.publish_persisted_only();
+    let mut db_batch_funding = make_empty_batch::<FundingDBRow>(
         exchange,
         transport,
         kind,
         symbol_for_task.clone(),
         writer_cfg,
     )?;
+    db_batch_funding.track_persisted = runtime.deps.publish_persisted_only();
 
     // Runtime knobs for redis / db
     let (knobs_tx, mut knobs_rx) =
@@ -1285,6 +2193,9 @@ pub async fn ws_hyperliquidperp_oifunding(
 
     let task: JoinHandle<()> = tokio::spawn(async move {
         let deps = deps.clone();
+        let batch_oi_for_stop = Arc::clone(&batch_oi);
+        let batch_funding_for_stop = Arc::clone(&batch_funding);
+        let knobs_rx_for_stop = knobs_rx.clone();
 
         // ✅ create counter first
         let test_msg_counter = Arc::new(AtomicUsize::new(0));
@@ -1316,6 +2227,7 @@ pub async fn ws_hyperliquidperp_oifunding(
             .ws
             .get("oi_funding")
             .expect("missing [ws.oi_funding] in hyperliquid perp config");
+        let time_field = stream.time_field.clone();
 
         // Build WS ctx (minimum fields typically used by control templates)
         // Adjust keys if your templates expect different names.
@@ -1333,7 +2245,11 @@ pub async fn ws_hyperliquidperp_oifunding(
         // ------------------------------------------------------------
         // on_event: parse -> map_to_events -> redis/db (same logic)
         // ------------------------------------------------------------
+        // Liveness gauge label (one per stream)
+        let stream_label: Arc<str> = Arc::from(stream_id_for_task.to_string());
         let mut on_event = move |ev: WsEvent| {
+            let time_field = time_field.clone();
+            let stream_label = Arc::clone(&stream_label);
             // clone for this invocation (moved into async block)
             let test_counter = Arc::clone(&test_counter);
 
@@ -1342,6 +2258,8 @@ pub async fn ws_hyperliquidperp_oifunding(
             let knobs: StreamKnobs = *knobs_rx.borrow_and_update();
             let batch_oi = Arc::clone(&batch_oi);
             let batch_funding = Arc::clone(&batch_funding);
+            let change_only_oi = Arc::clone(&change_only_oi);
+            let change_only_funding = Arc::clone(&change_only_funding);
             let map_ctx = Arc::clone(&map_ctx_for_task);
             let map_envelope = Arc::clone(&map_envelope_for_task);
             let deps = deps.clone();
@@ -1351,7 +2269,9 @@ pub async fn ws_hyperliquidperp_oifunding(
                     WsEvent::Text(s) => s,
                     WsEvent::Binary(_) => return Ok(()), // ignore (or add gzip handling if needed)
                     WsEvent::Ping(_) | WsEvent::Pong(_) => return Ok(()),
-                    WsEvent::Close(_) => return Ok(()),
+                    WsEvent::Close(_)
+                    | WsEvent::Connected { .. }
+                    | WsEvent::Disconnected { .. } => return Ok(()),
                 };
 
                 let v: serde_json::Value = match serde_json::from_str(&text) {
@@ -1370,33 +2290,45 @@ pub async fn ws_hyperliquidperp_oifunding(
                 }
 
                 // 3) Now typed deserialization is safe-ish
-                let item: HyperliquidPerpWsOIFundingUpdate =
-                    serde_json::from_value(v).map_err(|e| {
-                        AppError::Internal(format!("ws oi_funding deserialize error: {e}"))
-                    })?;
+                let item: HyperliquidPerpWsOIFundingUpdate = decode_payload(&v, "ws oi_funding")?;
 
-                let events = item.map_to_events(&map_ctx, Some((*map_envelope).clone()))?;
+                let mut events = item.map_to_events(&map_ctx, Some((*map_envelope).clone()))?;
+                apply_venue_time(time_field.as_ref(), &v, &mut events, "ws oi_funding");
 
-                // 1) Convert to DB rows (no lock yet)
-                let oi_db_rows: Vec<OpenInterestDBRow> = events
+                // 1) Convert to DB rows (no lock yet); store-on-change drops unchanged values
+                let oi_rows = events
                     .iter()
                     .filter_map(|e| match e {
-                        MarketEvent::OpenInterest(t) => Some(OpenInterestDBRow::from(t.clone())),
+                        MarketEvent::OpenInterest(t) => Some(t.clone()),
                         _ => None,
                     })
                     .collect();
+                let oi_db_rows: Vec<OpenInterestDBRow> =
+                    retain_changed(&change_only_oi, oi_rows, deps.ingest_metrics.as_deref())
+                        .into_iter()
+                        .map(OpenInterestDBRow::from)
+                        .collect();
 
                 // 1) Convert to DB rows (no lock yet)
-                let funding_db_rows: Vec<FundingDBRow> = events
+                let funding_rows = events
                     .iter()
                     .filter_map(|e| match e {
-                        MarketEvent::Funding(t) => Some(FundingDBRow::from(t.clone())),
+                        MarketEvent::Funding(t) => Some(t.clone()),
                         _ => None,
                     })
                     .collect();
-
-                // 2) Publish to redis (no lock)
-                if !knobs.disable_redis_publishes {
+                let funding_db_rows: Vec<FundingDBRow> = retain_changed(
+                    &change_only_funding,
+                    funding_rows,
+                    deps.ingest_metrics.as_deref(),
+                )
+                .into_iter()
+                .map(FundingDBRow::from)
+                .collect();
+
+                // 2) Publish to redis (no lock), unless only committed rows are published
+                let persisted_only = deps.publish_persisted_only() && !knobs.disable_db_writes;
+                if !knobs.disable_redis_publishes && !persisted_only {
                     for e in &events {
                         // Force stream kind based on the event variant (single responsibility)
                         let forced_stream_kind = match e {
@@ -1416,12 +2348,28 @@ pub async fn ws_hyperliquidperp_oifunding(
 
                 // 3) Now lock batch and extend + write
                 if !knobs.disable_db_writes {
-                    let mut guard_oi = batch_oi.lock().await;
-                    let mut guard_funding = batch_funding.lock().await;
-                    guard_oi.rows.extend(oi_db_rows);
-                    guard_funding.rows.extend(funding_db_rows);
-                    deps.db_write((&mut *guard_oi).into()).await?;
-                    deps.db_write((&mut *guard_funding).into()).await?;
+                    let publish = !knobs.disable_redis_publishes;
+                    deps.db_write_and_publish(
+                        exchange.as_str(),
+                        &batch_oi,
+                        oi_db_rows,
+                        false,
+                        publish,
+                    )
+                    .await?;
+                    deps.db_write_and_publish(
+                        exchange.as_str(),
+                        &batch_funding,
+                        funding_db_rows,
+                        false,
+                        publish,
+                    )
+                    .await?;
+                }
+
+                // Liveness: unix time of the last processed event
+                if let Some(m) = deps.ingest_metrics.as_deref() {
+                    m.mark_stream_event(&stream_label);
                 }
 
                 // 4) TEST ESCAPE HATCH
@@ -1445,10 +2393,11 @@ pub async fn ws_hyperliquidperp_oifunding(
         // One call runs the reconnect/breaker loop internally.
         // Cancellation is handled by passing our stream cancel token through.
         if let Err(e) = ws_client
-            .run_stream(
+            .run_stream_rendered(
                 ws_limiters,
                 stream,
                 ctx,
+                rendered,
                 &mut on_event,
                 None, // no test hook in production
                 Some(cancel_for_task.clone()),
@@ -1460,6 +2409,11 @@ pub async fn ws_hyperliquidperp_oifunding(
                 tracing::warn!(error=?e, "ws stream exited with error");
             }
         }
+
+        // Stream stopped: the rows both batches still hold
+        let knobs = *knobs_rx_for_stop.borrow();
+        flush_stream_batch(&deps, exchange, &batch_oi_for_stop, knobs).await;
+        flush_stream_batch(&deps, exchange, &batch_funding_for_stop, knobs).await;
     });
 
     // Build handle + register in state
@@ -1,23 +1,38 @@
 use crate::app::config::AppConfig; // or wherever your config module lives
 use crate::app::config::load_app_config;
-use crate::app::ports::{DbWriter, RedisPublisher};
-use crate::app::ports::{NoopDbWriter, NoopRedisPublisher, RealDbWriter, RealRedisPublisher};
-use crate::db::DbHandler;
+use crate::app::metrics::ConstLabels;
+use crate::app::pause::IngestPause;
+use crate::app::ports::AnyDbBatch;
+use crate::app::ports::{DbWriter, RedisPublisher, publish_persisted};
+use crate::app::ports::{
+    NoopDbWriter, NoopRedisPublisher, PausableDbWriter, PausableRedisPublisher, RealDbWriter,
+    RealRedisPublisher,
+};
+use crate::app::startup::Startup;
+use crate::app::stream_types::ExchangeId;
 use crate::db::config::TimescaleDbConfig;
 use crate::db::health::DBHealthController;
 use crate::db::metrics::DbMetrics;
 use crate::db::pools::DbPools;
+use crate::db::{Batch, DbHandler, TradeDBRow, WriteOutcome};
 use crate::error::AppError;
 use crate::error::AppResult;
 use crate::ingest::config::ExchangeConfigs;
+use crate::ingest::datamap::book::BookCache;
+use crate::ingest::datamap::naming::{
+    apply_exchange_name_check, apply_naming_check, check_exchange_names, check_sink_naming,
+};
 use crate::ingest::http::api_client::ApiClient;
 use crate::ingest::http::rate_limiter::RateLimiterRegistry;
+use crate::ingest::http::resync::ResyncThrottle;
 use crate::ingest::instruments::loader::InstrumentSpecLoader;
 use crate::ingest::metrics::IngestMetrics;
 use crate::ingest::ws::limiter_registry::WsLimiterRegistry;
+use crate::ingest::ws::subscriptions::WsSubscriptions;
 use crate::ingest::ws::ws_client::WsClient;
 use crate::redis::client::RedisClient;
 use crate::redis::config::RedisConfig;
+use crate::redis::fields::PersistedRedisPublish;
 use crate::redis::manager::RedisManager;
 use crate::redis::metrics::RedisMetrics;
 use std::sync::{
@@ -82,6 +97,8 @@ pub struct AppDeps {
     pub ws_limiters: Option<Arc<WsLimiterRegistry>>,
     pub binance_linear_ws: Option<Arc<WsClient>>,
     pub hyperliquid_perp_ws: Option<Arc<WsClient>>,
+    // Live subscriptions of both WS clients (None = `streams.ws_track_subscriptions` off)
+    pub ws_subscriptions: Option<Arc<WsSubscriptions>>,
 
     // DB (optional)
     pub db: Option<DbDeps>,
@@ -93,6 +110,9 @@ pub struct AppDeps {
     pub db_writer: Arc<dyn DbWriter>,
     pub redis_publisher: Arc<dyn RedisPublisher>,
 
+    // Global ingest pause, checked by both ports above
+    pub ingest_pause: Arc<IngestPause>,
+
     // ✅ Runtime gates (4 toggle methods operate on these)
     db_enabled: Arc<AtomicBool>,
     redis_enabled: Arc<AtomicBool>,
@@ -100,6 +120,12 @@ pub struct AppDeps {
     // Instrument loader
     pub instruments_loader: Arc<InstrumentSpecLoader>,
 
+    // Local order books of depth streams (bounded, LRU)
+    pub book_cache: Arc<BookCache>,
+
+    // REST depth snapshot fetches (book seed / re-seed), shared by all streams
+    pub resync_throttle: Arc<ResyncThrottle>,
+
     // Health loop handles
     health_loop_handles: HealthLoopHandles,
 
@@ -111,47 +137,83 @@ pub struct AppDeps {
 
 impl AppDeps {
     pub async fn new(from_env: bool, version: u32) -> AppResult<Self> {
-        // --------------------------------------------------
-        // Load core app config
-        // --------------------------------------------------
-        let app_cfgs = Arc::new(load_app_config(from_env, version)?);
+        Self::new_with_startup(from_env, version, &Startup::new()).await
+    }
 
+    /// `new`, bringing each subsystem up as a `startup` step (see `app::startup`).
+    pub async fn new_with_startup(
+        from_env: bool,
+        version: u32,
+        startup: &Startup,
+    ) -> AppResult<Self> {
         // --------------------------------------------------
-        // Load exchange configs (depends on app config)
+        // Load core app config + exchange configs (depend on app config)
         // --------------------------------------------------
-        let exchange_cfgs = Arc::new(ExchangeConfigs::new(&app_cfgs, from_env, version)?);
+        let (app_cfgs, exchange_cfgs) = startup
+            .step("config", async {
+                let app_cfgs = Arc::new(load_app_config(from_env, version)?);
+                let exchange_cfgs = Arc::new(ExchangeConfigs::new(&app_cfgs, from_env, version)?);
+                Ok((app_cfgs, exchange_cfgs))
+            })
+            .await?;
 
         // --------------------------------------------------
         // Optional ingest metrics
         // --------------------------------------------------
-        let ingest_metrics = Some(Arc::new(IngestMetrics::new()?));
+        let max_stream_labels = match app_cfgs.metrics.max_stream_labels {
+            0 => app_cfgs.limits.max_active_streams as usize,
+            n => n,
+        };
+        let ingest_metrics = Some(Arc::new(
+            IngestMetrics::with_const_labels(&app_cfgs.metric_const_labels())?
+                .with_max_stream_labels(max_stream_labels)
+                .with_lag_sample_every(app_cfgs.metrics.lag_sample_every)
+                .with_ping_rtt_by_exchange(app_cfgs.metrics.ws_ping_rtt_by_exchange),
+        ));
 
         // --------------------------------------------------
         // Redis (optional)
         // --------------------------------------------------
-        let redis: Option<RedisDeps> = if app_cfgs.redis.enabled {
-            let cfg = Arc::new(RedisConfig::load(from_env, version)?);
-            Some(Self::bootstrap_redis(cfg, from_env).await?)
-        } else {
-            None
-        };
+        let redis: Option<RedisDeps> = startup
+            .step("redis", async {
+                if !app_cfgs.redis.enabled {
+                    return Ok(None);
+                }
+                let cfg = Arc::new(RedisConfig::load(from_env, version)?);
+                Ok(Some(
+                    Self::bootstrap_redis(
+                        cfg,
+                        from_env,
+                        &exchange_cfgs,
+                        &app_cfgs.metric_const_labels(),
+                    )
+                    .await?,
+                ))
+            })
+            .await?;
 
         // --------------------------------------------------
         // DB (optional)
         // --------------------------------------------------
-        let db: Option<DbDeps> = if app_cfgs.db.enabled {
-            let cfg = Arc::new(TimescaleDbConfig::load(from_env, version)?);
-
-            if app_cfgs.db.enabled && !app_cfgs.db.verify {
-                return Err(AppError::InvalidConfig(
-                    "db.verify must be true when db.enabled is true".into(),
-                ));
-            }
-
-            Some(Self::bootstrap_db(cfg, app_cfgs.db.verify).await?)
-        } else {
-            None
-        };
+        let db: Option<DbDeps> = startup
+            .step("db", async {
+                if !app_cfgs.db.enabled {
+                    return Ok(None);
+                }
+                let cfg = Arc::new(TimescaleDbConfig::load(from_env, version)?);
+
+                if !app_cfgs.db.verify {
+                    return Err(AppError::InvalidConfig(
+                        "db.verify must be true when db.enabled is true".into(),
+                    ));
+                }
+
+                Ok(Some(
+                    Self::bootstrap_db(cfg, app_cfgs.db.verify, &app_cfgs.metric_const_labels())
+                        .await?,
+                ))
+            })
+            .await?;
 
         // --------------------------------------------------
         // Build runtime gates (start from config)
@@ -177,27 +239,65 @@ impl AppDeps {
             )),
             None => Arc::new(NoopRedisPublisher::new(Arc::clone(&redis_enabled))),
         };
-        // --------------------------------------------------
-        // HTTP clients
-        // --------------------------------------------------
-        let (http_limiters, binance_linear_client, hyperliquid_perp_client) =
-            Self::bootstrap_http(&app_cfgs, &exchange_cfgs, ingest_metrics.clone())?;
 
+        // Global ingest pause wraps both ports
+        let ingest_pause = Arc::new(IngestPause::new(app_cfgs.ingest_pause.mode));
+        let db_writer: Arc<dyn DbWriter> =
+            Arc::new(PausableDbWriter::new(Arc::clone(&ingest_pause), db_writer));
+        let redis_publisher: Arc<dyn RedisPublisher> = Arc::new(PausableRedisPublisher::new(
+            Arc::clone(&ingest_pause),
+            redis_publisher,
+        ));
         // --------------------------------------------------
-        // WS clients
+        // HTTP clients + instrument loader (shares the HTTP limiters)
         // --------------------------------------------------
-        let (ws_limiters, binance_linear_ws, hyperliquid_perp_ws) =
-            Self::bootstrap_ws(&app_cfgs, &exchange_cfgs, ingest_metrics.clone())?;
+        let (http_limiters, binance_linear_client, hyperliquid_perp_client, instruments_loader) =
+            startup
+                .step("http_clients", async {
+                    let (limiters, binance, hyperliquid) =
+                        Self::bootstrap_http(&app_cfgs, &exchange_cfgs, ingest_metrics.clone())?;
+                    let names = [&binance, &hyperliquid]
+                        .into_iter()
+                        .flatten()
+                        .map(|c| c.name);
+                    apply_exchange_name_check(
+                        app_cfgs.exchange_toggles.name_check,
+                        &check_exchange_names(names),
+                    )?;
+                    let excfg = ExchangeConfigs::new(&app_cfgs, from_env, version)?;
+                    let loader = Arc::new(InstrumentSpecLoader::new(
+                        excfg,
+                        limiters.clone(),
+                        ingest_metrics.clone(),
+                    )?);
+                    Ok((limiters, binance, hyperliquid, loader))
+                })
+                .await?;
 
         // --------------------------------------------------
-        // Instrument loader (use the already-built exchange_cfgs!)
+        // WS clients
         // --------------------------------------------------
-        let excfg = ExchangeConfigs::new(&app_cfgs, from_env, version)?;
-        let instruments_loader = Arc::new(InstrumentSpecLoader::new(
-            excfg,
-            http_limiters.clone(),
-            ingest_metrics.clone(),
-        )?);
+        let (ws_limiters, binance_linear_ws, hyperliquid_perp_ws, ws_subscriptions) = startup
+            .step("ws_clients", async {
+                let (limiters, binance, hyperliquid, subscriptions) =
+                    Self::bootstrap_ws(&app_cfgs, &exchange_cfgs, ingest_metrics.clone())?;
+                let names = [&binance, &hyperliquid]
+                    .into_iter()
+                    .flatten()
+                    .map(|c| c.name);
+                apply_exchange_name_check(
+                    app_cfgs.exchange_toggles.name_check,
+                    &check_exchange_names(names),
+                )?;
+                Ok((limiters, binance, hyperliquid, subscriptions))
+            })
+            .await?;
+
+        let book_cache = Arc::new(BookCache::from_config(&app_cfgs.streams));
+
+        let resync_throttle = Arc::new(
+            ResyncThrottle::from_config(&app_cfgs.streams).with_metrics(ingest_metrics.clone()),
+        );
 
         let health_loop_handles = HealthLoopHandles::default();
 
@@ -213,17 +313,21 @@ impl AppDeps {
             ws_limiters,
             binance_linear_ws,
             hyperliquid_perp_ws,
+            ws_subscriptions,
 
             db,
             redis,
 
             db_writer,
             redis_publisher,
+            ingest_pause,
 
             db_enabled,
             redis_enabled,
 
             instruments_loader,
+            book_cache,
+            resync_throttle,
 
             health_loop_handles,
 
@@ -232,20 +336,34 @@ impl AppDeps {
         })
     }
 
-    pub async fn bootstrap_redis(cfg: Arc<RedisConfig>, from_env: bool) -> AppResult<RedisDeps> {
+    pub async fn bootstrap_redis(
+        cfg: Arc<RedisConfig>,
+        from_env: bool,
+        exchange_cfgs: &ExchangeConfigs,
+        const_labels: &ConstLabels,
+    ) -> AppResult<RedisDeps> {
         // 1) Metrics
-        let metrics = Arc::new(RedisMetrics::new()?);
+        let metrics = Arc::new(RedisMetrics::with_const_labels(const_labels)?);
 
         // 2) Connect client
         let client = Arc::new(RedisClient::connect_from_config(&cfg, from_env).await?);
 
         // 3) Manager (prefer Arc metrics if your manager can accept it;
         //    keeping your existing signature here).
-        let manager = Arc::new(RedisManager::new(
-            (*cfg).clone(),
-            Arc::clone(&client),
-            (*metrics).clone(),
-        )?);
+        let mut manager =
+            RedisManager::new((*cfg).clone(), Arc::clone(&client), (*metrics).clone())?
+                .with_symbol_cases(exchange_cfgs.symbol_cases());
+        apply_naming_check(
+            cfg.streams.naming_check,
+            &check_sink_naming(exchange_cfgs.symbol_cases().into_iter().map(|(ex, _)| ex)),
+        )?;
+
+        // 4) Optional dedicated health-probe connection
+        if cfg.connection.dedicated_probe_connection {
+            let probe = Arc::new(RedisClient::connect_from_config(&cfg, from_env).await?);
+            manager = manager.with_probe_io(probe);
+        }
+        let manager = Arc::new(manager);
 
         Ok(RedisDeps {
             cfg,
@@ -255,19 +373,26 @@ impl AppDeps {
         })
     }
 
-    pub async fn bootstrap_db(cfg: Arc<TimescaleDbConfig>, verify: bool) -> AppResult<DbDeps> {
+    pub async fn bootstrap_db(
+        cfg: Arc<TimescaleDbConfig>,
+        verify: bool,
+        const_labels: &ConstLabels,
+    ) -> AppResult<DbDeps> {
         // 1) Build pools
         let pools = Arc::new(DbPools::new((*cfg).clone(), verify).await?);
 
         // 2) Build metrics
-        let metrics = Arc::new(DbMetrics::new()?);
+        let metrics = Arc::new(DbMetrics::with_const_labels(const_labels)?);
 
         // 3) Build handler
-        let handler = Arc::new(DbHandler::new(
-            Arc::clone(&pools),
-            cfg.writer.clone(),
-            Arc::clone(&metrics),
-        ));
+        let handler = Arc::new(
+            DbHandler::new(Arc::clone(&pools), cfg.writer.clone(), Arc::clone(&metrics))
+                .with_min_healthy_shards(cfg.health.min_healthy_shards),
+        );
+        // Deployments older than the trades conflict index write plain INSERTs until migrated
+        if verify {
+            handler.check_conflict_indexes::<TradeDBRow>("trades").await;
+        }
 
         let health_configs = cfg.clone().as_ref().health.clone();
         let health = Arc::new(DBHealthController::new(
@@ -313,12 +438,16 @@ impl AppDeps {
                 .as_ref()
                 .ok_or_else(|| AppError::MissingConfig("binance_linear exchange config"))?;
 
-            Some(Arc::new(ApiClient::new(
-                "binance_linear",
-                binance_cfg.api_base_url.clone(),
-                http_limiters.clone(),
-                ingest_metrics.clone(),
-            )))
+            Some(Arc::new(
+                ApiClient::new(
+                    ExchangeId::BinanceLinear.as_str(),
+                    binance_cfg.api_base_url.clone(),
+                    http_limiters.clone(),
+                    ingest_metrics.clone(),
+                )
+                .with_max_response_bytes(binance_cfg.api_max_response_bytes)
+                .with_default_headers(&binance_cfg.resolved_headers)?,
+            ))
         } else {
             None
         };
@@ -329,12 +458,16 @@ impl AppDeps {
                 .as_ref()
                 .ok_or_else(|| AppError::MissingConfig("hyperliquid_perp exchange config"))?;
 
-            Some(Arc::new(ApiClient::new(
-                "hyperliquid_perp",
-                hyper_cfg.api_base_url.clone(),
-                http_limiters.clone(),
-                ingest_metrics.clone(),
-            )))
+            Some(Arc::new(
+                ApiClient::new(
+                    ExchangeId::HyperliquidPerp.as_str(),
+                    hyper_cfg.api_base_url.clone(),
+                    http_limiters.clone(),
+                    ingest_metrics.clone(),
+                )
+                .with_max_response_bytes(hyper_cfg.api_max_response_bytes)
+                .with_default_headers(&hyper_cfg.resolved_headers)?,
+            ))
         } else {
             None
         };
@@ -354,6 +487,7 @@ impl AppDeps {
         Option<Arc<WsLimiterRegistry>>,
         Option<Arc<WsClient>>,
         Option<Arc<WsClient>>,
+        Option<Arc<WsSubscriptions>>,
     )> {
         // Create limiters only if any WS exchange is enabled
         let any_ws =
@@ -369,18 +503,26 @@ impl AppDeps {
             None
         };
 
+        let subscriptions = app_cfg
+            .streams
+            .ws_track_subscriptions
+            .then(|| Arc::new(WsSubscriptions::new()));
+
         let binance_linear_ws = if app_cfg.exchange_toggles.binance_linear {
             let binance_cfg = exchange_cfgs
                 .binance_linear
                 .as_ref()
                 .ok_or_else(|| AppError::MissingConfig("binance_linear exchange config"))?;
 
-            Some(Arc::new(WsClient::new(
-                "binance_linear",
-                binance_cfg.clone(),
-                ingest_metrics.clone(),
-                Some(app_cfg),
-            )))
+            Some(Arc::new(
+                WsClient::new(
+                    ExchangeId::BinanceLinear.as_str(),
+                    binance_cfg.clone(),
+                    ingest_metrics.clone(),
+                    Some(app_cfg),
+                )
+                .with_subscriptions(subscriptions.clone()),
+            ))
         } else {
             None
         };
@@ -391,17 +533,25 @@ impl AppDeps {
                 .as_ref()
                 .ok_or_else(|| AppError::MissingConfig("hyperliquid_perp exchange config"))?;
 
-            Some(Arc::new(WsClient::new(
-                "hyperliquid_perp",
-                hyper_cfg.clone(),
-                ingest_metrics.clone(),
-                Some(app_cfg),
-            )))
+            Some(Arc::new(
+                WsClient::new(
+                    ExchangeId::HyperliquidPerp.as_str(),
+                    hyper_cfg.clone(),
+                    ingest_metrics.clone(),
+                    Some(app_cfg),
+                )
+                .with_subscriptions(subscriptions.clone()),
+            ))
         } else {
             None
         };
 
-        Ok((ws_limiters, binance_linear_ws, hyperliquid_perp_ws))
+        Ok((
+            ws_limiters,
+            binance_linear_ws,
+            hyperliquid_perp_ws,
+            subscriptions,
+        ))
     }
 }
 
@@ -552,9 +702,59 @@ impl AppDeps {
             .await
     }
 
-    pub async fn db_write(&self, batch: crate::app::ports::AnyDbBatch<'_>) -> AppResult<()> {
+    /// True when Redis only gets rows the DB committed (`writer.publish_persisted_only`).
+    /// Without a DB nothing is ever committed, so events are published on receipt.
+    pub fn publish_persisted_only(&self) -> bool {
+        self.db
+            .as_ref()
+            .is_some_and(|db| db.cfg.writer.publish_persisted_only)
+    }
+
+    /// Add `rows` to `batch` and write it (`flush`: whatever it holds, see `db_flush`), then
+    /// publish the rows that write committed (`Batch::take_persisted`, empty unless
+    /// `publish_persisted_only`). `publish` false drops them (stream publishes off). The batch
+    /// lock is released before publishing.
+    pub async fn db_write_and_publish<T>(
+        &self,
+        exchange: &str,
+        batch: &tokio::sync::Mutex<Batch<T>>,
+        rows: Vec<T>,
+        flush: bool,
+        publish: bool,
+    ) -> AppResult<WriteOutcome>
+    where
+        T: PersistedRedisPublish + Send + Sync,
+        for<'a> AnyDbBatch<'a>: From<&'a mut Batch<T>>,
+    {
+        let mut guard = batch.lock().await;
+        guard.extend(rows);
+        let outcome = if flush {
+            self.db_flush((&mut *guard).into()).await?
+        } else {
+            self.db_write((&mut *guard).into()).await?
+        };
+        let persisted = guard.take_persisted();
+        drop(guard);
+        if publish {
+            publish_persisted(self.redis_publisher.as_ref(), exchange, &persisted).await?;
+        }
+        Ok(outcome)
+    }
+
+    pub async fn db_write(
+        &self,
+        batch: crate::app::ports::AnyDbBatch<'_>,
+    ) -> AppResult<crate::db::WriteOutcome> {
         self.db_writer.write_batch(batch).await
     }
+
+    /// `db_write` without waiting for the batch size / interval (drain on stream stop).
+    pub async fn db_flush(
+        &self,
+        batch: crate::app::ports::AnyDbBatch<'_>,
+    ) -> AppResult<crate::db::WriteOutcome> {
+        self.db_writer.flush_batch(batch).await
+    }
 }
 // -------------------------
 // 4 toggle methods (runtime)
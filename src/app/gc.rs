@@ -0,0 +1,276 @@
+//! app/gc.rs
+//!
+//! Dead-stream GC.
+//!
+//! Cross-references the enabled rows of `mini_fintickstreams.stream_registry` against the
+//! current instrument registry and picks the streams whose instrument has been delisted for
+//! longer than `stream_gc.grace_sec`. The caller disables (never deletes) those registry rows
+//! and stops the running stream, so a restart no longer tries to revive them.
+//!
+//! An instrument counts as delisted when:
+//! - it is no longer in the instrument registry (loaders skip non-TRADING / delisted markets), or
+//! - its `delivery_date_ms` is in the past (expired futures).
+//!
+//! Safety rules:
+//! - Streams whose instrument is live are never selected (and their grace timer is reset).
+//! - Exchanges with ZERO instruments in the registry are skipped entirely: an empty snapshot
+//!   means "loader failed / exchange disabled", not "everything was delisted".
+//! - "Missing" instruments have no delisting timestamp, so their grace period starts when the
+//!   GC first notices them (in-memory; a restart restarts the grace period).
+
+use crate::app::config::StreamGcConfig;
+use crate::app::control::StartStreamParams;
+use crate::app::stream_types::StreamId;
+use crate::ingest::instruments::registry::InstrumentRegistry;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DelistReason {
+    /// Instrument no longer present in the instrument registry.
+    Missing,
+    /// Instrument delivery date is in the past.
+    Expired,
+}
+
+impl DelistReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DelistReason::Missing => "missing",
+            DelistReason::Expired => "expired",
+        }
+    }
+}
+
+/// A registry stream selected for disabling.
+#[derive(Debug, Clone)]
+pub struct DeadStream {
+    pub id: StreamId,
+    pub params: StartStreamParams,
+    pub reason: DelistReason,
+    pub dead_since: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+pub struct DeadStreamGc {
+    grace: Duration,
+    // stream_id -> first time the GC saw its instrument delisted
+    first_dead: HashMap<String, DateTime<Utc>>,
+}
+
+impl DeadStreamGc {
+    pub fn new(grace_sec: u64) -> Self {
+        Self {
+            grace: Duration::seconds(grace_sec as i64),
+            first_dead: HashMap::new(),
+        }
+    }
+
+    pub fn from_config(cfg: &StreamGcConfig) -> Self {
+        Self::new(cfg.grace_sec)
+    }
+
+    /// Number of streams currently inside their grace period (or past it, not yet disabled).
+    #[inline]
+    pub fn tracked_len(&self) -> usize {
+        self.first_dead.len()
+    }
+
+    /// Why `p`'s instrument is considered delisted, if it is.
+    pub fn delist_reason(
+        registry: &InstrumentRegistry,
+        p: &StartStreamParams,
+        now: DateTime<Utc>,
+    ) -> Option<DelistReason> {
+        match registry.get(p.exchange.as_str(), &p.symbol) {
+            None => Some(DelistReason::Missing),
+            Some(spec) => match spec.delivery_date_ms {
+                Some(ms) if (ms as i64) <= now.timestamp_millis() => Some(DelistReason::Expired),
+                _ => None,
+            },
+        }
+    }
+
+    /// Pick the streams whose instrument has been delisted for longer than the grace period.
+    pub fn select(
+        &mut self,
+        registry: &InstrumentRegistry,
+        streams: &[StartStreamParams],
+        now: DateTime<Utc>,
+    ) -> Vec<DeadStream> {
+        let mut out = Vec::new();
+        let mut current: HashSet<String> = HashSet::with_capacity(streams.len());
+
+        for p in streams {
+            let id = StreamId::new(p.exchange.as_str(), &p.symbol, p.kind, p.transport);
+            current.insert(id.0.clone());
+
+            if registry.by_exchange(p.exchange.as_str()).next().is_none() {
+                // no snapshot for this exchange -> we know nothing, touch nothing
+                continue;
+            }
+
+            let Some(reason) = Self::delist_reason(registry, p, now) else {
+                self.first_dead.remove(&id.0);
+                continue;
+            };
+
+            let mut dead_since = *self.first_dead.entry(id.0.clone()).or_insert(now);
+            if reason == DelistReason::Expired
+                && let Some(ms) = registry
+                    .get(p.exchange.as_str(), &p.symbol)
+                    .and_then(|s| s.delivery_date_ms)
+                && let Some(delivered) = DateTime::<Utc>::from_timestamp_millis(ms as i64)
+            {
+                dead_since = dead_since.min(delivered);
+            }
+
+            if now - dead_since >= self.grace {
+                out.push(DeadStream {
+                    id,
+                    params: p.clone(),
+                    reason,
+                    dead_since,
+                });
+            }
+        }
+
+        // streams that left the enabled set (removed / disabled elsewhere) stop being tracked
+        self.first_dead.retain(|id, _| current.contains(id));
+
+        out
+    }
+
+    /// Drop the grace timer of a stream once it has been disabled.
+    pub fn forget(&mut self, id: &StreamId) {
+        self.first_dead.remove(&id.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::stream_types::{ExchangeId, StreamKind, StreamTransport};
+    use crate::ingest::instruments::spec::{InstrumentKind, InstrumentSpec, QtyUnit};
+
+    const T0_MS: i64 = 1_700_000_000_000;
+
+    fn at(sec: i64) -> DateTime<Utc> {
+        DateTime::<Utc>::from_timestamp_millis(T0_MS + sec * 1000).unwrap()
+    }
+
+    fn perp(symbol: &str) -> InstrumentSpec {
+        InstrumentSpec::new(
+            "binance_linear",
+            symbol,
+            InstrumentKind::PerpLinear,
+            QtyUnit::Base,
+            None,
+            None,
+            None,
+        )
+        .unwrap()
+    }
+
+    fn future(symbol: &str, delivery_ms: i64) -> InstrumentSpec {
+        InstrumentSpec::new(
+            "binance_linear",
+            symbol,
+            InstrumentKind::FutureLinear,
+            QtyUnit::Base,
+            None,
+            Some(delivery_ms as u64),
+            None,
+        )
+        .unwrap()
+    }
+
+    fn stream(exchange: ExchangeId, symbol: &str) -> StartStreamParams {
+        StartStreamParams {
+            exchange,
+            transport: StreamTransport::Ws,
+            kind: StreamKind::Trades,
+            symbol: symbol.into(),
+        }
+    }
+
+    fn symbols(dead: &[DeadStream]) -> Vec<&str> {
+        dead.iter().map(|d| d.params.symbol.as_str()).collect()
+    }
+
+    #[test]
+    fn delisted_instrument_stream_is_disabled_after_grace() {
+        let mut gc = DeadStreamGc::new(60);
+        let streams = vec![
+            stream(ExchangeId::BinanceLinear, "BTCUSDT"),
+            stream(ExchangeId::BinanceLinear, "DELISTEDUSDT"),
+        ];
+
+        // Both live
+        let reg = InstrumentRegistry::build(vec![perp("BTCUSDT"), perp("DELISTEDUSDT")]).unwrap();
+        assert!(gc.select(&reg, &streams, at(0)).is_empty());
+        assert_eq!(gc.tracked_len(), 0);
+
+        // DELISTEDUSDT dropped from the exchange snapshot: grace period starts
+        let reg = InstrumentRegistry::build(vec![perp("BTCUSDT")]).unwrap();
+        assert!(gc.select(&reg, &streams, at(10)).is_empty(), "inside grace");
+        assert_eq!(gc.tracked_len(), 1);
+
+        // Past grace -> auto-disabled; the live instrument is never touched
+        let dead = gc.select(&reg, &streams, at(70));
+        assert_eq!(symbols(&dead), vec!["DELISTEDUSDT"]);
+        assert_eq!(dead[0].reason, DelistReason::Missing);
+        assert_eq!(dead[0].dead_since, at(10));
+
+        gc.forget(&dead[0].id);
+        assert_eq!(gc.tracked_len(), 0);
+    }
+
+    #[test]
+    fn relisted_instrument_resets_grace() {
+        let mut gc = DeadStreamGc::new(60);
+        let streams = vec![stream(ExchangeId::BinanceLinear, "ETHUSDT")];
+
+        let gone = InstrumentRegistry::build(vec![perp("BTCUSDT")]).unwrap();
+        let back = InstrumentRegistry::build(vec![perp("BTCUSDT"), perp("ETHUSDT")]).unwrap();
+
+        assert!(gc.select(&gone, &streams, at(0)).is_empty());
+        assert!(gc.select(&back, &streams, at(30)).is_empty());
+        assert!(
+            gc.select(&gone, &streams, at(80)).is_empty(),
+            "timer restarted"
+        );
+        assert_eq!(
+            symbols(&gc.select(&gone, &streams, at(140))),
+            vec!["ETHUSDT"]
+        );
+    }
+
+    #[test]
+    fn expired_future_uses_delivery_date() {
+        let mut gc = DeadStreamGc::new(60);
+        let streams = vec![stream(ExchangeId::BinanceLinear, "BTCUSDT_240329")];
+        let reg = InstrumentRegistry::build(vec![future("BTCUSDT_240329", T0_MS)]).unwrap();
+
+        assert!(
+            gc.select(&reg, &streams, at(-1)).is_empty(),
+            "not delivered yet"
+        );
+
+        // First noticed well after delivery: grace counts from the delivery date
+        let dead = gc.select(&reg, &streams, at(120));
+        assert_eq!(symbols(&dead), vec!["BTCUSDT_240329"]);
+        assert_eq!(dead[0].reason, DelistReason::Expired);
+        assert_eq!(dead[0].dead_since, at(0));
+    }
+
+    #[test]
+    fn exchange_without_snapshot_is_never_touched() {
+        let mut gc = DeadStreamGc::new(0);
+        let streams = vec![stream(ExchangeId::HyperliquidPerp, "BTC")];
+        let reg = InstrumentRegistry::build(vec![perp("BTCUSDT")]).unwrap();
+
+        assert!(gc.select(&reg, &streams, at(1_000)).is_empty());
+        assert_eq!(gc.tracked_len(), 0);
+    }
+}
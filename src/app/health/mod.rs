@@ -1,10 +1,12 @@
 pub mod eval;
 pub mod guard;
 pub mod sampler;
+pub mod stall;
 mod test;
 pub mod types;
 
 pub use eval::*;
 pub use guard::*;
 pub use sampler::*;
+pub use stall::*;
 pub use types::*;
@@ -0,0 +1,286 @@
+//! app/health/stall.rs
+//!
+//! Stream stall watchdog: a stream is stalled when it has sent nothing for
+//! `health.stall.stall_after_sec` while its instrument's market is open.
+//!
+//! - Closed sessions (see `TradingSchedule`) are never stalls; the stream keeps running (and
+//!   reconnecting) through the close so the first messages after the open are caught.
+//! - Idle time is counted from the latest of: last event, session open, first time the
+//!   watchdog saw the stream. A market that just opened gets a full `stall_after` to speak.
+//! - The threshold is per stream kind (`stall_after_sec_by_kind`); event-driven kinds can be
+//!   left out (0). Sessions come from `health.stall.sessions`, else the instrument registry.
+
+use crate::app::config::StallConfig;
+use crate::app::stream_types::StreamKind;
+use crate::error::{AppError, AppResult};
+use crate::ingest::instruments::schedule::{Session, TradingSchedule};
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Liveness {
+    /// Data seen recently enough.
+    Live,
+    /// Market open and nothing for `idle_secs`.
+    Stalled { idle_secs: i64 },
+    /// Market closed: no data expected.
+    Closed,
+    /// No verdict: stall detection is off for the stream's kind, or there is no liveness
+    /// data for it (metrics off, or its gauge dropped over the label cap).
+    Unknown,
+}
+
+impl Liveness {
+    pub fn is_stalled(self) -> bool {
+        matches!(self, Liveness::Stalled { .. })
+    }
+}
+
+/// Liveness of one stream at `now`.
+pub fn evaluate_liveness(
+    schedule: &TradingSchedule,
+    last_event: Option<DateTime<Utc>>,
+    watched_since: DateTime<Utc>,
+    now: DateTime<Utc>,
+    stall_after: Duration,
+) -> Liveness {
+    if !schedule.is_open(now) {
+        return Liveness::Closed;
+    }
+
+    let since = [last_event, schedule.session_start(now)]
+        .into_iter()
+        .flatten()
+        .fold(watched_since, DateTime::max);
+
+    let idle = now - since;
+    if idle > stall_after {
+        Liveness::Stalled {
+            idle_secs: idle.num_seconds(),
+        }
+    } else {
+        Liveness::Live
+    }
+}
+
+/// Per-stream state kept between watchdog passes.
+#[derive(Debug)]
+pub struct StallWatchdog {
+    stall_after: Duration,
+    // zero = never stalled
+    stall_after_by_kind: HashMap<StreamKind, Duration>,
+    // `exchange` or `exchange:SYMBOL` -> configured sessions
+    schedules: HashMap<String, TradingSchedule>,
+    // stream -> first pass that saw it (idle time never starts before this)
+    first_seen: HashMap<String, DateTime<Utc>>,
+}
+
+impl StallWatchdog {
+    pub fn new(stall_after_sec: u64) -> Self {
+        Self {
+            stall_after: Duration::seconds(stall_after_sec as i64),
+            stall_after_by_kind: HashMap::new(),
+            schedules: HashMap::new(),
+            first_seen: HashMap::new(),
+        }
+    }
+
+    /// Watchdog of `health.stall` (kinds and sessions are checked at config load).
+    pub fn from_config(cfg: &StallConfig) -> AppResult<Self> {
+        let mut wd = Self::new(cfg.stall_after_sec);
+        for (kind, secs) in &cfg.stall_after_sec_by_kind {
+            let kind = kind.parse::<StreamKind>().map_err(|e| {
+                AppError::InvalidConfig(format!("health.stall.stall_after_sec_by_kind: {e}"))
+            })?;
+            wd.stall_after_by_kind
+                .insert(kind, Duration::seconds(*secs as i64));
+        }
+        for (key, sessions) in &cfg.sessions {
+            if sessions.is_empty() {
+                return Err(AppError::InvalidConfig(format!(
+                    "health.stall.sessions.{key}: no session (omit it for 24/7)"
+                )));
+            }
+            let sessions = sessions
+                .iter()
+                .map(|s| s.parse::<Session>())
+                .collect::<AppResult<Vec<_>>>()?;
+            wd.schedules
+                .insert(key.clone(), TradingSchedule::Weekly(sessions));
+        }
+        Ok(wd)
+    }
+
+    /// Configured schedule of `symbol` on `exchange` (symbol entry first), else `fallback`.
+    pub fn schedule<'a>(
+        &'a self,
+        exchange: &str,
+        symbol: &str,
+        fallback: &'a TradingSchedule,
+    ) -> &'a TradingSchedule {
+        self.schedules
+            .get(&format!("{exchange}:{symbol}"))
+            .or_else(|| self.schedules.get(exchange))
+            .unwrap_or(fallback)
+    }
+
+    /// Evaluate `stream` (of `kind`) at `now`.
+    pub fn check(
+        &mut self,
+        stream: &str,
+        kind: StreamKind,
+        schedule: &TradingSchedule,
+        last_event: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+    ) -> Liveness {
+        let stall_after = self
+            .stall_after_by_kind
+            .get(&kind)
+            .copied()
+            .unwrap_or(self.stall_after);
+        if stall_after.is_zero() {
+            return Liveness::Unknown;
+        }
+        let since = *self.first_seen.entry(stream.to_string()).or_insert(now);
+        evaluate_liveness(schedule, last_event, since, now, stall_after)
+    }
+
+    /// Forget streams not in `live` (stopped/removed since the last pass).
+    pub fn retain(&mut self, live: &[String]) {
+        self.first_seen.retain(|k, _| live.contains(k));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ingest::instruments::schedule::Session;
+    use StreamKind::{Liquidations, Trades};
+    use chrono::{TimeZone, Weekday};
+
+    #[test]
+    fn closed_session_is_not_a_stall() {
+        // Mon-Fri 14:30-21:00 UTC
+        let schedule = TradingSchedule::Weekly(
+            [
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+                Weekday::Fri,
+            ]
+            .into_iter()
+            .map(|d| Session::weekly((d, 14, 30), (d, 21, 0)))
+            .collect(),
+        );
+        let mut wd = StallWatchdog::new(120);
+        let stream = "cme:ESH4:Ws:Trades";
+
+        // Friday close, last trade at 20:59:59; the whole weekend stays silent
+        let last = Utc.with_ymd_and_hms(2024, 1, 5, 20, 59, 59).unwrap();
+        let fri_close = Utc.with_ymd_and_hms(2024, 1, 5, 21, 0, 0).unwrap();
+        assert_eq!(
+            wd.check(stream, Trades, &schedule, Some(last), fri_close),
+            Liveness::Closed
+        );
+        let sunday = Utc.with_ymd_and_hms(2024, 1, 7, 12, 0, 0).unwrap();
+        assert_eq!(
+            wd.check(stream, Trades, &schedule, Some(last), sunday),
+            Liveness::Closed
+        );
+
+        // Monday open: grace from the open, not from Friday's last trade
+        let mon_open = Utc.with_ymd_and_hms(2024, 1, 8, 14, 31, 0).unwrap();
+        assert_eq!(
+            wd.check(stream, Trades, &schedule, Some(last), mon_open),
+            Liveness::Live
+        );
+
+        // Still silent well after the open: a real stall
+        let later = Utc.with_ymd_and_hms(2024, 1, 8, 14, 40, 0).unwrap();
+        assert_eq!(
+            wd.check(stream, Trades, &schedule, Some(last), later),
+            Liveness::Stalled { idle_secs: 600 }
+        );
+
+        // The same silence on a 24/7 instrument is a stall at any hour
+        let crypto = "binance_linear:BTCUSDT:Ws:Trades";
+        let mut wd = StallWatchdog::new(120);
+        wd.check(
+            crypto,
+            Trades,
+            &TradingSchedule::AlwaysOpen,
+            Some(last),
+            last,
+        );
+        assert!(
+            wd.check(
+                crypto,
+                Trades,
+                &TradingSchedule::AlwaysOpen,
+                Some(last),
+                sunday
+            )
+            .is_stalled()
+        );
+    }
+
+    #[test]
+    fn kinds_and_sessions_come_from_config() {
+        let cfg = StallConfig {
+            stall_after_sec_by_kind: [("Liquidations".to_string(), 0)].into(),
+            sessions: [
+                ("cme".to_string(), vec!["Sun 23:00-Fri 22:00".to_string()]),
+                (
+                    "cme:BTC".to_string(),
+                    vec!["Sat 00:00-Sun 00:00".to_string()],
+                ),
+            ]
+            .into(),
+            ..StallConfig::default()
+        };
+        let mut wd = StallWatchdog::from_config(&cfg).unwrap();
+        let t0 = Utc.with_ymd_and_hms(2024, 1, 10, 12, 0, 0).unwrap();
+        let later = t0 + Duration::hours(6);
+
+        // Hours without a liquidation are not a stall; the same silence on trades is
+        wd.check("liq", Liquidations, &TradingSchedule::AlwaysOpen, None, t0);
+        assert_eq!(
+            wd.check(
+                "liq",
+                Liquidations,
+                &TradingSchedule::AlwaysOpen,
+                None,
+                later
+            ),
+            Liveness::Unknown
+        );
+        wd.check("t", Trades, &TradingSchedule::AlwaysOpen, None, t0);
+        assert!(
+            wd.check("t", Trades, &TradingSchedule::AlwaysOpen, None, later)
+                .is_stalled()
+        );
+
+        // Configured sessions override the registry's; a symbol entry beats its exchange's
+        let registry = TradingSchedule::AlwaysOpen;
+        let sat = Utc.with_ymd_and_hms(2024, 1, 6, 12, 0, 0).unwrap();
+        assert!(!wd.schedule("cme", "ES", &registry).is_open(sat));
+        assert!(wd.schedule("cme", "BTC", &registry).is_open(sat));
+        assert!(
+            wd.schedule("binance_linear", "BTCUSDT", &registry)
+                .is_always_open()
+        );
+
+        // Unknown kinds and unparsable sessions are config errors
+        let bad_kind = StallConfig {
+            stall_after_sec_by_kind: [("Liquidation".to_string(), 0)].into(),
+            ..StallConfig::default()
+        };
+        assert!(StallWatchdog::from_config(&bad_kind).is_err());
+        let bad_session = StallConfig {
+            sessions: [("cme".to_string(), vec!["weekdays".to_string()])].into(),
+            ..StallConfig::default()
+        };
+        assert!(StallWatchdog::from_config(&bad_session).is_err());
+    }
+}
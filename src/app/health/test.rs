@@ -8,12 +8,37 @@ mod tests {
     use crate::error::AppResult;
 
     use crate::app::health::eval::{RuntimeEvalState, evaluate_runtime};
-    use crate::app::health::types::{HealthState, RuntimeSnapshot};
+    use crate::app::health::types::{AppHealth, HealthState, OverallHealth, RuntimeSnapshot};
 
     fn load_cfg() -> Arc<crate::app::config::AppConfig> {
         Arc::new(load_app_config(false, 0).expect("failed to load app config"))
     }
 
+    #[test]
+    fn app_health_db_is_source_of_truth_redis_optional() {
+        use OverallHealth::*;
+        // (db_ready, redis_enabled, redis_required) -> overall
+        let cases = [
+            ((true, true, false), Healthy),
+            ((true, false, false), Degraded),
+            ((false, true, false), Unhealthy),
+            ((false, false, false), Unhealthy),
+            ((true, true, true), Healthy),
+            ((true, false, true), Unhealthy),
+            ((false, true, true), Unhealthy),
+            ((false, false, true), Unhealthy),
+        ];
+        for ((db_ready, redis_enabled, redis_required), want) in cases {
+            let h = AppHealth::evaluate(db_ready, redis_enabled, redis_required);
+            assert_eq!(
+                h.overall, want,
+                "db_ready={db_ready} redis_enabled={redis_enabled} redis_required={redis_required}"
+            );
+            assert_eq!((h.db_ready, h.redis_enabled), (db_ready, redis_enabled));
+            assert_eq!(h.is_unhealthy(), want == Unhealthy);
+        }
+    }
+
     #[test]
     fn runtime_health_goes_red_on_high_rss() -> AppResult<()> {
         let cfg = load_cfg();
@@ -1,5 +1,7 @@
 // src/app/health/types.rs
 
+use serde::Serialize;
+
 /// Health state for the runtime guard.
 /// GREEN = normal operation
 /// RED   = protect the process (deny admissions, etc.)
@@ -94,3 +96,47 @@ impl RuntimeDecision {
         }
     }
 }
+
+/// Composite pipeline health (`GET /health`, `/readyz`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverallHealth {
+    Healthy,
+    /// Writing to the DB, but without Redis acceleration.
+    Degraded,
+    Unhealthy,
+}
+
+/// DB shard health + Redis gate, combined into one status.
+///
+/// The DB is the source of truth: not write-ready is always unhealthy. Redis is optional
+/// acceleration, so it being disabled only degrades, unless `health.redis_required`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct AppHealth {
+    /// Enough DB shards healthy to write (true when the DB is disabled by config).
+    pub db_ready: bool,
+    /// Redis gate open and the manager can publish.
+    pub redis_enabled: bool,
+    pub overall: OverallHealth,
+}
+
+impl AppHealth {
+    pub fn evaluate(db_ready: bool, redis_enabled: bool, redis_required: bool) -> Self {
+        let overall = match (db_ready, redis_enabled) {
+            (false, _) => OverallHealth::Unhealthy,
+            (true, true) => OverallHealth::Healthy,
+            (true, false) if redis_required => OverallHealth::Unhealthy,
+            (true, false) => OverallHealth::Degraded,
+        };
+        Self {
+            db_ready,
+            redis_enabled,
+            overall,
+        }
+    }
+
+    #[inline]
+    pub fn is_unhealthy(&self) -> bool {
+        self.overall == OverallHealth::Unhealthy
+    }
+}
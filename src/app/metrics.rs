@@ -1,8 +1,32 @@
 use crate::error::{AppError, AppResult};
+use std::collections::BTreeMap;
 
 #[cfg(feature = "metrics")]
 use prometheus::{IntCounter, IntGauge, Opts, Registry};
 
+/// Labels put on every series of a registry (see `AppConfig::metric_const_labels`), so the
+/// series of many instances stay apart once scraped into one Prometheus.
+pub type ConstLabels = BTreeMap<String, String>;
+
+/// Label names the registries set per series, plus Prometheus' own histogram / summary ones.
+/// A const label under one of these names would clash with them.
+pub const SERIES_LABEL_NAMES: &[&str] = &[
+    "exchange", "stream", "kind", "group", "reason", "le", "quantile",
+];
+
+/// Registry that adds `const_labels` to every series it gathers.
+#[cfg(feature = "metrics")]
+pub fn registry_with_const_labels(const_labels: &ConstLabels) -> AppResult<Registry> {
+    if const_labels.is_empty() {
+        return Ok(Registry::new());
+    }
+    let labels = const_labels
+        .iter()
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect();
+    Ok(Registry::new_custom(None, Some(labels))?)
+}
+
 /// App-level / orchestrator metrics.
 ///
 /// Scope:
@@ -62,6 +86,15 @@ pub struct AppMetrics {
     pub streams_active: IntGauge,
     #[cfg(feature = "metrics")]
     pub streams_limit: IntGauge,
+    // 1 = instruments registry is past max age and could not be refreshed
+    #[cfg(feature = "metrics")]
+    pub registry_stale: IntGauge,
+    // Instruments whose declared precision the global scales cannot represent
+    #[cfg(feature = "metrics")]
+    pub scale_mismatch: IntGauge,
+    // 1 = global ingest pause on (no DB writes / Redis publishes)
+    #[cfg(feature = "metrics")]
+    pub ingest_paused: IntGauge,
 
     // --------------------------------------------------
     // Control-plane operations
@@ -94,19 +127,34 @@ pub struct AppMetrics {
 }
 
 impl AppMetrics {
-    pub fn new(app_id: &str, env: &str, config_version: u32, max_streams: u32) -> AppResult<Self> {
+    /// `const_labels` go on every series; `app_info` leaves out its own labels they already set.
+    pub fn new(
+        app_id: &str,
+        env: &str,
+        config_version: u32,
+        max_streams: u32,
+        const_labels: &ConstLabels,
+    ) -> AppResult<Self> {
         #[cfg(feature = "metrics")]
         {
-            let registry = Registry::new();
+            let registry = registry_with_const_labels(const_labels)?;
 
             // --------------------------------------------------
             // app_info (const labels)
             // --------------------------------------------------
+            let info_labels = [
+                ("app_id", app_id.to_string()),
+                ("env", env.to_string()),
+                ("config_version", config_version.to_string()),
+            ];
             let app_info = IntGauge::with_opts(
-                Opts::new("app_info", "Static app identity info")
-                    .const_label("app_id", app_id)
-                    .const_label("env", env)
-                    .const_label("config_version", config_version.to_string()),
+                info_labels
+                    .into_iter()
+                    .filter(|(k, _)| !const_labels.contains_key(*k))
+                    .fold(
+                        Opts::new("app_info", "Static app identity info"),
+                        |opts, (k, v)| opts.const_label(k, v),
+                    ),
             )?;
             app_info.set(1);
             registry.register(Box::new(app_info))?;
@@ -180,6 +228,21 @@ impl AppMetrics {
             ))?;
             streams_limit.set(max_streams as i64);
 
+            let registry_stale = IntGauge::with_opts(Opts::new(
+                "instruments_registry_stale",
+                "Instruments registry older than max age and refresh failed (0/1)",
+            ))?;
+
+            let scale_mismatch = IntGauge::with_opts(Opts::new(
+                "instruments_scale_mismatch",
+                "Instruments declaring more decimals than the global price/qty scales hold",
+            ))?;
+
+            let ingest_paused = IntGauge::with_opts(Opts::new(
+                "ingest_paused",
+                "Global ingest pause on: DB writes and Redis publishes skipped (0/1)",
+            ))?;
+
             // --------------------------------------------------
             // Control-plane ops
             // --------------------------------------------------
@@ -240,6 +303,9 @@ impl AppMetrics {
                 &runtime_red_cpu,
                 &streams_active,
                 &streams_limit,
+                &registry_stale,
+                &scale_mismatch,
+                &ingest_paused,
             ] {
                 registry.register(Box::new(g.clone()))?;
             }
@@ -280,6 +346,9 @@ impl AppMetrics {
 
                 streams_active,
                 streams_limit,
+                registry_stale,
+                scale_mismatch,
+                ingest_paused,
 
                 streams_add_total,
                 streams_remove_total,
@@ -384,6 +453,24 @@ impl AppMetrics {
 
     // -------- existing stream metrics --------
 
+    #[inline]
+    pub fn set_registry_stale(&self, stale: bool) {
+        #[cfg(feature = "metrics")]
+        self.registry_stale.set(stale as i64);
+    }
+
+    #[inline]
+    pub fn set_scale_mismatch(&self, n: usize) {
+        #[cfg(feature = "metrics")]
+        self.scale_mismatch.set(n as i64);
+    }
+
+    #[inline]
+    pub fn set_ingest_paused(&self, _paused: bool) {
+        #[cfg(feature = "metrics")]
+        self.ingest_paused.set(_paused as i64);
+    }
+
     #[inline]
     pub fn set_streams_active(&self, n: i64) {
         #[cfg(feature = "metrics")]
@@ -438,3 +525,88 @@ impl AppMetrics {
         self.streams_add_denied_db_total.inc();
     }
 }
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn const_labels_are_on_every_gathered_series() -> AppResult<()> {
+        let const_labels: ConstLabels = [
+            ("instance_id", "ingest-a"),
+            ("env", "prod"),
+            ("deploy_version", "2024.06.1"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let m = AppMetrics::new("ingest-a", "prod", 3, 10, &const_labels)?;
+        m.inc_config_reload();
+
+        let families = m.registry.gather();
+        assert!(!families.is_empty());
+        for mf in &families {
+            for metric in mf.get_metric() {
+                let labels: Vec<(&str, &str)> = metric
+                    .get_label()
+                    .iter()
+                    .map(|l| (l.name(), l.value()))
+                    .collect();
+                for (k, v) in &const_labels {
+                    let n = labels.iter().filter(|(name, _)| name == k).count();
+                    assert_eq!(n, 1, "{} has {k} {n} times", mf.name());
+                    assert!(labels.contains(&(k.as_str(), v.as_str())), "{}", mf.name());
+                }
+            }
+        }
+
+        // app_info keeps the identity labels the const labels do not set
+        let text = m.encode_text()?;
+        assert!(text.lines().any(|l| l.starts_with("app_info{")
+            && l.contains("config_version=\"3\"")
+            && l.contains("app_id=\"ingest-a\"")));
+        Ok(())
+    }
+
+    #[test]
+    fn series_label_names_cover_every_registry() -> AppResult<()> {
+        use crate::db::metrics::DbMetrics;
+        use crate::ingest::metrics::IngestMetrics;
+        use crate::redis::metrics::RedisMetrics;
+
+        // One series in every labelled family
+        let ingest = IngestMetrics::new()?;
+        ingest.set_stream_last_event("s", 1.0);
+        ingest.set_stream_liveness("s", false, true);
+        ingest.observe_ws_ping_rtt("x", 0.1);
+        ingest.observe_lag(0.1);
+        let db = DbMetrics::new()?;
+        db.inc_db_error("x");
+        let redis = RedisMetrics::new()?;
+        redis.set_group_pending("g", 1);
+        redis.disable_with_reason("r");
+        let app = AppMetrics::new("a", "dev", 1, 1, &ConstLabels::new())?;
+
+        let texts = [
+            ingest.encode_text()?,
+            db.encode_text()?,
+            redis.encode_text()?,
+            app.encode_text()?,
+        ];
+        // app_info's labels are const labels of its own, left out when already set
+        let series = texts
+            .iter()
+            .flat_map(|t| t.lines())
+            .filter(|l| !l.starts_with('#') && !l.starts_with("app_info{"));
+        for line in series {
+            let Some((_, rest)) = line.split_once('{') else {
+                continue;
+            };
+            let labels = rest.split_once('}').map_or("", |(l, _)| l);
+            for (name, _) in labels.split(',').filter_map(|kv| kv.split_once('=')) {
+                assert!(SERIES_LABEL_NAMES.contains(&name), "{line}");
+            }
+        }
+        Ok(())
+    }
+}
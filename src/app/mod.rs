@@ -2,10 +2,14 @@ pub mod capabilities;
 pub mod config;
 pub mod control;
 pub mod dependencies;
+pub mod gc;
 pub mod health;
 pub mod metrics;
+pub mod pause;
 pub mod ports;
 pub mod runtime;
+pub mod shutdown_report;
+pub mod startup;
 pub mod state;
 pub mod stream_types;
 
@@ -13,9 +17,13 @@ pub use capabilities::*;
 pub use config::*;
 pub use control::*;
 pub use dependencies::*;
+pub use gc::*;
 pub use health::*;
 pub use metrics::*;
+pub use pause::*;
 pub use ports::*;
 pub use runtime::*;
+pub use shutdown_report::*;
+pub use startup::*;
 pub use state::*;
 pub use stream_types::*;
@@ -1,8 +1,13 @@
+use crate::app::pause::{IngestPause, PauseMode};
 use crate::db::Batch as DbBatch;
 use crate::db::DbHandler;
-use crate::db::{DepthDeltaDBRow, FundingDBRow, LiquidationDBRow, OpenInterestDBRow, TradeDBRow};
+use crate::db::{
+    BboRow, DepthDeltaDBRow, FundingDBRow, LiquidationDBRow, MicropriceRow, OpenInterestDBRow,
+    TradeDBRow, TradeImbalanceRow, WriteOutcome,
+};
 use crate::error::AppResult;
 use crate::redis::client::RedisClient;
+use crate::redis::fields::{PersistedRedisPublish, as_publish_fields};
 use crate::redis::manager::{PublishOutcome, RedisManager};
 use crate::redis::streams::StreamKind;
 use async_trait::async_trait;
@@ -55,6 +60,55 @@ impl RedisPublisher for RealRedisPublisher {
     }
 }
 
+/// Publish rows a DB flush committed (`writer.publish_persisted_only`), in commit order.
+pub async fn publish_persisted<T: PersistedRedisPublish + Sync>(
+    publisher: &dyn RedisPublisher,
+    exchange: &str,
+    rows: &[T],
+) -> AppResult<()> {
+    for row in rows {
+        let fields = row.redis_fields();
+        publisher
+            .publish(
+                exchange,
+                row.redis_symbol(),
+                row.redis_kind(),
+                &as_publish_fields(&fields),
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+/// Skips every publish while the global ingest pause is on (see `app::pause`).
+#[derive(Clone, Debug)]
+pub struct PausableRedisPublisher {
+    pause: Arc<IngestPause>,
+    inner: Arc<dyn RedisPublisher>,
+}
+
+impl PausableRedisPublisher {
+    pub fn new(pause: Arc<IngestPause>, inner: Arc<dyn RedisPublisher>) -> Self {
+        Self { pause, inner }
+    }
+}
+
+#[async_trait]
+impl RedisPublisher for PausableRedisPublisher {
+    async fn publish(
+        &self,
+        exchange: &str,
+        symbol: &str,
+        kind: StreamKind,
+        fields: &[(&str, &str)],
+    ) -> AppResult<PublishOutcome> {
+        if self.pause.is_paused() {
+            return Ok(PublishOutcome::Skipped);
+        }
+        self.inner.publish(exchange, symbol, kind, fields).await
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct NoopRedisPublisher {
     _enabled: Arc<AtomicBool>,
@@ -89,6 +143,9 @@ pub enum AnyDbBatch<'a> {
     DepthDeltas(&'a mut DbBatch<DepthDeltaDBRow>),
     Fundings(&'a mut DbBatch<FundingDBRow>),
     OpenInterests(&'a mut DbBatch<OpenInterestDBRow>),
+    Bbo(&'a mut DbBatch<BboRow>),
+    Microprice(&'a mut DbBatch<MicropriceRow>),
+    TradeImbalance(&'a mut DbBatch<TradeImbalanceRow>),
 }
 
 impl<'a> From<&'a mut DbBatch<OpenInterestDBRow>> for AnyDbBatch<'a> {
@@ -121,9 +178,64 @@ impl<'a> From<&'a mut DbBatch<FundingDBRow>> for AnyDbBatch<'a> {
     }
 }
 
+impl<'a> From<&'a mut DbBatch<BboRow>> for AnyDbBatch<'a> {
+    fn from(b: &'a mut DbBatch<BboRow>) -> Self {
+        AnyDbBatch::Bbo(b)
+    }
+}
+
+impl<'a> From<&'a mut DbBatch<MicropriceRow>> for AnyDbBatch<'a> {
+    fn from(b: &'a mut DbBatch<MicropriceRow>) -> Self {
+        AnyDbBatch::Microprice(b)
+    }
+}
+
+impl<'a> From<&'a mut DbBatch<TradeImbalanceRow>> for AnyDbBatch<'a> {
+    fn from(b: &'a mut DbBatch<TradeImbalanceRow>) -> Self {
+        AnyDbBatch::TradeImbalance(b)
+    }
+}
+
+macro_rules! with_any_batch {
+    ($batch:expr, $b:ident => $body:expr) => {
+        match $batch {
+            AnyDbBatch::Trades($b) => $body,
+            AnyDbBatch::Liquidations($b) => $body,
+            AnyDbBatch::DepthDeltas($b) => $body,
+            AnyDbBatch::Fundings($b) => $body,
+            AnyDbBatch::OpenInterests($b) => $body,
+            AnyDbBatch::Bbo($b) => $body,
+            AnyDbBatch::Microprice($b) => $body,
+            AnyDbBatch::TradeImbalance($b) => $body,
+        }
+    };
+}
+
+impl AnyDbBatch<'_> {
+    /// Drop every pending row (resets the timer). Returns how many.
+    pub fn discard_pending(&mut self) -> usize {
+        with_any_batch!(self, b => b.take_rows().len())
+    }
+
+    /// Keep pending rows, trimmed to the hard cap (oldest first). Returns how many were dropped.
+    pub fn hold_pending(&mut self) -> usize {
+        with_any_batch!(self, b => {
+            let before = b.len();
+            b.enforce_cap();
+            before - b.len()
+        })
+    }
+}
+
 #[async_trait]
 pub trait DbWriter: Send + Sync + Debug {
-    async fn write_batch(&self, batch: AnyDbBatch<'_>) -> AppResult<()>;
+    async fn write_batch(&self, batch: AnyDbBatch<'_>) -> AppResult<WriteOutcome>;
+
+    /// Write whatever the batch holds now, not waiting for its size / interval (e.g. partial
+    /// batches of a stream that stops). Defaults to `write_batch`.
+    async fn flush_batch(&self, batch: AnyDbBatch<'_>) -> AppResult<WriteOutcome> {
+        self.write_batch(batch).await
+    }
 }
 
 /// Real DB writer: downcasts Batch<T> to the supported concrete Batch<Row> types.
@@ -141,9 +253,9 @@ impl RealDbWriter {
 
 #[async_trait]
 impl DbWriter for RealDbWriter {
-    async fn write_batch(&self, batch: AnyDbBatch<'_>) -> AppResult<()> {
+    async fn write_batch(&self, batch: AnyDbBatch<'_>) -> AppResult<WriteOutcome> {
         if !self.enabled.load(Ordering::Relaxed) {
-            return Ok(());
+            return Ok(WriteOutcome::not_flushed());
         }
 
         match batch {
@@ -152,7 +264,58 @@ impl DbWriter for RealDbWriter {
             AnyDbBatch::DepthDeltas(b) => self.handler.write_batch(b).await,
             AnyDbBatch::Fundings(b) => self.handler.write_batch(b).await,
             AnyDbBatch::OpenInterests(b) => self.handler.write_batch(b).await,
+            AnyDbBatch::Bbo(b) => self.handler.write_batch(b).await,
+            AnyDbBatch::Microprice(b) => self.handler.write_batch(b).await,
+            AnyDbBatch::TradeImbalance(b) => self.handler.write_batch(b).await,
+        }
+    }
+
+    async fn flush_batch(&self, batch: AnyDbBatch<'_>) -> AppResult<WriteOutcome> {
+        if !self.enabled.load(Ordering::Relaxed) {
+            return Ok(WriteOutcome::not_flushed());
         }
+
+        with_any_batch!(batch, b => self.handler.force_flush(b).await)
+    }
+}
+
+/// Writes nothing while the global ingest pause is on: pending rows are dropped or held
+/// in their batch per `ingest_pause.mode` (see `app::pause`).
+#[derive(Clone, Debug)]
+pub struct PausableDbWriter {
+    pause: Arc<IngestPause>,
+    inner: Arc<dyn DbWriter>,
+}
+
+impl PausableDbWriter {
+    pub fn new(pause: Arc<IngestPause>, inner: Arc<dyn DbWriter>) -> Self {
+        Self { pause, inner }
+    }
+
+    fn skip_paused(&self, mut batch: AnyDbBatch<'_>) -> AppResult<WriteOutcome> {
+        let dropped = match self.pause.mode() {
+            PauseMode::Drop => batch.discard_pending(),
+            PauseMode::Buffer => batch.hold_pending(),
+        };
+        self.pause.add_dropped_rows(dropped as u64);
+        Ok(WriteOutcome::not_flushed())
+    }
+}
+
+#[async_trait]
+impl DbWriter for PausableDbWriter {
+    async fn write_batch(&self, batch: AnyDbBatch<'_>) -> AppResult<WriteOutcome> {
+        if !self.pause.is_paused() {
+            return self.inner.write_batch(batch).await;
+        }
+        self.skip_paused(batch)
+    }
+
+    async fn flush_batch(&self, batch: AnyDbBatch<'_>) -> AppResult<WriteOutcome> {
+        if !self.pause.is_paused() {
+            return self.inner.flush_batch(batch).await;
+        }
+        self.skip_paused(batch)
     }
 }
 
@@ -171,7 +334,228 @@ impl NoopDbWriter {
 
 #[async_trait]
 impl DbWriter for NoopDbWriter {
-    async fn write_batch(&self, _batch: AnyDbBatch<'_>) -> AppResult<()> {
+    async fn write_batch(&self, _batch: AnyDbBatch<'_>) -> AppResult<WriteOutcome> {
+        Ok(WriteOutcome::not_flushed())
+    }
+
+    async fn flush_batch(&self, _batch: AnyDbBatch<'_>) -> AppResult<WriteOutcome> {
+        Ok(WriteOutcome::not_flushed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::control::make_batch_key;
+    use crate::app::stream_types::{ExchangeId, StreamKind as AppStreamKind, StreamTransport};
+    use crate::db::config::WriterConfig;
+    use std::sync::Mutex;
+
+    /// Records every publish as (exchange, symbol, kind, trade_id).
+    #[derive(Debug, Default)]
+    struct RecordingPublisher {
+        published: Mutex<Vec<(String, String, StreamKind, String)>>,
+    }
+
+    #[async_trait]
+    impl RedisPublisher for RecordingPublisher {
+        async fn publish(
+            &self,
+            exchange: &str,
+            symbol: &str,
+            kind: StreamKind,
+            fields: &[(&str, &str)],
+        ) -> AppResult<PublishOutcome> {
+            let trade_id = fields
+                .iter()
+                .find(|(k, _)| *k == "trade_id")
+                .map(|(_, v)| v.to_string())
+                .unwrap_or_default();
+            self.published.lock().unwrap().push((
+                exchange.to_string(),
+                symbol.to_string(),
+                kind,
+                trade_id,
+            ));
+            Ok(PublishOutcome::Published)
+        }
+    }
+
+    fn trade(id: i64, price_i: i64) -> TradeDBRow {
+        TradeDBRow {
+            time: chrono::Utc::now(),
+            symbol: "BTCUSDT".into(),
+            side: 0,
+            price_i,
+            qty_i: 1_000,
+            trade_id: Some(id),
+            is_maker: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn deduped_row_is_neither_committed_nor_published() -> AppResult<()> {
+        let key = make_batch_key(
+            ExchangeId::BinanceLinear,
+            StreamTransport::Ws,
+            AppStreamKind::Trades,
+            "BTCUSDT",
+        )?;
+        let mut batch = DbBatch::new(key, vec![], &WriterConfig::default());
+        batch.track_persisted = true;
+        batch.extend(vec![trade(1, 100), trade(1, 999), trade(2, 101)]);
+
+        // What `DbHandler::write_batch` does around a successful insert
+        assert_eq!(batch.dedup_pending(), 1);
+        let outcome = WriteOutcome::flushed(&batch.rows);
+        batch.clear_flushed();
+        assert_eq!(outcome.rows_written, 2);
+        assert!(batch.rows.is_empty());
+
+        let persisted = batch.take_persisted();
+        assert_eq!(
+            persisted.iter().map(|r| r.price_i).collect::<Vec<_>>(),
+            vec![100, 101],
+            "the repeat (price 999) is dropped, the first occurrence kept"
+        );
+        assert!(batch.take_persisted().is_empty(), "taken only once");
+
+        let publisher = RecordingPublisher::default();
+        publish_persisted(&publisher, "binance_linear", &persisted).await?;
+        let published = publisher.published.lock().unwrap().clone();
+        assert_eq!(
+            published,
+            vec![
+                (
+                    "binance_linear".into(),
+                    "BTCUSDT".into(),
+                    StreamKind::Trades,
+                    "1".into()
+                ),
+                (
+                    "binance_linear".into(),
+                    "BTCUSDT".into(),
+                    StreamKind::Trades,
+                    "2".into()
+                ),
+            ]
+        );
+
+        // Untracked batches drop committed rows as before
+        let mut untracked = DbBatch::new(
+            batch.key.clone(),
+            vec![trade(3, 1)],
+            &WriterConfig::default(),
+        );
+        untracked.clear_flushed();
+        assert!(untracked.take_persisted().is_empty());
+        Ok(())
+    }
+
+    /// Commits every row it is handed.
+    #[derive(Debug, Default)]
+    struct CountingWriter {
+        written: Mutex<u64>,
+    }
+
+    #[async_trait]
+    impl DbWriter for CountingWriter {
+        async fn write_batch(&self, batch: AnyDbBatch<'_>) -> AppResult<WriteOutcome> {
+            let AnyDbBatch::Trades(b) = batch else {
+                unreachable!()
+            };
+            let outcome = WriteOutcome::flushed(&b.rows);
+            b.clear_flushed();
+            *self.written.lock().unwrap() += outcome.rows_written;
+            Ok(outcome)
+        }
+    }
+
+    #[tokio::test]
+    async fn ingest_pause_stops_and_resumes_writes() -> AppResult<()> {
+        let key = make_batch_key(
+            ExchangeId::BinanceLinear,
+            StreamTransport::Ws,
+            AppStreamKind::Trades,
+            "BTCUSDT",
+        )?;
+        let cfg = WriterConfig {
+            hard_batch_size: 3,
+            ..WriterConfig::default()
+        };
+
+        for mode in [PauseMode::Drop, PauseMode::Buffer] {
+            let pause = Arc::new(IngestPause::new(mode));
+            let inner = Arc::new(CountingWriter::default());
+            let writer = PausableDbWriter::new(Arc::clone(&pause), inner.clone());
+            let publisher_inner = Arc::new(RecordingPublisher::default());
+            let publisher =
+                PausableRedisPublisher::new(Arc::clone(&pause), publisher_inner.clone());
+            let mut batch = DbBatch::new(key.clone(), vec![], &cfg);
+
+            batch.extend(vec![trade(1, 100)]);
+            writer.write_batch((&mut batch).into()).await?;
+            assert_eq!(*inner.written.lock().unwrap(), 1);
+
+            assert!(pause.pause());
+            assert!(!pause.pause(), "already paused");
+            batch.extend((2..=6).map(|id| trade(id, 100)).collect());
+            let out = writer.write_batch((&mut batch).into()).await?;
+            assert!(!out.flushed);
+            assert_eq!(*inner.written.lock().unwrap(), 1, "no write while paused");
+            let outcome = publisher
+                .publish("binance_linear", "BTCUSDT", StreamKind::Trades, &[])
+                .await?;
+            assert_eq!(outcome, PublishOutcome::Skipped);
+            assert!(publisher_inner.published.lock().unwrap().is_empty());
+
+            assert!(pause.resume());
+            batch.extend(vec![trade(7, 100)]);
+            writer.write_batch((&mut batch).into()).await?;
+            publisher
+                .publish("binance_linear", "BTCUSDT", StreamKind::Trades, &[])
+                .await?;
+            assert_eq!(publisher_inner.published.lock().unwrap().len(), 1);
+
+            match mode {
+                // 5 rows of the paused window discarded, only #7 written after resume
+                PauseMode::Drop => {
+                    assert_eq!(pause.dropped_rows(), 5);
+                    assert_eq!(*inner.written.lock().unwrap(), 2);
+                }
+                // Held up to the hard cap (#4..#6), written with #7 after resume
+                PauseMode::Buffer => {
+                    assert_eq!(pause.dropped_rows(), 2);
+                    assert_eq!(*inner.written.lock().unwrap(), 5);
+                }
+            }
+        }
         Ok(())
     }
+
+    #[test]
+    fn persisted_trade_fields_match_event_fields() {
+        use crate::ingest::datamap::event::{TradeRow, TradeSide};
+        use crate::redis::fields::ToRedisPublish;
+
+        let event = TradeRow {
+            exchange: "binance_linear",
+            time: chrono::Utc::now(),
+            symbol: "BTCUSDT".into(),
+            side: TradeSide::Sell,
+            price_i: 42,
+            qty_i: 7,
+            trade_id: Some(9),
+            is_maker: Some(true),
+        };
+        let row = TradeDBRow::from(event.clone());
+        assert_eq!(
+            PersistedRedisPublish::redis_fields(&row),
+            ToRedisPublish::redis_fields(&event)
+        );
+        assert_eq!(
+            PersistedRedisPublish::redis_kind(&row),
+            ToRedisPublish::redis_kind(&event)
+        );
+    }
 }
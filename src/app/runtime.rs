@@ -1,23 +1,34 @@
 use crate::app::AvailableStream;
 use crate::app::StartStreamParams;
+use crate::app::control::batch::make_batch_key;
+use crate::app::control::helpers::render_stream_specs;
+use crate::app::control::stream::start_stream_rendered;
 use crate::app::dependencies::AppDeps;
-use crate::app::health::{RuntimeHealthHandle, start_runtime_health_guard};
+use crate::app::gc::DeadStreamGc;
+use crate::app::health::{
+    AppHealth, Liveness, RuntimeHealthHandle, StallWatchdog, start_runtime_health_guard,
+};
 use crate::app::metrics::AppMetrics;
+use crate::app::shutdown_report::ShutdownReport;
+use crate::app::startup::Startup;
 use crate::app::state::AppState;
 use crate::app::state::StreamKnobs;
 use crate::app::stream_types::{
     ExchangeId, StreamId, StreamKind, StreamSpec, StreamStatus, StreamTransport,
 };
 use crate::error::{AppError, AppResult};
+use crate::ingest::instruments::loader::LoadRetry;
 use crate::ingest::instruments::registry::InstrumentRegistry;
 use crate::ingest::instruments::spec::{InstrumentKind, InstrumentSpec};
+use crate::telemetry::redact::set_payload_redaction;
+use crate::telemetry::throttle::{log_throttle, set_log_repeat_window};
 use arc_swap::{ArcSwap, Guard};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
 use tokio::task::JoinHandle;
 use tokio::time::sleep;
-use tracing::{debug, info, instrument, warn};
+use tracing::{debug, error, info, instrument, warn};
 
 /// Holds a pinned snapshot of the registry so iterators can safely borrow from it.
 pub struct InstrumentsView {
@@ -39,12 +50,33 @@ pub struct AppRuntime {
     // Keep the JoinHandle private and shared across clones.
     runtime_health_task: Arc<Mutex<Option<JoinHandle<()>>>>,
     runtime_health_cancel: tokio_util::sync::CancellationToken,
+
+    // Dead-stream GC (grace timers survive between runs)
+    stream_gc: Arc<std::sync::Mutex<DeadStreamGc>>,
+    stream_gc_cancel: tokio_util::sync::CancellationToken,
+    log_flush_cancel: tokio_util::sync::CancellationToken,
+
+    // Stream stall watchdog (schedule-aware)
+    stall_watchdog_cancel: tokio_util::sync::CancellationToken,
+
+    // SIGUSR1/SIGUSR2 ingest pause listener (`ingest_pause.signals`)
+    pause_signals_cancel: tokio_util::sync::CancellationToken,
 }
 
 impl AppRuntime {
     pub async fn new(from_env: bool, version: u32) -> AppResult<Self> {
+        Self::new_with_startup(from_env, version, &Startup::new()).await
+    }
+
+    /// `new`, reporting each subsystem to `startup` (see `app::startup`) so a failure names
+    /// the subsystem instead of cascading.
+    pub async fn new_with_startup(
+        from_env: bool,
+        version: u32,
+        startup: &Startup,
+    ) -> AppResult<Self> {
         crate::crypto_init::init_rustls_crypto_provider();
-        let mut deps = AppDeps::new(from_env, version).await?; // <-- mutable, not Arc yet
+        let mut deps = AppDeps::new_with_startup(from_env, version, startup).await?; // <-- mutable, not Arc yet
 
         deps.spawn_db_health_loop()?;
         deps.spawn_redis_health_loop()?;
@@ -53,12 +85,15 @@ impl AppRuntime {
 
         // Assuming deps.app_cfgs is an Arc<AppConfig> or something cloneable
         let cfg = deps.app_cfgs.clone();
+        set_log_repeat_window(Duration::from_secs(cfg.logging.repeat_window_secs));
+        set_payload_redaction(&cfg.logging.redact_fields, &cfg.logging.redact_placeholder);
 
         let metrics = Arc::new(AppMetrics::new(
             &cfg.id,
             &cfg.env,
             cfg.config_version,
             cfg.limits.max_active_streams,
+            &cfg.metric_const_labels(),
         )?);
 
         let state = Arc::new(AppState::new());
@@ -69,19 +104,32 @@ impl AppRuntime {
 
         // Start runtime health guard (GREEN/RED) + keep join handle privately
         let token = tokio_util::sync::CancellationToken::new();
-        let (runtime_health, jh) =
-            start_runtime_health_guard(cfg.clone(), Some(metrics.clone()), token.clone())?;
+        let (runtime_health, jh) = startup
+            .step("runtime_health", async {
+                start_runtime_health_guard(cfg.clone(), Some(metrics.clone()), token.clone())
+            })
+            .await?;
 
         let runtime_health_task = Arc::new(Mutex::new(Some(jh)));
 
         // Load instruments
-        let instruments = deps.instruments_loader.load_all().await?;
-        let registry = InstrumentRegistry::build(instruments)?;
+        let registry = startup
+            .step("instruments", async {
+                let retry = LoadRetry::from_config(&cfg.instruments);
+                InstrumentRegistry::build(deps.instruments_loader.load_all_with_retry(retry).await?)
+            })
+            .await?;
+        metrics.set_scale_mismatch(registry.warn_scale_mismatches(&cfg.scales));
+        registry.check_scale_round_trip(&cfg.scales)?;
 
         // ArcSwap wants an Arc<T>
         let instruments_registry = Arc::new(ArcSwap::from(Arc::new(registry)));
 
-        Ok(Self {
+        let stream_gc = Arc::new(std::sync::Mutex::new(DeadStreamGc::from_config(
+            &cfg.stream_gc,
+        )));
+
+        let app = Self {
             deps,
             state,
             metrics,
@@ -89,7 +137,27 @@ impl AppRuntime {
             runtime_health,
             runtime_health_task,
             runtime_health_cancel: token,
-        })
+            stream_gc,
+            stream_gc_cancel: tokio_util::sync::CancellationToken::new(),
+            log_flush_cancel: tokio_util::sync::CancellationToken::new(),
+            stall_watchdog_cancel: tokio_util::sync::CancellationToken::new(),
+            pause_signals_cancel: tokio_util::sync::CancellationToken::new(),
+        };
+
+        if cfg.stream_gc.enabled {
+            app.spawn_stream_gc_loop();
+        }
+        if cfg.health.enabled && cfg.health.stall.enabled {
+            app.spawn_stall_watchdog_loop();
+        }
+        if cfg.ingest_pause.signals {
+            app.spawn_pause_signal_loop();
+        }
+        if cfg.logging.repeat_window_secs > 0 {
+            app.spawn_log_flush_loop();
+        }
+
+        Ok(app)
     }
 }
 // --------------------------------------------------
@@ -141,6 +209,55 @@ impl AppRuntime {
         tracing::debug!(component = "admission", "stream admission allowed");
         Ok(())
     }
+
+    /// Onboarding gate: at least `health.min_healthy_shards` DB shards must be reachable,
+    /// so new streams never route to dead shards.
+    /// No-op when the DB is disabled / not initialized (the admission check covers that).
+    pub async fn ensure_db_write_ready(&self) -> AppResult<()> {
+        let Some(db) = self.deps.db.as_ref() else {
+            return Ok(());
+        };
+        if !self.deps.is_db_enabled() {
+            return Ok(());
+        }
+
+        let report = db.handler.shard_health_report().await;
+        if report.is_write_ready() {
+            return Ok(());
+        }
+
+        self.metrics.inc_stream_add_denied_db();
+        tracing::warn!(
+            component = "admission",
+            healthy = report.healthy,
+            total = report.total,
+            required = report.required,
+            unhealthy = ?report.unhealthy,
+            "stream admission denied: not enough healthy db shards"
+        );
+        Err(AppError::Disabled(format!(
+            "db:shards_unhealthy ({}/{} healthy, need {})",
+            report.healthy, report.total, report.required
+        )))
+    }
+
+    /// Readiness (`/readyz`): runtime GREEN and the composite health not unhealthy.
+    pub async fn is_ready(&self) -> bool {
+        self.runtime_ok() && !self.app_health().await.is_unhealthy()
+    }
+
+    /// DB write-readiness (when the DB is enabled) combined with the Redis gate.
+    pub async fn app_health(&self) -> AppHealth {
+        let db_ready = match self.deps.db.as_ref() {
+            Some(db) if self.deps.is_db_enabled() => db.handler.is_write_ready().await,
+            _ => true,
+        };
+        AppHealth::evaluate(
+            db_ready,
+            self.deps.redis_can_publish(),
+            self.deps.app_cfgs.health.redis_required,
+        )
+    }
     /// Optional: if you want to await or abort the background task on shutdown,
     /// call this once (subsequent calls return None).
     pub async fn take_runtime_health_task(&self) -> Option<JoinHandle<()>> {
@@ -155,13 +272,46 @@ impl AppRuntime {
     pub async fn refresh_instruments_registry(&self) -> AppResult<()> {
         let instruments = self.deps.instruments_loader.load_all().await?;
         let new_registry = InstrumentRegistry::build(instruments)?;
+        self.metrics
+            .set_scale_mismatch(new_registry.warn_scale_mismatches(&self.deps.app_cfgs.scales));
 
         self.instruments_registry.store(Arc::new(new_registry));
+        self.metrics.set_registry_stale(false);
 
         info!(component = "registry", "instruments registry refreshed");
         Ok(())
     }
 
+    /// Stream admission: refuse to run on stale instrument metadata.
+    /// A registry past `instruments.max_registry_age_sec` is refreshed first; only a failed
+    /// refresh blocks the start (`registry_stale`).
+    pub async fn ensure_registry_fresh(&self) -> AppResult<()> {
+        let max_age_ms = self
+            .deps
+            .app_cfgs
+            .instruments
+            .max_registry_age_sec
+            .saturating_mul(1000);
+
+        let reason = match self.instruments_registry.load().ensure_fresh(max_age_ms) {
+            Ok(()) => return Ok(()),
+            Err(AppError::Disabled(reason)) => reason,
+            Err(e) => e.to_string(),
+        };
+        warn!(component = "registry", %reason, "instruments registry is stale; refreshing");
+
+        if let Err(e) = self.refresh_instruments_registry().await {
+            self.metrics.set_registry_stale(true);
+            error!(
+                component = "registry",
+                error = %e,
+                "registry_stale: refresh failed; refusing to start streams on stale instrument metadata"
+            );
+            return Err(AppError::Disabled(format!("{reason}; refresh failed: {e}")));
+        }
+        Ok(())
+    }
+
     #[inline]
     pub fn instruments_guard(&self) -> Guard<Arc<InstrumentRegistry>> {
         self.instruments_registry.load()
@@ -198,6 +348,28 @@ impl AppRuntime {
 // Stream control
 // -------------------------------------
 impl AppRuntime {
+    /// Canonical casing of `symbol` for `exchange` (per-exchange `symbol_case`).
+    #[inline]
+    pub fn canonical_symbol(&self, exchange: ExchangeId, symbol: &str) -> String {
+        self.deps.exchange_cfgs.canonical_symbol(exchange, symbol)
+    }
+
+    /// StreamId with the symbol in its canonical casing.
+    pub fn stream_id(
+        &self,
+        exchange: ExchangeId,
+        symbol: &str,
+        kind: StreamKind,
+        transport: StreamTransport,
+    ) -> StreamId {
+        StreamId::new(
+            exchange.as_str(),
+            &self.canonical_symbol(exchange, symbol),
+            kind,
+            transport,
+        )
+    }
+
     #[instrument(
         name = "runtime.add_stream",
         skip(self, params),
@@ -237,9 +409,9 @@ impl AppRuntime {
         err
     )]
     pub async fn remove_stream(&self, params: StartStreamParams) -> AppResult<()> {
-        let id = StreamId::new(
-            params.exchange.as_str(),
-            params.symbol.as_str(),
+        let id = self.stream_id(
+            params.exchange,
+            &params.symbol,
             params.kind,
             params.transport,
         );
@@ -262,6 +434,11 @@ impl AppRuntime {
         let result = self.state.stop_and_remove(&id).await?;
 
         if result {
+            // Removed on purpose: drop its liveness series so it does not alert as stalled
+            if let Some(m) = self.deps.ingest_metrics.as_deref() {
+                m.forget_stream(&id.to_string());
+            }
+            self.deps.book_cache.remove(&id.to_string());
             info!(component = "streams", stream_id = %id, "remove_stream succeeded");
             Ok(())
         } else {
@@ -277,11 +454,40 @@ impl AppRuntime {
             .as_ref()
             .ok_or_else(|| AppError::Disabled("db_unavailable".into()))?;
 
-        let streams = db.handler.load_enabled_streams_from_registry().await?;
+        let mut streams = db.handler.load_enabled_streams_from_registry().await?;
+        // Rendered below from the symbol their start uses
+        for s in &mut streams {
+            s.symbol = self.canonical_symbol(s.exchange, &s.symbol);
+        }
+
+        // Before anything starts: with strict_shard_coverage, every stream must route (the
+        // keys are only built for that check)
+        if db.cfg.strict_shard_coverage {
+            let keys = streams
+                .iter()
+                .map(|s| make_batch_key(s.exchange, s.transport, s.kind, &s.symbol))
+                .collect::<AppResult<Vec<_>>>()?;
+            db.cfg.check_shard_coverage(&keys)?;
+        }
+
+        // ... and every spec must render: all of them at once, off the async workers; the
+        // starts below use these instead of rendering them again
+        let configs = Arc::clone(&self.deps.exchange_cfgs);
+        let budget = Some(self.deps.app_cfgs.streams.resolve_budget_ms)
+            .filter(|ms| *ms > 0)
+            .map(Duration::from_millis);
+        let (streams, rendered) = tokio::task::spawn_blocking(move || {
+            render_stream_specs(&configs, &streams, budget).map(|r| (streams, r))
+        })
+        .await??;
 
         debug!("on_crash: restoring {} streams", streams.len());
 
-        for stream in streams {
+        // Registry rows of the restored streams, written in one upsert at the end (also when
+        // a start fails, for the streams started before it)
+        let mut restored = Vec::with_capacity(streams.len());
+        let mut result = Ok(());
+        for (stream, specs) in streams.into_iter().zip(rendered) {
             let stream_id = StreamId::new(
                 stream.exchange.as_str(),
                 stream.symbol.as_str(),
@@ -300,8 +506,27 @@ impl AppRuntime {
                 "on_crash: re-adding stream"
             );
 
-            self.add_stream(stream).await?;
+            match start_stream_rendered(self, stream, specs).await {
+                Ok(entry) => {
+                    info!(component = "streams", stream_id = %stream_id, "stream restored");
+                    restored.push(entry);
+                }
+                Err(e) => {
+                    warn!(
+                        component = "streams",
+                        stream_id = %stream_id,
+                        error = %e,
+                        "stream restore failed"
+                    );
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+        if !restored.is_empty() {
+            db.handler.upsert_stream_registry_many(&restored).await?;
         }
+        result?;
 
         debug!("on_crash: all streams restored");
 
@@ -309,6 +534,371 @@ impl AppRuntime {
     }
 }
 
+// --------------------------------------------------
+// Dead-stream GC
+// --------------------------------------------------
+impl AppRuntime {
+    /// Disable registry streams whose instrument has been delisted beyond the grace period.
+    /// Rows are disabled (not deleted) and running streams are stopped. A stream that fails
+    /// either step is logged and the pass moves on to the next one.
+    /// Returns the ids of the disabled streams.
+    #[instrument(name = "runtime.gc_dead_streams", skip_all, err)]
+    pub async fn gc_dead_streams(&self) -> AppResult<Vec<StreamId>> {
+        let db = self
+            .deps
+            .db
+            .as_ref()
+            .ok_or_else(|| AppError::Disabled("db_unavailable".into()))?;
+
+        let streams = db.handler.load_enabled_streams_from_registry().await?;
+        let registry = self.instruments_registry.load_full();
+
+        let dead = self
+            .stream_gc
+            .lock()
+            .expect("stream gc mutex poisoned")
+            .select(&registry, &streams, chrono::Utc::now());
+
+        let mut disabled = Vec::with_capacity(dead.len());
+        for d in dead {
+            let spec = StreamSpec {
+                exchange: d.params.exchange.as_str(),
+                instrument: d.params.symbol.clone(),
+                kind: d.params.kind,
+                transport: d.params.transport,
+            };
+
+            if let Err(e) = db.handler.set_stream_registry_enabled(&spec, false).await {
+                warn!(
+                    component = "stream_gc",
+                    stream_id = %d.id,
+                    error = %e,
+                    "failed to disable stream of delisted instrument; retrying next pass"
+                );
+                continue;
+            }
+            // The row is disabled either way, so the entry is done with even if the stop fails
+            let was_running = match self.state.stop_and_remove(&d.id).await {
+                Ok(was_running) => was_running,
+                Err(e) => {
+                    warn!(
+                        component = "stream_gc",
+                        stream_id = %d.id,
+                        error = %e,
+                        "failed to stop stream of delisted instrument"
+                    );
+                    false
+                }
+            };
+
+            self.stream_gc
+                .lock()
+                .expect("stream gc mutex poisoned")
+                .forget(&d.id);
+
+            warn!(
+                component = "stream_gc",
+                stream_id = %d.id,
+                reason = d.reason.as_str(),
+                dead_since = %d.dead_since,
+                was_running,
+                "disabled stream of delisted instrument"
+            );
+            disabled.push(d.id);
+        }
+
+        info!(
+            component = "stream_gc",
+            checked = streams.len(),
+            disabled = disabled.len(),
+            "dead-stream gc finished"
+        );
+        Ok(disabled)
+    }
+
+    /// Periodic GC: refresh the instruments registry, then run `gc_dead_streams`.
+    /// A failed refresh skips the pass (stale snapshot must not disable anything new).
+    fn spawn_stream_gc_loop(&self) {
+        let app = self.clone();
+        let every = Duration::from_secs(self.deps.app_cfgs.stream_gc.interval_sec);
+        let cancel = self.stream_gc_cancel.clone();
+
+        tokio::spawn(async move {
+            let mut tick = tokio::time::interval(every);
+            tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            tick.tick().await; // first tick fires immediately; skip it
+
+            loop {
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = tick.tick() => {}
+                }
+
+                if let Err(e) = app.refresh_instruments_registry().await {
+                    warn!(component = "stream_gc", error = %e, "registry refresh failed; skipping gc pass");
+                    continue;
+                }
+                if let Err(e) = app.gc_dead_streams().await {
+                    warn!(component = "stream_gc", error = %e, "dead-stream gc failed");
+                }
+            }
+        });
+    }
+
+    pub fn stop_stream_gc(&self) {
+        self.stream_gc_cancel.cancel()
+    }
+
+    /// Logs the suppressed counts of throttled errors that stopped recurring (the throttle
+    /// only summarizes on the next occurrence, which may never come).
+    fn spawn_log_flush_loop(&self) {
+        let every = log_throttle().window();
+        let cancel = self.log_flush_cancel.clone();
+
+        tokio::spawn(async move {
+            let mut tick = tokio::time::interval(every);
+            tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            tick.tick().await; // first tick fires immediately; skip it
+
+            loop {
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = tick.tick() => {}
+                }
+
+                for (key, suppressed) in log_throttle().flush_due() {
+                    warn!(key = %key, suppressed, "repeated log lines suppressed");
+                }
+            }
+        });
+    }
+}
+
+// --------------------------------------------------
+// Stream stall watchdog
+// --------------------------------------------------
+impl AppRuntime {
+    /// One watchdog pass over the running streams: publishes `ingest_stream_stalled` /
+    /// `ingest_stream_session_open` and warns on stalls. Closed sessions never alert, nor do
+    /// streams without liveness data or of a kind the watchdog leaves out.
+    pub async fn check_stalls(&self, watchdog: &mut StallWatchdog) {
+        let Some(m) = self.deps.ingest_metrics.as_deref() else {
+            return;
+        };
+        let now = chrono::Utc::now();
+        let registry = self.instruments_registry.load();
+
+        let mut live = Vec::new();
+        for (id, status, spec) in self.state.list().await {
+            if status != StreamStatus::Running {
+                continue;
+            }
+            let stream = id.to_string();
+            if !m.stream_liveness_known(&stream) {
+                live.push(stream);
+                continue;
+            }
+            let registry_schedule = registry
+                .get(spec.exchange, &spec.instrument)
+                .map(|i| i.schedule.clone())
+                .unwrap_or_default();
+            let schedule = watchdog
+                .schedule(spec.exchange, &spec.instrument, &registry_schedule)
+                .clone();
+            let last_event = m
+                .stream_last_event(&stream)
+                .and_then(|s| chrono::DateTime::from_timestamp_millis((s * 1000.0) as i64));
+
+            let liveness = watchdog.check(&stream, spec.kind, &schedule, last_event, now);
+            m.set_stream_liveness(&stream, liveness.is_stalled(), schedule.is_open(now));
+            let throttle_key = format!("stream stalled:{stream}");
+            if let Liveness::Stalled { idle_secs } = liveness
+                && let Some(suppressed) = log_throttle().allow(&throttle_key)
+            {
+                warn!(
+                    component = "stall",
+                    stream_id = %stream,
+                    idle_secs,
+                    suppressed,
+                    "stream stalled (market open, no events)"
+                );
+            }
+            live.push(stream);
+        }
+        watchdog.retain(&live);
+    }
+
+    fn spawn_stall_watchdog_loop(&self) {
+        let app = self.clone();
+        let cfg = self.deps.app_cfgs.health.stall.clone();
+        let cancel = self.stall_watchdog_cancel.clone();
+
+        tokio::spawn(async move {
+            let mut watchdog = match StallWatchdog::from_config(&cfg) {
+                Ok(wd) => wd,
+                Err(e) => {
+                    // Checked at config load; kept as a guard
+                    warn!(component = "stall", error = %e, "stall watchdog disabled");
+                    return;
+                }
+            };
+            let mut tick = tokio::time::interval(Duration::from_secs(cfg.interval_sec.max(1)));
+            tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+            loop {
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = tick.tick() => {}
+                }
+                app.check_stalls(&mut watchdog).await;
+            }
+        });
+    }
+}
+
+// --------------------------------------------------
+// Global ingest pause (kill switch)
+// --------------------------------------------------
+impl AppRuntime {
+    /// Stop all DB writes and Redis publishes; connections stay up (see `app::pause`).
+    /// Returns false if already paused.
+    pub fn pause_ingest(&self) -> bool {
+        let changed = self.deps.ingest_pause.pause();
+        self.metrics.set_ingest_paused(true);
+        if changed {
+            warn!(
+                component = "ingest_pause",
+                mode = ?self.deps.ingest_pause.mode(),
+                "ingest paused: DB writes and Redis publishes stopped"
+            );
+        }
+        changed
+    }
+
+    /// Lift the global pause. Returns false if not paused.
+    pub fn resume_ingest(&self) -> bool {
+        let changed = self.deps.ingest_pause.resume();
+        self.metrics.set_ingest_paused(false);
+        if changed {
+            info!(
+                component = "ingest_pause",
+                dropped_rows = self.deps.ingest_pause.dropped_rows(),
+                "ingest resumed"
+            );
+        }
+        changed
+    }
+
+    pub fn is_ingest_paused(&self) -> bool {
+        self.deps.ingest_pause.is_paused()
+    }
+
+    #[cfg(unix)]
+    fn spawn_pause_signal_loop(&self) {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        let (mut usr1, mut usr2) = match (
+            signal(SignalKind::user_defined1()),
+            signal(SignalKind::user_defined2()),
+        ) {
+            (Ok(a), Ok(b)) => (a, b),
+            (Err(e), _) | (_, Err(e)) => {
+                warn!(component = "ingest_pause", error = %e, "cannot listen for SIGUSR1/SIGUSR2");
+                return;
+            }
+        };
+        let app = self.clone();
+        let cancel = self.pause_signals_cancel.clone();
+
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    Some(()) = usr1.recv() => { app.pause_ingest(); }
+                    Some(()) = usr2.recv() => { app.resume_ingest(); }
+                }
+            }
+        });
+    }
+
+    #[cfg(not(unix))]
+    fn spawn_pause_signal_loop(&self) {
+        warn!(
+            component = "ingest_pause",
+            "ingest_pause.signals needs unix signals; ignored"
+        );
+    }
+}
+
+// --------------------------------------------------
+// Shutdown
+// --------------------------------------------------
+impl AppRuntime {
+    /// Graceful shutdown: cancel the app token and background loops, wait for the streams to
+    /// end (up to `streams.stop_timeout_ms`), then close the DB writer so pending
+    /// `write_batch` calls return `AppError::Shutdown` instead of blocking.
+    /// Returns the run summary (logged / written per `[shutdown_report]`).
+    pub async fn shutdown(&self) -> ShutdownReport {
+        let streams_stopped = self
+            .list_stream_ids()
+            .await
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+
+        self.state.trigger_shutdown();
+        let stop_timeout = Duration::from_millis(self.deps.app_cfgs.streams.stop_timeout_ms);
+        let still_running = self.state.cancel_all_streams(stop_timeout).await;
+        if still_running > 0 {
+            warn!(
+                component = "runtime",
+                still_running, "streams still running after stop_timeout_ms; closing the DB anyway"
+            );
+        }
+        // The streams have ended (or timed out): their final flushes are in the report
+        let report = ShutdownReport {
+            streams_still_running: still_running,
+            ..self.shutdown_report(streams_stopped)
+        };
+        self.stop_stream_gc();
+        self.log_flush_cancel.cancel();
+        self.stall_watchdog_cancel.cancel();
+        self.pause_signals_cancel.cancel();
+        self.stop_runtime_health().await;
+        if let Some(db) = self.deps.db.as_ref() {
+            db.handler.close();
+        }
+        if let Some(redis) = self.deps.redis.as_ref() {
+            redis.manager.shutdown().await;
+        }
+
+        let cfg = &self.deps.app_cfgs.shutdown_report;
+        if cfg.enabled {
+            report.log();
+            if !cfg.path.is_empty()
+                && let Err(e) = report.write_json(std::path::Path::new(&cfg.path))
+            {
+                warn!(component = "runtime", error = %e, "failed to write shutdown report");
+            }
+        }
+
+        info!(component = "runtime", "shutdown complete");
+        report
+    }
+
+    /// Summary of what this process committed, failed, or left unflushed.
+    pub fn shutdown_report(&self, streams_stopped: Vec<String>) -> ShutdownReport {
+        let db = self.deps.db.as_ref();
+        let pending = db.map(|db| db.handler.pending_budget());
+        ShutdownReport::collect(
+            streams_stopped,
+            db.map(|db| db.handler.commit_ledger().snapshot()),
+            db.map(|db| db.metrics.write_totals()),
+            pending.as_deref(),
+        )
+    }
+}
+
 // --------------------------------------------------
 // Health Control
 // --------------------------------------------------
@@ -574,6 +1164,78 @@ impl AppRuntime {
         res
     }
 
+    #[instrument(
+        name = "runtime.set_stream_emit_bbo",
+        skip(self),
+        fields(stream_id = %id, enabled),
+        err
+    )]
+    pub async fn set_stream_emit_bbo(&self, id: &StreamId, enabled: bool) -> AppResult<bool> {
+        let spec = self
+            .state
+            .stream_spec(id)
+            .await
+            .ok_or_else(|| AppError::StreamNotFound(id.to_string()))?;
+        let db = self
+            .deps
+            .db
+            .as_ref()
+            .ok_or_else(|| AppError::Disabled("db_unavailable".into()))?;
+        let mut knobs = self
+            .state
+            .stream_knobs_snapshot(id)
+            .await
+            .ok_or_else(|| AppError::StreamNotFound(id.to_string()))?;
+        knobs.emit_bbo = enabled;
+        db.handler.update_stream_knobs(&spec, &knobs).await?;
+        let res = self.state.set_emit_bbo(id, enabled).await;
+        match &res {
+            Ok(changed) => info!(component = "knobs", changed = *changed, "emit_bbo updated"),
+            Err(e) => warn!(component = "knobs", error = %e, "emit_bbo update failed"),
+        }
+        res
+    }
+
+    #[instrument(
+        name = "runtime.set_stream_emit_microprice",
+        skip(self),
+        fields(stream_id = %id, enabled),
+        err
+    )]
+    pub async fn set_stream_emit_microprice(
+        &self,
+        id: &StreamId,
+        enabled: bool,
+    ) -> AppResult<bool> {
+        let spec = self
+            .state
+            .stream_spec(id)
+            .await
+            .ok_or_else(|| AppError::StreamNotFound(id.to_string()))?;
+        let db = self
+            .deps
+            .db
+            .as_ref()
+            .ok_or_else(|| AppError::Disabled("db_unavailable".into()))?;
+        let mut knobs = self
+            .state
+            .stream_knobs_snapshot(id)
+            .await
+            .ok_or_else(|| AppError::StreamNotFound(id.to_string()))?;
+        knobs.emit_microprice = enabled;
+        db.handler.update_stream_knobs(&spec, &knobs).await?;
+        let res = self.state.set_emit_microprice(id, enabled).await;
+        match &res {
+            Ok(changed) => info!(
+                component = "knobs",
+                changed = *changed,
+                "emit_microprice updated"
+            ),
+            Err(e) => warn!(component = "knobs", error = %e, "emit_microprice update failed"),
+        }
+        res
+    }
+
     #[instrument(
         name = "runtime.set_stream_flush_rows",
         skip(self),
@@ -0,0 +1,224 @@
+//! app/shutdown_report.rs
+//!
+//! Structured summary of a run, produced by `AppRuntime::shutdown`.
+//!
+//! Answers "did the deploy lose data?" from what the process already tracks:
+//! - committed rows/batches per shard and final watermarks per stream (`CommitLedger`)
+//! - failed/retried batches and dropped rows (`DbMetrics`), and what was lost for good:
+//!   batches dropped after a failed write, rows dropped with their batch unwritten
+//! - rows still pending in batches when the writer closed (`PendingBatchBudget`)
+//! - the streams that were stopped, and those still running when shutdown stopped waiting
+//!
+//! Built once the stream tasks have been joined, so their final flushes are counted.
+
+use crate::db::{CommitLedgerSnapshot, DbWriteTotals, PendingBatchBudget};
+use crate::error::{AppError, AppResult};
+use chrono::Utc;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ShutdownReport {
+    /// RFC 3339.
+    pub generated_at: String,
+    pub streams_stopped: Vec<String>,
+    /// Stream tasks still running after `streams.stop_timeout_ms` (their rows may be lost).
+    pub streams_still_running: usize,
+
+    // Committed (this process)
+    pub rows_written_by_shard: BTreeMap<String, u64>,
+    pub batches_flushed_by_shard: BTreeMap<String, u64>,
+    pub rows_written_total: u64,
+    pub batches_flushed_total: u64,
+    /// `exchange:stream:symbol` -> max committed event time (RFC 3339).
+    pub final_watermarks: BTreeMap<String, String>,
+
+    // Failures
+    /// Failed write attempts, including ones a later flush of the same rows wrote.
+    pub batches_failed: u64,
+    pub batches_retried: u64,
+    /// Repeated rows removed before the insert (not lost).
+    pub rows_dropped: u64,
+    /// Batches dropped still holding rows after a failed write.
+    pub batches_lost: u64,
+    /// Rows never written (still batched when their batch was dropped).
+    pub rows_unwritten: u64,
+
+    // Never flushed (writer closed with rows still batched)
+    pub pending_batches_at_shutdown: usize,
+    pub pending_bytes_at_shutdown: usize,
+}
+
+impl ShutdownReport {
+    /// Build the report from the DB ledger/counters (None when the DB is disabled).
+    pub fn collect(
+        streams_stopped: Vec<String>,
+        ledger: Option<CommitLedgerSnapshot>,
+        totals: Option<DbWriteTotals>,
+        pending: Option<&PendingBatchBudget>,
+    ) -> Self {
+        let ledger = ledger.unwrap_or_default();
+        let totals = totals.unwrap_or_default();
+        Self {
+            generated_at: Utc::now().to_rfc3339(),
+            streams_stopped,
+            streams_still_running: 0,
+            rows_written_total: ledger.rows_total(),
+            batches_flushed_total: ledger.batches_total(),
+            rows_written_by_shard: ledger.rows_by_shard,
+            batches_flushed_by_shard: ledger.batches_by_shard,
+            final_watermarks: ledger
+                .watermarks
+                .into_iter()
+                .map(|(stream, wm)| (stream, wm.to_rfc3339()))
+                .collect(),
+            batches_failed: totals.batches_failed,
+            batches_retried: totals.batches_retried,
+            rows_dropped: totals.rows_dropped,
+            batches_lost: totals.batches_lost,
+            rows_unwritten: totals.rows_unwritten,
+            pending_batches_at_shutdown: pending.map_or(0, |p| p.tracked_batches()),
+            pending_bytes_at_shutdown: pending.map_or(0, |p| p.total_bytes()),
+        }
+    }
+
+    /// True when rows may have been lost: failed for good, dropped unwritten, or left
+    /// unflushed. Failed attempts a retry wrote and deduplicated repeats are not loss.
+    pub fn possible_data_loss(&self) -> bool {
+        self.batches_lost > 0
+            || self.rows_unwritten > 0
+            || self.pending_bytes_at_shutdown > 0
+            || self.streams_still_running > 0
+    }
+
+    /// Log the report as one structured event (warn when data may have been lost).
+    pub fn log(&self) {
+        let report = serde_json::to_string(self).unwrap_or_default();
+        if self.possible_data_loss() {
+            tracing::warn!(
+                component = "runtime",
+                streams_stopped = self.streams_stopped.len(),
+                streams_still_running = self.streams_still_running,
+                rows_written = self.rows_written_total,
+                batches_lost = self.batches_lost,
+                rows_unwritten = self.rows_unwritten,
+                pending_bytes = self.pending_bytes_at_shutdown,
+                report = %report,
+                "shutdown report: possible data loss"
+            );
+        } else {
+            tracing::info!(
+                component = "runtime",
+                streams_stopped = self.streams_stopped.len(),
+                rows_written = self.rows_written_total,
+                batches_flushed = self.batches_flushed_total,
+                report = %report,
+                "shutdown report"
+            );
+        }
+    }
+
+    /// Write the report as pretty JSON.
+    pub fn write_json(&self, path: &Path) -> AppResult<()> {
+        let json = serde_json::to_vec_pretty(self)?;
+        std::fs::write(path, json).map_err(|source| AppError::ConfigIoCtx {
+            operation: "write shutdown report",
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::stream_types::{StreamKind, StreamTransport};
+    use crate::db::{BatchKey, CommitLedger};
+    use chrono::TimeZone;
+
+    #[test]
+    fn report_reflects_known_run() {
+        let ledger = CommitLedger::new();
+        let key = |sym: &str| BatchKey {
+            exchange: "binance_linear".into(),
+            transport: StreamTransport::Ws,
+            kind: StreamKind::Trades,
+            stream: "trades".into(),
+            symbol: sym.into(),
+            derived: None,
+        };
+        let t = |s: u32| Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, s).unwrap();
+
+        // Known run: 3 flushes on shard a, 1 on shard b
+        ledger.record("a", &key("BTCUSDT"), 100, Some(t(10)));
+        ledger.record("a", &key("BTCUSDT"), 50, Some(t(20)));
+        ledger.record("a", &key("ETHUSDT"), 25, Some(t(15)));
+        ledger.record("b", &key("SOLUSDT"), 7, Some(t(30)));
+
+        let totals = DbWriteTotals {
+            rows_written: 182,
+            batches_written: 4,
+            batches_failed: 0,
+            batches_retried: 1,
+            rows_dropped: 0,
+            batches_lost: 0,
+            rows_unwritten: 0,
+        };
+        let pending = PendingBatchBudget::new(0, None);
+
+        let report = ShutdownReport::collect(
+            vec!["binance_linear:BTCUSDT:trades:ws".into()],
+            Some(ledger.snapshot()),
+            Some(totals),
+            Some(&pending),
+        );
+
+        assert_eq!(report.rows_written_total, 182);
+        assert_eq!(report.batches_flushed_total, 4);
+        assert_eq!(report.rows_written_by_shard["a"], 175);
+        assert_eq!(report.rows_written_by_shard["b"], 7);
+        assert_eq!(report.batches_flushed_by_shard["a"], 3);
+        assert_eq!(
+            report.final_watermarks["binance_linear:trades:BTCUSDT"],
+            t(20).to_rfc3339()
+        );
+        assert_eq!(report.final_watermarks.len(), 3);
+        assert_eq!(report.batches_retried, 1);
+        assert_eq!(report.streams_stopped.len(), 1);
+        assert!(!report.possible_data_loss());
+
+        // Rows left in a batch when the writer closed are reported as possible loss
+        pending.record(&key("BTCUSDT"), 0, 4096);
+        let report = ShutdownReport::collect(Vec::new(), None, None, Some(&pending));
+        assert_eq!(report.pending_batches_at_shutdown, 1);
+        assert_eq!(report.pending_bytes_at_shutdown, 4096);
+        assert!(report.possible_data_loss());
+
+        // Failed attempts a retry later wrote, and deduplicated repeats, are not loss
+        let retried = DbWriteTotals {
+            batches_failed: 3,
+            rows_dropped: 12,
+            ..totals
+        };
+        let report = ShutdownReport::collect(Vec::new(), None, Some(retried), None);
+        assert!(!report.possible_data_loss());
+
+        // ... but a batch dropped with its rows after a failed write is
+        let lost = DbWriteTotals {
+            batches_lost: 1,
+            rows_unwritten: 40,
+            ..retried
+        };
+        let report = ShutdownReport::collect(Vec::new(), None, Some(lost), None);
+        assert_eq!(report.rows_unwritten, 40);
+        assert!(report.possible_data_loss());
+
+        // ... as are streams shutdown stopped waiting for
+        let report = ShutdownReport {
+            streams_still_running: 1,
+            ..ShutdownReport::collect(Vec::new(), None, None, None)
+        };
+        assert!(report.possible_data_loss());
+    }
+}
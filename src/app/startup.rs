@@ -0,0 +1,287 @@
+//! app/startup.rs
+//!
+//! Startup orchestration: subsystems come up in a declared dependency order and the first
+//! failure stops startup with an error naming the subsystem (and what was already up),
+//! instead of surfacing later as an unrelated error in a dependent subsystem.
+//!
+//! - `STARTUP_PLAN` declares each subsystem and what must be up before it.
+//! - `Startup::step` runs one subsystem's init after checking its dependencies.
+//! - `Startup::encode_prometheus_text` exposes progress, so a metrics server started before
+//!   the rest (`prometheus.toml: start_early`) can report where startup stopped.
+
+use crate::error::{AppError, AppResult};
+use std::fmt::Write as _;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Subsystems in startup order, each with the subsystems it needs.
+/// A subsystem disabled in config still runs its step (it comes up as a no-op).
+pub const STARTUP_PLAN: &[(&str, &[&str])] = &[
+    ("config", &[]),
+    ("redis", &["config"]),
+    ("db", &["config"]),
+    ("http_clients", &["config"]),
+    ("ws_clients", &["config"]),
+    ("runtime_health", &["config"]),
+    ("instruments", &["http_clients"]),
+    (
+        "streams",
+        &["redis", "db", "ws_clients", "runtime_health", "instruments"],
+    ),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepState {
+    Pending,
+    Starting,
+    Up,
+    Failed,
+}
+
+impl StepState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            StepState::Pending => "pending",
+            StepState::Starting => "starting",
+            StepState::Up => "up",
+            StepState::Failed => "failed",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Step {
+    name: &'static str,
+    deps: &'static [&'static str],
+    state: StepState,
+}
+
+/// Tracks subsystem startup against a plan (shared between `main` and `AppRuntime::new`).
+#[derive(Debug)]
+pub struct Startup {
+    steps: Mutex<Vec<Step>>,
+}
+
+impl Default for Startup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Startup {
+    /// Startup following `STARTUP_PLAN`.
+    pub fn new() -> Self {
+        Self::with_plan(STARTUP_PLAN).expect("STARTUP_PLAN is well-formed")
+    }
+
+    /// Startup following `plan`. Dependencies must be declared before the subsystem that
+    /// needs them, so declaration order is always a valid startup order.
+    pub fn with_plan(plan: &[(&'static str, &'static [&'static str])]) -> AppResult<Self> {
+        let mut steps: Vec<Step> = Vec::with_capacity(plan.len());
+        for &(name, deps) in plan {
+            if steps.iter().any(|s| s.name == name) {
+                return Err(AppError::InvalidConfig(format!(
+                    "startup plan declares `{name}` twice"
+                )));
+            }
+            if let Some(dep) = deps.iter().find(|d| !steps.iter().any(|s| s.name == **d)) {
+                return Err(AppError::InvalidConfig(format!(
+                    "startup plan: `{name}` depends on `{dep}`, which is not declared before it"
+                )));
+            }
+            steps.push(Step {
+                name,
+                deps,
+                state: StepState::Pending,
+            });
+        }
+        Ok(Self {
+            steps: Mutex::new(steps),
+        })
+    }
+
+    /// Bring up `name` with `init`, once all its dependencies are up.
+    ///
+    /// Errors (missing dependency, or `init` failing) are `AppError::Startup` naming the
+    /// subsystem and the subsystems already up, in startup order.
+    pub async fn step<T, F>(&self, name: &'static str, init: F) -> AppResult<T>
+    where
+        F: Future<Output = AppResult<T>>,
+    {
+        {
+            let mut steps = self.steps.lock().expect("steps mutex poisoned");
+            let idx = steps.iter().position(|s| s.name == name).ok_or_else(|| {
+                AppError::Internal(format!("subsystem `{name}` is not in the startup plan"))
+            })?;
+
+            let deps = steps[idx].deps;
+            if let Some(dep) = deps.iter().find(|d| {
+                steps
+                    .iter()
+                    .any(|s| s.name == **d && s.state != StepState::Up)
+            }) {
+                steps[idx].state = StepState::Failed;
+                let up = Self::up_names(&steps);
+                return Err(AppError::Startup {
+                    subsystem: name,
+                    up,
+                    source: Box::new(AppError::Internal(format!("dependency `{dep}` is not up"))),
+                });
+            }
+            steps[idx].state = StepState::Starting;
+        }
+
+        tracing::info!(subsystem = name, "starting");
+        let t0 = Instant::now();
+        let res = init.await;
+
+        let mut steps = self.steps.lock().expect("steps mutex poisoned");
+        let state = if res.is_ok() {
+            StepState::Up
+        } else {
+            StepState::Failed
+        };
+        if let Some(s) = steps.iter_mut().find(|s| s.name == name) {
+            s.state = state;
+        }
+
+        match res {
+            Ok(v) => {
+                tracing::info!(
+                    subsystem = name,
+                    elapsed_ms = t0.elapsed().as_millis() as u64,
+                    "up"
+                );
+                Ok(v)
+            }
+            // Already attributed by a nested step
+            Err(e @ AppError::Startup { .. }) => Err(e),
+            Err(e) => {
+                let up = Self::up_names(&steps);
+                tracing::error!(subsystem = name, up = ?up, error = %e, "startup failed");
+                Err(AppError::Startup {
+                    subsystem: name,
+                    up,
+                    source: Box::new(e),
+                })
+            }
+        }
+    }
+
+    fn up_names(steps: &[Step]) -> Vec<&'static str> {
+        steps
+            .iter()
+            .filter(|s| s.state == StepState::Up)
+            .map(|s| s.name)
+            .collect()
+    }
+
+    /// Subsystems up so far, in startup order.
+    pub fn up(&self) -> Vec<&'static str> {
+        Self::up_names(&self.steps.lock().expect("steps mutex poisoned"))
+    }
+
+    /// First subsystem that failed, if any.
+    pub fn failed(&self) -> Option<&'static str> {
+        self.steps
+            .lock()
+            .expect("steps mutex poisoned")
+            .iter()
+            .find(|s| s.state == StepState::Failed)
+            .map(|s| s.name)
+    }
+
+    /// State of every subsystem, in startup order.
+    pub fn states(&self) -> Vec<(&'static str, StepState)> {
+        self.steps
+            .lock()
+            .expect("steps mutex poisoned")
+            .iter()
+            .map(|s| (s.name, s.state))
+            .collect()
+    }
+
+    /// Prometheus text: one `app_startup_subsystem_state` series per subsystem
+    /// (0=pending, 1=starting, 2=up, 3=failed).
+    pub fn encode_prometheus_text(&self) -> String {
+        let mut out = String::from(
+            "# HELP app_startup_subsystem_state Subsystem startup state (0=pending,1=starting,2=up,3=failed)\n\
+             # TYPE app_startup_subsystem_state gauge\n",
+        );
+        for (name, state) in self.states() {
+            let v = match state {
+                StepState::Pending => 0,
+                StepState::Starting => 1,
+                StepState::Up => 2,
+                StepState::Failed => 3,
+            };
+            let _ = writeln!(
+                out,
+                "app_startup_subsystem_state{{subsystem=\"{name}\"}} {v}"
+            );
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn failed_subsystem_is_named_with_what_was_up() {
+        let startup = Startup::new();
+
+        startup.step("config", async { Ok(()) }).await.unwrap();
+        startup.step("redis", async { Ok(()) }).await.unwrap();
+        let err = startup
+            .step("db", async {
+                Err::<(), _>(AppError::Internal("connection refused".into()))
+            })
+            .await
+            .expect_err("db init fails");
+
+        match &err {
+            AppError::Startup {
+                subsystem,
+                up,
+                source,
+            } => {
+                assert_eq!(*subsystem, "db");
+                assert_eq!(up, &vec!["config", "redis"]);
+                assert!(source.to_string().contains("connection refused"));
+            }
+            other => panic!("expected Startup error, got {other:?}"),
+        }
+        let msg = err.to_string();
+        assert!(
+            msg.contains("`db`") && msg.contains("connection refused"),
+            "{msg}"
+        );
+
+        // Dependents fail fast on the ordering check, without running their init
+        let err = startup
+            .step("streams", async { panic!("must not run") as AppResult<()> })
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("dependency `db` is not up"),
+            "{err}"
+        );
+
+        assert_eq!(startup.failed(), Some("db"));
+        let text = startup.encode_prometheus_text();
+        assert!(text.contains("app_startup_subsystem_state{subsystem=\"db\"} 3"));
+        assert!(text.contains("app_startup_subsystem_state{subsystem=\"redis\"} 2"));
+    }
+
+    #[test]
+    fn plan_must_declare_dependencies_first() {
+        assert!(Startup::with_plan(STARTUP_PLAN).is_ok());
+        assert!(
+            Startup::with_plan(&[("streams", &["instruments"]), ("instruments", &[])]).is_err()
+        );
+        assert!(Startup::with_plan(&[("config", &[]), ("config", &[])]).is_err());
+    }
+}
@@ -1,4 +1,6 @@
-use std::{collections::HashMap, sync::Arc};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use crate::app::dependencies::AppDeps;
 use crate::app::stream_types::{StreamId, StreamSpec, StreamStatus};
@@ -33,6 +35,13 @@ pub struct StreamKnobs {
     pub flush_interval_ms: u64,
     pub chunk_rows: usize,    // rows per SQL INSERT chunk
     pub hard_cap_rows: usize, // memory safety cap
+    /// Depth streams: derive and persist best bid/ask rows (`ex_<exchange>.bbo`).
+    #[serde(default)]
+    pub emit_bbo: bool,
+    /// Depth streams: derive and persist the microprice of each BBO row
+    /// (`ex_<exchange>.microprice`).
+    #[serde(default)]
+    pub emit_microprice: bool,
 }
 
 impl Default for StreamKnobs {
@@ -44,6 +53,8 @@ impl Default for StreamKnobs {
             flush_interval_ms: 1000,
             chunk_rows: 1000,
             hard_cap_rows: 5000,
+            emit_bbo: false,
+            emit_microprice: false,
         }
     }
 }
@@ -130,6 +141,14 @@ impl StreamHandle {
             .send_modify(|k| k.disable_redis_publishes = !enabled);
     }
 
+    pub fn set_emit_bbo(&self, enabled: bool) {
+        self.knobs.send_modify(|k| k.emit_bbo = enabled);
+    }
+
+    pub fn set_emit_microprice(&self, enabled: bool) {
+        self.knobs.send_modify(|k| k.emit_microprice = enabled);
+    }
+
     // ---------------------------
     // NEW: batching knob setters
     // ---------------------------
@@ -203,10 +222,30 @@ struct AppStateInner {
 ///
 /// - `shutdown`: cancels the whole app (propagate to streams).
 /// - `inner.streams`: registry of currently running stream tasks.
+/// - `starting`: streams between the duplicate check and `insert` (see `reserve_start`).
 #[derive(Clone, Debug)]
 pub struct AppState {
     pub shutdown: CancellationToken,
     inner: Arc<RwLock<AppStateInner>>,
+    starting: Arc<Mutex<HashSet<StreamId>>>,
+}
+
+/// Claim on a stream id while its start is in progress; released on drop.
+/// Held until the handle is inserted, so a concurrent start of the same stream is refused
+/// before it opens a second connection.
+#[derive(Debug)]
+pub struct StartReservation {
+    starting: Arc<Mutex<HashSet<StreamId>>>,
+    id: StreamId,
+}
+
+impl Drop for StartReservation {
+    fn drop(&mut self) {
+        self.starting
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.id);
+    }
 }
 
 impl AppState {
@@ -214,6 +253,7 @@ impl AppState {
         Self {
             shutdown: CancellationToken::new(),
             inner: Arc::new(RwLock::new(AppStateInner::default())),
+            starting: Arc::new(Mutex::new(HashSet::new())),
         }
     }
     // ---------------------------
@@ -250,6 +290,29 @@ impl AppState {
             .collect()
     }
 
+    /// Claim `id` for a stream start: `StreamAlreadyExists` if it is running or being started.
+    /// Keep the reservation until `insert` returned.
+    pub async fn reserve_start(&self, id: &StreamId) -> AppResult<StartReservation> {
+        // Claim first, then check the running set: a start that finished meanwhile has
+        // already inserted its handle, one still in progress holds the claim.
+        if !self
+            .starting
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(id.clone())
+        {
+            return Err(AppError::StreamAlreadyExists(id.to_string()));
+        }
+        let reservation = StartReservation {
+            starting: Arc::clone(&self.starting),
+            id: id.clone(),
+        };
+        if self.contains(id).await {
+            return Err(AppError::StreamAlreadyExists(id.to_string()));
+        }
+        Ok(reservation)
+    }
+
     /// Insert a newly spawned stream handle.
     /// Returns Err if the stream id already exists.
     pub async fn insert(&self, id: StreamId, handle: StreamHandle) -> AppResult<()> {
@@ -320,12 +383,32 @@ impl AppState {
     // Shutdown helpers
     // --------------------------------------------------
 
-    /// Cancel all streams (does not await join handles).
-    pub async fn cancel_all_streams(&self) {
-        let inner = self.inner.read().await;
-        for (_, h) in inner.streams.iter() {
-            h.cancel.cancel();
+    /// Cancel all streams, then wait up to `join_timeout` for their tasks (final batch
+    /// flushes included) to end. Returns how many tasks were still running; those are left
+    /// detached.
+    pub async fn cancel_all_streams(&self, join_timeout: Duration) -> usize {
+        let tasks: Vec<JoinHandle<()>> = {
+            let mut inner = self.inner.write().await;
+            inner
+                .streams
+                .values_mut()
+                .flat_map(|h| {
+                    h.status = StreamStatus::Stopping;
+                    h.cancel.cancel();
+                    h.task.take().into_iter().chain(h.knobs_tasks.drain(..))
+                })
+                .collect()
+        };
+
+        // One deadline for all of them, awaited outside the lock
+        let deadline = tokio::time::Instant::now() + join_timeout;
+        let mut still_running = 0;
+        for mut task in tasks {
+            if tokio::time::timeout_at(deadline, &mut task).await.is_err() {
+                still_running += 1;
+            }
         }
+        still_running
     }
 
     /// Cancel app shutdown token (call once on graceful shutdown).
@@ -430,6 +513,28 @@ impl AppState {
         self.set_redis_publishes_enabled(id, true).await
     }
 
+    // ---------------------------
+    // BBO / microprice emission
+    // ---------------------------
+
+    pub async fn set_emit_bbo(&self, id: &StreamId, enabled: bool) -> AppResult<bool> {
+        let inner = self.inner.read().await;
+        let Some(h) = inner.streams.get(id) else {
+            return Ok(false);
+        };
+        h.set_emit_bbo(enabled);
+        Ok(true)
+    }
+
+    pub async fn set_emit_microprice(&self, id: &StreamId, enabled: bool) -> AppResult<bool> {
+        let inner = self.inner.read().await;
+        let Some(h) = inner.streams.get(id) else {
+            return Ok(false);
+        };
+        h.set_emit_microprice(enabled);
+        Ok(true)
+    }
+
     // ---------------------------
     // Batching knobs
     // ---------------------------
@@ -486,3 +591,107 @@ impl AppState {
         Ok(true)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::stream_types::{StreamKind, StreamTransport};
+
+    fn handle(spec: &StreamSpec) -> StreamHandle {
+        let (knobs, _) = tokio::sync::watch::channel(StreamKnobs::default());
+        StreamHandle::new(
+            spec.clone(),
+            StreamStatus::Running,
+            CancellationToken::new(),
+            tokio::spawn(async {}),
+            knobs,
+            vec![],
+        )
+    }
+
+    #[tokio::test]
+    async fn starting_a_running_or_starting_stream_is_refused() {
+        let state = AppState::new();
+        let spec = StreamSpec {
+            exchange: "binance_linear",
+            instrument: "BTCUSDT".into(),
+            kind: StreamKind::Trades,
+            transport: StreamTransport::Ws,
+        };
+        let id = StreamId::new(spec.exchange, &spec.instrument, spec.kind, spec.transport);
+
+        // Two starts racing: the second is refused while the first is in progress
+        let first = state.reserve_start(&id).await.unwrap();
+        assert!(matches!(
+            state.reserve_start(&id).await,
+            Err(AppError::StreamAlreadyExists(_))
+        ));
+
+        // ... and once it runs
+        state.insert(id.clone(), handle(&spec)).await.unwrap();
+        drop(first);
+        assert!(matches!(
+            state.reserve_start(&id).await,
+            Err(AppError::StreamAlreadyExists(_))
+        ));
+
+        // A failed start releases its claim; a stopped stream can be started again
+        state.remove(&id).await;
+        drop(state.reserve_start(&id).await.unwrap());
+        assert!(state.reserve_start(&id).await.is_ok());
+
+        // Other streams of the symbol are unaffected
+        let depth = StreamId::new(
+            spec.exchange,
+            &spec.instrument,
+            StreamKind::L2Book,
+            spec.transport,
+        );
+        assert!(state.reserve_start(&depth).await.is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn shutdown_waits_for_stream_tasks_up_to_the_timeout() {
+        let state = AppState::new();
+        let spec = StreamSpec {
+            exchange: "binance_linear",
+            instrument: "BTCUSDT".into(),
+            kind: StreamKind::Trades,
+            transport: StreamTransport::Ws,
+        };
+        let flushed = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let insert = |kind, flush: Duration| {
+            let spec = StreamSpec {
+                kind,
+                ..spec.clone()
+            };
+            let cancel = CancellationToken::new();
+            let flushed = Arc::clone(&flushed);
+            let task = tokio::spawn({
+                let cancel = cancel.clone();
+                async move {
+                    cancel.cancelled().await;
+                    // Final flush after the stream loop ended
+                    tokio::time::sleep(flush).await;
+                    flushed.store(true, std::sync::atomic::Ordering::SeqCst);
+                }
+            });
+            let (knobs, _) = tokio::sync::watch::channel(StreamKnobs::default());
+            let id = StreamId::new(spec.exchange, &spec.instrument, kind, spec.transport);
+            (
+                id,
+                StreamHandle::new(spec, StreamStatus::Running, cancel, task, knobs, vec![]),
+            )
+        };
+
+        let (id, h) = insert(StreamKind::Trades, Duration::from_millis(500));
+        state.insert(id, h).await.unwrap();
+        assert_eq!(state.cancel_all_streams(Duration::from_secs(5)).await, 0);
+        assert!(flushed.load(std::sync::atomic::Ordering::SeqCst));
+
+        // A task outliving the timeout is counted and left behind
+        let (id, h) = insert(StreamKind::L2Book, Duration::from_secs(60));
+        state.insert(id, h).await.unwrap();
+        assert_eq!(state.cancel_all_streams(Duration::from_secs(5)).await, 1);
+    }
+}
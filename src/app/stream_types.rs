@@ -6,6 +6,26 @@ pub enum ExchangeId {
     HyperliquidPerp,
 }
 
+impl ExchangeId {
+    /// Every exchange, in declaration order.
+    pub const ALL: &'static [ExchangeId] =
+        &[ExchangeId::BinanceLinear, ExchangeId::HyperliquidPerp];
+
+    /// Canonical name: WS/HTTP client name, instrument registry key, Redis key and DB schema
+    /// token (`ex_<name>`). The only place these strings are spelled out.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExchangeId::BinanceLinear => "binance_linear",
+            ExchangeId::HyperliquidPerp => "hyperliquid_perp",
+        }
+    }
+
+    /// Inverse of `as_str`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|ex| ex.as_str() == name)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub enum StreamTransport {
     Ws,
@@ -60,3 +80,32 @@ pub struct StreamSpec {
     pub kind: StreamKind,
     pub transport: StreamTransport,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn every_exchange_id_has_a_name_and_round_trips() {
+        // No wildcard arm: a new variant does not compile until it is placed here and in `ALL`.
+        let position = |ex: ExchangeId| match ex {
+            ExchangeId::BinanceLinear => 0,
+            ExchangeId::HyperliquidPerp => 1,
+        };
+        for (i, ex) in ExchangeId::ALL.iter().copied().enumerate() {
+            assert_eq!(position(ex), i, "{ex:?} out of place in ExchangeId::ALL");
+
+            let name = ex.as_str();
+            assert!(!name.is_empty());
+            assert_eq!(ExchangeId::from_name(name), Some(ex));
+            assert_eq!(ExchangeId::from_str(name).unwrap(), ex);
+            assert_eq!(ex.to_string(), name);
+        }
+        assert_eq!(
+            ExchangeId::ALL.len(),
+            position(ExchangeId::HyperliquidPerp) + 1
+        );
+        assert_eq!(ExchangeId::from_name("binance"), None);
+    }
+}
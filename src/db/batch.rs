@@ -1,17 +1,35 @@
-use crate::db::config::WriterConfig;
-use std::time::Instant;
+use crate::app::stream_types::{StreamKind, StreamTransport};
+use crate::db::budget::PendingBatchBudget;
+use crate::db::config::{ValueRange, WriterConfig};
+use crate::db::traits::BatchInsertRow;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 /// Key used for sharding + dynamic table selection.
+///
+/// One key per (exchange, transport, kind, symbol): each kind of a symbol batches and
+/// flushes on its own. `stream` is the config endpoint key derived from the first three
+/// (see `make_batch_key`) and is what shard rules match on.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct BatchKey {
     pub exchange: String,
+    pub transport: StreamTransport,
+    pub kind: StreamKind,
     pub stream: String,
     pub symbol: String,
+    /// Series derived from the stream ("bbo", "microprice", "trade_imbalance"); None for the
+    /// stream's own rows. A derived series batches (and is budgeted / watermarked) on its
+    /// own, but routes like its source: shard rules only see exchange/stream/symbol.
+    pub derived: Option<&'static str>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct Batch<T> {
     pub key: BatchKey,
+    /// Monotonic start of the current fill. `Instant` never goes backwards, but whether it
+    /// advances while the host is suspended is platform-specific, so after a sleep/VM pause
+    /// `elapsed()` can be far larger than any real wait (see `flush_delay`).
     pub enqueued_at: Instant,
     pub rows: Vec<T>,
 
@@ -26,6 +44,53 @@ pub struct Batch<T> {
 
     /// Maximum rows per db insert
     pub chunk_rows: usize,
+
+    /// Keep committed rows for `take_persisted` (`writer.publish_persisted_only`)
+    pub track_persisted: bool,
+
+    /// Rows committed by past flushes, not yet taken (only when `track_persisted`).
+    persisted: Vec<T>,
+
+    /// Global pending-memory accounting (shared across all batches).
+    budget: Option<Arc<PendingBatchBudget>>,
+
+    /// Size last reported to `budget`.
+    budget_bytes: usize,
+
+    /// The last write of the held rows failed (cleared by a successful flush).
+    write_failed: bool,
+}
+
+// Manual Clone: a clone is a detached copy and must not share (and later release)
+// the original's budget accounting.
+impl<T: Clone> Clone for Batch<T> {
+    fn clone(&self) -> Self {
+        Self {
+            key: self.key.clone(),
+            enqueued_at: self.enqueued_at,
+            rows: self.rows.clone(),
+            flush_rows: self.flush_rows,
+            flush_interval_ms: self.flush_interval_ms,
+            hard_cap_rows: self.hard_cap_rows,
+            chunk_rows: self.chunk_rows,
+            track_persisted: self.track_persisted,
+            persisted: Vec::new(),
+            budget: None,
+            budget_bytes: 0,
+            write_failed: false,
+        }
+    }
+}
+
+impl<T> Drop for Batch<T> {
+    fn drop(&mut self) {
+        if let Some(b) = &self.budget {
+            b.release(&self.key, self.budget_bytes);
+            if !self.rows.is_empty() {
+                b.unwritten(self.rows.len(), self.write_failed);
+            }
+        }
+    }
 }
 
 impl<T> Batch<T> {
@@ -43,6 +108,11 @@ impl<T> Batch<T> {
             flush_interval_ms,
             hard_cap_rows,
             chunk_rows,
+            track_persisted: false,
+            persisted: Vec::new(),
+            budget: None,
+            budget_bytes: 0,
+            write_failed: false,
         };
 
         // Ensure we respect cap even if rows is pre-filled
@@ -67,15 +137,50 @@ impl<T> Batch<T> {
         self.enforce_cap();
     }
 
+    /// Append rows. The first rows into an empty batch start its fill timer, so time the
+    /// batch sat empty (e.g. after `take_rows`) does not count toward the flush interval.
     pub fn extend(&mut self, rows: Vec<T>) {
+        if self.rows.is_empty() && !rows.is_empty() {
+            self.enqueued_at = Instant::now();
+        }
         self.rows.extend(rows);
+        self.sync_budget();
     }
 
-    fn enforce_cap(&mut self) {
+    /// Drop the oldest rows over `hard_cap_rows`.
+    pub fn enforce_cap(&mut self) {
         if self.rows.len() > self.hard_cap_rows {
             let excess = self.rows.len() - self.hard_cap_rows;
             self.rows.drain(0..excess); // drop oldest overflow only
         }
+        self.sync_budget();
+    }
+
+    /// Register this batch with the global pending-memory budget (idempotent).
+    pub fn attach_budget(&mut self, budget: Arc<PendingBatchBudget>) {
+        if self.budget.is_none() {
+            self.budget = Some(budget);
+        }
+        self.sync_budget();
+    }
+
+    /// Approximate bytes held by buffered rows (inline size only).
+    pub fn approx_bytes(&self) -> usize {
+        self.rows.len() * std::mem::size_of::<T>()
+    }
+
+    /// Report current size to the budget. Call after mutating `rows` directly.
+    pub fn sync_budget(&mut self) {
+        if let Some(b) = &self.budget {
+            let bytes = self.approx_bytes();
+            b.record(&self.key, self.budget_bytes, bytes);
+            self.budget_bytes = bytes;
+        }
+    }
+
+    /// True if the global budget picked this batch to flush early.
+    pub fn forced_by_budget(&self) -> bool {
+        self.budget.as_ref().is_some_and(|b| b.is_forced(&self.key))
     }
 
     /// Flush decision uses internal knobs (no args).
@@ -86,13 +191,50 @@ impl<T> Batch<T> {
 
         let flush_due = (self.enqueued_at.elapsed().as_millis() as u64) >= self.flush_interval_ms;
 
-        self.rows.len() >= self.flush_rows || flush_due
+        self.rows.len() >= self.flush_rows || flush_due || self.forced_by_budget()
+    }
+
+    /// Time from `enqueued_at` to `now`, capped at `max` (zero = uncapped).
+    /// The flag is true when the cap applied, i.e. the elapsed time was implausible.
+    pub fn flush_delay(&self, now: Instant, max: Duration) -> (Duration, bool) {
+        let elapsed = now.saturating_duration_since(self.enqueued_at);
+        if !max.is_zero() && elapsed > max {
+            (max, true)
+        } else {
+            (elapsed, false)
+        }
     }
 
     /// Move buffered rows out (empties the batch) and resets timer.
     pub fn take_rows(&mut self) -> Vec<T> {
         self.enqueued_at = Instant::now();
-        std::mem::take(&mut self.rows)
+        let rows = std::mem::take(&mut self.rows);
+        self.sync_budget();
+        rows
+    }
+
+    /// Clear rows after a successful write and reset the timer.
+    /// With `track_persisted`, the rows move to the persisted set instead.
+    pub fn clear_flushed(&mut self) {
+        if self.track_persisted {
+            self.persisted.append(&mut self.rows);
+        } else {
+            self.rows.clear();
+        }
+        self.enqueued_at = Instant::now();
+        self.write_failed = false;
+        self.sync_budget();
+    }
+
+    /// A write of the held rows failed: if the batch is dropped before a later flush
+    /// succeeds, it counts as lost (see `PendingBatchBudget::unwritten`).
+    pub fn mark_write_failed(&mut self) {
+        self.write_failed = true;
+    }
+
+    /// Rows committed since the last call (empty unless `track_persisted`).
+    pub fn take_persisted(&mut self) -> Vec<T> {
+        std::mem::take(&mut self.persisted)
     }
 
     /// Call when you clear manually on success (alternative to take_rows()).
@@ -109,3 +251,197 @@ impl<T> Batch<T> {
     }
 }
 
+impl<T: BatchInsertRow> Batch<T> {
+    /// Drop pending rows whose `dedup_key` already appeared in this batch (first one wins).
+    /// Returns the number of rows dropped.
+    pub fn dedup_pending(&mut self) -> u64 {
+        let before = self.rows.len();
+        let mut seen = HashSet::new();
+        self.rows
+            .retain(|row| row.dedup_key().is_none_or(|id| seen.insert(id)));
+        let dropped = (before - self.rows.len()) as u64;
+        if dropped > 0 {
+            self.sync_budget();
+        }
+        dropped
+    }
+
+    /// `clear_flushed`, keeping for `take_persisted` only the rows whose `dedup_key` the insert
+    /// reported back (the others were skipped by `ON CONFLICT DO NOTHING`); rows without a key
+    /// are always kept.
+    pub fn clear_flushed_inserted(&mut self, inserted: &HashSet<i64>) {
+        self.rows
+            .retain(|row| row.dedup_key().is_none_or(|id| inserted.contains(&id)));
+        self.clear_flushed();
+    }
+
+    /// Drop pending rows with a scaled value outside its `writer.value_ranges` entry.
+    /// Returns the rejected rows, e.g. for logging.
+    pub fn reject_out_of_range(&mut self, ranges: &BTreeMap<String, ValueRange>) -> Vec<T> {
+        if ranges.is_empty() {
+            return Vec::new();
+        }
+        let in_range = |row: &T| {
+            ranges
+                .iter()
+                .all(|(column, r)| row.scaled_value(column).is_none_or(|v| r.contains(v)))
+        };
+        if self.rows.iter().all(in_range) {
+            return Vec::new();
+        }
+        let (kept, rejected): (Vec<T>, Vec<T>) = std::mem::take(&mut self.rows)
+            .into_iter()
+            .partition(in_range);
+        self.rows = kept;
+        if !rejected.is_empty() {
+            self.sync_budget();
+        }
+        rejected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::control::make_batch_key;
+    use crate::app::stream_types::ExchangeId;
+    use crate::db::rows::TradeDBRow;
+
+    fn trade(id: i64, price_i: i64, qty_i: i64) -> TradeDBRow {
+        TradeDBRow {
+            time: chrono::Utc::now(),
+            symbol: "BTCUSDT".into(),
+            side: 0,
+            price_i,
+            qty_i,
+            trade_id: Some(id),
+            is_maker: None,
+        }
+    }
+
+    #[test]
+    fn out_of_range_rows_are_rejected_before_insert() {
+        let key = make_batch_key(
+            ExchangeId::BinanceLinear,
+            StreamTransport::Ws,
+            StreamKind::Trades,
+            "BTCUSDT",
+        )
+        .unwrap();
+        let mut cfg = WriterConfig::default();
+        cfg.value_ranges.insert(
+            "price_i".into(),
+            ValueRange {
+                min: Some(1),
+                max: Some(10_000_000_000_000_000),
+            },
+        );
+        cfg.value_ranges.insert(
+            "qty_i".into(),
+            ValueRange {
+                min: Some(1),
+                max: None,
+            },
+        );
+
+        let mut batch = Batch::new(key, vec![], &cfg);
+        batch.track_persisted = true;
+        batch.extend(vec![
+            trade(1, 9_000_000_000_000, 1_000),
+            trade(2, 0, 1_000),             // price 0
+            trade(3, 9_000_000_000_000, 0), // qty 0
+            trade(4, i64::MAX, 1_000),      // absurd price
+            trade(5, 9_000_100_000_000, 2_000),
+        ]);
+
+        // What `DbHandler::write_batch` does before the insert
+        let rejected = batch.reject_out_of_range(&cfg.value_ranges);
+        assert_eq!(
+            rejected
+                .iter()
+                .filter_map(|r| r.trade_id)
+                .collect::<Vec<_>>(),
+            vec![2, 3, 4]
+        );
+        assert_eq!(
+            batch
+                .rows
+                .iter()
+                .filter_map(|r| r.trade_id)
+                .collect::<Vec<_>>(),
+            vec![1, 5]
+        );
+
+        // Rejected rows are never reported as persisted
+        batch.clear_flushed();
+        assert_eq!(batch.take_persisted().len(), 2);
+
+        // No ranges configured: nothing is checked
+        batch.extend(vec![trade(6, 0, 0)]);
+        assert!(batch.reject_out_of_range(&BTreeMap::new()).is_empty());
+        assert_eq!(batch.len(), 1);
+    }
+
+    #[test]
+    fn only_rows_the_insert_reported_are_kept_for_publish() {
+        let key = make_batch_key(
+            ExchangeId::BinanceLinear,
+            StreamTransport::Ws,
+            StreamKind::Trades,
+            "BTCUSDT",
+        )
+        .unwrap();
+        let mut batch = Batch::new(key, vec![], &WriterConfig::default());
+        batch.track_persisted = true;
+        let mut unkeyed = trade(0, 100, 1);
+        unkeyed.trade_id = None;
+        batch.extend(vec![trade(1, 100, 1), trade(2, 100, 1), unkeyed]);
+
+        // Trade 1 was already stored (skipped by ON CONFLICT DO NOTHING)
+        batch.clear_flushed_inserted(&HashSet::from([2]));
+        assert!(batch.is_empty());
+        assert_eq!(
+            batch
+                .take_persisted()
+                .iter()
+                .map(|r| r.trade_id)
+                .collect::<Vec<_>>(),
+            vec![Some(2), None]
+        );
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn dropping_rows_after_a_failed_write_counts_them_lost() {
+        use crate::db::metrics::DbMetrics;
+
+        let key = make_batch_key(
+            ExchangeId::BinanceLinear,
+            StreamTransport::Ws,
+            StreamKind::Trades,
+            "BTCUSDT",
+        )
+        .unwrap();
+        let metrics = Arc::new(DbMetrics::new().unwrap());
+        let budget = Arc::new(PendingBatchBudget::new(0, Some(metrics.clone())));
+
+        // Failed, then written by a later flush: nothing lost
+        let mut batch = Batch::new(key.clone(), vec![], &WriterConfig::default());
+        batch.attach_budget(budget.clone());
+        batch.extend(vec![trade(1, 100, 1)]);
+        batch.mark_write_failed();
+        batch.clear_flushed();
+        drop(batch);
+        assert_eq!(metrics.write_totals().batches_lost, 0);
+        assert_eq!(metrics.write_totals().rows_unwritten, 0);
+
+        // Failed and dropped still holding its rows
+        let mut batch = Batch::new(key, vec![], &WriterConfig::default());
+        batch.attach_budget(budget);
+        batch.extend(vec![trade(2, 100, 1), trade(3, 100, 1)]);
+        batch.mark_write_failed();
+        drop(batch);
+        assert_eq!(metrics.write_totals().batches_lost, 1);
+        assert_eq!(metrics.write_totals().rows_unwritten, 2);
+    }
+}
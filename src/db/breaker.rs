@@ -0,0 +1,360 @@
+//! db/breaker.rs
+//!
+//! Per-shard circuit breaker.
+//!
+//! A shard whose Postgres is down makes every write routed to it wait for the connect
+//! timeout. Once its failure score reaches `trip_after_failures` the breaker opens and
+//! `DbPools::pool_by_id` fails fast for that shard only; after `cooldown_ms` one request goes
+//! through as a half-open probe: success closes the breaker, failure re-opens it for another
+//! cooldown.
+//!
+//! The score is not the consecutive count: each failure adds 1, the score halves every
+//! `score_half_life_ms` and a success keeps only `score_keep_on_success` of it. With no decay
+//! and keep 0 it is the consecutive count. With a share kept across successes, a shard that
+//! fails once every few writes (intermittent) builds up a score and eventually trips, while a
+//! single blip among many successes fades away. Errors reported by the server itself (constraint, missing table, ...)
+//! mean the shard is up and count as a success. A pool timeout while every connection is
+//! checked out is local saturation: it never counts, and a probe that hits it is handed to the
+//! next request.
+
+use crate::db::config::ShardBreakerConfig;
+use crate::error::{AppError, AppResult};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    Closed,
+    Open,
+    /// Cooldown over, one probe request in flight.
+    HalfOpen,
+}
+
+#[derive(Debug, Default)]
+struct Breaker {
+    score: f64,
+    score_at: Option<Instant>,
+    open_until: Option<Instant>,
+    probing: bool,
+}
+
+impl Breaker {
+    /// Failure score at `now` (time decay applied).
+    fn score_at(&self, now: Instant, half_life: Duration) -> f64 {
+        match self.score_at {
+            Some(at) if !half_life.is_zero() => {
+                let elapsed = now.saturating_duration_since(at).as_secs_f64();
+                self.score * 0.5_f64.powf(elapsed / half_life.as_secs_f64())
+            }
+            _ => self.score,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ShardBreakers {
+    trip_after: u32,
+    cooldown: Duration,
+    score_half_life: Duration,
+    score_keep_on_success: f64,
+    by_shard: Mutex<HashMap<String, Breaker>>,
+}
+
+impl ShardBreakers {
+    /// `trip_after` of 0 disables the breakers. Trips on consecutive failures until
+    /// `with_score_decay`.
+    pub fn new(trip_after: u32, cooldown: Duration) -> Self {
+        Self {
+            trip_after,
+            cooldown,
+            score_half_life: Duration::ZERO,
+            score_keep_on_success: 0.0,
+            by_shard: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Decay the failure score over time (`half_life`, zero = off) and across successes
+    /// (`keep_on_success` in [0, 1)).
+    pub fn with_score_decay(mut self, half_life: Duration, keep_on_success: f64) -> Self {
+        self.score_half_life = half_life;
+        self.score_keep_on_success = keep_on_success.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn from_config(cfg: &ShardBreakerConfig) -> Self {
+        Self::new(
+            cfg.trip_after_failures,
+            Duration::from_millis(cfg.cooldown_ms),
+        )
+        .with_score_decay(
+            Duration::from_millis(cfg.score_half_life_ms),
+            cfg.score_keep_on_success,
+        )
+    }
+
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.trip_after > 0
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Breaker>> {
+        self.by_shard.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Ok if a request may go to `shard_id`; the first call after the cooldown is the probe.
+    pub fn check(&self, shard_id: &str) -> AppResult<()> {
+        self.check_at(shard_id, Instant::now())
+    }
+
+    pub fn check_at(&self, shard_id: &str, now: Instant) -> AppResult<()> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        let mut map = self.lock();
+        let Some(b) = map.get_mut(shard_id) else {
+            return Ok(());
+        };
+        let Some(until) = b.open_until else {
+            return Ok(());
+        };
+        if now < until {
+            return Err(AppError::Internal(format!("shard {shard_id} circuit open")));
+        }
+        // Half-open: let this one through; a probe that never reports is retried a cooldown later
+        b.open_until = Some(now + self.cooldown);
+        b.probing = true;
+        Ok(())
+    }
+
+    /// A success: the half-open probe's closes the breaker and clears the score; otherwise the
+    /// failure score keeps its configured share. A success while open (a request started
+    /// before the trip) is ignored.
+    pub fn on_success(&self, shard_id: &str) {
+        self.on_success_at(shard_id, Instant::now())
+    }
+
+    pub fn on_success_at(&self, shard_id: &str, now: Instant) {
+        if !self.is_enabled() {
+            return;
+        }
+        if let Some(b) = self.lock().get_mut(shard_id) {
+            if b.probing {
+                tracing::info!(shard = shard_id, "shard circuit closed");
+                *b = Breaker::default();
+                return;
+            }
+            if b.open_until.is_some() {
+                return;
+            }
+            b.score = b.score_at(now, self.score_half_life) * self.score_keep_on_success;
+            b.score_at = Some(now);
+        }
+    }
+
+    /// The request ended without saying anything about the shard (local pool saturation).
+    /// A half-open probe is released so the next request probes instead of waiting a cooldown.
+    pub fn on_inconclusive(&self, shard_id: &str) {
+        self.on_inconclusive_at(shard_id, Instant::now())
+    }
+
+    pub fn on_inconclusive_at(&self, shard_id: &str, now: Instant) {
+        if !self.is_enabled() {
+            return;
+        }
+        if let Some(b) = self.lock().get_mut(shard_id)
+            && b.probing
+        {
+            b.probing = false;
+            b.open_until = Some(now);
+        }
+    }
+
+    /// A failed acquire/execute that says nothing about the shard being up.
+    pub fn on_failure(&self, shard_id: &str) {
+        self.on_failure_at(shard_id, Instant::now())
+    }
+
+    pub fn on_failure_at(&self, shard_id: &str, now: Instant) {
+        if !self.is_enabled() {
+            return;
+        }
+        let mut map = self.lock();
+        let b = map.entry(shard_id.to_string()).or_default();
+        b.score = b.score_at(now, self.score_half_life) + 1.0;
+        b.score_at = Some(now);
+        if b.probing || (b.open_until.is_none() && b.score >= self.trip_after as f64) {
+            if b.open_until.is_none() {
+                tracing::warn!(
+                    shard = shard_id,
+                    score = b.score,
+                    cooldown_ms = self.cooldown.as_millis() as u64,
+                    "shard circuit open: requests to it fail fast until a probe succeeds"
+                );
+            }
+            b.open_until = Some(now + self.cooldown);
+            b.probing = false;
+        }
+    }
+
+    pub fn state(&self, shard_id: &str) -> BreakerState {
+        match self.lock().get(shard_id) {
+            Some(b) if b.probing => BreakerState::HalfOpen,
+            Some(b) if b.open_until.is_some() => BreakerState::Open,
+            _ => BreakerState::Closed,
+        }
+    }
+}
+
+/// True if `e` may mean the shard is unreachable. The server answering with an error does not,
+/// nor does a pool timeout while the pool is `saturated` (every connection checked out); a
+/// timeout with free slots was spent connecting and does.
+pub fn counts_against_shard(e: &sqlx::Error, saturated: bool) -> bool {
+    match e {
+        sqlx::Error::Database(_) => false,
+        sqlx::Error::PoolTimedOut => !saturated,
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn breaker_opens_fails_fast_and_probes_after_cooldown() {
+        let b = ShardBreakers::new(3, Duration::from_secs(10));
+        let t0 = Instant::now();
+        let at = |secs| t0 + Duration::from_secs(secs);
+
+        // Below the threshold, and a success resets the count
+        b.on_failure_at("shard0", t0);
+        b.on_failure_at("shard0", t0);
+        b.on_success("shard0");
+        b.on_failure_at("shard0", t0);
+        b.on_failure_at("shard0", t0);
+        assert!(b.check_at("shard0", t0).is_ok());
+        assert_eq!(b.state("shard0"), BreakerState::Closed);
+
+        // Third consecutive failure opens it; other shards are unaffected
+        b.on_failure_at("shard0", t0);
+        assert_eq!(b.state("shard0"), BreakerState::Open);
+        assert!(matches!(
+            b.check_at("shard0", at(1)),
+            Err(AppError::Internal(m)) if m.contains("shard0")
+        ));
+        assert!(b.check_at("shard1", at(1)).is_ok());
+
+        // After the cooldown one probe goes through, the next request still fails fast
+        assert!(b.check_at("shard0", at(10)).is_ok());
+        assert_eq!(b.state("shard0"), BreakerState::HalfOpen);
+        assert!(b.check_at("shard0", at(10)).is_err());
+
+        // Failed probe: open for another cooldown
+        b.on_failure_at("shard0", at(11));
+        assert_eq!(b.state("shard0"), BreakerState::Open);
+        assert!(b.check_at("shard0", at(20)).is_err());
+
+        // Successful probe closes it
+        assert!(b.check_at("shard0", at(21)).is_ok());
+        b.on_success("shard0");
+        assert_eq!(b.state("shard0"), BreakerState::Closed);
+        assert!(b.check_at("shard0", at(21)).is_ok());
+
+        // A probe that times out on a saturated pool is handed to the next request
+        b.on_failure_at("shard0", at(21));
+        b.on_failure_at("shard0", at(21));
+        b.on_failure_at("shard0", at(21));
+        assert!(b.check_at("shard0", at(31)).is_ok());
+        assert!(!counts_against_shard(&sqlx::Error::PoolTimedOut, true));
+        b.on_inconclusive_at("shard0", at(31));
+        assert!(b.check_at("shard0", at(31)).is_ok());
+        assert_eq!(b.state("shard0"), BreakerState::HalfOpen);
+
+        // Disabled
+        let off = ShardBreakers::new(0, Duration::from_secs(10));
+        (0..10).for_each(|_| off.on_failure("shard0"));
+        assert!(off.check("shard0").is_ok());
+    }
+
+    #[test]
+    fn decaying_score_trips_on_intermittent_failures() {
+        let t0 = Instant::now();
+        let at = |secs| t0 + Duration::from_secs(secs);
+        // Two failures, then a good write, over and over
+        let cycle = |b: &ShardBreakers, t| {
+            b.on_failure_at("shard0", t);
+            b.on_failure_at("shard0", t);
+            let open = b.state("shard0") == BreakerState::Open;
+            b.on_success_at("shard0", t);
+            open
+        };
+
+        // Consecutive count only: never more than 2 in a row, the breaker never opens
+        let b = ShardBreakers::new(5, Duration::from_secs(10));
+        assert!(!(0..50).any(|i| cycle(&b, at(i))));
+
+        // Decaying score: the intermittent failures add up and the breaker opens
+        let b =
+            ShardBreakers::new(5, Duration::from_secs(10)).with_score_decay(Duration::ZERO, 0.8);
+        let opened_at = (0..50).find(|i| cycle(&b, at(*i)));
+        assert!(matches!(opened_at, Some(n) if n > 0), "{opened_at:?}");
+
+        // A single failure among many successes fades away
+        let b =
+            ShardBreakers::new(2, Duration::from_secs(10)).with_score_decay(Duration::ZERO, 0.8);
+        b.on_failure_at("shard0", t0);
+        (0..20).for_each(|_| b.on_success_at("shard0", t0));
+        b.on_failure_at("shard0", t0);
+        assert_eq!(b.state("shard0"), BreakerState::Closed);
+
+        // Time decay: the score halves every half-life (4 -> 1 after two, +1 stays below 5)
+        let b = ShardBreakers::new(5, Duration::from_secs(10))
+            .with_score_decay(Duration::from_secs(60), 0.0);
+        (0..4).for_each(|_| b.on_failure_at("shard0", t0));
+        b.on_failure_at("shard0", at(120));
+        assert_eq!(b.state("shard0"), BreakerState::Closed);
+        (0..3).for_each(|_| b.on_failure_at("shard0", at(120)));
+        assert_eq!(b.state("shard0"), BreakerState::Open);
+
+        // A successful probe starts from a clean slate
+        assert!(b.check_at("shard0", at(130)).is_ok());
+        b.on_success_at("shard0", at(130));
+        b.on_failure_at("shard0", at(130));
+        assert_eq!(b.state("shard0"), BreakerState::Closed);
+    }
+
+    #[test]
+    fn late_success_does_not_close_an_open_breaker() {
+        let b = ShardBreakers::new(2, Duration::from_secs(10));
+        let t0 = Instant::now();
+        let at = |secs| t0 + Duration::from_secs(secs);
+
+        // A write started before the trip finishes after it: still open
+        b.on_failure_at("shard0", t0);
+        b.on_failure_at("shard0", t0);
+        b.on_success_at("shard0", at(1));
+        assert_eq!(b.state("shard0"), BreakerState::Open);
+        assert!(b.check_at("shard0", at(1)).is_err());
+
+        // ...also once the cooldown has passed, until the probe reports
+        b.on_success_at("shard0", at(10));
+        assert_eq!(b.state("shard0"), BreakerState::Open);
+        assert!(b.check_at("shard0", at(10)).is_ok());
+        b.on_success_at("shard0", at(10));
+        assert_eq!(b.state("shard0"), BreakerState::Closed);
+    }
+
+    #[test]
+    fn pool_timeouts_count_only_when_the_pool_was_connecting() {
+        assert!(!counts_against_shard(&sqlx::Error::PoolTimedOut, true));
+        assert!(counts_against_shard(&sqlx::Error::PoolTimedOut, false));
+        assert!(counts_against_shard(&sqlx::Error::PoolClosed, true));
+        assert!(counts_against_shard(
+            &sqlx::Error::Io(std::io::ErrorKind::ConnectionRefused.into()),
+            true
+        ));
+    }
+}
@@ -0,0 +1,289 @@
+//! db/budget.rs
+//!
+//! Global pending-batch memory budget.
+//!
+//! Every `Batch<T>` has its own `hard_cap_rows`, but with thousands of streams the sum of
+//! all pending batches is still unbounded. This module keeps a process-wide accounting of the
+//! approximate bytes held by every open batch and, when the total goes over
+//! `writer.max_pending_bytes`, marks the LARGEST batches as "forced" so their next
+//! `should_flush()` returns true regardless of flush_rows / flush_interval_ms.
+//!
+//! - Batches register themselves (see `Batch::attach_budget`) and report their size (and the
+//!   size they reported last) on every grow/shrink; dropping a batch releases its share
+//!   (and reports the rows it still held as never written).
+//! - The total and the batch count are lock-free counters (gauge, shutdown report).
+//! - `max_bytes == 0` disables enforcement: only the counters are kept. Enabled, the sizes are
+//!   kept by `BatchKey` (one open batch per key) and the largest batches are picked again only
+//!   when flushing the ones already picked would no longer bring the total under budget.
+
+use crate::db::batch::BatchKey;
+use crate::db::config::WriterConfig;
+use crate::db::metrics::DbMetrics;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Default)]
+struct BudgetState {
+    by_key: HashMap<BatchKey, usize>,
+    /// Batches that must flush early to bring the total back under budget.
+    forced: HashSet<BatchKey>,
+    /// Bytes held by the `forced` batches.
+    forced_bytes: usize,
+}
+
+#[derive(Debug)]
+pub struct PendingBatchBudget {
+    max_bytes: usize,
+    metrics: Option<Arc<DbMetrics>>,
+    total_bytes: AtomicUsize,
+    /// Batches holding rows.
+    tracked: AtomicUsize,
+    /// Some batch is forced (`is_forced` skips the lock otherwise).
+    any_forced: AtomicBool,
+    /// Per-batch sizes; only used when enforcing.
+    state: Mutex<BudgetState>,
+}
+
+impl PendingBatchBudget {
+    pub fn new(max_bytes: usize, metrics: Option<Arc<DbMetrics>>) -> Self {
+        Self {
+            max_bytes,
+            metrics,
+            total_bytes: AtomicUsize::new(0),
+            tracked: AtomicUsize::new(0),
+            any_forced: AtomicBool::new(false),
+            state: Mutex::new(BudgetState::default()),
+        }
+    }
+
+    pub fn from_config(writer: &WriterConfig, metrics: Option<Arc<DbMetrics>>) -> Self {
+        Self::new(writer.max_pending_bytes, metrics)
+    }
+
+    #[inline]
+    pub fn max_bytes(&self) -> usize {
+        self.max_bytes
+    }
+
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.max_bytes > 0
+    }
+
+    /// Total approximate bytes currently held by all registered batches.
+    pub fn total_bytes(&self) -> usize {
+        self.total_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Number of registered (non-empty) batches.
+    pub fn tracked_batches(&self) -> usize {
+        self.tracked.load(Ordering::Relaxed)
+    }
+
+    /// Report the current size of one batch (`prev`: the size it reported last). Called on
+    /// every grow/shrink.
+    pub fn record(&self, key: &BatchKey, prev: usize, bytes: usize) {
+        if prev == bytes {
+            return;
+        }
+        if prev == 0 {
+            self.tracked.fetch_add(1, Ordering::Relaxed);
+        } else if bytes == 0 {
+            self.tracked.fetch_sub(1, Ordering::Relaxed);
+        }
+        if !self.is_enabled() {
+            let total = if bytes > prev {
+                self.total_bytes.fetch_add(bytes - prev, Ordering::Relaxed) + (bytes - prev)
+            } else {
+                self.total_bytes.fetch_sub(prev - bytes, Ordering::Relaxed) - (prev - bytes)
+            };
+            if let Some(m) = &self.metrics {
+                m.set_pending_batch_bytes(total as i64);
+            }
+            return;
+        }
+
+        let mut st = self.state.lock().expect("pending budget mutex poisoned");
+        let forced = st.forced.contains(key);
+        if bytes == 0 {
+            st.by_key.remove(key);
+            if forced {
+                st.forced.remove(key);
+                st.forced_bytes = st.forced_bytes.saturating_sub(prev);
+            }
+        } else {
+            match st.by_key.get_mut(key) {
+                Some(b) => *b = bytes,
+                None => {
+                    st.by_key.insert(key.clone(), bytes);
+                }
+            }
+            if forced {
+                st.forced_bytes = st.forced_bytes.saturating_sub(prev).saturating_add(bytes);
+            }
+        }
+        // Updated under the lock: enabled, every change goes through it
+        let total = self
+            .total_bytes
+            .load(Ordering::Relaxed)
+            .saturating_sub(prev)
+            .saturating_add(bytes);
+        self.total_bytes.store(total, Ordering::Relaxed);
+
+        if total <= self.max_bytes {
+            if !st.forced.is_empty() {
+                st.forced.clear();
+                st.forced_bytes = 0;
+            }
+        } else if total - st.forced_bytes > self.max_bytes {
+            self.select_forced(&mut st, total);
+        }
+        self.any_forced
+            .store(!st.forced.is_empty(), Ordering::Relaxed);
+
+        if let Some(m) = &self.metrics {
+            m.set_pending_batch_bytes(total as i64);
+        }
+    }
+
+    /// Drop a batch (holding `bytes`) from the accounting (stream removed / batch dropped).
+    pub fn release(&self, key: &BatchKey, bytes: usize) {
+        self.record(key, bytes, 0);
+    }
+
+    /// A batch was dropped still holding `rows` rows (never written); `failed`: its last
+    /// write had failed. Counted for the shutdown report (`db_rows_unwritten_total`).
+    pub fn unwritten(&self, rows: usize, failed: bool) {
+        if let Some(m) = &self.metrics {
+            m.add_unwritten(rows as u64, failed);
+        }
+    }
+
+    /// True if this batch was picked to flush early to reclaim memory.
+    pub fn is_forced(&self, key: &BatchKey) -> bool {
+        self.any_forced.load(Ordering::Relaxed)
+            && self
+                .state
+                .lock()
+                .expect("pending budget mutex poisoned")
+                .forced
+                .contains(key)
+    }
+
+    /// Pick the largest batches until flushing them would bring the total under budget.
+    fn select_forced(&self, st: &mut BudgetState, total: usize) {
+        let mut sizes: Vec<(&BatchKey, usize)> = st.by_key.iter().map(|(k, v)| (k, *v)).collect();
+        sizes.sort_unstable_by_key(|(_, bytes)| std::cmp::Reverse(*bytes));
+
+        let mut remaining = total;
+        let mut forced = HashSet::new();
+        for (key, bytes) in sizes {
+            if remaining <= self.max_bytes {
+                break;
+            }
+            remaining = remaining.saturating_sub(bytes);
+            forced.insert(key.clone());
+        }
+
+        let newly_forced = forced.difference(&st.forced).count();
+        if newly_forced > 0
+            && let Some(m) = &self.metrics
+        {
+            m.add_forced_flushes(newly_forced as u64);
+        }
+        st.forced = forced;
+        st.forced_bytes = total - remaining;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::stream_types::{StreamKind, StreamTransport};
+    use crate::db::batch::Batch;
+
+    fn key(i: usize) -> BatchKey {
+        BatchKey {
+            exchange: "binance_linear".into(),
+            transport: StreamTransport::Ws,
+            kind: StreamKind::Trades,
+            stream: "trades".into(),
+            symbol: format!("SYM{i}USDT"),
+            derived: None,
+        }
+    }
+
+    fn writer_cfg() -> WriterConfig {
+        WriterConfig {
+            batch_size: 1_000_000,
+            hard_batch_size: 1_000_000,
+            flush_interval_ms: 60_000,
+            ..WriterConfig::default()
+        }
+    }
+
+    #[test]
+    fn many_batches_over_budget_force_largest_to_flush() {
+        let row_bytes = std::mem::size_of::<u64>();
+        // 50 batches, batch i holds (i+1)*10 rows => total = 12_750 rows
+        // budget = 10_000 rows worth of bytes
+        let budget = Arc::new(PendingBatchBudget::new(10_000 * row_bytes, None));
+
+        let mut batches: Vec<Batch<u64>> = (0..50)
+            .map(|i| {
+                let mut b = Batch::new(key(i), vec![], &writer_cfg());
+                b.attach_budget(Arc::clone(&budget));
+                b
+            })
+            .collect();
+
+        for (i, b) in batches.iter_mut().enumerate() {
+            b.extend(vec![0u64; (i + 1) * 10]);
+        }
+
+        assert_eq!(budget.tracked_batches(), 50);
+        assert_eq!(budget.total_bytes(), 12_750 * row_bytes);
+
+        // Nothing is due by rows/interval, so any flush here is budget-forced.
+        let forced: Vec<usize> = batches
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.should_flush())
+            .map(|(i, _)| i)
+            .collect();
+        assert!(!forced.is_empty(), "expected forced flushes over budget");
+
+        // Largest batches are picked first: 500+490+480+470+460+450 = 2850 >= 2750 excess
+        assert_eq!(forced, vec![44, 45, 46, 47, 48, 49]);
+
+        // Flushing the forced batches brings us back under budget and clears the flags.
+        for i in forced {
+            batches[i].take_rows();
+        }
+        assert!(budget.total_bytes() <= budget.max_bytes());
+        assert!(batches.iter().all(|b| !b.should_flush()));
+
+        // Dropping batches releases their share.
+        drop(batches);
+        assert_eq!(budget.total_bytes(), 0);
+        assert_eq!(budget.tracked_batches(), 0);
+    }
+
+    #[test]
+    fn zero_budget_only_accounts() {
+        let budget = Arc::new(PendingBatchBudget::new(0, None));
+        let mut b = Batch::new(key(0), vec![], &writer_cfg());
+        b.attach_budget(Arc::clone(&budget));
+        b.extend(vec![1u64; 1000]);
+
+        assert_eq!(budget.total_bytes(), 1000 * std::mem::size_of::<u64>());
+        assert_eq!(budget.tracked_batches(), 1);
+        assert!(!b.should_flush());
+
+        // No per-key state is kept; the counters still follow the batch
+        assert!(budget.state.lock().unwrap().by_key.is_empty());
+        b.take_rows();
+        assert_eq!((budget.total_bytes(), budget.tracked_batches()), (0, 0));
+    }
+}
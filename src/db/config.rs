@@ -1,7 +1,11 @@
+use crate::db::batch::BatchKey;
 use crate::error::{AppError, AppResult};
 use serde::Deserialize;
 use std::env;
-use std::{collections::HashSet, fs};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+};
 use std::{io::ErrorKind, path::Path};
 
 #[derive(Debug, Clone, Deserialize)]
@@ -9,6 +13,39 @@ pub struct TimescaleDbConfig {
     pub shards: Vec<ShardConfig>,
     pub writer: WriterConfig,
     pub health: HealthConfig,
+    /// Refuse to start when an enabled stream matches no shard rule (see
+    /// `check_shard_coverage`). Off = such streams only fail when their first batch is routed.
+    #[serde(default)]
+    pub strict_shard_coverage: bool,
+    /// Per-shard circuit breaker (see `db::breaker`).
+    #[serde(default)]
+    pub shard_breaker: ShardBreakerConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ShardBreakerConfig {
+    /// Failure score (acquire/execute failures, see below) that opens a shard's breaker
+    /// (0 = off).
+    pub trip_after_failures: u32,
+    /// Fail fast this long before letting one probe request through.
+    pub cooldown_ms: u64,
+    /// The failure score halves every this long (0 = no time decay).
+    pub score_half_life_ms: u64,
+    /// Share of the failure score kept across a successful request, in [0, 1)
+    /// (0 = a success clears it: the breaker counts consecutive failures).
+    pub score_keep_on_success: f64,
+}
+
+impl Default for ShardBreakerConfig {
+    fn default() -> Self {
+        Self {
+            trip_after_failures: 5,
+            cooldown_ms: 30_000,
+            score_half_life_ms: 0,
+            score_keep_on_success: 0.0,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -21,17 +58,64 @@ pub struct ShardConfig {
     pub pool_max: u32,
     pub connect_timeout_ms: u64,
     pub idle_timeout_sec: u64,
+    /// Upper bound on building this shard's pool (connect + optional verify); a blackholed
+    /// host fails startup after this long instead of hanging it.
+    #[serde(default = "default_pool_init_timeout_ms")]
+    pub pool_init_timeout_ms: u64,
 
     // Routing rules
     #[serde(default)]
     pub rules: Vec<ShardRule>,
 }
 
+fn default_pool_init_timeout_ms() -> u64 {
+    15_000
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct ShardRule {
     pub exchange: String,
     pub stream: String,
     pub symbol: String,
+    /// Spread matching symbols over these shard ids instead of routing to the shard the rule
+    /// is declared on: the symbol is hashed (FNV-1a, case-insensitive) to pick one, so every
+    /// row of a symbol lands on the same shard.
+    #[serde(default)]
+    pub hash_symbols_across: Vec<String>,
+}
+
+impl ShardRule {
+    /// `*` matches anything; other values match case-insensitively.
+    pub fn matches(&self, exchange: &str, stream: &str, symbol: &str) -> bool {
+        field_matches(&self.exchange, exchange)
+            && field_matches(&self.stream, stream)
+            && field_matches(&self.symbol, symbol)
+    }
+
+    /// Shard a matching `symbol` is routed to; `own_shard_id` is the shard declaring the rule.
+    pub fn target_shard<'a>(&'a self, own_shard_id: &'a str, symbol: &str) -> &'a str {
+        if self.hash_symbols_across.is_empty() {
+            return own_shard_id;
+        }
+        let h = fnv1a_ignore_ascii_case(symbol.trim());
+        let idx = (h % self.hash_symbols_across.len() as u64) as usize;
+        &self.hash_symbols_across[idx]
+    }
+}
+
+/// 64-bit FNV-1a; stable across builds and platforms (unlike `DefaultHasher`).
+fn fnv1a_ignore_ascii_case(s: &str) -> u64 {
+    const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    s.bytes().fold(OFFSET, |h, b| {
+        (h ^ b.to_ascii_lowercase() as u64).wrapping_mul(PRIME)
+    })
+}
+
+fn field_matches(rule_val: &str, actual: &str) -> bool {
+    let rv = rule_val.trim();
+    let av = actual.trim();
+    rv == "*" || rv.eq_ignore_ascii_case(av)
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -41,7 +125,68 @@ pub struct WriterConfig {
     pub flush_interval_ms: u64,
     pub chunk_rows: usize, // max rows per insert
     pub max_inflight_batches: usize,
+    /// COPY instead of INSERT for tables without a conflict target (off by default).
+    #[serde(default)]
     pub use_copy: bool,
+    /// Global budget (bytes) for rows pending across ALL batches. 0 = no budget.
+    #[serde(default)]
+    pub max_pending_bytes: usize,
+    /// Depth streams: merge deltas per price level within this window (ms). 0 = off.
+    #[serde(default)]
+    pub depth_coalesce_window_ms: u64,
+    /// Depth streams with `emit_bbo` / `emit_microprice`: min time between two BBO (and
+    /// microprice) rows (ms). 0 = every change.
+    #[serde(default)]
+    pub bbo_min_interval_ms: u64,
+    /// Trade streams: sum buy/sell volume per window of this length (ms) into
+    /// `ex_<exchange>.trade_imbalance`. 0 = off.
+    #[serde(default)]
+    pub trade_imbalance_window_ms: u64,
+    /// Symbols whose last value each store-on-change filter holds; the least recently seen is
+    /// evicted over the cap (its next row is stored even if unchanged). 0 = unbounded.
+    #[serde(default)]
+    pub max_change_only_symbols: usize,
+    /// Store open-interest rows only when the value changes (see `ChangeOnlyFilter`).
+    #[serde(default)]
+    pub oi_store_on_change: bool,
+    /// Store funding rows only when the rate changes.
+    #[serde(default)]
+    pub funding_store_on_change: bool,
+    /// Store-on-change: an unchanged row is still stored after this long (heartbeat, ms).
+    #[serde(default = "default_store_on_change_max_suppress_ms")]
+    pub store_on_change_max_suppress_ms: u64,
+    /// Dev/onboarding only: on a missing table, create schema/table (+ hypertable) and retry
+    /// the insert once. Keep off in prod.
+    #[serde(default)]
+    pub auto_create_tables: bool,
+    /// Publish to Redis only rows the DB committed (after dedup), when the flush commits them.
+    /// Redis then mirrors the DB exactly but lags by up to one flush. Off = publish on receipt.
+    #[serde(default)]
+    pub publish_persisted_only: bool,
+    /// Cap on the observed flush delay (ms). Longer waits (host suspend, paused VM) are
+    /// recorded at the cap and counted as anomalies. 0 = uncapped.
+    #[serde(default = "default_max_flush_delay_ms")]
+    pub max_flush_delay_ms: u64,
+    /// Last data-quality gate before the insert: allowed range of scaled values per column
+    /// (`price_i`, `qty_i`, ...). Rows outside are rejected and counted. Empty = no check.
+    #[serde(default)]
+    pub value_ranges: BTreeMap<String, ValueRange>,
+}
+
+/// Inclusive bounds of a scaled column (either side optional).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub struct ValueRange {
+    #[serde(default)]
+    pub min: Option<i64>,
+    #[serde(default)]
+    pub max: Option<i64>,
+}
+
+impl ValueRange {
+    #[inline]
+    pub fn contains(&self, v: i64) -> bool {
+        self.min.is_none_or(|min| v >= min) && self.max.is_none_or(|max| v <= max)
+    }
 }
 
 impl Default for WriterConfig {
@@ -52,7 +197,19 @@ impl Default for WriterConfig {
             flush_interval_ms: 50,
             chunk_rows: 500,
             max_inflight_batches: 4,
-            use_copy: true,
+            use_copy: false,
+            max_pending_bytes: 0,
+            depth_coalesce_window_ms: 0,
+            bbo_min_interval_ms: 0,
+            trade_imbalance_window_ms: 0,
+            max_change_only_symbols: 0,
+            oi_store_on_change: false,
+            funding_store_on_change: false,
+            store_on_change_max_suppress_ms: default_store_on_change_max_suppress_ms(),
+            auto_create_tables: false,
+            publish_persisted_only: false,
+            max_flush_delay_ms: default_max_flush_delay_ms(),
+            value_ranges: BTreeMap::new(),
         }
     }
 }
@@ -242,6 +399,11 @@ impl TimescaleDbConfig {
                     "{prefix}: idle_timeout_sec must be > 0"
                 )));
             }
+            if shard.pool_init_timeout_ms == 0 {
+                return Err(AppError::InvalidConfig(format!(
+                    "{prefix}: pool_init_timeout_ms must be > 0"
+                )));
+            }
 
             if shard.rules.is_empty() {
                 return Err(AppError::InvalidConfig(format!(
@@ -258,6 +420,19 @@ impl TimescaleDbConfig {
             }
         }
 
+        // Hash targets may name shards declared later, so check once all ids are known
+        for (i, shard) in self.shards.iter().enumerate() {
+            for (r, rule) in shard.rules.iter().enumerate() {
+                for target in &rule.hash_symbols_across {
+                    if !seen_ids.contains(target) {
+                        return Err(AppError::InvalidConfig(format!(
+                            "timescale_db.toml: shards[{i}].rules[{r}]: hash_symbols_across references unknown shard id '{target}'"
+                        )));
+                    }
+                }
+            }
+        }
+
         // ---- Writer checks
         if self.writer.batch_size == 0 {
             return Err(AppError::InvalidConfig(
@@ -274,6 +449,23 @@ impl TimescaleDbConfig {
                 "timescale_db.toml: writer.max_inflight_batches must be > 0".into(),
             ));
         }
+        for (column, r) in &self.writer.value_ranges {
+            if let (Some(min), Some(max)) = (r.min, r.max)
+                && min > max
+            {
+                return Err(AppError::InvalidConfig(format!(
+                    "timescale_db.toml: writer.value_ranges.{column}: min ({min}) must be <= max ({max})"
+                )));
+            }
+        }
+
+        // ---- Breaker checks (keep = 1 would never forget a failure)
+        let keep = self.shard_breaker.score_keep_on_success;
+        if !(0.0..1.0).contains(&keep) {
+            return Err(AppError::InvalidConfig(format!(
+                "timescale_db.toml: shard_breaker.score_keep_on_success ({keep}) must be in [0, 1)"
+            )));
+        }
 
         // ---- Health checks (minimal)
         let h = &self.health;
@@ -287,6 +479,20 @@ impl TimescaleDbConfig {
                 "timescale_db.toml: health.hold_down_ms must be > 0".into(),
             ));
         }
+        match h.min_healthy_shards {
+            MinHealthyShards::Count(n) if n == 0 || n > self.shards.len() => {
+                return Err(AppError::InvalidConfig(format!(
+                    "timescale_db.toml: health.min_healthy_shards ({n}) must be between 1 and the number of shards ({})",
+                    self.shards.len()
+                )));
+            }
+            MinHealthyShards::Fraction(f) if !(f > 0.0 && f <= 1.0) => {
+                return Err(AppError::InvalidConfig(format!(
+                    "timescale_db.toml: health.min_healthy_shards fraction ({f}) must be in (0, 1]"
+                )));
+            }
+            _ => {}
+        }
 
         let t = &h.thresholds;
 
@@ -333,6 +539,40 @@ impl TimescaleDbConfig {
 
         Ok(())
     }
+
+    /// Streams (batch keys) that no shard rule matches, in input order.
+    pub fn uncovered_streams<'a>(&self, keys: &'a [BatchKey]) -> Vec<&'a BatchKey> {
+        keys.iter()
+            .filter(|k| {
+                !self
+                    .shards
+                    .iter()
+                    .flat_map(|s| &s.rules)
+                    .any(|r| r.matches(&k.exchange, &k.stream, &k.symbol))
+            })
+            .collect()
+    }
+
+    /// With `strict_shard_coverage`, error listing every stream in `keys` no shard rule
+    /// matches. Without it, always Ok.
+    pub fn check_shard_coverage(&self, keys: &[BatchKey]) -> AppResult<()> {
+        if !self.strict_shard_coverage {
+            return Ok(());
+        }
+        let uncovered = self.uncovered_streams(keys);
+        if uncovered.is_empty() {
+            return Ok(());
+        }
+        let list: Vec<String> = uncovered
+            .iter()
+            .map(|k| format!("{}/{}/{}", k.exchange, k.stream, k.symbol))
+            .collect();
+        Err(AppError::InvalidConfig(format!(
+            "timescale_db.toml: strict_shard_coverage: {} enabled stream(s) match no shard rule (exchange/stream/symbol): {}",
+            list.len(),
+            list.join(", ")
+        )))
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -349,9 +589,42 @@ pub struct HealthConfig {
     #[serde(default)]
     pub admission_policy: AdmissionPolicy,
 
+    /// Shards that must answer a connectivity probe for the DB to count as write-ready.
+    #[serde(default)]
+    pub min_healthy_shards: MinHealthyShards,
+
     pub thresholds: HealthThresholds,
 }
 
+/// `min_healthy_shards = 2` (absolute count) or `min_healthy_shards = 0.5` (fraction of shards).
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum MinHealthyShards {
+    Count(usize),
+    Fraction(f64),
+}
+
+impl Default for MinHealthyShards {
+    fn default() -> Self {
+        MinHealthyShards::Count(1)
+    }
+}
+
+impl MinHealthyShards {
+    /// Healthy shards required out of `total` (fractions round up, at least one shard).
+    pub fn required(self, total: usize) -> usize {
+        match self {
+            MinHealthyShards::Count(n) => n,
+            MinHealthyShards::Fraction(f) => ((f * total as f64).ceil() as usize).max(1),
+        }
+    }
+
+    #[inline]
+    pub fn is_satisfied(self, healthy: usize, total: usize) -> bool {
+        total > 0 && healthy >= self.required(total)
+    }
+}
+
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum AdmissionPolicy {
@@ -385,6 +658,13 @@ fn default_eval_interval_ms() -> u64 {
 fn default_hold_down_ms() -> u64 {
     3000
 }
+fn default_store_on_change_max_suppress_ms() -> u64 {
+    60_000
+}
+
+fn default_max_flush_delay_ms() -> u64 {
+    60_000
+}
 
 fn validate_rule_field(prefix: &str, field: &str, value: &str) -> AppResult<()> {
     let v = value.trim();
@@ -409,4 +689,120 @@ mod tests {
         assert!(!cfg.shards.is_empty());
         assert!(cfg.writer.batch_size > 0);
     }
+
+    #[test]
+    fn min_healthy_shards_below_and_above_threshold() {
+        use crate::db::config::MinHealthyShards;
+
+        // Absolute count
+        let need_two = MinHealthyShards::Count(2);
+        assert!(!need_two.is_satisfied(1, 3), "below threshold");
+        assert!(need_two.is_satisfied(2, 3));
+        assert!(need_two.is_satisfied(3, 3), "above threshold");
+
+        // Fraction rounds up: 0.5 of 3 shards -> 2
+        let half = MinHealthyShards::Fraction(0.5);
+        assert_eq!(half.required(3), 2);
+        assert!(!half.is_satisfied(1, 3));
+        assert!(half.is_satisfied(2, 3));
+
+        // Tiny fractions still require one shard; no shards is never ready
+        assert_eq!(MinHealthyShards::Fraction(0.01).required(4), 1);
+        assert!(!MinHealthyShards::Count(1).is_satisfied(0, 0));
+    }
+
+    #[test]
+    fn min_healthy_shards_parses_count_or_fraction() {
+        use crate::db::config::MinHealthyShards;
+
+        #[derive(serde::Deserialize)]
+        struct T {
+            v: MinHealthyShards,
+        }
+        let count: T = toml::from_str("v = 2").unwrap();
+        assert_eq!(count.v, MinHealthyShards::Count(2));
+        let frac: T = toml::from_str("v = 0.75").unwrap();
+        assert_eq!(frac.v, MinHealthyShards::Fraction(0.75));
+        // A float is always a fraction: 1.0 means every shard, not one
+        let all: T = toml::from_str("v = 1.0").unwrap();
+        assert_eq!(all.v.required(3), 3);
+
+        // Shipped config: one reachable shard is enough
+        let raw = std::fs::read_to_string("src/config/timescale_db.toml").unwrap();
+        let cfg: TimescaleDbConfig = toml::from_str(&raw).unwrap();
+        assert_eq!(cfg.health.min_healthy_shards, MinHealthyShards::Count(1));
+    }
+
+    #[test]
+    fn strict_shard_coverage_lists_uncovered_streams() {
+        use crate::app::control::batch::make_batch_key;
+        use crate::app::stream_types::{ExchangeId, StreamKind, StreamTransport};
+        use crate::db::config::ShardRule;
+        use crate::error::AppError;
+
+        // Parsed without `validate` (no DSN env needed)
+        let raw = std::fs::read_to_string("src/config/timescale_db.toml").unwrap();
+        let mut cfg: TimescaleDbConfig = toml::from_str(&raw).unwrap();
+        cfg.shards[0].rules = vec![ShardRule {
+            exchange: "binance_linear".into(),
+            stream: "*".into(),
+            symbol: "*".into(),
+            hash_symbols_across: Vec::new(),
+        }];
+
+        let keys = vec![
+            make_batch_key(
+                ExchangeId::BinanceLinear,
+                StreamTransport::Ws,
+                StreamKind::Trades,
+                "BTCUSDT",
+            )
+            .unwrap(),
+            make_batch_key(
+                ExchangeId::HyperliquidPerp,
+                StreamTransport::Ws,
+                StreamKind::Trades,
+                "BTC",
+            )
+            .unwrap(),
+        ];
+        assert_eq!(cfg.uncovered_streams(&keys), [&keys[1]]);
+
+        // Lenient: startup goes on
+        cfg.strict_shard_coverage = false;
+        assert!(cfg.check_shard_coverage(&keys).is_ok());
+
+        // Strict: startup error naming the uncovered stream only
+        cfg.strict_shard_coverage = true;
+        match cfg.check_shard_coverage(&keys) {
+            Err(AppError::InvalidConfig(msg)) => {
+                assert!(msg.contains("hyperliquid_perp/"), "{msg}");
+                assert!(msg.contains("/BTC"), "{msg}");
+                assert!(!msg.contains("BTCUSDT"), "{msg}");
+            }
+            other => panic!("expected InvalidConfig, got {other:?}"),
+        }
+        assert!(cfg.check_shard_coverage(&keys[..1]).is_ok());
+    }
+
+    #[test]
+    fn hash_symbols_across_must_name_configured_shards() {
+        use crate::error::AppError;
+
+        let raw = std::fs::read_to_string("src/config/timescale_db.toml").unwrap();
+        let mut cfg: TimescaleDbConfig = toml::from_str(&raw).unwrap();
+        // SAFETY: variable only read by this test
+        unsafe { std::env::set_var("TEST_HASH_RULE_DSN", "postgres://u:p@localhost/db") };
+        cfg.shards[0].dsn_env = "TEST_HASH_RULE_DSN".into();
+
+        cfg.shards[0].rules[0].hash_symbols_across = vec!["nope".into()];
+        assert!(matches!(
+            cfg.validate(),
+            Err(AppError::InvalidConfig(m)) if m.contains("unknown shard id 'nope'")
+        ));
+
+        let own = cfg.shards[0].id.clone();
+        cfg.shards[0].rules[0].hash_symbols_across = vec![own];
+        assert!(cfg.validate().is_ok());
+    }
 }
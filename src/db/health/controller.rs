@@ -366,6 +366,7 @@ mod tests {
             evaluate_interval_ms: 50,
             hold_down_ms: 50,
             admission_policy: AdmissionPolicy::GreenOnly,
+            min_healthy_shards: Default::default(),
             thresholds: HealthThresholds {
                 flush_delay_p95_ms_yellow: 200,
                 flush_delay_p95_ms_red: 500,
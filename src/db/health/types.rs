@@ -1,4 +1,7 @@
-use crate::db::config::{AdmissionPolicy, HealthConfig};
+use crate::db::breaker::BreakerState;
+use crate::db::config::{AdmissionPolicy, HealthConfig, MinHealthyShards};
+use serde::Serialize;
+use std::collections::BTreeMap;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum HealthState {
@@ -33,3 +36,92 @@ impl HealthStatus {
         }
     }
 }
+
+/// Shard connectivity vs. `health.min_healthy_shards` ("are enough shards up to write?").
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ShardHealthReport {
+    pub total: usize,
+    pub healthy: usize,
+    pub required: usize,
+    pub unhealthy: Vec<String>,
+    /// Circuit breaker of each shard (see `db::breaker`); writes to an open one fail fast.
+    pub breakers: BTreeMap<String, BreakerState>,
+}
+
+impl ShardHealthReport {
+    pub fn from_probes(probes: Vec<(String, bool)>, min: MinHealthyShards) -> Self {
+        let total = probes.len();
+        let unhealthy: Vec<String> = probes
+            .into_iter()
+            .filter(|(_, ok)| !ok)
+            .map(|(id, _)| id)
+            .collect();
+        Self {
+            total,
+            healthy: total - unhealthy.len(),
+            required: min.required(total),
+            unhealthy,
+            breakers: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_breakers(
+        mut self,
+        breakers: impl IntoIterator<Item = (String, BreakerState)>,
+    ) -> Self {
+        self.breakers = breakers.into_iter().collect();
+        self
+    }
+
+    #[inline]
+    pub fn is_write_ready(&self) -> bool {
+        self.total > 0 && self.healthy >= self.required
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn probes(states: &[bool]) -> Vec<(String, bool)> {
+        states
+            .iter()
+            .enumerate()
+            .map(|(i, ok)| (format!("shard_{i}"), *ok))
+            .collect()
+    }
+
+    #[test]
+    fn below_threshold_is_not_write_ready() {
+        let r = ShardHealthReport::from_probes(
+            probes(&[true, false, false]),
+            MinHealthyShards::Count(2),
+        );
+        assert_eq!((r.healthy, r.total, r.required), (1, 3, 2));
+        assert_eq!(r.unhealthy, vec!["shard_1", "shard_2"]);
+        assert!(!r.is_write_ready());
+    }
+
+    #[test]
+    fn at_or_above_threshold_is_write_ready() {
+        let min = MinHealthyShards::Fraction(0.5);
+        assert!(ShardHealthReport::from_probes(probes(&[true, true, false]), min).is_write_ready());
+        assert!(ShardHealthReport::from_probes(probes(&[true, true, true]), min).is_write_ready());
+        assert!(!ShardHealthReport::from_probes(Vec::new(), min).is_write_ready());
+    }
+
+    #[test]
+    fn report_serializes_breaker_states() {
+        let r = ShardHealthReport::from_probes(probes(&[true, false]), MinHealthyShards::Count(1))
+            .with_breakers([
+                ("shard_0".to_string(), BreakerState::Closed),
+                ("shard_1".to_string(), BreakerState::Open),
+            ]);
+        let v = serde_json::to_value(&r).unwrap();
+        assert_eq!(
+            v["breakers"],
+            serde_json::json!({"shard_0": "closed", "shard_1": "open"})
+        );
+        assert_eq!(v["unhealthy"], serde_json::json!(["shard_1"]));
+    }
+}
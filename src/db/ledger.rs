@@ -0,0 +1,105 @@
+//! db/ledger.rs
+//!
+//! Commit ledger: what this process durably wrote, kept in memory for the shutdown report.
+//!
+//! - Updated by `DbHandler::write_batch` after every successful flush.
+//! - Rows/batches per shard, and the max committed event time per stream (batch key).
+//! - Cheap: one short mutex section per flush, maps bounded by shards / open streams.
+
+use crate::db::batch::BatchKey;
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+/// Point-in-time copy of the ledger.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommitLedgerSnapshot {
+    pub rows_by_shard: BTreeMap<String, u64>,
+    pub batches_by_shard: BTreeMap<String, u64>,
+    /// `exchange:stream:symbol` -> max committed event time.
+    pub watermarks: BTreeMap<String, DateTime<Utc>>,
+}
+
+impl CommitLedgerSnapshot {
+    pub fn rows_total(&self) -> u64 {
+        self.rows_by_shard.values().sum()
+    }
+
+    pub fn batches_total(&self) -> u64 {
+        self.batches_by_shard.values().sum()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct CommitLedger {
+    state: Mutex<CommitLedgerSnapshot>,
+}
+
+impl CommitLedger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stream label used for watermarks (a derived series gets its own: `stream.derived`).
+    pub fn stream_key(key: &BatchKey) -> String {
+        match key.derived {
+            Some(d) => format!("{}:{}.{}:{}", key.exchange, key.stream, d, key.symbol),
+            None => format!("{}:{}:{}", key.exchange, key.stream, key.symbol),
+        }
+    }
+
+    /// Record one committed flush.
+    pub fn record(
+        &self,
+        shard_id: &str,
+        key: &BatchKey,
+        rows: u64,
+        watermark: Option<DateTime<Utc>>,
+    ) {
+        let mut s = self.state.lock().unwrap_or_else(|p| p.into_inner());
+        *s.rows_by_shard.entry(shard_id.to_string()).or_default() += rows;
+        *s.batches_by_shard.entry(shard_id.to_string()).or_default() += 1;
+        if let Some(wm) = watermark {
+            let slot = s.watermarks.entry(Self::stream_key(key)).or_insert(wm);
+            if wm > *slot {
+                *slot = wm;
+            }
+        }
+    }
+
+    pub fn snapshot(&self) -> CommitLedgerSnapshot {
+        self.state.lock().unwrap_or_else(|p| p.into_inner()).clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::stream_types::{StreamKind, StreamTransport};
+    use chrono::TimeZone;
+
+    #[test]
+    fn ledger_sums_per_shard_and_keeps_max_watermark() {
+        let ledger = CommitLedger::new();
+        let key = BatchKey {
+            exchange: "binance_linear".into(),
+            transport: StreamTransport::Ws,
+            kind: StreamKind::Trades,
+            stream: "trades".into(),
+            symbol: "BTCUSDT".into(),
+            derived: None,
+        };
+        let t = |s: u32| Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, s).unwrap();
+
+        ledger.record("a", &key, 10, Some(t(5)));
+        ledger.record("a", &key, 5, Some(t(2))); // late batch: watermark does not regress
+        ledger.record("b", &key, 1, None);
+
+        let snap = ledger.snapshot();
+        assert_eq!(snap.rows_by_shard["a"], 15);
+        assert_eq!(snap.batches_by_shard["a"], 2);
+        assert_eq!(snap.rows_total(), 16);
+        assert_eq!(snap.batches_total(), 3);
+        assert_eq!(snap.watermarks["binance_linear:trades:BTCUSDT"], t(5));
+    }
+}
@@ -1,3 +1,6 @@
+use crate::app::metrics::ConstLabels;
+#[cfg(feature = "metrics")]
+use crate::app::metrics::registry_with_const_labels;
 use crate::error::AppResult;
 
 #[cfg(feature = "metrics")]
@@ -41,6 +44,18 @@ pub struct DbMetrics {
     pub retried_batches_total: IntCounter,
     #[cfg(feature = "metrics")]
     pub rows_dropped_total: IntCounter,
+    /// Rows rejected before the insert for out-of-range values (`writer.value_ranges`).
+    #[cfg(feature = "metrics")]
+    pub rows_rejected_total: IntCounter,
+    /// Batches whose COPY failed and were written with INSERT instead (`writer.use_copy`).
+    #[cfg(feature = "metrics")]
+    pub copy_fallback_total: IntCounter,
+    /// Batches dropped still holding rows after a failed write (failed for good).
+    #[cfg(feature = "metrics")]
+    pub batches_lost_total: IntCounter,
+    /// Rows never written: still in their batch when it was dropped.
+    #[cfg(feature = "metrics")]
+    pub rows_unwritten_total: IntCounter,
 
     // --- Pool health (per-shard would be nicer later; start global)
     #[cfg(feature = "metrics")]
@@ -73,17 +88,44 @@ pub struct DbMetrics {
     #[cfg(feature = "metrics")]
     pub oldest_batch_age_seconds: Gauge,
 
+    /// Approx bytes held by all pending batches (global budget accounting).
+    #[cfg(feature = "metrics")]
+    pub pending_batch_bytes: IntGauge,
+    /// Batches forced to flush early because the global pending budget was exceeded.
+    #[cfg(feature = "metrics")]
+    pub pending_budget_forced_flushes_total: IntCounter,
+    /// Flush delays above `writer.max_flush_delay_ms` (suspend/pause), recorded at the cap.
+    #[cfg(feature = "metrics")]
+    pub flush_delay_anomalies_total: IntCounter,
+
     // no-op fallback data (keeps struct non-empty without feature)
     #[cfg(not(feature = "metrics"))]
     _noop: (),
 }
 
+/// Snapshot of the DB write counters (see `DbMetrics::write_totals`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct DbWriteTotals {
+    pub rows_written: u64,
+    pub batches_written: u64,
+    pub batches_failed: u64,
+    pub batches_retried: u64,
+    pub rows_dropped: u64,
+    pub batches_lost: u64,
+    pub rows_unwritten: u64,
+}
+
 impl DbMetrics {
     /// Create metrics (registers them).
     pub fn new() -> AppResult<Self> {
+        Self::with_const_labels(&ConstLabels::new())
+    }
+
+    /// Like `new`, with `const_labels` on every series (`[metrics]` instance labels).
+    pub fn with_const_labels(const_labels: &ConstLabels) -> AppResult<Self> {
         #[cfg(feature = "metrics")]
         {
-            let registry = Registry::new();
+            let registry = registry_with_const_labels(const_labels)?;
 
             let rows_written_total =
                 IntCounter::with_opts(Opts::new("db_rows_written_total", "Rows written total"))?;
@@ -134,6 +176,23 @@ impl DbMetrics {
 
             let rows_dropped_total =
                 IntCounter::with_opts(Opts::new("db_rows_dropped_total", "Rows dropped total"))?;
+            let rows_rejected_total = IntCounter::with_opts(Opts::new(
+                "db_rows_rejected_total",
+                "Rows rejected before insert for values outside writer.value_ranges",
+            ))?;
+            let copy_fallback_total = IntCounter::with_opts(Opts::new(
+                "db_copy_fallback_total",
+                "Batches written with INSERT after their COPY failed",
+            ))?;
+
+            let batches_lost_total = IntCounter::with_opts(Opts::new(
+                "db_batches_lost_total",
+                "Batches dropped still holding rows after a failed write",
+            ))?;
+            let rows_unwritten_total = IntCounter::with_opts(Opts::new(
+                "db_rows_unwritten_total",
+                "Rows never written: still in their batch when it was dropped",
+            ))?;
 
             let pool_in_use =
                 IntGauge::with_opts(Opts::new("db_pool_in_use", "Connections in use"))?;
@@ -174,6 +233,21 @@ impl DbMetrics {
                 "Oldest pending batch age in seconds",
             ))?;
 
+            let pending_batch_bytes = IntGauge::with_opts(Opts::new(
+                "db_pending_batch_bytes",
+                "Approx bytes held by all pending batches",
+            ))?;
+
+            let pending_budget_forced_flushes_total = IntCounter::with_opts(Opts::new(
+                "db_pending_budget_forced_flushes_total",
+                "Batches forced to flush early by the global pending-memory budget",
+            ))?;
+
+            let flush_delay_anomalies_total = IntCounter::with_opts(Opts::new(
+                "db_flush_delay_anomalies_total",
+                "Flush delays above max_flush_delay_ms (clamped in db_flush_delay_seconds)",
+            ))?;
+
             // Register everything
             registry.register(Box::new(rows_written_total.clone()))?;
             registry.register(Box::new(batches_written_total.clone()))?;
@@ -186,6 +260,10 @@ impl DbMetrics {
             registry.register(Box::new(failed_batches_total.clone()))?;
             registry.register(Box::new(retried_batches_total.clone()))?;
             registry.register(Box::new(rows_dropped_total.clone()))?;
+            registry.register(Box::new(rows_rejected_total.clone()))?;
+            registry.register(Box::new(copy_fallback_total.clone()))?;
+            registry.register(Box::new(batches_lost_total.clone()))?;
+            registry.register(Box::new(rows_unwritten_total.clone()))?;
             registry.register(Box::new(pool_in_use.clone()))?;
             registry.register(Box::new(pool_idle.clone()))?;
             registry.register(Box::new(pool_max.clone()))?;
@@ -199,6 +277,9 @@ impl DbMetrics {
             registry.register(Box::new(rows_enqueued_total.clone()))?;
             registry.register(Box::new(batches_enqueued_total.clone()))?;
             registry.register(Box::new(oldest_batch_age_seconds.clone()))?;
+            registry.register(Box::new(pending_batch_bytes.clone()))?;
+            registry.register(Box::new(pending_budget_forced_flushes_total.clone()))?;
+            registry.register(Box::new(flush_delay_anomalies_total.clone()))?;
 
             Ok(Self {
                 registry,
@@ -213,6 +294,10 @@ impl DbMetrics {
                 failed_batches_total,
                 retried_batches_total,
                 rows_dropped_total,
+                rows_rejected_total,
+                copy_fallback_total,
+                batches_lost_total,
+                rows_unwritten_total,
                 pool_in_use,
                 pool_idle,
                 pool_max,
@@ -224,11 +309,15 @@ impl DbMetrics {
                 rows_enqueued_total,
                 batches_enqueued_total,
                 oldest_batch_age_seconds,
+                pending_batch_bytes,
+                pending_budget_forced_flushes_total,
+                flush_delay_anomalies_total,
             })
         }
 
         #[cfg(not(feature = "metrics"))]
         {
+            let _ = const_labels;
             Ok(Self { _noop: () })
         }
     }
@@ -250,6 +339,27 @@ impl DbMetrics {
         ))
     }
 
+    /// Current write counter values (zeros without the `metrics` feature).
+    pub fn write_totals(&self) -> DbWriteTotals {
+        #[cfg(feature = "metrics")]
+        {
+            DbWriteTotals {
+                rows_written: self.rows_written_total.get(),
+                batches_written: self.batches_written_total.get(),
+                batches_failed: self.failed_batches_total.get(),
+                batches_retried: self.retried_batches_total.get(),
+                rows_dropped: self.rows_dropped_total.get(),
+                batches_lost: self.batches_lost_total.get(),
+                rows_unwritten: self.rows_unwritten_total.get(),
+            }
+        }
+
+        #[cfg(not(feature = "metrics"))]
+        {
+            DbWriteTotals::default()
+        }
+    }
+
     // --- No-op helpers (so handler code can call these unconditionally)
 
     #[inline]
@@ -312,6 +422,30 @@ impl DbMetrics {
         self.rows_dropped_total.inc_by(_n);
     }
 
+    /// A batch was dropped holding `_rows` rows; `_failed`: its last write had failed.
+    #[inline]
+    pub fn add_unwritten(&self, _rows: u64, _failed: bool) {
+        #[cfg(feature = "metrics")]
+        {
+            self.rows_unwritten_total.inc_by(_rows);
+            if _failed {
+                self.batches_lost_total.inc();
+            }
+        }
+    }
+
+    #[inline]
+    pub fn add_rows_rejected(&self, _n: u64) {
+        #[cfg(feature = "metrics")]
+        self.rows_rejected_total.inc_by(_n);
+    }
+
+    #[inline]
+    pub fn inc_copy_fallback(&self) {
+        #[cfg(feature = "metrics")]
+        self.copy_fallback_total.inc();
+    }
+
     #[inline]
     pub fn observe_pool_wait(&self, _secs: f64) {
         #[cfg(feature = "metrics")]
@@ -359,4 +493,35 @@ impl DbMetrics {
         #[cfg(feature = "metrics")]
         self.oldest_batch_age_seconds.set(_secs);
     }
+
+    #[inline]
+    pub fn set_pending_batch_bytes(&self, _bytes: i64) {
+        #[cfg(feature = "metrics")]
+        self.pending_batch_bytes.set(_bytes);
+    }
+
+    #[inline]
+    pub fn add_forced_flushes(&self, _n: u64) {
+        #[cfg(feature = "metrics")]
+        self.pending_budget_forced_flushes_total.inc_by(_n);
+    }
+
+    #[inline]
+    pub fn inc_flush_delay_anomaly(&self) {
+        #[cfg(feature = "metrics")]
+        self.flush_delay_anomalies_total.inc();
+    }
+
+    /// Flush delays clamped so far (0 without the `metrics` feature).
+    pub fn flush_delay_anomalies(&self) -> u64 {
+        #[cfg(feature = "metrics")]
+        {
+            self.flush_delay_anomalies_total.get()
+        }
+
+        #[cfg(not(feature = "metrics"))]
+        {
+            0
+        }
+    }
 }
@@ -1,6 +1,9 @@
 pub mod batch;
+pub mod breaker;
+pub mod budget;
 pub mod config;
 pub mod health;
+pub mod ledger;
 pub mod metrics;
 pub mod pools;
 pub mod rows;
@@ -8,8 +11,11 @@ pub mod traits;
 pub mod writer;
 
 pub use batch::*;
+pub use breaker::*;
+pub use budget::*;
 pub use config::*;
 pub use health::*;
+pub use ledger::*;
 pub use metrics::*;
 pub use pools::*;
 pub use rows::*;
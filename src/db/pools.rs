@@ -5,7 +5,10 @@
 //! - Routes (exchange, stream, symbol) -> shard via rules.
 //! - Routing uses "most specific match wins" (fewer '*') to avoid catch-all rules stealing traffic.
 //! - Public API stays simple: `pool_for(exchange, stream, symbol)`.
+//! - A shard whose breaker is open (see `db::breaker`) fails fast in `pool_by_id`.
+//! - Resolved routes are cached per (exchange, stream, symbol); any shard change clears them.
 
+use crate::db::breaker::{BreakerState, ShardBreakers, counts_against_shard};
 use crate::db::config::{ShardConfig, ShardRule, TimescaleDbConfig};
 use crate::error::{AppError, AppResult};
 
@@ -13,6 +16,7 @@ use sqlx::{
     Pool, Postgres,
     postgres::{PgConnectOptions, PgPoolOptions},
 };
+use std::sync::RwLock as SyncRwLock;
 use std::{collections::HashMap, env, str::FromStr, time::Duration};
 use tokio::sync::RwLock;
 use tokio::time::timeout;
@@ -21,14 +25,53 @@ use tokio::time::timeout;
 pub struct DbPools {
     pools_by_id: RwLock<HashMap<String, Pool<Postgres>>>,
     shards: RwLock<Vec<ShardConfig>>,
+    breakers: ShardBreakers,
+    // (exchange, stream, symbol) -> shard id, filled by `shard_id_for`, cleared with `shards`
+    routes: SyncRwLock<RouteCache>,
+}
+
+/// Resolved routes, nested per key part so a lookup borrows the `&str`s (no allocation on a
+/// hit; only a miss copies the tuple in).
+#[derive(Debug, Default)]
+struct RouteCache {
+    by_exchange: HashMap<String, HashMap<String, HashMap<String, String>>>,
+}
+
+impl RouteCache {
+    fn get(&self, exchange: &str, stream: &str, symbol: &str) -> Option<&String> {
+        self.by_exchange.get(exchange)?.get(stream)?.get(symbol)
+    }
+
+    fn insert(&mut self, exchange: &str, stream: &str, symbol: &str, shard_id: String) {
+        self.by_exchange
+            .entry(exchange.to_string())
+            .or_default()
+            .entry(stream.to_string())
+            .or_default()
+            .insert(symbol.to_string(), shard_id);
+    }
+
+    fn clear(&mut self) {
+        self.by_exchange.clear();
+    }
+}
+
+/// A shard's config and the state of its circuit breaker.
+#[derive(Debug, Clone)]
+pub struct ShardSnapshot {
+    pub shard: ShardConfig,
+    pub breaker_state: BreakerState,
 }
 
 impl DbPools {
     /// Build pools for every configured shard (and optionally check connectivity).
+    /// Each shard gets at most its `pool_init_timeout_ms`, so startup never hangs on one.
     pub async fn new(cfg: TimescaleDbConfig, verify: bool) -> AppResult<Self> {
         let this = Self {
             pools_by_id: RwLock::new(HashMap::with_capacity(cfg.shards.len())),
             shards: RwLock::new(Vec::with_capacity(cfg.shards.len())),
+            breakers: ShardBreakers::from_config(&cfg.shard_breaker),
+            routes: SyncRwLock::new(RouteCache::default()),
         };
 
         for shard in cfg.shards {
@@ -43,14 +86,7 @@ impl DbPools {
     /// - Routing rules are replaced for that shard id.
     pub async fn add_pool(&self, shard: ShardConfig, verify: bool) -> AppResult<()> {
         // Build pool first (no locks held during await-heavy work).
-        let pool = build_pool(&shard).await?;
-
-        if verify {
-            sqlx::query("SELECT 1")
-                .execute(&pool)
-                .await
-                .map_err(AppError::Sqlx)?;
-        }
+        let pool = init_pool(&shard, verify).await?;
 
         // Update pool map
         {
@@ -59,14 +95,7 @@ impl DbPools {
         }
 
         // Update shards list (replace existing shard config with same id)
-        {
-            let mut shards = self.shards.write().await;
-            if let Some(pos) = shards.iter().position(|s| s.id == shard.id) {
-                shards[pos] = shard;
-            } else {
-                shards.push(shard);
-            }
-        }
+        self.upsert_shard(shard).await;
 
         Ok(())
     }
@@ -74,14 +103,7 @@ impl DbPools {
     /// Replace a shard pool at runtime (safe; builds new pool first, then swaps).
     pub async fn replace_pool(&self, shard: ShardConfig, verify: bool) -> AppResult<()> {
         // 1) Build new pool first (await-heavy work happens outside locks)
-        let new_pool = build_pool(&shard).await?;
-
-        if verify {
-            sqlx::query("SELECT 1")
-                .execute(&new_pool)
-                .await
-                .map_err(AppError::Sqlx)?;
-        }
+        let new_pool = init_pool(&shard, verify).await?;
 
         // 2) Swap into the map quickly (short lock)
         let old_pool = {
@@ -90,14 +112,7 @@ impl DbPools {
         };
 
         // 3) Update shard config (short lock)
-        {
-            let mut shards = self.shards.write().await;
-            if let Some(pos) = shards.iter().position(|s| s.id == shard.id) {
-                shards[pos] = shard;
-            } else {
-                shards.push(shard);
-            }
-        }
+        self.upsert_shard(shard).await;
 
         // 4) Gracefully close old pool in the background (optional)
         if let Some(old) = old_pool {
@@ -109,11 +124,23 @@ impl DbPools {
         Ok(())
     }
 
+    /// Replace the config of `shard.id` (or append it) and drop every cached route.
+    async fn upsert_shard(&self, shard: ShardConfig) {
+        let mut shards = self.shards.write().await;
+        if let Some(pos) = shards.iter().position(|s| s.id == shard.id) {
+            shards[pos] = shard;
+        } else {
+            shards.push(shard);
+        }
+        // Under the `shards` write lock: no lookup can cache a route of the old rules
+        self.routes.write().expect("routes lock poisoned").clear();
+    }
+
     /// Route by (exchange, stream, symbol) using shard rules.
     ///
     /// Matching:
     /// - `*` matches anything
-    /// - by default matching is case-insensitive (see `field_matches`)
+    /// - by default matching is case-insensitive (see `ShardRule::matches`)
     ///
     /// Selection:
     /// - most specific match wins (specificity = number of non-"*" fields)
@@ -121,6 +148,10 @@ impl DbPools {
     ///   1) higher specificity
     ///   2) earlier rule (within shard)
     ///   3) earlier shard (config order)
+    /// - the winning rule routes to its own shard, or with `hash_symbols_across` to the
+    ///   listed shard the symbol hashes to
+    ///
+    /// The result is cached per tuple until the shards change.
     pub async fn shard_id_for(
         &self,
         exchange: &str,
@@ -128,6 +159,15 @@ impl DbPools {
         symbol: &str,
     ) -> AppResult<String> {
         let shards = self.shards.read().await;
+        if let Some(shard_id) = self
+            .routes
+            .read()
+            .expect("routes lock poisoned")
+            .get(exchange, stream, symbol)
+        {
+            return Ok(shard_id.clone());
+        }
+
         let key = RouteKey {
             exchange,
             stream,
@@ -140,7 +180,7 @@ impl DbPools {
             for (rule_idx, rule) in shard.rules.iter().enumerate() {
                 if rule_matches(rule, &key) {
                     let cand = Best {
-                        shard_id: shard.id.as_str(),
+                        shard_id: rule.target_shard(shard.id.as_str(), symbol),
                         score: specificity(rule),
                         shard_idx,
                         rule_idx,
@@ -154,15 +194,25 @@ impl DbPools {
             }
         }
 
-        best.map(|b| b.shard_id.to_string()).ok_or_else(|| {
+        let shard_id = best.map(|b| b.shard_id.to_string()).ok_or_else(|| {
             AppError::InvalidConfig(format!(
                 "No shard routing rule matched exchange='{exchange}', stream='{stream}', symbol='{symbol}'"
             ))
-        })
+        })?;
+        // Still under the `shards` read lock, so the rules cannot have changed meanwhile
+        self.routes.write().expect("routes lock poisoned").insert(
+            exchange,
+            stream,
+            symbol,
+            shard_id.clone(),
+        );
+        Ok(shard_id)
     }
 
     /// Get pool by shard id (cloned; Pool is cheap to clone).
+    /// `AppError::Internal` right away while the shard's breaker is open.
     pub async fn pool_by_id(&self, shard_id: &str) -> AppResult<Pool<Postgres>> {
+        self.breakers.check(shard_id)?;
         let pools = self.pools_by_id.read().await;
         pools
             .get(shard_id)
@@ -170,6 +220,30 @@ impl DbPools {
             .ok_or_else(|| AppError::InvalidConfig(format!("Unknown shard id '{shard_id}'")))
     }
 
+    /// Pool and config of a routed shard (breaker checked as in `pool_by_id`).
+    /// `AppError::Internal` when the shard has no config or no pool: routing and the pool map
+    /// disagree, which is a bug to surface, not a shard to skip.
+    pub async fn routed_shard(&self, shard_id: &str) -> AppResult<(Pool<Postgres>, ShardConfig)> {
+        let shard = self
+            .shards
+            .read()
+            .await
+            .iter()
+            .find(|s| s.id == shard_id)
+            .cloned();
+        let pool = self.pools_by_id.read().await.get(shard_id).cloned();
+        match (pool, shard) {
+            (Some(pool), Some(shard)) => {
+                // Checked last so a half-open probe is only taken by a request that will run
+                self.breakers.check(shard_id)?;
+                Ok((pool, shard))
+            }
+            _ => Err(AppError::Internal(format!(
+                "shard {shard_id} routed but not configured"
+            ))),
+        }
+    }
+
     /// Route and return the pool (the only call most code should need).
     pub async fn pool_for(
         &self,
@@ -187,9 +261,83 @@ impl DbPools {
         pools.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
     }
 
-    pub async fn shards_snapshot(&self) -> AppResult<Vec<ShardConfig>> {
+    pub async fn shards_snapshot(&self) -> AppResult<Vec<ShardSnapshot>> {
         let shards = self.shards.read().await;
-        Ok(shards.clone())
+        Ok(shards
+            .iter()
+            .map(|shard| ShardSnapshot {
+                shard: shard.clone(),
+                breaker_state: self.breakers.state(&shard.id),
+            })
+            .collect())
+    }
+
+    /// A request to `shard_id` succeeded (closes its breaker).
+    pub fn on_shard_success(&self, shard_id: &str) {
+        self.breakers.on_success(shard_id);
+    }
+
+    /// A request to `shard_id` over `pool` failed; counts towards its breaker unless the
+    /// server answered (a success) or the pool was saturated (inconclusive).
+    pub fn on_shard_error(&self, shard_id: &str, pool: &Pool<Postgres>, e: &sqlx::Error) {
+        let saturated = pool.num_idle() == 0 && pool.size() >= pool.options().get_max_connections();
+        if counts_against_shard(e, saturated) {
+            self.breakers.on_failure(shard_id);
+        } else if matches!(e, sqlx::Error::Database(_)) {
+            self.breakers.on_success(shard_id);
+        } else {
+            self.breakers.on_inconclusive(shard_id);
+        }
+    }
+
+    /// Report the outcome of a request to `shard_id` to its breaker; errors as `AppError::Sqlx`.
+    /// Every path that went through `pool_by_id` reports, so a half-open probe always resolves.
+    pub fn report<T>(
+        &self,
+        shard_id: &str,
+        pool: &Pool<Postgres>,
+        res: Result<T, sqlx::Error>,
+    ) -> AppResult<T> {
+        match res {
+            Ok(v) => {
+                self.on_shard_success(shard_id);
+                Ok(v)
+            }
+            Err(e) => Err(self.report_err(shard_id, pool, e)),
+        }
+    }
+
+    /// Report a failed request (e.g. an acquire, whose success proves nothing yet).
+    pub fn report_err(&self, shard_id: &str, pool: &Pool<Postgres>, e: sqlx::Error) -> AppError {
+        self.on_shard_error(shard_id, pool, &e);
+        AppError::Sqlx(e)
+    }
+
+    /// Connectivity probe (`SELECT 1`) against every shard, concurrently.
+    /// Each probe is bounded by the shard's `connect_timeout_ms`; returns (shard_id, ok).
+    pub async fn probe_shards(&self) -> Vec<(String, bool)> {
+        let shards = self.shards.read().await.clone();
+        let pools = self.pools_by_id.read().await.clone();
+
+        let probes = shards.into_iter().map(|shard| {
+            let pool = pools.get(&shard.id).cloned();
+            async move {
+                let ok = match pool {
+                    Some(pool) => matches!(
+                        timeout(
+                            Duration::from_millis(shard.connect_timeout_ms),
+                            sqlx::query("SELECT 1").execute(&pool),
+                        )
+                        .await,
+                        Ok(Ok(_))
+                    ),
+                    None => false,
+                };
+                (shard.id, ok)
+            }
+        });
+
+        futures_util::future::join_all(probes).await
     }
 }
 
@@ -223,15 +371,7 @@ impl<'a> Best<'a> {
 }
 
 fn rule_matches(rule: &ShardRule, key: &RouteKey<'_>) -> bool {
-    field_matches(&rule.exchange, key.exchange)
-        && field_matches(&rule.stream, key.stream)
-        && field_matches(&rule.symbol, key.symbol)
-}
-
-fn field_matches(rule_val: &str, actual: &str) -> bool {
-    let rv = rule_val.trim();
-    let av = actual.trim();
-    rv == "*" || rv.eq_ignore_ascii_case(av)
+    rule.matches(key.exchange, key.stream, key.symbol)
 }
 
 fn specificity(rule: &ShardRule) -> i32 {
@@ -242,6 +382,24 @@ fn specificity(rule: &ShardRule) -> i32 {
 
 /* ------------------------- pool build (private) ----------------------- */
 
+/// Build (and optionally verify) a shard pool within the shard's `pool_init_timeout_ms`.
+async fn init_pool(shard: &ShardConfig, verify: bool) -> AppResult<Pool<Postgres>> {
+    let init = async {
+        let pool = build_pool(shard).await?;
+        if verify {
+            sqlx::query("SELECT 1")
+                .execute(&pool)
+                .await
+                .map_err(AppError::Sqlx)?;
+        }
+        Ok(pool)
+    };
+
+    timeout(Duration::from_millis(shard.pool_init_timeout_ms), init)
+        .await
+        .map_err(|_| AppError::Internal(format!("shard {} pool init timed out", shard.id)))?
+}
+
 async fn build_pool(shard: &ShardConfig) -> AppResult<Pool<Postgres>> {
     let connect_timeout = Duration::from_millis(shard.connect_timeout_ms);
     let idle_timeout = Duration::from_secs(shard.idle_timeout_sec);
@@ -312,11 +470,89 @@ mod tests {
             exchange: "*".to_string(),
             stream: "*".to_string(),
             symbol: "*".to_string(),
+            hash_symbols_across: Vec::new(),
         }];
 
         shard
     }
 
+    fn rule(exchange: &str, stream: &str, symbol: &str) -> ShardRule {
+        ShardRule {
+            exchange: exchange.to_string(),
+            stream: stream.to_string(),
+            symbol: symbol.to_string(),
+            hash_symbols_across: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn cached_routes_follow_rule_precedence_and_shard_changes() {
+        // Real config shape, no shards: nothing connects
+        let raw = std::fs::read_to_string(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/src/config/timescale_db.toml"
+        ))
+        .unwrap();
+        let mut cfg: TimescaleDbConfig = toml::from_str(&raw).unwrap();
+        let base = cfg.shards[0].clone();
+        cfg.shards.clear();
+        let pools = DbPools::new(cfg, false).await.unwrap();
+
+        let shard = |id: &str, rules: Vec<ShardRule>| ShardConfig {
+            id: id.to_string(),
+            rules,
+            ..base.clone()
+        };
+        pools
+            .upsert_shard(shard("catch_all", vec![rule("*", "*", "*")]))
+            .await;
+        pools
+            .upsert_shard(shard("depth", vec![rule("binance_linear", "depth", "*")]))
+            .await;
+
+        // Most specific rule wins, on the first lookup and from the cache alike
+        for _ in 0..2 {
+            let route =
+                |stream: &'static str| pools.shard_id_for("binance_linear", stream, "BTCUSDT");
+            assert_eq!(route("depth").await.unwrap(), "depth");
+            assert_eq!(route("trades").await.unwrap(), "catch_all");
+        }
+        {
+            let routes = pools.routes.read().unwrap();
+            let cached = |stream| routes.get("binance_linear", stream, "BTCUSDT").cloned();
+            assert_eq!(cached("depth").as_deref(), Some("depth"));
+            assert_eq!(cached("trades").as_deref(), Some("catch_all"));
+        }
+
+        // A more specific rule added later takes over the cached tuple
+        pools
+            .upsert_shard(shard(
+                "btc_depth",
+                vec![rule("binance_linear", "depth", "btcusdt")],
+            ))
+            .await;
+        assert!(pools.routes.read().unwrap().by_exchange.is_empty());
+        assert_eq!(
+            pools
+                .shard_id_for("binance_linear", "depth", "BTCUSDT")
+                .await
+                .unwrap(),
+            "btc_depth"
+        );
+
+        // Replacing a shard's rules drops its routes too
+        pools
+            .upsert_shard(shard("depth", vec![rule("hyperliquid_perp", "depth", "*")]))
+            .await;
+        assert_eq!(
+            pools
+                .shard_id_for("binance_linear", "depth", "ETHUSDT")
+                .await
+                .unwrap(),
+            "catch_all"
+        );
+    }
+
     #[tokio::test]
     async fn dbpools_new_works_with_toml_config() {
         if !db_tests_enabled() {
@@ -378,6 +614,8 @@ mod tests {
         let pools = DbPools {
             pools_by_id: RwLock::new(HashMap::new()),
             shards: RwLock::new(Vec::new()),
+            breakers: ShardBreakers::from_config(&cfg.shard_breaker),
+            routes: SyncRwLock::new(RouteCache::default()),
         };
 
         let runtime_id = "runtime_shard";
@@ -408,7 +646,7 @@ mod tests {
         println!("[test] shards_snapshot len = {}", snapshot.len());
 
         assert_eq!(snapshot.len(), 1);
-        assert_eq!(snapshot[0].id, runtime_id);
+        assert_eq!(snapshot[0].shard.id, runtime_id);
 
         println!("[test] Runtime add_pool() test OK");
     }
@@ -456,6 +694,132 @@ mod tests {
 
         println!("[test] pool_for() routing test OK");
     }
+
+    #[tokio::test]
+    async fn new_times_out_on_a_blackholed_shard() {
+        // Accepts TCP but never answers the Postgres handshake
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let _hold = tokio::spawn(async move {
+            let mut conns = Vec::new();
+            while let Ok((sock, _)) = listener.accept().await {
+                conns.push(sock);
+            }
+        });
+        unsafe {
+            std::env::set_var(
+                "TEST_BLACKHOLE_SHARD_DSN",
+                format!("postgres://u:p@{addr}/db"),
+            )
+        };
+
+        let raw = std::fs::read_to_string("src/config/timescale_db.toml").unwrap();
+        let mut cfg: TimescaleDbConfig = toml::from_str(&raw).unwrap();
+        let mut shard = make_runtime_shard_from(&cfg.shards[0], "blackhole");
+        shard.dsn_env = "TEST_BLACKHOLE_SHARD_DSN".into();
+        shard.connect_timeout_ms = 10_000;
+        shard.pool_init_timeout_ms = 300;
+        cfg.shards = vec![shard];
+
+        let started = std::time::Instant::now();
+        let err = DbPools::new(cfg, true).await.expect_err("must time out");
+        let elapsed = started.elapsed();
+
+        assert!(
+            matches!(&err, AppError::Internal(m) if m == "shard blackhole pool init timed out"),
+            "unexpected error: {err:?}"
+        );
+        assert!(elapsed >= Duration::from_millis(300), "{elapsed:?}");
+        assert!(elapsed < Duration::from_millis(1_500), "{elapsed:?}");
+    }
+
+    #[tokio::test]
+    async fn hashed_rule_spreads_symbols_deterministically() {
+        let raw = std::fs::read_to_string("src/config/timescale_db.toml").unwrap();
+        let cfg: TimescaleDbConfig = toml::from_str(&raw).unwrap();
+        let base = cfg.shards[0].clone();
+
+        let spread: Vec<String> = ["hl_a", "hl_b", "hl_c"].map(String::from).to_vec();
+        let mut router = make_runtime_shard_from(&base, "router");
+        router.rules = vec![
+            ShardRule {
+                exchange: "hyperliquid_perp".into(),
+                stream: "*".into(),
+                symbol: "*".into(),
+                hash_symbols_across: spread.clone(),
+            },
+            ShardRule {
+                exchange: "*".into(),
+                stream: "*".into(),
+                symbol: "*".into(),
+                hash_symbols_across: Vec::new(),
+            },
+        ];
+        let pools = DbPools {
+            pools_by_id: RwLock::new(HashMap::new()),
+            shards: RwLock::new(vec![router]),
+            breakers: ShardBreakers::from_config(&cfg.shard_breaker),
+            routes: SyncRwLock::new(RouteCache::default()),
+        };
+
+        let mut used = std::collections::HashSet::new();
+        for i in 0..64 {
+            let symbol = format!("SYM{i}");
+            let id = pools
+                .shard_id_for("hyperliquid_perp", "trades", &symbol)
+                .await
+                .unwrap();
+            assert!(spread.contains(&id));
+            // Same symbol (any casing, any stream) -> same shard
+            let again = pools
+                .shard_id_for("hyperliquid_perp", "depth", &symbol.to_lowercase())
+                .await
+                .unwrap();
+            assert_eq!(id, again);
+            used.insert(id);
+        }
+        assert_eq!(used.len(), spread.len());
+
+        // Non-hashed rule routes to its own shard
+        let id = pools
+            .shard_id_for("binance_linear", "trades", "BTCUSDT")
+            .await
+            .unwrap();
+        assert_eq!(id, "router");
+    }
+
+    #[tokio::test]
+    async fn routed_shard_without_config_or_pool_is_an_error() {
+        let raw = std::fs::read_to_string("src/config/timescale_db.toml").unwrap();
+        let cfg: TimescaleDbConfig = toml::from_str(&raw).unwrap();
+        let mut router = make_runtime_shard_from(&cfg.shards[0], "router");
+        router.rules[0].hash_symbols_across = vec!["ghost".into()];
+        let pools = DbPools {
+            pools_by_id: RwLock::new(HashMap::new()),
+            shards: RwLock::new(vec![router]),
+            breakers: ShardBreakers::from_config(&cfg.shard_breaker),
+            routes: SyncRwLock::new(RouteCache::default()),
+        };
+
+        // Routed to a shard id no shard declares
+        let id = pools
+            .shard_id_for("binance_linear", "trades", "BTCUSDT")
+            .await
+            .unwrap();
+        assert_eq!(id, "ghost");
+        let err = pools.routed_shard(&id).await.unwrap_err();
+        assert!(
+            matches!(&err, AppError::Internal(m) if m == "shard ghost routed but not configured"),
+            "{err:?}"
+        );
+
+        // Declared, but its pool is missing from the pool map
+        let err = pools.routed_shard("router").await.unwrap_err();
+        assert!(
+            matches!(&err, AppError::Internal(m) if m == "shard router routed but not configured"),
+            "{err:?}"
+        );
+    }
     #[tokio::test]
     async fn add_pool_with_same_id_replaces_and_does_not_duplicate() {
         if !db_tests_enabled() {
@@ -521,7 +885,7 @@ mod tests {
             "expected exactly one shard config after replacing same id"
         );
         assert_eq!(
-            snap[0].id, shard0.id,
+            snap[0].shard.id, shard0.id,
             "expected shard id to remain the same"
         );
 
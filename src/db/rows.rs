@@ -1,7 +1,9 @@
-use crate::db::traits::BatchInsertRow;
+use crate::db::traits::{BatchInsertRow, CopyLine};
 use crate::ingest::datamap::event::{
-    BookSide, DepthDeltaRow, FundingRow, LiquidationRow, OpenInterestRow, TradeRow, TradeSide,
+    BookSide, DepthDeltaRow, DepthSnapshotRow, FundingRow, LiquidationRow, OpenInterestRow,
+    TradeRow, TradeSide,
 };
+use crate::ingest::datamap::naming::db_table;
 use chrono::{DateTime, Utc};
 use sqlx::Postgres;
 use sqlx::query_builder::Separated;
@@ -21,9 +23,34 @@ impl BatchInsertRow for TradeDBRow {
     const COLUMNS: &'static [&'static str] = &[
         "time", "symbol", "side", "price_i", "qty_i", "trade_id", "is_maker",
     ];
+    const COLUMN_TYPES: &'static [&'static str] = &[
+        "TIMESTAMPTZ NOT NULL",
+        "TEXT NOT NULL",
+        "SMALLINT NOT NULL",
+        "BIGINT NOT NULL",
+        "BIGINT NOT NULL",
+        "BIGINT NULL",
+        "BOOLEAN NULL",
+    ];
+    // Replayed trades (reconnect overlap, restart) land once; see dbsetup.sql
+    const CONFLICT_TARGET: Option<&'static str> = Some("time, symbol, trade_id");
+    const DEDUP_COLUMN: Option<&'static str> = Some("trade_id");
 
     fn table(&self, exchange: &str) -> String {
-        format!("ex_{}.trades", exchange)
+        db_table(exchange, "trades")
+    }
+    fn event_time(&self) -> DateTime<Utc> {
+        self.time
+    }
+    fn dedup_key(&self) -> Option<i64> {
+        self.trade_id
+    }
+    fn scaled_value(&self, column: &str) -> Option<i64> {
+        match column {
+            "price_i" => Some(self.price_i),
+            "qty_i" => Some(self.qty_i),
+            _ => None,
+        }
     }
 
     fn push_binds(&self, b: &mut Separated<'_, '_, Postgres, &'static str>) {
@@ -35,6 +62,16 @@ impl BatchInsertRow for TradeDBRow {
             .push_bind(self.trade_id)
             .push_bind(self.is_maker);
     }
+
+    fn push_copy_fields(&self, line: &mut CopyLine<'_>) {
+        line.field(&self.time)
+            .field(&self.symbol)
+            .field(&self.side)
+            .field(&self.price_i)
+            .field(&self.qty_i)
+            .field(&self.trade_id)
+            .field(&self.is_maker);
+    }
 }
 
 // TradeRow -> TradeDBRow
@@ -65,9 +102,27 @@ pub struct DepthDeltaDBRow {
 impl BatchInsertRow for DepthDeltaDBRow {
     const COLUMNS: &'static [&'static str] =
         &["time", "symbol", "side", "price_i", "size_i", "seq"];
+    const COLUMN_TYPES: &'static [&'static str] = &[
+        "TIMESTAMPTZ NOT NULL",
+        "TEXT NOT NULL",
+        "SMALLINT NOT NULL",
+        "BIGINT NOT NULL",
+        "BIGINT NOT NULL",
+        "BIGINT NULL",
+    ];
 
     fn table(&self, exchange: &str) -> String {
-        format!("ex_{}.depth_deltas", exchange)
+        db_table(exchange, "depth_deltas")
+    }
+    fn event_time(&self) -> DateTime<Utc> {
+        self.time
+    }
+    fn scaled_value(&self, column: &str) -> Option<i64> {
+        match column {
+            "price_i" => Some(self.price_i),
+            "size_i" => Some(self.size_i),
+            _ => None,
+        }
     }
 
     fn push_binds(&self, b: &mut Separated<'_, '_, Postgres, &'static str>) {
@@ -78,6 +133,15 @@ impl BatchInsertRow for DepthDeltaDBRow {
             .push_bind(self.size_i)
             .push_bind(self.seq);
     }
+
+    fn push_copy_fields(&self, line: &mut CopyLine<'_>) {
+        line.field(&self.time)
+            .field(&self.symbol)
+            .field(&self.side)
+            .field(&self.price_i)
+            .field(&self.size_i)
+            .field(&self.seq);
+    }
 }
 
 // DepthDeltaRow -> DepthDeltaDBRow
@@ -94,6 +158,87 @@ impl From<DepthDeltaRow> for DepthDeltaDBRow {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct DepthSnapshotDBRow {
+    pub time: DateTime<Utc>,
+    pub symbol: String,
+    pub side: i16, // 0=bid, 1=ask
+    pub price_i: i64,
+    pub size_i: i64,
+    pub level_idx: i32,   // 0 = best level of the side
+    pub snapshot_id: i64, // shared by every level of one snapshot
+}
+
+impl BatchInsertRow for DepthSnapshotDBRow {
+    const COLUMNS: &'static [&'static str] = &[
+        "time",
+        "symbol",
+        "side",
+        "price_i",
+        "size_i",
+        "level_idx",
+        "snapshot_id",
+    ];
+    const COLUMN_TYPES: &'static [&'static str] = &[
+        "TIMESTAMPTZ NOT NULL",
+        "TEXT NOT NULL",
+        "SMALLINT NOT NULL",
+        "BIGINT NOT NULL",
+        "BIGINT NOT NULL",
+        "INTEGER NOT NULL",
+        "BIGINT NOT NULL",
+    ];
+
+    fn table(&self, exchange: &str) -> String {
+        db_table(exchange, "depth_snapshots")
+    }
+    fn event_time(&self) -> DateTime<Utc> {
+        self.time
+    }
+    fn scaled_value(&self, column: &str) -> Option<i64> {
+        match column {
+            "price_i" => Some(self.price_i),
+            "size_i" => Some(self.size_i),
+            _ => None,
+        }
+    }
+
+    fn push_binds(&self, b: &mut Separated<'_, '_, Postgres, &'static str>) {
+        b.push_bind(self.time)
+            .push_bind(self.symbol.clone())
+            .push_bind(self.side)
+            .push_bind(self.price_i)
+            .push_bind(self.size_i)
+            .push_bind(self.level_idx)
+            .push_bind(self.snapshot_id);
+    }
+
+    fn push_copy_fields(&self, line: &mut CopyLine<'_>) {
+        line.field(&self.time)
+            .field(&self.symbol)
+            .field(&self.side)
+            .field(&self.price_i)
+            .field(&self.size_i)
+            .field(&self.level_idx)
+            .field(&self.snapshot_id);
+    }
+}
+
+// DepthSnapshotRow -> DepthSnapshotDBRow
+impl From<DepthSnapshotRow> for DepthSnapshotDBRow {
+    fn from(d: DepthSnapshotRow) -> Self {
+        DepthSnapshotDBRow {
+            time: d.time,
+            symbol: d.symbol,
+            side: d.side.as_i16(), // 0=bid, 1=ask
+            price_i: d.price_i,
+            size_i: d.size_i,
+            level_idx: d.level_idx,
+            snapshot_id: d.snapshot_id,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct OpenInterestDBRow {
     pub time: DateTime<Utc>,
@@ -103,9 +248,17 @@ pub struct OpenInterestDBRow {
 
 impl BatchInsertRow for OpenInterestDBRow {
     const COLUMNS: &'static [&'static str] = &["time", "symbol", "oi_i"];
+    const COLUMN_TYPES: &'static [&'static str] =
+        &["TIMESTAMPTZ NOT NULL", "TEXT NOT NULL", "BIGINT NOT NULL"];
 
     fn table(&self, exchange: &str) -> String {
-        format!("ex_{}.open_interest", exchange)
+        db_table(exchange, "open_interest")
+    }
+    fn event_time(&self) -> DateTime<Utc> {
+        self.time
+    }
+    fn scaled_value(&self, column: &str) -> Option<i64> {
+        (column == "oi_i").then_some(self.oi_i)
     }
 
     fn push_binds(&self, b: &mut Separated<'_, '_, Postgres, &'static str>) {
@@ -113,6 +266,10 @@ impl BatchInsertRow for OpenInterestDBRow {
             .push_bind(self.symbol.clone())
             .push_bind(self.oi_i);
     }
+
+    fn push_copy_fields(&self, line: &mut CopyLine<'_>) {
+        line.field(&self.time).field(&self.symbol).field(&self.oi_i);
+    }
 }
 
 // OpenInterestRow -> OpenInterestDBRow
@@ -136,9 +293,21 @@ pub struct FundingDBRow {
 
 impl BatchInsertRow for FundingDBRow {
     const COLUMNS: &'static [&'static str] = &["time", "symbol", "funding_rate", "funding_time"];
+    const COLUMN_TYPES: &'static [&'static str] = &[
+        "TIMESTAMPTZ NOT NULL",
+        "TEXT NOT NULL",
+        "BIGINT NOT NULL",
+        "TIMESTAMPTZ NULL",
+    ];
 
     fn table(&self, exchange: &str) -> String {
-        format!("ex_{}.funding", exchange)
+        db_table(exchange, "funding")
+    }
+    fn event_time(&self) -> DateTime<Utc> {
+        self.time
+    }
+    fn scaled_value(&self, column: &str) -> Option<i64> {
+        (column == "funding_rate").then_some(self.funding_rate)
     }
 
     fn push_binds(&self, b: &mut Separated<'_, '_, Postgres, &'static str>) {
@@ -147,6 +316,13 @@ impl BatchInsertRow for FundingDBRow {
             .push_bind(self.funding_rate)
             .push_bind(self.funding_time.clone());
     }
+
+    fn push_copy_fields(&self, line: &mut CopyLine<'_>) {
+        line.field(&self.time)
+            .field(&self.symbol)
+            .field(&self.funding_rate)
+            .field(&self.funding_time);
+    }
 }
 
 // FundingRow -> FundingDBRow
@@ -174,9 +350,27 @@ pub struct LiquidationDBRow {
 impl BatchInsertRow for LiquidationDBRow {
     const COLUMNS: &'static [&'static str] =
         &["time", "symbol", "side", "price_i", "qty_i", "liq_id"];
+    const COLUMN_TYPES: &'static [&'static str] = &[
+        "TIMESTAMPTZ NOT NULL",
+        "TEXT NOT NULL",
+        "SMALLINT NOT NULL",
+        "BIGINT NULL",
+        "BIGINT NOT NULL",
+        "BIGINT NULL",
+    ];
 
     fn table(&self, exchange: &str) -> String {
-        format!("ex_{}.liquidations", exchange)
+        db_table(exchange, "liquidations")
+    }
+    fn event_time(&self) -> DateTime<Utc> {
+        self.time
+    }
+    fn scaled_value(&self, column: &str) -> Option<i64> {
+        match column {
+            "price_i" => self.price_i,
+            "qty_i" => Some(self.qty_i),
+            _ => None,
+        }
     }
 
     fn push_binds(&self, b: &mut Separated<'_, '_, Postgres, &'static str>) {
@@ -187,6 +381,15 @@ impl BatchInsertRow for LiquidationDBRow {
             .push_bind(self.qty_i)
             .push_bind(self.liq_id);
     }
+
+    fn push_copy_fields(&self, line: &mut CopyLine<'_>) {
+        line.field(&self.time)
+            .field(&self.symbol)
+            .field(&self.side)
+            .field(&self.price_i)
+            .field(&self.qty_i)
+            .field(&self.liq_id);
+    }
 }
 
 // LiquidationRow -> LiquidationDBRow
@@ -202,3 +405,156 @@ impl From<LiquidationRow> for LiquidationDBRow {
         }
     }
 }
+
+/// Best bid / best ask (top of book), derived by the `BookState` assembler.
+/// `None` columns mean that side of the book is empty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BboRow {
+    pub time: DateTime<Utc>,
+    pub symbol: String,
+    pub bid_px_i: Option<i64>, // scaled
+    pub bid_sz_i: Option<i64>, // scaled
+    pub ask_px_i: Option<i64>, // scaled
+    pub ask_sz_i: Option<i64>, // scaled
+}
+
+impl BatchInsertRow for BboRow {
+    const COLUMNS: &'static [&'static str] = &[
+        "time", "symbol", "bid_px_i", "bid_sz_i", "ask_px_i", "ask_sz_i",
+    ];
+    const COLUMN_TYPES: &'static [&'static str] = &[
+        "TIMESTAMPTZ NOT NULL",
+        "TEXT NOT NULL",
+        "BIGINT NULL",
+        "BIGINT NULL",
+        "BIGINT NULL",
+        "BIGINT NULL",
+    ];
+
+    fn table(&self, exchange: &str) -> String {
+        db_table(exchange, "bbo")
+    }
+    fn event_time(&self) -> DateTime<Utc> {
+        self.time
+    }
+    fn scaled_value(&self, column: &str) -> Option<i64> {
+        match column {
+            "bid_px_i" => self.bid_px_i,
+            "bid_sz_i" => self.bid_sz_i,
+            "ask_px_i" => self.ask_px_i,
+            "ask_sz_i" => self.ask_sz_i,
+            _ => None,
+        }
+    }
+
+    fn push_binds(&self, b: &mut Separated<'_, '_, Postgres, &'static str>) {
+        b.push_bind(self.time)
+            .push_bind(self.symbol.clone())
+            .push_bind(self.bid_px_i)
+            .push_bind(self.bid_sz_i)
+            .push_bind(self.ask_px_i)
+            .push_bind(self.ask_sz_i);
+    }
+
+    fn push_copy_fields(&self, line: &mut CopyLine<'_>) {
+        line.field(&self.time)
+            .field(&self.symbol)
+            .field(&self.bid_px_i)
+            .field(&self.bid_sz_i)
+            .field(&self.ask_px_i)
+            .field(&self.ask_sz_i);
+    }
+}
+
+/// Microprice (size-weighted mid) of the top of book, derived alongside `BboRow` when both
+/// sides are non-empty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MicropriceRow {
+    pub time: DateTime<Utc>,
+    pub symbol: String,
+    pub microprice_i: i64, // scaled like the prices
+}
+
+impl BatchInsertRow for MicropriceRow {
+    const COLUMNS: &'static [&'static str] = &["time", "symbol", "microprice_i"];
+    const COLUMN_TYPES: &'static [&'static str] =
+        &["TIMESTAMPTZ NOT NULL", "TEXT NOT NULL", "BIGINT NOT NULL"];
+
+    fn table(&self, exchange: &str) -> String {
+        db_table(exchange, "microprice")
+    }
+    fn event_time(&self) -> DateTime<Utc> {
+        self.time
+    }
+    fn scaled_value(&self, column: &str) -> Option<i64> {
+        match column {
+            "microprice_i" => Some(self.microprice_i),
+            _ => None,
+        }
+    }
+
+    fn push_binds(&self, b: &mut Separated<'_, '_, Postgres, &'static str>) {
+        b.push_bind(self.time)
+            .push_bind(self.symbol.clone())
+            .push_bind(self.microprice_i);
+    }
+
+    fn push_copy_fields(&self, line: &mut CopyLine<'_>) {
+        line.field(&self.time)
+            .field(&self.symbol)
+            .field(&self.microprice_i);
+    }
+}
+
+/// Buy / sell volume of one symbol over one window (`time` = window start), derived from the
+/// trades by the `TradeImbalance` aggregator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TradeImbalanceRow {
+    pub time: DateTime<Utc>,
+    pub symbol: String,
+    pub buy_vol_i: i64,  // scaled, sum of buy qty_i
+    pub sell_vol_i: i64, // scaled, sum of sell qty_i
+    pub count: i64,      // trades in the window
+}
+
+impl BatchInsertRow for TradeImbalanceRow {
+    const COLUMNS: &'static [&'static str] =
+        &["time", "symbol", "buy_vol_i", "sell_vol_i", "count"];
+    const COLUMN_TYPES: &'static [&'static str] = &[
+        "TIMESTAMPTZ NOT NULL",
+        "TEXT NOT NULL",
+        "BIGINT NOT NULL",
+        "BIGINT NOT NULL",
+        "BIGINT NOT NULL",
+    ];
+
+    fn table(&self, exchange: &str) -> String {
+        db_table(exchange, "trade_imbalance")
+    }
+    fn event_time(&self) -> DateTime<Utc> {
+        self.time
+    }
+    fn scaled_value(&self, column: &str) -> Option<i64> {
+        match column {
+            "buy_vol_i" => Some(self.buy_vol_i),
+            "sell_vol_i" => Some(self.sell_vol_i),
+            _ => None,
+        }
+    }
+
+    fn push_binds(&self, b: &mut Separated<'_, '_, Postgres, &'static str>) {
+        b.push_bind(self.time)
+            .push_bind(self.symbol.clone())
+            .push_bind(self.buy_vol_i)
+            .push_bind(self.sell_vol_i)
+            .push_bind(self.count);
+    }
+
+    fn push_copy_fields(&self, line: &mut CopyLine<'_>) {
+        line.field(&self.time)
+            .field(&self.symbol)
+            .field(&self.buy_vol_i)
+            .field(&self.sell_vol_i)
+            .field(&self.count);
+    }
+}
@@ -1,10 +1,437 @@
-use sqlx::Postgres;
+use chrono::{DateTime, SecondsFormat, Utc};
 use sqlx::query_builder::Separated;
+use sqlx::{Postgres, QueryBuilder};
+
+/// `ON CONFLICT` behaviour of `BatchInsertRow::CONFLICT_TARGET`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictAction {
+    /// Keep the stored row.
+    DoNothing,
+    /// Overwrite the stored row's non-target columns with the new values.
+    DoUpdate,
+}
 
 pub trait BatchInsertRow {
     fn table(&self, exchange: &str) -> String;
     const COLUMNS: &'static [&'static str];
+    /// Postgres type + nullability per column (same order as `COLUMNS`), e.g. "BIGINT NOT NULL".
+    /// Mirrors dbsetup.sql; used by `writer.auto_create_tables`.
+    const COLUMN_TYPES: &'static [&'static str];
+
+    /// Columns of a unique index on the table (`"time, symbol, trade_id"`). When set, inserts
+    /// add `ON CONFLICT (...)` so re-sent rows are not duplicated (COPY is skipped, it has no
+    /// conflict handling) and rows written count only what the server applied.
+    const CONFLICT_TARGET: Option<&'static str> = None;
+    /// What a conflicting row does (only with `CONFLICT_TARGET`).
+    const CONFLICT_ACTION: ConflictAction = ConflictAction::DoNothing;
+
+    /// Event time of the row (used for the persisted watermark).
+    fn event_time(&self) -> DateTime<Utc>;
+
+    /// Column holding `dedup_key` (`"trade_id"`). With a `CONFLICT_TARGET`, a flush tracked for
+    /// `publish_persisted_only` reads it back (`RETURNING`) to tell inserted rows from rows the
+    /// conflict skipped.
+    const DEDUP_COLUMN: Option<&'static str> = None;
+
+    /// Venue id of the row; with `publish_persisted_only`, repeats within one flush are dropped
+    /// (None = never deduped).
+    fn dedup_key(&self) -> Option<i64> {
+        None
+    }
+
+    /// Scaled (fixed-point) value of `column`, for `writer.value_ranges`
+    /// (None = not a scaled column of this row, or NULL).
+    fn scaled_value(&self, _column: &str) -> Option<i64> {
+        None
+    }
 
     fn push_binds(&self, b: &mut Separated<'_, '_, Postgres, &'static str>);
+
+    /// Same values as `push_binds`, in `COLUMNS` order, as one COPY text line (`writer.use_copy`).
+    fn push_copy_fields(&self, line: &mut CopyLine<'_>);
+}
+
+/// One row of a `COPY ... FROM STDIN` (text format): tab-separated fields, `\N` for NULL,
+/// backslash escapes for tab / newline / CR / backslash.
+pub struct CopyLine<'a> {
+    buf: &'a mut String,
+    first: bool,
+}
+
+impl<'a> CopyLine<'a> {
+    pub fn new(buf: &'a mut String) -> Self {
+        Self { buf, first: true }
+    }
+
+    pub fn field<V: CopyField + ?Sized>(&mut self, v: &V) -> &mut Self {
+        if !self.first {
+            self.buf.push('\t');
+        }
+        self.first = false;
+        v.write_copy(self.buf);
+        self
+    }
+
+    /// Terminate the row.
+    pub fn end(self) {
+        self.buf.push('\n');
+    }
+}
+
+/// A value that can be written as a COPY text field.
+pub trait CopyField {
+    fn write_copy(&self, out: &mut String);
+}
+
+macro_rules! copy_field_display {
+    ($($t:ty),*) => {
+        $(impl CopyField for $t {
+            fn write_copy(&self, out: &mut String) {
+                use std::fmt::Write;
+                let _ = write!(out, "{self}");
+            }
+        })*
+    };
+}
+copy_field_display!(i16, i32, i64);
+
+impl CopyField for bool {
+    fn write_copy(&self, out: &mut String) {
+        out.push(if *self { 't' } else { 'f' });
+    }
+}
+
+impl CopyField for str {
+    fn write_copy(&self, out: &mut String) {
+        for c in self.chars() {
+            match c {
+                '\\' => out.push_str("\\\\"),
+                '\t' => out.push_str("\\t"),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                c => out.push(c),
+            }
+        }
+    }
+}
+
+impl CopyField for String {
+    fn write_copy(&self, out: &mut String) {
+        self.as_str().write_copy(out);
+    }
+}
+
+impl CopyField for DateTime<Utc> {
+    fn write_copy(&self, out: &mut String) {
+        out.push_str(&self.to_rfc3339_opts(SecondsFormat::AutoSi, true));
+    }
+}
+
+impl<T: CopyField> CopyField for Option<T> {
+    fn write_copy(&self, out: &mut String) {
+        match self {
+            Some(v) => v.write_copy(out),
+            None => out.push_str("\\N"),
+        }
+    }
 }
 
+/// Check that `row` binds one value per column of `T::COLUMNS`, with `push_binds` and with
+/// `push_copy_fields` (and that `COLUMN_TYPES` matches too). A row type edited on one side only
+/// fails here instead of with a cryptic Postgres error mid-batch; `writer` debug-asserts it on
+/// every batch. `Err` names the row type and the counts.
+pub fn check_row_shape<T: BatchInsertRow>(row: &T) -> Result<(), String> {
+    let columns = T::COLUMNS.len();
+
+    let mut qb: QueryBuilder<Postgres> = QueryBuilder::new("INSERT INTO t ");
+    qb.push_values(std::iter::once(row), |mut b, row| {
+        row.push_binds(&mut b);
+    });
+    let binds = qb.sql().matches('$').count();
+
+    let mut buf = String::new();
+    let mut line = CopyLine::new(&mut buf);
+    row.push_copy_fields(&mut line);
+    let copy_fields = if buf.is_empty() {
+        0
+    } else {
+        buf.split('\t').count()
+    };
+
+    if binds == columns && copy_fields == columns && T::COLUMN_TYPES.len() == columns {
+        return Ok(());
+    }
+    Err(format!(
+        "{}: {columns} COLUMNS, {} COLUMN_TYPES, {binds} binds, {copy_fields} COPY fields",
+        std::any::type_name::<T>(),
+        T::COLUMN_TYPES.len()
+    ))
+}
+
+/// Quote a `schema.table` name for SQL (`"schema"."table"`).
+pub fn quote_table_name(table: &str) -> String {
+    format!("\"{}\"", table.replace('.', "\".\""))
+}
+
+/// `CREATE TABLE IF NOT EXISTS` for `T`'s columns (see `BatchInsertRow::COLUMN_TYPES`).
+pub fn create_table_sql<T: BatchInsertRow>(table: &str) -> String {
+    debug_assert_eq!(T::COLUMNS.len(), T::COLUMN_TYPES.len());
+    let columns = T::COLUMNS
+        .iter()
+        .zip(T::COLUMN_TYPES)
+        .map(|(name, ty)| format!("\"{name}\" {ty}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "CREATE TABLE IF NOT EXISTS {} ({columns})",
+        quote_table_name(table)
+    )
+}
+
+/// `ON CONFLICT (...) DO ...` clause for `T`, or None without a `CONFLICT_TARGET`.
+pub fn on_conflict_sql<T: BatchInsertRow>() -> Option<String> {
+    let target = T::CONFLICT_TARGET?;
+    let target_cols: Vec<&str> = target.split(',').map(str::trim).collect();
+    let quoted = target_cols
+        .iter()
+        .map(|c| format!("\"{c}\""))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let updates = T::COLUMNS
+        .iter()
+        .filter(|c| !target_cols.contains(c))
+        .map(|c| format!("\"{c}\" = EXCLUDED.\"{c}\""))
+        .collect::<Vec<_>>();
+    // Nothing left to update if every column is part of the key
+    if T::CONFLICT_ACTION == ConflictAction::DoNothing || updates.is_empty() {
+        return Some(format!("ON CONFLICT ({quoted}) DO NOTHING"));
+    }
+    Some(format!(
+        "ON CONFLICT ({quoted}) DO UPDATE SET {}",
+        updates.join(", ")
+    ))
+}
+
+/// Unique index backing `T::CONFLICT_TARGET` (for `writer.auto_create_tables`).
+pub fn conflict_index_sql<T: BatchInsertRow>(table: &str) -> Option<String> {
+    let target = T::CONFLICT_TARGET?;
+    let quoted = target
+        .split(',')
+        .map(|c| format!("\"{}\"", c.trim()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    Some(format!(
+        "CREATE UNIQUE INDEX IF NOT EXISTS \"{}_conflict_uq\" ON {} ({quoted})",
+        table.replace('.', "_"),
+        quote_table_name(table)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::rows::{
+        BboRow, DepthDeltaDBRow, DepthSnapshotDBRow, FundingDBRow, LiquidationDBRow, MicropriceRow,
+        OpenInterestDBRow, TradeDBRow, TradeImbalanceRow,
+    };
+
+    fn same_len<T: BatchInsertRow>() -> bool {
+        T::COLUMNS.len() == T::COLUMN_TYPES.len()
+    }
+
+    #[test]
+    fn create_table_sql_matches_columns() {
+        assert_eq!(
+            create_table_sql::<OpenInterestDBRow>("ex_binance_linear.open_interest"),
+            "CREATE TABLE IF NOT EXISTS \"ex_binance_linear\".\"open_interest\" \
+             (\"time\" TIMESTAMPTZ NOT NULL, \"symbol\" TEXT NOT NULL, \"oi_i\" BIGINT NOT NULL)"
+        );
+        assert!(same_len::<TradeDBRow>());
+        assert!(same_len::<DepthDeltaDBRow>());
+        assert!(same_len::<OpenInterestDBRow>());
+        assert!(same_len::<FundingDBRow>());
+        assert!(same_len::<LiquidationDBRow>());
+        assert!(same_len::<BboRow>());
+        assert!(same_len::<MicropriceRow>());
+    }
+
+    struct UpsertRow;
+
+    impl BatchInsertRow for UpsertRow {
+        const COLUMNS: &'static [&'static str] = &["time", "symbol", "oi_i"];
+        const COLUMN_TYPES: &'static [&'static str] = &["TIMESTAMPTZ", "TEXT", "BIGINT"];
+        const CONFLICT_TARGET: Option<&'static str> = Some("time, symbol");
+        const CONFLICT_ACTION: ConflictAction = ConflictAction::DoUpdate;
+
+        fn table(&self, _exchange: &str) -> String {
+            "t".into()
+        }
+        fn event_time(&self) -> DateTime<Utc> {
+            DateTime::UNIX_EPOCH
+        }
+        fn push_binds(&self, _b: &mut Separated<'_, '_, Postgres, &'static str>) {}
+        fn push_copy_fields(&self, _line: &mut CopyLine<'_>) {}
+    }
+
+    #[test]
+    fn on_conflict_sql_follows_target_and_action() {
+        assert_eq!(on_conflict_sql::<OpenInterestDBRow>(), None);
+        assert_eq!(
+            conflict_index_sql::<OpenInterestDBRow>("ex.open_interest"),
+            None
+        );
+        assert_eq!(
+            on_conflict_sql::<TradeDBRow>().as_deref(),
+            Some("ON CONFLICT (\"time\", \"symbol\", \"trade_id\") DO NOTHING")
+        );
+        assert_eq!(
+            conflict_index_sql::<TradeDBRow>("ex_binance_linear.trades").as_deref(),
+            Some(
+                "CREATE UNIQUE INDEX IF NOT EXISTS \"ex_binance_linear_trades_conflict_uq\" \
+                 ON \"ex_binance_linear\".\"trades\" (\"time\", \"symbol\", \"trade_id\")"
+            )
+        );
+        assert_eq!(
+            on_conflict_sql::<UpsertRow>().as_deref(),
+            Some("ON CONFLICT (\"time\", \"symbol\") DO UPDATE SET \"oi_i\" = EXCLUDED.\"oi_i\"")
+        );
+    }
+
+    #[test]
+    fn copy_line_escapes_text_and_writes_nulls() {
+        let row = TradeDBRow {
+            time: DateTime::from_timestamp_millis(1_700_000_000_123).unwrap(),
+            symbol: "BTC\tUSDT\\x".into(),
+            side: 1,
+            price_i: 9_000_000_000_000,
+            qty_i: 100,
+            trade_id: None,
+            is_maker: Some(true),
+        };
+        let mut buf = String::new();
+        let mut line = CopyLine::new(&mut buf);
+        row.push_copy_fields(&mut line);
+        line.end();
+        assert_eq!(
+            buf,
+            "2023-11-14T22:13:20.123Z\tBTC\\tUSDT\\\\x\t1\t9000000000000\t100\t\\N\tt\n"
+        );
+        assert_eq!(
+            buf.trim_end().split('\t').count(),
+            TradeDBRow::COLUMNS.len()
+        );
+    }
+
+    #[test]
+    fn every_row_type_binds_one_value_per_column() {
+        let t = DateTime::UNIX_EPOCH;
+        let s = || "BTCUSDT".to_string();
+        let checks = [
+            check_row_shape(&TradeDBRow {
+                time: t,
+                symbol: s(),
+                side: 0,
+                price_i: 1,
+                qty_i: 1,
+                trade_id: None,
+                is_maker: None,
+            }),
+            check_row_shape(&DepthDeltaDBRow {
+                time: t,
+                symbol: s(),
+                side: 0,
+                price_i: 1,
+                size_i: 1,
+                seq: None,
+            }),
+            check_row_shape(&DepthSnapshotDBRow {
+                time: t,
+                symbol: s(),
+                side: 0,
+                price_i: 1,
+                size_i: 1,
+                level_idx: 0,
+                snapshot_id: 1,
+            }),
+            check_row_shape(&OpenInterestDBRow {
+                time: t,
+                symbol: s(),
+                oi_i: 1,
+            }),
+            check_row_shape(&FundingDBRow {
+                time: t,
+                symbol: s(),
+                funding_rate: 1,
+                funding_time: None,
+            }),
+            check_row_shape(&LiquidationDBRow {
+                time: t,
+                symbol: s(),
+                side: 0,
+                price_i: None,
+                qty_i: 1,
+                liq_id: None,
+            }),
+            check_row_shape(&BboRow {
+                time: t,
+                symbol: s(),
+                bid_px_i: None,
+                bid_sz_i: None,
+                ask_px_i: None,
+                ask_sz_i: None,
+            }),
+            check_row_shape(&MicropriceRow {
+                time: t,
+                symbol: s(),
+                microprice_i: 1,
+            }),
+            check_row_shape(&TradeImbalanceRow {
+                time: t,
+                symbol: s(),
+                buy_vol_i: 0,
+                sell_vol_i: 0,
+                count: 0,
+            }),
+        ];
+        for check in checks {
+            assert_eq!(check, Ok(()));
+        }
+    }
+
+    /// A column added to `COLUMNS` / `push_copy_fields` but not to `push_binds`.
+    struct DriftedRow;
+
+    impl BatchInsertRow for DriftedRow {
+        const COLUMNS: &'static [&'static str] = &["time", "symbol", "oi_i"];
+        const COLUMN_TYPES: &'static [&'static str] = &["TIMESTAMPTZ", "TEXT", "BIGINT"];
+
+        fn table(&self, _exchange: &str) -> String {
+            "t".into()
+        }
+        fn event_time(&self) -> DateTime<Utc> {
+            DateTime::UNIX_EPOCH
+        }
+        fn push_binds(&self, b: &mut Separated<'_, '_, Postgres, &'static str>) {
+            b.push_bind(DateTime::<Utc>::UNIX_EPOCH)
+                .push_bind("BTCUSDT");
+        }
+        fn push_copy_fields(&self, line: &mut CopyLine<'_>) {
+            line.field(&DateTime::<Utc>::UNIX_EPOCH)
+                .field("BTCUSDT")
+                .field(&1i64);
+        }
+    }
+
+    #[test]
+    fn row_type_with_fewer_binds_than_columns_is_detected() {
+        let err = check_row_shape(&DriftedRow).unwrap_err();
+        assert!(
+            err.ends_with("DriftedRow: 3 COLUMNS, 3 COLUMN_TYPES, 2 binds, 3 COPY fields"),
+            "{err}"
+        );
+
+        // The no-op test row binds nothing at all
+        assert!(check_row_shape(&UpsertRow).is_err());
+    }
+}
@@ -14,55 +14,611 @@
 //!
 //! Caller usage pattern:
 //!     batch.rows.push(row);
-//!     db.write_batch(&mut batch).await?;
+//!     let out = db.write_batch(&mut batch).await?;
+//!     if out.flushed { /* advance durable offset to out.watermark */ }
 
 use crate::app::StartStreamParams;
 use crate::app::control::make_batch_key;
 use crate::app::{ExchangeId, StreamId, StreamKnobs, StreamSpec};
 use crate::app::{StreamKind, StreamTransport};
 use crate::db::Batch;
-use crate::db::config::WriterConfig;
+use crate::db::budget::PendingBatchBudget;
+use crate::db::config::{MinHealthyShards, WriterConfig};
+use crate::db::health::ShardHealthReport;
+use crate::db::ledger::CommitLedger;
 use crate::db::metrics::DbMetrics;
 use crate::db::pools::DbPools;
-use crate::db::traits::BatchInsertRow;
+use crate::db::traits::{
+    BatchInsertRow, CopyLine, check_row_shape, conflict_index_sql, create_table_sql,
+    on_conflict_sql, quote_table_name,
+};
 use crate::error::{AppError, AppResult};
+use crate::telemetry::throttle::log_throttle;
+use chrono::{DateTime, Utc};
 use sqlx::Row;
-use sqlx::{Postgres, QueryBuilder};
-use std::collections::HashSet;
+use sqlx::{PgConnection, Postgres, QueryBuilder};
+use std::collections::{BTreeMap, HashSet};
 use std::str::FromStr;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::Semaphore;
 
+/// Result of `write_batch`: what (if anything) was durably committed.
+///
+/// `Ok` alone does not mean rows were persisted (batches below thresholds are kept);
+/// only advance offsets / watermarks when `flushed` is true.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct WriteOutcome {
+    /// True if rows were written (and cleared from the batch).
+    pub flushed: bool,
+    /// Rows persisted by this call.
+    pub rows_written: u64,
+    /// Rows dropped before the insert as repeats of another row in the batch.
+    pub deduped: u64,
+    /// Rows rejected before the insert for a value outside `writer.value_ranges`.
+    pub rejected: u64,
+    /// Max event time of the persisted rows.
+    pub watermark: Option<DateTime<Utc>>,
+}
+
+impl WriteOutcome {
+    /// Nothing written (below thresholds, empty batch, or writes disabled).
+    pub fn not_flushed() -> Self {
+        Self::default()
+    }
+
+    /// Rows were committed.
+    pub fn flushed<T: BatchInsertRow>(rows: &[T]) -> Self {
+        Self {
+            flushed: true,
+            rows_written: rows.len() as u64,
+            deduped: 0,
+            rejected: 0,
+            watermark: rows.iter().map(BatchInsertRow::event_time).max(),
+        }
+    }
+}
+
+/// One stream for `DbHandler::upsert_stream_registry_many`.
+#[derive(Debug, Clone)]
+pub struct RegistryUpsert {
+    pub spec: StreamSpec,
+    pub knobs: StreamKnobs,
+    pub enabled: bool,
+}
+
+/// Rows per multi-row registry upsert (13 binds each; Postgres allows 65535 per statement).
+const REGISTRY_UPSERT_CHUNK: usize = 1000;
+
+/// Postgres SQLSTATEs for a missing relation / schema (`undefined_table`, `invalid_schema_name`).
+const MISSING_RELATION_CODES: [&str; 2] = ["42P01", "3F000"];
+
+/// True if `code` is a SQLSTATE meaning the target table or schema does not exist.
+pub fn is_missing_relation_code(code: &str) -> bool {
+    MISSING_RELATION_CODES.contains(&code)
+}
+
+/// Postgres SQLSTATEs a COPY may hit where an INSERT of the same rows would not
+/// (`feature_not_supported`: a pooler without COPY, `insufficient_privilege`).
+const COPY_FALLBACK_CODES: [&str; 2] = ["0A000", "42501"];
+
+/// True if a COPY failed for a reason of its own (protocol, the codes above): the batch may go
+/// through INSERT. Data, constraint and connection errors would fail the INSERT too.
+fn is_copy_specific(e: &sqlx::Error) -> bool {
+    match e {
+        sqlx::Error::Protocol(_) => true,
+        sqlx::Error::Database(db) => db
+            .code()
+            .is_some_and(|code| COPY_FALLBACK_CODES.contains(&code.as_ref())),
+        _ => false,
+    }
+}
+
+/// SQLSTATE `invalid_column_reference`: no unique index matches the `ON CONFLICT` target.
+const NO_CONFLICT_INDEX_CODE: &str = "42P10";
+
+/// True if an INSERT failed because the table lacks the unique index of `CONFLICT_TARGET`
+/// (a deployment older than the index, see migrations/).
+fn is_missing_conflict_index(e: &sqlx::Error) -> bool {
+    e.as_database_error()
+        .and_then(|db| db.code())
+        .is_some_and(|code| code == NO_CONFLICT_INDEX_CODE)
+}
+
+/// `relname` tables in `ex_*` schemas without a unique, non-partial index on exactly the
+/// columns of `$2` (what `ON CONFLICT (...)` infers), as `schema.table`.
+const TABLES_WITHOUT_CONFLICT_INDEX_SQL: &str = r#"
+SELECT n.nspname || '.' || c.relname
+FROM pg_class c
+JOIN pg_namespace n ON n.oid = c.relnamespace
+WHERE c.relname = $1
+  AND n.nspname LIKE 'ex\_%'
+  AND c.relkind IN ('r', 'p')
+  AND NOT EXISTS (
+    SELECT 1
+    FROM pg_index i
+    WHERE i.indrelid = c.oid
+      AND i.indisunique
+      AND i.indpred IS NULL
+      AND ARRAY(
+            SELECT a.attname::text
+            FROM pg_attribute a
+            WHERE a.attrelid = c.oid AND a.attnum = ANY (i.indkey)
+            ORDER BY 1
+          ) = ARRAY(SELECT unnest($2::text[]) ORDER BY 1)
+  )
+"#;
+
+fn is_missing_relation(e: &sqlx::Error) -> bool {
+    e.as_database_error()
+        .and_then(|db| db.code())
+        .is_some_and(|code| is_missing_relation_code(&code))
+}
+
+/// Map an INSERT failure: missing table/schema -> `AppError::MissingTable` (not retryable),
+/// anything else stays `AppError::Sqlx`.
+fn classify_insert_error(e: sqlx::Error, table: &str) -> AppError {
+    if !is_missing_relation(&e) {
+        return AppError::Sqlx(e);
+    }
+
+    if let Some(suppressed) = log_throttle().allow(&format!("missing table:{table}")) {
+        tracing::error!(
+            table,
+            suppressed,
+            error = %e,
+            "table does not exist: writes to it fail until the schema migration is run (not retried)"
+        );
+    }
+    AppError::MissingTable {
+        table: table.to_string(),
+    }
+}
+
+/// What a flush applied: rows written, and (`RETURNING`) the dedup keys of the inserted rows.
+#[derive(Debug, Default)]
+struct Inserted {
+    rows: u64,
+    keys: Option<HashSet<i64>>,
+}
+
+/// INSERT `rows` into `table` in chunks of `chunk_rows`. Rows skipped by `on_conflict`
+/// do not count; with `returning` (`T::DEDUP_COLUMN`) the keys of the inserted rows come back.
+async fn insert_rows<T: BatchInsertRow>(
+    conn: &mut PgConnection,
+    table: &str,
+    rows: &[T],
+    chunk_rows: usize,
+    on_conflict: Option<&str>,
+    returning: Option<&str>,
+) -> Result<Inserted, sqlx::Error> {
+    let mut out = Inserted {
+        rows: 0,
+        keys: returning.map(|_| HashSet::new()),
+    };
+
+    for chunk in rows.chunks(chunk_rows) {
+        let mut qb = insert_chunk_query(table, chunk, on_conflict);
+        match (returning, out.keys.as_mut()) {
+            (Some(column), Some(keys)) => {
+                qb.push(" RETURNING \"");
+                qb.push(column);
+                qb.push("\"");
+                let returned: Vec<Option<i64>> =
+                    qb.build_query_scalar().fetch_all(&mut *conn).await?;
+                out.rows += returned.len() as u64;
+                keys.extend(returned.into_iter().flatten());
+            }
+            _ => out.rows += qb.build().execute(&mut *conn).await?.rows_affected(),
+        }
+    }
+
+    Ok(out)
+}
+
+/// `INSERT INTO table (columns) VALUES (...), ... [ON CONFLICT ...]` for one chunk.
+fn insert_chunk_query<'a, T: BatchInsertRow>(
+    table: &str,
+    chunk: &'a [T],
+    on_conflict: Option<&str>,
+) -> QueryBuilder<'a, Postgres> {
+    let mut qb: QueryBuilder<Postgres> = QueryBuilder::new("INSERT INTO ");
+    qb.push(quote_table_name(table));
+
+    qb.push(" (");
+
+    for (i, col) in T::COLUMNS.iter().enumerate() {
+        if i > 0 {
+            qb.push(", ");
+        }
+        qb.push("\"");
+        qb.push(*col);
+        qb.push("\"");
+    }
+    qb.push(") ");
+
+    qb.push_values(chunk.iter(), |mut b, row| {
+        row.push_binds(&mut b);
+    });
+
+    if let Some(on_conflict) = on_conflict {
+        qb.push(" ");
+        qb.push(on_conflict);
+    }
+    qb
+}
+
+/// `COPY table (columns) FROM STDIN` (text format) of `rows`, one send per `chunk_rows` rows.
+/// A single statement: either every row lands or none does. Returns rows written.
+async fn copy_rows<T: BatchInsertRow>(
+    conn: &mut PgConnection,
+    table: &str,
+    rows: &[T],
+    chunk_rows: usize,
+) -> Result<u64, sqlx::Error> {
+    let columns = T::COLUMNS
+        .iter()
+        .map(|c| format!("\"{c}\""))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let stmt = format!("COPY {} ({columns}) FROM STDIN", quote_table_name(table));
+
+    let mut copy = conn.copy_in_raw(&stmt).await?;
+    let mut buf = String::new();
+    for chunk in rows.chunks(chunk_rows.max(1)) {
+        buf.clear();
+        for row in chunk {
+            let mut line = CopyLine::new(&mut buf);
+            row.push_copy_fields(&mut line);
+            line.end();
+        }
+        if let Err(e) = copy.send(buf.as_bytes()).await {
+            let _ = copy.abort("client send failed").await;
+            return Err(e);
+        }
+    }
+    copy.finish().await
+}
+
+/// Auto-DDL for `writer.auto_create_tables`: schema + table from `T::COLUMN_TYPES`, the unique
+/// index of `T::CONFLICT_TARGET`, and a hypertable on `time` when TimescaleDB is installed. Idempotent (IF NOT EXISTS everywhere).
+async fn create_missing_table<T: BatchInsertRow>(
+    conn: &mut PgConnection,
+    table: &str,
+) -> Result<(), sqlx::Error> {
+    if let Some((schema, _)) = table.split_once('.') {
+        sqlx::query(&format!("CREATE SCHEMA IF NOT EXISTS \"{schema}\""))
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    sqlx::query(&create_table_sql::<T>(table))
+        .execute(&mut *conn)
+        .await?;
+    if let Some(index) = conflict_index_sql::<T>(table) {
+        sqlx::query(&index).execute(&mut *conn).await?;
+    }
+
+    let timescale: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM pg_extension WHERE extname = 'timescaledb')",
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+    if timescale && T::COLUMNS.contains(&"time") {
+        sqlx::query("SELECT create_hypertable($1::regclass, 'time', if_not_exists => TRUE)")
+            .bind(quote_table_name(table))
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    tracing::warn!(
+        table,
+        timescale,
+        "auto_create_tables: created missing table (dev/onboarding only; run dbsetup.sql in prod)"
+    );
+    Ok(())
+}
+
+/// What a `write_batch` flush committed (passed to the post-commit hook).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitInfo {
+    pub shard_id: String,
+    /// Fully qualified target table.
+    pub table: String,
+    pub rows: u64,
+    /// Max event time of the committed rows.
+    pub max_event_time: Option<DateTime<Utc>>,
+}
+
+/// Post-commit callback for custom side effects (cache notify, watermark bump, ...).
+///
+/// Runs inline on the writing task after the inflight permit is released; keep it cheap and
+/// non-blocking (hand heavy work to a channel/task).
+pub type CommitHook = Arc<dyn Fn(&CommitInfo) + Send + Sync>;
+
 /// Main DB handler: routes -> acquires pool conn -> writes batch -> updates metrics.
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct DbHandler {
     pools: Arc<DbPools>,
     writer: WriterConfig,
     metrics: Arc<DbMetrics>,
     inflight: Arc<Semaphore>,
+    pending_budget: Arc<PendingBatchBudget>,
+    min_healthy_shards: MinHealthyShards,
+    commit_hook: Option<CommitHook>,
+    ledger: Arc<CommitLedger>,
+    /// (shard id, table) written without `ON CONFLICT`: the unique index of the row type's
+    /// `CONFLICT_TARGET` is missing there (see `check_conflict_indexes`).
+    no_conflict_index: Arc<std::sync::Mutex<HashSet<(String, String)>>>,
+}
+
+impl std::fmt::Debug for DbHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DbHandler")
+            .field("pools", &self.pools)
+            .field("writer", &self.writer)
+            .field("metrics", &self.metrics)
+            .field("inflight", &self.inflight)
+            .field("pending_budget", &self.pending_budget)
+            .field("min_healthy_shards", &self.min_healthy_shards)
+            .field("commit_hook", &self.commit_hook.is_some())
+            .field("ledger", &self.ledger)
+            .field("no_conflict_index", &self.no_conflict_index)
+            .finish()
+    }
 }
 
 impl DbHandler {
     pub fn new(pools: Arc<DbPools>, writer: WriterConfig, metrics: Arc<DbMetrics>) -> Self {
         let inflight = Arc::new(Semaphore::new(writer.max_inflight_batches));
+        let pending_budget = Arc::new(PendingBatchBudget::from_config(
+            &writer,
+            Some(Arc::clone(&metrics)),
+        ));
         Self {
             pools,
             writer,
             metrics,
             inflight,
+            pending_budget,
+            min_healthy_shards: MinHealthyShards::default(),
+            commit_hook: None,
+            ledger: Arc::new(CommitLedger::new()),
+            no_conflict_index: Arc::new(std::sync::Mutex::new(HashSet::new())),
+        }
+    }
+
+    /// Invoke `hook` after every successful `write_batch` flush (never on the no-flush path).
+    pub fn with_commit_hook(mut self, hook: CommitHook) -> Self {
+        self.commit_hook = Some(hook);
+        self
+    }
+
+    pub fn with_min_healthy_shards(mut self, min: MinHealthyShards) -> Self {
+        self.min_healthy_shards = min;
+        self
+    }
+
+    /// Probe every shard and compare against `health.min_healthy_shards`; carries each
+    /// shard's breaker state.
+    pub async fn shard_health_report(&self) -> ShardHealthReport {
+        let probes = self.pools.probe_shards().await;
+        let breakers = self
+            .pools
+            .shards_snapshot()
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .map(|s| (s.shard.id, s.breaker_state));
+        ShardHealthReport::from_probes(probes, self.min_healthy_shards).with_breakers(breakers)
+    }
+
+    /// True only when at least `min_healthy_shards` shards answer a connectivity probe.
+    pub async fn is_write_ready(&self) -> bool {
+        self.shard_health_report().await.is_write_ready()
+    }
+
+    /// Stop accepting writes (shutdown): closes the inflight semaphore so every flush waiting
+    /// for a permit, and every later one, returns `AppError::Shutdown` instead of blocking.
+    /// Writes already holding a permit run to completion.
+    pub fn close(&self) {
+        self.inflight.close();
+    }
+
+    #[inline]
+    pub fn is_closed(&self) -> bool {
+        self.inflight.is_closed()
+    }
+
+    /// Rows/batches committed per shard and per-stream watermarks since startup.
+    pub fn commit_ledger(&self) -> Arc<CommitLedger> {
+        Arc::clone(&self.ledger)
+    }
+
+    /// Startup check for the unique index behind `T::CONFLICT_TARGET` on the `relname` table of
+    /// every exchange schema, on every shard. Tables without it are written with a plain INSERT
+    /// (replayed rows may then be stored twice) and reported with a warning; run
+    /// migrations/0001_trades_conflict_index.sql to add it. Returns `shard:table` of each.
+    /// A shard that cannot be queried is skipped (the INSERT path falls back on its own).
+    pub async fn check_conflict_indexes<T: BatchInsertRow>(&self, relname: &str) -> Vec<String> {
+        let Some(target) = T::CONFLICT_TARGET else {
+            return Vec::new();
+        };
+        let columns: Vec<String> = target.split(',').map(|c| c.trim().to_string()).collect();
+
+        let mut missing = Vec::new();
+        for (shard_id, pool) in self.pools.all_pools().await {
+            let tables: Vec<String> = match sqlx::query_scalar(TABLES_WITHOUT_CONFLICT_INDEX_SQL)
+                .bind(relname)
+                .bind(&columns)
+                .fetch_all(&pool)
+                .await
+            {
+                Ok(tables) => tables,
+                Err(e) => {
+                    tracing::warn!(shard = %shard_id, table = relname, error = %e, "conflict index check failed; skipped");
+                    continue;
+                }
+            };
+            for table in tables {
+                tracing::warn!(
+                    shard = %shard_id,
+                    table = %table,
+                    conflict_target = target,
+                    "unique index of the conflict target is missing: writing with plain INSERT \
+                     (replays may duplicate rows); run migrations/0001_trades_conflict_index.sql"
+                );
+                missing.push(format!("{shard_id}:{table}"));
+                self.mark_no_conflict_index(&shard_id, &table);
+            }
         }
+        missing
     }
 
-    /// Write a batch using INSERT ... VALUES (...), (...), ...
+    fn mark_no_conflict_index(&self, shard_id: &str, table: &str) {
+        self.no_conflict_index
+            .lock()
+            .expect("no_conflict_index mutex poisoned")
+            .insert((shard_id.to_string(), table.to_string()));
+    }
+
+    fn has_conflict_index(&self, shard_id: &str, table: &str) -> bool {
+        !self
+            .no_conflict_index
+            .lock()
+            .expect("no_conflict_index mutex poisoned")
+            .contains(&(shard_id.to_string(), table.to_string()))
+    }
+
+    /// Global pending-batch memory accounting shared by every batch written through this handler.
+    pub fn pending_budget(&self) -> Arc<PendingBatchBudget> {
+        Arc::clone(&self.pending_budget)
+    }
+
+    /// Write a batch using INSERT ... VALUES (...), (...), ... or, with `writer.use_copy`,
+    /// COPY ... FROM STDIN. Tables with a `CONFLICT_TARGET` (trades) always INSERT: COPY
+    /// cannot skip conflicting rows. A COPY failing for a COPY-specific reason falls back to
+    /// INSERT once; any other error is the write's.
     ///
     /// NEW batching behavior:
-    /// - If batch is empty: returns Ok
-    /// - If batch has fewer than batch_size rows AND flush_interval has NOT elapsed: returns Ok (keeps rows)
-    /// - Otherwise: writes (in chunks of batch_size), then clears rows and resets enqueued_at
-    pub async fn write_batch<T: BatchInsertRow>(&self, batch: &mut Batch<T>) -> AppResult<()> {
+    /// - If batch is empty: returns `WriteOutcome::not_flushed()`
+    /// - If batch has fewer than batch_size rows AND flush_interval has NOT elapsed: returns
+    ///   `WriteOutcome::not_flushed()` (keeps rows) unless the global pending-memory budget
+    ///   picked it for an early flush
+    /// - Otherwise: writes (in chunks of chunk_rows), then clears rows and resets enqueued_at.
+    ///   With `track_persisted`, rows repeating a `dedup_key` are dropped first and only the
+    ///   rows the insert applied (not skipped by the conflict target) are kept for
+    ///   `take_persisted`;
+    ///   returns rows written + watermark (max event time) of the committed rows, then runs the
+    ///   commit hook (if any) outside the inflight permit
+    pub async fn write_batch<T: BatchInsertRow>(
+        &self,
+        batch: &mut Batch<T>,
+    ) -> AppResult<WriteOutcome> {
+        // Registers on first write, then reports the current size (rows were extended by caller).
+        batch.attach_budget(Arc::clone(&self.pending_budget));
+
         if !batch.should_flush() {
-            return Ok(());
+            return Ok(WriteOutcome::not_flushed());
+        }
+
+        self.write_pending(batch).await
+    }
+
+    /// Write whatever rows `batch` holds now, bypassing the `should_flush()` gate (batch size /
+    /// flush interval), e.g. to drain partially filled batches on shutdown. Same path as
+    /// `write_batch` otherwise: dedup, range check, `chunk_rows`, inflight permits, metrics,
+    /// ledger and commit hook. No-op on an empty batch.
+    pub async fn force_flush<T: BatchInsertRow>(
+        &self,
+        batch: &mut Batch<T>,
+    ) -> AppResult<WriteOutcome> {
+        if batch.is_empty() {
+            return Ok(WriteOutcome::not_flushed());
+        }
+        batch.attach_budget(Arc::clone(&self.pending_budget));
+
+        self.write_pending(batch).await
+    }
+
+    /// SQL of the INSERT statements a flush of `batch` would run (one per `chunk_rows` chunk),
+    /// with `$n` placeholders for the values. Nothing is executed: no connection, no metrics.
+    /// Shows the computed table name (`"ex_<exchange>"."trades"`) when onboarding an exchange.
+    ///
+    /// Rows are taken as they are now (before the flush-time dedup and range checks); with
+    /// `writer.use_copy`, tables without a `CONFLICT_TARGET` are written with COPY instead.
+    pub fn preview_batch_sql<T: BatchInsertRow>(&self, batch: &Batch<T>) -> AppResult<Vec<String>> {
+        let Some(first) = batch.rows.first() else {
+            return Ok(Vec::new());
+        };
+        let table = first.table(&batch.key.exchange);
+        let on_conflict = on_conflict_sql::<T>();
+
+        Ok(batch
+            .rows
+            .chunks(batch.chunk_rows.max(1))
+            .map(|chunk| insert_chunk_query(&table, chunk, on_conflict.as_deref()).into_sql())
+            .collect())
+    }
+
+    async fn write_pending<T: BatchInsertRow>(
+        &self,
+        batch: &mut Batch<T>,
+    ) -> AppResult<WriteOutcome> {
+        // Repeated venue ids are never published with `publish_persisted_only` (repeats across
+        // flushes are caught by the conflict target, see `returning` below)
+        let deduped = if batch.track_persisted {
+            batch.dedup_pending()
+        } else {
+            0
+        };
+        if deduped > 0 {
+            self.metrics.add_rows_dropped(deduped);
+            tracing::debug!(
+                exchange = %batch.key.exchange,
+                stream = %batch.key.stream,
+                symbol = %batch.key.symbol,
+                deduped,
+                "dropped repeated rows before insert"
+            );
+        }
+
+        // Last data-quality gate: absurd scaled values never reach the table
+        let rejected = batch.reject_out_of_range(&self.writer.value_ranges);
+        if let Some(row) = rejected.first() {
+            self.metrics.add_rows_rejected(rejected.len() as u64);
+            if let Some(suppressed) = log_throttle().allow("db rows out of range") {
+                let (column, value, range) = self
+                    .writer
+                    .value_ranges
+                    .iter()
+                    .find_map(|(c, r)| {
+                        row.scaled_value(c)
+                            .filter(|v| !r.contains(*v))
+                            .map(|v| (c.as_str(), v, *r))
+                    })
+                    .unwrap_or_default();
+                tracing::warn!(
+                    exchange = %batch.key.exchange,
+                    stream = %batch.key.stream,
+                    symbol = %batch.key.symbol,
+                    rejected = rejected.len(),
+                    column,
+                    value,
+                    min = ?range.min,
+                    max = ?range.max,
+                    suppressed,
+                    "rows with out-of-range values rejected before insert"
+                );
+            }
+        }
+        let rejected = rejected.len() as u64;
+        if batch.rows.is_empty() {
+            batch.reset_timer();
+            return Ok(WriteOutcome {
+                rejected,
+                ..WriteOutcome::not_flushed()
+            });
         }
 
         // --- Backpressure: wait for a permit (queue wait time)
@@ -81,7 +637,7 @@ impl DbHandler {
 
         // --- Flush delay (how long it waited since enqueue)
         self.metrics
-            .observe_flush_delay(batch.enqueued_at.elapsed().as_secs_f64());
+            .observe_flush_delay(self.flush_delay(batch, Instant::now()).as_secs_f64());
 
         // --- Route shard + get pool
         let shard_id = self
@@ -89,21 +645,16 @@ impl DbHandler {
             .shard_id_for(&batch.key.exchange, &batch.key.stream, &batch.key.symbol)
             .await?;
 
-        let pool = self.pools.pool_by_id(&shard_id).await?;
-
-        // --- Pool max (from shard config) for health gauge
-        let pool_max = self
-            .pools
-            .shards_snapshot()
-            .await?
-            .iter()
-            .find(|s| s.id == shard_id)
-            .map(|s| s.pool_max as i64)
-            .unwrap_or(0);
+        // Pool max (from shard config) for the health gauge
+        let (pool, shard) = self.pools.routed_shard(&shard_id).await?;
+        let pool_max = shard.pool_max as i64;
 
         // --- Pool wait (explicit acquire to measure wait time)
         let acquire_t0 = Instant::now();
-        let mut conn = pool.acquire().await.map_err(AppError::Sqlx)?;
+        let mut conn = match pool.acquire().await {
+            Ok(conn) => conn,
+            Err(e) => return Err(self.pools.report_err(&shard_id, &pool, e)),
+        };
         self.metrics
             .observe_pool_wait(acquire_t0.elapsed().as_secs_f64());
 
@@ -113,49 +664,132 @@ impl DbHandler {
         let in_use = (size - idle).max(0);
         self.metrics.set_pool_health(in_use, idle, pool_max);
 
-        // --- Build & execute INSERT batches (chunked by batch_size)
+        // --- Build & execute COPY / INSERT batches (chunked by chunk_rows)
         let write_t0 = Instant::now();
 
+        // A row type whose binds drifted from its columns fails here in tests / debug builds
+        debug_assert_eq!(check_row_shape(&batch.rows[0]), Ok(()));
+
         // Table name is dynamic (depends on exchange). Compute once.
         let table_name = batch.rows[0].table(&batch.key.exchange);
 
-        let mut total_written: u64 = 0;
-
-        for chunk in batch.rows.chunks(batch.chunk_rows) {
-            let mut qb: QueryBuilder<Postgres> = QueryBuilder::new("INSERT INTO ");
-            qb.push("\"");
-            qb.push(&table_name.replace('.', "\".\""));
-            qb.push("\"");
-
-            qb.push(" (");
-
-            for (i, col) in T::COLUMNS.iter().enumerate() {
-                if i > 0 {
-                    qb.push(", ");
+        // A table missing the conflict target's unique index takes a plain INSERT
+        let mut on_conflict =
+            on_conflict_sql::<T>().filter(|_| self.has_conflict_index(&shard_id, &table_name));
+
+        // Tracked rows of an idempotent table: read back which ones the conflict did not skip
+        let mut returning =
+            T::DEDUP_COLUMN.filter(|_| on_conflict.is_some() && batch.track_persisted);
+
+        // COPY cannot skip conflicting rows: idempotent row types always INSERT
+        let mut res = if self.writer.use_copy && T::CONFLICT_TARGET.is_none() {
+            match copy_rows(&mut conn, &table_name, &batch.rows, batch.chunk_rows).await {
+                Ok(rows) => Ok(Inserted { rows, keys: None }),
+                // COPY is all-or-nothing: nothing landed, the batch can go through INSERT as is
+                Err(e) if is_copy_specific(&e) => {
+                    self.metrics.inc_copy_fallback();
+                    if let Some(suppressed) = log_throttle().allow("db copy fallback") {
+                        tracing::warn!(
+                            table = %table_name,
+                            rows = batch.rows.len(),
+                            error = %e,
+                            suppressed,
+                            "COPY failed; writing the batch with INSERT"
+                        );
+                    }
+                    insert_rows(
+                        &mut conn,
+                        &table_name,
+                        &batch.rows,
+                        batch.chunk_rows,
+                        on_conflict.as_deref(),
+                        returning,
+                    )
+                    .await
                 }
-                qb.push("\"");
-                qb.push(*col);
-                qb.push("\"");
+                Err(e) => Err(e),
             }
-            qb.push(") ");
+        } else {
+            insert_rows(
+                &mut conn,
+                &table_name,
+                &batch.rows,
+                batch.chunk_rows,
+                on_conflict.as_deref(),
+                returning,
+            )
+            .await
+        };
+
+        // Deployment without the conflict index (every such insert fails with 42P10, forever):
+        // remember the table and write it with a plain INSERT from now on
+        if on_conflict.is_some()
+            && let Err(e) = &res
+            && is_missing_conflict_index(e)
+        {
+            tracing::warn!(
+                shard = %shard_id,
+                table = %table_name,
+                error = %e,
+                "unique index of the conflict target is missing: writing with plain INSERT \
+                 (replays may duplicate rows); run migrations/0001_trades_conflict_index.sql"
+            );
+            self.mark_no_conflict_index(&shard_id, &table_name);
+            on_conflict = None;
+            returning = None;
+            res = insert_rows(
+                &mut conn,
+                &table_name,
+                &batch.rows,
+                batch.chunk_rows,
+                None,
+                None,
+            )
+            .await;
+        }
 
-            qb.push_values(chunk.iter(), |mut b, row| {
-                row.push_binds(&mut b);
-            });
+        // Opt-in auto-DDL: create the missing schema/table, then retry the insert once
+        if self.writer.auto_create_tables
+            && let Err(e) = &res
+            && is_missing_relation(e)
+        {
+            match create_missing_table::<T>(&mut conn, &table_name).await {
+                Ok(()) => {
+                    res = insert_rows(
+                        &mut conn,
+                        &table_name,
+                        &batch.rows,
+                        batch.chunk_rows,
+                        on_conflict.as_deref(),
+                        returning,
+                    )
+                    .await;
+                }
+                Err(ddl_err) => tracing::error!(
+                    table = %table_name,
+                    error = %ddl_err,
+                    "auto_create_tables: creating the missing table failed"
+                ),
+            }
+        }
 
-            // Execute without capturing `permit` in a closure
-            let res = qb.build().execute(&mut *conn).await;
-            if let Err(e) = res {
+        let inserted = match res {
+            Ok(inserted) => {
+                self.pools.on_shard_success(&shard_id);
+                inserted
+            }
+            Err(e) => {
+                self.pools.on_shard_error(&shard_id, &pool, &e);
                 self.metrics.inc_failed_batch();
+                batch.mark_write_failed();
                 drop(permit); // release before returning
-                return Err(AppError::Sqlx(e));
+                return Err(classify_insert_error(e, &table_name));
             }
-
-            total_written += chunk.len() as u64;
-        }
+        };
 
         // release permit (drop) after successful writes
         drop(permit);
+        let total_written = inserted.rows;
 
         // Success metrics
         self.metrics
@@ -165,27 +799,72 @@ impl DbHandler {
         self.metrics.observe_rows_per_batch(total_written as f64);
 
         // Clear batch after successful write and reset timer
-        batch.rows.clear();
-        batch.enqueued_at = Instant::now();
+        let outcome = WriteOutcome {
+            deduped,
+            rejected,
+            rows_written: total_written,
+            ..WriteOutcome::flushed(&batch.rows)
+        };
+        match &inserted.keys {
+            Some(keys) => batch.clear_flushed_inserted(keys),
+            None => batch.clear_flushed(),
+        }
+        self.ledger
+            .record(&shard_id, &batch.key, total_written, outcome.watermark);
+
+        if let Some(hook) = self.commit_hook.as_ref() {
+            hook(&CommitInfo {
+                shard_id,
+                table: table_name,
+                rows: total_written,
+                max_event_time: outcome.watermark,
+            });
+        }
 
-        Ok(())
+        Ok(outcome)
+    }
+
+    /// Wait from `batch.enqueued_at` to `now`, clamped to `writer.max_flush_delay_ms`.
+    /// A clamped value (host suspend, paused VM) is counted instead of poisoning the histogram.
+    fn flush_delay<T>(&self, batch: &Batch<T>, now: Instant) -> Duration {
+        let max = Duration::from_millis(self.writer.max_flush_delay_ms);
+        let (delay, clamped) = batch.flush_delay(now, max);
+        if clamped {
+            self.metrics.inc_flush_delay_anomaly();
+            if let Some(suppressed) = log_throttle().allow("flush delay anomaly") {
+                tracing::warn!(
+                    exchange = %batch.key.exchange,
+                    stream = %batch.key.stream,
+                    symbol = %batch.key.symbol,
+                    elapsed_ms = now.saturating_duration_since(batch.enqueued_at).as_millis() as u64,
+                    max_flush_delay_ms = self.writer.max_flush_delay_ms,
+                    suppressed,
+                    "implausible flush delay (host suspended?); recorded at the cap"
+                );
+            }
+        }
+        delay
     }
 
     /// Simple retry helper (linear backoff).
     ///
     /// Note: No `T: Clone` needed now because we don't consume the batch.
     /// We also only clear rows on success inside `write_batch()`.
+    /// `Shutdown` and `MissingTable` are returned immediately (retrying cannot help).
     pub async fn write_batch_with_retry<T: BatchInsertRow>(
         &self,
         batch: &mut Batch<T>,
         retries: usize,
         backoff: Duration,
-    ) -> AppResult<()> {
+    ) -> AppResult<WriteOutcome> {
         let mut attempt = 0usize;
 
         loop {
             match self.write_batch(batch).await {
-                Ok(()) => return Ok(()),
+                Ok(outcome) => return Ok(outcome),
+                Err(AppError::Shutdown) => return Err(AppError::Shutdown),
+                // Retrying cannot create the table
+                Err(e @ AppError::MissingTable { .. }) => return Err(e),
                 Err(e) if attempt < retries => {
                     self.metrics.inc_retried_batch();
                     attempt += 1;
@@ -213,67 +892,93 @@ impl DbHandler {
         knobs: &StreamKnobs,
         enabled: bool,
     ) -> AppResult<()> {
-        // Build the canonical stream_id string (matches your StreamId::new)
-        let stream_id = StreamId::new(spec.exchange, &spec.instrument, spec.kind, spec.transport);
-
-        // Note: kind/transport are stored as TEXT in your table.
-        // If you already have as_str()/Display impls, use those.
-        let kind = spec.kind.to_string(); // e.g. "Trades"
-        let transport = spec.transport.to_string(); // e.g. "Ws" or your lowercase mapping
-        let exchange = spec.exchange;
-        let exchange_id = ExchangeId::from_str(exchange)?;
-        let instrument = &spec.instrument.clone();
-
-        let batch_key = make_batch_key(
-            exchange_id,
-            spec.transport.clone(),
-            spec.kind.clone(),
-            &spec.instrument,
-        )?;
-
-        // --- Route shard + get pool
-        let shard_id = self
-            .pools
-            .shard_id_for(&batch_key.exchange, &batch_key.stream, &batch_key.symbol)
-            .await?;
+        self.upsert_stream_registry_many(&[RegistryUpsert {
+            spec: spec.clone(),
+            knobs: *knobs,
+            enabled,
+        }])
+        .await
+        .map(|_| ())
+    }
 
-        let pool = self.pools.pool_by_id(&shard_id).await?;
-        let mut conn = pool.acquire().await.map_err(AppError::Sqlx)?;
+    /// `upsert_stream_registry` for many streams: one multi-row INSERT ... ON CONFLICT per
+    /// shard (in chunks of `REGISTRY_UPSERT_CHUNK` rows) instead of a round trip per stream.
+    /// A stream listed twice keeps its last entry. Returns the number of rows upserted.
+    pub async fn upsert_stream_registry_many(&self, entries: &[RegistryUpsert]) -> AppResult<u64> {
+        // --- Route each entry to its shard (last entry per stream_id wins: Postgres refuses
+        // to update the same row twice in one statement)
+        let mut by_shard: BTreeMap<String, BTreeMap<String, &RegistryUpsert>> = BTreeMap::new();
+        for entry in entries {
+            let spec = &entry.spec;
+            let stream_id =
+                StreamId::new(spec.exchange, &spec.instrument, spec.kind, spec.transport);
+            let batch_key = make_batch_key(
+                ExchangeId::from_str(spec.exchange)?,
+                spec.transport,
+                spec.kind,
+                &spec.instrument,
+            )?;
+            let shard_id = self
+                .pools
+                .shard_id_for(&batch_key.exchange, &batch_key.stream, &batch_key.symbol)
+                .await?;
+            by_shard
+                .entry(shard_id)
+                .or_default()
+                .insert(stream_id.0, entry);
+        }
 
-        let mut qb: QueryBuilder<Postgres> = QueryBuilder::new(
-            r#"
+        let mut total: u64 = 0;
+        for (shard_id, rows) in by_shard {
+            let pool = self.pools.pool_by_id(&shard_id).await?;
+            let mut conn = pool
+                .acquire()
+                .await
+                .map_err(|e| self.pools.report_err(&shard_id, &pool, e))?;
+
+            let rows: Vec<(String, &RegistryUpsert)> = rows.into_iter().collect();
+            for chunk in rows.chunks(REGISTRY_UPSERT_CHUNK) {
+                let mut qb: QueryBuilder<Postgres> = QueryBuilder::new(
+                    r#"
             INSERT INTO mini_fintickstreams.stream_registry (
               stream_id, exchange, instrument, kind, transport, enabled,
               disable_db_writes, disable_redis_publishes,
               flush_rows, flush_interval_ms, chunk_rows, hard_cap_rows,
+              emit_bbo, emit_microprice,
               created_at, updated_at
             )
             "#,
-        );
-
-        qb.push_values(std::iter::once(()), |mut b, _| {
-            b.push_bind(stream_id.0.as_str());
-            b.push_bind(exchange);
-            b.push_bind(instrument);
-            b.push_bind(kind.as_str());
-            b.push_bind(transport.as_str());
-            b.push_bind(enabled);
-
-            b.push_bind(knobs.disable_db_writes);
-            b.push_bind(knobs.disable_redis_publishes);
-
-            b.push_bind(knobs.flush_rows as i32);
-            b.push_bind(knobs.flush_interval_ms as i64);
-            b.push_bind(knobs.chunk_rows as i32);
-            b.push_bind(knobs.hard_cap_rows as i32);
-
-            // timestamps
-            b.push("now()");
-            b.push("now()");
-        });
+                );
+
+                qb.push_values(chunk.iter(), |mut b, (stream_id, entry)| {
+                    let spec = &entry.spec;
+                    let knobs = &entry.knobs;
+
+                    b.push_bind(stream_id.clone());
+                    b.push_bind(spec.exchange);
+                    b.push_bind(spec.instrument.clone());
+                    // Note: kind/transport are stored as TEXT (e.g. "Trades", "Ws").
+                    b.push_bind(spec.kind.to_string());
+                    b.push_bind(spec.transport.to_string());
+                    b.push_bind(entry.enabled);
+
+                    b.push_bind(knobs.disable_db_writes);
+                    b.push_bind(knobs.disable_redis_publishes);
+
+                    b.push_bind(knobs.flush_rows as i32);
+                    b.push_bind(knobs.flush_interval_ms as i64);
+                    b.push_bind(knobs.chunk_rows as i32);
+                    b.push_bind(knobs.hard_cap_rows as i32);
+                    b.push_bind(knobs.emit_bbo);
+                    b.push_bind(knobs.emit_microprice);
+
+                    // timestamps
+                    b.push("now()");
+                    b.push("now()");
+                });
 
-        qb.push(
-            r#"
+                qb.push(
+                    r#"
             ON CONFLICT (stream_id) DO UPDATE SET
               exchange = EXCLUDED.exchange,
               instrument = EXCLUDED.instrument,
@@ -287,17 +992,21 @@ impl DbHandler {
               flush_interval_ms = EXCLUDED.flush_interval_ms,
               chunk_rows = EXCLUDED.chunk_rows,
               hard_cap_rows = EXCLUDED.hard_cap_rows,
+              emit_bbo = EXCLUDED.emit_bbo,
+              emit_microprice = EXCLUDED.emit_microprice,
 
               updated_at = now()
             "#,
-        );
+                );
 
-        qb.build()
-            .execute(&mut *conn)
-            .await
-            .map_err(AppError::Sqlx)?;
+                let res =
+                    self.pools
+                        .report(&shard_id, &pool, qb.build().execute(&mut *conn).await)?;
+                total += res.rows_affected();
+            }
+        }
 
-        Ok(())
+        Ok(total)
     }
 
     pub async fn update_stream_knobs(
@@ -319,7 +1028,10 @@ impl DbHandler {
             .shard_id_for(&batch_key.exchange, &batch_key.stream, &batch_key.symbol)
             .await?;
         let pool = self.pools.pool_by_id(&shard_id).await?;
-        let mut conn = pool.acquire().await.map_err(AppError::Sqlx)?;
+        let mut conn = pool
+            .acquire()
+            .await
+            .map_err(|e| self.pools.report_err(&shard_id, &pool, e))?;
 
         let stream_id = StreamId::new(spec.exchange, &spec.instrument, spec.kind, spec.transport);
 
@@ -346,14 +1058,56 @@ impl DbHandler {
         qb.push(", hard_cap_rows = ");
         qb.push_bind(knobs.hard_cap_rows as i32);
 
+        qb.push(", emit_bbo = ");
+        qb.push_bind(knobs.emit_bbo);
+
+        qb.push(", emit_microprice = ");
+        qb.push_bind(knobs.emit_microprice);
+
         qb.push(", updated_at = now() WHERE stream_id = ");
         qb.push_bind(stream_id.0.as_str());
 
-        let res = qb
-            .build()
-            .execute(&mut *conn)
+        let res = self
+            .pools
+            .report(&shard_id, &pool, qb.build().execute(&mut *conn).await)?;
+        if res.rows_affected() == 0 {
+            return Err(AppError::StreamNotFound(stream_id.0));
+        }
+
+        Ok(())
+    }
+
+    /// Flip the `enabled` flag of a registry row (knobs untouched).
+    /// Disabled rows are skipped by `load_enabled_streams_from_registry`.
+    pub async fn set_stream_registry_enabled(
+        &self,
+        spec: &StreamSpec,
+        enabled: bool,
+    ) -> AppResult<()> {
+        let exchange_id = ExchangeId::from_str(spec.exchange)?;
+        let batch_key = make_batch_key(exchange_id, spec.transport, spec.kind, &spec.instrument)?;
+
+        let shard_id = self
+            .pools
+            .shard_id_for(&batch_key.exchange, &batch_key.stream, &batch_key.symbol)
+            .await?;
+        let pool = self.pools.pool_by_id(&shard_id).await?;
+        let mut conn = pool
+            .acquire()
             .await
-            .map_err(AppError::Sqlx)?;
+            .map_err(|e| self.pools.report_err(&shard_id, &pool, e))?;
+
+        let stream_id = StreamId::new(spec.exchange, &spec.instrument, spec.kind, spec.transport);
+
+        let mut qb: QueryBuilder<Postgres> =
+            QueryBuilder::new("UPDATE mini_fintickstreams.stream_registry SET enabled = ");
+        qb.push_bind(enabled);
+        qb.push(", updated_at = now() WHERE stream_id = ");
+        qb.push_bind(stream_id.0.as_str());
+
+        let res = self
+            .pools
+            .report(&shard_id, &pool, qb.build().execute(&mut *conn).await)?;
         if res.rows_affected() == 0 {
             return Err(AppError::StreamNotFound(stream_id.0));
         }
@@ -378,17 +1132,18 @@ impl DbHandler {
             .await?;
 
         let pool = self.pools.pool_by_id(&shard_id).await?;
-        let mut conn = pool.acquire().await.map_err(AppError::Sqlx)?;
+        let mut conn = pool
+            .acquire()
+            .await
+            .map_err(|e| self.pools.report_err(&shard_id, &pool, e))?;
 
         let mut qb: QueryBuilder<Postgres> =
             QueryBuilder::new("DELETE FROM mini_fintickstreams.stream_registry WHERE stream_id = ");
         qb.push_bind(stream_id.0.as_str());
 
-        let res = qb
-            .build()
-            .execute(&mut *conn)
-            .await
-            .map_err(AppError::Sqlx)?;
+        let res = self
+            .pools
+            .report(&shard_id, &pool, qb.build().execute(&mut *conn).await)?;
         if res.rows_affected() == 0 {
             return Err(AppError::StreamNotFound(stream_id.0));
         }
@@ -406,9 +1161,13 @@ impl DbHandler {
         let mut seen: HashSet<String> = HashSet::new();
         let mut out: Vec<StartStreamParams> = Vec::new();
 
-        for shard in shards {
-            let pool = self.pools.pool_by_id(&shard.id).await?;
-            let mut conn = pool.acquire().await.map_err(AppError::Sqlx)?;
+        for snap in shards {
+            let shard_id = &snap.shard.id;
+            let pool = self.pools.pool_by_id(shard_id).await?;
+            let mut conn = pool
+                .acquire()
+                .await
+                .map_err(|e| self.pools.report_err(shard_id, &pool, e))?;
 
             // Pull only what we need for restart
             let rows = sqlx::query(
@@ -419,8 +1178,8 @@ impl DbHandler {
                 "#,
             )
             .fetch_all(&mut *conn)
-            .await
-            .map_err(AppError::Sqlx)?;
+            .await;
+            let rows = self.pools.report(shard_id, &pool, rows)?;
 
             for r in rows {
                 let stream_id: String = r.try_get("stream_id").map_err(AppError::Sqlx)?;
@@ -460,3 +1219,244 @@ impl DbHandler {
         Ok(out)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::BatchKey;
+    use crate::db::config::TimescaleDbConfig;
+    use crate::db::rows::OpenInterestDBRow;
+    use chrono::TimeZone;
+
+    fn oi(sec: u32) -> OpenInterestDBRow {
+        OpenInterestDBRow {
+            time: Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, sec).unwrap(),
+            symbol: "BTCUSDT".into(),
+            oi_i: 1,
+        }
+    }
+
+    #[test]
+    fn write_outcome_not_flushed_has_no_watermark() {
+        let out = WriteOutcome::not_flushed();
+        assert!(!out.flushed);
+        assert_eq!(out.rows_written, 0);
+        assert_eq!(out.watermark, None);
+    }
+
+    #[test]
+    fn write_outcome_flushed_uses_max_event_time() {
+        let rows = vec![oi(3), oi(9), oi(1)];
+        let out = WriteOutcome::flushed(&rows);
+        assert!(out.flushed);
+        assert_eq!(out.rows_written, 3);
+        assert_eq!(out.watermark, Some(oi(9).time));
+    }
+
+    #[test]
+    fn missing_relation_codes_are_classified() {
+        assert!(is_missing_relation_code("42P01"));
+        assert!(is_missing_relation_code("3F000"));
+        // unique_violation / connection failure stay generic (retryable)
+        assert!(!is_missing_relation_code("23505"));
+        assert!(!is_missing_relation_code("08006"));
+    }
+
+    #[test]
+    fn only_copy_specific_errors_fall_back_to_insert() {
+        assert!(is_copy_specific(&sqlx::Error::Protocol(
+            "COPY not supported".into()
+        )));
+        // Saturation / connection loss would fail the INSERT as well
+        assert!(!is_copy_specific(&sqlx::Error::PoolTimedOut));
+        assert!(!is_copy_specific(&sqlx::Error::Io(std::io::Error::other(
+            "reset"
+        ))));
+        assert!(!is_copy_specific(&sqlx::Error::RowNotFound));
+    }
+
+    async fn handler_without_shards(writer: WriterConfig) -> DbHandler {
+        // Real config shape, no shards: nothing connects
+        let raw = std::fs::read_to_string(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/src/config/timescale_db.toml"
+        ))
+        .unwrap();
+        let mut cfg: TimescaleDbConfig = toml::from_str(&raw).unwrap();
+        cfg.shards.clear();
+        cfg.writer = writer.clone();
+
+        let pools = DbPools::new(cfg, false).await.unwrap();
+        DbHandler::new(Arc::new(pools), writer, Arc::new(DbMetrics::new().unwrap()))
+    }
+
+    #[tokio::test]
+    async fn flush_delay_after_suspend_is_clamped_and_counted() {
+        let writer = WriterConfig {
+            max_flush_delay_ms: 5_000,
+            ..WriterConfig::default()
+        };
+        let handler = handler_without_shards(writer.clone()).await;
+
+        let key = BatchKey {
+            exchange: "binance_linear".into(),
+            transport: StreamTransport::HttpPoll,
+            kind: StreamKind::OpenInterest,
+            stream: "open_interest".into(),
+            symbol: "BTCUSDT".into(),
+            derived: None,
+        };
+        let mut batch = Batch::new(key, vec![oi(1)], &writer);
+
+        // Fresh batch: observed as is
+        let enqueued = batch.enqueued_at;
+        assert!(handler.flush_delay(&batch, Instant::now()) < Duration::from_secs(5));
+        assert_eq!(handler.metrics.flush_delay_anomalies(), 0);
+
+        // Simulate a 10 min suspend between enqueue and flush
+        let after_suspend = enqueued + Duration::from_secs(600);
+        assert_eq!(
+            handler.flush_delay(&batch, after_suspend),
+            Duration::from_secs(5)
+        );
+        #[cfg(feature = "metrics")]
+        assert_eq!(handler.metrics.flush_delay_anomalies(), 1);
+
+        // 0 = uncapped
+        let (raw, clamped) = batch.flush_delay(after_suspend, Duration::ZERO);
+        assert!(!clamped && raw == Duration::from_secs(600));
+
+        // Emptied, then refilled much later: the wait starts at the refill
+        batch.take_rows();
+        let emptied = batch.enqueued_at;
+        std::thread::sleep(Duration::from_millis(5));
+        batch.extend(vec![oi(2)]);
+        assert!(batch.enqueued_at >= emptied + Duration::from_millis(5));
+    }
+
+    #[tokio::test]
+    async fn force_flush_bypasses_the_flush_gate() {
+        let writer = WriterConfig {
+            batch_size: 1000,
+            flush_interval_ms: 60_000,
+            ..WriterConfig::default()
+        };
+        let handler = handler_without_shards(writer.clone()).await;
+        let key = BatchKey {
+            exchange: "binance_linear".into(),
+            transport: StreamTransport::HttpPoll,
+            kind: StreamKind::OpenInterest,
+            stream: "open_interest".into(),
+            symbol: "BTCUSDT".into(),
+            derived: None,
+        };
+
+        // Empty: no-op
+        let mut batch: Batch<OpenInterestDBRow> = Batch::new(key, vec![], &writer);
+        let out = handler.force_flush(&mut batch).await.unwrap();
+        assert!(!out.flushed);
+
+        // Below batch_size, interval not elapsed: write_batch keeps the rows untouched
+        batch.extend(vec![oi(1), oi(2)]);
+        let out = handler.write_batch(&mut batch).await.unwrap();
+        assert!(!out.flushed);
+        assert_eq!(batch.len(), 2);
+
+        // force_flush goes on to write them (here: no shard to route to), keeping them on failure
+        assert!(handler.force_flush(&mut batch).await.is_err());
+        assert_eq!(batch.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn preview_batch_sql_renders_one_insert_per_chunk() {
+        use crate::db::rows::TradeDBRow;
+
+        let writer = WriterConfig::default();
+        let handler = handler_without_shards(writer.clone()).await;
+        let key = BatchKey {
+            exchange: "binance_linear".into(),
+            transport: StreamTransport::Ws,
+            kind: StreamKind::Trades,
+            stream: "trades".into(),
+            symbol: "BTCUSDT".into(),
+            derived: None,
+        };
+        let trade = |id: i64| TradeDBRow {
+            time: oi(1).time,
+            symbol: "BTCUSDT".into(),
+            side: 0,
+            price_i: 42_000_000,
+            qty_i: 1_000,
+            trade_id: Some(id),
+            is_maker: None,
+        };
+
+        let mut batch = Batch::new(key, vec![], &writer);
+        assert!(handler.preview_batch_sql(&batch).unwrap().is_empty());
+
+        batch.extend((0..5).map(trade).collect());
+        batch.chunk_rows = 2;
+        let sql = handler.preview_batch_sql(&batch).unwrap();
+        assert_eq!(sql.len(), 3);
+        assert_eq!(
+            sql[0],
+            "INSERT INTO \"ex_binance_linear\".\"trades\" \
+             (\"time\", \"symbol\", \"side\", \"price_i\", \"qty_i\", \"trade_id\", \"is_maker\") \
+             VALUES ($1, $2, $3, $4, $5, $6, $7), ($8, $9, $10, $11, $12, $13, $14) \
+             ON CONFLICT (\"time\", \"symbol\", \"trade_id\") DO NOTHING"
+        );
+        assert!(sql[2].contains("VALUES ($1, $2, $3, $4, $5, $6, $7) ON CONFLICT"));
+
+        // Nothing was written or counted
+        assert_eq!(batch.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn close_unblocks_waiter_on_saturated_semaphore() {
+        let writer = WriterConfig {
+            batch_size: 1,
+            max_inflight_batches: 1,
+            ..WriterConfig::default()
+        };
+        let handler = handler_without_shards(writer.clone()).await;
+
+        // Saturate: the only permit is held by an "in-progress" write
+        let held = Arc::clone(&handler.inflight).acquire_owned().await.unwrap();
+
+        let key = BatchKey {
+            exchange: "binance_linear".into(),
+            transport: StreamTransport::HttpPoll,
+            kind: StreamKind::OpenInterest,
+            stream: "open_interest".into(),
+            symbol: "BTCUSDT".into(),
+            derived: None,
+        };
+        let waiter = {
+            let handler = handler.clone();
+            let mut batch = Batch::new(key.clone(), vec![oi(1)], &writer);
+            tokio::spawn(async move { handler.write_batch(&mut batch).await })
+        };
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiter.is_finished(), "waiter blocks on the permit");
+
+        handler.close();
+        assert!(handler.is_closed());
+
+        let res = tokio::time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .expect("waiter released promptly")
+            .unwrap();
+        assert!(matches!(res, Err(AppError::Shutdown)), "{res:?}");
+
+        // Later flushes fail fast too, and are not retried
+        let mut batch = Batch::new(key, vec![oi(2)], &writer);
+        let res = handler
+            .write_batch_with_retry(&mut batch, 3, Duration::from_secs(10))
+            .await;
+        assert!(matches!(res, Err(AppError::Shutdown)));
+        assert_eq!(batch.rows.len(), 1, "rows are kept on shutdown");
+
+        drop(held);
+    }
+}
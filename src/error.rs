@@ -41,6 +41,14 @@ pub enum AppError {
         body: String,
     },
 
+    /// Remote API response body exceeded the configured size limit (download aborted)
+    #[error("Response too large from {service}: limit={limit} bytes, received>={received} bytes")]
+    ResponseTooLarge {
+        service: String,
+        limit: usize,
+        received: usize,
+    },
+
     // =========
     // Serialization / deserialization
     // =========
@@ -53,6 +61,10 @@ pub enum AppError {
     #[error("Database error: {0}")]
     Sqlx(#[from] sqlx::Error),
 
+    /// Target schema/table does not exist (migration not applied). Never retried.
+    #[error("Missing table {table}: run the schema migration")]
+    MissingTable { table: String },
+
     // =========
     // Metrics / Prometheus
     // =========
@@ -111,6 +123,15 @@ pub enum AppError {
     #[error("Disabled error: {0}")]
     Disabled(String),
 
+    /// A subsystem failed to come up (see `app::startup`); `up` lists what was already up.
+    #[error("Startup failed at `{subsystem}` (up: {up:?}): {source}")]
+    Startup {
+        subsystem: &'static str,
+        up: Vec<&'static str>,
+        #[source]
+        source: Box<AppError>,
+    },
+
     /// Config IO error with explicit context (path + operation)
     #[error("Configuration file IO error during {operation}: path={path}, error={source}")]
     ConfigIoCtx {
@@ -215,6 +236,12 @@ mod axum_impl {
                 (code, "upstream_api_error", format!("{}: {}", service, body))
             }
 
+            AppError::ResponseTooLarge { .. } => (
+                StatusCode::BAD_GATEWAY,
+                "upstream_response_too_large",
+                e.to_string(),
+            ),
+
             // Typical infra-ish errors: treat as 502/503
             AppError::Reqwest(_) | AppError::WebSocket(_) | AppError::Redis(_) => {
                 (StatusCode::BAD_GATEWAY, "upstream_transport", e.to_string())
@@ -222,6 +249,11 @@ mod axum_impl {
 
             // DB errors usually mean dependency down or query failed
             AppError::Sqlx(_) => (StatusCode::SERVICE_UNAVAILABLE, "db_error", e.to_string()),
+            AppError::MissingTable { .. } => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "missing_table",
+                e.to_string(),
+            ),
 
             // Everything else
             _ => (StatusCode::INTERNAL_SERVER_ERROR, "internal", e.to_string()),
@@ -1,9 +1,33 @@
 use crate::app::config::AppConfig;
 use crate::error::{AppError, AppResult};
+use crate::ingest::datamap::ctx::QtyPrecision;
+use crate::ingest::datamap::frame::WsArrayFrames;
+use crate::ingest::datamap::naming::symbol_token;
+use crate::ingest::datamap::trade_variant::{TradeVariant, check_trade_variants};
+use crate::ingest::datamap::venue_time::VenueTimeField;
+use crate::ingest::spec::template::render_string;
+use crate::ingest::spec::types::{BodyShape, RequestTiming, ctx_from_pairs};
+use crate::ingest::ws::proxy::WsProxy;
+use crate::ingest::ws::subscribe_limiter::WsLimiterOrder;
+use http::header::{HeaderMap, HeaderName, HeaderValue};
 use serde::Deserialize;
 use std::collections::BTreeMap;
 use std::fs;
 use std::{io::ErrorKind, path::Path};
+/// Build an HTTP header map (REST client defaults / WS handshake) from name/value pairs.
+pub fn header_map(headers: &[(String, String)]) -> AppResult<HeaderMap> {
+    let mut map = HeaderMap::with_capacity(headers.len());
+    for (name, value) in headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|e| AppError::InvalidConfig(format!("invalid header name `{name}`: {e}")))?;
+        let value = HeaderValue::from_str(value).map_err(|e| {
+            AppError::InvalidConfig(format!("invalid value for header `{name}`: {e}"))
+        })?;
+        map.insert(name, value);
+    }
+    Ok(map)
+}
+
 // -----------------------------
 // Root config
 // -----------------------------
@@ -20,10 +44,31 @@ pub struct ExchangeConfig {
     pub max_weight: Option<u64>,
     pub window: u64,
     pub api_weight_header_key: String,
+    // Max REST response body size (bytes); larger downloads are aborted
+    #[serde(default)]
+    pub api_max_response_bytes: Option<usize>,
 
     // WebSocket
     pub ws_base_url: String,
     pub ws_connection_timeout_seconds: u64,
+    // Reconnect when no frame (data, ping or pong) arrives for this long; 0 = off
+    #[serde(default)]
+    pub ws_read_idle_timeout_seconds: u64,
+    // Binary frames are compressed JSON: "gzip" or "deflate" (raw DEFLATE); decoded to text
+    #[serde(default)]
+    pub ws_compression: Option<String>,
+    // Tunnel WS connections through this proxy: http:// (CONNECT) or socks5://; unset = direct
+    #[serde(default)]
+    pub ws_proxy_url: Option<String>,
+    // Env var holding the proxy's `user:password` (never inline in the config)
+    #[serde(default)]
+    pub ws_proxy_auth_env: Option<String>,
+    // Append every received WS frame (JSON lines) to this file, for debugging/replay; unset = off
+    #[serde(default)]
+    pub ws_record_frames_path: Option<String>,
+    // Rotate the frame recording (to `<path>.1`) past this size
+    #[serde(default = "default_ws_record_frames_max_bytes")]
+    pub ws_record_frames_max_bytes: u64,
     pub ws_max_streams_per_connection: u64,
 
     pub ws_heartbeat_type: Option<String>,
@@ -32,16 +77,93 @@ pub struct ExchangeConfig {
 
     // Sometimes "" (string), sometimes { method="ping" } (table)
     pub ws_heartbeat_frame: Option<StringOrTable>,
+    // "json" heartbeats only: the venue answers each with a pong (`pong`, {"op":"pong"}, ...);
+    // none for two heartbeat intervals drops the connection
+    #[serde(default)]
+    pub ws_heartbeat_expect_pong: bool,
 
     pub ws_reconnect_attempts_limit: u64,
     pub ws_reconnect_attempts_reset_seconds: u64,
 
+    // Per-exchange reconnect backoff (defaults: streams.ws_reconnect_backoff_initial_ms / _max_ms)
+    #[serde(default)]
+    pub ws_reconnect_backoff_ms: Option<u64>,
+    #[serde(default)]
+    pub ws_reconnect_backoff_max_ms: Option<u64>,
+
     pub ws_subscribe_attempt_limit: u64,
     pub ws_subscribe_attempts_reset_seconds: u64,
+    // How a (re)connect takes its reconnect and subscribe permits (see WsLimiterOrder)
+    #[serde(default)]
+    pub ws_limiter_order: WsLimiterOrder,
 
-    // Message templates differ a lot between exchanges
+    // Message templates differ a lot between exchanges. An array of tables is a multi-step
+    // handshake: one frame per entry, sent in order
     pub ws_subscribe_msg: Option<TableValue>,
     pub ws_unsubscribe_msg: Option<TableValue>,
+    // Pause between the frames of a multi-step subscribe (a step that expects an ack,
+    // see ws_subscribe_ack_match, waits for it instead)
+    #[serde(default = "default_ws_subscribe_step_delay_ms")]
+    pub ws_subscribe_step_delay_ms: u64,
+    // Template strings that are exactly one placeholder ("<stream_id>") render as JSON
+    // numbers/bools when the value parses as one
+    #[serde(default)]
+    pub ws_coerce_scalars: bool,
+
+    // Multiplexed subscribe limits (split into several messages when exceeded)
+    #[serde(default)]
+    pub ws_max_subscribe_bytes: Option<usize>,
+    #[serde(default)]
+    pub ws_max_symbols_per_subscribe: Option<usize>,
+    // Multiplexed subscribes list symbols in lexicographic order (same set => same messages)
+    #[serde(default = "default_ws_sort_subscribe")]
+    pub ws_sort_subscribe: bool,
+    // Frames that are a top-level JSON array: split into messages or drop
+    #[serde(default)]
+    pub ws_array_frames: WsArrayFrames,
+    // Wait this long for every subscribe to be acknowledged; a missing (or rejecting) ack is a
+    // failed subscribe and the connection is retried. 0 = don't wait
+    #[serde(default)]
+    pub ws_subscribe_ack_timeout_seconds: u64,
+    // Ack of a subscribe: ack JSON pointer -> subscribe message pointer, values must be equal
+    #[serde(default)]
+    pub ws_subscribe_ack_match: BTreeMap<String, String>,
+    // A matching ack that has this pointer rejects the subscribe
+    #[serde(default)]
+    pub ws_subscribe_ack_error_pointer: Option<String>,
+    // Remaining subscribe budget the venue reports in an ack (number or numeric string); the
+    // subscribe limiter's current window is cut down to it
+    #[serde(default)]
+    pub ws_subscribe_ack_remaining_pointer: Option<String>,
+
+    // Symbol casing
+    // - symbol_case: canonical casing for stream ids, registry lookups, row symbols, Redis keys
+    // - ws_symbol_case: casing used when rendering WS subscribe templates (defaults to symbol_case)
+    #[serde(default)]
+    pub symbol_case: SymbolCase,
+    #[serde(default)]
+    pub ws_symbol_case: Option<SymbolCase>,
+
+    // Funding plausibility bound: |rate| above this percent is dropped + counted (None = off)
+    #[serde(default)]
+    pub max_abs_funding_rate_pct: Option<f64>,
+
+    // Sizes with more decimals than the instrument declares: "flag" (count + warn) or
+    // "round" to the declared decimals before scaling (Hyperliquid szDecimals)
+    #[serde(default)]
+    pub qty_precision: QtyPrecision,
+
+    // Default headers (e.g. User-Agent) sent on every REST request and the WS handshake.
+    // Values are templates: <version> (crate version), <exchange>, <app_id>, <env> (app.toml).
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    // `headers` rendered by `ExchangeConfigs::new` (not read from TOML)
+    #[serde(skip)]
+    pub resolved_headers: Vec<(String, String)>,
+
+    // Timestamp/recv-window params for `timestamped` endpoints (defaults: timestamp, recvWindow)
+    #[serde(default)]
+    pub request_timing: Option<RequestTiming>,
 
     // Dynamic keyed tables: [api.*] and [ws.*]
     #[serde(default)]
@@ -50,6 +172,18 @@ pub struct ExchangeConfig {
     pub ws: BTreeMap<String, WsStream>,
 }
 
+fn default_ws_record_frames_max_bytes() -> u64 {
+    64 * 1024 * 1024
+}
+
+fn default_ws_sort_subscribe() -> bool {
+    true
+}
+
+fn default_ws_subscribe_step_delay_ms() -> u64 {
+    50
+}
+
 // -----------------------------
 // API endpoint table entries
 // -----------------------------
@@ -61,6 +195,25 @@ pub struct ApiEndpoint {
     pub params: Option<TableValue>,
     pub interval_seconds: u64,
     pub method: String,
+    /// Per-endpoint override of `api_max_response_bytes`.
+    #[serde(default)]
+    pub max_response_bytes: Option<usize>,
+    /// Where the venue event time lives in the response body (JSON pointer + unit).
+    #[serde(default)]
+    pub time_field: Option<VenueTimeField>,
+    /// Expected shape of the rendered JSON body, checked at resolve time (JSON-body venues).
+    #[serde(default)]
+    pub body_shape: Option<BodyShape>,
+    /// Time-sensitive endpoint: a fresh timestamp (+ recv window) is added on every send.
+    #[serde(default)]
+    pub timestamped: bool,
+    /// JSON body: strings that are exactly one placeholder (`"<limit>"`) render as numbers
+    /// or bools when the value parses as one.
+    #[serde(default)]
+    pub coerce_scalars: bool,
+    /// Exchange `request_timing` for timestamped endpoints (filled at load, not read from TOML).
+    #[serde(skip)]
+    pub timing: Option<RequestTiming>,
 }
 
 // -----------------------------
@@ -73,6 +226,24 @@ pub struct WsStream {
     pub stream_title: Option<String>,
     pub coin: Option<String>,
     pub subscription_type: Option<String>,
+    /// Where the venue event time lives in each message (JSON pointer + unit).
+    #[serde(default)]
+    pub time_field: Option<VenueTimeField>,
+    /// Trade streams: raw fills or aggregated trades (inferred from the subscription if unset).
+    #[serde(default)]
+    pub trade_variant: Option<TradeVariant>,
+}
+
+impl WsStream {
+    /// Trade variant carried by this stream (None for non-trade streams).
+    pub fn trade_variant(&self) -> Option<TradeVariant> {
+        self.trade_variant.or_else(|| {
+            self.stream_title
+                .as_deref()
+                .or(self.subscription_type.as_deref())
+                .and_then(TradeVariant::infer)
+        })
+    }
 }
 
 // -----------------------------
@@ -90,6 +261,256 @@ pub enum StringOrTable {
     Table(TableValue),
 }
 
+/// Per-exchange symbol casing policy.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SymbolCase {
+    Upper,
+    Lower,
+    #[default]
+    Preserve,
+}
+
+impl SymbolCase {
+    pub fn apply(self, symbol: &str) -> String {
+        match self {
+            SymbolCase::Upper => symbol.to_uppercase(),
+            SymbolCase::Lower => symbol.to_lowercase(),
+            SymbolCase::Preserve => symbol.to_string(),
+        }
+    }
+}
+
+impl ExchangeConfig {
+    /// Canonical casing of `symbol` for this exchange.
+    pub fn canonical_symbol(&self, symbol: &str) -> String {
+        symbol_token(self.symbol_case, symbol)
+    }
+
+    /// Validate declarative fields that serde cannot check on its own.
+    pub fn validate(&self) -> AppResult<()> {
+        let fields = self
+            .api
+            .iter()
+            .map(|(k, e)| ("api", k, &e.time_field))
+            .chain(self.ws.iter().map(|(k, s)| ("ws", k, &s.time_field)));
+
+        for (section, key, field) in fields {
+            if let Some(f) = field
+                && let Err(e) = f.validate()
+            {
+                return Err(AppError::InvalidConfig(format!(
+                    "\n❌ INVALID time_field\n\
+                     ├─ exchange: `{}`\n\
+                     ├─ endpoint: `[{}.{}]`\n\
+                     └─ error: {}\n",
+                    self.exchange, section, key, e
+                )));
+            }
+        }
+
+        if let Some(pct) = self.max_abs_funding_rate_pct
+            && !(pct.is_finite() && pct > 0.0)
+        {
+            return Err(AppError::InvalidConfig(format!(
+                "max_abs_funding_rate_pct must be > 0 (exchange `{}`, got {pct})",
+                self.exchange
+            )));
+        }
+
+        if self.ws_reconnect_backoff_ms == Some(0)
+            || matches!(
+                (self.ws_reconnect_backoff_ms, self.ws_reconnect_backoff_max_ms),
+                (Some(base), Some(max)) if base > max
+            )
+        {
+            return Err(AppError::InvalidConfig(format!(
+                "ws_reconnect_backoff_ms must be > 0 and <= ws_reconnect_backoff_max_ms (exchange `{}`)",
+                self.exchange
+            )));
+        }
+
+        if let Some(url) = &self.ws_proxy_url {
+            WsProxy::parse(url)?;
+        } else if self.ws_proxy_auth_env.is_some() {
+            return Err(AppError::InvalidConfig(format!(
+                "ws_proxy_auth_env needs ws_proxy_url (exchange `{}`)",
+                self.exchange
+            )));
+        }
+
+        if self.ws_record_frames_path.is_some() && self.ws_record_frames_max_bytes == 0 {
+            return Err(AppError::InvalidConfig(format!(
+                "ws_record_frames_max_bytes must be > 0 (exchange `{}`)",
+                self.exchange
+            )));
+        }
+
+        let json_heartbeat = self
+            .ws_heartbeat_type
+            .as_deref()
+            .is_some_and(|t| t.eq_ignore_ascii_case("json"));
+        if (json_heartbeat && self.ws_heartbeat_frame.is_none())
+            || (self.ws_heartbeat_expect_pong && !json_heartbeat)
+        {
+            return Err(AppError::InvalidConfig(format!(
+                "ws_heartbeat_type = \"json\" needs ws_heartbeat_frame, and ws_heartbeat_expect_pong needs ws_heartbeat_type = \"json\" (exchange `{}`)",
+                self.exchange
+            )));
+        }
+
+        for (name, tpl) in [
+            ("ws_subscribe_msg", &self.ws_subscribe_msg),
+            ("ws_unsubscribe_msg", &self.ws_unsubscribe_msg),
+        ] {
+            if tpl
+                .as_ref()
+                .and_then(TableValue::as_array)
+                .is_some_and(Vec::is_empty)
+            {
+                return Err(AppError::InvalidConfig(format!(
+                    "{name} has no steps (exchange `{}`)",
+                    self.exchange
+                )));
+            }
+        }
+
+        if self.ws_subscribe_ack_timeout_seconds > 0 && self.ws_subscribe_ack_match.is_empty() {
+            return Err(AppError::InvalidConfig(format!(
+                "ws_subscribe_ack_timeout_seconds needs ws_subscribe_ack_match (exchange `{}`)",
+                self.exchange
+            )));
+        }
+        if let Some(p) = self
+            .ws_subscribe_ack_match
+            .iter()
+            .flat_map(|(ack, sub)| [ack, sub])
+            .chain(&self.ws_subscribe_ack_error_pointer)
+            .chain(&self.ws_subscribe_ack_remaining_pointer)
+            .find(|p| !p.starts_with('/'))
+        {
+            return Err(AppError::InvalidConfig(format!(
+                "ws_subscribe_ack pointers must start with `/` (exchange `{}`, got {p:?})",
+                self.exchange
+            )));
+        }
+        // A subscribe none of whose steps has a matched pointer would never be awaited
+        if self.ws_subscribe_ack_timeout_seconds > 0
+            && let Some(tpl) = &self.ws_subscribe_msg
+        {
+            let tpl = serde_json::to_value(tpl).map_err(|e| {
+                AppError::InvalidConfig(format!(
+                    "ws_subscribe_msg is not JSON (exchange `{}`): {e}",
+                    self.exchange
+                ))
+            })?;
+            let steps = match &tpl {
+                serde_json::Value::Array(steps) => steps.as_slice(),
+                single => std::slice::from_ref(single),
+            };
+            let expects_ack = |step: &serde_json::Value| {
+                self.ws_subscribe_ack_match
+                    .values()
+                    .any(|p| step.pointer(p).is_some())
+            };
+            if !steps.iter().any(expects_ack) {
+                return Err(AppError::InvalidConfig(format!(
+                    "no ws_subscribe_ack_match subscribe pointer is in ws_subscribe_msg, so no ack would be awaited (exchange `{}`)",
+                    self.exchange
+                )));
+            }
+        }
+
+        if let Some(c) = self.ws_compression.as_deref()
+            && !matches!(c, "gzip" | "deflate")
+        {
+            return Err(AppError::InvalidConfig(format!(
+                "ws_compression must be \"gzip\" or \"deflate\" (exchange `{}`, got {c:?})",
+                self.exchange
+            )));
+        }
+
+        if let Some(t) = &self.request_timing
+            && (t.recv_window_ms == Some(0)
+                || t.timestamp_param.trim().is_empty()
+                || t.recv_window_param.trim().is_empty())
+        {
+            return Err(AppError::InvalidConfig(format!(
+                "request_timing: params must be non-empty and recv_window_ms > 0 (exchange `{}`)",
+                self.exchange
+            )));
+        }
+
+        check_trade_variants(
+            &self.exchange,
+            self.ws
+                .iter()
+                .filter_map(|(k, s)| s.trade_variant().map(|v| (k.as_str(), v))),
+        )
+    }
+
+    /// Hand the exchange `request_timing` (or the defaults) to every `timestamped` endpoint.
+    pub fn resolve_request_timing(&mut self) {
+        let timing = self.request_timing.clone().unwrap_or_default();
+        for ep in self.api.values_mut() {
+            ep.timing = ep.timestamped.then(|| timing.clone());
+        }
+    }
+
+    /// Check the reconnect backoff this exchange ends up with: an override of one bound must
+    /// also fit the app-wide value it inherits for the other (`streams.ws_reconnect_backoff_*`).
+    pub fn validate_reconnect_backoff(&self, app_cfg: &AppConfig) -> AppResult<()> {
+        let initial = self
+            .ws_reconnect_backoff_ms
+            .unwrap_or(app_cfg.streams.ws_reconnect_backoff_initial_ms);
+        let max = self
+            .ws_reconnect_backoff_max_ms
+            .unwrap_or(app_cfg.streams.ws_reconnect_backoff_max_ms);
+        if initial == 0 || initial > max {
+            return Err(AppError::InvalidConfig(format!(
+                "exchange `{}` reconnect backoff {initial}..{max} ms (overrides merged with \
+                 streams.ws_reconnect_backoff_initial_ms / _max_ms): initial must be > 0 and <= max",
+                self.exchange
+            )));
+        }
+        Ok(())
+    }
+
+    /// Render `headers` (see field docs) into `resolved_headers`; invalid names/values fail here.
+    pub fn resolve_headers(&mut self, app_cfg: Option<&AppConfig>) -> AppResult<()> {
+        let mut ctx = ctx_from_pairs([
+            ("version", env!("CARGO_PKG_VERSION")),
+            ("exchange", self.exchange.as_str()),
+        ]);
+        if let Some(app) = app_cfg {
+            ctx.insert("app_id".into(), app.id.clone());
+            ctx.insert("env".into(), app.env.clone());
+        }
+
+        let mut resolved = Vec::with_capacity(self.headers.len());
+        for (name, value) in &self.headers {
+            let value = render_string(value, &ctx).map_err(|e| {
+                AppError::InvalidConfig(format!(
+                    "exchange `{}`: header `{name}`: {e}",
+                    self.exchange
+                ))
+            })?;
+            resolved.push((name.clone(), value));
+        }
+        header_map(&resolved)?;
+        self.resolved_headers = resolved;
+        Ok(())
+    }
+
+    /// Casing of `symbol` as rendered into subscribe templates for `transport`.
+    pub fn template_symbol(&self, transport: StreamTransport, symbol: &str) -> String {
+        match (transport, self.ws_symbol_case) {
+            (StreamTransport::Ws, Some(case)) => case.apply(symbol),
+            _ => self.canonical_symbol(symbol),
+        }
+    }
+}
+
 // -----------------------------
 // Loader
 // -----------------------------
@@ -227,7 +648,9 @@ pub fn load_exchange_config(name: &str, from_env: bool, version: u32) -> AppResu
         _ => AppError::ConfigIo(e),
     })?;
 
-    let cfg = toml::from_str::<ExchangeConfig>(&toml_str).map_err(AppError::ConfigToml)?;
+    let mut cfg = toml::from_str::<ExchangeConfig>(&toml_str).map_err(AppError::ConfigToml)?;
+    cfg.validate()?;
+    cfg.resolve_request_timing();
     Ok(cfg)
 }
 
@@ -245,20 +668,26 @@ impl ExchangeConfigs {
         };
 
         if app_cfg.exchange_toggles.binance_linear {
-            exchanges.binance_linear =
-                Some(load_exchange_config("binance_linear", from_env, version)?);
+            let mut cfg =
+                load_exchange_config(ExchangeId::BinanceLinear.as_str(), from_env, version)?;
+            cfg.resolve_headers(Some(app_cfg))?;
+            cfg.validate_reconnect_backoff(app_cfg)?;
+            exchanges.binance_linear = Some(cfg);
         }
 
         if app_cfg.exchange_toggles.hyperliquid_perp {
-            exchanges.hyperliquid_perp =
-                Some(load_exchange_config("hyperliquid_perp", from_env, version)?);
+            let mut cfg =
+                load_exchange_config(ExchangeId::HyperliquidPerp.as_str(), from_env, version)?;
+            cfg.resolve_headers(Some(app_cfg))?;
+            cfg.validate_reconnect_backoff(app_cfg)?;
+            exchanges.hyperliquid_perp = Some(cfg);
         }
 
         Ok(exchanges)
     }
 }
 
-use crate::app::stream_types::ExchangeId;
+use crate::app::stream_types::{ExchangeId, StreamTransport};
 
 impl ExchangeConfigs {
     pub fn get(&self, exchange: ExchangeId) -> Option<&ExchangeConfig> {
@@ -274,11 +703,210 @@ impl ExchangeConfigs {
             ExchangeId::HyperliquidPerp => self.hyperliquid_perp.as_mut(),
         }
     }
+
+    /// Casing policy for `exchange` (`preserve` if the exchange is not loaded).
+    pub fn symbol_case(&self, exchange: ExchangeId) -> SymbolCase {
+        self.get(exchange)
+            .map(|c| c.symbol_case)
+            .unwrap_or_default()
+    }
+
+    /// Funding plausibility bound (percent) for `exchange`, if configured.
+    pub fn max_abs_funding_rate_pct(&self, exchange: ExchangeId) -> Option<f64> {
+        self.get(exchange).and_then(|c| c.max_abs_funding_rate_pct)
+    }
+
+    /// Over-precise size handling for `exchange` (default: flag only).
+    pub fn qty_precision(&self, exchange: ExchangeId) -> QtyPrecision {
+        self.get(exchange)
+            .map(|c| c.qty_precision)
+            .unwrap_or_default()
+    }
+
+    /// Canonical casing of `symbol` for `exchange`.
+    pub fn canonical_symbol(&self, exchange: ExchangeId, symbol: &str) -> String {
+        symbol_token(self.symbol_case(exchange), symbol)
+    }
+
+    /// (exchange key, casing policy) for every loaded exchange.
+    pub fn symbol_cases(&self) -> Vec<(&'static str, SymbolCase)> {
+        ExchangeId::ALL
+            .iter()
+            .filter_map(|&ex| self.get(ex).map(|c| (ex.as_str(), c.symbol_case)))
+            .collect()
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::load_exchange_config;
+    use super::{SymbolCase, load_exchange_config};
+    use crate::app::stream_types::StreamTransport;
+    use crate::ingest::datamap::trade_variant::TradeVariant;
+    use crate::ingest::datamap::venue_time::{TimeUnit, VenueTimeField};
+
+    #[test]
+    fn symbol_case_policies() {
+        assert_eq!(SymbolCase::Upper.apply("kPepe"), "KPEPE");
+        assert_eq!(SymbolCase::Lower.apply("kPepe"), "kpepe");
+        assert_eq!(SymbolCase::Preserve.apply("kPepe"), "kPepe");
+        assert_eq!(SymbolCase::default(), SymbolCase::Preserve);
+
+        #[derive(serde::Deserialize)]
+        struct Wrap {
+            c: SymbolCase,
+        }
+        for (raw, want) in [
+            ("upper", SymbolCase::Upper),
+            ("lower", SymbolCase::Lower),
+            ("preserve", SymbolCase::Preserve),
+        ] {
+            let w: Wrap = toml::from_str(&format!("c = \"{raw}\"")).unwrap();
+            assert_eq!(w.c, want);
+        }
+        assert!(toml::from_str::<Wrap>("c = \"title\"").is_err());
+    }
+
+    #[test]
+    fn exchange_symbol_case_from_config() {
+        let binance = load_exchange_config("binance_linear", false, 0).unwrap();
+        assert_eq!(binance.symbol_case, SymbolCase::Upper);
+        assert_eq!(binance.canonical_symbol("btcUSDT"), "BTCUSDT");
+        assert_eq!(
+            binance.template_symbol(StreamTransport::Ws, "BTCUSDT"),
+            "btcusdt"
+        );
+        assert_eq!(
+            binance.template_symbol(StreamTransport::HttpPoll, "btcusdt"),
+            "BTCUSDT"
+        );
+
+        let hyper = load_exchange_config("hyperliquid_perp", false, 0).unwrap();
+        assert_eq!(hyper.symbol_case, SymbolCase::Preserve);
+        assert_eq!(hyper.canonical_symbol("kPEPE"), "kPEPE");
+        assert_eq!(hyper.template_symbol(StreamTransport::Ws, "kPEPE"), "kPEPE");
+    }
+
+    #[test]
+    fn venue_time_fields_from_config() {
+        let binance = load_exchange_config("binance_linear", false, 0).unwrap();
+        assert_eq!(
+            binance.ws["trades"].time_field,
+            Some(VenueTimeField::new("/T", TimeUnit::Ms))
+        );
+        assert_eq!(
+            binance.api["funding_rate"].time_field,
+            Some(VenueTimeField::new("/0/fundingTime", TimeUnit::Ms))
+        );
+
+        let hyper = load_exchange_config("hyperliquid_perp", false, 0).unwrap();
+        assert_eq!(
+            hyper.ws["depth_update"].time_field,
+            Some(VenueTimeField::new("/data/time", TimeUnit::Ms))
+        );
+        // Per-fill times: no message-level field on trades
+        assert!(hyper.ws["trades"].time_field.is_none());
+        assert!(hyper.ws["oi_funding"].time_field.is_none());
+
+        // Configured pointers resolve against recorded venue payloads
+        for (field, file, ms) in [
+            (
+                &binance.ws["trades"].time_field,
+                "BinanceLinearWsAggTrade.json",
+                1765807986967,
+            ),
+            (
+                &binance.api["depth"].time_field,
+                "BinanceLinearDepthSnapshot.json",
+                1765807699442,
+            ),
+            (
+                &hyper.ws["depth_update"].time_field,
+                "HyperliquidPerpWsDepthUpdate.json",
+                1765807375203,
+            ),
+        ] {
+            let path = format!(
+                "{}/src/ingest/datamap/testdata/{file}",
+                env!("CARGO_MANIFEST_DIR")
+            );
+            let v: serde_json::Value =
+                serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+            let t = field.as_ref().unwrap().extract(&v).unwrap();
+            assert_eq!(t.timestamp_millis(), ms, "{file}");
+        }
+
+        // A malformed pointer is rejected at load/validate time
+        let mut bad = binance.clone();
+        bad.ws.get_mut("trades").unwrap().time_field = Some(VenueTimeField::new("T", TimeUnit::Ms));
+        let err = bad.validate().unwrap_err().to_string();
+        assert!(err.contains("[ws.trades]"), "{err}");
+    }
+
+    #[test]
+    fn subscribe_ack_match_must_resolve_in_the_subscribe_msg() {
+        let mut binance = load_exchange_config("binance_linear", false, 0).unwrap();
+        binance.ws_subscribe_ack_timeout_seconds = 10;
+        binance.ws_subscribe_ack_match = [("/id".to_string(), "/id".to_string())].into();
+        binance.validate().unwrap();
+
+        // Typo in the subscribe pointer: every subscribe would go unchecked
+        binance.ws_subscribe_ack_match = [("/id".to_string(), "/ID".to_string())].into();
+        let err = binance.validate().unwrap_err().to_string();
+        assert!(err.contains("ws_subscribe_ack_match"), "{err}");
+
+        // Multi-step: steps without it (auth) are fine as long as one has it
+        binance.ws_subscribe_msg = Some(
+            toml::from_str::<super::TableValue>(
+                r#"steps = [{ method = "AUTH" }, { method = "SUBSCRIBE", ID = 1 }]"#,
+            )
+            .unwrap()["steps"]
+                .clone(),
+        );
+        binance.validate().unwrap();
+    }
+
+    #[test]
+    fn expected_pongs_need_json_heartbeats() {
+        let mut hyperliquid = load_exchange_config("hyperliquid_perp", false, 0).unwrap();
+        assert!(hyperliquid.ws_heartbeat_expect_pong);
+        hyperliquid.validate().unwrap();
+
+        // Protocol pings are always timed against their pong; the flag would be a no-op
+        hyperliquid.ws_heartbeat_type = Some("ping".into());
+        let err = hyperliquid.validate().unwrap_err().to_string();
+        assert!(err.contains("ws_heartbeat_expect_pong"), "{err}");
+    }
+
+    #[test]
+    fn default_headers_are_rendered_and_validated() {
+        let app = crate::app::config::load_app_config(false, 0).unwrap();
+        let mut binance = load_exchange_config("binance_linear", false, 0).unwrap();
+        binance.resolve_headers(Some(&app)).unwrap();
+
+        let ua = binance
+            .resolved_headers
+            .iter()
+            .find(|(k, _)| k == "User-Agent")
+            .map(|(_, v)| v.clone())
+            .expect("User-Agent configured");
+        assert_eq!(
+            ua,
+            format!(
+                "mini-fintickstreams/{} ({}; {})",
+                env!("CARGO_PKG_VERSION"),
+                binance.exchange,
+                app.env
+            )
+        );
+        assert!(super::header_map(&binance.resolved_headers).is_ok());
+
+        // Unknown placeholder / illegal header name fail at load time
+        binance.headers.insert("X-Bad".into(), "<nope>".into());
+        assert!(binance.resolve_headers(Some(&app)).is_err());
+        binance.headers.remove("X-Bad");
+        binance.headers.insert("Bad Name".into(), "x".into());
+        assert!(binance.resolve_headers(Some(&app)).is_err());
+    }
 
     #[test]
     fn print_exchange_configs() {
@@ -304,4 +932,60 @@ mod tests {
             hyper.ws.get("depth_update")
         );
     }
+
+    #[test]
+    fn raw_and_aggregated_trades_together_are_rejected() {
+        let mut binance = load_exchange_config("binance_linear", false, 0).unwrap();
+        assert_eq!(
+            binance.ws["trades"].trade_variant(),
+            Some(TradeVariant::Aggregated)
+        );
+        assert!(binance.ws["depth_update"].trade_variant().is_none());
+        let hyper = load_exchange_config("hyperliquid_perp", false, 0).unwrap();
+        assert_eq!(hyper.ws["trades"].trade_variant(), Some(TradeVariant::Raw));
+
+        // Accidentally enabling raw trades next to aggTrade
+        let mut raw = binance.ws["trades"].clone();
+        raw.stream_title = Some("<symbol>@trade".into());
+        raw.trade_variant = None;
+        binance.ws.insert("raw_trades".into(), raw.clone());
+
+        let err = binance.validate().unwrap_err().to_string();
+        assert!(err.contains("DUPLICATE TRADE STREAMS"), "{err}");
+        assert!(
+            err.contains("raw_trades") && err.contains("\"trades\""),
+            "{err}"
+        );
+
+        // An explicit variant overrides the inferred one
+        raw.trade_variant = Some(TradeVariant::Aggregated);
+        binance.ws.insert("raw_trades".into(), raw);
+        assert!(binance.validate().is_ok());
+    }
+
+    #[test]
+    fn backoff_override_is_checked_against_the_inherited_bound() {
+        let app = crate::app::config::load_app_config(false, 0).unwrap();
+        let app_initial = app.streams.ws_reconnect_backoff_initial_ms;
+        let app_max = app.streams.ws_reconnect_backoff_max_ms;
+        let mut cfg = load_exchange_config("binance_linear", false, 0).unwrap();
+        assert!(cfg.validate_reconnect_backoff(&app).is_ok());
+
+        // Each override alone passes `validate`, but not against the app-wide other half
+        cfg.ws_reconnect_backoff_ms = Some(app_max + 1);
+        assert!(cfg.validate().is_ok());
+        let err = cfg
+            .validate_reconnect_backoff(&app)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("reconnect backoff"), "{err}");
+
+        cfg.ws_reconnect_backoff_ms = None;
+        cfg.ws_reconnect_backoff_max_ms = Some(app_initial - 1);
+        assert!(cfg.validate().is_ok());
+        assert!(cfg.validate_reconnect_backoff(&app).is_err());
+
+        cfg.ws_reconnect_backoff_ms = Some(app_initial - 1);
+        assert!(cfg.validate_reconnect_backoff(&app).is_ok());
+    }
 }
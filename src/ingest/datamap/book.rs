@@ -0,0 +1,868 @@
+//! ingest/datamap/book.rs
+//!
+//! Local order-book assembler + BBO (best bid / best ask) emission.
+//!
+//! `BookState` keeps the price levels of ONE symbol (scaled integers) and derives the top of
+//! book after every update. A `BboRow` is emitted whenever the top level (price OR size of the
+//! best bid / best ask) changes.
+//!
+//! - Delta streams (Binance `depthUpdate`) feed `apply_deltas`; `size_i == 0` deletes a level.
+//! - Full-book streams (Hyperliquid `l2Book`, REST snapshots) feed `apply_snapshot`.
+//! - Debounce is driven by row event time: at most one BBO row per
+//!   `writer.bbo_min_interval_ms`. A change inside the interval is not lost, it is emitted
+//!   (as the top at that moment) by the first update past the interval, or — when no update
+//!   follows — by `flush_due` (stream timer) / `flush_held` (stream stop).
+//! - `min_interval_ms == 0` emits on every top-of-book change.
+//! - `microprice_row` derives the microprice of an emitted BBO row, so it shares the same
+//!   debounce (one microprice row per BBO row with both sides present).
+//!
+//! `BookCache` holds the books of all depth streams, at most `streams.max_books` of them.
+//! Each stream owns its `BookSlot` (its own lock, so depth streams never contend); the cache
+//! only keeps the use order. Over the cap the least-recently-updated book is evicted; its
+//! stream finds it gone on the next update and re-seeds it: from the full-book message, or
+//! (Binance) from a REST snapshot fetched off the read loop while the deltas received
+//! meanwhile are buffered and replayed past the snapshot's `lastUpdateId`.
+
+use crate::app::config::StreamsConfig;
+use crate::db::config::WriterConfig;
+use crate::db::rows::{BboRow, MicropriceRow};
+use crate::ingest::datamap::event::{BookSide, DepthDeltaRow};
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+/// Best level of each side as (price_i, size_i).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TopOfBook {
+    pub bid: Option<(i64, i64)>,
+    pub ask: Option<(i64, i64)>,
+}
+
+impl TopOfBook {
+    /// Microprice `(bid_px*ask_sz + ask_px*bid_sz) / (bid_sz + ask_sz)`, in the price scale,
+    /// rounded to nearest. Computed in i128 (the products overflow i64 at realistic scales).
+    /// None when a side is empty, a size is not positive, or the result does not fit i64.
+    pub fn microprice_i(&self) -> Option<i64> {
+        let ((bid_px, bid_sz), (ask_px, ask_sz)) = (self.bid?, self.ask?);
+        if bid_sz <= 0 || ask_sz <= 0 {
+            return None;
+        }
+        let num = bid_px as i128 * ask_sz as i128 + ask_px as i128 * bid_sz as i128;
+        let den = bid_sz as i128 + ask_sz as i128;
+        let (q, r) = (num.div_euclid(den), num.rem_euclid(den));
+        let rounded = if 2 * r >= den { q + 1 } else { q };
+        i64::try_from(rounded).ok()
+    }
+}
+
+/// Microprice row of an emitted BBO row (None when a side of the book is empty).
+pub fn microprice_row(bbo: &BboRow) -> Option<MicropriceRow> {
+    let top = TopOfBook {
+        bid: bbo.bid_px_i.zip(bbo.bid_sz_i),
+        ask: bbo.ask_px_i.zip(bbo.ask_sz_i),
+    };
+    Some(MicropriceRow {
+        time: bbo.time,
+        symbol: bbo.symbol.clone(),
+        microprice_i: top.microprice_i()?,
+    })
+}
+
+#[derive(Debug)]
+pub struct BookState {
+    symbol: String,
+    bids: BTreeMap<i64, i64>,
+    asks: BTreeMap<i64, i64>,
+    min_interval: Duration,
+    last_emitted: TopOfBook,
+    last_emit_time: Option<DateTime<Utc>>,
+    // time of the latest update the debounce held back
+    held: Option<DateTime<Utc>>,
+    // sequence id of the last seed (Binance `lastUpdateId`): older deltas are skipped
+    seeded_seq: Option<i64>,
+}
+
+impl BookState {
+    pub fn new(symbol: impl Into<String>, min_interval_ms: u64) -> Self {
+        Self {
+            symbol: symbol.into(),
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+            min_interval: Duration::milliseconds(min_interval_ms as i64),
+            last_emitted: TopOfBook::default(),
+            last_emit_time: None,
+            held: None,
+            seeded_seq: None,
+        }
+    }
+
+    pub fn from_config(symbol: impl Into<String>, writer: &WriterConfig) -> Self {
+        Self::new(symbol, writer.bbo_min_interval_ms)
+    }
+
+    /// Current best bid (highest price) and best ask (lowest price).
+    pub fn top(&self) -> TopOfBook {
+        TopOfBook {
+            bid: self.bids.iter().next_back().map(|(p, s)| (*p, *s)),
+            ask: self.asks.iter().next().map(|(p, s)| (*p, *s)),
+        }
+    }
+
+    /// Number of (bid, ask) levels currently held.
+    #[inline]
+    pub fn depth(&self) -> (usize, usize) {
+        (self.bids.len(), self.asks.len())
+    }
+
+    /// Apply incremental level updates; returns a BBO row if the top changed.
+    /// Rows older than the seed (`seq` below its `seq`) are already in it and skipped.
+    pub fn apply_deltas(&mut self, rows: &[DepthDeltaRow]) -> Option<BboRow> {
+        let time = self.catch_up(rows)?;
+        self.maybe_emit(time)
+    }
+
+    /// Apply incremental level updates WITHOUT emitting (replaying the deltas buffered during a
+    /// re-seed); returns the time of the latest applied row.
+    pub fn catch_up(&mut self, rows: &[DepthDeltaRow]) -> Option<DateTime<Utc>> {
+        let seeded = self.seeded_seq;
+        let mut time = None;
+        for r in rows {
+            if let (Some(seeded), Some(seq)) = (seeded, r.seq)
+                && seq < seeded
+            {
+                continue;
+            }
+            self.set_level(r.side, r.price_i, r.size_i);
+            time = time.max(Some(r.time));
+        }
+        time
+    }
+
+    /// Replace the whole book with a full snapshot; returns a BBO row if the top changed.
+    pub fn apply_snapshot(&mut self, rows: &[DepthDeltaRow]) -> Option<BboRow> {
+        let time = rows.iter().map(|r| r.time).max()?;
+        self.seed(rows);
+        self.maybe_emit(time)
+    }
+
+    /// Replace the whole book WITHOUT emitting (e.g. REST snapshot before the WS diffs).
+    /// The first update afterwards emits the top. The snapshot's `seq` (Binance `lastUpdateId`)
+    /// is kept: deltas with a lower final update id are already in it.
+    pub fn seed(&mut self, rows: &[DepthDeltaRow]) {
+        self.seeded_seq = rows.iter().filter_map(|r| r.seq).max();
+        self.bids.clear();
+        self.asks.clear();
+        for r in rows {
+            self.set_level(r.side, r.price_i, r.size_i);
+        }
+    }
+
+    fn set_level(&mut self, side: BookSide, price_i: i64, size_i: i64) {
+        let levels = match side {
+            BookSide::Bid => &mut self.bids,
+            BookSide::Ask => &mut self.asks,
+        };
+        if size_i == 0 {
+            levels.remove(&price_i);
+        } else {
+            levels.insert(price_i, size_i);
+        }
+    }
+
+    /// Trailing edge of the debounce: the top held back inside the interval, once `now` is
+    /// past the interval (stream timer). The row keeps the time of the held update.
+    pub fn flush_due(&mut self, now: DateTime<Utc>) -> Option<BboRow> {
+        if let Some(last) = self.last_emit_time
+            && now - last < self.min_interval
+        {
+            return None;
+        }
+        self.flush_held()
+    }
+
+    /// The top held back by the debounce, regardless of the interval (stream stop).
+    pub fn flush_held(&mut self) -> Option<BboRow> {
+        let time = self.held.take()?;
+        let top = self.top();
+        (top != self.last_emitted).then(|| self.emit(top, time))
+    }
+
+    fn maybe_emit(&mut self, time: DateTime<Utc>) -> Option<BboRow> {
+        let top = self.top();
+        if top == self.last_emitted {
+            self.held = None;
+            return None;
+        }
+        if let Some(last) = self.last_emit_time
+            && time - last < self.min_interval
+        {
+            self.held = Some(time);
+            return None;
+        }
+        Some(self.emit(top, time))
+    }
+
+    fn emit(&mut self, top: TopOfBook, time: DateTime<Utc>) -> BboRow {
+        self.last_emitted = top;
+        self.last_emit_time = Some(time);
+        self.held = None;
+        BboRow {
+            time,
+            symbol: self.symbol.clone(),
+            bid_px_i: top.bid.map(|(p, _)| p),
+            bid_sz_i: top.bid.map(|(_, s)| s),
+            ask_px_i: top.ask.map(|(p, _)| p),
+            ask_sz_i: top.ask.map(|(_, s)| s),
+        }
+    }
+}
+
+/// Delta batches buffered per book while its re-seed is in flight. Past it the buffer is
+/// dropped and restarted, so the snapshot in flight no longer connects and is fetched again.
+pub const RESEED_BUFFER_MAX: usize = 4096;
+
+/// A delta batch buffered during a re-seed, with the first update id it covers (Binance `U`).
+#[derive(Debug)]
+struct BufferedDeltas {
+    first_seq: Option<i64>,
+    rows: Vec<DepthDeltaRow>,
+}
+
+#[derive(Debug, Default)]
+struct SlotState {
+    book: Option<BookState>,
+    // Some while a re-seed is in flight: the delta batches received meanwhile
+    reseed: Option<VecDeque<BufferedDeltas>>,
+}
+
+/// The book of ONE stream. Only that stream (and its timers) locks it; the cache only touches
+/// it to seed or evict.
+#[derive(Debug)]
+pub struct BookSlot {
+    key: String,
+    last_used: AtomicU64,
+    held: Arc<AtomicUsize>,
+    state: Mutex<SlotState>,
+}
+
+impl BookSlot {
+    fn lock(&self) -> std::sync::MutexGuard<'_, SlotState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    #[inline]
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// True while the book is held (seeded and not evicted).
+    pub fn is_held(&self) -> bool {
+        self.lock().book.is_some()
+    }
+}
+
+impl Drop for BookSlot {
+    fn drop(&mut self) {
+        let st = self.state.get_mut().unwrap_or_else(|e| e.into_inner());
+        if st.book.is_some() {
+            self.held.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Outcome of `BookCache::apply_deltas`.
+#[derive(Debug)]
+pub enum BookUpdate {
+    /// Applied to the held book (BBO row if the top changed).
+    Applied(Option<BboRow>),
+    /// The book is not held: the caller starts a re-seed (the deltas are buffered).
+    Reseed,
+    /// A re-seed is already in flight: the deltas are buffered for it.
+    Buffered,
+}
+
+/// Bounded set of per-stream books (see module docs). The registry lock is only taken to
+/// register, seed and evict, never per update.
+#[derive(Debug)]
+pub struct BookCache {
+    /// 0 = unbounded
+    max_books: usize,
+    tick: AtomicU64,
+    held: Arc<AtomicUsize>,
+    slots: Mutex<HashMap<String, Weak<BookSlot>>>,
+    evicted: AtomicU64,
+}
+
+impl BookCache {
+    pub fn new(max_books: usize) -> Self {
+        Self {
+            max_books,
+            tick: AtomicU64::new(0),
+            held: Arc::new(AtomicUsize::new(0)),
+            slots: Mutex::new(HashMap::new()),
+            evicted: AtomicU64::new(0),
+        }
+    }
+
+    pub fn from_config(cfg: &StreamsConfig) -> Self {
+        Self::new(cfg.max_books)
+    }
+
+    fn slots(&self) -> std::sync::MutexGuard<'_, HashMap<String, Weak<BookSlot>>> {
+        self.slots.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn touch(&self, slot: &BookSlot) {
+        let tick = self.tick.fetch_add(1, Ordering::Relaxed) + 1;
+        slot.last_used.store(tick, Ordering::Relaxed);
+    }
+
+    /// Register the stream `key` and seed its book (most recently used); evicts the coldest
+    /// books while over `max_books`. The book lives as long as the returned slot.
+    pub fn register(&self, key: &str, book: BookState) -> Arc<BookSlot> {
+        let slot = Arc::new(BookSlot {
+            key: key.to_string(),
+            last_used: AtomicU64::new(0),
+            held: Arc::clone(&self.held),
+            state: Mutex::new(SlotState::default()),
+        });
+        self.slots().insert(key.to_string(), Arc::downgrade(&slot));
+        self.seed(&slot, book);
+        slot
+    }
+
+    /// (Re-)seed the book of `slot` as most recently used; evicts over the cap.
+    pub fn seed(&self, slot: &BookSlot, book: BookState) {
+        {
+            let mut st = slot.lock();
+            if st.book.replace(book).is_none() {
+                self.held.fetch_add(1, Ordering::Relaxed);
+            }
+            st.reseed = None;
+        }
+        self.touch(slot);
+        self.evict_over_cap(&slot.key);
+    }
+
+    fn evict_over_cap(&self, keep: &str) {
+        if self.max_books == 0 {
+            return;
+        }
+        while self.held.load(Ordering::Relaxed) > self.max_books {
+            let mut slots = self.slots();
+            slots.retain(|_, s| s.strong_count() > 0);
+            let Some(cold) = slots
+                .values()
+                .filter_map(Weak::upgrade)
+                .filter(|s| s.key != keep && s.is_held())
+                .min_by_key(|s| s.last_used.load(Ordering::Relaxed))
+            else {
+                break;
+            };
+            drop(slots);
+            if cold.lock().book.take().is_some() {
+                self.held.fetch_sub(1, Ordering::Relaxed);
+                self.evicted.fetch_add(1, Ordering::Relaxed);
+                tracing::info!(
+                    component = "book",
+                    book = %cold.key,
+                    max_books = self.max_books,
+                    "book evicted (least recently updated); re-seeded on next update"
+                );
+            }
+        }
+    }
+
+    /// Run `f` on the book of `slot` and mark it most recently used.
+    /// None if the book is not held (evicted): the caller must re-seed.
+    pub fn with_book<R>(&self, slot: &BookSlot, f: impl FnOnce(&mut BookState) -> R) -> Option<R> {
+        let out = slot.lock().book.as_mut().map(f)?;
+        self.touch(slot);
+        Some(out)
+    }
+
+    /// Run `f` on the book of `slot` without marking it used (timer work must not keep a
+    /// cold book warm). None if the book is not held.
+    pub fn peek_book<R>(&self, slot: &BookSlot, f: impl FnOnce(&mut BookState) -> R) -> Option<R> {
+        slot.lock().book.as_mut().map(f)
+    }
+
+    /// Apply a delta batch to the book of `slot`, or buffer it while the book is re-seeded.
+    /// `first_seq` is the first update id of the batch (Binance `U`; its rows carry `u`), to
+    /// check that the re-seed snapshot connects to the buffered deltas.
+    pub fn apply_deltas(
+        &self,
+        slot: &BookSlot,
+        first_seq: Option<i64>,
+        rows: &[DepthDeltaRow],
+    ) -> BookUpdate {
+        let mut st = slot.lock();
+        if let Some(book) = st.book.as_mut() {
+            let bbo = book.apply_deltas(rows);
+            drop(st);
+            self.touch(slot);
+            return BookUpdate::Applied(bbo);
+        }
+        let started = st.reseed.is_none();
+        let buffer = st.reseed.get_or_insert_with(VecDeque::new);
+        if buffer.len() >= RESEED_BUFFER_MAX {
+            // Dropping only the oldest would leave a gap past the snapshot: restart the buffer
+            buffer.clear();
+            tracing::warn!(
+                component = "book",
+                book = %slot.key,
+                max = RESEED_BUFFER_MAX,
+                "book re-seed buffer full; buffered deltas dropped, snapshot fetched again"
+            );
+        }
+        buffer.push_back(BufferedDeltas {
+            first_seq,
+            rows: rows.to_vec(),
+        });
+        if started {
+            BookUpdate::Reseed
+        } else {
+            BookUpdate::Buffered
+        }
+    }
+
+    /// End of a re-seed: `book` (seeded from the snapshot) catches up on the buffered deltas
+    /// past the snapshot (see `BookState::seed`) and becomes the held book.
+    ///
+    /// False (nothing changed, the deltas stay buffered) when the snapshot does not connect:
+    /// the first buffered batch past it must start at or before `lastUpdateId + 1`
+    /// (`U <= lastUpdateId + 1 <= u`). The caller fetches a newer snapshot.
+    pub fn finish_reseed(&self, slot: &BookSlot, mut book: BookState) -> bool {
+        let mut st = slot.lock();
+        let Some(buffered) = st.reseed.as_ref() else {
+            // Seeded meanwhile (or aborted): the snapshot has nothing to connect to
+            drop(st);
+            self.seed(slot, book);
+            return true;
+        };
+        if let Some(last) = book.seeded_seq {
+            let next = buffered.iter().find(|b| {
+                b.rows
+                    .iter()
+                    .filter_map(|r| r.seq)
+                    .max()
+                    .is_some_and(|u| u > last)
+            });
+            if let Some(first) = next.and_then(|b| b.first_seq)
+                && first > last + 1
+            {
+                return false;
+            }
+        }
+        let buffered = st.reseed.take().unwrap_or_default();
+        drop(st);
+        for b in &buffered {
+            book.catch_up(&b.rows);
+        }
+        self.seed(slot, book);
+        true
+    }
+
+    /// A re-seed failed: drop the buffered deltas; the next update starts a new one.
+    pub fn abort_reseed(&self, slot: &BookSlot) {
+        slot.lock().reseed = None;
+    }
+
+    /// Drop the book of a removed stream (it also goes once the stream drops its slot).
+    pub fn remove(&self, key: &str) {
+        let slot = self.slots().remove(key).and_then(|s| s.upgrade());
+        if let Some(slot) = slot
+            && slot.lock().book.take().is_some()
+        {
+            self.held.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    /// True if the book of `key` is held.
+    pub fn contains(&self, key: &str) -> bool {
+        self.slots()
+            .get(key)
+            .and_then(Weak::upgrade)
+            .is_some_and(|s| s.is_held())
+    }
+
+    /// Books held.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.held.load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Books evicted over the cap since startup.
+    #[inline]
+    pub fn evicted_total(&self) -> u64 {
+        self.evicted.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: i64) -> DateTime<Utc> {
+        DateTime::<Utc>::from_timestamp_millis(1_700_000_000_000 + ms).unwrap()
+    }
+
+    fn row(ms: i64, side: BookSide, price_i: i64, size_i: i64) -> DepthDeltaRow {
+        DepthDeltaRow {
+            exchange: "binance_linear",
+            time: self::ms(ms),
+            symbol: "BTCUSDT".into(),
+            side,
+            price_i,
+            size_i,
+            seq: None,
+        }
+    }
+
+    fn bbo(r: &BboRow) -> (Option<i64>, Option<i64>, Option<i64>, Option<i64>) {
+        (r.bid_px_i, r.bid_sz_i, r.ask_px_i, r.ask_sz_i)
+    }
+
+    #[test]
+    fn bbo_rows_follow_top_of_book_transitions() {
+        let mut book = BookState::new("BTCUSDT", 0);
+
+        // Seed from a snapshot
+        let out = book
+            .apply_snapshot(&[
+                row(0, BookSide::Bid, 99, 5),
+                row(0, BookSide::Bid, 100, 2),
+                row(0, BookSide::Ask, 101, 3),
+                row(0, BookSide::Ask, 102, 8),
+            ])
+            .expect("initial top");
+        assert_eq!(bbo(&out), (Some(100), Some(2), Some(101), Some(3)));
+        assert_eq!(book.depth(), (2, 2));
+
+        // Changes below the top emit nothing
+        assert!(
+            book.apply_deltas(&[row(10, BookSide::Bid, 98, 4)])
+                .is_none()
+        );
+        assert!(
+            book.apply_deltas(&[row(11, BookSide::Ask, 102, 0)])
+                .is_none()
+        );
+
+        // Size change at the top
+        let out = book
+            .apply_deltas(&[row(20, BookSide::Ask, 101, 1)])
+            .unwrap();
+        assert_eq!(bbo(&out), (Some(100), Some(2), Some(101), Some(1)));
+        assert_eq!(out.time, row(20, BookSide::Ask, 0, 0).time);
+
+        // Better bid
+        let out = book
+            .apply_deltas(&[
+                row(30, BookSide::Bid, 100, 9),
+                row(30, BookSide::Bid, 100, 0),
+            ])
+            .unwrap();
+        assert_eq!(bbo(&out), (Some(99), Some(5), Some(101), Some(1)));
+
+        // Ask side wiped out -> empty side is NULL
+        let out = book
+            .apply_deltas(&[row(40, BookSide::Ask, 101, 0)])
+            .unwrap();
+        assert_eq!(bbo(&out), (Some(99), Some(5), None, None));
+        assert_eq!(out.symbol, "BTCUSDT");
+    }
+
+    #[test]
+    fn emission_is_debounced_by_min_interval() {
+        let mut book = BookState::new("BTCUSDT", 100);
+
+        assert!(
+            book.apply_deltas(&[row(0, BookSide::Bid, 100, 1)])
+                .is_some()
+        );
+
+        // Top changes inside the interval are held back
+        assert!(
+            book.apply_deltas(&[row(10, BookSide::Bid, 100, 2)])
+                .is_none()
+        );
+        assert!(
+            book.apply_deltas(&[row(50, BookSide::Bid, 101, 1)])
+                .is_none()
+        );
+
+        // First update past the interval emits the current top, even if it did not touch it
+        let out = book
+            .apply_deltas(&[row(120, BookSide::Ask, 200, 1)])
+            .unwrap();
+        assert_eq!(bbo(&out), (Some(101), Some(1), Some(200), Some(1)));
+
+        // Top unchanged since the last emitted row -> nothing to emit
+        assert!(
+            book.apply_deltas(&[row(300, BookSide::Bid, 90, 1)])
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn held_top_is_flushed_without_a_further_update() {
+        let mut book = BookState::new("BTCUSDT", 100);
+        assert!(
+            book.apply_deltas(&[row(0, BookSide::Bid, 100, 1)])
+                .is_some()
+        );
+        // Last update of a burst, inside the interval: held back
+        assert!(
+            book.apply_deltas(&[row(10, BookSide::Bid, 101, 1)])
+                .is_none()
+        );
+
+        // Timer inside the interval: still held
+        assert!(book.flush_due(ms(50)).is_none());
+        // Timer past the interval: the held top, at the time of its update
+        let out = book.flush_due(ms(120)).unwrap();
+        assert_eq!(bbo(&out), (Some(101), Some(1), None, None));
+        assert_eq!(out.time, ms(10));
+        assert!(book.flush_due(ms(500)).is_none());
+
+        // Stream stop flushes regardless of the interval
+        assert!(
+            book.apply_deltas(&[row(50, BookSide::Bid, 102, 1)])
+                .is_none()
+        );
+        let out = book.flush_held().unwrap();
+        assert_eq!(bbo(&out), (Some(102), Some(1), None, None));
+
+        // Held change reverted before the flush: nothing new to write
+        assert!(
+            book.apply_deltas(&[row(60, BookSide::Bid, 103, 1)])
+                .is_none()
+        );
+        assert!(
+            book.apply_deltas(&[row(70, BookSide::Bid, 103, 0)])
+                .is_none()
+        );
+        assert!(book.flush_held().is_none());
+    }
+
+    #[test]
+    fn seeded_book_emits_top_on_first_update() {
+        let mut book = BookState::new("BTCUSDT", 0);
+        book.seed(&[row(0, BookSide::Bid, 100, 2), row(0, BookSide::Ask, 101, 3)]);
+
+        // Update below the top still reports the seeded top once
+        let out = book.apply_deltas(&[row(5, BookSide::Bid, 90, 1)]).unwrap();
+        assert_eq!(bbo(&out), (Some(100), Some(2), Some(101), Some(3)));
+        assert!(book.apply_deltas(&[row(6, BookSide::Bid, 91, 1)]).is_none());
+    }
+
+    #[test]
+    fn empty_input_and_empty_book_emit_nothing() {
+        let mut book = BookState::new("BTCUSDT", 0);
+        assert!(book.apply_deltas(&[]).is_none());
+        assert!(
+            book.apply_deltas(&[row(0, BookSide::Bid, 100, 0)])
+                .is_none()
+        );
+        assert_eq!(book.top(), TopOfBook::default());
+    }
+
+    #[test]
+    fn microprice_weights_each_price_by_the_opposite_size() {
+        let mut book = BookState::new("BTCUSDT", 0);
+        // bid 100.00 x 3, ask 101.00 x 1 (prices scaled by 100): heavy bid pulls towards the ask
+        let out = book
+            .apply_snapshot(&[
+                row(0, BookSide::Bid, 10_000, 3),
+                row(0, BookSide::Ask, 10_100, 1),
+            ])
+            .unwrap();
+        let mp = microprice_row(&out).unwrap();
+        // (10_000*1 + 10_100*3) / 4 = 10_075
+        assert_eq!(mp.microprice_i, 10_075);
+        assert_eq!((mp.time, mp.symbol.as_str()), (out.time, "BTCUSDT"));
+
+        // Rounded to nearest: (100*1 + 101*2) / 3 = 100.67 -> 101
+        let top = TopOfBook {
+            bid: Some((100, 2)),
+            ask: Some((101, 1)),
+        };
+        assert_eq!(top.microprice_i(), Some(101));
+
+        // Products far past i64 (price 1e15 x size 1e12) still come out exact
+        let top = TopOfBook {
+            bid: Some((1_000_000_000_000_000, 1_000_000_000_000)),
+            ask: Some((1_000_000_000_000_002, 1_000_000_000_000)),
+        };
+        assert_eq!(top.microprice_i(), Some(1_000_000_000_000_001));
+
+        // One side empty -> no microprice
+        let out = book
+            .apply_deltas(&[row(10, BookSide::Ask, 10_100, 0)])
+            .unwrap();
+        assert!(microprice_row(&out).is_none());
+    }
+
+    #[test]
+    fn book_cache_evicts_coldest_and_reseeds_on_reactivation() {
+        let cache = BookCache::new(2);
+        let seeded = |sym: &str, bid: i64| {
+            let mut book = BookState::new(sym, 0);
+            book.seed(&[
+                row(0, BookSide::Bid, bid, 1),
+                row(0, BookSide::Ask, bid + 1, 1),
+            ]);
+            book
+        };
+
+        let btc = cache.register("BTCUSDT", seeded("BTCUSDT", 100));
+        let eth = cache.register("ETHUSDT", seeded("ETHUSDT", 200));
+
+        // BTC gets activity -> ETH is now the coldest
+        let out = cache.with_book(&btc, |b| b.apply_deltas(&[row(1, BookSide::Bid, 90, 1)]));
+        assert!(out.is_some());
+
+        // Third book over the cap of 2 evicts ETH
+        let sol = cache.register("SOLUSDT", seeded("SOLUSDT", 300));
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.evicted_total(), 1);
+        assert!(!cache.contains("ETHUSDT"));
+        assert!(cache.contains("BTCUSDT") && cache.contains("SOLUSDT"));
+
+        // Reactivated ETH finds its book gone -> caller re-seeds (BTC is coldest now)
+        let reactivated = cache.with_book(&eth, |b| b.apply_deltas(&[row(2, BookSide::Bid, 1, 1)]));
+        assert!(reactivated.is_none());
+        let mut book = seeded("ETHUSDT", 210);
+        let out = book.apply_deltas(&[row(2, BookSide::Bid, 205, 1)]).unwrap();
+        assert_eq!(bbo(&out), (Some(210), Some(1), Some(211), Some(1)));
+        cache.seed(&eth, book);
+
+        assert_eq!(cache.evicted_total(), 2);
+        assert!(!cache.contains("BTCUSDT"));
+        let top = cache.with_book(&eth, |b| b.top()).unwrap();
+        assert_eq!(top.bid, Some((210, 1)));
+
+        // A stopped stream drops its slot and frees its place
+        drop(sol);
+        assert_eq!(cache.len(), 1);
+
+        // Unbounded cache never evicts
+        let unbounded = BookCache::new(0);
+        let slots: Vec<_> = (0..100)
+            .map(|i| unbounded.register(&format!("S{i}"), BookState::new("S", 0)))
+            .collect();
+        assert_eq!((unbounded.len(), unbounded.evicted_total()), (100, 0));
+        drop(slots);
+        assert!(unbounded.is_empty());
+    }
+
+    #[test]
+    fn reseed_buffers_deltas_and_replays_past_the_snapshot() {
+        let with_seq = |ms, side, price_i, size_i, seq| DepthDeltaRow {
+            seq: Some(seq),
+            ..row(ms, side, price_i, size_i)
+        };
+        let cache = BookCache::new(1);
+        let btc = cache.register("BTCUSDT", BookState::new("BTCUSDT", 0));
+        let _eth = cache.register("ETHUSDT", BookState::new("ETHUSDT", 0));
+        assert!(!btc.is_held());
+
+        // First update of the evicted book starts the re-seed, the next ones are buffered
+        let old = [with_seq(1, BookSide::Bid, 99, 7, 10)];
+        let new = [with_seq(2, BookSide::Bid, 101, 2, 12)];
+        assert!(matches!(
+            cache.apply_deltas(&btc, Some(9), &old),
+            BookUpdate::Reseed
+        ));
+        assert!(matches!(
+            cache.apply_deltas(&btc, Some(11), &new),
+            BookUpdate::Buffered
+        ));
+
+        // Snapshot at lastUpdateId 11: the delta ending at 10 is already in it
+        let mut book = BookState::new("BTCUSDT", 0);
+        book.seed(&[
+            with_seq(0, BookSide::Bid, 100, 1, 11),
+            with_seq(0, BookSide::Ask, 102, 1, 11),
+        ]);
+        assert!(cache.finish_reseed(&btc, book));
+        assert!(btc.is_held());
+        let top = cache.with_book(&btc, |b| b.top()).unwrap();
+        assert_eq!(top.bid, Some((101, 2)));
+        assert_eq!(cache.with_book(&btc, |b| b.depth()), Some((2, 1)));
+
+        // Live deltas older than the snapshot are skipped too; the first live one emits the top
+        let out = cache.apply_deltas(&btc, Some(9), &[with_seq(3, BookSide::Bid, 99, 7, 9)]);
+        assert!(matches!(out, BookUpdate::Applied(None)));
+        let out = cache.apply_deltas(&btc, Some(13), &[with_seq(4, BookSide::Ask, 103, 1, 13)]);
+        let BookUpdate::Applied(Some(out)) = out else {
+            panic!("expected a BBO row, got {out:?}");
+        };
+        assert_eq!(bbo(&out), (Some(101), Some(2), Some(102), Some(1)));
+
+        // A failed re-seed drops the buffer; the next update starts a new one
+        let _sol = cache.register("SOLUSDT", BookState::new("SOLUSDT", 0));
+        assert!(matches!(
+            cache.apply_deltas(&btc, Some(11), &new),
+            BookUpdate::Reseed
+        ));
+        cache.abort_reseed(&btc);
+        assert!(matches!(
+            cache.apply_deltas(&btc, Some(11), &new),
+            BookUpdate::Reseed
+        ));
+    }
+
+    #[test]
+    fn reseed_refuses_a_snapshot_that_does_not_connect() {
+        let with_seq = |ms, side, price_i, size_i, seq| DepthDeltaRow {
+            seq: Some(seq),
+            ..row(ms, side, price_i, size_i)
+        };
+        let snapshot = |last| {
+            let mut book = BookState::new("BTCUSDT", 0);
+            book.seed(&[
+                with_seq(0, BookSide::Bid, 100, 1, last),
+                with_seq(0, BookSide::Ask, 102, 1, last),
+            ]);
+            book
+        };
+        let cache = BookCache::new(1);
+        let btc = cache.register("BTCUSDT", BookState::new("BTCUSDT", 0));
+        let _eth = cache.register("ETHUSDT", BookState::new("ETHUSDT", 0));
+
+        // Buffered deltas cover 21..=30: a snapshot at 15 leaves 16..=20 out
+        let first = [with_seq(1, BookSide::Bid, 101, 2, 30)];
+        assert!(matches!(
+            cache.apply_deltas(&btc, Some(21), &first),
+            BookUpdate::Reseed
+        ));
+        assert!(!cache.finish_reseed(&btc, snapshot(15)));
+        assert!(!btc.is_held());
+        // ...the deltas stay buffered for the next snapshot, which connects
+        assert!(cache.finish_reseed(&btc, snapshot(25)));
+        assert_eq!(
+            cache.with_book(&btc, |b| b.top()).unwrap().bid,
+            Some((101, 2))
+        );
+
+        // A full buffer restarts at the newest batch: the snapshot before the gap is refused
+        let _sol = cache.register("SOLUSDT", BookState::new("SOLUSDT", 0));
+        assert!(!btc.is_held());
+        for i in 0..RESEED_BUFFER_MAX as i64 + 1 {
+            let seq = 100 + i;
+            cache.apply_deltas(&btc, Some(seq), &[with_seq(2, BookSide::Bid, 101, 1, seq)]);
+        }
+        assert!(!cache.finish_reseed(&btc, snapshot(100)));
+        let last = 100 + RESEED_BUFFER_MAX as i64;
+        assert!(cache.finish_reseed(&btc, snapshot(last - 1)));
+        assert!(btc.is_held());
+    }
+}
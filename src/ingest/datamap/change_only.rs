@@ -0,0 +1,361 @@
+//! ingest/datamap/change_only.rs
+//!
+//! Store-on-change compaction for slowly-changing metrics (open interest, funding).
+//!
+//! Polling OI every few seconds mostly produces the same value over and over.
+//! `ChangeOnlyFilter` keeps the last STORED value per symbol and drops rows that repeat it:
+//! - A row is kept when its value differs from the last stored value for that symbol.
+//!   Funding is keyed on its `funding_time` too: a new funding period is a change even if
+//!   the rate repeats.
+//! - An unchanged row is still kept once `max_suppress_ms` has elapsed since the last stored
+//!   row (heartbeat), so a flat series never goes fully silent.
+//! - Windows are driven by row event time, like `DepthCoalescer`.
+//! - Only DB rows are filtered; Redis publishes still see every update.
+//! - Disabled filters pass rows straight through.
+//!
+//! The per-symbol last values are capped at `writer.max_change_only_symbols`; over the cap
+//! the least recently seen symbol is evicted. Evicting only costs a redundant row: the next
+//! value of that symbol is stored as if it were new, and its heartbeat window restarts.
+//! Held entries are reported in `ingest_dq_cache_entries` (summed over all filters).
+
+use crate::db::config::WriterConfig;
+use crate::ingest::datamap::event::{FundingRow, OpenInterestRow};
+use crate::ingest::metrics::IngestMetrics;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{BTreeMap, HashMap};
+
+/// Rows that can be compacted to change-only.
+pub trait ChangeOnlyRow {
+    fn symbol(&self) -> &str;
+    fn value(&self) -> i64;
+    fn event_time(&self) -> DateTime<Utc>;
+    /// Period the value belongs to; a new period counts as a change.
+    fn period(&self) -> Option<DateTime<Utc>> {
+        None
+    }
+}
+
+impl ChangeOnlyRow for OpenInterestRow {
+    fn symbol(&self) -> &str {
+        &self.symbol
+    }
+    fn value(&self) -> i64 {
+        self.oi_i
+    }
+    fn event_time(&self) -> DateTime<Utc> {
+        self.time
+    }
+}
+
+impl ChangeOnlyRow for FundingRow {
+    fn symbol(&self) -> &str {
+        &self.symbol
+    }
+    fn value(&self) -> i64 {
+        self.funding_rate
+    }
+    fn event_time(&self) -> DateTime<Utc> {
+        self.time
+    }
+    fn period(&self) -> Option<DateTime<Utc>> {
+        self.funding_time
+    }
+}
+
+/// Last stored value of one symbol.
+#[derive(Debug)]
+struct LastStored {
+    value: i64,
+    period: Option<DateTime<Utc>>,
+    /// Event time it was stored at
+    stored_at: DateTime<Utc>,
+    /// Last-use tick (key into `by_use`)
+    tick: u64,
+}
+
+#[derive(Debug)]
+pub struct ChangeOnlyFilter {
+    enabled: bool,
+    max_suppress: Duration,
+    /// 0 = unbounded
+    max_symbols: usize,
+    // symbol -> last stored value
+    last: HashMap<String, LastStored>,
+    // last-use tick -> symbol (oldest first)
+    by_use: BTreeMap<u64, String>,
+    tick: u64,
+    suppressed: u64,
+    evicted: u64,
+    metrics: Option<IngestMetrics>,
+}
+
+impl ChangeOnlyFilter {
+    pub fn new(enabled: bool, max_suppress_ms: u64) -> Self {
+        Self {
+            enabled,
+            max_suppress: Duration::milliseconds(max_suppress_ms as i64),
+            max_symbols: 0,
+            last: HashMap::new(),
+            by_use: BTreeMap::new(),
+            tick: 0,
+            suppressed: 0,
+            evicted: 0,
+            metrics: None,
+        }
+    }
+
+    /// `writer.oi_store_on_change`
+    pub fn open_interest(writer: &WriterConfig) -> Self {
+        Self::new(
+            writer.oi_store_on_change,
+            writer.store_on_change_max_suppress_ms,
+        )
+        .with_max_symbols(writer.max_change_only_symbols)
+    }
+
+    /// `writer.funding_store_on_change`
+    pub fn funding(writer: &WriterConfig) -> Self {
+        Self::new(
+            writer.funding_store_on_change,
+            writer.store_on_change_max_suppress_ms,
+        )
+        .with_max_symbols(writer.max_change_only_symbols)
+    }
+
+    /// Cap on symbols whose last value is held (0 = unbounded).
+    pub fn with_max_symbols(mut self, max_symbols: usize) -> Self {
+        self.max_symbols = max_symbols;
+        self
+    }
+
+    /// Report held entries / evictions to `metrics`.
+    pub fn with_metrics(mut self, metrics: Option<&IngestMetrics>) -> Self {
+        self.metrics = metrics.cloned();
+        self
+    }
+
+    /// Symbols whose last value is held.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.last.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.last.is_empty()
+    }
+
+    /// Symbols evicted over the cap since creation.
+    #[inline]
+    pub fn evicted_total(&self) -> u64 {
+        self.evicted
+    }
+
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Rows dropped since creation.
+    #[inline]
+    pub fn suppressed_total(&self) -> u64 {
+        self.suppressed
+    }
+
+    /// Decide whether a single value should be stored (and remember it if so).
+    pub fn admit(&mut self, symbol: &str, value: i64, time: DateTime<Utc>) -> bool {
+        self.admit_in_period(symbol, value, None, time)
+    }
+
+    /// `admit` for a value of `period`: a different period than the stored one is a change.
+    pub fn admit_in_period(
+        &mut self,
+        symbol: &str,
+        value: i64,
+        period: Option<DateTime<Utc>>,
+        time: DateTime<Utc>,
+    ) -> bool {
+        if !self.enabled {
+            return true;
+        }
+
+        self.tick += 1;
+        let tick = self.tick;
+        if let Some(last) = self.last.get_mut(symbol) {
+            self.by_use.remove(&last.tick);
+            self.by_use.insert(tick, symbol.to_string());
+            last.tick = tick;
+            if last.value == value
+                && last.period == period
+                && time - last.stored_at < self.max_suppress
+            {
+                self.suppressed += 1;
+                return false;
+            }
+            last.value = value;
+            last.period = period;
+            last.stored_at = time;
+            return true;
+        }
+
+        self.last.insert(
+            symbol.to_string(),
+            LastStored {
+                value,
+                period,
+                stored_at: time,
+                tick,
+            },
+        );
+        self.by_use.insert(tick, symbol.to_string());
+        if let Some(m) = &self.metrics {
+            m.add_dq_cache_entries(1);
+        }
+        while self.max_symbols > 0 && self.last.len() > self.max_symbols {
+            let Some((_, cold)) = self.by_use.pop_first() else {
+                break;
+            };
+            self.last.remove(&cold);
+            self.evicted += 1;
+            if let Some(m) = &self.metrics {
+                m.add_dq_cache_entries(-1);
+                m.inc_dq_cache_evicted();
+            }
+        }
+        true
+    }
+
+    /// Keep only the rows that should be stored, in order.
+    pub fn retain<R: ChangeOnlyRow>(&mut self, rows: Vec<R>) -> Vec<R> {
+        if !self.enabled {
+            return rows;
+        }
+        rows.into_iter()
+            .filter(|r| self.admit_in_period(r.symbol(), r.value(), r.period(), r.event_time()))
+            .collect()
+    }
+}
+
+impl Drop for ChangeOnlyFilter {
+    fn drop(&mut self) {
+        if let Some(m) = &self.metrics {
+            m.add_dq_cache_entries(-(self.last.len() as i64));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn oi(sec: i64, oi_i: i64) -> OpenInterestRow {
+        OpenInterestRow {
+            exchange: "binance_linear",
+            time: DateTime::<Utc>::from_timestamp_millis(1_700_000_000_000 + sec * 1000).unwrap(),
+            symbol: "BTCUSDT".into(),
+            oi_i,
+        }
+    }
+
+    fn secs(rows: &[OpenInterestRow]) -> Vec<i64> {
+        rows.iter()
+            .map(|r| (r.time.timestamp_millis() - 1_700_000_000_000) / 1000)
+            .collect()
+    }
+
+    #[test]
+    fn repeated_oi_is_suppressed_with_periodic_heartbeat() {
+        let mut f = ChangeOnlyFilter::new(true, 60_000);
+
+        // OI polled every 5s, flat for 2 minutes
+        let rows: Vec<_> = (0..=24).map(|i| oi(i * 5, 100)).collect();
+        let kept = f.retain(rows);
+
+        // first row + one heartbeat per minute
+        assert_eq!(secs(&kept), vec![0, 60, 120]);
+        assert_eq!(f.suppressed_total(), 22);
+    }
+
+    #[test]
+    fn changes_are_always_stored() {
+        let mut f = ChangeOnlyFilter::new(true, 60_000);
+
+        let kept = f.retain(vec![
+            oi(0, 100),
+            oi(5, 100),
+            oi(10, 101), // change
+            oi(15, 101),
+            oi(20, 100), // back to a previous value is still a change
+        ]);
+        assert_eq!(secs(&kept), vec![0, 10, 20]);
+        assert_eq!(f.suppressed_total(), 2);
+    }
+
+    #[test]
+    fn symbols_are_tracked_independently() {
+        let mut f = ChangeOnlyFilter::new(true, 60_000);
+        let t = oi(0, 1).time;
+
+        assert!(f.admit("BTCUSDT", 7, t));
+        assert!(f.admit("ETHUSDT", 7, t), "same value, other symbol");
+        assert!(!f.admit("BTCUSDT", 7, t));
+        assert!(!f.admit("ETHUSDT", 7, t));
+    }
+
+    #[test]
+    fn symbol_cap_evicts_least_recently_seen() {
+        let mut f = ChangeOnlyFilter::new(true, 60_000).with_max_symbols(100);
+        let t = oi(0, 1).time;
+
+        for i in 0..1_000 {
+            assert!(f.admit(&format!("SYM{i}"), 7, t));
+            assert!(f.len() <= 100);
+        }
+        assert_eq!(f.len(), 100);
+        assert_eq!(f.evicted_total(), 900);
+
+        // Recently seen symbols are still suppressed
+        assert!(!f.admit("SYM999", 7, t));
+        // Touching SYM900 keeps it over the next eviction, SYM901 goes instead
+        assert!(!f.admit("SYM900", 7, t));
+        assert!(f.admit("NEW", 7, t));
+        assert!(!f.admit("SYM900", 7, t));
+        // An evicted symbol degrades to "first value": stored again though unchanged
+        assert!(f.admit("SYM901", 7, t));
+        assert_eq!(f.len(), 100);
+    }
+
+    #[test]
+    fn new_funding_period_is_stored_even_if_the_rate_repeats() {
+        let mut f = ChangeOnlyFilter::new(true, 3_600_000);
+        let at = |sec: i64| oi(sec, 0).time;
+        let funding = |sec: i64, period: i64| FundingRow {
+            exchange: "binance_linear",
+            time: at(sec),
+            symbol: "BTCUSDT".into(),
+            funding_rate: 100,
+            funding_time: Some(at(period)),
+        };
+
+        let kept = f.retain(vec![
+            funding(0, 28_800),
+            funding(5, 28_800),
+            funding(10, 57_600), // next period, same rate
+            funding(15, 57_600),
+        ]);
+        let secs: Vec<i64> = kept
+            .iter()
+            .map(|r| (r.time.timestamp_millis() - 1_700_000_000_000) / 1000)
+            .collect();
+        assert_eq!(secs, vec![0, 10]);
+        assert_eq!(f.suppressed_total(), 2);
+    }
+
+    #[test]
+    fn disabled_filter_passes_through() {
+        let mut f = ChangeOnlyFilter::new(false, 60_000);
+        let kept = f.retain(vec![oi(0, 100), oi(5, 100), oi(10, 100)]);
+        assert_eq!(kept.len(), 3);
+        assert_eq!(f.suppressed_total(), 0);
+    }
+}
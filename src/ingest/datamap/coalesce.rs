@@ -0,0 +1,187 @@
+//! ingest/datamap/coalesce.rs
+//!
+//! Depth write-amplification guard.
+//!
+//! Depth diff streams can emit many tiny deltas per second for the same price level.
+//! `DepthCoalescer` merges deltas for the same (side, price) inside a short window
+//! (`writer.depth_coalesce_window_ms`) and only keeps the LATEST size per level.
+//!
+//! - Windows are driven by row event time (`DepthDeltaRow.time`), one coalescer per stream/symbol.
+//! - A window is released as soon as a row past its end arrives, BEFORE that row is buffered,
+//!   so rows never cross window boundaries (ordering across windows is preserved). A stream
+//!   that goes quiet releases it through `flush_due` (stream timer) / `flush` (stream stop).
+//! - Deletes (`size_i == 0`) are regular "latest size" values: a level set then deleted
+//!   inside a window emits the delete; deleted then re-set emits the new size.
+//! - Intra-window micro-movements are lost by design.
+//! - `window_ms == 0` disables coalescing (rows pass straight through).
+
+use crate::db::config::WriterConfig;
+use crate::ingest::datamap::event::{BookSide, DepthDeltaRow};
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashMap;
+
+#[derive(Debug)]
+pub struct DepthCoalescer {
+    window: Duration,
+    window_start: Option<DateTime<Utc>>,
+    // latest row per level, in first-seen order
+    pending: Vec<DepthDeltaRow>,
+    index: HashMap<(BookSide, i64), usize>,
+}
+
+impl DepthCoalescer {
+    pub fn new(window_ms: u64) -> Self {
+        Self {
+            window: Duration::milliseconds(window_ms as i64),
+            window_start: None,
+            pending: Vec::new(),
+            index: HashMap::new(),
+        }
+    }
+
+    pub fn from_config(writer: &WriterConfig) -> Self {
+        Self::new(writer.depth_coalesce_window_ms)
+    }
+
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.window > Duration::zero()
+    }
+
+    /// Rows buffered in the current window (one per level).
+    #[inline]
+    pub fn pending_len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Feed new deltas; returns the rows of every window that closed.
+    pub fn push(&mut self, rows: Vec<DepthDeltaRow>) -> Vec<DepthDeltaRow> {
+        if !self.is_enabled() {
+            return rows;
+        }
+
+        let mut out = Vec::new();
+        for row in rows {
+            if let Some(start) = self.window_start
+                && row.time - start >= self.window
+            {
+                out.extend(self.flush());
+            }
+            self.window_start.get_or_insert(row.time);
+
+            match self.index.get(&(row.side, row.price_i)) {
+                Some(&i) => self.pending[i] = row,
+                None => {
+                    self.index
+                        .insert((row.side, row.price_i), self.pending.len());
+                    self.pending.push(row);
+                }
+            }
+        }
+        out
+    }
+
+    /// Release the current window once `now` is past its end (stream timer).
+    pub fn flush_due(&mut self, now: DateTime<Utc>) -> Vec<DepthDeltaRow> {
+        match self.window_start {
+            Some(start) if now - start >= self.window => self.flush(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Release the current window regardless of its age.
+    pub fn flush(&mut self) -> Vec<DepthDeltaRow> {
+        self.window_start = None;
+        self.index.clear();
+        std::mem::take(&mut self.pending)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(ms: i64, side: BookSide, price_i: i64, size_i: i64, seq: i64) -> DepthDeltaRow {
+        DepthDeltaRow {
+            exchange: "binance_linear",
+            time: DateTime::<Utc>::from_timestamp_millis(1_700_000_000_000 + ms).unwrap(),
+            symbol: "BTCUSDT".into(),
+            side,
+            price_i,
+            size_i,
+            seq: Some(seq),
+        }
+    }
+
+    fn levels(rows: &[DepthDeltaRow]) -> Vec<(BookSide, i64, i64)> {
+        rows.iter().map(|r| (r.side, r.price_i, r.size_i)).collect()
+    }
+
+    #[test]
+    fn rapid_updates_to_same_level_coalesce_to_latest() {
+        let mut c = DepthCoalescer::new(100);
+
+        // 50 updates to the same bid level within 50ms + one ask level
+        let mut rows: Vec<_> = (0..50)
+            .map(|i| row(i, BookSide::Bid, 100, 10 + i, i))
+            .collect();
+        rows.push(row(10, BookSide::Ask, 101, 7, 100));
+        assert!(c.push(rows).is_empty(), "window still open");
+        assert_eq!(c.pending_len(), 2);
+
+        // Crossing the window boundary releases the coalesced window first
+        let out = c.push(vec![row(150, BookSide::Bid, 100, 0, 200)]);
+        assert_eq!(
+            levels(&out),
+            vec![(BookSide::Bid, 100, 59), (BookSide::Ask, 101, 7)]
+        );
+        assert_eq!(out[0].seq, Some(49), "latest update wins");
+
+        // The delete from the next window is kept (not merged backwards)
+        let out = c.flush();
+        assert_eq!(levels(&out), vec![(BookSide::Bid, 100, 0)]);
+        assert_eq!(c.pending_len(), 0);
+    }
+
+    #[test]
+    fn quiet_stream_window_is_released_by_the_clock() {
+        let at = |ms: i64| DateTime::<Utc>::from_timestamp_millis(1_700_000_000_000 + ms).unwrap();
+        let mut c = DepthCoalescer::new(100);
+        assert!(c.flush_due(at(500)).is_empty(), "nothing buffered");
+
+        assert!(c.push(vec![row(0, BookSide::Bid, 100, 5, 1)]).is_empty());
+        assert!(c.flush_due(at(99)).is_empty(), "window still open");
+        let out = c.flush_due(at(100));
+        assert_eq!(levels(&out), vec![(BookSide::Bid, 100, 5)]);
+        assert_eq!(c.pending_len(), 0);
+    }
+
+    #[test]
+    fn deletes_inside_window_are_preserved() {
+        let mut c = DepthCoalescer::new(100);
+
+        c.push(vec![
+            row(0, BookSide::Ask, 200, 5, 1),
+            row(10, BookSide::Ask, 200, 0, 2), // set then delete -> delete
+            row(20, BookSide::Bid, 90, 0, 3),
+            row(30, BookSide::Bid, 90, 4, 4), // delete then re-set -> new size
+        ]);
+
+        let out = c.flush();
+        assert_eq!(
+            levels(&out),
+            vec![(BookSide::Ask, 200, 0), (BookSide::Bid, 90, 4)]
+        );
+    }
+
+    #[test]
+    fn zero_window_passes_through() {
+        let mut c = DepthCoalescer::new(0);
+        let rows = vec![
+            row(0, BookSide::Bid, 100, 1, 1),
+            row(1, BookSide::Bid, 100, 2, 2),
+        ];
+        assert_eq!(c.push(rows).len(), 2);
+        assert_eq!(c.pending_len(), 0);
+    }
+}
@@ -1,10 +1,29 @@
 use crate::app::config::AppConfig;
 use crate::error::{AppError, AppResult};
+use crate::ingest::config::SymbolCase;
+use crate::ingest::datamap::naming::symbol_token;
 use crate::ingest::instruments::registry::InstrumentRegistry;
 use crate::ingest::instruments::spec::InstrumentSpec;
+use crate::ingest::metrics::IngestMetrics;
 use chrono::{DateTime, Utc};
-use rust_decimal::Decimal;
+use rust_decimal::prelude::FromPrimitive;
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::Deserialize;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// What to do with a size carrying more decimals than the instrument declares
+/// (`qty_precision` of the exchange config; Hyperliquid declares `szDecimals`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QtyPrecision {
+    /// Count + warn, scale the value as received.
+    #[default]
+    Flag,
+    /// Count + warn, then round half away from zero to the declared decimals before the
+    /// base conversion and scaling.
+    Round,
+}
 
 /// Anything a mapper needs to normalize raw messages.
 
@@ -13,11 +32,24 @@ pub struct MapCtx {
     pub inst: InstrumentSpec,
     pub now: DateTime<Utc>, // ingest time
 
-    // Global fixed-point scales (from config)
+    // Fixed-point scales (global config with the exchange's overrides)
     pub price_scale: i64,
     pub qty_scale: i64,
     pub open_interest_scale: i64,
     pub funding_scale: i64,
+
+    // Per-exchange symbol casing (applied to lookups + row symbols)
+    pub symbol_case: SymbolCase,
+
+    // Values with more decimals than `inst` declares (shared across clones)
+    pub precision_exceeded: Arc<AtomicU64>,
+    pub metrics: Option<Arc<IngestMetrics>>,
+
+    // Funding plausibility: |rate| above this fraction is dropped (None = unchecked)
+    pub max_abs_funding_rate: Option<Decimal>,
+    pub funding_rejected: Arc<AtomicU64>,
+
+    pub qty_precision: QtyPrecision,
 }
 
 impl MapCtx {
@@ -27,34 +59,136 @@ impl MapCtx {
         exchange: &str,
         symbol: &str,
     ) -> AppResult<Self> {
+        Self::new_with_symbol_case(registry, cfg, exchange, symbol, SymbolCase::Preserve)
+    }
+
+    /// Same as `new`, but normalizes `symbol` with the exchange casing policy before the
+    /// registry lookup and for every row symbol produced by the mappers.
+    pub fn new_with_symbol_case(
+        registry: Arc<InstrumentRegistry>,
+        cfg: &AppConfig,
+        exchange: &str,
+        symbol: &str,
+        symbol_case: SymbolCase,
+    ) -> AppResult<Self> {
+        let symbol = symbol_case.apply(symbol);
         let inst = registry
-            .get(exchange, symbol)
+            .get(exchange, &symbol)
             .ok_or_else(|| {
                 AppError::Internal(format!(
                     "unknown instrument: exchange='{exchange}' symbol='{symbol}'"
                 ))
             })?
             .clone();
+        let scales = cfg.scales.for_exchange(exchange);
         Ok(Self {
             inst,
             now: Utc::now(),
-            price_scale: cfg.scales.price,
-            qty_scale: cfg.scales.qty,
-            open_interest_scale: cfg.scales.open_interest,
-            funding_scale: cfg.scales.funding,
+            price_scale: scales.price,
+            qty_scale: scales.qty,
+            open_interest_scale: scales.open_interest,
+            funding_scale: scales.funding,
+            symbol_case,
+            precision_exceeded: Arc::new(AtomicU64::new(0)),
+            metrics: None,
+            max_abs_funding_rate: None,
+            funding_rejected: Arc::new(AtomicU64::new(0)),
+            qty_precision: QtyPrecision::default(),
         })
     }
 
+    /// Report precision drift to ingest metrics as well.
+    pub fn with_metrics(mut self, metrics: Option<Arc<IngestMetrics>>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Funding plausibility bound in percent (`max_abs_funding_rate_pct` of the exchange config).
+    pub fn with_max_abs_funding_rate_pct(mut self, pct: Option<f64>) -> Self {
+        self.max_abs_funding_rate = pct
+            .and_then(Decimal::from_f64)
+            .map(|p| p / Decimal::ONE_HUNDRED);
+        self
+    }
+
+    /// Over-precise size handling (`qty_precision` of the exchange config).
+    pub fn with_qty_precision(mut self, qty_precision: QtyPrecision) -> Self {
+        self.qty_precision = qty_precision;
+        self
+    }
+
+    /// Funding rates dropped as implausible.
+    #[inline]
+    pub fn funding_rejected_total(&self) -> u64 {
+        self.funding_rejected.load(Ordering::Relaxed)
+    }
+
+    /// Values seen with more decimals than the instrument declares.
+    #[inline]
+    pub fn precision_exceeded_total(&self) -> u64 {
+        self.precision_exceeded.load(Ordering::Relaxed)
+    }
+
+    /// Warn + count when `s` has more decimals than declared (a possible venue format change).
+    /// The value is still parsed and scaled as usual; the first hit per stream is a warning.
+    fn check_precision(&self, field: &'static str, s: &str, max_decimals: Option<u32>) {
+        if !InstrumentSpec::exceeds_precision(s, max_decimals) {
+            return;
+        }
+        let seen = self.precision_exceeded.fetch_add(1, Ordering::Relaxed);
+        if let Some(m) = self.metrics.as_ref() {
+            m.inc_precision_exceeded();
+        }
+        if seen == 0 {
+            tracing::warn!(
+                exchange = self.inst.exchange,
+                symbol = %self.inst.symbol,
+                field,
+                value = s,
+                declared_decimals = ?max_decimals,
+                "value has more decimals than the instrument declares (venue format change?)"
+            );
+        } else {
+            tracing::debug!(
+                exchange = self.inst.exchange,
+                symbol = %self.inst.symbol,
+                field,
+                value = s,
+                "value has more decimals than the instrument declares"
+            );
+        }
+    }
+
+    /// Parse a reported size, checked against (and with `QtyPrecision::Round`, rounded to)
+    /// the instrument's declared qty decimals.
+    fn declared_qty(&self, qty_str: &str) -> AppResult<Decimal> {
+        self.check_precision("qty", qty_str, self.inst.qty_decimals);
+        let qty = InstrumentSpec::dec_str(qty_str)?;
+        match (self.qty_precision, self.inst.qty_decimals) {
+            (QtyPrecision::Round, Some(dp)) => {
+                Ok(qty.round_dp_with_strategy(dp, RoundingStrategy::MidpointAwayFromZero))
+            }
+            _ => Ok(qty),
+        }
+    }
+
+    /// Canonical casing of a payload symbol.
+    #[inline]
+    pub fn canonical_symbol(&self, symbol: &str) -> String {
+        symbol_token(self.symbol_case, symbol)
+    }
+
     /// Convenience: parse price string to Decimal (exact).
     #[inline]
     pub fn price_dec(&self, price_str: &str) -> AppResult<Decimal> {
+        self.check_precision("price", price_str, self.inst.price_decimals);
         InstrumentSpec::dec_str(price_str)
     }
 
     /// Convenience: parse qty string to Decimal (exact).
     #[inline]
     pub fn qty_dec(&self, qty_str: &str) -> AppResult<Decimal> {
-        InstrumentSpec::dec_str(qty_str)
+        self.declared_qty(qty_str)
     }
 
     /// Scale any decimal-string using a scale.
@@ -74,16 +208,44 @@ impl MapCtx {
         self.scale_str_i64(oi_str, self.funding_scale)
     }
 
+    /// Scaled funding rate, or None when |rate| exceeds the configured bound (venue glitch).
+    /// Dropped rates are counted and logged instead of being persisted.
+    pub fn funding_rate_i64(&self, symbol: &str, rate_str: &str) -> AppResult<Option<i64>> {
+        if let Some(max_abs) = self.max_abs_funding_rate
+            && InstrumentSpec::dec_str(rate_str)?.abs() > max_abs
+        {
+            self.funding_rejected.fetch_add(1, Ordering::Relaxed);
+            if let Some(m) = self.metrics.as_ref() {
+                m.inc_funding_out_of_range();
+            }
+            tracing::warn!(
+                exchange = self.inst.exchange,
+                symbol,
+                funding_rate = rate_str,
+                max_abs = %max_abs,
+                "implausible funding rate dropped"
+            );
+            return Ok(None);
+        }
+        self.funding_str_to_i64(rate_str).map(Some)
+    }
+
     /// Trade normalization: (price_str, qty_str) -> (price_i, qty_i_base).
     /// Uses instrument semantics for qty unit conversion.
     pub fn trade_to_scaled_i64(&self, price_str: &str, qty_str: &str) -> AppResult<(i64, i64)> {
-        self.inst
-            .trade_to_scaled_i64(price_str, qty_str, self.price_scale, self.qty_scale)
+        self.check_precision("price", price_str, self.inst.price_decimals);
+        let price = InstrumentSpec::dec_str(price_str)?;
+        let qty_base = self.inst.qty_to_base(self.declared_qty(qty_str)?, price)?;
+        Ok((
+            InstrumentSpec::scale_i64(price, self.price_scale)?,
+            InstrumentSpec::scale_i64(qty_base, self.qty_scale)?,
+        ))
     }
 
     /// Convert qty string to BASE Decimal using price string.
     pub fn qty_str_to_base_dec(&self, qty_str: &str, price_str: &str) -> AppResult<Decimal> {
-        self.inst.qty_str_to_base(qty_str, price_str)
+        let price = InstrumentSpec::dec_str(price_str)?;
+        self.inst.qty_to_base(self.declared_qty(qty_str)?, price)
     }
 
     /// Depth normalization helper:
@@ -92,12 +254,116 @@ impl MapCtx {
     /// Most exchanges report size in BASE for order book, but if you ever ingest
     /// a venue that reports quote/contract sizes, this stays correct.
     pub fn book_size_to_base_i64(&self, size_str: &str, price_str: &str) -> AppResult<i64> {
-        let size_base_dec = self.inst.qty_str_to_base(size_str, price_str)?;
+        let size_base_dec = self.qty_str_to_base_dec(size_str, price_str)?;
         InstrumentSpec::scale_i64(size_base_dec, self.qty_scale)
     }
 
     /// Just scale a price string to i64.
     pub fn price_str_to_i64(&self, price_str: &str) -> AppResult<i64> {
+        self.check_precision("price", price_str, self.inst.price_decimals);
         self.scale_str_i64(price_str, self.price_scale)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::config::{ScaleOverride, load_app_config};
+    use crate::ingest::instruments::spec::{InstrumentKind, QtyUnit};
+
+    fn ctx() -> AppResult<MapCtx> {
+        let spec = InstrumentSpec::new(
+            "binance_linear",
+            "BTCUSDT",
+            InstrumentKind::PerpLinear,
+            QtyUnit::Base,
+            Some(1.0),
+            None,
+            None,
+        )?
+        .with_precision(Some(2), Some(3));
+        let reg = Arc::new(InstrumentRegistry::build(vec![spec])?);
+        let appconfig = load_app_config(false, 0)?;
+        MapCtx::new(reg, &appconfig, "binance_linear", "BTCUSDT")
+    }
+
+    #[test]
+    fn over_precise_price_is_counted_but_still_scales() -> AppResult<()> {
+        let ctx = ctx()?;
+
+        // Within the declared precision: no drift
+        let price_i = ctx.price_str_to_i64("87000.12")?;
+        assert_eq!(
+            ctx.trade_to_scaled_i64("87000.1", "0.003")?.0,
+            8_700_010_000_000
+        );
+        assert_eq!(ctx.precision_exceeded_total(), 0);
+
+        // Trailing zeros are padding, not precision
+        let padded = "87000.120000000000000000000000";
+        assert_eq!(InstrumentSpec::fractional_digits(padded), 2);
+        assert_eq!(InstrumentSpec::fractional_digits("0.010"), 2);
+        assert_eq!(ctx.price_str_to_i64(padded)?, price_i);
+        assert_eq!(ctx.precision_exceeded_total(), 0);
+
+        // 3 decimals for a 2-decimal instrument
+        assert_eq!(ctx.price_str_to_i64("87000.123")?, price_i + 300_000);
+        assert_eq!(ctx.precision_exceeded_total(), 1);
+
+        // Trades check both price and qty; clones share the counter
+        let clone = ctx.clone();
+        let (p, q) = clone.trade_to_scaled_i64("87000.1200", "0.0030")?;
+        assert_eq!((p, q), (price_i, 300_000));
+        assert_eq!(ctx.precision_exceeded_total(), 1);
+        clone.trade_to_scaled_i64("87000.125", "0.0035")?;
+        assert_eq!(ctx.precision_exceeded_total(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn exchange_scale_override_applies_to_that_exchange_only() -> AppResult<()> {
+        let spec = |exchange| {
+            InstrumentSpec::new(
+                exchange,
+                "BTC",
+                InstrumentKind::PerpLinear,
+                QtyUnit::Base,
+                Some(1.0),
+                None,
+                None,
+            )
+        };
+        let reg = Arc::new(InstrumentRegistry::build(vec![
+            spec("binance_linear")?,
+            spec("hyperliquid_perp")?,
+        ])?);
+        let mut cfg = load_app_config(false, 0)?;
+        cfg.scales.exchanges.insert(
+            "hyperliquid_perp".into(),
+            ScaleOverride {
+                price: Some(1_000_000),
+                ..Default::default()
+            },
+        );
+
+        let hl = MapCtx::new(reg.clone(), &cfg, "hyperliquid_perp", "BTC")?;
+        assert_eq!(hl.price_scale, 1_000_000);
+        assert_eq!(hl.qty_scale, cfg.scales.qty);
+        assert_eq!(hl.trade_to_scaled_i64("87000.5", "1")?.0, 87_000_500_000);
+
+        let bn = MapCtx::new(reg, &cfg, "binance_linear", "BTC")?;
+        assert_eq!(bn.price_scale, cfg.scales.price);
+        assert_eq!(bn.trade_to_scaled_i64("87000.5", "1")?.0, 8_700_050_000_000);
+
+        Ok(())
+    }
+
+    #[test]
+    fn undeclared_precision_is_unchecked() {
+        assert!(!InstrumentSpec::exceeds_precision("1.123456789", None));
+        assert!(InstrumentSpec::exceeds_precision("1.1234", Some(3)));
+        assert!(!InstrumentSpec::exceeds_precision("1.123", Some(3)));
+        assert!(!InstrumentSpec::exceeds_precision("100", Some(0)));
+    }
+}
@@ -83,6 +83,22 @@ pub struct DepthDeltaRow {
     pub seq: Option<i64>,
 }
 
+/// One level of a full L2 book snapshot (DB stores one row per level).
+///
+/// All levels of one snapshot share `snapshot_id`, so a reader can swap in a whole book at once.
+/// `level_idx` counts from the best price of each side (0 = top of book).
+#[derive(Debug, Clone)]
+pub struct DepthSnapshotRow {
+    pub exchange: &'static str,
+    pub time: DateTime<Utc>,
+    pub symbol: String,
+    pub side: BookSide, // 0=bid, 1=ask via as_i16()
+    pub price_i: i64,   // scaled
+    pub size_i: i64,    // scaled
+    pub level_idx: i32,
+    pub snapshot_id: i64,
+}
+
 #[derive(Debug, Clone)]
 pub struct OpenInterestRow {
     pub exchange: &'static str,
@@ -153,4 +169,16 @@ impl MarketEvent {
             MarketEvent::Liquidation(x) => x.time,
         }
     }
+
+    /// Overwrite the event time (venue time read through a configured `time_field`).
+    #[inline]
+    pub fn set_time(&mut self, time: DateTime<Utc>) {
+        match self {
+            MarketEvent::Trade(x) => x.time = time,
+            MarketEvent::DepthDelta(x) => x.time = time,
+            MarketEvent::OpenInterest(x) => x.time = time,
+            MarketEvent::Funding(x) => x.time = time,
+            MarketEvent::Liquidation(x) => x.time = time,
+        }
+    }
 }
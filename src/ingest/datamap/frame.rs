@@ -0,0 +1,295 @@
+//! ingest/datamap/frame.rs
+//!
+//! One WS text frame -> N normalized events.
+//!
+//! A frame may carry several events in two ways, and both must reach the sinks in full:
+//! - inside one message (Hyperliquid `{"channel":"trades","data":[t1, t2, ...]}`): the
+//!   message's `map_to_events` already yields one event per entry;
+//! - as a top-level JSON array of messages (`[{...}, {...}]`): handled here according to
+//!   `ws_array_frames` of the exchange config.
+//!
+//! Decode errors quote the offending payload, redacted (see `telemetry::redact`).
+//!
+//! With a `[ws.*] time_field`, the events of each message are stamped with the venue time read
+//! from that message (`apply_venue_time`), as the REST path does with `[api.*] time_field`.
+
+use crate::error::{AppError, AppResult};
+use crate::ingest::datamap::ctx::MapCtx;
+use crate::ingest::datamap::event::{MapEnvelope, MarketEvent};
+use crate::ingest::datamap::traits::MapToEvents;
+use crate::ingest::datamap::venue_time::VenueTimeField;
+use crate::telemetry::redact::payload_redactor;
+use crate::telemetry::throttle::log_throttle;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+/// What to do with a WS frame whose top level is a JSON array.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum WsArrayFrames {
+    /// Every element is handled as a message of its own.
+    #[default]
+    Split,
+    /// The frame is ignored (logged at debug).
+    Drop,
+}
+
+/// Messages carried by frame `v` (an object is one message).
+pub fn frame_messages(v: Value, array_frames: WsArrayFrames) -> Vec<Value> {
+    match v {
+        Value::Array(items) => match array_frames {
+            WsArrayFrames::Split => items,
+            WsArrayFrames::Drop => {
+                tracing::debug!(messages = items.len(), "array WS frame dropped");
+                Vec::new()
+            }
+        },
+        Value::Object(_) => vec![v],
+        _ => Vec::new(),
+    }
+}
+
+/// Map every message of frame `v` to events, in frame order.
+///
+/// `select` returns the payload to decode as `T`, or None to skip the message (acks, other
+/// channels). `what` names the payload in decode errors. `time_field` (`[ws.*] time_field`)
+/// points into each selected payload.
+pub fn map_frame<T>(
+    v: Value,
+    array_frames: WsArrayFrames,
+    what: &str,
+    mut select: impl FnMut(Value) -> Option<Value>,
+    ctx: &MapCtx,
+    env: Option<&MapEnvelope>,
+    time_field: Option<&VenueTimeField>,
+) -> AppResult<Vec<MarketEvent>>
+where
+    T: DeserializeOwned + MapToEvents,
+{
+    let mut out = Vec::new();
+    for payload in frame_messages(v, array_frames)
+        .into_iter()
+        .filter_map(&mut select)
+    {
+        let item: T = decode_payload(&payload, what)?;
+        let mut events = item.map_to_events(ctx, env.cloned())?;
+        apply_venue_time(time_field, &payload, &mut events, what);
+        out.extend(events);
+    }
+    Ok(out)
+}
+
+/// Stamp `events`, mapped from message `payload`, with the venue time read through the
+/// stream's `time_field` (pointer into `payload`). None, or a field the message lacks
+/// (warned, throttled), keeps the mapped time.
+pub fn apply_venue_time(
+    time_field: Option<&VenueTimeField>,
+    payload: &Value,
+    events: &mut [MarketEvent],
+    what: &str,
+) {
+    let Some(field) = time_field else {
+        return;
+    };
+    match field.extract(payload) {
+        Ok(t) => events.iter_mut().for_each(|e| e.set_time(t)),
+        Err(e) => {
+            if let Some(suppressed) = log_throttle().allow(&format!("venue time missing:{what}")) {
+                tracing::warn!(
+                    what,
+                    suppressed,
+                    error = %e,
+                    "venue time field unreadable; keeping the mapped time"
+                );
+            }
+        }
+    }
+}
+
+/// Decode one message payload as `T`; the error quotes the payload, redacted.
+pub fn decode_payload<T: DeserializeOwned>(payload: &Value, what: &str) -> AppResult<T> {
+    T::deserialize(payload).map_err(|e| {
+        AppError::Internal(format!(
+            "{what} deserialize error: {e}; payload: {}",
+            payload_redactor().preview(payload)
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::config::load_app_config;
+    use crate::app::control::batch::make_empty_batch;
+    use crate::app::stream_types::{ExchangeId, StreamKind, StreamTransport};
+    use crate::db::WriterConfig;
+    use crate::db::rows::TradeDBRow;
+    use crate::ingest::datamap::sources::hyperliquid_perp::types::HyperliquidPerpWsTrade;
+    use crate::ingest::datamap::venue_time::TimeUnit;
+    use crate::ingest::instruments::registry::InstrumentRegistry;
+    use crate::ingest::instruments::spec::{InstrumentKind, InstrumentSpec, QtyUnit};
+    use serde_json::json;
+    use std::sync::Arc;
+
+    fn trade(tid: u64, px: &str) -> Value {
+        json!({
+            "coin": "BTC", "side": "B", "px": px, "sz": "0.5",
+            "time": 1765807278093u64, "hash": "0x0", "tid": tid, "users": ["0xa", "0xb"]
+        })
+    }
+
+    fn trades(v: Value) -> Option<Value> {
+        (v.get("channel").and_then(|c| c.as_str()) == Some("trades")).then_some(v)
+    }
+
+    #[test]
+    fn every_trade_of_a_batched_frame_is_emitted_and_batched() -> AppResult<()> {
+        let spec = InstrumentSpec::new(
+            ExchangeId::HyperliquidPerp.as_str(),
+            "BTC",
+            InstrumentKind::PerpLinear,
+            QtyUnit::Base,
+            Some(1.0),
+            None,
+            None,
+        )?;
+        let registry = Arc::new(InstrumentRegistry::build(vec![spec])?);
+        let ctx = MapCtx::new(
+            registry,
+            &load_app_config(false, 0)?,
+            ExchangeId::HyperliquidPerp.as_str(),
+            "BTC",
+        )?;
+
+        // One message carrying three trades
+        let frame = json!({
+            "channel": "trades",
+            "data": [trade(1, "100.0"), trade(2, "100.5"), trade(3, "101.0")]
+        });
+        let events = map_frame::<HyperliquidPerpWsTrade>(
+            frame.clone(),
+            WsArrayFrames::Split,
+            "ws trades",
+            trades,
+            &ctx,
+            None,
+            None,
+        )?;
+        let ids: Vec<_> = events
+            .iter()
+            .map(|e| match e {
+                MarketEvent::Trade(t) => t.trade_id,
+                other => panic!("expected trade, got {other:?}"),
+            })
+            .collect();
+        assert_eq!(ids, vec![Some(1), Some(2), Some(3)]);
+
+        let mut batch = make_empty_batch::<TradeDBRow>(
+            ExchangeId::HyperliquidPerp,
+            StreamTransport::Ws,
+            StreamKind::Trades,
+            "BTC",
+            WriterConfig::default(),
+        )?;
+        batch.rows.extend(events.iter().filter_map(|e| match e {
+            MarketEvent::Trade(t) => Some(TradeDBRow::from(t.clone())),
+            _ => None,
+        }));
+        assert_eq!(batch.rows.len(), 3);
+
+        // A top-level array of messages (with an ack in between) is split, not dropped
+        let array = json!([frame, {"channel": "subscriptionResponse", "data": {}}, {
+            "channel": "trades", "data": [trade(4, "99.0")]
+        }]);
+        let split = map_frame::<HyperliquidPerpWsTrade>(
+            array.clone(),
+            WsArrayFrames::Split,
+            "ws trades",
+            trades,
+            &ctx,
+            None,
+            None,
+        )?;
+        assert_eq!(split.len(), 4);
+        let dropped = map_frame::<HyperliquidPerpWsTrade>(
+            array,
+            WsArrayFrames::Drop,
+            "ws trades",
+            trades,
+            &ctx,
+            None,
+            None,
+        )?;
+        assert!(dropped.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn ws_time_field_stamps_each_message_with_its_own_venue_time() -> AppResult<()> {
+        let spec = InstrumentSpec::new(
+            ExchangeId::HyperliquidPerp.as_str(),
+            "BTC",
+            InstrumentKind::PerpLinear,
+            QtyUnit::Base,
+            Some(1.0),
+            None,
+            None,
+        )?;
+        let registry = Arc::new(InstrumentRegistry::build(vec![spec])?);
+        let ctx = MapCtx::new(
+            registry,
+            &load_app_config(false, 0)?,
+            ExchangeId::HyperliquidPerp.as_str(),
+            "BTC",
+        )?;
+        let field = VenueTimeField::new("/ts", TimeUnit::Ms);
+
+        // Two messages (one without the field) of two and one trades
+        let frame = json!([
+            {"channel": "trades", "ts": 1765807300000u64, "data": [trade(1, "100.0"), trade(2, "100.5")]},
+            {"channel": "trades", "data": [trade(3, "101.0")]}
+        ]);
+        let events = map_frame::<HyperliquidPerpWsTrade>(
+            frame,
+            WsArrayFrames::Split,
+            "ws trades",
+            trades,
+            &ctx,
+            None,
+            Some(&field),
+        )?;
+        let times: Vec<i64> = events.iter().map(|e| e.time().timestamp_millis()).collect();
+        assert_eq!(times, vec![1765807300000, 1765807300000, 1765807278093]);
+        Ok(())
+    }
+
+    #[test]
+    fn decode_errors_quote_the_payload_redacted() {
+        crate::telemetry::redact::set_payload_redaction(
+            &["users".to_string()],
+            crate::telemetry::redact::DEFAULT_REDACT_PLACEHOLDER,
+        );
+
+        // A malformed trade (`sz` is not a string): the message is quoted in the error
+        let mut bad = trade(7, "100.0");
+        bad["users"] = json!(["0xaccount1", "0xaccount2"]);
+        bad["sz"] = json!({"oops": true});
+        let msg = json!({"channel": "trades", "data": [bad]});
+        let err = decode_payload::<HyperliquidPerpWsTrade>(&msg, "ws trades")
+            .map(|_| ())
+            .unwrap_err()
+            .to_string();
+
+        assert!(err.contains("ws trades deserialize error"), "{err}");
+        assert!(err.contains("\"tid\":7"), "{err}");
+        assert!(err.contains("[REDACTED]"), "{err}");
+        assert!(!err.contains("0xaccount1"), "{err}");
+
+        crate::telemetry::redact::set_payload_redaction(
+            &[],
+            crate::telemetry::redact::DEFAULT_REDACT_PLACEHOLDER,
+        );
+    }
+}
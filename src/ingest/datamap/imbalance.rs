@@ -0,0 +1,188 @@
+//! ingest/datamap/imbalance.rs
+//!
+//! Trade imbalance (signed volume) per window, derived from the trade stream.
+//!
+//! `TradeImbalance` sums the scaled buy and sell quantities of ONE symbol over windows of
+//! `writer.trade_imbalance_window_ms` (0 = off), aligned to the epoch: a 1s window covers
+//! `[12:00:00.000, 12:00:01.000)`. Downstream gets signed volume without re-reading trades.
+//!
+//! - Windows follow trade event time. A window is emitted (`TradeImbalanceRow`, time = window
+//!   start) by the first trade past its end, or by `close_due` (stream timer) once the clock
+//!   is past its end, so a quiet symbol still emits; windows without trades emit nothing.
+//! - A trade older than the open window (out of order across the boundary) is counted in the
+//!   open window, or in the window after the last emitted one: an emitted window is never
+//!   reopened.
+//! - `flush` emits the open window as it is, when the stream stops.
+
+use crate::db::config::WriterConfig;
+use crate::db::rows::TradeImbalanceRow;
+use crate::ingest::datamap::event::{TradeRow, TradeSide};
+use chrono::{DateTime, Utc};
+
+#[derive(Debug)]
+pub struct TradeImbalance {
+    symbol: String,
+    window_ms: i64,
+    open: Option<TradeImbalanceRow>,
+    // end of the last emitted window
+    closed_until: Option<DateTime<Utc>>,
+}
+
+impl TradeImbalance {
+    /// `window_ms` of 0 aggregates nothing.
+    pub fn new(symbol: impl Into<String>, window_ms: u64) -> Self {
+        Self {
+            symbol: symbol.into(),
+            window_ms: i64::try_from(window_ms).unwrap_or(i64::MAX),
+            open: None,
+            closed_until: None,
+        }
+    }
+
+    pub fn from_config(symbol: impl Into<String>, writer: &WriterConfig) -> Self {
+        Self::new(symbol, writer.trade_imbalance_window_ms)
+    }
+
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.window_ms > 0
+    }
+
+    fn window_start(&self, time: DateTime<Utc>) -> DateTime<Utc> {
+        let ms = time.timestamp_millis();
+        DateTime::from_timestamp_millis(ms - ms.rem_euclid(self.window_ms)).unwrap_or(time)
+    }
+
+    fn window_end(&self, start: DateTime<Utc>) -> DateTime<Utc> {
+        start + chrono::Duration::milliseconds(self.window_ms)
+    }
+
+    /// Count one trade; returns the previous window if this trade closed it.
+    pub fn push(&mut self, trade: &TradeRow) -> Option<TradeImbalanceRow> {
+        if !self.is_enabled() {
+            return None;
+        }
+        let start = self
+            .window_start(trade.time)
+            .max(self.closed_until.unwrap_or_default());
+        let closed = match &self.open {
+            Some(open) if start > open.time => self.take_open(),
+            _ => None,
+        };
+
+        let open = self.open.get_or_insert_with(|| TradeImbalanceRow {
+            time: start,
+            symbol: self.symbol.clone(),
+            buy_vol_i: 0,
+            sell_vol_i: 0,
+            count: 0,
+        });
+        match trade.side {
+            TradeSide::Buy => open.buy_vol_i = open.buy_vol_i.saturating_add(trade.qty_i),
+            TradeSide::Sell => open.sell_vol_i = open.sell_vol_i.saturating_add(trade.qty_i),
+        }
+        open.count += 1;
+        closed
+    }
+
+    /// The open window, once `now` is past its end (stream timer).
+    pub fn close_due(&mut self, now: DateTime<Utc>) -> Option<TradeImbalanceRow> {
+        let open = self.open.as_ref()?;
+        if now < self.window_end(open.time) {
+            return None;
+        }
+        self.take_open()
+    }
+
+    /// The open (partial) window, if any trade went into it.
+    pub fn flush(&mut self) -> Option<TradeImbalanceRow> {
+        self.take_open()
+    }
+
+    fn take_open(&mut self) -> Option<TradeImbalanceRow> {
+        let row = self.open.take()?;
+        self.closed_until = Some(self.window_end(row.time));
+        Some(row)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn trade(ms: i64, side: TradeSide, qty_i: i64) -> TradeRow {
+        TradeRow {
+            exchange: "binance_linear",
+            time: Utc.timestamp_millis_opt(ms).unwrap(),
+            symbol: "BTCUSDT".into(),
+            side,
+            price_i: 1_000_000,
+            qty_i,
+            trade_id: None,
+            is_maker: None,
+        }
+    }
+
+    #[test]
+    fn sums_buys_and_sells_per_window_and_flushes_the_partial_one() {
+        use TradeSide::{Buy, Sell};
+        let mut agg = TradeImbalance::new("BTCUSDT", 1_000);
+
+        // Window [1000, 2000): 2 buys, 1 sell (one out of order, same window)
+        assert!(agg.push(&trade(1_200, Buy, 5)).is_none());
+        assert!(agg.push(&trade(1_900, Sell, 3)).is_none());
+        assert!(agg.push(&trade(1_100, Buy, 7)).is_none());
+
+        // First trade of [3000, 4000) closes it; the empty [2000, 3000) emits nothing
+        let row = agg.push(&trade(3_000, Sell, 4)).expect("window closed");
+        assert_eq!(row.time, Utc.timestamp_millis_opt(1_000).unwrap());
+        assert_eq!(row.symbol, "BTCUSDT");
+        assert_eq!((row.buy_vol_i, row.sell_vol_i, row.count), (12, 3, 3));
+
+        // Late trade from a closed window lands in the open one
+        assert!(agg.push(&trade(1_950, Buy, 1)).is_none());
+
+        let partial = agg.flush().expect("partial window");
+        assert_eq!(partial.time, Utc.timestamp_millis_opt(3_000).unwrap());
+        assert_eq!(
+            (partial.buy_vol_i, partial.sell_vol_i, partial.count),
+            (1, 4, 2)
+        );
+        assert!(agg.flush().is_none());
+
+        // Off
+        let mut off = TradeImbalance::new("BTCUSDT", 0);
+        assert!(
+            off.close_due(Utc.timestamp_millis_opt(9_000).unwrap())
+                .is_none()
+        );
+        assert!(off.push(&trade(1_000, Buy, 1)).is_none());
+        assert!(off.flush().is_none());
+    }
+
+    #[test]
+    fn quiet_symbol_window_is_closed_by_the_clock() {
+        use TradeSide::{Buy, Sell};
+        let at = |ms| Utc.timestamp_millis_opt(ms).unwrap();
+        let mut agg = TradeImbalance::new("BTCUSDT", 1_000);
+
+        assert!(agg.push(&trade(1_200, Buy, 5)).is_none());
+        assert!(agg.push(&trade(1_300, Sell, 2)).is_none());
+
+        // No further trade: the timer closes [1000, 2000) once the clock is past its end
+        assert!(agg.close_due(at(1_999)).is_none());
+        let row = agg
+            .close_due(at(2_000))
+            .expect("window closed by the clock");
+        assert_eq!(row.time, at(1_000));
+        assert_eq!((row.buy_vol_i, row.sell_vol_i, row.count), (5, 2, 2));
+        assert!(agg.close_due(at(5_000)).is_none());
+
+        // A late trade of the emitted window opens the next one, never [1000, 2000) again
+        assert!(agg.push(&trade(1_900, Buy, 1)).is_none());
+        let row = agg.flush().expect("partial window");
+        assert_eq!(row.time, at(2_000));
+        assert_eq!(row.count, 1);
+    }
+}
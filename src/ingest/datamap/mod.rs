@@ -1,9 +1,25 @@
+pub mod book;
+pub mod change_only;
+pub mod coalesce;
 pub mod ctx;
 pub mod event;
+pub mod frame;
+pub mod imbalance;
+pub mod naming;
 pub mod sources;
+pub mod trade_variant;
 pub mod traits;
+pub mod venue_time;
 
+pub use book::*;
+pub use change_only::*;
+pub use coalesce::*;
 pub use ctx::*;
 pub use event::*;
+pub use frame::*;
+pub use imbalance::*;
+pub use naming::*;
 pub use sources::*;
+pub use trade_variant::*;
 pub use traits::*;
+pub use venue_time::*;
@@ -0,0 +1,215 @@
+//! ingest/datamap/naming.rs
+//!
+//! Exchange/symbol tokens shared by the Redis and DB sinks.
+//!
+//! Redis stream keys (`{exchange}:{symbol}:{kind}`) and DB tables (`ex_{exchange}.{table}` plus
+//! the `symbol` column) must spell an instrument the same way, or a consumer correlating both
+//! sees `BTCUSDT` on one side and `btcusdt` on the other.
+//!
+//! - `exchange_token` / `symbol_token` are the only normalization either sink applies; symbol
+//!   casing comes from the exchange's `symbol_case` on both sides.
+//! - `check_sink_naming` runs at startup (`streams.naming_check` in redis.toml) and reports
+//!   exchanges whose Redis token is no usable DB schema name (`ex_<exchange>`).
+//! - `check_exchange_names` runs at startup (`exchange_toggles.name_check` in app.toml) and
+//!   reports exchange clients not named after an `ExchangeId` (see `ExchangeId::as_str`).
+
+use crate::app::config::CheckMode;
+use crate::app::stream_types::ExchangeId;
+use crate::error::{AppError, AppResult};
+use crate::ingest::config::SymbolCase;
+
+/// Exchange token for Redis keys and DB schemas.
+/// Unquoted Postgres identifiers fold to lowercase, so both sinks use the lowercase form.
+#[inline]
+pub fn exchange_token(exchange: &str) -> String {
+    exchange.trim().to_ascii_lowercase()
+}
+
+/// Symbol token for Redis keys and the DB `symbol` column.
+#[inline]
+pub fn symbol_token(case: SymbolCase, symbol: &str) -> String {
+    case.apply(symbol)
+}
+
+/// Fully qualified DB table (`ex_<exchange>.<table>`).
+#[inline]
+pub fn db_table(exchange: &str, table: &str) -> String {
+    format!("ex_{}.{}", exchange_token(exchange), table)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamingMismatch {
+    pub exchange: String,
+    pub redis: String,
+    pub db: String,
+    pub reason: &'static str,
+}
+
+/// Exchanges whose Redis key token is not a plain SQL identifier: the DB schema
+/// (`ex_<exchange>`) would have to be quoted, so the tables could not carry the same token.
+pub fn check_sink_naming<'a>(exchanges: impl IntoIterator<Item = &'a str>) -> Vec<NamingMismatch> {
+    exchanges
+        .into_iter()
+        .filter_map(|exchange| {
+            let token = exchange_token(exchange);
+            let plain = !token.is_empty()
+                && token
+                    .bytes()
+                    .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_');
+            (!plain).then(|| NamingMismatch {
+                exchange: exchange.to_string(),
+                redis: token,
+                db: db_table(exchange, "<table>"),
+                reason: "exchange is not a plain SQL identifier",
+            })
+        })
+        .collect()
+}
+
+/// Client names (WS/HTTP) that are not the canonical name of any `ExchangeId`.
+///
+/// Instruments, rows and Redis keys are all keyed by `ExchangeId::as_str`; a client under any
+/// other name would label its data with a token no other subsystem knows.
+pub fn check_exchange_names<'a>(names: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    names
+        .into_iter()
+        .filter(|name| ExchangeId::from_name(name).is_none())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Log or reject unknown exchange client names according to `check`.
+pub fn apply_exchange_name_check(check: CheckMode, unknown: &[String]) -> AppResult<()> {
+    if unknown.is_empty() || check == CheckMode::Off {
+        return Ok(());
+    }
+    for name in unknown {
+        tracing::warn!(client = %name, "exchange client is not named after an ExchangeId");
+    }
+    if check == CheckMode::Warn {
+        return Ok(());
+    }
+
+    let known: Vec<&str> = ExchangeId::ALL.iter().map(|ex| ex.as_str()).collect();
+    Err(AppError::InvalidConfig(format!(
+        "exchange clients not named after an ExchangeId: {} (known: {})",
+        unknown.join(", "),
+        known.join(", ")
+    )))
+}
+
+/// Log or reject naming mismatches according to `check`.
+pub fn apply_naming_check(check: CheckMode, mismatches: &[NamingMismatch]) -> AppResult<()> {
+    if mismatches.is_empty() || check == CheckMode::Off {
+        return Ok(());
+    }
+    for m in mismatches {
+        tracing::warn!(
+            exchange = %m.exchange,
+            redis = %m.redis,
+            db = %m.db,
+            reason = m.reason,
+            "Redis and DB naming disagree"
+        );
+    }
+    if check == CheckMode::Warn {
+        return Ok(());
+    }
+
+    let lines: Vec<String> = mismatches
+        .iter()
+        .map(|m| {
+            format!(
+                "├─ {}: {} (redis `{}`, db `{}`)",
+                m.exchange, m.reason, m.redis, m.db
+            )
+        })
+        .collect();
+    Err(AppError::InvalidConfig(format!(
+        "\n❌ REDIS/DB NAMING MISMATCH\n\
+         {}\n\
+         └─ fix: rename the exchange, or set streams.naming_check = \"warn\"\n",
+        lines.join("\n")
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::rows::TradeDBRow;
+    use crate::db::traits::BatchInsertRow;
+    use crate::redis::config::RedisConfig;
+    use crate::redis::streams::{StreamKeyBuilder, StreamKind};
+
+    #[test]
+    fn redis_keys_and_db_tables_share_tokens() {
+        let cfg = RedisConfig::load_default().unwrap();
+        let cases = [
+            ("binance_linear", SymbolCase::Upper),
+            ("okx", SymbolCase::Lower),
+            ("hyperliquid_perp", SymbolCase::Preserve),
+        ];
+        let keys = cases.iter().fold(
+            StreamKeyBuilder::from_config(&cfg).unwrap(),
+            |k, (ex, c)| k.with_symbol_case(*ex, *c),
+        );
+        assert!(check_sink_naming(cases.map(|(ex, _)| ex)).is_empty());
+
+        for (exchange, case) in cases {
+            let row = TradeDBRow {
+                time: chrono::Utc::now(),
+                symbol: symbol_token(case, "kPepe"),
+                side: 0,
+                price_i: 1,
+                qty_i: 1,
+                trade_id: None,
+                is_maker: None,
+            };
+            let key = keys.key(exchange, "kPepe", StreamKind::Trades);
+            assert_eq!(key, format!("stream:{exchange}:{}:trades", row.symbol));
+            assert_eq!(row.table(exchange), format!("ex_{exchange}.trades"));
+        }
+
+        // A mixed-case exchange id lands on the same token in both sinks
+        assert_eq!(
+            keys.key("Binance_Linear", "btcusdt", StreamKind::Depth),
+            "stream:binance_linear:btcusdt:depth"
+        );
+        assert_eq!(
+            db_table("Binance_Linear", "depth"),
+            "ex_binance_linear.depth"
+        );
+    }
+
+    #[test]
+    fn exchange_without_a_plain_schema_name_is_reported_and_enforced() {
+        let found = check_sink_naming(["binance_linear", "bad-ex", " "]);
+        assert_eq!(found.len(), 2);
+        assert_eq!(
+            (found[0].redis.as_str(), found[0].db.as_str()),
+            ("bad-ex", "ex_bad-ex.<table>")
+        );
+        assert_eq!(found[1].redis, "");
+
+        assert!(apply_naming_check(CheckMode::Off, &found).is_ok());
+        assert!(apply_naming_check(CheckMode::Warn, &found).is_ok());
+        assert!(matches!(
+            apply_naming_check(CheckMode::Error, &found),
+            Err(AppError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn client_names_must_be_exchange_ids() {
+        let names = ExchangeId::ALL.iter().map(|ex| ex.as_str());
+        assert!(check_exchange_names(names).is_empty());
+
+        let unknown = check_exchange_names(["binance_linear", "binance_exchange_info", "okx"]);
+        assert_eq!(unknown, vec!["binance_exchange_info", "okx"]);
+        assert!(apply_exchange_name_check(CheckMode::Warn, &unknown).is_ok());
+        assert!(matches!(
+            apply_exchange_name_check(CheckMode::Error, &unknown),
+            Err(AppError::InvalidConfig(_))
+        ));
+    }
+}
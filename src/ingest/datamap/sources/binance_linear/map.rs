@@ -6,22 +6,21 @@ use crate::ingest::datamap::event::{
     BookSide, DepthDeltaRow, FundingRow, LiquidationRow, MarketEvent, OpenInterestRow, TradeRow,
     TradeSide,
 };
+use crate::ingest::datamap::venue_time::ms_to_utc;
 use crate::ingest::traits::MapToEvents;
 
 use crate::ingest::datamap::sources::binance_linear::types::{
     BinanceLinearDepthSnapshot, BinanceLinearFundingRateSnapshot,
     BinanceLinearOpenInterestSnapshot, BinanceLinearWsAggTrade, BinanceLinearWsDepthUpdate,
-    BinanceLinearWsForceOrder, PriceLevel,
+    BinanceLinearWsForceOrder, BinanceLinearWsMarkPriceUpdate, PriceLevel,
 };
 
 use crate::ingest::datamap::event::MapEnvelope;
 
 const EXCHANGE: &str = "binance_linear";
 
-fn ms_to_utc(ms: u64) -> AppResult<DateTime<Utc>> {
-    DateTime::<Utc>::from_timestamp_millis(ms as i64)
-        .ok_or_else(|| AppError::Internal(format!("invalid ms timestamp: {ms}")))
-}
+/// Binance USDT-M perps settle funding every 8h (00:00 / 08:00 / 16:00 UTC).
+const FUNDING_INTERVAL_MS: u64 = 8 * 60 * 60 * 1000;
 
 fn map_levels_to_depth_events(
     ctx: &MapCtx,
@@ -33,6 +32,7 @@ fn map_levels_to_depth_events(
     is_snapshot: bool,
 ) -> AppResult<Vec<MarketEvent>> {
     let mut out = Vec::with_capacity(levels.len());
+    let symbol = ctx.canonical_symbol(symbol);
 
     for lvl in levels {
         // lvl = [price_str, qty_str]
@@ -50,7 +50,7 @@ fn map_levels_to_depth_events(
         out.push(MarketEvent::DepthDelta(DepthDeltaRow {
             exchange: EXCHANGE,
             time,
-            symbol: symbol.to_string(),
+            symbol: symbol.clone(),
             side,
             price_i,
             size_i,
@@ -82,7 +82,7 @@ impl MapToEvents for BinanceLinearWsAggTrade {
         let row = TradeRow {
             exchange: EXCHANGE,
             time: ms_to_utc(self.trade_time_ms)?,
-            symbol: self.symbol,
+            symbol: ctx.canonical_symbol(&self.symbol),
             side,
             price_i,
             qty_i,
@@ -223,7 +223,7 @@ impl MapToEvents for BinanceLinearOpenInterestSnapshot {
         Ok(vec![MarketEvent::OpenInterest(OpenInterestRow {
             exchange: EXCHANGE,
             time,
-            symbol: self.symbol,
+            symbol: ctx.canonical_symbol(&self.symbol),
             oi_i,
         })])
     }
@@ -241,11 +241,15 @@ impl MapToEvents for BinanceLinearFundingRateSnapshot {
             .parse::<f64>()
             .map_err(|e| AppError::Internal(format!("funding_rate parse failed: {e}")))?;
 
+        let Some(funding_rate) = ctx.funding_rate_i64(&self.symbol, &self.funding_rate)? else {
+            return Ok(Vec::new());
+        };
+
         Ok(vec![MarketEvent::Funding(FundingRow {
             exchange: EXCHANGE,
             time: ctx.now,
-            symbol: self.symbol,
-            funding_rate: ctx.funding_str_to_i64(&self.funding_rate)?,
+            symbol: ctx.canonical_symbol(&self.symbol),
+            funding_rate,
             funding_time: Some(funding_time),
         })])
     }
@@ -261,6 +265,53 @@ impl MapToEvents for Vec<BinanceLinearFundingRateSnapshot> {
     }
 }
 
+//
+// -------------------- WS: Mark Price -> MarketEvent::Funding --------------------
+//
+// `r` is signed and kept as-is (negative = shorts pay longs).
+// `funding_time` is the NEXT settlement: `T` when Binance provides it, otherwise the next
+// 8h boundary after `E`. Contracts without funding (`r` == "") and implausible rates
+// (beyond `max_abs_funding_rate_pct`) map to no events.
+//
+impl BinanceLinearWsMarkPriceUpdate {
+    pub fn next_funding_time(&self) -> AppResult<DateTime<Utc>> {
+        let ms = if self.next_funding_time_ms > 0 {
+            self.next_funding_time_ms
+        } else {
+            (self.event_time_ms / FUNDING_INTERVAL_MS + 1) * FUNDING_INTERVAL_MS
+        };
+        ms_to_utc(ms)
+    }
+
+    pub fn to_funding_row(&self, ctx: &MapCtx) -> AppResult<Option<FundingRow>> {
+        let rate = self.funding_rate.trim();
+        if rate.is_empty() {
+            return Ok(None);
+        }
+        let Some(funding_rate) = ctx.funding_rate_i64(&self.symbol, rate)? else {
+            return Ok(None);
+        };
+
+        Ok(Some(FundingRow {
+            exchange: EXCHANGE,
+            time: ms_to_utc(self.event_time_ms)?,
+            symbol: ctx.canonical_symbol(&self.symbol),
+            funding_rate,
+            funding_time: Some(self.next_funding_time()?),
+        }))
+    }
+}
+
+impl MapToEvents for BinanceLinearWsMarkPriceUpdate {
+    fn map_to_events(self, ctx: &MapCtx, _env: Option<MapEnvelope>) -> AppResult<Vec<MarketEvent>> {
+        Ok(self
+            .to_funding_row(ctx)?
+            .map(MarketEvent::Funding)
+            .into_iter()
+            .collect())
+    }
+}
+
 //
 // -------------------- WS: Liquidation (forceOrder) -> MarketEvent::Liquidation --------------------
 //
@@ -310,7 +361,7 @@ impl MapToEvents for BinanceLinearWsForceOrder {
         Ok(vec![MarketEvent::Liquidation(LiquidationRow {
             exchange: EXCHANGE,
             time,
-            symbol: o.symbol,
+            symbol: ctx.canonical_symbol(&o.symbol),
             side: side_i16,
             price_i,
             qty_i,
@@ -342,8 +393,9 @@ mod tests {
     use crate::ingest::datamap::sources::binance_linear::types::{
         BinanceLinearDepthSnapshot, BinanceLinearFundingRateSnapshot,
         BinanceLinearOpenInterestSnapshot, BinanceLinearWsAggTrade, BinanceLinearWsDepthUpdate,
-        BinanceLinearWsForceOrder,
+        BinanceLinearWsForceOrder, BinanceLinearWsMarkPriceUpdate,
     };
+    use crate::ingest::instruments::spec::{InstrumentKind, InstrumentSpec, QtyUnit};
 
     fn testdata_path(file_name: &str) -> PathBuf {
         PathBuf::from(env!("CARGO_MANIFEST_DIR"))
@@ -494,4 +546,130 @@ mod tests {
         println!("\n=== ALL Binance testdata mapped successfully ===");
         Ok(())
     }
+
+    /// Offline ctx: a single hand-built BTCUSDT spec instead of the live exchangeInfo.
+    fn mk_offline_ctx() -> AppResult<MapCtx> {
+        let spec = InstrumentSpec::new(
+            "binance_linear",
+            "BTCUSDT",
+            InstrumentKind::PerpLinear,
+            QtyUnit::Base,
+            None,
+            None,
+            None,
+        )?;
+        let reg = Arc::new(InstrumentRegistry::build(vec![spec])?);
+        let appconfig = load_app_config(false, 0)?;
+        MapCtx::new(reg, &appconfig, "binance_linear", "BTCUSDT")
+    }
+
+    #[test]
+    fn binance_mark_price_maps_to_funding_row() -> AppResult<()> {
+        let ctx = mk_offline_ctx()?;
+
+        let mp =
+            load_and_parse::<BinanceLinearWsMarkPriceUpdate>("BinanceLinearWsMarkPriceUpdate")?;
+        let events = mp.clone().map_to_events(&ctx, None)?;
+        assert_eq!(events.len(), 1, "markPriceUpdate should map to 1 event");
+
+        let MarketEvent::Funding(row) = &events[0] else {
+            panic!("expected Funding event, got {:?}", events[0]);
+        };
+        assert_eq!(row.symbol, "BTCUSDT");
+        assert_eq!(row.time, ms_to_utc(1562305380000)?);
+        assert_eq!(row.funding_time, Some(ms_to_utc(1562306400000)?));
+        // sign preserved: -0.00038167 at 1e12
+        assert_eq!(row.funding_rate, -381_670_000);
+
+        // T == 0 -> next 8h boundary after E (2019-07-05 05:43 UTC -> 08:00 UTC)
+        let mut no_t = mp.clone();
+        no_t.next_funding_time_ms = 0;
+        assert_eq!(no_t.next_funding_time()?, ms_to_utc(1562313600000)?);
+
+        // no funding on this contract -> no events
+        let mut no_rate = mp;
+        no_rate.funding_rate = String::new();
+        assert!(no_rate.map_to_events(&ctx, None)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn binance_absurd_funding_rate_is_rejected() -> AppResult<()> {
+        let ctx = mk_offline_ctx()?.with_max_abs_funding_rate_pct(Some(5.0));
+        let mp =
+            load_and_parse::<BinanceLinearWsMarkPriceUpdate>("BinanceLinearWsMarkPriceUpdate")?;
+
+        // Normal rate (-0.038%) passes
+        assert_eq!(mp.clone().map_to_events(&ctx, None)?.len(), 1);
+        assert_eq!(ctx.funding_rejected_total(), 0);
+
+        // 100x-normal glitch (-38.167%) and a +50% rate are dropped and counted
+        for absurd in ["-0.38167", "0.5"] {
+            let mut glitch = mp.clone();
+            glitch.funding_rate = absurd.into();
+            assert!(glitch.map_to_events(&ctx, None)?.is_empty());
+        }
+        assert_eq!(ctx.funding_rejected_total(), 2);
+
+        // Exactly at the bound is still plausible
+        let mut edge = mp;
+        edge.funding_rate = "-0.05".into();
+        assert!(edge.to_funding_row(&ctx)?.is_some());
+        assert_eq!(ctx.funding_rejected_total(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn binance_symbol_case_applies_to_lookup_and_rows() -> AppResult<()> {
+        use crate::ingest::config::SymbolCase;
+
+        let spec = InstrumentSpec::new(
+            "binance_linear",
+            "BTCUSDT",
+            InstrumentKind::PerpLinear,
+            QtyUnit::Base,
+            None,
+            None,
+            None,
+        )?;
+        let reg = Arc::new(InstrumentRegistry::build(vec![spec])?);
+        let appconfig = load_app_config(false, 0)?;
+        let mp =
+            load_and_parse::<BinanceLinearWsMarkPriceUpdate>("BinanceLinearWsMarkPriceUpdate")?;
+
+        // upper: lowercase request + lowercase payload both land on BTCUSDT
+        let ctx = MapCtx::new_with_symbol_case(
+            Arc::clone(&reg),
+            &appconfig,
+            "binance_linear",
+            "btcusdt",
+            SymbolCase::Upper,
+        )?;
+        let mut lower = mp.clone();
+        lower.symbol = "btcusdt".into();
+        let row = lower.to_funding_row(&ctx)?.expect("funding row");
+        assert_eq!(row.symbol, "BTCUSDT");
+
+        // lower: rows follow the policy
+        let ctx = MapCtx::new(Arc::clone(&reg), &appconfig, "binance_linear", "BTCUSDT")?;
+        let ctx = MapCtx {
+            symbol_case: SymbolCase::Lower,
+            ..ctx
+        };
+        let row = mp.to_funding_row(&ctx)?.expect("funding row");
+        assert_eq!(row.symbol, "btcusdt");
+
+        // preserve: payload casing kept, lookup is exact
+        let ctx = MapCtx::new(Arc::clone(&reg), &appconfig, "binance_linear", "BTCUSDT")?;
+        assert_eq!(ctx.symbol_case, SymbolCase::Preserve);
+        assert_eq!(
+            mp.to_funding_row(&ctx)?.expect("funding row").symbol,
+            mp.symbol
+        );
+        assert!(MapCtx::new(reg, &appconfig, "binance_linear", "btcusdt").is_err());
+
+        Ok(())
+    }
 }
@@ -439,6 +439,40 @@ impl FromJsonStr for BinanceLinearWsAggTrade {
         serde_json::from_str(s).map_err(AppError::Json)
     }
 }
+//
+// ---- WS: Mark Price (markPriceUpdate) ----
+//
+// One message carries mark/index/estimated settle price AND the current funding rate
+// plus the next funding timestamp. Only the funding part is mapped to rows today.
+//
+#[derive(Debug, Clone, Deserialize)]
+pub struct BinanceLinearWsMarkPriceUpdate {
+    #[serde(rename = "e")]
+    pub event_type: String,
+    #[serde(rename = "E")]
+    pub event_time_ms: u64,
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "p")]
+    pub mark_price: String,
+    #[serde(rename = "i")]
+    pub index_price: String,
+    #[serde(rename = "P")]
+    pub est_settle_price: String,
+    /// Signed: positive = longs pay shorts, negative = shorts pay longs.
+    /// Binance sends "" for contracts without funding (e.g. delivery futures).
+    #[serde(rename = "r")]
+    pub funding_rate: String,
+    /// Next funding time (ms). Binance may send 0 when it is not known.
+    #[serde(rename = "T")]
+    pub next_funding_time_ms: u64,
+}
+
+impl FromJsonStr for BinanceLinearWsMarkPriceUpdate {
+    fn from_json_str(s: &str) -> AppResult<Self> {
+        serde_json::from_str(s).map_err(AppError::Json)
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -520,6 +554,7 @@ mod tests {
         ok &= load_and_parse::<BinanceLinearWsDepthUpdate>("BinanceLinearWsDepthUpdate");
         ok &= load_and_parse::<BinanceLinearWsForceOrder>("BinanceLinearWsForceOrder");
         ok &= load_and_parse::<BinanceLinearWsAggTrade>("BinanceLinearWsAggTrade");
+        ok &= load_and_parse::<BinanceLinearWsMarkPriceUpdate>("BinanceLinearWsMarkPriceUpdate");
 
         if ok {
             println!("\n=== ALL Binance testdata JSON parsed successfully ===");
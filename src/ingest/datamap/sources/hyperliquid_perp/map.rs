@@ -4,21 +4,18 @@ use crate::error::{AppError, AppResult};
 use crate::ingest::datamap::ctx::MapCtx;
 use crate::ingest::datamap::event::MapEnvelope;
 use crate::ingest::datamap::event::{
-    BookSide, DepthDeltaRow, FundingRow, MarketEvent, OpenInterestRow, TradeRow, TradeSide,
+    BookSide, DepthDeltaRow, DepthSnapshotRow, FundingRow, MarketEvent, OpenInterestRow, TradeRow,
+    TradeSide,
 };
 use crate::ingest::datamap::sources::hyperliquid_perp::types::{
     Hyperliquid_book_level, Hyperliquid_levels, HyperliquidPerpDepthSnapshot,
     HyperliquidPerpWsDepthUpdate, HyperliquidPerpWsOIFundingUpdate, HyperliquidPerpWsTrade,
 };
+use crate::ingest::datamap::venue_time::ms_to_utc;
 use crate::ingest::traits::MapToEvents;
 
 const EXCHANGE: &str = "hyperliquid_perp";
 
-fn ms_to_utc(ms: u64) -> AppResult<DateTime<Utc>> {
-    DateTime::<Utc>::from_timestamp_millis(ms as i64)
-        .ok_or_else(|| AppError::Internal(format!("invalid ms timestamp: {ms}")))
-}
-
 fn map_book_levels(
     ctx: &MapCtx,
     coin: &str,
@@ -28,6 +25,7 @@ fn map_book_levels(
     levels: &[Hyperliquid_book_level],
 ) -> AppResult<Vec<MarketEvent>> {
     let mut out = Vec::with_capacity(levels.len());
+    let coin = ctx.canonical_symbol(coin);
 
     for lvl in levels {
         // Hyperliquid: px, sz are strings
@@ -48,7 +46,7 @@ fn map_book_levels(
         out.push(MarketEvent::DepthDelta(DepthDeltaRow {
             exchange: EXCHANGE,
             time,
-            symbol: coin.to_string(),
+            symbol: coin.clone(),
             side,
             price_i,
             size_i,
@@ -91,6 +89,37 @@ impl MapToEvents for HyperliquidPerpDepthSnapshot {
     }
 }
 
+//
+// -------------------- REST: L2 snapshot -> DepthSnapshotRow* --------------------
+//
+impl HyperliquidPerpDepthSnapshot {
+    /// Full-book rows for `depth_snapshots`, best level first on each side.
+    /// `snapshot_id` is the snapshot time (ms), unique per symbol.
+    pub fn to_snapshot_rows(&self, ctx: &MapCtx) -> AppResult<Vec<DepthSnapshotRow>> {
+        let time = ms_to_utc(self.time)?;
+        let snapshot_id = time.timestamp_millis();
+        let coin = ctx.canonical_symbol(&self.coin);
+
+        let [bids, asks] = &self.levels;
+        let mut out = Vec::with_capacity(bids.len() + asks.len());
+        for (side, levels) in [(BookSide::Bid, bids), (BookSide::Ask, asks)] {
+            for (idx, lvl) in levels.iter().enumerate() {
+                out.push(DepthSnapshotRow {
+                    exchange: EXCHANGE,
+                    time,
+                    symbol: coin.clone(),
+                    side,
+                    price_i: ctx.price_str_to_i64(&lvl.px)?,
+                    size_i: ctx.book_size_to_base_i64(&lvl.sz, &lvl.px)?,
+                    level_idx: idx as i32,
+                    snapshot_id,
+                });
+            }
+        }
+        Ok(out)
+    }
+}
+
 //
 // -------------------- WS: depth update -> DepthDelta* --------------------
 //
@@ -110,7 +139,7 @@ impl MapToEvents for HyperliquidPerpWsDepthUpdate {
 //
 impl MapToEvents for HyperliquidPerpWsOIFundingUpdate {
     fn map_to_events(self, ctx: &MapCtx, _env: Option<MapEnvelope>) -> AppResult<Vec<MarketEvent>> {
-        let coin = self.data.coin;
+        let coin = ctx.canonical_symbol(&self.data.coin);
         let a = self.data.ctx;
 
         // Hyperliquid WS payload doesn't include a timestamp here; use ingest time.
@@ -119,8 +148,8 @@ impl MapToEvents for HyperliquidPerpWsOIFundingUpdate {
         // OI
         let oi_i = ctx.open_interest_str_to_i64(&a.open_interest)?;
 
-        // Funding: parse funding rate to f64
-        let funding_rate = ctx.funding_str_to_i64(&a.funding)?;
+        // Funding: scaled; None when beyond the plausibility bound (OI is still emitted)
+        let funding_rate = ctx.funding_rate_i64(&coin, &a.funding)?;
 
         let oi_evt = MarketEvent::OpenInterest(OpenInterestRow {
             exchange: EXCHANGE,
@@ -129,6 +158,10 @@ impl MapToEvents for HyperliquidPerpWsOIFundingUpdate {
             oi_i,
         });
 
+        let Some(funding_rate) = funding_rate else {
+            return Ok(vec![oi_evt]);
+        };
+
         // funding_time: none (unless you want to derive schedule; keep None for now)
         let funding_evt = MarketEvent::Funding(FundingRow {
             exchange: EXCHANGE,
@@ -165,7 +198,7 @@ impl MapToEvents for HyperliquidPerpWsTrade {
             out.push(MarketEvent::Trade(TradeRow {
                 exchange: EXCHANGE,
                 time: ms_to_utc(t.time)?,
-                symbol: t.coin,
+                symbol: ctx.canonical_symbol(&t.coin),
                 side,
                 price_i,
                 qty_i,
@@ -284,4 +317,110 @@ mod tests {
         println!("\n=== ALL Hyperliquid testdata mapped successfully ===");
         Ok(())
     }
+
+    #[test]
+    fn depth_snapshot_maps_to_grouped_level_rows() -> AppResult<()> {
+        use crate::db::rows::DepthSnapshotDBRow;
+        use crate::db::traits::BatchInsertRow;
+        use crate::ingest::datamap::event::BookSide;
+        use crate::ingest::instruments::spec::{InstrumentKind, InstrumentSpec, QtyUnit};
+
+        let spec = InstrumentSpec::new(
+            "hyperliquid_perp",
+            "BTC",
+            InstrumentKind::PerpLinear,
+            QtyUnit::Base,
+            Some(1.0),
+            None,
+            None,
+        )?;
+        let reg = Arc::new(InstrumentRegistry::build(vec![spec])?);
+        let ctx = MapCtx::new(reg, &load_app_config(false, 0)?, "hyperliquid_perp", "BTC")?;
+
+        let snap = load_and_parse::<HyperliquidPerpDepthSnapshot>("HyperliquidPerpDepthSnapshot")?;
+        let (n_bids, n_asks) = (snap.levels[0].len(), snap.levels[1].len());
+        let rows = snap.to_snapshot_rows(&ctx)?;
+        assert_eq!(rows.len(), n_bids + n_asks);
+
+        // One snapshot id, level_idx counting from the top of each side
+        assert!(rows.iter().all(|r| r.snapshot_id == 1_765_806_837_901));
+        assert_eq!(rows[0].side, BookSide::Bid);
+        assert_eq!((rows[0].level_idx, rows[1].level_idx), (0, 1));
+        assert_eq!(rows[n_bids].side, BookSide::Ask);
+        assert_eq!(rows[n_bids].level_idx, 0);
+
+        // Sizes go through book_size_to_base_i64 (1e8 qty scale)
+        assert_eq!(rows[0].price_i, ctx.price_str_to_i64("89421.0")?);
+        assert_eq!(
+            rows[0].size_i,
+            ctx.book_size_to_base_i64("0.00523", "89421.0")?
+        );
+        assert_eq!(rows[0].size_i, 523_000);
+
+        let db: DepthSnapshotDBRow = rows[n_bids].clone().into();
+        assert_eq!(db.side, 1);
+        assert_eq!(db.level_idx, 0);
+        assert_eq!(
+            db.table("hyperliquid_perp"),
+            "ex_hyperliquid_perp.depth_snapshots"
+        );
+        assert_eq!(db.scaled_value("size_i"), Some(rows[n_bids].size_i));
+        Ok(())
+    }
+
+    #[test]
+    fn over_precise_size_is_rounded_to_sz_decimals() -> AppResult<()> {
+        use crate::ingest::datamap::ctx::QtyPrecision;
+        use crate::ingest::datamap::event::TradeRow;
+        use crate::ingest::instruments::spec::{InstrumentKind, InstrumentSpec, QtyUnit};
+
+        // BTC: szDecimals 5 -> qty_decimals 5, price decimals 6 - 5 = 1
+        let spec = InstrumentSpec::new(
+            "hyperliquid_perp",
+            "BTC",
+            InstrumentKind::PerpLinear,
+            QtyUnit::Base,
+            Some(1.0),
+            None,
+            None,
+        )?
+        .with_precision(Some(1), Some(5));
+        let reg = Arc::new(InstrumentRegistry::build(vec![spec])?);
+        let cfg = load_app_config(false, 0)?;
+        let ctx = |p| -> AppResult<MapCtx> {
+            Ok(
+                MapCtx::new(Arc::clone(&reg), &cfg, "hyperliquid_perp", "BTC")?
+                    .with_qty_precision(p),
+            )
+        };
+
+        // 9 decimals: off the venue grid and not representable at the 1e8 qty scale
+        let msg: HyperliquidPerpWsTrade = serde_json::from_str(
+            r#"{"channel":"trades","data":[{"coin":"BTC","side":"B","px":"89421.0",
+                "sz":"0.123456789","time":1765807278093,"hash":"0x1","tid":1,"users":[]}]}"#,
+        )?;
+        let qty = |evs: Vec<MarketEvent>| match &evs[0] {
+            MarketEvent::Trade(TradeRow { qty_i, .. }) => *qty_i,
+            other => panic!("expected a trade, got {other:?}"),
+        };
+
+        // Round: half away from zero to 5 decimals, flagged as a possible venue change
+        let round = ctx(QtyPrecision::Round)?;
+        assert_eq!(qty(msg.clone().map_to_events(&round, None)?), 12_346_000);
+        assert_eq!(round.precision_exceeded_total(), 1);
+        assert_eq!(round.book_size_to_base_i64("0.000005", "89421.0")?, 1_000);
+
+        // Flag (default): counted, scaled as received, which fails here
+        let flag = ctx(QtyPrecision::Flag)?;
+        assert!(msg.map_to_events(&flag, None).is_err());
+        assert_eq!(flag.precision_exceeded_total(), 1);
+
+        // On-grid sizes are untouched either way
+        assert_eq!(
+            round.book_size_to_base_i64("0.23815", "89421.0")?,
+            23_815_000
+        );
+        assert_eq!(round.precision_exceeded_total(), 2);
+        Ok(())
+    }
 }
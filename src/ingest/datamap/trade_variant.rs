@@ -0,0 +1,119 @@
+//! ingest/datamap/trade_variant.rs
+//!
+//! Which flavour of trade a stream carries.
+//!
+//! Binance publishes both raw trades (`<symbol>@trade`, one message per fill) and aggregate
+//! trades (`<symbol>@aggTrade`, fills at the same price/time from one taker order merged).
+//! Both describe the same volume, so ingesting both for a symbol double-counts it.
+//!
+//! - A ws stream can declare its variant (`trade_variant = "raw" | "aggregated"`); otherwise it
+//!   is inferred from the subscription (`@aggTrade`, `@trade`, Hyperliquid `trades`).
+//! - `check_trade_variants` rejects an exchange config that carries both variants. Streams are
+//!   templates applied to every symbol, so both variants in one config means both per symbol.
+
+use crate::error::{AppError, AppResult};
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TradeVariant {
+    /// One row per fill.
+    Raw,
+    /// Fills merged by price/time (Binance aggTrade).
+    Aggregated,
+}
+
+impl TradeVariant {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TradeVariant::Raw => "raw",
+            TradeVariant::Aggregated => "aggregated",
+        }
+    }
+
+    /// Infer the variant from a ws subscription (stream title or subscription type).
+    /// Non-trade subscriptions return None.
+    pub fn infer(subscription: &str) -> Option<Self> {
+        let s = subscription.trim();
+        if s.ends_with("@aggTrade") {
+            Some(TradeVariant::Aggregated)
+        } else if s.ends_with("@trade") || s == "trades" {
+            Some(TradeVariant::Raw)
+        } else {
+            None
+        }
+    }
+}
+
+/// Refuse configs that ingest the same trades twice (raw + aggregated).
+///
+/// `streams` are (ws key, variant) pairs for every trade-carrying stream of one exchange.
+pub fn check_trade_variants<'a>(
+    exchange: &str,
+    streams: impl IntoIterator<Item = (&'a str, TradeVariant)>,
+) -> AppResult<()> {
+    let mut raw: Vec<&str> = Vec::new();
+    let mut aggregated: Vec<&str> = Vec::new();
+    for (key, variant) in streams {
+        match variant {
+            TradeVariant::Raw => raw.push(key),
+            TradeVariant::Aggregated => aggregated.push(key),
+        }
+    }
+
+    if raw.is_empty() || aggregated.is_empty() {
+        return Ok(());
+    }
+
+    tracing::error!(
+        exchange,
+        raw = ?raw,
+        aggregated = ?aggregated,
+        "raw AND aggregated trade streams enabled: volume would be double-counted"
+    );
+    Err(AppError::InvalidConfig(format!(
+        "\n❌ DUPLICATE TRADE STREAMS\n\
+         ├─ exchange: `{exchange}`\n\
+         ├─ raw trades: {raw:?}\n\
+         ├─ aggregated trades: {aggregated:?}\n\
+         └─ fix: keep only one trade variant per exchange (both double-count volume)\n"
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn infers_variant_from_subscription() {
+        assert_eq!(
+            TradeVariant::infer("<symbol>@aggTrade"),
+            Some(TradeVariant::Aggregated)
+        );
+        assert_eq!(
+            TradeVariant::infer("<symbol>@trade"),
+            Some(TradeVariant::Raw)
+        );
+        assert_eq!(TradeVariant::infer("trades"), Some(TradeVariant::Raw));
+        assert_eq!(TradeVariant::infer("<symbol>@depth@100ms"), None);
+        assert_eq!(TradeVariant::infer("<symbol>@forceOrder"), None);
+    }
+
+    #[test]
+    fn single_variant_is_accepted() {
+        assert!(
+            check_trade_variants("binance_linear", [("trades", TradeVariant::Aggregated)]).is_ok()
+        );
+        assert!(
+            check_trade_variants(
+                "binance_linear",
+                [
+                    ("trades", TradeVariant::Raw),
+                    ("trades_2", TradeVariant::Raw)
+                ]
+            )
+            .is_ok()
+        );
+        assert!(check_trade_variants("binance_linear", []).is_ok());
+    }
+}
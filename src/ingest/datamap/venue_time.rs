@@ -0,0 +1,246 @@
+//! ingest/datamap/venue_time.rs
+//!
+//! Declarative venue event-time extraction.
+//!
+//! Venues put the event timestamp in different fields (`time`, `T`, `E`, `ts`, nested under
+//! `data`, inside arrays, ...) and in different units. Instead of hardcoding that per mapper,
+//! an endpoint can declare it in the exchange TOML:
+//!
+//! ```toml
+//! [ws.trades]
+//! stream_title = "<symbol>@aggTrade"
+//! time_field = { pointer = "/T", unit = "ms" }
+//! ```
+//!
+//! - `pointer` is an RFC 6901 JSON pointer into the raw message / response body.
+//! - `unit` is one of `sec`, `ms` (default), `us`, `ns`.
+//! - Values may be JSON numbers or numeric strings; everything is normalized to epoch ms and
+//!   converted through `ms_to_utc` (sub-millisecond precision is dropped).
+//! - Pointers are validated at config load (`ExchangeConfig::validate`).
+//! - REST responses (`[api.*]`) are read through `ApiClient::execute_json_timed`; the HTTP
+//!   handlers stamp the mapped rows with the venue time when the field is present.
+//! - WS messages (`[ws.*]`) are read through `frame::apply_venue_time`: the pointer is into
+//!   each message (the `data` of a Binance combined-stream wrapper), and all events mapped
+//!   from it get that one time.
+
+use crate::error::{AppError, AppResult};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Epoch milliseconds -> UTC, rejecting out-of-range values.
+pub fn ms_to_utc(ms: u64) -> AppResult<DateTime<Utc>> {
+    i64::try_from(ms)
+        .ok()
+        .and_then(DateTime::<Utc>::from_timestamp_millis)
+        .ok_or_else(|| AppError::Internal(format!("invalid ms timestamp: {ms}")))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeUnit {
+    Sec,
+    #[default]
+    Ms,
+    Us,
+    Ns,
+}
+
+impl TimeUnit {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TimeUnit::Sec => "sec",
+            TimeUnit::Ms => "ms",
+            TimeUnit::Us => "us",
+            TimeUnit::Ns => "ns",
+        }
+    }
+
+    /// Integer value in this unit -> epoch ms (None on overflow).
+    pub fn to_ms(self, v: u64) -> Option<u64> {
+        match self {
+            TimeUnit::Sec => v.checked_mul(1_000),
+            TimeUnit::Ms => Some(v),
+            TimeUnit::Us => Some(v / 1_000),
+            TimeUnit::Ns => Some(v / 1_000_000),
+        }
+    }
+
+    /// Fractional value in this unit (e.g. `1700000000.123` seconds) -> epoch ms.
+    fn f64_to_ms(self, v: f64) -> Option<u64> {
+        let ms = match self {
+            TimeUnit::Sec => v * 1_000.0,
+            TimeUnit::Ms => v,
+            TimeUnit::Us => v / 1_000.0,
+            TimeUnit::Ns => v / 1_000_000.0,
+        };
+        (ms.is_finite() && ms >= 0.0 && ms < u64::MAX as f64).then(|| ms.round() as u64)
+    }
+}
+
+/// Where the venue event time lives in a payload, and in which unit.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct VenueTimeField {
+    pub pointer: String,
+    #[serde(default)]
+    pub unit: TimeUnit,
+}
+
+impl VenueTimeField {
+    pub fn new(pointer: impl Into<String>, unit: TimeUnit) -> Self {
+        Self {
+            pointer: pointer.into(),
+            unit,
+        }
+    }
+
+    /// Syntax check of the JSON pointer (RFC 6901).
+    /// The empty pointer (whole document) is rejected: a timestamp is never the whole payload.
+    pub fn validate(&self) -> AppResult<()> {
+        let p = self.pointer.as_str();
+        if !p.starts_with('/') {
+            return Err(AppError::InvalidConfig(format!(
+                "time_field.pointer `{p}` must start with '/' (e.g. \"/E\", \"/data/time\")"
+            )));
+        }
+        let mut chars = p.chars();
+        while let Some(c) = chars.next() {
+            if c == '~' && !matches!(chars.next(), Some('0') | Some('1')) {
+                return Err(AppError::InvalidConfig(format!(
+                    "time_field.pointer `{p}` has an invalid escape ('~' must be followed by 0 or 1)"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the venue event time from `payload`.
+    pub fn extract(&self, payload: &Value) -> AppResult<DateTime<Utc>> {
+        let raw = payload.pointer(&self.pointer).ok_or_else(|| {
+            AppError::Internal(format!("venue time field `{}` not found", self.pointer))
+        })?;
+
+        let ms = match raw {
+            Value::Number(n) => match n.as_u64() {
+                Some(v) => self.unit.to_ms(v),
+                None => n.as_f64().and_then(|v| self.unit.f64_to_ms(v)),
+            },
+            Value::String(s) => match s.trim().parse::<u64>() {
+                Ok(v) => self.unit.to_ms(v),
+                Err(_) => s
+                    .trim()
+                    .parse::<f64>()
+                    .ok()
+                    .and_then(|v| self.unit.f64_to_ms(v)),
+            },
+            _ => None,
+        }
+        .ok_or_else(|| {
+            AppError::Internal(format!(
+                "venue time field `{}` is not a valid {} timestamp: {raw}",
+                self.pointer,
+                self.unit.as_str()
+            ))
+        })?;
+
+        ms_to_utc(ms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const MS: i64 = 1_765_807_987_120;
+
+    fn want() -> DateTime<Utc> {
+        DateTime::<Utc>::from_timestamp_millis(MS).unwrap()
+    }
+
+    #[test]
+    fn extracts_each_unit() {
+        let cases = [
+            (TimeUnit::Sec, json!({ "ts": 1_765_807_987 }), MS - 120),
+            (TimeUnit::Sec, json!({ "ts": 1_765_807_987.12 }), MS),
+            (TimeUnit::Ms, json!({ "ts": MS }), MS),
+            (TimeUnit::Us, json!({ "ts": MS * 1_000 + 999 }), MS),
+            (
+                TimeUnit::Ns,
+                json!({ "ts": (MS as u64) * 1_000_000 + 1 }),
+                MS,
+            ),
+        ];
+        for (unit, payload, ms) in cases {
+            let f = VenueTimeField::new("/ts", unit);
+            let got = f.extract(&payload).unwrap();
+            assert_eq!(got.timestamp_millis(), ms, "unit {}", unit.as_str());
+        }
+    }
+
+    #[test]
+    fn follows_nested_pointers_and_numeric_strings() {
+        // Hyperliquid trades: {"channel":"trades","data":[{"time":...}]}
+        let hl = json!({ "channel": "trades", "data": [{ "coin": "BTC", "time": MS }] });
+        let f = VenueTimeField::new("/data/0/time", TimeUnit::Ms);
+        assert_eq!(f.extract(&hl).unwrap(), want());
+
+        // Binance long/short ratio endpoints return the timestamp as a string
+        let b = json!([{ "timestamp": MS.to_string() }]);
+        let f = VenueTimeField::new("/0/timestamp", TimeUnit::Ms);
+        assert_eq!(f.extract(&b).unwrap(), want());
+
+        // Escaped key
+        let odd = json!({ "a/b": { "t~": MS } });
+        let f = VenueTimeField::new("/a~1b/t~0", TimeUnit::Ms);
+        assert_eq!(f.extract(&odd).unwrap(), want());
+    }
+
+    #[test]
+    fn missing_or_invalid_values_are_errors() {
+        let f = VenueTimeField::new("/E", TimeUnit::Ms);
+        assert!(f.extract(&json!({ "T": MS })).is_err(), "renamed field");
+        assert!(f.extract(&json!({ "E": "soon" })).is_err());
+        assert!(f.extract(&json!({ "E": -5 })).is_err());
+        assert!(f.extract(&json!({ "E": null })).is_err());
+        assert!(
+            VenueTimeField::new("/E", TimeUnit::Sec)
+                .extract(&json!({ "E": u64::MAX }))
+                .is_err(),
+            "overflow"
+        );
+    }
+
+    #[test]
+    fn pointer_validation() {
+        for ok in ["/E", "/data/time", "/0/fundingTime", "/a~0b~1c"] {
+            assert!(
+                VenueTimeField::new(ok, TimeUnit::Ms).validate().is_ok(),
+                "{ok}"
+            );
+        }
+        for bad in ["", "E", "data/time", "/a~2", "/a~"] {
+            assert!(
+                VenueTimeField::new(bad, TimeUnit::Ms).validate().is_err(),
+                "{bad}"
+            );
+        }
+    }
+
+    #[test]
+    fn deserializes_from_toml() {
+        #[derive(Deserialize)]
+        struct Wrap {
+            time_field: VenueTimeField,
+        }
+        let w: Wrap = toml::from_str(r#"time_field = { pointer = "/T", unit = "us" }"#).unwrap();
+        assert_eq!(w.time_field, VenueTimeField::new("/T", TimeUnit::Us));
+
+        let w: Wrap = toml::from_str(r#"time_field = { pointer = "/T" }"#).unwrap();
+        assert_eq!(w.time_field.unit, TimeUnit::Ms, "ms is the default unit");
+
+        assert!(
+            toml::from_str::<Wrap>(r#"time_field = { pointer = "/T", unit = "min" }"#).is_err()
+        );
+    }
+}
@@ -1,11 +1,15 @@
 use super::rate_limiter::RateLimiterRegistry;
 use crate::error::{AppError, AppResult};
+use crate::ingest::config::header_map;
 use crate::ingest::metrics::IngestMetrics;
 use crate::ingest::spec::HttpRequestSpec;
+use crate::telemetry::throttle::log_throttle;
 use std::sync::Arc;
 
+use chrono::{DateTime, Utc};
 use reqwest::{Client, Response};
-use std::time::Duration;
+use serde_json::Value as JsonValue;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 #[derive(Debug, Clone)]
 pub struct ApiClient {
@@ -15,6 +19,8 @@ pub struct ApiClient {
     pub limiter_registry: Option<Arc<RateLimiterRegistry>>,
     pub timeout: Duration,
     pub metrics: Option<Arc<IngestMetrics>>,
+    /// Max response body size; `HttpRequestSpec.max_response_bytes` overrides it per request.
+    pub max_response_bytes: Option<usize>,
 }
 
 impl ApiClient {
@@ -31,16 +37,39 @@ impl ApiClient {
             limiter_registry,
             timeout: Duration::from_secs(10),
             metrics,
+            max_response_bytes: None,
         }
     }
 
+    pub fn with_max_response_bytes(mut self, max_response_bytes: Option<usize>) -> Self {
+        self.max_response_bytes = max_response_bytes;
+        self
+    }
+
+    /// Rebuild the HTTP client so every request carries `headers` (exchange `[headers]`).
+    pub fn with_default_headers(mut self, headers: &[(String, String)]) -> AppResult<Self> {
+        if headers.is_empty() {
+            return Ok(self);
+        }
+        self.http = Client::builder()
+            .default_headers(header_map(headers)?)
+            .build()
+            .map_err(|e| {
+                AppError::Internal(format!("{}: http client build failed: {e}", self.name))
+            })?;
+        Ok(self)
+    }
+
     /// Execute a fully-resolved HTTP request spec.
     /// Applies limiter (if present) and syncs used-weight from headers.
+    /// Timestamped specs get their timestamp here, after the limiter wait, so it is current.
     pub async fn execute(&self, spec: &HttpRequestSpec) -> AppResult<Response> {
         if let Some(l) = &self.limiter_registry {
             l.acquire(self.name, spec.weight).await;
         }
 
+        let stamp = spec.timing.as_ref().map(|t| t.params(now_ms()));
+
         let url = format!("{}{}", self.base_url, spec.path);
 
         let mut rb = self
@@ -56,8 +85,27 @@ impl ApiClient {
             rb = rb.header(k, v);
         }
 
-        if let Some(body) = &spec.json_body {
-            rb = rb.json(body);
+        match (&spec.json_body, stamp) {
+            // JSON-body endpoints carry the timestamp next to their params
+            (Some(JsonValue::Object(body)), Some(stamp)) => {
+                let mut body = body.clone();
+                for (k, v) in stamp {
+                    let v = v
+                        .parse::<u64>()
+                        .map(JsonValue::from)
+                        .unwrap_or(JsonValue::String(v));
+                    body.insert(k, v);
+                }
+                rb = rb.json(&body);
+            }
+            (body, stamp) => {
+                if let Some(stamp) = stamp {
+                    rb = rb.query(&stamp);
+                }
+                if let Some(body) = body {
+                    rb = rb.json(body);
+                }
+            }
         }
 
         let resp = rb.send().await.map_err(AppError::Reqwest)?;
@@ -71,17 +119,76 @@ impl ApiClient {
         Ok(resp)
     }
 
+    /// Execute and read the whole body, enforcing the response size limit.
+    pub async fn execute_bytes(&self, spec: &HttpRequestSpec) -> AppResult<Vec<u8>> {
+        let resp = self.execute(spec).await?;
+        self.read_body_limited(resp, spec.max_response_bytes.or(self.max_response_bytes))
+            .await
+    }
+
+    /// Read a response body chunk by chunk and abort as soon as it grows past `limit`.
+    /// Dropping the response on error closes the connection instead of draining it.
+    pub async fn read_body_limited(
+        &self,
+        mut resp: Response,
+        limit: Option<usize>,
+    ) -> AppResult<Vec<u8>> {
+        let Some(limit) = limit else {
+            return Ok(resp.bytes().await.map_err(AppError::Reqwest)?.to_vec());
+        };
+
+        // Fast path: announced length is already over the limit
+        if let Some(len) = resp.content_length()
+            && len > limit as u64
+        {
+            return Err(self.response_too_large(limit, len as usize));
+        }
+
+        let mut body = Vec::new();
+        while let Some(chunk) = resp.chunk().await.map_err(AppError::Reqwest)? {
+            let received = body.len() + chunk.len();
+            if received > limit {
+                return Err(self.response_too_large(limit, received));
+            }
+            body.extend_from_slice(&chunk);
+        }
+
+        Ok(body)
+    }
+
+    fn response_too_large(&self, limit: usize, received: usize) -> AppError {
+        if let Some(m) = &self.metrics {
+            m.inc_error();
+        }
+        tracing::warn!(
+            service = self.name,
+            limit,
+            received,
+            "http response exceeds max_response_bytes; download aborted"
+        );
+        AppError::ResponseTooLarge {
+            service: self.name.to_string(),
+            limit,
+            received,
+        }
+    }
+
     /// Execute and deserialize JSON, with consistent API error mapping.
     pub async fn execute_json<T: serde::de::DeserializeOwned>(
         &self,
         spec: &HttpRequestSpec,
     ) -> AppResult<T> {
         let resp = self.execute(spec).await?;
+        let limit = spec.max_response_bytes.or(self.max_response_bytes);
 
         if !resp.status().is_success() {
             println!("status = {}", resp.status());
             let status = resp.status();
-            let body = resp.text().await.unwrap_or_default();
+            let body = self
+                .read_body_limited(resp, limit)
+                .await
+                .map(|b| String::from_utf8_lossy(&b).into_owned())
+                .unwrap_or_default();
 
             if let Some(m) = &self.metrics {
                 m.inc_error();
@@ -94,19 +201,77 @@ impl ApiClient {
             });
         }
 
-        resp.json::<T>().await.map_err(|e| {
+        let body = self.read_body_limited(resp, limit).await?;
+
+        serde_json::from_slice::<T>(&body).map_err(|e| {
             if let Some(m) = &self.metrics {
                 m.inc_error();
             }
-            // This is a JSON decode/transport-level error from reqwest:
-            AppError::Reqwest(e)
+            AppError::Json(e)
         })
     }
+
+    /// `execute_json` plus the venue event time read through `spec.time_field`.
+    /// None when the endpoint declares no time field or the response lacks it (warned);
+    /// the mapper's own time is kept then.
+    pub async fn execute_json_timed<T: serde::de::DeserializeOwned>(
+        &self,
+        spec: &HttpRequestSpec,
+    ) -> AppResult<(T, Option<DateTime<Utc>>)> {
+        let Some(field) = &spec.time_field else {
+            return Ok((self.execute_json::<T>(spec).await?, None));
+        };
+
+        let value: JsonValue = self.execute_json(spec).await?;
+        let time = match field.extract(&value) {
+            Ok(t) => Some(t),
+            Err(e) => {
+                if let Some(suppressed) =
+                    log_throttle().allow(&format!("venue time missing:{}", self.name))
+                {
+                    tracing::warn!(
+                        client = self.name,
+                        path = %spec.path,
+                        suppressed,
+                        error = %e,
+                        "venue time field unreadable; keeping the mapped time"
+                    );
+                }
+                None
+            }
+        };
+        let item = serde_json::from_value::<T>(value).map_err(|e| {
+            if let Some(m) = &self.metrics {
+                m.inc_error();
+            }
+            AppError::Json(e)
+        })?;
+        Ok((item, time))
+    }
+}
+
+/// Wall-clock epoch milliseconds (what venues compare request timestamps against).
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+pub(crate) fn is_retryable(e: &AppError) -> bool {
+    match e {
+        AppError::Reqwest(_) => true,
+        AppError::Api { status, .. } => {
+            status.is_server_error() || *status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        }
+        _ => false,
+    }
 }
 
 impl ApiClient {
     /// Poll a fixed, fully-resolved request spec forever.
     /// Best when the spec is stable (symbol/coin doesn't change).
+    /// `on_item` also gets the venue event time (`execute_json_timed`).
     pub async fn poll_json_spec<T, F, Fut>(
         &self,
         spec: HttpRequestSpec,
@@ -114,7 +279,7 @@ impl ApiClient {
     ) -> AppResult<()>
     where
         T: serde::de::DeserializeOwned,
-        F: FnMut(T) -> Fut,
+        F: FnMut(T, Option<DateTime<Utc>>) -> Fut,
         Fut: std::future::Future<Output = AppResult<()>>,
     {
         let mut interval = tokio::time::interval(Duration::from_secs(spec.interval_seconds));
@@ -122,13 +287,13 @@ impl ApiClient {
         loop {
             interval.tick().await;
 
-            match self.execute_json::<T>(&spec).await {
-                Ok(item) => {
+            match self.execute_json_timed::<T>(&spec).await {
+                Ok((item, venue_time)) => {
                     if let Some(m) = &self.metrics {
                         m.inc_in();
                     }
 
-                    match on_item(item).await {
+                    match on_item(item, venue_time).await {
                         Ok(()) => {
                             if let Some(m) = &self.metrics {
                                 m.inc_processed();
@@ -138,7 +303,16 @@ impl ApiClient {
                             if let Some(m) = &self.metrics {
                                 m.inc_error();
                             }
-                            tracing::warn!(error=?e, "on_item failed; dropping this snapshot");
+                            if let Some(suppressed) =
+                                log_throttle().allow(&format!("on_item failed:{}", self.name))
+                            {
+                                tracing::warn!(
+                                    client = self.name,
+                                    suppressed,
+                                    error = ?e,
+                                    "on_item failed; dropping this snapshot"
+                                );
+                            }
                             continue; // ✅ skip this snapshot, keep polling
                         }
                     }
@@ -245,6 +419,250 @@ mod tests {
         }
     }
 
+    /// One-shot HTTP server answering every connection with `body`.
+    /// `announce_len = false` omits Content-Length (body ends on close) so the
+    /// streaming byte counter is exercised instead of the header fast path.
+    async fn mock_server(body: Vec<u8>, announce_len: bool) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            while let Ok((mut sock, _)) = listener.accept().await {
+                let body = body.clone();
+                tokio::spawn(async move {
+                    let mut buf = [0u8; 4096];
+                    let _ = sock.read(&mut buf).await;
+
+                    let head = if announce_len {
+                        format!(
+                            "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n",
+                            body.len()
+                        )
+                    } else {
+                        "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\nconnection: close\r\n\r\n"
+                            .to_string()
+                    };
+                    let _ = sock.write_all(head.as_bytes()).await;
+                    for chunk in body.chunks(1024) {
+                        if sock.write_all(chunk).await.is_err() {
+                            return; // client aborted the download
+                        }
+                    }
+                    let _ = sock.shutdown().await;
+                });
+            }
+        });
+
+        format!("http://{addr}")
+    }
+
+    fn get_spec(max_response_bytes: Option<usize>) -> HttpRequestSpec {
+        HttpRequestSpec {
+            method: reqwest::Method::GET,
+            path: "/depth".into(),
+            query: Vec::new(),
+            headers: Vec::new(),
+            json_body: None,
+            weight: 1,
+            interval_seconds: 1,
+            max_response_bytes,
+            timing: None,
+            time_field: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn venue_time_is_read_through_the_time_field() {
+        use crate::ingest::datamap::venue_time::{TimeUnit, VenueTimeField};
+
+        let body = br#"{"symbol":"BTCUSDT","time":1700000000123}"#.to_vec();
+        let client = ApiClient::new("mock", mock_server(body, true).await, None, None);
+
+        let mut spec = get_spec(None);
+        spec.time_field = Some(VenueTimeField::new("/time", TimeUnit::Ms));
+        let (v, t): (JsonValue, _) = client.execute_json_timed(&spec).await.unwrap();
+        assert_eq!(v["symbol"], "BTCUSDT");
+        assert_eq!(t.unwrap().timestamp_millis(), 1700000000123);
+    }
+
+    #[tokio::test]
+    async fn missing_venue_time_keeps_the_item() {
+        use crate::ingest::datamap::venue_time::{TimeUnit, VenueTimeField};
+
+        let body = br#"{"symbol":"BTCUSDT"}"#.to_vec();
+        let client = ApiClient::new("mock", mock_server(body, true).await, None, None);
+
+        let mut spec = get_spec(None);
+        spec.time_field = Some(VenueTimeField::new("/time", TimeUnit::Ms));
+        let (v, t): (JsonValue, _) = client.execute_json_timed(&spec).await.unwrap();
+        assert_eq!(v["symbol"], "BTCUSDT");
+        assert!(t.is_none());
+    }
+
+    #[tokio::test]
+    async fn default_headers_are_sent_on_every_request() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Echo the raw request head back so the test can inspect the headers
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut sock, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut buf = vec![0u8; 8192];
+                    let n = sock.read(&mut buf).await.unwrap_or(0);
+                    let body = serde_json::to_vec(&String::from_utf8_lossy(&buf[..n])).unwrap();
+                    let head = format!(
+                        "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n",
+                        body.len()
+                    );
+                    let _ = sock.write_all(head.as_bytes()).await;
+                    let _ = sock.write_all(&body).await;
+                    let _ = sock.shutdown().await;
+                });
+            }
+        });
+
+        let headers = vec![
+            (
+                "User-Agent".to_string(),
+                "mini-fintickstreams/test".to_string(),
+            ),
+            ("X-Client".to_string(), "ingest".to_string()),
+        ];
+        let client = ApiClient::new("mock", format!("http://{addr}"), None, None)
+            .with_default_headers(&headers)
+            .unwrap();
+
+        let head: String = client.execute_json(&get_spec(None)).await.unwrap();
+        let head = head.to_ascii_lowercase();
+        assert!(
+            head.contains("user-agent: mini-fintickstreams/test"),
+            "{head}"
+        );
+        assert!(head.contains("x-client: ingest"), "{head}");
+
+        // Invalid header values are rejected up front
+        let bad = vec![("User-Agent".to_string(), "bad\nvalue".to_string())];
+        assert!(
+            ApiClient::new("mock", "http://x", None, None)
+                .with_default_headers(&bad)
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn timestamp_is_fresh_on_every_attempt_including_retries() {
+        use crate::ingest::instruments::loader::{LoadRetry, retry_transient};
+        use crate::ingest::spec::RequestTiming;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // First two requests fail with 503, the third succeeds; every request line is recorded
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut served = 0;
+            while let Ok((mut sock, _)) = listener.accept().await {
+                let mut buf = vec![0u8; 8192];
+                let n = sock.read(&mut buf).await.unwrap_or(0);
+                let head = String::from_utf8_lossy(&buf[..n]).into_owned();
+                let _ = tx.send(head.lines().next().unwrap_or_default().to_string());
+                served += 1;
+                let resp = if served < 3 {
+                    "HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+                } else {
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: 2\r\nconnection: close\r\n\r\n{}"
+                };
+                let _ = sock.write_all(resp.as_bytes()).await;
+                let _ = sock.shutdown().await;
+            }
+        });
+
+        let mut spec = get_spec(None);
+        spec.query = vec![("symbol".into(), "BTCUSDT".into())];
+        spec.timing = Some(RequestTiming {
+            recv_window_ms: Some(5000),
+            ..RequestTiming::default()
+        });
+
+        let client = ApiClient::new("mock", format!("http://{addr}"), None, None);
+        let backoff = Duration::from_millis(30);
+        let before = now_ms();
+        // Retried the way callers retry (`retry_transient`): each attempt is a fresh `execute`
+        let retry = LoadRetry {
+            attempts: 3,
+            initial_backoff: backoff,
+            max_backoff: backoff,
+        };
+        let _: JsonValue = retry_transient(retry, || client.execute_json(&spec))
+            .await
+            .expect("third attempt succeeds");
+        let after = now_ms();
+
+        let mut stamps = Vec::new();
+        while let Ok(line) = rx.try_recv() {
+            assert!(line.contains("symbol=BTCUSDT"), "{line}");
+            assert!(line.contains("recvWindow=5000"), "{line}");
+            let ts = line
+                .split(['?', '&', ' '])
+                .find_map(|kv| kv.strip_prefix("timestamp="))
+                .and_then(|v| v.parse::<u64>().ok())
+                .unwrap_or_else(|| panic!("no timestamp in {line}"));
+            stamps.push(ts);
+        }
+        assert_eq!(stamps.len(), 3, "two retries + final attempt");
+        for w in stamps.windows(2) {
+            assert!(
+                w[1] >= w[0] + backoff.as_millis() as u64,
+                "re-stamped: {stamps:?}"
+            );
+        }
+        assert!(stamps[0] >= before && stamps[2] <= after, "{stamps:?}");
+
+        // The resolved spec itself is never stamped
+        assert!(spec.query.iter().all(|(k, _)| k != "timestamp"));
+    }
+
+    #[tokio::test]
+    async fn response_over_limit_is_aborted_with_clean_error() {
+        // ~200 KiB JSON array
+        let body = serde_json::to_vec(&vec![123_456_789u64; 20_000]).unwrap();
+        let limit = 64 * 1024;
+
+        for announce_len in [true, false] {
+            let base = mock_server(body.clone(), announce_len).await;
+            let client =
+                ApiClient::new("mock", base, None, None).with_max_response_bytes(Some(limit));
+
+            let err = client
+                .execute_json::<JsonValue>(&get_spec(None))
+                .await
+                .expect_err("body over the limit must fail");
+            match err {
+                AppError::ResponseTooLarge {
+                    service,
+                    limit: l,
+                    received,
+                } => {
+                    assert_eq!(service, "mock");
+                    assert_eq!(l, limit);
+                    assert!(received > limit, "announce_len={announce_len}");
+                }
+                other => panic!("expected ResponseTooLarge, got {other:?}"),
+            }
+
+            // Per-request limit overrides the client limit
+            let ok: JsonValue = client
+                .execute_json(&get_spec(Some(body.len())))
+                .await
+                .expect("body fits the per-request limit");
+            assert_eq!(ok.as_array().map(|a| a.len()), Some(20_000));
+        }
+    }
+
     #[tokio::test]
     async fn test_api_calls_binance_and_hyperliquid_depth() -> AppResult<()> {
         // 1) Load configs
@@ -1,5 +1,7 @@
 pub mod api_client;
 pub mod rate_limiter;
+pub mod resync;
 
 pub use api_client::*;
 pub use rate_limiter::*;
+pub use resync::*;
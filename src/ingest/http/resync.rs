@@ -0,0 +1,170 @@
+//! ingest/http/resync.rs
+//!
+//! Shared throttle for REST depth snapshot fetches (book seed / re-seed).
+//!
+//! When many depth streams need a snapshot at once (mass restart after a venue hiccup, a
+//! burst of evicted books), firing them all at the venue's REST API risks a ban and turns a
+//! short outage into a long one. Every snapshot fetch goes through one `ResyncThrottle`:
+//! - at most `max_concurrent` fetches run at a time (the rest queue, FIFO);
+//! - fetch starts are spaced at least `min_interval` apart across all streams.
+//!
+//! `run` queues for its turn. Callers are stream starts and WS book re-seeds, which run in
+//! a background task of their own (see `spawn_binance_book_reseed`), so no WS read loop ever
+//! waits in the queue.
+//!
+//! Regular HTTP polling is not affected (it has its own rate limiters).
+
+use crate::app::config::StreamsConfig;
+use crate::error::{AppError, AppResult};
+use crate::ingest::metrics::IngestMetrics;
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::sync::{Mutex, Semaphore};
+use tokio::time::Instant;
+
+#[derive(Debug)]
+pub struct ResyncThrottle {
+    permits: Semaphore,
+    min_interval: Duration,
+    // earliest start of the next fetch
+    next_start: Mutex<Instant>,
+    pending: AtomicUsize,
+    metrics: Option<Arc<IngestMetrics>>,
+}
+
+impl ResyncThrottle {
+    /// `max_concurrent` of 0 is treated as 1.
+    pub fn new(max_concurrent: usize, min_interval: Duration) -> Self {
+        Self {
+            permits: Semaphore::new(max_concurrent.max(1)),
+            min_interval,
+            next_start: Mutex::new(Instant::now()),
+            pending: AtomicUsize::new(0),
+            metrics: None,
+        }
+    }
+
+    pub fn from_config(cfg: &StreamsConfig) -> Self {
+        Self::new(
+            cfg.resync_max_concurrent,
+            Duration::from_millis(cfg.resync_min_interval_ms),
+        )
+    }
+
+    /// Report pending resyncs to ingest metrics as well.
+    pub fn with_metrics(mut self, metrics: Option<Arc<IngestMetrics>>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Resyncs waiting for their turn.
+    pub fn pending(&self) -> usize {
+        self.pending.load(Ordering::Relaxed)
+    }
+
+    /// Run `fetch` once a slot is free and the pacing interval has passed.
+    pub async fn run<T, F>(&self, fetch: F) -> AppResult<T>
+    where
+        F: Future<Output = AppResult<T>>,
+    {
+        let _permit = {
+            // Dropped (stream cancelled) while queued: still leaves the queue
+            let _queued = Queued::enter(self);
+            let permit = self
+                .permits
+                .acquire()
+                .await
+                .map_err(|_| AppError::Internal("resync throttle closed".to_string()))?;
+            self.pace().await;
+            permit
+        };
+        fetch.await
+    }
+
+    async fn pace(&self) {
+        let start = {
+            let mut next = self.next_start.lock().await;
+            let start = (*next).max(Instant::now());
+            *next = start + self.min_interval;
+            start
+        };
+        tokio::time::sleep_until(start).await;
+    }
+}
+
+/// One resync in the queue (pending count + gauge), until dropped.
+struct Queued<'a>(&'a ResyncThrottle);
+
+impl<'a> Queued<'a> {
+    fn enter(throttle: &'a ResyncThrottle) -> Self {
+        throttle.pending.fetch_add(1, Ordering::Relaxed);
+        if let Some(m) = throttle.metrics.as_deref() {
+            m.inc_resync_pending();
+        }
+        Self(throttle)
+    }
+}
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        self.0.pending.fetch_sub(1, Ordering::Relaxed);
+        if let Some(m) = self.0.metrics.as_deref() {
+            m.dec_resync_pending();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn simultaneous_resyncs_are_throttled() {
+        let throttle = Arc::new(ResyncThrottle::new(2, Duration::from_millis(20)));
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+        let t0 = Instant::now();
+
+        // 8 streams need a snapshot at the same instant
+        let tasks: Vec<_> = (0..8)
+            .map(|_| {
+                let throttle = Arc::clone(&throttle);
+                let in_flight = Arc::clone(&in_flight);
+                let max_in_flight = Arc::clone(&max_in_flight);
+                tokio::spawn(async move {
+                    throttle
+                        .run(async {
+                            let started = Instant::now();
+                            let n = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                            max_in_flight.fetch_max(n, Ordering::SeqCst);
+                            tokio::time::sleep(Duration::from_millis(50)).await;
+                            in_flight.fetch_sub(1, Ordering::SeqCst);
+                            Ok(started)
+                        })
+                        .await
+                        .unwrap()
+                })
+            })
+            .collect();
+
+        // The queue is visible while the first fetches run
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(throttle.pending() >= 6, "pending={}", throttle.pending());
+
+        let mut starts = Vec::new();
+        for t in tasks {
+            starts.push(t.await.unwrap());
+        }
+        starts.sort();
+
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 2);
+        for pair in starts.windows(2) {
+            assert!(pair[1] - pair[0] >= Duration::from_millis(19));
+        }
+        // 2 at a time, 50ms each: the last one starts after 3 full rounds
+        assert!(starts[7] - t0 >= Duration::from_millis(150));
+        assert_eq!(throttle.pending(), 0);
+    }
+}
@@ -3,13 +3,18 @@
 //! Loads `InstrumentSpec` sets from exchange "exchange_info"/meta endpoints.
 //! Output is a single `Vec<InstrumentSpec>`; indexing/lookup belongs in a registry layer.
 
+use std::future::Future;
 use std::sync::Arc;
+use std::time::Duration;
 
 use serde_json::Value as JsonValue;
 
+use crate::app::config::InstrumentsConfig;
+use crate::app::stream_types::ExchangeId;
 use crate::error::{AppError, AppResult};
 use crate::ingest::config::ExchangeConfigs;
 use crate::ingest::http::ApiClient;
+use crate::ingest::http::api_client::is_retryable;
 use crate::ingest::metrics::IngestMetrics;
 use crate::ingest::rate_limiter::RateLimiterRegistry;
 use crate::ingest::spec::resolve::resolve_http_request;
@@ -19,6 +24,56 @@ use crate::ingest::datamap::sources::binance_linear::types::BinanceLinearExchang
 use crate::ingest::datamap::sources::hyperliquid_perp::types::HyperliquidPerpInfoSnapshot;
 use crate::ingest::instruments::spec::{InstrumentKind, InstrumentSpec, QtyUnit};
 
+/// Hyperliquid perps: price decimals are capped at `MAX_DECIMALS - szDecimals`.
+const HYPERLIQUID_PERP_MAX_DECIMALS: u32 = 6;
+
+/// Bounded exponential backoff for the instrument load (see `load_all_with_retry`).
+#[derive(Debug, Clone, Copy)]
+pub struct LoadRetry {
+    pub attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl LoadRetry {
+    pub fn from_config(cfg: &InstrumentsConfig) -> Self {
+        Self {
+            attempts: cfg.load_retry_attempts,
+            initial_backoff: Duration::from_millis(cfg.load_retry_initial_backoff_ms),
+            max_backoff: Duration::from_millis(cfg.load_retry_max_backoff_ms),
+        }
+    }
+}
+
+/// Run `load` until it succeeds, fails with a non-transient error, or `retry.attempts`
+/// retries are used up. The last error is returned.
+pub async fn retry_transient<T, F, Fut>(retry: LoadRetry, mut load: F) -> AppResult<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = AppResult<T>>,
+{
+    let mut backoff = retry.initial_backoff;
+    let mut attempt = 0;
+    loop {
+        match load().await {
+            Ok(v) => return Ok(v),
+            Err(e) if attempt < retry.attempts && is_retryable(&e) => {
+                attempt += 1;
+                tracing::warn!(
+                    error = %e,
+                    attempt,
+                    max_attempts = retry.attempts,
+                    backoff_ms = backoff.as_millis() as u64,
+                    "instrument load failed (transient); retrying"
+                );
+                tokio::time::sleep(backoff).await;
+                backoff = std::cmp::min(retry.max_backoff, backoff * 2);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
 /// Loader that owns API clients and knows how to fetch+parse exchange metadata into `InstrumentSpec`s.
 ///
 /// Parsing is exchange-specific; we keep the fetch/resolve plumbing centralized here.
@@ -43,23 +98,35 @@ impl InstrumentSpecLoader {
         let ctx = Ctx::new();
 
         // Build clients only if that exchange config exists.
-        let binance_client = exchange_configs.binance_linear.as_ref().map(|binance| {
-            ApiClient::new(
-                "binance_exchange_info",
-                binance.api_base_url.clone(),
-                limiter_registry.clone(),
-                metrics.clone(),
-            )
-        });
-
-        let hyperliquid_client = exchange_configs.hyperliquid_perp.as_ref().map(|hyper| {
-            ApiClient::new(
-                "hyperliquid_exchange_info",
-                hyper.api_base_url.clone(),
-                limiter_registry.clone(),
-                metrics.clone(),
-            )
-        });
+        let binance_client = exchange_configs
+            .binance_linear
+            .as_ref()
+            .map(|binance| {
+                ApiClient::new(
+                    "binance_exchange_info",
+                    binance.api_base_url.clone(),
+                    limiter_registry.clone(),
+                    metrics.clone(),
+                )
+                .with_max_response_bytes(binance.api_max_response_bytes)
+                .with_default_headers(&binance.resolved_headers)
+            })
+            .transpose()?;
+
+        let hyperliquid_client = exchange_configs
+            .hyperliquid_perp
+            .as_ref()
+            .map(|hyper| {
+                ApiClient::new(
+                    "hyperliquid_exchange_info",
+                    hyper.api_base_url.clone(),
+                    limiter_registry.clone(),
+                    metrics.clone(),
+                )
+                .with_max_response_bytes(hyper.api_max_response_bytes)
+                .with_default_headers(&hyper.resolved_headers)
+            })
+            .transpose()?;
 
         Ok(Self {
             ctx,
@@ -84,6 +151,11 @@ impl InstrumentSpecLoader {
         Ok(out)
     }
 
+    /// `load_all`, retried with backoff on transient errors (network blips, 5xx, 429).
+    pub async fn load_all_with_retry(&self, retry: LoadRetry) -> AppResult<Vec<InstrumentSpec>> {
+        retry_transient(retry, || self.load_all()).await
+    }
+
     /// Load Binance linear exchange info and parse into instrument specs.
     ///
     pub async fn load_binance_linear_instrument_specs(&self) -> AppResult<Vec<InstrumentSpec>> {
@@ -106,10 +178,9 @@ impl InstrumentSpecLoader {
             AppError::Internal("binance_client not initialized (binance_linear missing?)".into())
         })?;
 
-        let resp = client.execute(&req_spec).await?;
-        let body = resp.text().await.map_err(AppError::Reqwest)?;
+        let body = client.execute_bytes(&req_spec).await?;
 
-        let json: JsonValue = serde_json::from_str(&body).map_err(AppError::Json)?;
+        let json: JsonValue = serde_json::from_slice(&body).map_err(AppError::Json)?;
 
         // Exchange-specific parsing (stub for now)
         self.parse_binance_linear_exchange_info(&json)
@@ -138,10 +209,9 @@ impl InstrumentSpecLoader {
             )
         })?;
 
-        let resp = client.execute(&req_spec).await?;
-        let body = resp.text().await.map_err(AppError::Reqwest)?;
+        let body = client.execute_bytes(&req_spec).await?;
 
-        let json: JsonValue = serde_json::from_str(&body).map_err(AppError::Json)?;
+        let json: JsonValue = serde_json::from_slice(&body).map_err(AppError::Json)?;
 
         // Exchange-specific parsing (stub for now)
         self.parse_hyperliquid_perp_exchange_info(&json)
@@ -192,15 +262,20 @@ impl InstrumentSpecLoader {
             // Onboard date exists in payload; store it.
             let onboard_date_ms = Some(s.onboard_date_ms);
 
-            out.push(InstrumentSpec::new(
-                "binance_linear",
-                s.symbol,
-                kind,
-                reported_qty_unit,
-                contract_size,
-                delivery_date_ms,
-                onboard_date_ms,
-            )?);
+            let (price_decimals, qty_decimals) = (s.price_precision, s.quantity_precision);
+
+            out.push(
+                InstrumentSpec::new(
+                    ExchangeId::BinanceLinear.as_str(),
+                    s.symbol,
+                    kind,
+                    reported_qty_unit,
+                    contract_size,
+                    delivery_date_ms,
+                    onboard_date_ms,
+                )?
+                .with_precision(Some(price_decimals), Some(qty_decimals)),
+            );
         }
 
         Ok(out)
@@ -233,15 +308,22 @@ impl InstrumentSpecLoader {
             let delivery_date_ms = None;
             let onboard_date_ms = None;
 
-            out.push(InstrumentSpec::new(
-                "hyperliquid_perp",
-                u.name, // e.g. "BTC"
-                kind,
-                reported_qty_unit,
-                contract_size,
-                delivery_date_ms,
-                onboard_date_ms,
-            )?);
+            // Perp prices carry at most 6 - szDecimals decimals; sizes at most szDecimals.
+            let price_decimals = HYPERLIQUID_PERP_MAX_DECIMALS.saturating_sub(u.sz_decimals);
+            let qty_decimals = u.sz_decimals;
+
+            out.push(
+                InstrumentSpec::new(
+                    ExchangeId::HyperliquidPerp.as_str(),
+                    u.name, // e.g. "BTC"
+                    kind,
+                    reported_qty_unit,
+                    contract_size,
+                    delivery_date_ms,
+                    onboard_date_ms,
+                )?
+                .with_precision(Some(price_decimals), Some(qty_decimals)),
+            );
         }
 
         Ok(out)
@@ -279,6 +361,56 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn transient_load_errors_are_retried_permanent_ones_are_not() {
+        use std::sync::atomic::{AtomicU32, Ordering};
+
+        let retry = LoadRetry {
+            attempts: 3,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(2),
+        };
+        let unavailable = || AppError::Api {
+            service: "hyperliquid_exchange_info".into(),
+            status: reqwest::StatusCode::SERVICE_UNAVAILABLE,
+            body: String::new(),
+        };
+
+        // Flaky: fails once, succeeds on the second attempt
+        let calls = AtomicU32::new(0);
+        let specs = retry_transient(retry, || async {
+            if calls.fetch_add(1, Ordering::Relaxed) == 0 {
+                Err(unavailable())
+            } else {
+                Ok(vec!["BTC"])
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(specs, ["BTC"]);
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+
+        // Permanent: no retry
+        let calls = AtomicU32::new(0);
+        let res: AppResult<()> = retry_transient(retry, || async {
+            calls.fetch_add(1, Ordering::Relaxed);
+            Err(AppError::InvalidConfig("api.exchange_info missing".into()))
+        })
+        .await;
+        assert!(matches!(res, Err(AppError::InvalidConfig(_))));
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+        // Still down after every retry: the last error surfaces
+        let calls = AtomicU32::new(0);
+        let res: AppResult<()> = retry_transient(retry, || async {
+            calls.fetch_add(1, Ordering::Relaxed);
+            Err(unavailable())
+        })
+        .await;
+        assert!(matches!(res, Err(AppError::Api { .. })));
+        assert_eq!(calls.load(Ordering::Relaxed), 4);
+    }
+
     #[tokio::test]
     async fn load_and_print_future_linear_instruments() -> AppResult<()> {
         let appconfig = load_app_config(false, 0)?;
@@ -1,8 +1,10 @@
 mod inspect;
 pub mod loader;
 pub mod registry;
+pub mod schedule;
 pub mod spec;
 
 pub use loader::*;
 pub use registry::*;
+pub use schedule::*;
 pub use spec::*;
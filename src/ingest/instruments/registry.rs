@@ -4,11 +4,16 @@
 //! Loader returns a flat Vec; this registry builds fast lookup tables on top.
 //!
 //! Duplicates by (exchange, symbol) are disallowed (build + update).
+//! The registry remembers when it was loaded so callers can refuse stale metadata.
+//! `scale_mismatches` cross-checks declared precision against the global fixed-point scales;
+//! `check_scale_round_trip` scales one tick / step of every instrument and back (startup).
 
 use std::collections::{BTreeMap, HashMap, HashSet};
 
+use crate::app::config::{CheckMode, ScalesConfig};
 use crate::error::{AppError, AppResult};
-use crate::ingest::instruments::spec::{InstrumentKind, InstrumentSpec};
+use crate::ingest::instruments::spec::{InstrumentKind, InstrumentSpec, QtyUnit};
+use rust_decimal::Decimal;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -27,6 +32,30 @@ impl InstrumentKey {
     }
 }
 
+/// An instrument whose declared decimals the global scale cannot represent
+/// (every value past `scale_decimals` is truncated on write).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScaleMismatch {
+    pub exchange: String,
+    pub symbol: String,
+    /// "price" | "qty"
+    pub field: &'static str,
+    pub declared_decimals: u32,
+    pub scale_decimals: u32,
+}
+
+/// An instrument whose smallest price/qty increment does not survive scaling to i64 and back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScaleRoundTripFailure {
+    pub exchange: String,
+    pub symbol: String,
+    /// "price" | "qty"
+    pub field: &'static str,
+    /// One tick (price) or one step in BASE (qty).
+    pub step: Decimal,
+    pub scale: i64,
+}
+
 #[derive(Debug, Clone)]
 pub struct InstrumentRegistry {
     specs: Vec<InstrumentSpec>,
@@ -38,6 +67,9 @@ pub struct InstrumentRegistry {
     by_exchange: HashMap<String, Vec<usize>>,
     by_kind: HashMap<InstrumentKind, Vec<usize>>,
     by_exchange_kind: HashMap<(String, InstrumentKind), Vec<usize>>,
+
+    // When the specs were fetched (epoch ms); reset by `update`
+    loaded_at_ms: u64,
 }
 
 impl InstrumentRegistry {
@@ -49,6 +81,7 @@ impl InstrumentRegistry {
             by_exchange: HashMap::new(),
             by_kind: HashMap::new(),
             by_exchange_kind: HashMap::new(),
+            loaded_at_ms: Self::now_ms(),
         };
 
         reg.insert_many(specs)?;
@@ -69,9 +102,45 @@ impl InstrumentRegistry {
         // 3) Insert + sort
         self.insert_many(new_specs)?;
         self.sort_indices_by_symbol();
+        self.loaded_at_ms = Self::now_ms();
         Ok(())
     }
 
+    /// Override the load timestamp (e.g. specs restored from a cache written earlier).
+    pub fn with_loaded_at_ms(mut self, loaded_at_ms: u64) -> Self {
+        self.loaded_at_ms = loaded_at_ms;
+        self
+    }
+
+    #[inline]
+    pub fn loaded_at_ms(&self) -> u64 {
+        self.loaded_at_ms
+    }
+
+    #[inline]
+    pub fn age_ms(&self, now_ms: u64) -> u64 {
+        now_ms.saturating_sub(self.loaded_at_ms)
+    }
+
+    /// True if older than `max_age_ms` (0 = never stale).
+    #[inline]
+    pub fn is_stale(&self, max_age_ms: u64, now_ms: u64) -> bool {
+        max_age_ms > 0 && self.age_ms(now_ms) > max_age_ms
+    }
+
+    /// Error (`registry_stale`) if the registry is older than `max_age_ms`.
+    pub fn ensure_fresh(&self, max_age_ms: u64) -> AppResult<()> {
+        let now_ms = Self::now_ms();
+        if !self.is_stale(max_age_ms, now_ms) {
+            return Ok(());
+        }
+        Err(AppError::Disabled(format!(
+            "registry_stale: instruments registry is {}s old (max {}s)",
+            self.age_ms(now_ms) / 1000,
+            max_age_ms / 1000
+        )))
+    }
+
     #[inline]
     fn is_expired(spec: &InstrumentSpec) -> bool {
         match spec.delivery_date_ms {
@@ -229,6 +298,132 @@ impl InstrumentRegistry {
         out
     }
 
+    /// Instruments declaring more price/qty decimals than the scales of their exchange hold
+    /// (beyond `scales.max_decimal_mismatch`). Undeclared precision is skipped.
+    pub fn scale_mismatches(&self, scales: &ScalesConfig) -> Vec<ScaleMismatch> {
+        let slack = scales.max_decimal_mismatch;
+
+        let mut out = Vec::new();
+        for spec in &self.specs {
+            let ex_scales = scales.for_exchange(spec.exchange);
+            let price_decimals = ScalesConfig::decimals(ex_scales.price);
+            let qty_decimals = ScalesConfig::decimals(ex_scales.qty);
+            for (field, declared, scale_decimals) in [
+                ("price", spec.price_decimals, price_decimals),
+                ("qty", spec.qty_decimals, qty_decimals),
+            ] {
+                if let Some(declared) = declared
+                    && declared > scale_decimals.saturating_add(slack)
+                {
+                    out.push(ScaleMismatch {
+                        exchange: spec.exchange.to_string(),
+                        symbol: spec.symbol.clone(),
+                        field,
+                        declared_decimals: declared,
+                        scale_decimals,
+                    });
+                }
+            }
+        }
+        out
+    }
+
+    /// Log one warning per scale mismatch; returns the number of affected instruments.
+    pub fn warn_scale_mismatches(&self, scales: &ScalesConfig) -> usize {
+        let mismatches = self.scale_mismatches(scales);
+        for m in &mismatches {
+            tracing::warn!(
+                component = "registry",
+                exchange = %m.exchange,
+                symbol = %m.symbol,
+                field = m.field,
+                declared_decimals = m.declared_decimals,
+                scale_decimals = m.scale_decimals,
+                "scale_mismatch: configured scale cannot represent instrument precision (values truncated)"
+            );
+        }
+        mismatches
+            .iter()
+            .map(|m| (&m.exchange, &m.symbol))
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    /// Instruments whose price tick / qty step (`10^-decimals`; qty converted to BASE for
+    /// base-sized and linear-contract instruments) is not exactly `i64 / scale` at the scales
+    /// of their exchange. Undeclared precision and price-dependent qty units are skipped.
+    pub fn scale_round_trip_failures(&self, scales: &ScalesConfig) -> Vec<ScaleRoundTripFailure> {
+        let mut out = Vec::new();
+        for spec in &self.specs {
+            let ex_scales = scales.for_exchange(spec.exchange);
+            let increment = |decimals: Option<u32>| decimals.map(|d| Decimal::try_new(1, d).ok());
+            let qty_step = match (spec.reported_qty_unit, spec.kind) {
+                (QtyUnit::Base, _)
+                | (QtyUnit::Contracts, InstrumentKind::PerpLinear | InstrumentKind::FutureLinear) => {
+                    increment(spec.qty_decimals)
+                        .map(|s| s.and_then(|s| spec.qty_to_base(s, Decimal::ONE).ok()))
+                }
+                // BASE size of one step depends on the traded price
+                _ => None,
+            };
+            for (field, step, scale) in [
+                ("price", increment(spec.price_decimals), ex_scales.price),
+                ("qty", qty_step, ex_scales.qty),
+            ] {
+                let Some(step) = step else {
+                    continue;
+                };
+                let exact = step.is_some_and(|s| {
+                    InstrumentSpec::scale_i64(s, scale)
+                        .is_ok_and(|i| Decimal::from(i) / Decimal::from(scale) == s)
+                });
+                if !exact {
+                    out.push(ScaleRoundTripFailure {
+                        exchange: spec.exchange.to_string(),
+                        symbol: spec.symbol.clone(),
+                        field,
+                        // Finer than Decimal holds: reported as zero
+                        step: step.unwrap_or_default(),
+                        scale,
+                    });
+                }
+            }
+        }
+        out
+    }
+
+    /// Startup round-trip check (`scales.round_trip_check`): log every failure, and with
+    /// `error` refuse to start. Returns the number of affected instruments.
+    pub fn check_scale_round_trip(&self, scales: &ScalesConfig) -> AppResult<usize> {
+        if scales.round_trip_check == CheckMode::Off {
+            return Ok(0);
+        }
+        let failures = self.scale_round_trip_failures(scales);
+        for f in &failures {
+            tracing::warn!(
+                component = "registry",
+                exchange = %f.exchange,
+                symbol = %f.symbol,
+                field = f.field,
+                step = %f.step,
+                scale = f.scale,
+                "scale round trip lossy: one increment is not an exact fixed-point value"
+            );
+        }
+        let instruments = failures
+            .iter()
+            .map(|f| (&f.exchange, &f.symbol))
+            .collect::<HashSet<_>>()
+            .len();
+        if instruments > 0 && scales.round_trip_check == CheckMode::Error {
+            return Err(AppError::InvalidConfig(format!(
+                "scales cannot represent {instruments} instrument(s) exactly (first: {} {} {})",
+                failures[0].exchange, failures[0].symbol, failures[0].field
+            )));
+        }
+        Ok(instruments)
+    }
+
     // ---------------- internal ----------------
 
     fn insert_many(&mut self, specs: Vec<InstrumentSpec>) -> AppResult<()> {
@@ -299,7 +494,7 @@ impl InstrumentRegistry {
 mod tests {
     use super::*;
 
-    use crate::app::config::load_app_config;
+    use crate::app::config::{ScaleOverride, load_app_config};
     use crate::error::AppResult;
     use crate::ingest::config::ExchangeConfigs;
     use crate::ingest::instruments::loader::InstrumentSpecLoader;
@@ -309,6 +504,150 @@ mod tests {
     // Unit tests (fast)
     // ------------------------
 
+    #[test]
+    fn scale_mismatch_flags_instruments_finer_than_global_scales() -> AppResult<()> {
+        let spec = |symbol: &str, price_decimals: u32, qty_decimals: u32| {
+            InstrumentSpec::new(
+                "hyperliquid_perp",
+                symbol,
+                InstrumentKind::PerpLinear,
+                QtyUnit::Base,
+                Some(1.0),
+                None,
+                None,
+            )
+            .map(|s| s.with_precision(Some(price_decimals), Some(qty_decimals)))
+        };
+        let reg = InstrumentRegistry::build(vec![spec("BTC", 1, 5)?, spec("kPEPE", 6, 10)?])?;
+
+        let mut scales = ScalesConfig {
+            price: 100_000_000,
+            qty: 100_000_000,
+            open_interest: 100_000_000,
+            funding: 1_000_000_000_000,
+            max_decimal_mismatch: 0,
+            round_trip_check: CheckMode::Off,
+            exchanges: Default::default(),
+        };
+        assert_eq!(ScalesConfig::decimals(scales.qty), 8);
+
+        // szDecimals 10 cannot be held by a 1e8 qty scale
+        assert_eq!(
+            reg.scale_mismatches(&scales),
+            vec![ScaleMismatch {
+                exchange: "hyperliquid_perp".into(),
+                symbol: "kPEPE".into(),
+                field: "qty",
+                declared_decimals: 10,
+                scale_decimals: 8,
+            }]
+        );
+        assert_eq!(reg.warn_scale_mismatches(&scales), 1);
+
+        // Coarser price scale flags both instruments (kPEPE twice, counted once)
+        scales.price = 100_000;
+        assert_eq!(reg.scale_mismatches(&scales).len(), 2);
+        assert_eq!(reg.warn_scale_mismatches(&scales), 1);
+        scales.price = 10;
+        assert_eq!(reg.warn_scale_mismatches(&scales), 1);
+        scales.price = 1;
+        assert_eq!(reg.warn_scale_mismatches(&scales), 2);
+
+        // Slack tolerates small shortfalls
+        scales.price = 100_000_000;
+        scales.max_decimal_mismatch = 2;
+        assert!(reg.scale_mismatches(&scales).is_empty());
+
+        // A finer qty scale for hyperliquid alone also covers kPEPE
+        scales.max_decimal_mismatch = 0;
+        scales.exchanges.insert(
+            "hyperliquid_perp".into(),
+            ScaleOverride {
+                qty: Some(10_000_000_000),
+                ..Default::default()
+            },
+        );
+        assert!(reg.scale_mismatches(&scales).is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn scale_round_trip_flags_increments_finer_than_the_scale() -> AppResult<()> {
+        let reg = InstrumentRegistry::build(vec![
+            InstrumentSpec::new(
+                "binance_linear",
+                "BTCUSDT",
+                InstrumentKind::PerpLinear,
+                QtyUnit::Base,
+                Some(1.0),
+                None,
+                None,
+            )?
+            .with_precision(Some(2), Some(3)),
+            // qty step of 1e-10 BASE: finer than a 1e8 qty scale
+            InstrumentSpec::new(
+                "hyperliquid_perp",
+                "kPEPE",
+                InstrumentKind::PerpLinear,
+                QtyUnit::Base,
+                Some(1.0),
+                None,
+                None,
+            )?
+            .with_precision(Some(6), Some(10)),
+            // 6 declared decimals fit, but one contract is 0.001 BASE: 1e-9 BASE per step
+            InstrumentSpec::new(
+                "binance_linear",
+                "1000SHIBUSDT",
+                InstrumentKind::PerpLinear,
+                QtyUnit::Contracts,
+                Some(0.001),
+                None,
+                None,
+            )?
+            .with_precision(Some(6), Some(6)),
+        ])?;
+        let mut scales = ScalesConfig {
+            price: 100_000_000,
+            qty: 100_000_000,
+            open_interest: 100_000_000,
+            funding: 1_000_000_000_000,
+            max_decimal_mismatch: 0,
+            round_trip_check: CheckMode::Off,
+            exchanges: Default::default(),
+        };
+
+        let failures = reg.scale_round_trip_failures(&scales);
+        assert_eq!(
+            failures
+                .iter()
+                .map(|f| (f.symbol.as_str(), f.field, f.step.to_string()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("kPEPE", "qty", "0.0000000001".to_string()),
+                ("1000SHIBUSDT", "qty", "0.000000001".to_string()),
+            ]
+        );
+        // The declared-decimals check alone misses the contract-sized instrument
+        assert_eq!(reg.scale_mismatches(&scales).len(), 1);
+
+        // Off skips the check, warn reports, error refuses to start
+        assert_eq!(reg.check_scale_round_trip(&scales)?, 0);
+        scales.round_trip_check = CheckMode::Warn;
+        assert_eq!(reg.check_scale_round_trip(&scales)?, 2);
+        scales.round_trip_check = CheckMode::Error;
+        let err = reg.check_scale_round_trip(&scales).unwrap_err();
+        assert!(matches!(err, AppError::InvalidConfig(_)), "{err:?}");
+
+        // A finer qty scale represents both
+        scales.qty = 10_000_000_000;
+        assert!(reg.scale_round_trip_failures(&scales).is_empty());
+        assert_eq!(reg.check_scale_round_trip(&scales)?, 0);
+
+        Ok(())
+    }
+
     #[test]
     fn build_rejects_duplicates() -> AppResult<()> {
         let a = InstrumentSpec::new(
@@ -367,6 +706,42 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn stale_registry_is_refused() -> AppResult<()> {
+        let spec = InstrumentSpec::new(
+            "binance_linear",
+            "BTCUSDT",
+            InstrumentKind::PerpLinear,
+            QtyUnit::Base,
+            Some(1.0),
+            None,
+            None,
+        )?;
+        let day_ms = 86_400_000;
+
+        // Fresh registry passes the guard
+        let reg = InstrumentRegistry::build(vec![spec.clone()])?;
+        assert!(reg.ensure_fresh(day_ms).is_ok());
+
+        // Loaded from a cache written three days ago
+        let now = InstrumentRegistry::now_ms();
+        let mut reg = InstrumentRegistry::build(vec![spec])?.with_loaded_at_ms(now - 3 * day_ms);
+        assert!(reg.is_stale(day_ms, now));
+        let err = reg
+            .ensure_fresh(day_ms)
+            .expect_err("stale registry must be refused");
+        assert!(matches!(err, AppError::Disabled(_)));
+        assert!(err.to_string().contains("registry_stale"), "{err}");
+
+        // Guard disabled
+        assert!(reg.ensure_fresh(0).is_ok());
+
+        // A successful refresh resets the clock
+        reg.update(vec![])?;
+        assert!(reg.ensure_fresh(day_ms).is_ok());
+        Ok(())
+    }
+
     // ------------------------
     // Integration tests (live HTTP)
     // ------------------------
@@ -0,0 +1,170 @@
+//! instruments/schedule.rs
+//!
+//! Trading schedule of an instrument: when the venue is expected to send data.
+//!
+//! Crypto venues trade 24/7 (`TradingSchedule::AlwaysOpen`, the default). Traditional
+//! instruments (futures with sessions) are silent while the market is closed, which must not
+//! read as a stalled stream. Sessions are weekly windows in UTC at minute resolution; a
+//! window may wrap past the end of the week (e.g. Sunday 23:00 -> Friday 22:00).
+
+use crate::error::{AppError, AppResult};
+use chrono::{DateTime, Datelike, Duration, Timelike, Utc, Weekday};
+use serde::Serialize;
+use std::str::FromStr;
+
+const MINUTES_PER_WEEK: u32 = 7 * 24 * 60;
+
+/// One weekly open window, as minutes since Monday 00:00 UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Session {
+    pub open_min: u32,
+    pub close_min: u32,
+}
+
+impl Session {
+    /// Window from `open` (weekday, hour, minute) to `close`, both UTC.
+    pub fn weekly(open: (Weekday, u32, u32), close: (Weekday, u32, u32)) -> Self {
+        Self {
+            open_min: minute_of_week_parts(open.0, open.1, open.2),
+            close_min: minute_of_week_parts(close.0, close.1, close.2),
+        }
+    }
+
+    /// True if `minute` (of the week) falls inside the window (open inclusive, close exclusive).
+    fn contains(&self, minute: u32) -> bool {
+        if self.open_min <= self.close_min {
+            (self.open_min..self.close_min).contains(&minute)
+        } else {
+            minute >= self.open_min || minute < self.close_min
+        }
+    }
+}
+
+/// `"Sun 23:00-Fri 22:00"` (UTC), as written in `health.stall.sessions`.
+impl FromStr for Session {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> AppResult<Self> {
+        let invalid = || {
+            AppError::InvalidConfig(format!(
+                "invalid trading session `{s}` (expected e.g. `Sun 23:00-Fri 22:00`, UTC)"
+            ))
+        };
+        let bound = |b: &str| -> AppResult<(Weekday, u32, u32)> {
+            let (day, time) = b.trim().split_once(' ').ok_or_else(invalid)?;
+            let (hour, minute) = time.trim().split_once(':').ok_or_else(invalid)?;
+            let day = day.parse::<Weekday>().map_err(|_| invalid())?;
+            let hour = hour
+                .parse::<u32>()
+                .ok()
+                .filter(|h| *h < 24)
+                .ok_or_else(invalid)?;
+            let minute = minute
+                .parse::<u32>()
+                .ok()
+                .filter(|m| *m < 60)
+                .ok_or_else(invalid)?;
+            Ok((day, hour, minute))
+        };
+        let (open, close) = s.split_once('-').ok_or_else(invalid)?;
+        Ok(Self::weekly(bound(open)?, bound(close)?))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub enum TradingSchedule {
+    /// 24/7 (crypto).
+    #[default]
+    AlwaysOpen,
+    /// Open only inside these weekly sessions.
+    Weekly(Vec<Session>),
+}
+
+impl TradingSchedule {
+    pub fn is_always_open(&self) -> bool {
+        matches!(self, TradingSchedule::AlwaysOpen)
+    }
+
+    /// True if data is expected at `now`.
+    pub fn is_open(&self, now: DateTime<Utc>) -> bool {
+        match self {
+            TradingSchedule::AlwaysOpen => true,
+            TradingSchedule::Weekly(sessions) => {
+                let m = minute_of_week(now);
+                sessions.iter().any(|s| s.contains(m))
+            }
+        }
+    }
+
+    /// Start of the session open at `now` (None when closed, or always open).
+    pub fn session_start(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let TradingSchedule::Weekly(sessions) = self else {
+            return None;
+        };
+        let m = minute_of_week(now);
+        let since_open = sessions
+            .iter()
+            .filter(|s| s.contains(m))
+            .map(|s| (m + MINUTES_PER_WEEK - s.open_min) % MINUTES_PER_WEEK)
+            .max()?;
+        let minute_floor = now
+            - Duration::seconds(now.second() as i64)
+            - Duration::nanoseconds(now.nanosecond() as i64);
+        Some(minute_floor - Duration::minutes(since_open as i64))
+    }
+}
+
+fn minute_of_week_parts(day: Weekday, hour: u32, minute: u32) -> u32 {
+    (day.num_days_from_monday() * 24 * 60 + hour * 60 + minute) % MINUTES_PER_WEEK
+}
+
+fn minute_of_week(t: DateTime<Utc>) -> u32 {
+    minute_of_week_parts(t.weekday(), t.hour(), t.minute())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn weekly_sessions_wrap_the_week() {
+        // CME-style: Sunday 23:00 -> Friday 22:00 UTC
+        let s = TradingSchedule::Weekly(vec![Session::weekly(
+            (Weekday::Sun, 23, 0),
+            (Weekday::Fri, 22, 0),
+        )]);
+
+        // 2024-01-06 is a Saturday
+        let sat = Utc.with_ymd_and_hms(2024, 1, 6, 12, 0, 0).unwrap();
+        let sun_open = Utc.with_ymd_and_hms(2024, 1, 7, 23, 0, 0).unwrap();
+        let wed = Utc.with_ymd_and_hms(2024, 1, 10, 9, 30, 15).unwrap();
+        assert!(!s.is_open(sat));
+        assert!(s.is_open(sun_open));
+        assert!(s.is_open(wed));
+        assert!(!s.is_open(Utc.with_ymd_and_hms(2024, 1, 12, 22, 0, 0).unwrap()));
+
+        assert_eq!(s.session_start(wed), Some(sun_open));
+        assert_eq!(s.session_start(sat), None);
+        assert!(TradingSchedule::AlwaysOpen.is_open(sat));
+    }
+
+    #[test]
+    fn sessions_parse_from_config_strings() {
+        assert_eq!(
+            "Sun 23:00-Fri 22:00".parse::<Session>().unwrap(),
+            Session::weekly((Weekday::Sun, 23, 0), (Weekday::Fri, 22, 0))
+        );
+        for bad in [
+            "Sun 23:00",
+            "Sun 24:00-Fri 22:00",
+            "Someday 1:00-Fri 2:00",
+            "",
+        ] {
+            assert!(matches!(
+                bad.parse::<Session>(),
+                Err(AppError::InvalidConfig(_))
+            ));
+        }
+    }
+}
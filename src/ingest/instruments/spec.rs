@@ -1,4 +1,5 @@
 use crate::error::{AppError, AppResult};
+use crate::ingest::instruments::schedule::TradingSchedule;
 use rust_decimal::Decimal;
 use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
 use serde::{Deserialize, Serialize};
@@ -34,6 +35,12 @@ pub struct InstrumentSpec {
     pub contract_size: Option<f64>,
     pub delivery_date_ms: Option<u64>,
     pub onboard_date_ms: Option<u64>,
+    /// Max fractional digits the venue is expected to send for prices (None = unchecked).
+    pub price_decimals: Option<u32>,
+    /// Max fractional digits the venue is expected to send for quantities (None = unchecked).
+    pub qty_decimals: Option<u32>,
+    /// When the venue sends data (24/7 unless set); closed sessions are not stalls.
+    pub schedule: TradingSchedule,
 }
 
 impl InstrumentSpec {
@@ -108,9 +115,47 @@ impl InstrumentSpec {
             contract_size,
             delivery_date_ms,
             onboard_date_ms,
+            price_decimals: None,
+            qty_decimals: None,
+            schedule: TradingSchedule::AlwaysOpen,
         })
     }
 
+    /// Declare the expected price/qty decimal precision (from exchange metadata).
+    pub fn with_precision(
+        mut self,
+        price_decimals: Option<u32>,
+        qty_decimals: Option<u32>,
+    ) -> Self {
+        self.price_decimals = price_decimals;
+        self.qty_decimals = qty_decimals;
+        self
+    }
+
+    /// Declare the trading sessions (traditional instruments; crypto stays 24/7).
+    pub fn with_schedule(mut self, schedule: TradingSchedule) -> Self {
+        self.schedule = schedule;
+        self
+    }
+
+    /// Number of significant fractional digits in a decimal string; trailing zeros are
+    /// padding, so `"0.010"` counts as 2.
+    pub fn fractional_digits(s: &str) -> u32 {
+        match s.trim().split_once('.') {
+            Some((_, frac)) => {
+                let digits = frac.bytes().take_while(u8::is_ascii_digit).count();
+                frac[..digits].trim_end_matches('0').len() as u32
+            }
+            None => 0,
+        }
+    }
+
+    /// True if `s` carries more fractional digits than `max_decimals` (None = never).
+    #[inline]
+    pub fn exceeds_precision(s: &str, max_decimals: Option<u32>) -> bool {
+        max_decimals.is_some_and(|max| Self::fractional_digits(s) > max)
+    }
+
     /// Parse a base-10 decimal from an exchange-provided string exactly.
     pub fn dec_str(s: &str) -> AppResult<Decimal> {
         Decimal::from_str(s)
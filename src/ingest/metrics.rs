@@ -1,14 +1,38 @@
 // src/ingest/metrics.rs
+use crate::app::metrics::ConstLabels;
+#[cfg(feature = "metrics")]
+use crate::app::metrics::registry_with_const_labels;
 use crate::error::{AppError, AppResult};
 
 #[cfg(feature = "metrics")]
-use prometheus::{Histogram, HistogramOpts, IntCounter, IntGauge, Opts, Registry};
+use prometheus::{
+    GaugeVec, Histogram, HistogramOpts, HistogramVec, IntCounter, IntGauge, Opts, Registry,
+};
+#[cfg(feature = "metrics")]
+use rand::Rng;
+#[cfg(feature = "metrics")]
+use std::collections::HashSet;
+#[cfg(feature = "metrics")]
+use std::sync::{Arc, Mutex};
+
+/// Default cap on distinct `stream` label values (see `with_max_stream_labels`).
+pub const DEFAULT_MAX_STREAM_LABELS: usize = 500;
 
 /// Minimal metrics for ingest pipelines (WS/HTTP -> queue -> process -> ack).
 ///
 /// No labels by design (avoid high-cardinality early). If you later want per-exchange,
 /// create one IngestMetrics instance per exchange/stream and serve them separately,
 /// or add const labels at registration time.
+///
+/// Exception: `ingest_last_event_timestamp_seconds{stream}` (liveness alerting). Its label set
+/// is capped; streams beyond the cap are counted in `ingest_stream_labels_dropped_total`.
+///
+/// `ingest_lag_seconds` can be sampled (`metrics.lag_sample_every`): each measurement is kept
+/// with probability 1/N, independently of its stream, so streams sharing these metrics cannot
+/// alias onto each other's turns. Its `_count` / `_sum` then cover ~1/N of the messages
+/// (multiply by N for approximate totals); quantiles come from N times fewer points, so a p99
+/// needs N times more traffic to be as stable and a quiet stream's tail may not show at all.
+/// Counters are never sampled.
 #[derive(Clone, Debug)]
 pub struct IngestMetrics {
     #[cfg(feature = "metrics")]
@@ -29,12 +53,28 @@ pub struct IngestMetrics {
     pub retried_total: IntCounter,
     #[cfg(feature = "metrics")]
     pub duplicates_total: IntCounter,
+    #[cfg(feature = "metrics")]
+    pub unchanged_suppressed_total: IntCounter,
+    /// Per-symbol entries held by the data-quality caches (store-on-change last values)
+    #[cfg(feature = "metrics")]
+    pub dq_cache_entries: IntGauge,
+    #[cfg(feature = "metrics")]
+    pub dq_cache_evicted_total: IntCounter,
+    #[cfg(feature = "metrics")]
+    pub precision_exceeded_total: IntCounter,
+    #[cfg(feature = "metrics")]
+    pub funding_out_of_range_total: IntCounter,
 
     // --- Backpressure / lag
     #[cfg(feature = "metrics")]
     pub queue_depth: IntGauge,
     #[cfg(feature = "metrics")]
     pub lag_seconds: Histogram,
+    #[cfg(feature = "metrics")]
+    lag_sample_every: u64,
+    /// REST snapshot resyncs queued behind the shared resync throttle
+    #[cfg(feature = "metrics")]
+    pub resync_pending: IntGauge,
 
     // --- Rate limiting
     #[cfg(feature = "metrics")]
@@ -56,6 +96,27 @@ pub struct IngestMetrics {
     pub ws_reconnect_rate_limited_total: IntCounter,
     #[cfg(feature = "metrics")]
     pub ws_reconnect_wait_seconds: Histogram,
+    /// Protocol ping -> matching pong of live connections, by exchange (or "all")
+    #[cfg(feature = "metrics")]
+    pub ws_ping_rtt_seconds: HistogramVec,
+    #[cfg(feature = "metrics")]
+    ping_rtt_by_exchange: bool,
+
+    // --- Liveness (per stream, capped)
+    #[cfg(feature = "metrics")]
+    pub last_event_timestamp_seconds: GaugeVec,
+    /// 1 = no event for `health.stall.stall_after_sec` while the market is open
+    #[cfg(feature = "metrics")]
+    pub stream_stalled: GaugeVec,
+    /// 1 = instrument trading session open (data expected), 0 = closed
+    #[cfg(feature = "metrics")]
+    pub stream_session_open: GaugeVec,
+    #[cfg(feature = "metrics")]
+    pub stream_labels_dropped_total: IntCounter,
+    #[cfg(feature = "metrics")]
+    stream_labels: Arc<Mutex<HashSet<String>>>,
+    #[cfg(feature = "metrics")]
+    max_stream_labels: usize,
 
     // no-op fallback data (keeps struct non-empty without feature)
     #[cfg(not(feature = "metrics"))]
@@ -64,9 +125,14 @@ pub struct IngestMetrics {
 
 impl IngestMetrics {
     pub fn new() -> AppResult<Self> {
+        Self::with_const_labels(&ConstLabels::new())
+    }
+
+    /// Like `new`, with `const_labels` on every series (`[metrics]` instance labels).
+    pub fn with_const_labels(const_labels: &ConstLabels) -> AppResult<Self> {
         #[cfg(feature = "metrics")]
         {
-            let registry = Registry::new();
+            let registry = registry_with_const_labels(const_labels)?;
 
             // --- Throughput
             let in_total = IntCounter::with_opts(Opts::new(
@@ -100,6 +166,31 @@ impl IngestMetrics {
                 "Duplicate deliveries detected total",
             ))?;
 
+            let unchanged_suppressed_total = IntCounter::with_opts(Opts::new(
+                "ingest_unchanged_suppressed_total",
+                "Rows not stored because the value did not change (store-on-change)",
+            ))?;
+
+            let dq_cache_entries = IntGauge::with_opts(Opts::new(
+                "ingest_dq_cache_entries",
+                "Per-symbol entries held by data-quality caches (store-on-change last values)",
+            ))?;
+
+            let dq_cache_evicted_total = IntCounter::with_opts(Opts::new(
+                "ingest_dq_cache_evicted_total",
+                "Data-quality cache entries evicted over writer.max_change_only_symbols",
+            ))?;
+
+            let precision_exceeded_total = IntCounter::with_opts(Opts::new(
+                "ingest_precision_exceeded_total",
+                "Price/qty strings with more decimals than the instrument declares",
+            ))?;
+
+            let funding_out_of_range_total = IntCounter::with_opts(Opts::new(
+                "ingest_funding_out_of_range_total",
+                "Funding rates dropped for exceeding the per-exchange plausibility bound",
+            ))?;
+
             // --- Backpressure / lag
             let queue_depth = IntGauge::with_opts(Opts::new(
                 "ingest_queue_depth",
@@ -111,6 +202,11 @@ impl IngestMetrics {
                 "End-to-end lag in seconds (now - message timestamp or enqueue time)",
             ))?;
 
+            let resync_pending = IntGauge::with_opts(Opts::new(
+                "ingest_resync_pending",
+                "REST depth snapshot resyncs waiting for the shared resync throttle",
+            ))?;
+
             // --- Rate limiting
             let rate_limited_total = IntCounter::with_opts(Opts::new(
                 "ingest_rate_limited_total",
@@ -148,6 +244,40 @@ impl IngestMetrics {
                 "ws_reconnect_wait_seconds",
                 "Time spent waiting to perform a WS reconnect attempt (seconds)",
             ))?;
+            let ws_ping_rtt_seconds = HistogramVec::new(
+                HistogramOpts::new(
+                    "ws_ping_rtt_seconds",
+                    "Round trip of a WS heartbeat ping to its matching pong (seconds)",
+                ),
+                &["exchange"],
+            )?;
+
+            // --- Liveness (per stream, capped)
+            let last_event_timestamp_seconds = GaugeVec::new(
+                Opts::new(
+                    "ingest_last_event_timestamp_seconds",
+                    "Unix time of the last processed event, per stream",
+                ),
+                &["stream"],
+            )?;
+            let stream_stalled = GaugeVec::new(
+                Opts::new(
+                    "ingest_stream_stalled",
+                    "1 if the stream sent nothing for too long while its market is open",
+                ),
+                &["stream"],
+            )?;
+            let stream_session_open = GaugeVec::new(
+                Opts::new(
+                    "ingest_stream_session_open",
+                    "1 if the stream's instrument is in a trading session (data expected)",
+                ),
+                &["stream"],
+            )?;
+            let stream_labels_dropped_total = IntCounter::with_opts(Opts::new(
+                "ingest_stream_labels_dropped_total",
+                "Per-stream label updates dropped by the cardinality cap",
+            ))?;
 
             // Register everything
             registry.register(Box::new(in_total.clone()))?;
@@ -156,8 +286,14 @@ impl IngestMetrics {
             registry.register(Box::new(errors_total.clone()))?;
             registry.register(Box::new(retried_total.clone()))?;
             registry.register(Box::new(duplicates_total.clone()))?;
+            registry.register(Box::new(unchanged_suppressed_total.clone()))?;
+            registry.register(Box::new(dq_cache_entries.clone()))?;
+            registry.register(Box::new(dq_cache_evicted_total.clone()))?;
+            registry.register(Box::new(precision_exceeded_total.clone()))?;
+            registry.register(Box::new(funding_out_of_range_total.clone()))?;
             registry.register(Box::new(queue_depth.clone()))?;
             registry.register(Box::new(lag_seconds.clone()))?;
+            registry.register(Box::new(resync_pending.clone()))?;
             registry.register(Box::new(rate_limited_total.clone()))?;
             registry.register(Box::new(rate_limit_wait_seconds.clone()))?;
 
@@ -167,6 +303,11 @@ impl IngestMetrics {
             registry.register(Box::new(ws_reconnect_attempts_total.clone()))?;
             registry.register(Box::new(ws_reconnect_rate_limited_total.clone()))?;
             registry.register(Box::new(ws_reconnect_wait_seconds.clone()))?;
+            registry.register(Box::new(ws_ping_rtt_seconds.clone()))?;
+            registry.register(Box::new(last_event_timestamp_seconds.clone()))?;
+            registry.register(Box::new(stream_stalled.clone()))?;
+            registry.register(Box::new(stream_session_open.clone()))?;
+            registry.register(Box::new(stream_labels_dropped_total.clone()))?;
 
             Ok(Self {
                 registry,
@@ -176,8 +317,15 @@ impl IngestMetrics {
                 errors_total,
                 retried_total,
                 duplicates_total,
+                unchanged_suppressed_total,
+                dq_cache_entries,
+                dq_cache_evicted_total,
+                precision_exceeded_total,
+                funding_out_of_range_total,
                 queue_depth,
                 lag_seconds,
+                lag_sample_every: 1,
+                resync_pending,
                 rate_limited_total,
                 rate_limit_wait_seconds,
                 ws_subscribe_attempts_total,
@@ -186,15 +334,51 @@ impl IngestMetrics {
                 ws_reconnect_attempts_total,
                 ws_reconnect_rate_limited_total,
                 ws_reconnect_wait_seconds,
+                ws_ping_rtt_seconds,
+                ping_rtt_by_exchange: true,
+                last_event_timestamp_seconds,
+                stream_stalled,
+                stream_session_open,
+                stream_labels_dropped_total,
+                stream_labels: Arc::new(Mutex::new(HashSet::new())),
+                max_stream_labels: DEFAULT_MAX_STREAM_LABELS,
             })
         }
 
         #[cfg(not(feature = "metrics"))]
         {
+            let _ = const_labels;
             Ok(Self { _noop: () })
         }
     }
 
+    /// Cap the number of distinct `stream` label values (0 keeps the default).
+    pub fn with_max_stream_labels(mut self, _max: usize) -> Self {
+        #[cfg(feature = "metrics")]
+        if _max > 0 {
+            self.max_stream_labels = _max;
+        }
+        self
+    }
+
+    /// Observe each lag measurement with probability 1/`every` (0 and 1 observe all of them).
+    pub fn with_lag_sample_every(mut self, _every: u64) -> Self {
+        #[cfg(feature = "metrics")]
+        {
+            self.lag_sample_every = _every.max(1);
+        }
+        self
+    }
+
+    /// Label `ws_ping_rtt_seconds` by exchange (default), or aggregate under "all".
+    pub fn with_ping_rtt_by_exchange(mut self, _by_exchange: bool) -> Self {
+        #[cfg(feature = "metrics")]
+        {
+            self.ping_rtt_by_exchange = _by_exchange;
+        }
+        self
+    }
+
     /// Encode metrics to Prometheus text format.
     #[cfg(feature = "metrics")]
     pub fn encode_text(&self) -> AppResult<String> {
@@ -250,16 +434,62 @@ impl IngestMetrics {
         self.duplicates_total.inc();
     }
 
+    #[inline]
+    pub fn add_unchanged_suppressed(&self, _n: u64) {
+        #[cfg(feature = "metrics")]
+        self.unchanged_suppressed_total.inc_by(_n);
+    }
+
+    #[inline]
+    pub fn add_dq_cache_entries(&self, _delta: i64) {
+        #[cfg(feature = "metrics")]
+        self.dq_cache_entries.add(_delta);
+    }
+
+    #[inline]
+    pub fn inc_dq_cache_evicted(&self) {
+        #[cfg(feature = "metrics")]
+        self.dq_cache_evicted_total.inc();
+    }
+
+    #[inline]
+    pub fn inc_precision_exceeded(&self) {
+        #[cfg(feature = "metrics")]
+        self.precision_exceeded_total.inc();
+    }
+
+    #[inline]
+    pub fn inc_funding_out_of_range(&self) {
+        #[cfg(feature = "metrics")]
+        self.funding_out_of_range_total.inc();
+    }
+
     #[inline]
     pub fn set_queue_depth(&self, _depth: i64) {
         #[cfg(feature = "metrics")]
         self.queue_depth.set(_depth);
     }
 
+    #[inline]
+    pub fn inc_resync_pending(&self) {
+        #[cfg(feature = "metrics")]
+        self.resync_pending.inc();
+    }
+
+    #[inline]
+    pub fn dec_resync_pending(&self) {
+        #[cfg(feature = "metrics")]
+        self.resync_pending.dec();
+    }
+
+    /// Sampled with `with_lag_sample_every` (see the type docs).
     #[inline]
     pub fn observe_lag(&self, _secs: f64) {
         #[cfg(feature = "metrics")]
-        self.lag_seconds.observe(_secs);
+        if self.lag_sample_every == 1 || rand::rng().random_bool(1.0 / self.lag_sample_every as f64)
+        {
+            self.lag_seconds.observe(_secs);
+        }
     }
 
     #[inline]
@@ -311,4 +541,228 @@ impl IngestMetrics {
         #[cfg(feature = "metrics")]
         self.ws_reconnect_wait_seconds.observe(_secs);
     }
+
+    #[inline]
+    pub fn observe_ws_ping_rtt(&self, _exchange: &str, _secs: f64) {
+        #[cfg(feature = "metrics")]
+        {
+            let label = if self.ping_rtt_by_exchange {
+                _exchange
+            } else {
+                "all"
+            };
+            self.ws_ping_rtt_seconds
+                .with_label_values(&[label])
+                .observe(_secs);
+        }
+    }
+
+    // --- Liveness helpers (safe to call unconditionally)
+
+    /// Set `ingest_last_event_timestamp_seconds{stream}` to now.
+    #[inline]
+    pub fn mark_stream_event(&self, _stream: &str) {
+        #[cfg(feature = "metrics")]
+        self.set_stream_last_event(
+            _stream,
+            chrono::Utc::now().timestamp_millis() as f64 / 1000.0,
+        );
+    }
+
+    /// Set the last-event time of `stream` (unix seconds). New labels beyond the cap are dropped.
+    pub fn set_stream_last_event(&self, _stream: &str, _unix_secs: f64) {
+        #[cfg(feature = "metrics")]
+        {
+            let mut labels = self
+                .stream_labels
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            if !labels.contains(_stream) {
+                if labels.len() >= self.max_stream_labels {
+                    self.stream_labels_dropped_total.inc();
+                    return;
+                }
+                labels.insert(_stream.to_string());
+            }
+            self.last_event_timestamp_seconds
+                .with_label_values(&[_stream])
+                .set(_unix_secs);
+        }
+    }
+
+    /// Drop the series of a removed stream (frees its slot under the cap).
+    pub fn forget_stream(&self, _stream: &str) {
+        #[cfg(feature = "metrics")]
+        {
+            let mut labels = self
+                .stream_labels
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            if labels.remove(_stream) {
+                let _ = self
+                    .last_event_timestamp_seconds
+                    .remove_label_values(&[_stream]);
+                let _ = self.stream_stalled.remove_label_values(&[_stream]);
+                let _ = self.stream_session_open.remove_label_values(&[_stream]);
+            }
+        }
+    }
+
+    /// Last-event time of `stream` (unix seconds); None if it has no series yet (or no metrics).
+    pub fn stream_last_event(&self, _stream: &str) -> Option<f64> {
+        #[cfg(feature = "metrics")]
+        {
+            let labels = self
+                .stream_labels
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            if labels.contains(_stream) {
+                return Some(
+                    self.last_event_timestamp_seconds
+                        .with_label_values(&[_stream])
+                        .get(),
+                );
+            }
+        }
+        None
+    }
+
+    /// True when `stream` has (or can still get) a last-event series: metrics on and its label
+    /// within the cap. Otherwise its silence says nothing.
+    pub fn stream_liveness_known(&self, _stream: &str) -> bool {
+        #[cfg(feature = "metrics")]
+        {
+            let labels = self
+                .stream_labels
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            if labels.contains(_stream) || labels.len() < self.max_stream_labels {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Publish the watchdog verdict of a stream that already has a liveness series.
+    pub fn set_stream_liveness(&self, _stream: &str, _stalled: bool, _session_open: bool) {
+        #[cfg(feature = "metrics")]
+        {
+            let labels = self
+                .stream_labels
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            if labels.contains(_stream) {
+                self.stream_stalled
+                    .with_label_values(&[_stream])
+                    .set(_stalled as i64 as f64);
+                self.stream_session_open
+                    .with_label_values(&[_stream])
+                    .set(_session_open as i64 as f64);
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use super::*;
+
+    fn last_event(m: &IngestMetrics, stream: &str) -> f64 {
+        m.last_event_timestamp_seconds
+            .with_label_values(&[stream])
+            .get()
+    }
+
+    #[test]
+    fn last_event_gauge_updates_per_stream() -> AppResult<()> {
+        let m = IngestMetrics::new()?;
+
+        m.set_stream_last_event("binance_linear:BTCUSDT:trades", 100.0);
+        m.set_stream_last_event("hyperliquid_perp:BTC:trades", 200.0);
+        assert_eq!(last_event(&m, "binance_linear:BTCUSDT:trades"), 100.0);
+        assert_eq!(last_event(&m, "hyperliquid_perp:BTC:trades"), 200.0);
+
+        // Every event moves only its own stream
+        m.set_stream_last_event("binance_linear:BTCUSDT:trades", 101.5);
+        assert_eq!(last_event(&m, "binance_linear:BTCUSDT:trades"), 101.5);
+        assert_eq!(last_event(&m, "hyperliquid_perp:BTC:trades"), 200.0);
+
+        // Wall clock
+        let before = chrono::Utc::now().timestamp() as f64;
+        m.mark_stream_event("hyperliquid_perp:BTC:trades");
+        assert!(last_event(&m, "hyperliquid_perp:BTC:trades") >= before);
+
+        let text = m.encode_text()?;
+        assert!(text.contains(
+            "ingest_last_event_timestamp_seconds{stream=\"binance_linear:BTCUSDT:trades\"} 101.5"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn stream_labels_are_capped() -> AppResult<()> {
+        let m = IngestMetrics::new()?.with_max_stream_labels(2);
+
+        m.set_stream_last_event("a", 1.0);
+        m.set_stream_last_event("b", 1.0);
+        m.set_stream_last_event("c", 1.0);
+        assert_eq!(m.stream_labels_dropped_total.get(), 1);
+        assert!(!m.encode_text()?.contains("stream=\"c\""));
+
+        // Known labels keep updating; a removed stream frees its slot
+        m.set_stream_last_event("a", 2.0);
+        assert_eq!(m.stream_labels_dropped_total.get(), 1);
+        m.forget_stream("b");
+        m.set_stream_last_event("c", 3.0);
+        assert_eq!(last_event(&m, "c"), 3.0);
+        assert!(!m.encode_text()?.contains("stream=\"b\""));
+        Ok(())
+    }
+
+    #[test]
+    fn lag_histogram_observes_one_in_n() -> AppResult<()> {
+        let m = IngestMetrics::new()?.with_lag_sample_every(10);
+        for _ in 0..10_000 {
+            m.observe_lag(0.25);
+        }
+        // Binomial(10_000, 0.1): mean 1_000, sd 30
+        let n = m.lag_seconds.get_sample_count();
+        assert!((850..=1_150).contains(&n), "{n}");
+        assert_eq!(m.lag_seconds.get_sample_sum(), 0.25 * n as f64);
+
+        // Two streams taking turns on every other message: both still show up (a shared
+        // 1-in-2 counter would only ever see the first one)
+        let m = IngestMetrics::new()?.with_lag_sample_every(2);
+        for _ in 0..2_000 {
+            m.observe_lag(0.0);
+            m.observe_lag(1.0);
+        }
+        let mean = m.lag_seconds.get_sample_sum() / m.lag_seconds.get_sample_count() as f64;
+        assert!((0.4..=0.6).contains(&mean), "{mean}");
+
+        // Default (and 0) observe every message
+        let all = IngestMetrics::new()?.with_lag_sample_every(0);
+        (0..7).for_each(|_| all.observe_lag(0.1));
+        assert_eq!(all.lag_seconds.get_sample_count(), 7);
+        Ok(())
+    }
+
+    #[test]
+    fn ping_rtt_exchange_label_is_optional() -> AppResult<()> {
+        let m = IngestMetrics::new()?;
+        m.observe_ws_ping_rtt("binance_linear", 0.05);
+        let rtt = |m: &IngestMetrics, label: &str| {
+            m.ws_ping_rtt_seconds
+                .with_label_values(&[label])
+                .get_sample_count()
+        };
+        assert_eq!(rtt(&m, "binance_linear"), 1);
+
+        let agg = IngestMetrics::new()?.with_ping_rtt_by_exchange(false);
+        agg.observe_ws_ping_rtt("binance_linear", 0.05);
+        agg.observe_ws_ping_rtt("hyperliquid_perp", 0.05);
+        assert_eq!(rtt(&agg, "all"), 2);
+        assert_eq!(rtt(&agg, "binance_linear"), 0);
+        Ok(())
+    }
 }
@@ -1,9 +1,18 @@
-use super::template::{render_params_as_query, render_string, render_toml_as_json};
+use super::template::{
+    collect_used_keys, render_params_as_query, render_string, render_toml_as_json,
+    render_toml_strict,
+};
 use super::types::{Ctx, HttpRequestSpec, ParamPlacement, WsControlSpec};
 use crate::error::{AppError, AppResult};
-use crate::ingest::config::{ApiEndpoint, ExchangeConfig, WsStream};
+use crate::ingest::config::{ApiEndpoint, ExchangeConfig, TableValue, WsStream};
+use crate::telemetry::throttle::log_throttle;
+use rayon::prelude::*;
 use reqwest::Method;
-use std::collections::BTreeMap;
+use serde_json::Value as JsonValue;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
 
 /// Parse an HTTP method string from config into reqwest::Method.
 pub fn parse_method(method: &str) -> AppResult<Method> {
@@ -14,7 +23,8 @@ pub fn parse_method(method: &str) -> AppResult<Method> {
 /// Resolve an ApiEndpoint + ctx into a concrete HttpRequestSpec.
 ///
 /// ParamPlacement decides whether `params` becomes query params (Binance GET style)
-/// or JSON body (Hyperliquid POST /info style).
+/// or JSON body (Hyperliquid POST /info style). A JSON body that does not match the
+/// endpoint's `body_shape` is a config error, raised here rather than by the venue.
 pub fn resolve_http_request(
     ep: &ApiEndpoint,
     ctx: &Ctx,
@@ -30,15 +40,31 @@ pub fn resolve_http_request(
         json_body: None,
         weight: ep.weight as u32,
         interval_seconds: ep.interval_seconds,
+        max_response_bytes: ep.max_response_bytes,
+        timing: ep.timing.clone(),
+        time_field: ep.time_field.clone(),
     };
 
     if let Some(params) = &ep.params {
+        let (_, unused) = render_toml_strict(params, ctx)?;
+        if !unused.is_empty() {
+            warn_orphaned_ctx_keys(&format!("{} {}", ep.method, ep.endpoint), &unused);
+        }
         match placement {
             ParamPlacement::Query => {
                 spec.query = render_params_as_query(params, ctx)?;
             }
             ParamPlacement::JsonBody => {
-                spec.json_body = Some(render_toml_as_json(params, ctx)?);
+                let body = render_toml_as_json(params, ctx, ep.coerce_scalars)?;
+                if let Some(shape) = &ep.body_shape {
+                    shape.check(&body).map_err(|e| {
+                        AppError::InvalidConfig(format!(
+                            "{} {} params do not match body_shape: {e} (rendered body: {body})",
+                            ep.method, ep.endpoint
+                        ))
+                    })?;
+                }
+                spec.json_body = Some(body);
             }
         }
     }
@@ -87,6 +113,9 @@ pub fn seed_ws_stream_ctx(stream: &WsStream, ctx: &mut Ctx) -> AppResult<()> {
 /// 1) resolve_ws_stream_title(...) to get a stream title
 /// 2) put it into ctx as "stream_title" (stringified or already-json depending on your template style)
 /// 3) call this function to render the final messages.
+///
+/// A template that is an array renders to one message per entry (multi-step handshakes, e.g.
+/// auth then subscribe), kept in order.
 pub fn resolve_ws_control(config: &ExchangeConfig, ctx: &Ctx) -> AppResult<WsControlSpec> {
     let sub_tpl = config.ws_subscribe_msg.as_ref().ok_or_else(|| {
         AppError::InvalidConfig("ws_subscribe_msg missing in exchange config".into())
@@ -96,16 +125,338 @@ pub fn resolve_ws_control(config: &ExchangeConfig, ctx: &Ctx) -> AppResult<WsCon
         AppError::InvalidConfig("ws_unsubscribe_msg missing in exchange config".into())
     })?;
 
-    // These templates are TOML tables (or values), so render to JSON.
-    let sub_json = render_toml_as_json(sub_tpl, ctx)?;
-    let unsub_json = render_toml_as_json(unsub_tpl, ctx)?;
+    let unused = orphaned_ws_ctx_keys(config, ctx)?;
+    if !unused.is_empty() {
+        warn_orphaned_ctx_keys(&format!("{} ws_subscribe_msg", config.exchange), &unused);
+    }
 
+    // These templates are TOML tables (or values), so render to JSON.
     Ok(WsControlSpec {
-        subscribe: sub_json,
-        unsubscribe: unsub_json,
+        subscribe: render_ws_steps(sub_tpl, ctx, config.ws_coerce_scalars)?,
+        unsubscribe: render_ws_steps(unsub_tpl, ctx, config.ws_coerce_scalars)?,
+    })
+}
+
+/// Ctx keys neither WS control template references, nor the exchange's stream templates
+/// that seeded other ctx values (`seed_ws_stream_ctx`).
+fn orphaned_ws_ctx_keys(config: &ExchangeConfig, ctx: &Ctx) -> AppResult<BTreeSet<String>> {
+    let mut unused = BTreeSet::new();
+    if let Some(tpl) = &config.ws_subscribe_msg {
+        unused = render_toml_strict(tpl, ctx)?.1;
+    }
+    if let Some(tpl) = &config.ws_unsubscribe_msg {
+        let unsub_unused = render_toml_strict(tpl, ctx)?.1;
+        unused.retain(|k| unsub_unused.contains(k));
+    }
+    if !unused.is_empty() {
+        let mut seeded = BTreeSet::new();
+        for s in config.ws.values() {
+            for t in [&s.stream_title, &s.coin, &s.subscription_type]
+                .into_iter()
+                .flatten()
+            {
+                collect_used_keys(t, ctx, &mut seeded);
+            }
+        }
+        unused.retain(|k| !seeded.contains(k));
+    }
+    Ok(unused)
+}
+
+/// A ctx key no placeholder referenced is most likely a typo, whose placeholder then quietly
+/// took its default. Warned once per template, then throttled.
+fn warn_orphaned_ctx_keys(template: &str, unused: &BTreeSet<String>) {
+    if let Some(suppressed) = log_throttle().allow(&format!("orphaned ctx keys:{template}")) {
+        tracing::warn!(
+            template,
+            keys = ?unused,
+            suppressed,
+            "ctx keys not referenced by any template placeholder (typo?)"
+        );
+    }
+}
+
+fn render_ws_steps(tpl: &TableValue, ctx: &Ctx, coerce: bool) -> AppResult<Vec<JsonValue>> {
+    match tpl {
+        TableValue::Array(steps) => steps
+            .iter()
+            .map(|s| render_toml_as_json(s, ctx, coerce))
+            .collect(),
+        one => Ok(vec![render_toml_as_json(one, ctx, coerce)?]),
+    }
+}
+
+/// Resolve subscribe/unsubscribe messages for MANY streams sharing one connection.
+///
+/// Each ctx is rendered with `resolve_ws_control`, then consecutive payloads are merged
+/// (arrays concatenated, everything else must match, e.g. Binance `params`) as long as the
+/// merged subscribe stays within:
+/// - `ws_max_subscribe_bytes` (serialized JSON length), and
+/// - `ws_max_symbols_per_subscribe`.
+///
+/// Payloads that cannot be merged (e.g. Hyperliquid: one subscription per message) stay separate.
+/// A single stream whose subscribe alone exceeds `ws_max_subscribe_bytes` is a config error.
+/// Multi-step messages merge step by step (every step must merge); the limits apply per step.
+///
+/// With `ws_sort_subscribe`, streams are ordered by symbol (`symbol`, then `coin`, then
+/// `stream_title`) first, so the same set always yields the same messages. Only the order
+/// changes: every stream is still subscribed exactly once.
+pub fn resolve_ws_control_batches(
+    config: &ExchangeConfig,
+    ctxs: &[Ctx],
+) -> AppResult<Vec<WsControlSpec>> {
+    resolve_ws_control_batches_within(config, ctxs, None, &log_resolve_progress)
+}
+
+/// `resolve_ws_control_batches`, rendering the streams in parallel and reporting progress;
+/// streams still rendering after `budget` are reported (rendering goes on).
+pub fn resolve_ws_control_batches_within(
+    config: &ExchangeConfig,
+    ctxs: &[Ctx],
+    budget: Option<Duration>,
+    progress: &(dyn Fn(ResolveProgress) + Sync),
+) -> AppResult<Vec<WsControlSpec>> {
+    let mut ordered: Vec<&Ctx> = ctxs.iter().collect();
+    if config.ws_sort_subscribe {
+        ordered.sort_by(|a, b| subscribe_sort_key(a).cmp(&subscribe_sort_key(b)));
+    }
+
+    let max_bytes = config.ws_max_subscribe_bytes.unwrap_or(usize::MAX);
+    let max_symbols = config
+        .ws_max_symbols_per_subscribe
+        .unwrap_or(usize::MAX)
+        .max(1);
+
+    let specs = resolve_parallel(
+        &ordered,
+        |ctx| ctx_label(ctx),
+        |ctx| resolve_ws_control(config, ctx),
+        budget,
+        progress,
+    )?;
+
+    let mut out: Vec<WsControlSpec> = Vec::new();
+    let mut symbols_in_last = 0usize;
+    // Serialized length of each subscribe step of `out.last()`
+    let mut bytes_in_last: Vec<usize> = Vec::new();
+
+    for spec in specs {
+        let sizes: Vec<usize> = spec.subscribe.iter().map(|s| s.to_string().len()).collect();
+        if let Some(&size) = sizes.iter().find(|&&size| size > max_bytes) {
+            return Err(AppError::InvalidConfig(format!(
+                "ws subscribe payload is {size} bytes, above ws_max_subscribe_bytes={max_bytes}"
+            )));
+        }
+
+        if let Some(last) = out.last_mut()
+            && symbols_in_last < max_symbols
+            && let Some(growth) = merge_ws_steps_growth(&last.subscribe, &spec.subscribe)
+            && bytes_in_last
+                .iter()
+                .zip(&growth)
+                .all(|(n, g)| n + g <= max_bytes)
+            && merge_ws_steps_growth(&last.unsubscribe, &spec.unsubscribe).is_some()
+        {
+            merge_ws_steps(&mut last.subscribe, &spec.subscribe);
+            merge_ws_steps(&mut last.unsubscribe, &spec.unsubscribe);
+            for (n, g) in bytes_in_last.iter_mut().zip(growth) {
+                *n += g;
+            }
+            symbols_in_last += 1;
+            continue;
+        }
+
+        out.push(spec);
+        symbols_in_last = 1;
+        bytes_in_last = sizes;
+    }
+
+    Ok(out)
+}
+
+/// Below this many items resolution runs inline: no rayon, no budget watcher thread.
+pub const RESOLVE_PARALLEL_MIN_ITEMS: usize = 64;
+/// Below this many items progress is not reported.
+pub const RESOLVE_PROGRESS_MIN_ITEMS: usize = 500;
+/// Pending items named in an over-budget report.
+const RESOLVE_PENDING_SHOWN: usize = 20;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResolveProgress {
+    /// Every 10% of a large resolve.
+    Resolved { done: usize, total: usize },
+    /// The budget ran out with these items (first few, by label) still resolving.
+    OverBudget {
+        pending: Vec<String>,
+        pending_total: usize,
+        total: usize,
+    },
+}
+
+/// Default progress reporter: info logs, a warning when over budget.
+pub fn log_resolve_progress(p: ResolveProgress) {
+    match p {
+        ResolveProgress::Resolved { done, total } => {
+            tracing::info!(done, total, "resolving stream specs")
+        }
+        ResolveProgress::OverBudget {
+            pending,
+            pending_total,
+            total,
+        } => tracing::warn!(
+            pending_total,
+            total,
+            pending = ?pending,
+            "resolving stream specs is over budget; still resolving"
+        ),
+    }
+}
+
+/// Resolve `items` on the rayon pool, results in input order (first error in order wins).
+/// Blocking: async callers run it in `spawn_blocking`.
+///
+/// Small batches (below `RESOLVE_PARALLEL_MIN_ITEMS`) resolve inline, unreported. Large
+/// batches (`RESOLVE_PROGRESS_MIN_ITEMS`) report every 10%; with a `budget`, a watcher
+/// reports the items not resolved yet once it runs out. Resolution is never cut short.
+pub fn resolve_parallel<I, T, L, F>(
+    items: &[I],
+    label: L,
+    resolve: F,
+    budget: Option<Duration>,
+    progress: &(dyn Fn(ResolveProgress) + Sync),
+) -> AppResult<Vec<T>>
+where
+    I: Sync,
+    T: Send,
+    L: Fn(&I) -> String + Sync,
+    F: Fn(&I) -> AppResult<T> + Sync,
+{
+    let total = items.len();
+    if total < RESOLVE_PARALLEL_MIN_ITEMS {
+        return items.iter().map(resolve).collect();
+    }
+    let finished: Vec<AtomicBool> = items.iter().map(|_| AtomicBool::new(false)).collect();
+    let done = AtomicUsize::new(0);
+    let step = (total / 10).max(1);
+    let report_steps = total >= RESOLVE_PROGRESS_MIN_ITEMS;
+
+    let (stop_tx, stop_rx) = mpsc::channel::<()>();
+    std::thread::scope(|s| {
+        if let Some(budget) = budget {
+            let (finished, label) = (&finished, &label);
+            s.spawn(move || {
+                if stop_rx.recv_timeout(budget) != Err(mpsc::RecvTimeoutError::Timeout) {
+                    return;
+                }
+                let pending: Vec<usize> = (0..total)
+                    .filter(|&i| !finished[i].load(Ordering::Acquire))
+                    .collect();
+                if !pending.is_empty() {
+                    progress(ResolveProgress::OverBudget {
+                        pending: pending
+                            .iter()
+                            .take(RESOLVE_PENDING_SHOWN)
+                            .map(|&i| label(&items[i]))
+                            .collect(),
+                        pending_total: pending.len(),
+                        total,
+                    });
+                }
+            });
+        }
+
+        let started = Instant::now();
+        let out = items
+            .par_iter()
+            .enumerate()
+            .map(|(i, item)| {
+                let res = resolve(item);
+                finished[i].store(true, Ordering::Release);
+                let n = done.fetch_add(1, Ordering::Relaxed) + 1;
+                if report_steps && (n.is_multiple_of(step) || n == total) {
+                    progress(ResolveProgress::Resolved { done: n, total });
+                }
+                res
+            })
+            .collect::<AppResult<Vec<T>>>();
+        drop(stop_tx);
+        tracing::debug!(
+            total,
+            elapsed_ms = started.elapsed().as_millis() as u64,
+            "stream specs resolved"
+        );
+        out
     })
 }
 
+/// Stream name of a ws ctx for reports: `stream_title`, else `symbol` / `coin`.
+fn ctx_label(ctx: &Ctx) -> String {
+    ["stream_title", "symbol", "coin"]
+        .iter()
+        .find_map(|k| ctx.get(*k).cloned())
+        .unwrap_or_default()
+}
+
+fn subscribe_sort_key(ctx: &Ctx) -> [Option<&String>; 3] {
+    [ctx.get("symbol"), ctx.get("coin"), ctx.get("stream_title")]
+}
+
+/// `merge_ws_growth` for each step of two messages with the same number of steps.
+fn merge_ws_steps_growth(a: &[JsonValue], b: &[JsonValue]) -> Option<Vec<usize>> {
+    if a.len() != b.len() {
+        return None;
+    }
+    a.iter()
+        .zip(b)
+        .map(|(x, y)| merge_ws_growth(x, y))
+        .collect()
+}
+
+/// `merge_ws_payloads` for each step (shapes checked by `merge_ws_steps_growth`).
+fn merge_ws_steps(a: &mut [JsonValue], b: &[JsonValue]) {
+    for (x, y) in a.iter_mut().zip(b) {
+        merge_ws_payloads(x, y);
+    }
+}
+
+/// Bytes that merging `b` into `a` adds to `a`'s serialized length, without merging.
+/// None if the shapes differ (see `merge_ws_payloads`).
+fn merge_ws_growth(a: &JsonValue, b: &JsonValue) -> Option<usize> {
+    match (a, b) {
+        (JsonValue::Array(_), JsonValue::Array(y)) if y.is_empty() => Some(0),
+        (JsonValue::Array(x), JsonValue::Array(y)) => {
+            // y's items, their commas, and one more comma after a non-empty `a`
+            let items: usize = y.iter().map(|v| v.to_string().len()).sum();
+            Some(items + y.len() - 1 + usize::from(!x.is_empty()))
+        }
+        (JsonValue::Object(x), JsonValue::Object(y)) => {
+            if x.len() != y.len() {
+                return None;
+            }
+            x.iter().map(|(k, xv)| merge_ws_growth(xv, y.get(k)?)).sum()
+        }
+        _ if a == b => Some(0),
+        _ => None,
+    }
+}
+
+/// Merge two rendered control payloads of the same shape: arrays are concatenated,
+/// objects merged key by key, scalars must be equal. `b` is merged into `a` in place;
+/// check the shapes first with `merge_ws_growth`.
+fn merge_ws_payloads(a: &mut JsonValue, b: &JsonValue) {
+    match (a, b) {
+        (JsonValue::Array(x), JsonValue::Array(y)) => x.extend(y.iter().cloned()),
+        (JsonValue::Object(x), JsonValue::Object(y)) => {
+            for (k, xv) in x.iter_mut() {
+                if let Some(yv) = y.get(k) {
+                    merge_ws_payloads(xv, yv);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
 /// Convenience: resolve all HTTP endpoints by name using the same ctx and placement.
 /// Returns a new map { endpoint_name -> HttpRequestSpec }.
 pub fn resolve_all_http(
@@ -211,11 +562,11 @@ mod tests {
         let binance_ctrl = resolve_ws_control(binance, &ws_ctx_binance)?;
         println!(
             "binance subscribe msg => {}",
-            pretty_json(&binance_ctrl.subscribe)
+            pretty_json(&binance_ctrl.subscribe[0])
         );
         println!(
             "binance unsubscribe msg => {}",
-            pretty_json(&binance_ctrl.unsubscribe)
+            pretty_json(&binance_ctrl.unsubscribe[0])
         );
 
         // 7) Resolve WS control for Hyperliquid (seed ctx, ensure subscription_type if template expects it)
@@ -237,11 +588,11 @@ mod tests {
         let hyper_ctrl = resolve_ws_control(hyper, &ws_ctx_hyper)?;
         println!(
             "hyper subscribe msg => {}",
-            pretty_json(&hyper_ctrl.subscribe)
+            pretty_json(&hyper_ctrl.subscribe[0])
         );
         println!(
             "hyper unsubscribe msg => {}",
-            pretty_json(&hyper_ctrl.unsubscribe)
+            pretty_json(&hyper_ctrl.unsubscribe[0])
         );
 
         // 8) Test resolve_all_http()
@@ -254,4 +605,376 @@ mod tests {
 
         Ok(())
     }
+
+    fn symbol_ctxs(stream: &WsStream, key: &str, symbols: &[String]) -> AppResult<Vec<Ctx>> {
+        symbols
+            .iter()
+            .map(|sym| {
+                let mut ctx = Ctx::new();
+                ctx.insert(key.into(), sym.clone());
+                ctx.insert("stream_id".into(), "1".into());
+                seed_ws_stream_ctx(stream, &mut ctx)?;
+                Ok(ctx)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn ws_multi_step_subscribe_renders_and_merges_step_by_step() -> AppResult<()> {
+        let appconfig = load_app_config(false, 0)?;
+        let exchangeconfigs = ExchangeConfigs::new(&appconfig, false, 0)?;
+        let mut binance = exchangeconfigs.binance_linear.clone().ok_or_else(|| {
+            AppError::InvalidConfig("binance_linear missing in ExchangeConfigs".into())
+        })?;
+        let stream = binance.ws.get("trades").cloned().unwrap();
+        binance.ws_subscribe_msg = Some(
+            toml::from_str::<TableValue>(
+                r#"steps = [
+                { method = "AUTH", key = "k" },
+                { method = "SUBSCRIBE", params = ["<stream_title>"], id = "<stream_id>" },
+            ]"#,
+            )?["steps"]
+                .clone(),
+        );
+        binance.ws_max_symbols_per_subscribe = None;
+
+        let symbols: Vec<String> = vec!["btcusdt".into(), "ethusdt".into()];
+        let batches =
+            resolve_ws_control_batches(&binance, &symbol_ctxs(&stream, "symbol", &symbols)?)?;
+        assert_eq!(batches.len(), 1);
+        let sub = &batches[0].subscribe;
+        assert_eq!(sub.len(), 2);
+        assert_eq!(sub[0], serde_json::json!({"method": "AUTH", "key": "k"}));
+        assert_eq!(sub[1]["params"].as_array().unwrap().len(), 2);
+        // Single-message unsubscribe: still merged on its own
+        assert_eq!(batches[0].unsubscribe.len(), 1);
+        assert_eq!(batches[0].unsubscribe[0]["params"], sub[1]["params"]);
+        Ok(())
+    }
+
+    #[test]
+    fn ws_subscribe_splits_when_payload_too_large() -> AppResult<()> {
+        let appconfig = load_app_config(false, 0)?;
+        let exchangeconfigs = ExchangeConfigs::new(&appconfig, false, 0)?;
+        let mut binance = exchangeconfigs.binance_linear.clone().ok_or_else(|| {
+            AppError::InvalidConfig("binance_linear missing in ExchangeConfigs".into())
+        })?;
+        let stream = binance.ws.get("trades").cloned().unwrap();
+
+        let symbols: Vec<String> = (0..40).map(|i| format!("sym{i:02}usdt")).collect();
+        let ctxs = symbol_ctxs(&stream, "symbol", &symbols)?;
+
+        // No limits: one multiplexed message with every stream title
+        binance.ws_max_subscribe_bytes = None;
+        binance.ws_max_symbols_per_subscribe = None;
+        let all = resolve_ws_control_batches(&binance, &ctxs)?;
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].subscribe[0]["params"].as_array().unwrap().len(), 40);
+
+        // Byte limit: forces splitting, every message fits, nothing lost, order kept
+        binance.ws_max_subscribe_bytes = Some(256);
+        let batches = resolve_ws_control_batches(&binance, &ctxs)?;
+        assert!(batches.len() > 1, "expected split, got {}", batches.len());
+
+        let mut titles = Vec::new();
+        for b in &batches {
+            assert!(b.subscribe[0].to_string().len() <= 256);
+            assert_eq!(b.subscribe[0]["method"], "SUBSCRIBE");
+            assert_eq!(b.unsubscribe[0]["params"], b.subscribe[0]["params"]);
+            for t in b.subscribe[0]["params"].as_array().unwrap() {
+                titles.push(t.as_str().unwrap().to_string());
+            }
+        }
+        let expected: Vec<String> = ctxs.iter().map(|c| c["stream_title"].clone()).collect();
+        assert_eq!(titles, expected);
+
+        // Symbol limit
+        binance.ws_max_subscribe_bytes = None;
+        binance.ws_max_symbols_per_subscribe = Some(15);
+        let batches = resolve_ws_control_batches(&binance, &ctxs)?;
+        let sizes: Vec<usize> = batches
+            .iter()
+            .map(|b| b.subscribe[0]["params"].as_array().unwrap().len())
+            .collect();
+        assert_eq!(sizes, vec![15, 15, 10]);
+
+        // A single stream above the byte limit is rejected
+        binance.ws_max_subscribe_bytes = Some(10);
+        assert!(resolve_ws_control_batches(&binance, &ctxs).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn ws_subscribe_is_deterministic_for_the_same_symbol_set() -> AppResult<()> {
+        let appconfig = load_app_config(false, 0)?;
+        let exchangeconfigs = ExchangeConfigs::new(&appconfig, false, 0)?;
+        let mut binance = exchangeconfigs.binance_linear.clone().ok_or_else(|| {
+            AppError::InvalidConfig("binance_linear missing in ExchangeConfigs".into())
+        })?;
+        let stream = binance.ws.get("trades").cloned().unwrap();
+        binance.ws_max_subscribe_bytes = Some(256);
+        binance.ws_sort_subscribe = true;
+
+        let symbols: Vec<String> = ["solusdt", "btcusdt", "xrpusdt", "ethusdt", "adausdt"]
+            .map(String::from)
+            .to_vec();
+        let mut shuffled = symbols.clone();
+        shuffled.rotate_left(2);
+        shuffled.swap(0, 3);
+
+        let a = resolve_ws_control_batches(&binance, &symbol_ctxs(&stream, "symbol", &symbols)?)?;
+        let b = resolve_ws_control_batches(&binance, &symbol_ctxs(&stream, "symbol", &shuffled)?)?;
+        let payloads = |v: &[WsControlSpec]| -> Vec<String> {
+            v.iter()
+                .map(|c| format!("{:?} {:?}", c.subscribe, c.unsubscribe))
+                .collect()
+        };
+        assert_eq!(payloads(&a), payloads(&b));
+
+        // Same streams, lexicographic order
+        let titles: Vec<String> = a
+            .iter()
+            .flat_map(|c| c.subscribe[0]["params"].as_array().unwrap().clone())
+            .map(|t| t.as_str().unwrap().to_string())
+            .collect();
+        let mut expected: Vec<String> = symbols.iter().map(|s| format!("{s}@aggTrade")).collect();
+        expected.sort();
+        assert_eq!(titles, expected);
+
+        // Off: input order is kept
+        binance.ws_sort_subscribe = false;
+        let c = resolve_ws_control_batches(&binance, &symbol_ctxs(&stream, "symbol", &shuffled)?)?;
+        assert_ne!(payloads(&a), payloads(&c));
+
+        Ok(())
+    }
+
+    #[test]
+    fn ws_merge_growth_matches_the_merged_length() {
+        let mut a = serde_json::json!({"method": "SUBSCRIBE", "params": ["a@trade"], "id": 1});
+        for b in [
+            serde_json::json!({"method": "SUBSCRIBE", "params": ["b@trade", "c@trade"], "id": 1}),
+            serde_json::json!({"method": "SUBSCRIBE", "params": [], "id": 1}),
+            serde_json::json!({"method": "SUBSCRIBE", "params": [{"k": "\"q\""}], "id": 1}),
+        ] {
+            let growth = merge_ws_growth(&a, &b).unwrap();
+            let before = a.to_string().len();
+            merge_ws_payloads(&mut a, &b);
+            assert_eq!(a.to_string().len(), before + growth);
+        }
+
+        let mut empty = serde_json::json!({"params": []});
+        let b = serde_json::json!({"params": ["x"]});
+        let growth = merge_ws_growth(&empty, &b).unwrap();
+        merge_ws_payloads(&mut empty, &b);
+        assert_eq!(empty.to_string().len(), r#"{"params":[]}"#.len() + growth);
+
+        // Different scalars / keys do not merge
+        assert!(
+            merge_ws_growth(
+                &a,
+                &serde_json::json!({"method": "SUBSCRIBE", "params": [], "id": 2})
+            )
+            .is_none()
+        );
+        assert!(
+            merge_ws_growth(
+                &a,
+                &serde_json::json!({"method": "SUBSCRIBE", "args": [], "id": 1})
+            )
+            .is_none()
+        );
+    }
+
+    #[test]
+    fn ws_subscribe_non_mergeable_payloads_stay_separate() -> AppResult<()> {
+        let appconfig = load_app_config(false, 0)?;
+        let exchangeconfigs = ExchangeConfigs::new(&appconfig, false, 0)?;
+        let hyper = exchangeconfigs.hyperliquid_perp.as_ref().ok_or_else(|| {
+            AppError::InvalidConfig("hyperliquid_perp missing in ExchangeConfigs".into())
+        })?;
+        let stream = hyper.ws.get("trades").cloned().unwrap();
+
+        let coins: Vec<String> = ["BTC", "ETH", "SOL"].map(String::from).to_vec();
+        let ctxs = symbol_ctxs(&stream, "coin", &coins)?;
+
+        let batches = resolve_ws_control_batches(hyper, &ctxs)?;
+        assert_eq!(batches.len(), 3);
+        for (b, coin) in batches.iter().zip(&coins) {
+            assert_eq!(b.subscribe[0]["subscription"]["coin"], coin.as_str());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn json_body_not_matching_body_shape_is_a_config_error() -> AppResult<()> {
+        let appconfig = load_app_config(false, 0)?;
+        let exchangeconfigs = ExchangeConfigs::new(&appconfig, false, 0)?;
+        let hyper = exchangeconfigs.hyperliquid_perp.as_ref().ok_or_else(|| {
+            AppError::InvalidConfig("hyperliquid_perp missing in ExchangeConfigs".into())
+        })?;
+        let mut depth = hyper.api.get("depth").cloned().unwrap();
+        assert!(depth.body_shape.is_some(), "depth declares its body shape");
+
+        let mut ctx = Ctx::new();
+        ctx.insert("coin".into(), "BTC".into());
+        let spec = resolve_http_request(&depth, &ctx, ParamPlacement::JsonBody)?;
+        assert_eq!(spec.json_body.unwrap()["coin"], "BTC");
+
+        // Typo in the key name: `coins` instead of `coin`
+        depth.params = Some(toml::from_str(
+            r#"type = "l2Book"
+coins = "<coin>"
+nSigFigs = 5"#,
+        )?);
+        let err = resolve_http_request(&depth, &ctx, ParamPlacement::JsonBody).unwrap_err();
+        assert!(
+            matches!(&err, AppError::InvalidConfig(m) if m.contains("missing required key `coin`") && m.contains("/info")),
+            "{err}"
+        );
+
+        // Wrong value type
+        depth.params = Some(toml::from_str(
+            r#"type = "l2Book"
+coin = "<coin>"
+nSigFigs = "5""#,
+        )?);
+        let err = resolve_http_request(&depth, &ctx, ParamPlacement::JsonBody).unwrap_err();
+        assert!(
+            matches!(&err, AppError::InvalidConfig(m) if m.contains("`nSigFigs` is a string, expected integer")),
+            "{err}"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn many_streams_resolve_in_parallel_with_progress() -> AppResult<()> {
+        use std::sync::Mutex;
+
+        let appconfig = load_app_config(false, 0)?;
+        let exchangeconfigs = ExchangeConfigs::new(&appconfig, false, 0)?;
+        let mut binance = exchangeconfigs.binance_linear.clone().ok_or_else(|| {
+            AppError::InvalidConfig("binance_linear missing in ExchangeConfigs".into())
+        })?;
+        binance.ws_max_subscribe_bytes = None;
+        binance.ws_max_symbols_per_subscribe = Some(200);
+        let stream = binance.ws.get("trades").cloned().unwrap();
+
+        let symbols: Vec<String> = (0..5000).map(|i| format!("sym{i:04}usdt")).collect();
+        let ctxs = symbol_ctxs(&stream, "symbol", &symbols)?;
+
+        let reports = Mutex::new(Vec::new());
+        let batches = resolve_ws_control_batches_within(&binance, &ctxs, None, &|p| {
+            reports.lock().unwrap().push(p)
+        })?;
+
+        // Same messages as rendering one stream after the other
+        assert_eq!(batches.len(), 25);
+        let titles: Vec<&str> = batches
+            .iter()
+            .flat_map(|b| b.subscribe[0]["params"].as_array().unwrap())
+            .map(|t| t.as_str().unwrap())
+            .collect();
+        let expected: Vec<&str> = ctxs.iter().map(|c| c["stream_title"].as_str()).collect();
+        assert_eq!(titles, expected);
+
+        // Every 10%, through to the end
+        let reports = reports.into_inner().unwrap();
+        assert_eq!(reports.len(), 10);
+        assert!(reports.contains(&ResolveProgress::Resolved {
+            done: 5000,
+            total: 5000
+        }));
+
+        // Small batches do not report
+        let quiet = Mutex::new(0);
+        resolve_ws_control_batches_within(&binance, &ctxs[..10], None, &|_| {
+            *quiet.lock().unwrap() += 1
+        })?;
+        assert_eq!(*quiet.lock().unwrap(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_over_budget_names_the_pending_items() -> AppResult<()> {
+        use std::sync::Mutex;
+
+        // The last item takes far longer than the budget; the rest are instant
+        let items: Vec<u64> = (0..RESOLVE_PARALLEL_MIN_ITEMS as u64).collect();
+        let last = items.len() as u64 - 1;
+        let reports = Mutex::new(Vec::new());
+        let out = resolve_parallel(
+            &items,
+            |i| format!("item{i}"),
+            |&i| {
+                if i == last {
+                    std::thread::sleep(Duration::from_millis(300));
+                }
+                Ok(i * 2)
+            },
+            Some(Duration::from_millis(50)),
+            &|p| reports.lock().unwrap().push(p),
+        )?;
+        assert_eq!(out, items.iter().map(|i| i * 2).collect::<Vec<_>>());
+        assert_eq!(
+            reports.into_inner().unwrap(),
+            vec![ResolveProgress::OverBudget {
+                pending: vec![format!("item{last}")],
+                pending_total: 1,
+                total: items.len(),
+            }]
+        );
+
+        // Small batches resolve inline: no watcher, no report
+        let quiet = Mutex::new(0);
+        resolve_parallel(
+            &items[..8],
+            |i| i.to_string(),
+            |&i| {
+                if i == 7 {
+                    std::thread::sleep(Duration::from_millis(100));
+                }
+                Ok(i)
+            },
+            Some(Duration::from_millis(10)),
+            &|_| *quiet.lock().unwrap() += 1,
+        )?;
+        assert_eq!(*quiet.lock().unwrap(), 0);
+
+        // Errors still surface, first in input order
+        let err = resolve_parallel(
+            &items,
+            |i| i.to_string(),
+            |&i| match i {
+                3 | 5 => Err(AppError::InvalidConfig(format!("bad {i}"))),
+                _ => Ok(i),
+            },
+            None,
+            &log_resolve_progress,
+        );
+        assert!(matches!(err, Err(AppError::InvalidConfig(m)) if m == "bad 3"));
+        Ok(())
+    }
+
+    #[test]
+    fn orphaned_ws_ctx_keys_skip_keys_the_stream_templates_used() -> AppResult<()> {
+        let binance = crate::ingest::config::load_exchange_config("binance_linear", false, 0)?;
+        let mut ctx = Ctx::new();
+        ctx.insert("symbol".into(), "btcusdt".into());
+        seed_ws_stream_ctx(&binance.ws["trades"], &mut ctx)?;
+        ctx.insert("stream_id".into(), "1".into());
+
+        // `symbol` only fed `stream_title`: not orphaned
+        assert!(orphaned_ws_ctx_keys(&binance, &ctx)?.is_empty());
+
+        ctx.insert("stream_idd".into(), "2".into());
+        assert_eq!(
+            orphaned_ws_ctx_keys(&binance, &ctx)?,
+            BTreeSet::from(["stream_idd".to_string()])
+        );
+        Ok(())
+    }
 }
@@ -5,42 +5,81 @@ use std::collections::BTreeSet;
 use toml::Value as TomlValue;
 
 /// Render a string replacing any occurrences of `<key>` with ctx[key].
-/// - Replaces multiple occurrences.
-/// - Returns a nice error if a placeholder is present but missing in ctx.
+/// - Replaces multiple occurrences. Replacements are literal (not rendered again).
+/// - `<key|default>` renders `default` when `key` is not in ctx. The default is literal: it
+///   runs from the first `|` up to the first `>` (so it may contain `|` but not `>`), and is
+///   not rendered again.
+/// - `<key:lower>`, `<key:upper>`, `<key:trim>` transform the value (ctx value or default);
+///   chains apply left to right (`<symbol:trim:lower|BTCUSDT>`). An unknown transform is a
+///   config error.
+/// - `<<` and `>>` render a literal `<` / `>` (e.g. `"a<<b>>c"` => `"a<b>c"`). Inside a
+///   placeholder they are not escapes: `<key|a>>b>` is `<key|a>` followed by `>b>`.
+/// - Returns a nice error if a `<key>` placeholder (no default) is missing in ctx.
 pub fn render_string(input: &str, ctx: &Ctx) -> AppResult<String> {
-    // Fast path: no templates
-    if !input.contains('<') {
+    render_string_tracked(input, ctx, &mut BTreeSet::new())
+}
+
+/// `render_string`, adding every ctx key a placeholder replaced to `used`.
+fn render_string_tracked(input: &str, ctx: &Ctx, used: &mut BTreeSet<String>) -> AppResult<String> {
+    // Fast path: no templates, no escapes
+    if !input.contains('<') && !input.contains(">>") {
         return Ok(input.to_string());
     }
 
     // Collect placeholders of the form <...>
     let mut missing: BTreeSet<String> = BTreeSet::new();
-    let mut out = input.to_string();
+    let mut out = String::with_capacity(input.len());
 
     // A simple, robust approach:
     // scan for `<` ... `>` segments and replace exact tokens.
     // This supports strings like "<symbol>@aggTrade" and JSON ids "<stream_id>".
-    let mut i = 0usize;
-    while let Some(start) = out[i..].find('<') {
-        let start = i + start;
-        if let Some(end_rel) = out[start..].find('>') {
-            let end = start + end_rel;
-            let key = &out[start + 1..end]; // inside <...>
-
-            if let Some(val) = ctx.get(key) {
-                // Replace this specific occurrence only
-                out.replace_range(start..=end, val);
-                // Continue scanning from start (val could contain '<', though unlikely)
-                i = start;
-            } else {
+    let mut rest = input;
+    while let Some(pos) = rest.find(['<', '>']) {
+        out.push_str(&rest[..pos]);
+        let tail = &rest[pos..];
+
+        // Escapes
+        if let Some(after) = tail.strip_prefix("<<").or_else(|| tail.strip_prefix(">>")) {
+            out.push_str(&tail[..1]);
+            rest = after;
+            continue;
+        }
+        if let Some(after) = tail.strip_prefix('>') {
+            out.push('>');
+            rest = after;
+            continue;
+        }
+
+        let Some(end) = tail.find('>') else {
+            break; // no closing '>', the rest is literal
+        };
+        let inner = &tail[1..end]; // inside <...>
+        let (key, default) = match inner.split_once('|') {
+            Some((key, default)) => (key, Some(default)),
+            None => (inner, None),
+        };
+        let (key, transforms) = match key.split_once(':') {
+            Some((key, transforms)) => (key, Some(transforms)),
+            None => (key, None),
+        };
+
+        let val = ctx.get(key).map(String::as_str);
+        if val.is_some() && !used.contains(key) {
+            used.insert(key.to_string());
+        }
+        match (val.or(default), transforms) {
+            (Some(val), None) => out.push_str(val),
+            (Some(val), Some(chain)) => out.push_str(&apply_transforms(val, chain)?),
+            (None, chain) => {
+                if let Some(chain) = chain {
+                    apply_transforms("", chain)?;
+                }
                 missing.insert(key.to_string());
-                // Skip past this '>' to continue scanning
-                i = end + 1;
             }
-        } else {
-            break; // no closing '>', stop
         }
+        rest = &tail[end + 1..];
     }
+    out.push_str(rest);
 
     if !missing.is_empty() {
         return Err(AppError::InvalidConfig(format!(
@@ -52,18 +91,65 @@ pub fn render_string(input: &str, ctx: &Ctx) -> AppResult<String> {
     Ok(out)
 }
 
+/// Apply a `:`-separated transform chain (`trim:lower`) left to right.
+fn apply_transforms(value: &str, chain: &str) -> AppResult<String> {
+    let mut out = value.to_string();
+    for transform in chain.split(':') {
+        out = match transform {
+            "lower" => out.to_lowercase(),
+            "upper" => out.to_uppercase(),
+            "trim" => out.trim().to_string(),
+            other => {
+                return Err(AppError::InvalidConfig(format!(
+                    "Unknown template transform '{other}' (expected lower, upper or trim)"
+                )));
+            }
+        };
+    }
+    Ok(out)
+}
+
 /// Recursively render templates inside a TOML value.
 /// - Strings are rendered via `render_string`.
 /// - Tables/Arrays are traversed.
 /// - Scalars are passed through.
 pub fn render_toml(value: &TomlValue, ctx: &Ctx) -> AppResult<TomlValue> {
+    render_toml_tracked(value, ctx, &mut BTreeSet::new())
+}
+
+/// `render_toml` that also returns the ctx keys no placeholder referenced.
+///
+/// A typo in a ctx key (`symobl`) is not an error for `render_toml`: the key is just never
+/// used, and a `<symbol|default>` placeholder silently takes its default. The resolver can
+/// warn or fail on the orphaned keys returned here instead.
+pub fn render_toml_strict(
+    value: &TomlValue,
+    ctx: &Ctx,
+) -> AppResult<(TomlValue, BTreeSet<String>)> {
+    let mut used = BTreeSet::new();
+    let rendered = render_toml_tracked(value, ctx, &mut used)?;
+    let unused = ctx.keys().filter(|k| !used.contains(*k)).cloned().collect();
+    Ok((rendered, unused))
+}
+
+/// Add the ctx keys referenced by `input`'s placeholders to `used`, whether or not it renders
+/// (templates that fed other ctx values, e.g. `stream_title = "<symbol>@aggTrade"`).
+pub fn collect_used_keys(input: &str, ctx: &Ctx, used: &mut BTreeSet<String>) {
+    let _ = render_string_tracked(input, ctx, used);
+}
+
+fn render_toml_tracked(
+    value: &TomlValue,
+    ctx: &Ctx,
+    used: &mut BTreeSet<String>,
+) -> AppResult<TomlValue> {
     Ok(match value {
-        TomlValue::String(s) => TomlValue::String(render_string(s, ctx)?),
+        TomlValue::String(s) => TomlValue::String(render_string_tracked(s, ctx, used)?),
 
         TomlValue::Array(arr) => {
             let mut out = Vec::with_capacity(arr.len());
             for v in arr {
-                out.push(render_toml(v, ctx)?);
+                out.push(render_toml_tracked(v, ctx, used)?);
             }
             TomlValue::Array(out)
         }
@@ -72,7 +158,7 @@ pub fn render_toml(value: &TomlValue, ctx: &Ctx) -> AppResult<TomlValue> {
             let mut out = toml::map::Map::new();
             for (k, v) in tbl {
                 // keys are literal; values can be templated
-                out.insert(k.clone(), render_toml(v, ctx)?);
+                out.insert(k.clone(), render_toml_tracked(v, ctx, used)?);
             }
             TomlValue::Table(out)
         }
@@ -102,7 +188,9 @@ fn toml_scalar_to_string(v: &TomlValue, key: &str) -> AppResult<String> {
 }
 
 /// Render `params` (TOML table) into query params.
-/// - Requires `params` to be a TOML table of scalars after rendering.
+/// - Requires `params` to be a TOML table of scalars (or arrays of scalars) after rendering.
+/// - An array becomes one pair per element, in order: `symbols = ["BTCUSDT", "ETHUSDT"]` =>
+///   `?symbols=BTCUSDT&symbols=ETHUSDT`.
 /// - Great for Binance-style `GET ...?symbol=...&limit=...`.
 pub fn render_params_as_query(params: &TomlValue, ctx: &Ctx) -> AppResult<Vec<(String, String)>> {
     let rendered = render_toml(params, ctx)?;
@@ -113,7 +201,14 @@ pub fn render_params_as_query(params: &TomlValue, ctx: &Ctx) -> AppResult<Vec<(S
 
     let mut out = Vec::with_capacity(tbl.len());
     for (k, v) in tbl {
-        out.push((k.clone(), toml_scalar_to_string(v, k)?));
+        match v {
+            TomlValue::Array(items) => {
+                for item in items {
+                    out.push((k.clone(), toml_scalar_to_string(item, k)?));
+                }
+            }
+            _ => out.push((k.clone(), toml_scalar_to_string(v, k)?)),
+        }
     }
 
     Ok(out)
@@ -150,7 +245,283 @@ pub fn toml_to_json(value: &TomlValue) -> AppResult<JsonValue> {
 /// Render params into a JSON value (after template replacement).
 /// - Great for Hyperliquid-style `{ type="l2Book", coin="<coin>" }` bodies.
 /// - Also great for WS control messages like Binance's subscribe templates.
-pub fn render_toml_as_json(value: &TomlValue, ctx: &Ctx) -> AppResult<JsonValue> {
-    let rendered = render_toml(value, ctx)?;
-    toml_to_json(&rendered)
+/// - `coerce_scalars`: a string that is exactly one placeholder (`"<limit>"`, `"<limit|1000>"`)
+///   becomes a JSON number/bool when its rendered value parses as one (`1000`, not `"1000"`).
+///   Anything else around the placeholder (`"<symbol>@aggTrade"`) keeps it a string.
+pub fn render_toml_as_json(
+    value: &TomlValue,
+    ctx: &Ctx,
+    coerce_scalars: bool,
+) -> AppResult<JsonValue> {
+    if !coerce_scalars {
+        let rendered = render_toml(value, ctx)?;
+        return toml_to_json(&rendered);
+    }
+
+    Ok(match value {
+        TomlValue::String(s) => {
+            let rendered = render_string(s, ctx)?;
+            match is_single_placeholder(s).then(|| json_scalar(&rendered)) {
+                Some(Some(scalar)) => scalar,
+                _ => JsonValue::String(rendered),
+            }
+        }
+        TomlValue::Array(arr) => {
+            let mut out = Vec::with_capacity(arr.len());
+            for v in arr {
+                out.push(render_toml_as_json(v, ctx, true)?);
+            }
+            JsonValue::Array(out)
+        }
+        TomlValue::Table(tbl) => {
+            let mut map = serde_json::Map::new();
+            for (k, v) in tbl {
+                map.insert(k.clone(), render_toml_as_json(v, ctx, true)?);
+            }
+            JsonValue::Object(map)
+        }
+        other => toml_to_json(other)?,
+    })
+}
+
+/// `<key>` / `<key|default>` and nothing else (`<<` escapes are not placeholders).
+fn is_single_placeholder(s: &str) -> bool {
+    s.strip_prefix('<')
+        .and_then(|rest| rest.strip_suffix('>'))
+        .is_some_and(|inner| !inner.is_empty() && !inner.contains(['<', '>']))
+}
+
+/// `true` / `false` / a JSON number literal (`1000`, `-0.5`, `1e3`); None keeps the string.
+/// Strict JSON grammar, so `"007"`, `"+1"` or `"NaN"` stay strings.
+fn json_scalar(s: &str) -> Option<JsonValue> {
+    match s {
+        "true" => Some(JsonValue::Bool(true)),
+        "false" => Some(JsonValue::Bool(false)),
+        _ => s.parse::<serde_json::Number>().ok().map(JsonValue::Number),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctx(pairs: &[(&str, &str)]) -> Ctx {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn default_is_used_only_when_key_is_absent() {
+        let input = "<symbol>?limit=<limit|1000>";
+        assert_eq!(
+            render_string(input, &ctx(&[("symbol", "BTCUSDT")])).unwrap(),
+            "BTCUSDT?limit=1000"
+        );
+        assert_eq!(
+            render_string(input, &ctx(&[("symbol", "BTCUSDT"), ("limit", "5")])).unwrap(),
+            "BTCUSDT?limit=5"
+        );
+        // Empty default
+        assert_eq!(render_string("a<x|>b", &ctx(&[])).unwrap(), "ab");
+    }
+
+    #[test]
+    fn key_without_default_still_errors_when_absent() {
+        let err = render_string("<symbol>@depth<speed|@100ms>", &ctx(&[])).unwrap_err();
+        let msg = err.to_string();
+        assert!(msg.contains("symbol"), "{msg}");
+        assert!(!msg.contains("speed"), "{msg}");
+    }
+
+    #[test]
+    fn default_is_literal_up_to_the_first_gt() {
+        // `|` inside the default is kept; `<` is not rendered again
+        assert_eq!(
+            render_string("<sep|a|b>", &ctx(&[("a", "x")])).unwrap(),
+            "a|b"
+        );
+        assert_eq!(render_string("<q|<a>", &ctx(&[("a", "x")])).unwrap(), "<a");
+        // The default ends at the first `>`: the rest is plain text
+        assert_eq!(render_string("<cmp|a>b>", &ctx(&[])).unwrap(), "ab>");
+    }
+
+    #[test]
+    fn doubled_angle_brackets_render_literally() {
+        assert_eq!(render_string("a<<b>>c", &ctx(&[])).unwrap(), "a<b>c");
+        assert_eq!(render_string("a>>b", &ctx(&[])).unwrap(), "a>b");
+        // A lone `>` outside a placeholder stays as is
+        assert_eq!(render_string("a>b", &ctx(&[])).unwrap(), "a>b");
+    }
+
+    #[test]
+    fn escapes_mix_with_placeholders() {
+        let c = ctx(&[("symbol", "BTCUSDT"), ("op", "<=")]);
+        assert_eq!(
+            render_string("<<<symbol>>>@<<depth|20>>", &c).unwrap(),
+            "<BTCUSDT>@<depth|20>"
+        );
+        assert_eq!(
+            render_string("<<x>> <op> <limit|5> <<<<", &c).unwrap(),
+            "<x> <= 5 <<"
+        );
+        // A replacement containing `<` is not scanned again
+        assert_eq!(render_string("<op>1", &c).unwrap(), "<=1");
+        // Escapes do not hide a missing placeholder
+        let err = render_string("<<a>><b>", &c).unwrap_err().to_string();
+        assert!(err.contains(": b"), "{err}");
+    }
+
+    #[test]
+    fn array_params_become_repeated_query_keys() {
+        let params: TomlValue = toml::from_str(
+            r#"symbols = ["BTCUSDT", "<extra>"]
+limit = 5"#,
+        )
+        .unwrap();
+        let query = render_params_as_query(&params, &ctx(&[("extra", "ETHUSDT")])).unwrap();
+        assert_eq!(
+            query,
+            vec![
+                ("limit".to_string(), "5".to_string()),
+                ("symbols".to_string(), "BTCUSDT".to_string()),
+                ("symbols".to_string(), "ETHUSDT".to_string()),
+            ]
+        );
+
+        // Tables (also inside arrays) are still rejected
+        for bad in [r#"x = { a = 1 }"#, r#"x = [{ a = 1 }]"#, r#"x = [[1]]"#] {
+            let params: TomlValue = toml::from_str(bad).unwrap();
+            assert!(render_params_as_query(&params, &ctx(&[])).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn transforms_apply_to_the_resolved_value_left_to_right() {
+        let c = ctx(&[("symbol", "BtcUsdt"), ("padded", "  ETHusdt ")]);
+
+        assert_eq!(
+            render_string("<symbol:lower>@aggTrade", &c).unwrap(),
+            "btcusdt@aggTrade"
+        );
+        assert_eq!(render_string("<symbol:upper>", &c).unwrap(), "BTCUSDT");
+        assert_eq!(render_string("<padded:trim:lower>", &c).unwrap(), "ethusdt");
+        assert_eq!(
+            render_string("[<padded:lower>]", &c).unwrap(),
+            "[  ethusdt ]"
+        );
+        // Defaults go through the chain too; the default may contain ':'
+        assert_eq!(render_string("<coin:lower|BTC>", &c).unwrap(), "btc");
+        assert_eq!(render_string("<coin|a:b>", &c).unwrap(), "a:b");
+
+        // The key is still tracked without its transforms
+        let tpl = TomlValue::String("<symbol:lower>".into());
+        let (_, unused) = render_toml_strict(&tpl, &c).unwrap();
+        assert_eq!(unused, BTreeSet::from(["padded".to_string()]));
+
+        // Unknown transforms are named, present key or not
+        for input in ["<symbol:title>", "<coin:lower:title>", "<symbol:>"] {
+            let err = render_string(input, &c).unwrap_err();
+            assert!(matches!(err, AppError::InvalidConfig(_)), "{err:?}");
+            let name = input.rsplit(':').next().unwrap().trim_end_matches('>');
+            assert!(
+                format!("{err:?}").contains(&format!("'{name}'")),
+                "{input}: {err:?}"
+            );
+        }
+        assert!(render_string("<coin:upper>", &c).is_err());
+    }
+
+    #[test]
+    fn strict_render_reports_ctx_keys_no_placeholder_used() {
+        let tpl: TomlValue = toml::from_str(
+            r#"method = "SUBSCRIBE"
+params = ["<symbol|btcusdt>@aggTrade", "<symbol|btcusdt>@depth"]
+id = "<stream_id>""#,
+        )
+        .unwrap();
+
+        // Typo in the ctx key: rendering succeeds with the default, the key is reported
+        let c = ctx(&[("symobl", "ethusdt"), ("stream_id", "1")]);
+        let (rendered, unused) = render_toml_strict(&tpl, &c).unwrap();
+        assert_eq!(rendered["params"][0].as_str(), Some("btcusdt@aggTrade"));
+        assert_eq!(unused, BTreeSet::from(["symobl".to_string()]));
+        assert_eq!(rendered, render_toml(&tpl, &c).unwrap());
+
+        let c = ctx(&[("symbol", "ethusdt"), ("stream_id", "1")]);
+        let (rendered, unused) = render_toml_strict(&tpl, &c).unwrap();
+        assert_eq!(rendered["params"][1].as_str(), Some("ethusdt@depth"));
+        assert!(unused.is_empty());
+
+        // Missing placeholders still fail
+        assert!(render_toml_strict(&tpl, &ctx(&[])).is_err());
+    }
+
+    #[test]
+    fn whole_placeholder_strings_coerce_to_json_scalars() {
+        let tpl: TomlValue = toml::from_str(
+            r#"type = "l2Book"
+coin = "<coin>"
+nSigFigs = "<n_sig_figs|5>"
+mantissa = "<mantissa>"
+snapshot = "<snapshot>"
+params = ["<symbol>@depth", "<stream_id>"]
+label = "<<stream_id>>"
+padded = "<padded>"
+literal = 7"#,
+        )
+        .unwrap();
+        let c = ctx(&[
+            ("coin", "BTC"),
+            ("mantissa", "2.5"),
+            ("snapshot", "true"),
+            ("symbol", "1000"),
+            ("stream_id", "12"),
+            ("padded", "007"),
+        ]);
+
+        let body = render_toml_as_json(&tpl, &c, true).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "type": "l2Book",
+                "coin": "BTC",
+                "nSigFigs": 5,
+                "mantissa": 2.5,
+                "snapshot": true,
+                // Concatenations stay strings even when the value is numeric
+                "params": ["1000@depth", 12],
+                "label": "<stream_id>",
+                "padded": "007",
+                "literal": 7,
+            })
+        );
+
+        // Without the flag every rendered value stays a string
+        let body = render_toml_as_json(&tpl, &c, false).unwrap();
+        assert_eq!(body["nSigFigs"], "5");
+        assert_eq!(body["params"][1], "12");
+        assert_eq!(body["literal"], 7);
+
+        // Missing placeholders still fail
+        assert!(render_toml_as_json(&tpl, &ctx(&[]), true).is_err());
+    }
+
+    #[test]
+    fn params_render_defaults_into_query() {
+        let params: TomlValue = toml::from_str(
+            r#"symbol = "<symbol>"
+limit = "<limit|1000>""#,
+        )
+        .unwrap();
+        let query = render_params_as_query(&params, &ctx(&[("symbol", "BTCUSDT")])).unwrap();
+        assert_eq!(
+            query,
+            vec![
+                ("limit".to_string(), "1000".to_string()),
+                ("symbol".to_string(), "BTCUSDT".to_string()),
+            ]
+        );
+    }
 }
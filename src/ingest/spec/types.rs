@@ -1,4 +1,6 @@
+use crate::ingest::datamap::venue_time::VenueTimeField;
 use reqwest::Method;
+use serde::Deserialize;
 use serde_json::Value as JsonValue;
 use std::collections::BTreeMap;
 
@@ -30,6 +32,134 @@ pub struct HttpRequestSpec {
     /// Rate-limit weight for your client-side throttle.
     pub weight: u32,
     pub interval_seconds: u64,
+    /// Per-request body size limit (falls back to the client's limit).
+    pub max_response_bytes: Option<usize>,
+    /// Time-sensitive endpoints: timestamp (+ recv window) added by the client on every
+    /// send, so each attempt (retries included) carries a current timestamp.
+    pub timing: Option<RequestTiming>,
+    /// Where the venue event time lives in the response body (`[api.*] time_field`).
+    pub time_field: Option<VenueTimeField>,
+}
+
+/// Per-exchange timestamp/recv-window params for time-sensitive (signed) requests.
+///
+/// Venues reject requests whose timestamp is older than the receive window, so the values
+/// are never rendered into the spec; `ApiClient::execute` stamps them at send time.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct RequestTiming {
+    /// Param carrying the send time in epoch milliseconds.
+    pub timestamp_param: String,
+    /// Param carrying `recv_window_ms`.
+    pub recv_window_param: String,
+    /// Validity window sent with the timestamp (None = timestamp only).
+    pub recv_window_ms: Option<u64>,
+}
+
+impl Default for RequestTiming {
+    fn default() -> Self {
+        Self {
+            timestamp_param: "timestamp".to_string(),
+            recv_window_param: "recvWindow".to_string(),
+            recv_window_ms: None,
+        }
+    }
+}
+
+impl RequestTiming {
+    /// Params for a request sent at `now_ms`.
+    pub fn params(&self, now_ms: u64) -> Vec<(String, String)> {
+        let mut out = vec![(self.timestamp_param.clone(), now_ms.to_string())];
+        if let Some(w) = self.recv_window_ms {
+            out.push((self.recv_window_param.clone(), w.to_string()));
+        }
+        out
+    }
+}
+
+/// JSON type expected for a `BodyShape` key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JsonKind {
+    String,
+    /// Any JSON number.
+    Number,
+    /// A JSON number without a fractional part.
+    Integer,
+    Bool,
+    Object,
+    Array,
+}
+
+impl JsonKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            JsonKind::String => "string",
+            JsonKind::Number => "number",
+            JsonKind::Integer => "integer",
+            JsonKind::Bool => "bool",
+            JsonKind::Object => "object",
+            JsonKind::Array => "array",
+        }
+    }
+
+    pub fn matches(self, v: &JsonValue) -> bool {
+        match self {
+            JsonKind::String => v.is_string(),
+            JsonKind::Number => v.is_number(),
+            JsonKind::Integer => v.is_i64() || v.is_u64(),
+            JsonKind::Bool => v.is_boolean(),
+            JsonKind::Object => v.is_object(),
+            JsonKind::Array => v.is_array(),
+        }
+    }
+}
+
+fn json_type_name(v: &JsonValue) -> &'static str {
+    match v {
+        JsonValue::Null => "null",
+        JsonValue::Bool(_) => "bool",
+        JsonValue::Number(_) => "number",
+        JsonValue::String(_) => "string",
+        JsonValue::Array(_) => "array",
+        JsonValue::Object(_) => "object",
+    }
+}
+
+/// Expected shape of a rendered JSON request body (`body_shape` of an `[api.*]` endpoint),
+/// e.g. `body_shape = { required = { type = "string", coin = "string" } }`.
+///
+/// Only the listed top-level keys are checked; other keys are allowed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct BodyShape {
+    #[serde(default)]
+    pub required: BTreeMap<String, JsonKind>,
+}
+
+impl BodyShape {
+    /// Err names the first missing or mistyped key.
+    pub fn check(&self, body: &JsonValue) -> Result<(), String> {
+        let Some(obj) = body.as_object() else {
+            return Err(format!(
+                "body is a JSON {}, expected an object",
+                json_type_name(body)
+            ));
+        };
+        for (key, kind) in &self.required {
+            match obj.get(key) {
+                None => return Err(format!("missing required key `{key}`")),
+                Some(v) if !kind.matches(v) => {
+                    return Err(format!(
+                        "key `{key}` is a {}, expected {}",
+                        json_type_name(v),
+                        kind.as_str()
+                    ));
+                }
+                Some(_) => {}
+            }
+        }
+        Ok(())
+    }
 }
 
 ///// A generic WS subscription "payload" after rendering templates.
@@ -41,10 +171,11 @@ pub struct HttpRequestSpec {
 //}
 
 /// A resolved WS subscribe/unsubscribe message spec (already rendered).
+/// Each side is an ordered list of frames: one, or every step of a multi-step handshake.
 #[derive(Debug, Clone)]
 pub struct WsControlSpec {
-    pub subscribe: JsonValue,
-    pub unsubscribe: JsonValue,
+    pub subscribe: Vec<JsonValue>,
+    pub unsubscribe: Vec<JsonValue>,
 }
 
 /// Convenience helper: build a context from an iterator of pairs.
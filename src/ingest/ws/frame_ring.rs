@@ -0,0 +1,253 @@
+//! ingest/ws/frame_ring.rs
+//!
+//! Last raw frames of one WS stream, dumped only when it fails.
+//!
+//! Every `run_stream` keeps a small ring of the text/binary frames it handed to its handler
+//! (`streams.ws_frame_ring_size`, 0 = off). When the handler returns an error or panics
+//! (caught at the call, dumped, then resumed), the frames that led up to it are logged, and
+//! written to `streams.ws_frame_ring_dump_dir` when set, to reproduce the failure offline.
+//! Frames go through the payload redactor (`logging.redact_fields`) before leaving the ring.
+//!
+//! The ring holds the refcounted buffers of the received `Message`s, so keeping a frame is a
+//! refcount bump on the read path; frames are cut and rendered only when dumped.
+
+use crate::telemetry::redact::payload_redactor;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio_tungstenite::tungstenite::{Bytes, Utf8Bytes};
+
+/// Longest frame dumped (bytes); longer ones are cut and marked.
+pub const MAX_RING_FRAME_BYTES: usize = 4096;
+
+/// Dumps written by this process (part of the file name: two dumps never collide).
+static DUMP_SEQ: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone)]
+enum RingFrame {
+    Text(Utf8Bytes),
+    Binary(Bytes),
+}
+
+impl RingFrame {
+    fn render(&self) -> String {
+        let text = match self {
+            RingFrame::Text(s) => std::borrow::Cow::Borrowed(s.as_str()),
+            RingFrame::Binary(b) => String::from_utf8_lossy(b),
+        };
+        if text.len() <= MAX_RING_FRAME_BYTES {
+            return text.into_owned();
+        }
+        let mut cut = MAX_RING_FRAME_BYTES;
+        while !text.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        format!("{}...[{} bytes]", &text[..cut], text.len())
+    }
+}
+
+#[derive(Debug)]
+pub struct FrameRing {
+    exchange: &'static str,
+    /// Venue stream names of the connection (`btcusdt@aggTrade`), for the dump header.
+    streams: String,
+    /// File-name-safe stream label (first stream, `+N` for the others).
+    label: String,
+    cap: usize,
+    frames: VecDeque<RingFrame>,
+    dump_dir: Option<PathBuf>,
+}
+
+impl FrameRing {
+    /// `cap` of 0 keeps nothing and never dumps.
+    pub fn new(
+        exchange: &'static str,
+        streams: &[String],
+        cap: usize,
+        dump_dir: Option<PathBuf>,
+    ) -> Self {
+        let mut label: String = streams
+            .first()
+            .map(|s| {
+                s.chars()
+                    .map(|c| match c {
+                        'a'..='z' | 'A'..='Z' | '0'..='9' | '@' | '.' | '_' | '-' => c,
+                        _ => '_',
+                    })
+                    .take(64)
+                    .collect()
+            })
+            .unwrap_or_default();
+        if streams.len() > 1 {
+            label.push_str(&format!("+{}", streams.len() - 1));
+        }
+        Self {
+            exchange,
+            streams: streams.join(","),
+            label,
+            cap,
+            frames: VecDeque::with_capacity(cap),
+            dump_dir,
+        }
+    }
+
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.cap > 0
+    }
+
+    /// Keep a text frame (a clone of the message's buffer, not a copy).
+    pub fn push_text(&mut self, text: &Utf8Bytes) {
+        self.push(RingFrame::Text(text.clone()));
+    }
+
+    /// Keep a decompressed frame; hands it back for delivery (copied only while enabled).
+    pub fn push_decoded(&mut self, text: String) -> String {
+        if !self.is_enabled() {
+            return text;
+        }
+        let text = Utf8Bytes::from(text);
+        self.push_text(&text);
+        text.to_string()
+    }
+
+    /// Keep a binary frame (rendered as lossy UTF-8 when dumped).
+    pub fn push_binary(&mut self, bytes: &Bytes) {
+        self.push(RingFrame::Binary(bytes.clone()));
+    }
+
+    fn push(&mut self, frame: RingFrame) {
+        if !self.is_enabled() {
+            return;
+        }
+        if self.frames.len() == self.cap {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(frame);
+    }
+
+    /// Kept frames, oldest first, cut and redacted.
+    pub fn frames(&self) -> Vec<String> {
+        let redactor = payload_redactor();
+        self.frames
+            .iter()
+            .map(|f| redactor.redact_text(&f.render()))
+            .collect()
+    }
+
+    /// Log the kept frames (and write them to the dump dir); returns the file written.
+    pub fn dump(&self, cause: &str) -> Option<PathBuf> {
+        if !self.is_enabled() || self.frames.is_empty() {
+            return None;
+        }
+        let frames = self.frames();
+        tracing::error!(
+            exchange = self.exchange,
+            streams = %self.streams,
+            cause,
+            frames = frames.len(),
+            recent_frames = %frames.join("\n"),
+            "ws stream failed; last raw frames"
+        );
+        let dir = self.dump_dir.as_deref()?;
+        match write_dump(
+            dir,
+            self.exchange,
+            &self.label,
+            &self.streams,
+            cause,
+            &frames,
+        ) {
+            Ok(path) => Some(path),
+            Err(e) => {
+                tracing::warn!(exchange = self.exchange, dir = %dir.display(), error = %e, "ws frame dump not written");
+                None
+            }
+        }
+    }
+}
+
+/// Message of a caught panic payload.
+pub fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    match payload.downcast_ref::<&str>() {
+        Some(s) => format!("panic: {s}"),
+        None => match payload.downcast_ref::<String>() {
+            Some(s) => format!("panic: {s}"),
+            None => "panic".to_string(),
+        },
+    }
+}
+
+/// One frame per line (newlines inside a frame escaped), after a `# exchange streams cause`
+/// header, in `<exchange>-<label>-<unix_ms>-<seq>.frames`.
+fn write_dump(
+    dir: &Path,
+    exchange: &str,
+    label: &str,
+    streams: &str,
+    cause: &str,
+    frames: &[String],
+) -> std::io::Result<PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    let now_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    let seq = DUMP_SEQ.fetch_add(1, Ordering::Relaxed);
+    let path = dir.join(format!("{exchange}-{label}-{now_ms}-{seq}.frames"));
+    let mut out = format!("# {exchange} {streams} {}\n", cause.replace('\n', " "));
+    for f in frames {
+        out.push_str(&f.replace('\n', "\\n"));
+        out.push('\n');
+    }
+    std::fs::write(&path, out)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ring_keeps_last_n_and_dumps_to_dir() {
+        let dir = std::env::temp_dir().join(format!("frame_ring_test_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let mut ring = FrameRing::new("binance_linear", &["btcusdt@aggTrade".into()], 2, None);
+        for i in 1..=3 {
+            ring.push_text(&format!(r#"{{"n":{i}}}"#).into());
+        }
+        ring.push_binary(&Bytes::from("x".repeat(MAX_RING_FRAME_BYTES + 10)));
+        let frames = ring.frames();
+        assert_eq!(frames[0], r#"{"n":3}"#);
+        assert!(frames[1].ends_with(&format!("...[{} bytes]", MAX_RING_FRAME_BYTES + 10)));
+
+        // Off: nothing kept
+        let mut off = FrameRing::new("binance_linear", &[], 0, Some(dir.clone()));
+        off.push_text(&"{}".into());
+        assert!(off.frames().is_empty() && off.dump("error").is_none());
+
+        let mut ring = FrameRing::new("hyperliquid_perp", &[], 4, Some(dir.clone()));
+        ring.push_text(&"{\"channel\":\n\"trades\"}".into());
+        let path = ring.dump("panic: handler bug").expect("dump written");
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            text,
+            "# hyperliquid_perp  panic: handler bug\n{\"channel\":\\n\"trades\"}\n"
+        );
+
+        // Streams of one exchange failing at once write distinct, labelled files
+        let streams = ["btcusdt@depth@100ms".to_string(), "ethusdt@depth".into()];
+        let mut a = FrameRing::new("binance_linear", &streams, 4, Some(dir.clone()));
+        a.push_text(&"{}".into());
+        let (first, second) = (a.dump("error").unwrap(), a.dump("error").unwrap());
+        assert_ne!(first, second);
+        let name = first.file_name().unwrap().to_string_lossy().into_owned();
+        assert!(
+            name.starts_with("binance_linear-btcusdt@depth@100ms+1-"),
+            "{name}"
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -6,8 +6,8 @@ use crate::{
         config::ExchangeConfigs,
         metrics::IngestMetrics,
         ws::{
-            ReconnectAttemptLimiter, SubscribeAttemptLimiter, build_ws_reconnect_limiter,
-            build_ws_subscribe_limiter,
+            ReconnectAttemptLimiter, SubscribeAttemptLimiter, WsLimiterOrder, acquire_together,
+            build_ws_reconnect_limiter, build_ws_subscribe_limiter,
         },
     },
 };
@@ -69,11 +69,51 @@ impl WsLimiterRegistry {
         Ok(())
     }
 
+    /// Permits of one (re)connect, taken in `order`: the reconnect permit (unless `reconnect`
+    /// is false, e.g. a fast reconnect) and `subscribes` subscribe permits.
+    pub async fn acquire_for_connect(
+        &self,
+        exchange: &str,
+        order: WsLimiterOrder,
+        reconnect: bool,
+        subscribes: usize,
+    ) -> AppResult<()> {
+        let r = self.get_reconnect(exchange)?;
+        let s = self.get_subscribe(exchange)?;
+        let n = u32::try_from(subscribes).unwrap_or(u32::MAX);
+        match order {
+            _ if !reconnect => s.acquire_n(n).await,
+            WsLimiterOrder::ReconnectFirst | WsLimiterOrder::AfterConnect => {
+                r.acquire().await;
+                s.acquire_n(n).await;
+            }
+            WsLimiterOrder::SubscribeFirst => {
+                s.acquire_n(n).await;
+                r.acquire().await;
+            }
+            WsLimiterOrder::Together => acquire_together(r, s, n).await,
+        }
+        Ok(())
+    }
+
     pub async fn set_used_subscribe_attempts(&self, exchange: &str, used: u32) -> AppResult<()> {
         self.get_subscribe(exchange)?.set_used_attempts(used).await;
         Ok(())
     }
 
+    /// Feed a venue-reported remaining subscribe budget into the exchange's subscribe limiter
+    /// (see `SubscribeAttemptLimiter::observe_venue_remaining`).
+    pub async fn observe_subscribe_remaining(
+        &self,
+        exchange: &str,
+        remaining: u32,
+    ) -> AppResult<u32> {
+        Ok(self
+            .get_subscribe(exchange)?
+            .observe_venue_remaining(remaining)
+            .await)
+    }
+
     pub async fn set_used_reconnect_attempts(&self, exchange: &str, used: u32) -> AppResult<()> {
         self.get_reconnect(exchange)?.set_used_attempts(used).await;
         Ok(())
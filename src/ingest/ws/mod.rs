@@ -1,10 +1,18 @@
+pub mod frame_ring;
 pub mod limiter_registry;
+pub mod proxy;
+pub mod recorder;
 pub mod subscribe_limiter;
+pub mod subscriptions;
 pub mod ws_client;
 
 #[cfg(test)]
 mod ws_tests;
 
+pub use frame_ring::*;
 pub use limiter_registry::*;
+pub use proxy::*;
+pub use recorder::*;
 pub use subscribe_limiter::*;
+pub use subscriptions::*;
 pub use ws_client::*;
@@ -0,0 +1,232 @@
+//! ingest/ws/proxy.rs
+//!
+//! WS connections through a forward proxy (`ws_proxy_url`).
+//!
+//! The TCP connection to the venue is tunnelled through the proxy first (HTTP `CONNECT`, or a
+//! SOCKS5 `CONNECT`), then TLS (for `wss://`) and the WebSocket handshake run over the tunnel
+//! exactly as for a direct connection. Credentials never sit in the config: `ws_proxy_auth_env`
+//! names an env var holding `user:password`, sent as `Proxy-Authorization: Basic` (HTTP) or
+//! username/password auth (SOCKS5).
+//!
+//! Proxy failures surface as `tungstenite::Error::Io`, like any other failed connect, so the
+//! reconnect loop backs off and retries the same way.
+
+use crate::error::{AppError, AppResult};
+use crate::ingest::config::ExchangeConfig;
+use base64::Engine;
+use std::io::{Error as IoError, ErrorKind};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::handshake::client::{Request, Response};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, client_async_tls, tungstenite};
+
+/// Longest proxy reply head read before giving up (bytes).
+const MAX_PROXY_REPLY_BYTES: usize = 8 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyKind {
+    /// `http://`: HTTP `CONNECT` tunnel.
+    Http,
+    /// `socks5://` / `socks5h://`: the proxy resolves the venue host either way.
+    Socks5,
+}
+
+#[derive(Debug, Clone)]
+pub struct WsProxy {
+    pub kind: ProxyKind,
+    pub host: String,
+    pub port: u16,
+    /// `user:password`, read from `ws_proxy_auth_env`.
+    auth: Option<String>,
+}
+
+impl WsProxy {
+    /// Parse `ws_proxy_url` (`http://host:port`, `socks5://host:port`); no auth.
+    pub fn parse(url: &str) -> AppResult<Self> {
+        let invalid = |why: &str| AppError::InvalidConfig(format!("ws_proxy_url `{url}`: {why}"));
+        let uri: http::Uri = url.parse().map_err(|_| invalid("not a URL"))?;
+        let (kind, default_port) = match uri.scheme_str() {
+            Some("http") => (ProxyKind::Http, 8080),
+            Some("socks5" | "socks5h") => (ProxyKind::Socks5, 1080),
+            _ => return Err(invalid("scheme must be http, socks5 or socks5h")),
+        };
+        if uri.authority().is_some_and(|a| a.as_str().contains('@')) {
+            return Err(invalid(
+                "credentials go in the env var named by ws_proxy_auth_env",
+            ));
+        }
+        let host = uri.host().ok_or_else(|| invalid("missing host"))?;
+        Ok(Self {
+            kind,
+            host: host.to_string(),
+            port: uri.port_u16().unwrap_or(default_port),
+            auth: None,
+        })
+    }
+
+    /// Proxy of an exchange (None = direct connect); fails when `ws_proxy_auth_env` is unset.
+    pub fn from_config(cfg: &ExchangeConfig) -> AppResult<Option<Self>> {
+        let Some(url) = cfg.ws_proxy_url.as_deref() else {
+            return Ok(None);
+        };
+        let mut proxy = Self::parse(url)?;
+        if let Some(key) = cfg.ws_proxy_auth_env.as_deref() {
+            let auth = std::env::var(key).map_err(|_| {
+                AppError::InvalidConfig(format!(
+                    "Missing environment variable '{key}' for ws_proxy_auth_env (exchange `{}`)",
+                    cfg.exchange
+                ))
+            })?;
+            if !auth.contains(':') {
+                return Err(AppError::InvalidConfig(format!(
+                    "'{key}' must hold user:password (exchange `{}`)",
+                    cfg.exchange
+                )));
+            }
+            proxy.auth = Some(auth);
+        }
+        Ok(Some(proxy))
+    }
+
+    /// Open a tunnel to the venue of `request`, then TLS + the WebSocket handshake over it.
+    pub async fn connect(
+        &self,
+        request: Request,
+    ) -> Result<(WebSocketStream<MaybeTlsStream<TcpStream>>, Response), tungstenite::Error> {
+        let target_host = request
+            .uri()
+            .host()
+            .ok_or(tungstenite::Error::Url(
+                tungstenite::error::UrlError::NoHostName,
+            ))?
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_string();
+        let target_port = request
+            .uri()
+            .port_u16()
+            .unwrap_or(match request.uri().scheme_str() {
+                Some("wss") => 443,
+                _ => 80,
+            });
+
+        let mut tcp = TcpStream::connect((self.host.as_str(), self.port)).await?;
+        tcp.set_nodelay(true)?;
+        match self.kind {
+            ProxyKind::Http => {
+                self.http_connect(&mut tcp, &target_host, target_port)
+                    .await?
+            }
+            ProxyKind::Socks5 => {
+                self.socks5_connect(&mut tcp, &target_host, target_port)
+                    .await?
+            }
+        }
+
+        client_async_tls(request, tcp).await
+    }
+
+    async fn http_connect(
+        &self,
+        tcp: &mut TcpStream,
+        host: &str,
+        port: u16,
+    ) -> std::io::Result<()> {
+        let target = if host.contains(':') {
+            format!("[{host}]:{port}")
+        } else {
+            format!("{host}:{port}")
+        };
+        let mut head = format!("CONNECT {target} HTTP/1.1\r\nHost: {target}\r\n");
+        if let Some(auth) = &self.auth {
+            let token = base64::engine::general_purpose::STANDARD.encode(auth);
+            head.push_str(&format!("Proxy-Authorization: Basic {token}\r\n"));
+        }
+        head.push_str("\r\n");
+        tcp.write_all(head.as_bytes()).await?;
+
+        // Read the reply head byte by byte: nothing past it may be consumed (TLS follows)
+        let mut reply = Vec::with_capacity(256);
+        while !reply.ends_with(b"\r\n\r\n") {
+            if reply.len() >= MAX_PROXY_REPLY_BYTES {
+                return Err(proxy_error("proxy CONNECT reply too long"));
+            }
+            reply.push(tcp.read_u8().await?);
+        }
+        let status_line = String::from_utf8_lossy(&reply);
+        let status_line = status_line.lines().next().unwrap_or_default();
+        let status = status_line.split_whitespace().nth(1);
+        if !status.is_some_and(|s| s.starts_with('2')) {
+            return Err(proxy_error(format!("proxy CONNECT refused: {status_line}")));
+        }
+        Ok(())
+    }
+
+    async fn socks5_connect(
+        &self,
+        tcp: &mut TcpStream,
+        host: &str,
+        port: u16,
+    ) -> std::io::Result<()> {
+        // Greeting: no auth, or username/password when configured
+        let greeting: &[u8] = match self.auth {
+            Some(_) => &[5, 2, 0, 2],
+            None => &[5, 1, 0],
+        };
+        tcp.write_all(greeting).await?;
+        let mut choice = [0u8; 2];
+        tcp.read_exact(&mut choice).await?;
+        match (choice, &self.auth) {
+            ([5, 0], _) => {}
+            ([5, 2], Some(auth)) => {
+                let (user, pass) = auth.split_once(':').unwrap_or((auth, ""));
+                if user.len() > 255 || pass.len() > 255 {
+                    return Err(proxy_error("socks5 credentials too long"));
+                }
+                let mut msg = vec![1, user.len() as u8];
+                msg.extend_from_slice(user.as_bytes());
+                msg.push(pass.len() as u8);
+                msg.extend_from_slice(pass.as_bytes());
+                tcp.write_all(&msg).await?;
+                let mut status = [0u8; 2];
+                tcp.read_exact(&mut status).await?;
+                if status[1] != 0 {
+                    return Err(proxy_error("socks5 authentication failed"));
+                }
+            }
+            _ => return Err(proxy_error("socks5 proxy offered no usable auth method")),
+        }
+
+        // CONNECT by domain name (the proxy resolves it)
+        if host.len() > 255 {
+            return Err(proxy_error("socks5 target host too long"));
+        }
+        let mut req = vec![5, 1, 0, 3, host.len() as u8];
+        req.extend_from_slice(host.as_bytes());
+        req.extend_from_slice(&port.to_be_bytes());
+        tcp.write_all(&req).await?;
+
+        let mut reply = [0u8; 4];
+        tcp.read_exact(&mut reply).await?;
+        if reply[1] != 0 {
+            return Err(proxy_error(format!(
+                "socks5 CONNECT refused (reply {})",
+                reply[1]
+            )));
+        }
+        // Bound address: skip it
+        let addr_len = match reply[3] {
+            1 => 4,
+            4 => 16,
+            3 => tcp.read_u8().await? as usize,
+            other => return Err(proxy_error(format!("socks5 address type {other}"))),
+        };
+        let mut bound = vec![0u8; addr_len + 2];
+        tcp.read_exact(&mut bound).await?;
+        Ok(())
+    }
+}
+
+fn proxy_error(msg: impl Into<String>) -> IoError {
+    IoError::new(ErrorKind::ConnectionRefused, msg.into())
+}
@@ -0,0 +1,249 @@
+//! ingest/ws/recorder.rs
+//!
+//! Raw WS frame recorder (`ws_record_frames_path`), for debugging shape changes and replaying
+//! venue payloads into mapper tests.
+//!
+//! Every text/binary frame a `WsClient` receives is appended as one JSON line:
+//! `{"ts_ms":..,"exchange":..,"stream":..,"kind":"text"|"binary","data":..}` (binary frames
+//! base64, as received: before `ws_compression` decoding). Text frames go through the payload
+//! redactor (`logging.redact_fields`) like the frame ring dumps.
+//!
+//! - The read path only `try_send`s into a bounded channel; a background thread does the file
+//!   I/O. When the writer falls behind, frames are dropped (counted, logged) instead of
+//!   slowing reads.
+//! - Past `ws_record_frames_max_bytes` the file is rotated: renamed to `<path>.1` (replacing
+//!   the previous one) and a new file started, so at most twice the limit stays on disk.
+
+use crate::telemetry::redact::payload_redactor;
+use crate::telemetry::throttle::log_throttle;
+use base64::Engine;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{Receiver, SyncSender, TrySendError, sync_channel};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Frames queued for the writer before new ones are dropped.
+const RECORDER_QUEUE: usize = 4096;
+
+#[derive(Debug)]
+enum FrameData {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+#[derive(Debug)]
+struct RecordedFrame {
+    ts_ms: i64,
+    stream: String,
+    data: FrameData,
+}
+
+#[derive(Debug, Clone)]
+pub struct FrameRecorder {
+    exchange: &'static str,
+    tx: SyncSender<RecordedFrame>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl FrameRecorder {
+    /// Start the writer thread for `path` (parent dirs created on first write). The thread
+    /// ends once every clone of the recorder is dropped and the queue is written out.
+    pub fn spawn(exchange: &'static str, path: PathBuf, max_bytes: u64) -> Self {
+        let (tx, rx) = sync_channel(RECORDER_QUEUE);
+        let spawned = std::thread::Builder::new()
+            .name(format!("ws-recorder-{exchange}"))
+            .spawn(move || write_frames(exchange, &path, max_bytes, rx));
+        if let Err(e) = spawned {
+            tracing::warn!(exchange, error = %e, "ws frame recorder not started");
+        }
+        Self {
+            exchange,
+            tx,
+            dropped: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    pub fn record_text(&self, stream: &str, text: &str) {
+        self.record(stream, FrameData::Text(text.to_string()));
+    }
+
+    pub fn record_binary(&self, stream: &str, bytes: &[u8]) {
+        self.record(stream, FrameData::Binary(bytes.to_vec()));
+    }
+
+    /// Frames dropped because the writer fell behind (or is gone).
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    fn record(&self, stream: &str, data: FrameData) {
+        let frame = RecordedFrame {
+            ts_ms: now_ms(),
+            stream: stream.to_string(),
+            data,
+        };
+        match self.tx.try_send(frame) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_) | TrySendError::Disconnected(_)) => {
+                let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+                let key = format!("ws recorder full:{}", self.exchange);
+                if let Some(suppressed) = log_throttle().allow(&key) {
+                    tracing::warn!(
+                        exchange = self.exchange,
+                        dropped,
+                        suppressed,
+                        "ws frame recorder behind; frames dropped"
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+fn open_append(path: &Path) -> std::io::Result<(BufWriter<File>, u64)> {
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)?;
+    }
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let len = file.metadata()?.len();
+    Ok((BufWriter::new(file), len))
+}
+
+fn rotated_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".1");
+    PathBuf::from(name)
+}
+
+fn frame_line(exchange: &str, frame: RecordedFrame) -> String {
+    let (kind, data) = match frame.data {
+        FrameData::Text(text) => ("text", payload_redactor().redact_text(&text)),
+        FrameData::Binary(bytes) => (
+            "binary",
+            base64::engine::general_purpose::STANDARD.encode(bytes),
+        ),
+    };
+    let mut line = serde_json::json!({
+        "ts_ms": frame.ts_ms,
+        "exchange": exchange,
+        "stream": frame.stream,
+        "kind": kind,
+        "data": data,
+    })
+    .to_string();
+    line.push('\n');
+    line
+}
+
+/// Writer thread: drain the queue, write each batch, flush, rotate when over `max_bytes`.
+fn write_frames(exchange: &str, path: &Path, max_bytes: u64, rx: Receiver<RecordedFrame>) {
+    let mut out: Option<(BufWriter<File>, u64)> = None;
+    while let Ok(first) = rx.recv() {
+        let result = (|| -> std::io::Result<()> {
+            for frame in std::iter::once(first).chain(rx.try_iter()) {
+                let line = frame_line(exchange, frame);
+                if let Some((w, written)) = &mut out
+                    && *written > 0
+                    && *written + line.len() as u64 > max_bytes
+                {
+                    w.flush()?;
+                    out = None;
+                    std::fs::rename(path, rotated_path(path))?;
+                }
+                let (w, written) = match &mut out {
+                    Some(o) => o,
+                    None => out.insert(open_append(path)?),
+                };
+                w.write_all(line.as_bytes())?;
+                *written += line.len() as u64;
+            }
+            match &mut out {
+                Some((w, _)) => w.flush(),
+                None => Ok(()),
+            }
+        })();
+
+        if let Err(e) = result {
+            // Reopen on the next batch
+            out = None;
+            let key = format!("ws recorder write:{exchange}");
+            if let Some(suppressed) = log_throttle().allow(&key) {
+                tracing::warn!(
+                    exchange,
+                    path = %path.display(),
+                    error = %e,
+                    suppressed,
+                    "ws frame recorder write failed"
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn records_frames_as_json_lines_and_rotates() {
+        let dir = std::env::temp_dir().join(format!("ws_recorder_test_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("binance.ndjson");
+
+        // Each line is ~125 bytes: 3 fit under 400, the 4th rotates
+        let rec = FrameRecorder::spawn("binance_linear", path.clone(), 400);
+        for i in 0..4 {
+            rec.record_text(
+                "btcusdt@aggTrade",
+                &format!(r#"{{"e":"aggTrade","n":{i}}}"#),
+            );
+        }
+        rec.record_binary("btcusdt@aggTrade", &[0x1f, 0x8b, 0x00]);
+        assert_eq!(rec.dropped(), 0);
+        drop(rec);
+
+        let read_lines = |p: &Path| -> Vec<Value> {
+            std::fs::read_to_string(p)
+                .unwrap_or_default()
+                .lines()
+                .map(|l| serde_json::from_str(l).unwrap())
+                .collect()
+        };
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while (
+            read_lines(&rotated_path(&path)).len(),
+            read_lines(&path).len(),
+        ) != (3, 2)
+            && Instant::now() < deadline
+        {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        let rotated = read_lines(&rotated_path(&path));
+        let current = read_lines(&path);
+        assert_eq!(rotated.len(), 3);
+        assert_eq!(current.len(), 2);
+        assert_eq!(rotated[0]["exchange"], "binance_linear");
+        assert_eq!(rotated[0]["stream"], "btcusdt@aggTrade");
+        assert_eq!(rotated[0]["kind"], "text");
+        assert_eq!(rotated[0]["data"], r#"{"e":"aggTrade","n":0}"#);
+        assert!(rotated[0]["ts_ms"].as_i64().unwrap() > 0);
+        assert_eq!(current[0]["data"], r#"{"e":"aggTrade","n":3}"#);
+        assert_eq!(current[1]["kind"], "binary");
+        assert_eq!(current[1]["data"], "H4sA");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,6 +1,7 @@
 // src/ingest/ws_limiters.rs
 use crate::ingest::config::ExchangeConfig;
 use crate::ingest::metrics::IngestMetrics;
+use serde::Deserialize;
 use std::{
     sync::Arc,
     time::{Duration, Instant},
@@ -27,6 +28,36 @@ impl State {
             used: 0,
         }
     }
+
+    /// Start a new window if the current one is over; returns the time left in it.
+    fn roll(&mut self, window: Duration) -> Duration {
+        if self.window_start.elapsed() >= window {
+            self.window_start = Instant::now();
+            self.used = 0;
+        }
+        window.saturating_sub(self.window_start.elapsed())
+    }
+}
+
+/// Order in which a (re)connect takes its reconnect and subscribe permits
+/// (`ws_limiter_order`).
+///
+/// Taking the subscribe permits after the socket is open leaves the connection idle (and the
+/// reconnect permit spent) while the subscribe window refills; under a mass reconnect that
+/// adds up across streams. Every mode but `after_connect` takes all permits before connecting,
+/// so subscribes go out as soon as the socket is open.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WsLimiterOrder {
+    /// Reconnect permit, then one subscribe permit per message.
+    #[default]
+    ReconnectFirst,
+    /// Subscribe permits, then the reconnect permit.
+    SubscribeFirst,
+    /// Both at once, only when both limiters have room: nothing is held while waiting.
+    Together,
+    /// Reconnect permit before connecting, each subscribe permit right before its message.
+    AfterConnect,
 }
 
 /// Limits WS SUBSCRIBE attempts: each attempt costs weight=1.
@@ -115,6 +146,24 @@ impl SubscribeAttemptLimiter {
         let used = self.used_attempts().await;
         self.cfg.max_attempts.saturating_sub(used)
     }
+
+    /// The venue reports `remaining` subscribes left: cap the active window to it (never
+    /// grants more than `max_attempts`; the next window starts with the full budget again).
+    /// Returns the remaining attempts after the update.
+    pub async fn observe_venue_remaining(&self, remaining: u32) -> u32 {
+        let mut st = self.state.lock().await;
+        st.roll(self.cfg.window);
+        let used = self.cfg.max_attempts.saturating_sub(remaining);
+        st.used = st.used.max(used);
+        self.cfg.max_attempts.saturating_sub(st.used)
+    }
+
+    /// Acquire `n` subscribe attempts, one after the other.
+    pub async fn acquire_n(&self, n: u32) {
+        for _ in 0..n {
+            self.acquire().await;
+        }
+    }
 }
 
 /// Limits WS RECONNECT attempts: each attempt costs weight=1.
@@ -203,6 +252,70 @@ impl ReconnectAttemptLimiter {
     }
 }
 
+/// Acquire 1 reconnect attempt and `subscribes` subscribe attempts together: all are taken
+/// in the same instant once both windows have room, none while waiting. Subscribes above one
+/// window's `max_attempts` can never fit at once; the rest are acquired after, one by one.
+///
+/// Locks are always taken reconnect first, so concurrent callers cannot wait on each other.
+pub async fn acquire_together(
+    reconnect: &ReconnectAttemptLimiter,
+    subscribe: &SubscribeAttemptLimiter,
+    subscribes: u32,
+) {
+    let at_once = subscribes.min(subscribe.cfg.max_attempts);
+    let start_wait = Instant::now();
+    loop {
+        let mut rst = reconnect.state.lock().await;
+        let mut sst = subscribe.state.lock().await;
+        let r_left = rst.roll(reconnect.cfg.window);
+        let s_left = sst.roll(subscribe.cfg.window);
+
+        let r_room = rst.used < reconnect.cfg.max_attempts;
+        let s_room = subscribe.cfg.max_attempts.saturating_sub(sst.used) >= at_once;
+        if r_room && s_room {
+            rst.used += 1;
+            sst.used += at_once;
+            drop(sst);
+            drop(rst);
+
+            let waited = start_wait.elapsed().as_secs_f64();
+            if let Some(m) = &reconnect.metrics {
+                m.inc_ws_reconnect_attempt();
+                if waited > 0.0 {
+                    m.observe_ws_reconnect_wait(waited);
+                }
+            }
+            if let Some(m) = &subscribe.metrics {
+                (0..at_once).for_each(|_| m.inc_ws_subscribe_attempt());
+                if waited > 0.0 {
+                    m.observe_ws_subscribe_wait(waited);
+                }
+            }
+            break;
+        }
+
+        // Wait for every window that is full to reset
+        let mut sleep_for = Duration::ZERO;
+        if !r_room {
+            if let Some(m) = &reconnect.metrics {
+                m.inc_ws_reconnect_rate_limited();
+            }
+            sleep_for = sleep_for.max(r_left);
+        }
+        if !s_room {
+            if let Some(m) = &subscribe.metrics {
+                m.inc_ws_subscribe_rate_limited();
+            }
+            sleep_for = sleep_for.max(s_left);
+        }
+        drop(sst);
+        drop(rst);
+        tokio::time::sleep(sleep_for).await;
+    }
+
+    subscribe.acquire_n(subscribes - at_once).await;
+}
+
 /// Builder: subscribe limiter from ExchangeConfig.
 pub fn build_ws_subscribe_limiter(
     cfg: &ExchangeConfig,
@@ -0,0 +1,157 @@
+//! ingest/ws/subscriptions.rs
+//!
+//! Live WS subscriptions, per connection (`GET /ws/subscriptions`).
+//!
+//! Ground truth about what is subscribed right now, as opposed to intent (the stream
+//! registry) or connection state. A connection's streams are recorded once every subscribe
+//! message went out and, when the venue is asked to ack (`ws_subscribe_ack_timeout_seconds`),
+//! every ack came back; they are dropped when the connection ends, however it ends.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SubscribedStream {
+    /// Venue stream name (`btcusdt@aggTrade`, `trades:BTC`).
+    pub stream: String,
+    pub subscribed_at_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WsConnectionSubscriptions {
+    pub conn_id: u64,
+    pub exchange: &'static str,
+    pub streams: Vec<SubscribedStream>,
+}
+
+#[derive(Debug, Default)]
+pub struct WsSubscriptions {
+    next_conn_id: AtomicU64,
+    by_conn: Mutex<BTreeMap<u64, WsConnectionSubscriptions>>,
+}
+
+impl WsSubscriptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<u64, WsConnectionSubscriptions>> {
+        self.by_conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Record a connection whose subscribes all went out. The streams stay listed until the
+    /// returned guard is dropped (disconnect, cancel, or an error leaving the read loop).
+    pub fn on_subscribed(
+        self: &Arc<Self>,
+        exchange: &'static str,
+        streams: &[String],
+    ) -> SubscriptionGuard {
+        let conn_id = self.next_conn_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let subscribed_at_ms = now_ms();
+        let entry = WsConnectionSubscriptions {
+            conn_id,
+            exchange,
+            streams: streams
+                .iter()
+                .map(|s| SubscribedStream {
+                    stream: s.clone(),
+                    subscribed_at_ms,
+                })
+                .collect(),
+        };
+        self.lock().insert(conn_id, entry);
+        SubscriptionGuard {
+            subs: self.clone(),
+            conn_id,
+        }
+    }
+
+    fn on_disconnected(&self, conn_id: u64) {
+        self.lock().remove(&conn_id);
+    }
+
+    /// Live connections in connect order, optionally for one exchange.
+    pub fn snapshot(&self, exchange: Option<&str>) -> Vec<WsConnectionSubscriptions> {
+        self.lock()
+            .values()
+            .filter(|c| exchange.is_none_or(|ex| c.exchange == ex))
+            .cloned()
+            .collect()
+    }
+
+    /// True if some live connection of `exchange` is subscribed to `stream`.
+    pub fn is_subscribed(&self, exchange: &str, stream: &str) -> bool {
+        self.lock()
+            .values()
+            .any(|c| c.exchange == exchange && c.streams.iter().any(|s| s.stream == stream))
+    }
+}
+
+/// Clears its connection's subscriptions on drop.
+#[derive(Debug)]
+pub struct SubscriptionGuard {
+    subs: Arc<WsSubscriptions>,
+    conn_id: u64,
+}
+
+impl SubscriptionGuard {
+    pub fn conn_id(&self) -> u64 {
+        self.conn_id
+    }
+}
+
+impl Drop for SubscriptionGuard {
+    fn drop(&mut self) {
+        self.subs.on_disconnected(self.conn_id);
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registry_reflects_subscribes_and_disconnects() {
+        let subs = Arc::new(WsSubscriptions::new());
+        let bn = subs.on_subscribed(
+            "binance_linear",
+            &["btcusdt@aggTrade".into(), "btcusdt@depth@100ms".into()],
+        );
+        let hl = subs.on_subscribed("hyperliquid_perp", &["trades:BTC".into()]);
+
+        let all = subs.snapshot(None);
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].conn_id, bn.conn_id());
+        assert_eq!(all[0].streams.len(), 2);
+        assert!(all[0].streams.iter().all(|s| s.subscribed_at_ms > 0));
+        assert!(subs.is_subscribed("binance_linear", "btcusdt@aggTrade"));
+        assert!(!subs.is_subscribed("hyperliquid_perp", "btcusdt@aggTrade"));
+
+        let only_hl = subs.snapshot(Some("hyperliquid_perp"));
+        assert_eq!(only_hl.len(), 1);
+        assert_eq!(only_hl[0].conn_id, hl.conn_id());
+
+        // Disconnect clears that connection only; a reconnect is a new connection
+        drop(bn);
+        assert!(!subs.is_subscribed("binance_linear", "btcusdt@aggTrade"));
+        assert_eq!(subs.snapshot(None).len(), 1);
+
+        let bn2 = subs.on_subscribed("binance_linear", &["btcusdt@aggTrade".into()]);
+        assert_ne!(bn2.conn_id(), all[0].conn_id);
+        assert!(subs.is_subscribed("binance_linear", "btcusdt@aggTrade"));
+
+        drop(hl);
+        drop(bn2);
+        assert!(subs.snapshot(None).is_empty());
+    }
+}
@@ -1,19 +1,35 @@
 use crate::app::AppConfig;
 use crate::error::{AppError, AppResult};
-use crate::ingest::config::{ExchangeConfig, StringOrTable, WsStream};
+use crate::ingest::config::{ExchangeConfig, StringOrTable, WsStream, header_map};
 use crate::ingest::metrics::IngestMetrics;
-use crate::ingest::spec::{Ctx, resolve_ws_control, seed_ws_stream_ctx};
+use crate::ingest::spec::{
+    Ctx, RESOLVE_PARALLEL_MIN_ITEMS, WsControlSpec, log_resolve_progress,
+    resolve_ws_control_batches_within, seed_ws_stream_ctx,
+};
+use crate::ingest::ws::frame_ring::{FrameRing, panic_message};
 use crate::ingest::ws::limiter_registry::WsLimiterRegistry;
-use futures_util::{SinkExt, StreamExt};
+use crate::ingest::ws::proxy::WsProxy;
+use crate::ingest::ws::recorder::FrameRecorder;
+use crate::ingest::ws::subscribe_limiter::WsLimiterOrder;
+use crate::ingest::ws::subscriptions::WsSubscriptions;
+use crate::telemetry::throttle::log_throttle;
+use futures_util::{FutureExt, SinkExt, StreamExt};
 use rand::{Rng, rng};
 use serde_json::Value as JsonValue;
+use std::collections::VecDeque;
+use std::io::Read;
+use std::panic::AssertUnwindSafe;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::time::{Instant, interval};
 use tokio::time::{sleep, sleep_until};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::handshake::client::Request;
+use tokio_tungstenite::tungstenite::protocol::{CloseFrame, WebSocketConfig};
 use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
 use tokio_util::sync::CancellationToken;
-use tracing::{error, info, warn};
+use tracing::{debug, error, info, warn};
 
 #[derive(Debug, Clone)]
 pub enum WsEvent {
@@ -22,6 +38,56 @@ pub enum WsEvent {
     Ping(Vec<u8>),
     Pong(Vec<u8>),
     Close(Option<String>),
+    /// Socket open, before subscribing. `attempt` counts connections of this run (1 = first;
+    /// higher = a reconnect, in-memory state built from the stream is stale).
+    Connected {
+        attempt: u32,
+    },
+    /// Connection left, whichever way (also after `Close`); same reason as the test hook gets.
+    /// `close` is set when the venue closed it with a close frame.
+    Disconnected {
+        reason: Option<String>,
+        close: Option<WsCloseInfo>,
+    },
+}
+
+/// Close frame sent by the venue. A close without a frame is reported as 1005 (no status).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WsCloseInfo {
+    pub code: u16,
+    pub reason: String,
+}
+
+impl WsCloseInfo {
+    pub fn from_frame(frame: Option<&CloseFrame>) -> Self {
+        match frame {
+            Some(f) => Self {
+                code: f.code.into(),
+                reason: f.reason.to_string(),
+            },
+            None => Self {
+                code: 1005,
+                reason: String::new(),
+            },
+        }
+    }
+
+    /// 1008 (policy violation) and 1013 (try again later): how venues close rate-limited
+    /// connections. Such a close never takes the fast reconnect path, and its backoff starts
+    /// at `ws_reconnect_backoff_max_ms` (see `ReconnectState::on_throttle`).
+    pub fn is_throttle(&self) -> bool {
+        matches!(self.code, 1008 | 1013)
+    }
+}
+
+impl std::fmt::Display for WsCloseInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.reason.is_empty() {
+            write!(f, "close: {}", self.code)
+        } else {
+            write!(f, "close: {} {}", self.code, self.reason)
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -34,6 +100,18 @@ pub struct WsClient {
     pub ws_reconnect_backoff_max_ms: u64,
     pub ws_reconnect_trip_after_failures: u32,
     pub ws_reconnect_cooldown_seconds: u64,
+    /// Connections that stayed up at least this long reconnect immediately
+    /// (no backoff, no reconnect limiter). 0 = always use the full path.
+    pub ws_stable_connection_threshold_ms: u64,
+    /// Live subscriptions are recorded here when set (`streams.ws_track_subscriptions`).
+    pub subscriptions: Option<Arc<WsSubscriptions>>,
+    /// Last raw frames kept per stream for a failure dump (`streams.ws_frame_ring_size`, 0 = off).
+    pub ws_frame_ring_size: usize,
+    pub ws_frame_ring_dump_dir: Option<PathBuf>,
+    /// Multiplexed subscribes still rendering after this long are reported (`streams.resolve_budget_ms`).
+    pub resolve_budget: Option<Duration>,
+    /// Every received text/binary frame is appended here when set (`ws_record_frames_path`).
+    pub recorder: Option<FrameRecorder>,
 }
 
 impl WsClient {
@@ -42,6 +120,7 @@ impl WsClient {
     const DEFAULT_WS_RECONNECT_BACKOFF_MAX_MS: u64 = 30_000;
     const DEFAULT_WS_RECONNECT_TRIP_AFTER_FAILURES: u32 = 10;
     const DEFAULT_WS_RECONNECT_COOLDOWN_SECONDS: u64 = 120;
+    const DEFAULT_WS_STABLE_CONNECTION_THRESHOLD_MS: u64 = 0;
     pub fn new(
         name: &'static str,
         cfg: ExchangeConfig,
@@ -49,21 +128,44 @@ impl WsClient {
         app_cfg: Option<&AppConfig>,
     ) -> Self {
         // pick from app config if provided, otherwise defaults
-        let (initial_ms, max_ms, trip_after, cooldown_s) = match app_cfg.as_deref() {
+        let (initial_ms, max_ms, trip_after, cooldown_s, stable_ms) = match app_cfg {
             Some(ac) => (
                 ac.streams.ws_reconnect_backoff_initial_ms,
                 ac.streams.ws_reconnect_backoff_max_ms,
                 ac.streams.ws_reconnect_trip_after_failures,
                 ac.streams.ws_reconnect_cooldown_seconds,
+                ac.streams.ws_stable_connection_threshold_ms,
             ),
             None => (
                 Self::DEFAULT_WS_RECONNECT_BACKOFF_INITIAL_MS,
                 Self::DEFAULT_WS_RECONNECT_BACKOFF_MAX_MS,
                 Self::DEFAULT_WS_RECONNECT_TRIP_AFTER_FAILURES,
                 Self::DEFAULT_WS_RECONNECT_COOLDOWN_SECONDS,
+                Self::DEFAULT_WS_STABLE_CONNECTION_THRESHOLD_MS,
             ),
         };
 
+        let (ring_size, ring_dump_dir) = app_cfg
+            .map(|ac| {
+                (
+                    ac.streams.ws_frame_ring_size,
+                    ac.streams.ws_frame_ring_dump_dir.clone().map(PathBuf::from),
+                )
+            })
+            .unwrap_or_default();
+        let resolve_budget = app_cfg
+            .map(|ac| ac.streams.resolve_budget_ms)
+            .filter(|ms| *ms > 0)
+            .map(Duration::from_millis);
+
+        let recorder = cfg.ws_record_frames_path.as_ref().map(|path| {
+            FrameRecorder::spawn(name, PathBuf::from(path), cfg.ws_record_frames_max_bytes)
+        });
+
+        // Exchange overrides of the app-wide backoff
+        let initial_ms = cfg.ws_reconnect_backoff_ms.unwrap_or(initial_ms);
+        let max_ms = cfg.ws_reconnect_backoff_max_ms.unwrap_or(max_ms);
+
         Self {
             name,
             cfg,
@@ -72,19 +174,65 @@ impl WsClient {
             ws_reconnect_backoff_max_ms: max_ms,
             ws_reconnect_trip_after_failures: trip_after,
             ws_reconnect_cooldown_seconds: cooldown_s,
+            ws_stable_connection_threshold_ms: stable_ms,
+            subscriptions: None,
+            ws_frame_ring_size: ring_size,
+            ws_frame_ring_dump_dir: ring_dump_dir,
+            resolve_budget,
+            recorder,
+        }
+    }
+
+    /// Keep the last `size` frames of each stream, dumped (to `dump_dir` too) on failure.
+    pub fn with_frame_ring(mut self, size: usize, dump_dir: Option<PathBuf>) -> Self {
+        self.ws_frame_ring_size = size;
+        self.ws_frame_ring_dump_dir = dump_dir;
+        self
+    }
+
+    /// Append raw frames to `recorder` (replaces the one from `ws_record_frames_path`).
+    pub fn with_recorder(mut self, recorder: Option<FrameRecorder>) -> Self {
+        self.recorder = recorder;
+        self
+    }
+
+    /// Hand a received text/binary frame to the recorder, if any (never blocks).
+    fn record_frame(&self, streams: &str, msg: &Message) {
+        let Some(rec) = &self.recorder else {
+            return;
+        };
+        match msg {
+            Message::Text(s) => rec.record_text(streams, s),
+            Message::Binary(b) => rec.record_binary(streams, b),
+            _ => {}
         }
     }
 
-    /// Run ONE stream per connection.
+    pub fn with_subscriptions(mut self, subscriptions: Option<Arc<WsSubscriptions>>) -> Self {
+        self.subscriptions = subscriptions;
+        self
+    }
+
+    fn reconnect_state(&self) -> ReconnectState {
+        ReconnectState::new(
+            self.ws_reconnect_backoff_initial_ms,
+            Duration::from_millis(self.ws_stable_connection_threshold_ms),
+        )
+    }
+
+    /// Run ONE stream per connection (`run_streams` with a single stream).
     ///
     /// ws_limiters is optional to make tests easier (no registry needed).
     /// test_hook is optional to allow terminating the reconnect loop deterministically in tests.
+    /// cancel stops the stream (e.g. `remove_stream`): the connection is left after a
+    /// best-effort unsubscribe, or the limiter / connect wait abandoned, and `Ok(())` returned
+    /// without reconnecting.
     pub async fn run_stream<F, Fut>(
         &self,
         ws_limiters: Option<&WsLimiterRegistry>,
         stream: &WsStream,
-        mut ctx: Ctx,
-        mut on_event: F,
+        ctx: Ctx,
+        on_event: F,
         test_hook: Option<&mut WsTestHook>,
         cancel: Option<CancellationToken>,
     ) -> AppResult<()>
@@ -92,17 +240,93 @@ impl WsClient {
         F: FnMut(WsEvent) -> Fut,
         Fut: std::future::Future<Output = AppResult<()>>,
     {
-        seed_ws_stream_ctx(stream, &mut ctx)?;
+        self.run_streams(
+            ws_limiters,
+            vec![(stream, ctx)],
+            on_event,
+            test_hook,
+            cancel,
+        )
+        .await
+    }
 
-        ctx.entry("stream_id".to_string())
-            .or_insert_with(|| "1".to_string());
+    /// `run_stream` with its subscribe batches rendered beforehand (`resolve_ws_control_batches`
+    /// of the same ctx, e.g. by the startup restore rendering every stream at once); None
+    /// renders them here.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn run_stream_rendered<F, Fut>(
+        &self,
+        ws_limiters: Option<&WsLimiterRegistry>,
+        stream: &WsStream,
+        ctx: Ctx,
+        rendered: Option<Vec<WsControlSpec>>,
+        on_event: F,
+        test_hook: Option<&mut WsTestHook>,
+        cancel: Option<CancellationToken>,
+    ) -> AppResult<()>
+    where
+        F: FnMut(WsEvent) -> Fut,
+        Fut: std::future::Future<Output = AppResult<()>>,
+    {
+        let Some(batches) = rendered else {
+            return self
+                .run_stream(ws_limiters, stream, ctx, on_event, test_hook, cancel)
+                .await;
+        };
+        let ctxs = seed_stream_ctxs(vec![(stream, ctx)])?;
+        self.connect_loop(
+            ws_limiters,
+            ControlMsgs::new(&ctxs, batches),
+            on_event,
+            test_hook,
+            cancel,
+        )
+        .await
+    }
+
+    /// Run MANY streams on one connection.
+    ///
+    /// Subscribes are multiplexed and split per `ws_max_subscribe_bytes` /
+    /// `ws_max_symbols_per_subscribe`; each message goes through the subscribe limiter.
+    /// Frames of every stream go to the one `on_event`; every subscribe is re-sent on
+    /// reconnect.
+    pub async fn run_streams<F, Fut>(
+        &self,
+        ws_limiters: Option<&WsLimiterRegistry>,
+        streams: Vec<(&WsStream, Ctx)>,
+        on_event: F,
+        test_hook: Option<&mut WsTestHook>,
+        cancel: Option<CancellationToken>,
+    ) -> AppResult<()>
+    where
+        F: FnMut(WsEvent) -> Fut,
+        Fut: std::future::Future<Output = AppResult<()>>,
+    {
+        let mut ctxs = seed_stream_ctxs(streams)?;
 
-        let control = resolve_ws_control(&self.cfg, &ctx)?;
+        let batches = if ctxs.len() < RESOLVE_PARALLEL_MIN_ITEMS {
+            resolve_ws_control_batches_within(
+                &self.cfg,
+                &ctxs,
+                self.resolve_budget,
+                &log_resolve_progress,
+            )?
+        } else {
+            // Many streams: render on the blocking pool, not on this async worker
+            let (cfg, budget) = (self.cfg.clone(), self.resolve_budget);
+            let (batches, back) = tokio::task::spawn_blocking(move || {
+                let batches =
+                    resolve_ws_control_batches_within(&cfg, &ctxs, budget, &log_resolve_progress);
+                (batches, ctxs)
+            })
+            .await?;
+            ctxs = back;
+            batches?
+        };
 
         self.connect_loop(
             ws_limiters,
-            control.subscribe,
-            control.unsubscribe,
+            ControlMsgs::new(&ctxs, batches),
             on_event,
             test_hook,
             cancel,
@@ -110,11 +334,28 @@ impl WsClient {
         .await
     }
 
+    /// WS upgrade request for `ws_base_url`, carrying the exchange default headers.
+    fn handshake_request(&self) -> AppResult<Request> {
+        let mut req = self
+            .cfg
+            .ws_base_url
+            .as_str()
+            .into_client_request()
+            .map_err(|e| {
+                AppError::InvalidConfig(format!(
+                    "{}: invalid ws_base_url `{}`: {e}",
+                    self.name, self.cfg.ws_base_url
+                ))
+            })?;
+        req.headers_mut()
+            .extend(header_map(&self.cfg.resolved_headers)?);
+        Ok(req)
+    }
+
     async fn connect_loop<F, Fut>(
         &self,
         ws_limiters: Option<&WsLimiterRegistry>,
-        subscribe_msg: JsonValue,
-        unsubscribe_msg: JsonValue,
+        control: ControlMsgs,
         mut on_event: F,
         mut test_hook: Option<&mut WsTestHook>,
         cancel: Option<CancellationToken>,
@@ -125,8 +366,24 @@ impl WsClient {
     {
         let cancel = cancel.unwrap_or_else(CancellationToken::new);
 
-        let mut consecutive_failures: u32 = 0;
-        let mut backoff_ms: u64 = self.ws_reconnect_backoff_initial_ms;
+        // Bad URL / header / proxy config is not retryable
+        let handshake = self.handshake_request()?;
+        let proxy = WsProxy::from_config(&self.cfg)?;
+
+        let mut rs = self.reconnect_state();
+        let mut fast_reconnect = false;
+        let skip_sleep = test_hook.as_deref().is_some_and(|h| h.skip_reconnect_sleep);
+        let mut connects: u32 = 0;
+        // Last frames handed to on_event, dumped if it fails (or panics)
+        let mut ring = FrameRing::new(
+            self.name,
+            &control.stream_labels,
+            self.ws_frame_ring_size,
+            self.ws_frame_ring_dump_dir.clone(),
+        );
+        let record_streams = control.stream_labels.join(",");
+        let subscribe_frames: usize = control.subscribe.iter().map(Vec::len).sum();
+        let step_delay = Duration::from_millis(self.cfg.ws_subscribe_step_delay_ms);
 
         loop {
             if cancel.is_cancelled() {
@@ -141,54 +398,182 @@ impl WsClient {
                 }
             }
 
-            // --- RECONNECT limiter
+            // --- LIMITERS: reconnect (skipped on the fast path) and, unless after_connect,
+            // every subscribe permit, so the socket never sits open waiting for one
+            let order = self.cfg.ws_limiter_order;
+            let reconnect = !std::mem::take(&mut fast_reconnect);
             if let Some(lims) = ws_limiters {
-                lims.acquire_reconnect(self.name).await?;
+                let subscribes = match order {
+                    WsLimiterOrder::AfterConnect => 0,
+                    _ => subscribe_frames,
+                };
+                // Cancel also ends a stream still waiting for its permits
+                let acquire = lims.acquire_for_connect(self.name, order, reconnect, subscribes);
+                if cancel
+                    .run_until_cancelled(acquire)
+                    .await
+                    .transpose()?
+                    .is_none()
+                {
+                    info!(exchange = self.name, "ws cancelled (waiting for limiter)");
+                    return Ok(());
+                }
             }
 
-            let url = self.cfg.ws_base_url.clone();
-            info!(exchange = self.name, url = %url, "ws connecting");
+            info!(
+                exchange = self.name,
+                url = %self.cfg.ws_base_url,
+                proxy = proxy.as_ref().map(|p| format!("{}:{}", p.host, p.port)),
+                "ws connecting"
+            );
 
-            let (ws, _resp) = match connect_async(url).await {
+            let connect = async {
+                match &proxy {
+                    Some(proxy) => proxy.connect(handshake.clone()).await,
+                    None => connect_async(handshake.clone()).await,
+                }
+            };
+            let Some(connected) = cancel.run_until_cancelled(connect).await else {
+                info!(exchange = self.name, "ws cancelled (connecting)");
+                return Ok(());
+            };
+            let (ws, _resp) = match connected {
                 Ok(ok) => ok,
                 Err(e) => {
-                    consecutive_failures = consecutive_failures.saturating_add(1);
-                    warn!(
-                        exchange = self.name,
-                        failures = consecutive_failures,
-                        error = %e,
-                        "ws connect failed"
-                    );
-                    reconnect_sleep(&cancel, self, &mut consecutive_failures, &mut backoff_ms)
-                        .await?;
+                    rs.on_failure();
+                    if let Some(suppressed) =
+                        log_throttle().allow(&format!("ws connect failed:{}", self.name))
+                    {
+                        warn!(
+                            exchange = self.name,
+                            failures = rs.consecutive_failures,
+                            suppressed,
+                            error = %e,
+                            "ws connect failed"
+                        );
+                    }
+                    reconnect_sleep(&cancel, self, &mut rs, skip_sleep).await?;
                     continue;
                 }
             };
 
+            connects = connects.saturating_add(1);
+            on_event(WsEvent::Connected { attempt: connects }).await?;
+
             let (mut write, mut read) = ws.split();
 
-            // --- SUBSCRIBE limiter
-            if let Some(lims) = ws_limiters {
-                lims.acquire_subscribe(self.name).await?;
+            let mut hb = self.heartbeat_sender();
+
+            // --- SUBSCRIBE (sequential; after_connect: each frame through the limiter). Before
+            // the next step of a message, a step that expects an ack (`ws_subscribe_ack_match`)
+            // waits for it, any other `ws_subscribe_step_delay_ms`
+            let await_acks = self.cfg.ws_subscribe_ack_timeout_seconds > 0;
+            let mut subscribe_err = None;
+            let mut ack_err = None;
+            'subscribe: for steps in &control.subscribe {
+                for (step, subscribe_msg) in steps.iter().enumerate() {
+                    if step > 0 {
+                        let prev = &steps[step - 1];
+                        if await_acks && self.expects_ack(prev) {
+                            if let Err(reason) = self
+                                .await_subscribe_acks(
+                                    &mut read,
+                                    &mut write,
+                                    ws_limiters,
+                                    &[prev],
+                                    &record_streams,
+                                    &mut on_event,
+                                    &mut ring,
+                                    &mut hb,
+                                )
+                                .await?
+                            {
+                                ack_err = Some(reason);
+                                break 'subscribe;
+                            }
+                        } else if !step_delay.is_zero()
+                            && cancel
+                                .run_until_cancelled(tokio::time::sleep(step_delay))
+                                .await
+                                .is_none()
+                        {
+                            info!(exchange = self.name, "ws cancelled (subscribing)");
+                            return Ok(());
+                        }
+                    }
+                    if order == WsLimiterOrder::AfterConnect
+                        && let Some(lims) = ws_limiters
+                    {
+                        let acquire = lims.acquire_subscribe(self.name);
+                        if cancel
+                            .run_until_cancelled(acquire)
+                            .await
+                            .transpose()?
+                            .is_none()
+                        {
+                            info!(exchange = self.name, "ws cancelled (waiting for limiter)");
+                            return Ok(());
+                        }
+                    }
+                    if let Err(e) = send_ws_payload(&mut write, subscribe_msg).await {
+                        subscribe_err = Some(e);
+                        break 'subscribe;
+                    }
+                }
             }
 
-            if let Err(e) = send_ws_payload(&mut write, &subscribe_msg).await {
-                consecutive_failures = consecutive_failures.saturating_add(1);
-                warn!(
-                    exchange = self.name,
-                    failures = consecutive_failures,
-                    error = %e,
-                    "ws subscribe failed"
-                );
-                reconnect_sleep(&cancel, self, &mut consecutive_failures, &mut backoff_ms).await?;
-                continue;
+            // --- SUBSCRIBE ACKS (optional): the last step of every message, awaited together;
+            // no ack in time = not subscribed
+            if await_acks && subscribe_err.is_none() && ack_err.is_none() {
+                let last_steps: Vec<&JsonValue> =
+                    control.subscribe.iter().filter_map(|s| s.last()).collect();
+                if let Err(reason) = self
+                    .await_subscribe_acks(
+                        &mut read,
+                        &mut write,
+                        ws_limiters,
+                        &last_steps,
+                        &record_streams,
+                        &mut on_event,
+                        &mut ring,
+                        &mut hb,
+                    )
+                    .await?
+                {
+                    ack_err = Some(reason);
+                }
+            }
+            if let Some(reason) = ack_err {
+                if let Some(m) = &self.metrics {
+                    m.inc_error();
+                }
+                subscribe_err = Some(AppError::Internal(reason));
             }
 
-            // success path → reset breaker state
-            consecutive_failures = 0;
-            backoff_ms = self.ws_reconnect_backoff_initial_ms;
+            if let Some(e) = subscribe_err {
+                rs.on_failure();
+                if let Some(suppressed) =
+                    log_throttle().allow(&format!("ws subscribe failed:{}", self.name))
+                {
+                    warn!(
+                        exchange = self.name,
+                        failures = rs.consecutive_failures,
+                        suppressed,
+                        error = %e,
+                        "ws subscribe failed"
+                    );
+                }
+                reconnect_sleep(&cancel, self, &mut rs, skip_sleep).await?;
+                continue;
+            }
 
-            let mut hb = self.heartbeat_sender();
+            rs.on_connected();
+            let connected_at = Instant::now();
+            // Listed until this connection is left, whichever way
+            let subscribed = self
+                .subscriptions
+                .as_ref()
+                .map(|s| s.on_subscribed(self.name, &control.stream_labels));
 
             let timeout_secs = self.cfg.ws_connection_timeout_seconds;
             let deadline = if timeout_secs > 0 {
@@ -197,7 +582,15 @@ impl WsClient {
                 None
             };
 
+            // A socket can stay open while the venue sends nothing; reset on every frame
+            let idle = match self.cfg.ws_read_idle_timeout_seconds {
+                0 => None,
+                s => Some(Duration::from_secs(s)),
+            };
+            let mut idle_deadline = idle.map(|d| Instant::now() + d);
+
             let mut close_reason: Option<String> = None;
+            let mut close_info: Option<WsCloseInfo> = None;
 
             loop {
                 tokio::select! {
@@ -225,18 +618,48 @@ impl WsClient {
                         futures_util::future::pending::<()>().await;
                     }
                 } => {
-                    if let Err(e) = maybe_send_ws_heartbeat(&self.cfg, &mut write).await {
+                    let Some(hb) = hb.as_mut() else { continue };
+                    if hb.oldest_unanswered().is_some_and(|age| age >= hb.pong_timeout) {
+                        close_reason = Some("pong timeout".into());
+                        break;
+                    }
+                    let payload = hb.ping_payload();
+                    if let Err(e) = maybe_send_ws_heartbeat(&self.cfg, &mut write, payload).await {
                         close_reason = Some(format!("heartbeat error: {e}"));
                         break;
                     }
                 }
 
-                            msg = read.next() => {
+                            msg = async {
+                                match idle_deadline {
+                                    Some(dl) => tokio::time::timeout_at(dl, read.next()).await.ok(),
+                                    None => Some(read.next().await),
+                                }
+                            } => {
+                                let Some(msg) = msg else {
+                                    close_reason = Some("read idle timeout".into());
+                                    let key = format!("ws read idle:{}", self.name);
+                                    if let Some(suppressed) = log_throttle().allow(&key) {
+                                        warn!(
+                                            exchange = self.name,
+                                            suppressed,
+                                            idle_secs = self.cfg.ws_read_idle_timeout_seconds,
+                                            "ws read idle timeout; reconnecting"
+                                        );
+                                    }
+                                    break;
+                                };
                                 let msg = match msg {
-                                    Some(Ok(m)) => m,
+                                    Some(Ok(m)) => {
+                                        idle_deadline = idle.map(|d| Instant::now() + d);
+                                        m
+                                    }
                                     Some(Err(e)) => {
                                         close_reason = Some(format!("read error: {e}"));
-                                        error!(exchange = self.name, error = %e, "ws read error");
+                                        let key = format!("ws read error:{}", self.name);
+                                        if let Some(suppressed) = log_throttle().allow(&key) {
+                                            error!(exchange = self.name, suppressed, error = %e, "ws read error");
+                                        }
                                         break;
                                     }
                                     None => {
@@ -244,37 +667,38 @@ impl WsClient {
                                         break;
                                     }
                                 };
-
-                                match msg {
-                                    Message::Text(s) => {
-                                        if let Some(m) = &self.metrics { m.inc_in(); }
-                                        on_event(WsEvent::Text(s.to_string())).await?;
-                                        if let Some(m) = &self.metrics { m.inc_processed(); }
-                                    }
-                                    Message::Binary(b) => {
-                                        if let Some(m) = &self.metrics { m.inc_in(); }
-                                        on_event(WsEvent::Binary(b.to_vec())).await?;
-                                        if let Some(m) = &self.metrics { m.inc_processed(); }
-                                    }
-                                    Message::Ping(p) => {
-                                        on_event(WsEvent::Ping(p.clone().to_vec())).await?;
-                                        let _ = write.send(Message::Pong(p)).await;
-                                    }
-                                    Message::Pong(p) => {
-                                        on_event(WsEvent::Pong(p.to_vec())).await?;
-                                    }
-                                    Message::Close(frame) => {
-                                        close_reason = Some(format!("close: {:?}", frame));
+                                let frame = self
+                                    .handle_frame(msg, &record_streams, &mut write, &mut on_event, &mut ring, &mut hb)
+                                    .await?;
+                                match frame {
+                                    Frame::Text(text) => self.deliver_text(&mut on_event, &ring, text).await?,
+                                    Frame::Handled => {}
+                                    Frame::Close(info) => {
+                                        close_reason = Some(info.to_string());
+                                        close_info = Some(info);
                                         let _ = on_event(WsEvent::Close(close_reason.clone())).await;
                                         break;
                                     }
-                                    _ => {}
                                 }
                             }
                         }
             }
+            if let Some(h) = test_hook.as_deref_mut() {
+                h.on_disconnected(close_reason.as_deref(), close_info.as_ref());
+            }
+            let disconnected = on_event(WsEvent::Disconnected {
+                reason: close_reason.clone(),
+                close: close_info.clone(),
+            })
+            .await;
+            drop(subscribed);
+
             // best-effort unsubscribe
-            let _ = send_ws_payload(&mut write, &unsubscribe_msg).await;
+            for unsubscribe_msg in control.unsubscribe.iter().flatten() {
+                let _ = send_ws_payload(&mut write, unsubscribe_msg).await;
+            }
+            // A failing handler ends the run, as on `Connected` (after the unsubscribe)
+            disconnected?;
 
             if cancel.is_cancelled() {
                 info!(exchange = self.name, "ws cancelled; not reconnecting");
@@ -287,21 +711,251 @@ impl WsClient {
                 "ws reconnecting"
             );
 
-            if let Some(h) = test_hook.as_deref_mut() {
-                h.on_disconnected(close_reason.as_deref());
+            let decision = if close_info.as_ref().is_some_and(WsCloseInfo::is_throttle) {
+                rs.on_throttle(self.ws_reconnect_backoff_max_ms);
+                ReconnectDecision::Backoff
+            } else {
+                rs.on_disconnect(connected_at.elapsed())
+            };
+            match decision {
+                ReconnectDecision::Fast => {
+                    info!(
+                        exchange = self.name,
+                        uptime_ms = connected_at.elapsed().as_millis() as u64,
+                        "ws was stable; fast reconnect"
+                    );
+                    fast_reconnect = true;
+                }
+                ReconnectDecision::Backoff => {
+                    reconnect_sleep(&cancel, self, &mut rs, skip_sleep).await?
+                }
             }
+        }
+    }
 
-            consecutive_failures = consecutive_failures.saturating_add(1);
+    /// Read until every subscribe of `subscribe` got its ack (`ws_subscribe_ack_match`),
+    /// within `ws_subscribe_ack_timeout_seconds`. Frames with none of the matched pointers
+    /// (e.g. the auth step of a multi-step subscribe) expect no ack. Frames arriving meanwhile
+    /// go through `handle_frame` as on the live connection; data that is not an ack is
+    /// delivered (`on_event` errors are returned as is).
+    /// With `ws_subscribe_ack_remaining_pointer`, the budget an ack reports is fed into the
+    /// subscribe limiter.
+    /// Inner `Err`: why the subscribe failed.
+    #[allow(clippy::too_many_arguments)]
+    async fn await_subscribe_acks<R, S, F, Fut>(
+        &self,
+        read: &mut R,
+        write: &mut S,
+        ws_limiters: Option<&WsLimiterRegistry>,
+        subscribe: &[&JsonValue],
+        record_streams: &str,
+        on_event: &mut F,
+        ring: &mut FrameRing,
+        hb: &mut Option<HeartbeatDriver>,
+    ) -> AppResult<Result<(), String>>
+    where
+        R: futures_util::Stream<Item = Result<Message, tungstenite::Error>> + Unpin,
+        S: futures_util::Sink<Message> + Unpin,
+        F: FnMut(WsEvent) -> Fut,
+        Fut: std::future::Future<Output = AppResult<()>>,
+    {
+        let timeout = Duration::from_secs(self.cfg.ws_subscribe_ack_timeout_seconds);
+        let deadline = Instant::now() + timeout;
+        let pairs = &self.cfg.ws_subscribe_ack_match;
+        let mut pending: Vec<&JsonValue> = subscribe
+            .iter()
+            .copied()
+            .filter(|sub| self.expects_ack(sub))
+            .collect();
+        let expected = pending.len();
 
-            reconnect_sleep(&cancel, self, &mut consecutive_failures, &mut backoff_ms).await?;
+        while !pending.is_empty() {
+            let msg = match tokio::time::timeout_at(deadline, read.next()).await {
+                Err(_) => {
+                    return Ok(Err(format!(
+                        "{} of {} subscribes not acknowledged within {}s",
+                        pending.len(),
+                        expected,
+                        timeout.as_secs()
+                    )));
+                }
+                Ok(None) => return Ok(Err("stream ended awaiting acks".into())),
+                Ok(Some(Err(e))) => return Ok(Err(format!("read error awaiting acks: {e}"))),
+                Ok(Some(Ok(m))) => m,
+            };
+            // Frames are handled as on the live connection; only data text can be an ack
+            let text = match self
+                .handle_frame(msg, record_streams, write, on_event, ring, hb)
+                .await?
+            {
+                Frame::Text(text) => text,
+                Frame::Handled => continue,
+                Frame::Close(info) => return Ok(Err(format!("closed awaiting acks ({info})"))),
+            };
+
+            let ack = serde_json::from_str::<JsonValue>(&text).ok().and_then(|v| {
+                let i = pending
+                    .iter()
+                    .position(|sub| is_subscribe_ack(&v, sub, pairs))?;
+                Some((i, v))
+            });
+            let Some((i, v)) = ack else {
+                // Not an ack: data of a stream that is already subscribed
+                self.deliver_text(on_event, ring, text).await?;
+                continue;
+            };
+            if let Some(m) = &self.metrics {
+                m.inc_processed();
+            }
+            if let Some(err) = self
+                .cfg
+                .ws_subscribe_ack_error_pointer
+                .as_deref()
+                .and_then(|p| v.pointer(p))
+            {
+                return Ok(Err(format!("subscribe rejected: {err}")));
+            }
+            if let Some(lims) = ws_limiters
+                && let Some(remaining) = self
+                    .cfg
+                    .ws_subscribe_ack_remaining_pointer
+                    .as_deref()
+                    .and_then(|p| v.pointer(p))
+                    .and_then(ack_remaining)
+            {
+                let left = lims
+                    .observe_subscribe_remaining(self.name, remaining)
+                    .await?;
+                debug!(
+                    exchange = self.name,
+                    venue_remaining = remaining,
+                    limiter_remaining = left,
+                    "venue subscribe budget applied to limiter"
+                );
+            }
+            pending.swap_remove(i);
+        }
+        Ok(Ok(()))
+    }
+
+    /// True if `sub` has one of the subscribe pointers of `ws_subscribe_ack_match`.
+    fn expects_ack(&self, sub: &JsonValue) -> bool {
+        self.cfg
+            .ws_subscribe_ack_match
+            .values()
+            .any(|p| sub.pointer(p).is_some())
+    }
+
+    /// Handle one received frame, the same way while awaiting acks and on the live
+    /// connection: recorded (`ws_record_frames_path`), counted in, kept in the frame ring;
+    /// binary data decompressed (`ws_compression`; a bad frame is dropped) or delivered,
+    /// pings answered, pongs (protocol, or text with `ws_heartbeat_expect_pong`) fed to `hb`
+    /// and delivered. Data text is returned for the caller to deliver (`deliver_text`) or
+    /// consume; `on_event` errors are returned as is.
+    async fn handle_frame<S, F, Fut>(
+        &self,
+        msg: Message,
+        record_streams: &str,
+        write: &mut S,
+        on_event: &mut F,
+        ring: &mut FrameRing,
+        hb: &mut Option<HeartbeatDriver>,
+    ) -> AppResult<Frame>
+    where
+        S: futures_util::Sink<Message> + Unpin,
+        F: FnMut(WsEvent) -> Fut,
+        Fut: std::future::Future<Output = AppResult<()>>,
+    {
+        self.record_frame(record_streams, &msg);
+        match msg {
+            Message::Text(s)
+                if hb.as_ref().is_some_and(|h| h.expect_text_pong) && is_text_pong(&s) =>
+            {
+                if let Some(rtt) = hb.as_mut().and_then(|h| h.on_text_pong())
+                    && let Some(m) = &self.metrics
+                {
+                    m.observe_ws_ping_rtt(self.name, rtt.as_secs_f64());
+                }
+                on_event(WsEvent::Pong(s.as_bytes().to_vec())).await?;
+            }
+            Message::Text(s) => {
+                if let Some(m) = &self.metrics {
+                    m.inc_in();
+                }
+                ring.push_text(&s);
+                return Ok(Frame::Text(s.to_string()));
+            }
+            Message::Binary(b) => {
+                if let Some(m) = &self.metrics {
+                    m.inc_in();
+                }
+                let Some(kind) = self.cfg.ws_compression.as_deref() else {
+                    ring.push_binary(&b);
+                    deliver_frame(on_event, ring, WsEvent::Binary(b.to_vec())).await?;
+                    if let Some(m) = &self.metrics {
+                        m.inc_processed();
+                    }
+                    return Ok(Frame::Handled);
+                };
+                match decompress_frame(kind, &b) {
+                    Ok(text) => return Ok(Frame::Text(ring.push_decoded(text))),
+                    Err(e) => {
+                        // One bad frame is dropped, the connection stays up
+                        if let Some(m) = &self.metrics {
+                            m.inc_error();
+                        }
+                        let key = format!("ws decompress:{}", self.name);
+                        if let Some(suppressed) = log_throttle().allow(&key) {
+                            warn!(exchange = self.name, suppressed, compression = kind, error = %e, "ws frame decompression failed; dropped");
+                        }
+                    }
+                }
+            }
+            Message::Ping(p) => {
+                on_event(WsEvent::Ping(p.to_vec())).await?;
+                let _ = write.send(Message::Pong(p)).await;
+            }
+            Message::Pong(p) => {
+                if let Some(rtt) = hb.as_mut().and_then(|h| h.on_pong(&p))
+                    && let Some(m) = &self.metrics
+                {
+                    m.observe_ws_ping_rtt(self.name, rtt.as_secs_f64());
+                }
+                on_event(WsEvent::Pong(p.to_vec())).await?;
+            }
+            Message::Close(frame) => {
+                return Ok(Frame::Close(WsCloseInfo::from_frame(frame.as_ref())));
+            }
+            _ => {}
         }
+        Ok(Frame::Handled)
+    }
+
+    /// Deliver a data text frame returned by `handle_frame`.
+    async fn deliver_text<F, Fut>(
+        &self,
+        on_event: &mut F,
+        ring: &FrameRing,
+        text: String,
+    ) -> AppResult<()>
+    where
+        F: FnMut(WsEvent) -> Fut,
+        Fut: std::future::Future<Output = AppResult<()>>,
+    {
+        deliver_frame(on_event, ring, WsEvent::Text(text)).await?;
+        if let Some(m) = &self.metrics {
+            m.inc_processed();
+        }
+        Ok(())
     }
 
     fn heartbeat_sender(&self) -> Option<HeartbeatDriver> {
         let hb_type = self.cfg.ws_heartbeat_type.as_ref()?.to_lowercase();
-        if hb_type != "ping" {
-            return None;
-        }
+        let json = match hb_type.as_str() {
+            "ping" => false,
+            "json" => true,
+            _ => return None,
+        };
 
         let timeout = self.cfg.ws_heartbeat_timeout_seconds.unwrap_or(30);
         let period = std::cmp::max(1, timeout / 2);
@@ -309,16 +963,105 @@ impl WsClient {
         Some(HeartbeatDriver {
             interval: interval(Duration::from_secs(period)),
             frame: self.cfg.ws_heartbeat_frame.clone(),
+            // json: unanswered for two intervals
+            pong_timeout: Duration::from_secs(if json { 2 * period } else { timeout }),
+            expect_text_pong: json && self.cfg.ws_heartbeat_expect_pong,
+            next_seq: 0,
+            pending: VecDeque::new(),
         })
     }
 }
 
-// helper: breaker + backoff + jitter, cancellable sleep
+/// A received frame after `WsClient::handle_frame`.
+enum Frame {
+    /// Data text (decompressed if need be), not yet delivered.
+    Text(String),
+    /// Dealt with: delivered, answered, or dropped.
+    Handled,
+    /// The venue closed the connection.
+    Close(WsCloseInfo),
+}
+
+/// What to do after a live connection drops.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReconnectDecision {
+    /// Connection was stable: reconnect immediately, skip backoff + reconnect limiter.
+    Fast,
+    /// Quick disconnect (or fast path disabled): breaker + backoff + limiter.
+    Backoff,
+}
+
+/// Reconnect backoff state machine.
+///
+/// - Connect/subscribe failures always count towards the breaker and ramp the backoff.
+/// - With `stable_threshold == 0` a successful subscribe resets the state (legacy behavior).
+/// - With `stable_threshold > 0` the state is only reset once a connection has stayed up for
+///   at least `stable_threshold`; such a disconnect is a one-off blip and takes the fast path.
+///   Repeated quick disconnects (flapping) keep ramping into full backoff / the breaker.
+#[derive(Debug, Clone)]
+pub struct ReconnectState {
+    pub consecutive_failures: u32,
+    pub backoff_ms: u64,
+    initial_backoff_ms: u64,
+    stable_threshold: Duration,
+}
+
+impl ReconnectState {
+    pub fn new(initial_backoff_ms: u64, stable_threshold: Duration) -> Self {
+        Self {
+            consecutive_failures: 0,
+            backoff_ms: initial_backoff_ms,
+            initial_backoff_ms,
+            stable_threshold,
+        }
+    }
+
+    #[inline]
+    pub fn fast_path_enabled(&self) -> bool {
+        !self.stable_threshold.is_zero()
+    }
+
+    pub fn reset(&mut self) {
+        self.consecutive_failures = 0;
+        self.backoff_ms = self.initial_backoff_ms;
+    }
+
+    /// Connect or subscribe attempt failed.
+    pub fn on_failure(&mut self) {
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+    }
+
+    /// The venue closed the connection as rate-limited: a failure whose backoff is at least
+    /// `floor_ms`, however stable the connection was.
+    pub fn on_throttle(&mut self, floor_ms: u64) {
+        self.on_failure();
+        self.backoff_ms = self.backoff_ms.max(floor_ms);
+    }
+
+    /// Connected and subscribed.
+    pub fn on_connected(&mut self) {
+        if !self.fast_path_enabled() {
+            self.reset();
+        }
+    }
+
+    /// A live connection dropped after `uptime`.
+    pub fn on_disconnect(&mut self, uptime: Duration) -> ReconnectDecision {
+        if self.fast_path_enabled() && uptime >= self.stable_threshold {
+            self.reset();
+            return ReconnectDecision::Fast;
+        }
+        self.on_failure();
+        ReconnectDecision::Backoff
+    }
+}
+
+// helper: breaker + backoff + jitter, cancellable sleep (observed in ws_reconnect_wait_seconds)
 async fn reconnect_sleep(
     cancel: &CancellationToken,
     client: &WsClient,
-    consecutive_failures: &mut u32,
-    backoff_ms: &mut u64,
+    rs: &mut ReconnectState,
+    skip_sleep: bool,
 ) -> AppResult<()> {
     use rand::Rng;
     use tokio::time::sleep;
@@ -330,26 +1073,28 @@ async fn reconnect_sleep(
     // --------------------------------------------------
     // Circuit breaker
     // --------------------------------------------------
-    if *consecutive_failures >= client.ws_reconnect_trip_after_failures {
+    if rs.consecutive_failures >= client.ws_reconnect_trip_after_failures {
         let cooldown = Duration::from_secs(client.ws_reconnect_cooldown_seconds);
 
         warn!(
             exchange = client.name,
-            failures = *consecutive_failures,
+            failures = rs.consecutive_failures,
             cooldown_secs = client.ws_reconnect_cooldown_seconds,
             "ws breaker tripped; cooling down"
         );
 
-        tokio::select! {
-            _ = cancel.cancelled() => {
-                return Ok(());
-            }
-            _ = sleep(cooldown) => {
-                *consecutive_failures = 0;
-                *backoff_ms = client.ws_reconnect_backoff_initial_ms;
-                return Ok(());
+        if !skip_sleep {
+            let t0 = Instant::now();
+            tokio::select! {
+                _ = cancel.cancelled() => {
+                    return Ok(());
+                }
+                _ = sleep(cooldown) => {}
             }
+            observe_reconnect_wait(client, t0.elapsed());
         }
+        rs.reset();
+        return Ok(());
     }
 
     // --------------------------------------------------
@@ -360,25 +1105,120 @@ async fn reconnect_sleep(
         let jitter = 0.2_f64;
         let mut rng = rand::rng(); // ✅ rand 0.9 API
         let j = rng.random_range(-jitter..=jitter);
-        ((*backoff_ms as f64) * (1.0 + j)).max(0.0) as u64
+        ((rs.backoff_ms as f64) * (1.0 + j)).max(0.0) as u64
     };
 
-    tokio::select! {
-        _ = cancel.cancelled() => {
-            return Ok(());
+    if !skip_sleep {
+        let t0 = Instant::now();
+        tokio::select! {
+            _ = cancel.cancelled() => {
+                return Ok(());
+            }
+            _ = sleep(Duration::from_millis(sleep_ms)) => {}
         }
-        _ = sleep(Duration::from_millis(sleep_ms)) => {}
+        observe_reconnect_wait(client, t0.elapsed());
     }
 
     // --------------------------------------------------
     // Increase backoff (capped)
     // --------------------------------------------------
-    let next = (*backoff_ms).saturating_mul(2);
-    *backoff_ms = std::cmp::min(next, client.ws_reconnect_backoff_max_ms);
+    let next = rs.backoff_ms.saturating_mul(2);
+    rs.backoff_ms = std::cmp::min(next, client.ws_reconnect_backoff_max_ms);
 
     Ok(())
 }
 
+fn observe_reconnect_wait(client: &WsClient, waited: Duration) {
+    if let Some(m) = &client.metrics {
+        m.observe_ws_reconnect_wait(waited.as_secs_f64());
+    }
+}
+
+/// Hand a data frame to `on_event`; an error or panic there dumps the frame ring first.
+async fn deliver_frame<F, Fut>(on_event: &mut F, ring: &FrameRing, ev: WsEvent) -> AppResult<()>
+where
+    F: FnMut(WsEvent) -> Fut,
+    Fut: std::future::Future<Output = AppResult<()>>,
+{
+    match AssertUnwindSafe(on_event(ev)).catch_unwind().await {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(e)) => {
+            ring.dump(&e.to_string());
+            Err(e)
+        }
+        Err(panic) => {
+            ring.dump(&panic_message(panic.as_ref()));
+            std::panic::resume_unwind(panic)
+        }
+    }
+}
+
+/// True if `ack` acknowledges the subscribe message `sub`: every `ack pointer -> sub pointer`
+/// pair of `pairs` resolves to equal values, and at least one of them exists in `sub`.
+pub fn is_subscribe_ack(
+    ack: &JsonValue,
+    sub: &JsonValue,
+    pairs: &std::collections::BTreeMap<String, String>,
+) -> bool {
+    let mut any = false;
+    for (ack_ptr, sub_ptr) in pairs {
+        let expected = sub.pointer(sub_ptr);
+        if ack.pointer(ack_ptr) != expected {
+            return false;
+        }
+        any |= expected.is_some();
+    }
+    any
+}
+
+/// Remaining budget in an ack field: a non-negative number or numeric string (saturating).
+pub fn ack_remaining(v: &JsonValue) -> Option<u32> {
+    let n = match v {
+        JsonValue::Number(n) => n.as_f64()?,
+        JsonValue::String(s) => s.trim().parse::<f64>().ok()?,
+        _ => return None,
+    };
+    (n.is_finite() && n >= 0.0).then(|| n.min(u32::MAX as f64) as u32)
+}
+
+/// Decode a compressed binary frame (`ws_compression`) to UTF-8 text. The output is capped
+/// at tungstenite's `max_message_size` (the most an uncompressed frame may carry): a frame
+/// inflating past it is an error, so the caller drops it.
+pub fn decompress_frame(kind: &str, bytes: &[u8]) -> std::io::Result<String> {
+    let limit = WebSocketConfig::default()
+        .max_message_size
+        .unwrap_or(usize::MAX);
+    decompress_frame_within(kind, bytes, limit)
+}
+
+/// `decompress_frame` with an explicit output cap in bytes.
+pub fn decompress_frame_within(kind: &str, bytes: &[u8], limit: usize) -> std::io::Result<String> {
+    // One byte past the cap tells a frame at the limit from one above it
+    let cap = (limit as u64).saturating_add(1);
+    let mut out = String::new();
+    match kind {
+        "gzip" => flate2::read::GzDecoder::new(bytes)
+            .take(cap)
+            .read_to_string(&mut out)?,
+        "deflate" => flate2::read::DeflateDecoder::new(bytes)
+            .take(cap)
+            .read_to_string(&mut out)?,
+        other => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                format!("unknown ws_compression {other:?}"),
+            ));
+        }
+    };
+    if out.len() > limit {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("decompressed ws frame exceeds {limit} bytes"),
+        ));
+    }
+    Ok(out)
+}
+
 /// Optional test hook to make reconnect loops deterministic in tests.
 #[derive(Debug, Default)]
 pub struct WsTestHook {
@@ -387,6 +1227,10 @@ pub struct WsTestHook {
     pub reconnect_attempts: u32,
     /// Optional callback-like storage for assertions.
     pub disconnects: Vec<Option<String>>,
+    /// Venue close frame of each disconnect, in the same order (None = not closed by the venue).
+    pub closes: Vec<Option<WsCloseInfo>>,
+    /// Skip the backoff / breaker sleeps between attempts (the backoff still ramps).
+    pub skip_reconnect_sleep: bool,
 }
 
 impl WsTestHook {
@@ -400,15 +1244,29 @@ impl WsTestHook {
         }
     }
 
-    /// Called after a disconnect (inner loop ended) with the close reason (if any).
-    pub fn on_disconnected(&mut self, reason: Option<&str>) {
+    /// Called after a disconnect (inner loop ended, cancellation included) with the close
+    /// reason (if any), right before the `WsEvent::Disconnected` carrying the same reason.
+    pub fn on_disconnected(&mut self, reason: Option<&str>, close: Option<&WsCloseInfo>) {
         self.disconnects.push(reason.map(|s| s.to_string()));
+        self.closes.push(close.cloned());
     }
 }
 
+/// Pings kept waiting for their pong; older ones are forgotten.
+const MAX_PENDING_PINGS: usize = 16;
+
+/// Client-driven heartbeat. Protocol pings (no `ws_heartbeat_frame`) carry a sequence number
+/// so the matching pong gives the round trip (`ws_ping_rtt_seconds`); one left unanswered for
+/// `ws_heartbeat_timeout_seconds` closes the connection. Venue-level text pings are not timed,
+/// except `json` heartbeats with `ws_heartbeat_expect_pong`: a text pong answers every
+/// heartbeat sent so far, none for two intervals closes the connection.
 struct HeartbeatDriver {
     interval: tokio::time::Interval,
     frame: Option<StringOrTable>,
+    pong_timeout: Duration,
+    expect_text_pong: bool,
+    next_seq: u64,
+    pending: VecDeque<(u64, Instant)>,
 }
 
 impl HeartbeatDriver {
@@ -416,6 +1274,89 @@ impl HeartbeatDriver {
         self.interval.tick().await;
         Some(())
     }
+
+    /// Payload of the next protocol ping, recorded as pending (empty for text heartbeats,
+    /// which are recorded only when a text pong is expected).
+    fn ping_payload(&mut self) -> Vec<u8> {
+        if self.frame.is_some() && !self.expect_text_pong {
+            return Vec::new();
+        }
+        self.next_seq = self.next_seq.wrapping_add(1);
+        if self.pending.len() == MAX_PENDING_PINGS {
+            self.pending.pop_front();
+        }
+        self.pending.push_back((self.next_seq, Instant::now()));
+        if self.frame.is_some() {
+            return Vec::new();
+        }
+        self.next_seq.to_be_bytes().to_vec()
+    }
+
+    /// Round trip of the latest text heartbeat; a text pong answers all of them.
+    fn on_text_pong(&mut self) -> Option<Duration> {
+        let (_, sent_at) = self.pending.drain(..).next_back()?;
+        Some(sent_at.elapsed())
+    }
+
+    /// Round trip of the ping `payload` answers; earlier unanswered pings are dropped with it.
+    fn on_pong(&mut self, payload: &[u8]) -> Option<Duration> {
+        let seq = u64::from_be_bytes(payload.try_into().ok()?);
+        let idx = self.pending.iter().position(|(s, _)| *s == seq)?;
+        let (_, sent_at) = self.pending.drain(..=idx).next_back()?;
+        Some(sent_at.elapsed())
+    }
+
+    fn oldest_unanswered(&self) -> Option<Duration> {
+        self.pending.front().map(|(_, sent_at)| sent_at.elapsed())
+    }
+}
+
+/// Resolved control messages of one connection (each one or more steps), with the venue
+/// names of its streams.
+struct ControlMsgs {
+    stream_labels: Vec<String>,
+    subscribe: Vec<Vec<JsonValue>>,
+    unsubscribe: Vec<Vec<JsonValue>>,
+}
+
+impl ControlMsgs {
+    /// Messages of the rendered `batches` of the seeded `ctxs`.
+    fn new(ctxs: &[Ctx], batches: Vec<WsControlSpec>) -> Self {
+        let (subscribe, unsubscribe) = batches
+            .into_iter()
+            .map(|c| (c.subscribe, c.unsubscribe))
+            .unzip();
+        Self {
+            stream_labels: ctxs.iter().map(subscription_label).collect(),
+            subscribe,
+            unsubscribe,
+        }
+    }
+}
+
+/// Ctx of each stream, seeded from its stream templates (`stream_id` defaults to "1").
+fn seed_stream_ctxs(streams: Vec<(&WsStream, Ctx)>) -> AppResult<Vec<Ctx>> {
+    let mut ctxs = Vec::with_capacity(streams.len());
+    for (stream, mut ctx) in streams {
+        seed_ws_stream_ctx(stream, &mut ctx)?;
+        ctx.entry("stream_id".to_string())
+            .or_insert_with(|| "1".to_string());
+        ctxs.push(ctx);
+    }
+    Ok(ctxs)
+}
+
+/// Venue stream name of a seeded ctx: `stream_title` (Binance), else
+/// `subscription_type:coin` (Hyperliquid).
+fn subscription_label(ctx: &Ctx) -> String {
+    if let Some(title) = ctx.get("stream_title") {
+        return title.clone();
+    }
+    ["subscription_type", "coin"]
+        .iter()
+        .filter_map(|k| ctx.get(*k).map(String::as_str))
+        .collect::<Vec<_>>()
+        .join(":")
 }
 
 async fn send_ws_payload<S>(write: &mut S, payload: &serde_json::Value) -> AppResult<()>
@@ -430,7 +1371,30 @@ where
     Ok(())
 }
 
-async fn maybe_send_ws_heartbeat<S>(cfg: &ExchangeConfig, write: &mut S) -> AppResult<()>
+/// Venue-level pong: `pong`, or a JSON object with a top-level `"pong"` value
+/// (`{"op":"pong"}`, `{"channel":"pong"}`).
+pub fn is_text_pong(text: &str) -> bool {
+    let text = text.trim();
+    if text.eq_ignore_ascii_case("pong") {
+        return true;
+    }
+    if text.len() > 256 {
+        return false;
+    }
+    match serde_json::from_str::<JsonValue>(text) {
+        Ok(JsonValue::String(s)) => s.eq_ignore_ascii_case("pong"),
+        Ok(JsonValue::Object(o)) => o
+            .values()
+            .any(|v| v.as_str().is_some_and(|s| s.eq_ignore_ascii_case("pong"))),
+        _ => false,
+    }
+}
+
+async fn maybe_send_ws_heartbeat<S>(
+    cfg: &ExchangeConfig,
+    write: &mut S,
+    ping_payload: Vec<u8>,
+) -> AppResult<()>
 where
     S: futures_util::Sink<Message> + Unpin,
     S::Error: std::fmt::Display,
@@ -440,7 +1404,7 @@ where
         None => return Ok(()),
     };
 
-    if hb_type != "ping" {
+    if hb_type != "ping" && hb_type != "json" {
         return Ok(());
     }
 
@@ -462,7 +1426,7 @@ where
         }
         None => {
             write
-                .send(Message::Ping(Vec::new().into()))
+                .send(Message::Ping(ping_payload.into()))
                 .await
                 .map_err(|e| AppError::Internal(format!("ws heartbeat send ping error: {e}")))?;
         }
@@ -4,19 +4,23 @@
 
 use crate::app::config::load_app_config;
 use crate::error::{AppError, AppResult};
-use crate::ingest::config::ExchangeConfigs;
+use crate::ingest::config::{ExchangeConfig, ExchangeConfigs};
 use crate::ingest::spec::Ctx;
 use crate::ingest::ws::limiter_registry::WsLimiterRegistry;
-use crate::ingest::ws::ws_client::{WsClient, WsEvent, WsTestHook};
+use crate::ingest::ws::ws_client::{WsClient, WsCloseInfo, WsEvent, WsTestHook};
 
 use futures_util::{SinkExt, StreamExt};
+use std::net::SocketAddr;
 use std::sync::{
     Arc,
     atomic::{AtomicUsize, Ordering},
 };
 use std::time::Duration;
-use tokio::net::TcpListener;
-use tokio_tungstenite::{accept_async, tungstenite::protocol::Message};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::{WebSocketStream, accept_async, tungstenite::protocol::Message};
+
+/// What the `on_event` handlers below return.
+type EventFuture = std::pin::Pin<Box<dyn std::future::Future<Output = AppResult<()>> + Send>>;
 
 fn mk_ctx_btc() -> Ctx {
     let mut ctx = Ctx::new();
@@ -25,6 +29,15 @@ fn mk_ctx_btc() -> Ctx {
     ctx
 }
 
+fn gzip(s: &str) -> Vec<u8> {
+    use flate2::{Compression, write::GzEncoder};
+    use std::io::Write;
+
+    let mut enc = GzEncoder::new(Vec::new(), Compression::default());
+    enc.write_all(s.as_bytes()).unwrap();
+    enc.finish().unwrap()
+}
+
 fn is_test_done(err: &AppError) -> bool {
     match err {
         AppError::Internal(s) => s == "__TEST_DONE__",
@@ -34,9 +47,7 @@ fn is_test_done(err: &AppError) -> bool {
 
 async fn stop_after_n_text_messages(
     n: usize,
-) -> impl FnMut(WsEvent) -> std::pin::Pin<Box<dyn std::future::Future<Output = AppResult<()>> + Send>>
-+ Send
-+ 'static {
+) -> impl FnMut(WsEvent) -> EventFuture + Send + 'static {
     let seen = Arc::new(AtomicUsize::new(0));
 
     move |ev: WsEvent| {
@@ -54,6 +65,42 @@ async fn stop_after_n_text_messages(
     }
 }
 
+/// The shipped Binance config pointed at a local server (`ws://{addr}`): no connection
+/// timeout, no client heartbeat.
+fn binance_test_cfg(addr: impl std::fmt::Display) -> AppResult<ExchangeConfig> {
+    let appcfg = load_app_config(false, 0)?;
+    let ex = ExchangeConfigs::new(&appcfg, false, 0)?;
+    let mut cfg = ex.binance_linear.expect("binance_linear config must exist");
+    cfg.ws_base_url = format!("ws://{addr}");
+    cfg.ws_connection_timeout_seconds = 0;
+    cfg.ws_heartbeat_type = None;
+    Ok(cfg)
+}
+
+/// Local WS server on a free port: every accepted connection is upgraded and handed to
+/// `serve` in its own task.
+async fn spawn_ws_server<F, Fut>(serve: F) -> AppResult<SocketAddr>
+where
+    F: Fn(WebSocketStream<TcpStream>) -> Fut + Send + Sync + 'static,
+    Fut: std::future::Future<Output = ()> + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .map_err(|e| AppError::Internal(format!("ws test server bind error: {e}")))?;
+    let addr = listener.local_addr().unwrap();
+    let serve = Arc::new(serve);
+    tokio::spawn(async move {
+        while let Ok((tcp, _peer)) = listener.accept().await {
+            let serve = Arc::clone(&serve);
+            tokio::spawn(async move {
+                let ws = accept_async(tcp).await.expect("accept_async");
+                serve(ws).await;
+            });
+        }
+    });
+    Ok(addr)
+}
+
 #[tokio::test]
 async fn test_live_ws_binance_trades_no_limiter_10_messages() -> AppResult<()> {
     crate::telemetry::init_for_tests();
@@ -236,62 +283,6 @@ async fn test_live_ws_hyperliquid_trades_with_limiters_10_messages() -> AppResul
 
 // --- Local WS server tests (deterministic ping/pong + reconnect)
 
-async fn spawn_local_ws_server_ping_close(
-    addr: &str,
-    accept_count: Arc<AtomicUsize>,
-    pong_count: Arc<AtomicUsize>,
-) -> AppResult<()> {
-    let listener = TcpListener::bind(addr)
-        .await
-        .map_err(|e| AppError::Internal(format!("ws test server bind error: {e}")))?;
-
-    loop {
-        let (tcp, _peer) = listener
-            .accept()
-            .await
-            .map_err(|e| AppError::Internal(format!("ws test server accept error: {e}")))?;
-
-        let accept_count = accept_count.clone();
-        let pong_count = pong_count.clone();
-
-        tokio::spawn(async move {
-            let ws = accept_async(tcp).await.expect("accept_async");
-            let (mut write, mut read) = ws.split();
-
-            accept_count.fetch_add(1, Ordering::SeqCst);
-
-            // Client will send a subscribe payload immediately; read and ignore it (or timeout).
-            let _ = tokio::time::timeout(Duration::from_secs(2), read.next()).await;
-
-            // Send Ping and expect Pong.
-            let payload = b"ping_payload".to_vec();
-            write.send(Message::Ping(payload.clone().into())).await.ok();
-
-            // Wait for pong (best effort).
-            if let Ok(Some(Ok(Message::Pong(p)))) =
-                tokio::time::timeout(Duration::from_secs(2), read.next()).await
-            {
-                if p == payload {
-                    pong_count.fetch_add(1, Ordering::SeqCst);
-                }
-            }
-
-            // Send a couple messages so the client can forward events.
-            write
-                .send(Message::Text(r#"{"type":"test","n":1}"#.into()))
-                .await
-                .ok();
-            write
-                .send(Message::Text(r#"{"type":"test","n":2}"#.into()))
-                .await
-                .ok();
-
-            // Close to force reconnect.
-            write.send(Message::Close(None)).await.ok();
-        });
-    }
-}
-
 #[tokio::test]
 async fn test_local_ws_reconnects_and_pongs_no_limiter_binance() -> AppResult<()> {
     crate::telemetry::init_for_tests();
@@ -299,31 +290,15 @@ async fn test_local_ws_reconnects_and_pongs_no_limiter_binance() -> AppResult<()
     let accept_count = Arc::new(AtomicUsize::new(0));
     let pong_count = Arc::new(AtomicUsize::new(0));
 
-    // Bind once, keep listener alive, and pass it to the server task (Binance-style).
-    let listener = TcpListener::bind("127.0.0.1:0")
-        .await
-        .map_err(|e| AppError::Internal(format!("ws test server bind error: {e}")))?;
-    let local_addr = listener.local_addr().unwrap();
-
     let server_accepts = accept_count.clone();
     let server_pongs = pong_count.clone();
-    tokio::spawn(async move {
-        let _ =
-            spawn_local_ws_server_ping_close_listener(listener, server_accepts, server_pongs).await;
-    });
-
-    // Load real Binance config and override ws_base_url to local ws://
-    let appcfg = load_app_config(false, 0)?;
-    let ex = ExchangeConfigs::new(&appcfg, false, 0)?;
-    let mut cfg = ex
-        .binance_linear
-        .as_ref()
-        .expect("binance_linear config must exist")
-        .clone();
+    let local_addr = spawn_ws_server(move |ws| {
+        serve_ping_close(ws, Arc::clone(&server_accepts), Arc::clone(&server_pongs))
+    })
+    .await?;
 
-    cfg.ws_base_url = format!("ws://{}", local_addr);
-    cfg.ws_connection_timeout_seconds = 0;
-    cfg.ws_heartbeat_type = None; // Binance-style: server Ping -> client Pong
+    // Real Binance config against the local server; Binance-style: server Ping -> client Pong
+    let cfg = binance_test_cfg(local_addr)?;
 
     // Binance stream config: uses stream_title and stream_id
     let stream = cfg
@@ -395,32 +370,10 @@ use tokio_util::sync::CancellationToken;
 async fn test_local_ws_cancel_stops_stuck_read_loop() -> AppResult<()> {
     crate::telemetry::init_for_tests();
 
-    // Bind local WS server
-    let listener = TcpListener::bind("127.0.0.1:0")
-        .await
-        .map_err(|e| AppError::Internal(format!("ws test server bind error: {e}")))?;
-    let local_addr = listener.local_addr().unwrap();
-
-    tokio::spawn(async move {
-        let _ = spawn_local_ws_server_silent_listener(listener).await;
-    });
-
-    // Load real Binance config and override ws_base_url to local ws://
-    let appcfg = load_app_config(false, 0)?;
-    let ex = ExchangeConfigs::new(&appcfg, false, 0)?;
-    let mut cfg = ex
-        .binance_linear
-        .as_ref()
-        .expect("binance_linear config must exist")
-        .clone();
+    let local_addr = spawn_ws_server(serve_silent).await?;
 
-    cfg.ws_base_url = format!("ws://{}", local_addr);
-
-    // Important: disable connection timeout so the only exit is cancellation
-    cfg.ws_connection_timeout_seconds = 0;
-
-    // Disable client-driven heartbeat (server is silent; we only want cancel to stop it)
-    cfg.ws_heartbeat_type = None;
+    // No connection timeout and no client-driven heartbeat: the only exit is cancellation
+    let cfg = binance_test_cfg(local_addr)?;
 
     let stream = cfg
         .ws
@@ -473,69 +426,1765 @@ async fn test_local_ws_cancel_stops_stuck_read_loop() -> AppResult<()> {
     }
 }
 
-async fn spawn_local_ws_server_ping_close_listener(
-    listener: TcpListener,
+async fn serve_ping_close(
+    ws: WebSocketStream<TcpStream>,
     accept_count: Arc<AtomicUsize>,
     pong_count: Arc<AtomicUsize>,
-) -> AppResult<()> {
-    loop {
-        let (tcp, _peer) = listener
-            .accept()
+) {
+    let (mut write, mut read) = ws.split();
+
+    accept_count.fetch_add(1, Ordering::SeqCst);
+
+    // Read the client's subscribe message (Binance-style JSON)
+    let _ = tokio::time::timeout(Duration::from_secs(2), read.next()).await;
+
+    // Ping + expect Pong payload match
+    let payload = b"binance_ping".to_vec();
+    write.send(Message::Ping(payload.clone().into())).await.ok();
+
+    if let Ok(Some(Ok(Message::Pong(p)))) =
+        tokio::time::timeout(Duration::from_secs(2), read.next()).await
+    {
+        if p == payload {
+            pong_count.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    // Send Binance-style subscribe ACK
+    let _ = write
+        .send(Message::Text(r#"{"result":null,"id":"1"}"#.into()))
+        .await;
+
+    // Close to force reconnect
+    let _ = write.send(Message::Close(None)).await;
+}
+
+// --- Local WS server: reads subscribe, then stays silent forever.
+async fn serve_silent(ws: WebSocketStream<TcpStream>) {
+    let (_write, mut read) = ws.split();
+
+    // Read the client's subscribe message (best-effort, so test doesn't hang here)
+    let _ = tokio::time::timeout(Duration::from_secs(2), read.next()).await;
+
+    // Now: be completely silent forever (or long enough)
+    futures_util::future::pending::<()>().await;
+}
+
+#[test]
+fn test_reconnect_fast_path_stable_blip_vs_flapping() {
+    use crate::ingest::ws::ws_client::{ReconnectDecision, ReconnectState};
+
+    let stable = Duration::from_secs(60);
+
+    // Stable connection that blips once: fast reconnect, state stays clean.
+    let mut rs = ReconnectState::new(500, stable);
+    rs.on_connected();
+    assert_eq!(
+        rs.on_disconnect(Duration::from_secs(3600)),
+        ReconnectDecision::Fast
+    );
+    assert_eq!(rs.consecutive_failures, 0);
+    assert_eq!(rs.backoff_ms, 500);
+
+    // Flapping: every connection dies quickly -> full path, failures keep counting
+    // (a successful subscribe no longer resets the breaker).
+    let mut rs = ReconnectState::new(500, stable);
+    for i in 1..=5u32 {
+        rs.on_connected();
+        assert_eq!(
+            rs.on_disconnect(Duration::from_millis(200)),
+            ReconnectDecision::Backoff
+        );
+        assert_eq!(rs.consecutive_failures, i);
+    }
+
+    // Once the connection proves stable again, the next blip is a one-off.
+    rs.on_connected();
+    assert_eq!(
+        rs.on_disconnect(Duration::from_secs(61)),
+        ReconnectDecision::Fast
+    );
+    assert_eq!(rs.consecutive_failures, 0);
+    assert_eq!(rs.backoff_ms, 500);
+
+    // Threshold 0 disables the fast path (legacy: reset on subscribe, always back off).
+    let mut rs = ReconnectState::new(500, Duration::ZERO);
+    rs.on_failure();
+    rs.on_connected();
+    assert_eq!(rs.consecutive_failures, 0);
+    assert_eq!(
+        rs.on_disconnect(Duration::from_secs(3600)),
+        ReconnectDecision::Backoff
+    );
+
+    // A throttle close backs off from the floor, or from further up if already there.
+    let mut rs = ReconnectState::new(500, stable);
+    rs.on_throttle(30_000);
+    assert_eq!((rs.consecutive_failures, rs.backoff_ms), (1, 30_000));
+    rs.backoff_ms = 60_000;
+    rs.on_throttle(30_000);
+    assert_eq!(rs.backoff_ms, 60_000);
+}
+
+#[tokio::test]
+async fn test_local_ws_handshake_sends_configured_user_agent() -> AppResult<()> {
+    use tokio_tungstenite::accept_hdr_async;
+    use tokio_tungstenite::tungstenite::handshake::server::{
+        Callback, ErrorResponse, Request, Response,
+    };
+
+    // A named callback rather than a closure: the handshake dictates the (large) error type
+    struct CaptureUserAgent(tokio::sync::oneshot::Sender<Option<String>>);
+
+    impl Callback for CaptureUserAgent {
+        fn on_request(self, req: &Request, resp: Response) -> Result<Response, ErrorResponse> {
+            let ua = req
+                .headers()
+                .get("user-agent")
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string);
+            let _ = self.0.send(ua);
+            Ok(resp)
+        }
+    }
+
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .map_err(|e| AppError::Internal(format!("ws test server bind error: {e}")))?;
+    let local_addr = listener.local_addr().unwrap();
+
+    // Capture the User-Agent of the first upgrade request, then stay silent
+    let (ua_tx, ua_rx) = tokio::sync::oneshot::channel::<Option<String>>();
+    tokio::spawn(async move {
+        let (tcp, _peer) = listener.accept().await.expect("accept");
+        let ws = accept_hdr_async(tcp, CaptureUserAgent(ua_tx))
+            .await
+            .expect("accept_hdr_async");
+        let (_write, mut read) = ws.split();
+        let _ = tokio::time::timeout(Duration::from_secs(2), read.next()).await;
+        futures_util::future::pending::<()>().await;
+    });
+
+    let cfg = binance_test_cfg(local_addr)?;
+
+    let want = cfg
+        .resolved_headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("user-agent"))
+        .map(|(_, v)| v.clone())
+        .expect("binance_linear config sets a User-Agent");
+
+    let stream = cfg.ws.get("trades").expect("missing [ws.trades]").clone();
+    let client = WsClient::new("binance_linear", cfg, None, None);
+
+    let cancel = CancellationToken::new();
+    let cancel_for_stream = cancel.clone();
+    let run_task = tokio::spawn(async move {
+        client
+            .run_stream(
+                None,
+                &stream,
+                mk_ctx_btc(),
+                |_ev| Box::pin(async move { Ok(()) }),
+                None,
+                Some(cancel_for_stream),
+            )
+            .await
+    });
+
+    let got = tokio::time::timeout(Duration::from_secs(5), ua_rx)
+        .await
+        .map_err(|_| AppError::Internal("no ws handshake within 5s".into()))?
+        .map_err(|_| AppError::Internal("ws test server dropped".into()))?;
+    cancel.cancel();
+    let _ = tokio::time::timeout(Duration::from_secs(2), run_task).await;
+
+    assert_eq!(got.as_deref(), Some(want.as_str()));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_connect_failures_back_off_per_exchange_and_observe_wait() -> AppResult<()> {
+    use crate::ingest::metrics::IngestMetrics;
+
+    // Free port with nothing listening: every connect fails fast
+    let addr = {
+        let l = TcpListener::bind("127.0.0.1:0")
+            .await
+            .map_err(|e| AppError::Internal(format!("bind: {e}")))?;
+        l.local_addr().unwrap()
+    };
+
+    let appcfg = load_app_config(false, 0)?;
+    let mut cfg = binance_test_cfg(addr)?;
+    cfg.ws_reconnect_backoff_ms = Some(20);
+    cfg.ws_reconnect_backoff_max_ms = Some(40);
+    let stream = cfg.ws.get("trades").expect("missing [ws.trades]").clone();
+
+    let metrics = Arc::new(IngestMetrics::new()?);
+    let client = WsClient::new(
+        "binance_linear",
+        cfg,
+        Some(Arc::clone(&metrics)),
+        Some(&appcfg),
+    );
+    assert_eq!(client.ws_reconnect_backoff_initial_ms, 20);
+    assert_eq!(client.ws_reconnect_backoff_max_ms, 40);
+
+    let on_event = |_ev| Box::pin(async { AppResult::Ok(()) });
+
+    // 4 failed attempts, each followed by a backoff sleep: 20, 40, 40, 40 ms (+-20% jitter)
+    let mut hook = WsTestHook {
+        max_reconnect_attempts: Some(4),
+        ..Default::default()
+    };
+    let t0 = std::time::Instant::now();
+    let run = client.run_stream(None, &stream, mk_ctx_btc(), on_event, Some(&mut hook), None);
+    tokio::time::timeout(Duration::from_secs(10), run)
+        .await
+        .map_err(|_| AppError::Internal("backoff test timed out".into()))??;
+    assert!(
+        t0.elapsed() >= Duration::from_millis(110),
+        "{:?}",
+        t0.elapsed()
+    );
+    assert_eq!(metrics.ws_reconnect_wait_seconds.get_sample_count(), 4);
+    assert!(metrics.ws_reconnect_wait_seconds.get_sample_sum() >= 0.11);
+
+    // The hook can short-circuit the sleeps
+    let mut hook = WsTestHook {
+        max_reconnect_attempts: Some(4),
+        skip_reconnect_sleep: true,
+        ..Default::default()
+    };
+    client
+        .run_stream(None, &stream, mk_ctx_btc(), on_event, Some(&mut hook), None)
+        .await?;
+    assert_eq!(metrics.ws_reconnect_wait_seconds.get_sample_count(), 4);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_local_ws_silent_connection_hits_read_idle_timeout() -> AppResult<()> {
+    let local_addr = spawn_ws_server(serve_silent).await?;
+
+    let mut cfg = binance_test_cfg(local_addr)?;
+    cfg.ws_read_idle_timeout_seconds = 1;
+    let stream = cfg.ws.get("trades").expect("missing [ws.trades]").clone();
+
+    let client = WsClient::new("binance_linear", cfg, None, None);
+
+    // Open but silent: each connection is dropped after the idle timeout and reconnected
+    let mut hook = WsTestHook {
+        max_reconnect_attempts: Some(2),
+        skip_reconnect_sleep: true,
+        ..Default::default()
+    };
+    let run = client.run_stream(
+        None,
+        &stream,
+        mk_ctx_btc(),
+        |_ev| Box::pin(async { Ok(()) }),
+        Some(&mut hook),
+        None,
+    );
+    tokio::time::timeout(Duration::from_secs(10), run)
+        .await
+        .map_err(|_| AppError::Internal("idle timeout test timed out".into()))??;
+
+    assert_eq!(
+        hook.disconnects,
+        vec![
+            Some("read idle timeout".to_string()),
+            Some("read idle timeout".to_string())
+        ]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_local_ws_gzip_frames_are_decoded_and_bad_frames_dropped() -> AppResult<()> {
+    use crate::ingest::metrics::IngestMetrics;
+
+    let local_addr = spawn_ws_server(|ws| async move {
+        let (mut write, mut read) = ws.split();
+        let _ = tokio::time::timeout(Duration::from_secs(2), read.next()).await;
+
+        // Good frame, corrupt frame, good frame: all on the same connection
+        for frame in [gzip(r#"{"n":1}"#), b"not gzip".to_vec(), gzip(r#"{"n":2}"#)] {
+            write.send(Message::Binary(frame.into())).await.ok();
+        }
+        futures_util::future::pending::<()>().await;
+    })
+    .await?;
+
+    let mut cfg = binance_test_cfg(local_addr)?;
+    cfg.ws_compression = Some("gzip".into());
+    let stream = cfg.ws.get("trades").expect("missing [ws.trades]").clone();
+
+    let metrics = Arc::new(IngestMetrics::new()?);
+    let client = WsClient::new("binance_linear", cfg, Some(Arc::clone(&metrics)), None);
+
+    let texts = Arc::new(std::sync::Mutex::new(Vec::<String>::new()));
+    let seen = Arc::clone(&texts);
+    let on_event = move |ev| {
+        let seen = Arc::clone(&seen);
+        Box::pin(async move {
+            match ev {
+                WsEvent::Text(s) => {
+                    let mut v = seen.lock().unwrap();
+                    v.push(s);
+                    if v.len() == 2 {
+                        return Err(AppError::Internal("__TEST_DONE__".into()));
+                    }
+                    Ok(())
+                }
+                WsEvent::Binary(_) => Err(AppError::Internal("raw binary forwarded".into())),
+                _ => Ok(()),
+            }
+        }) as EventFuture
+    };
+
+    let mut hook = WsTestHook {
+        max_reconnect_attempts: Some(1),
+        ..Default::default()
+    };
+    let run = client.run_stream(None, &stream, mk_ctx_btc(), on_event, Some(&mut hook), None);
+    match tokio::time::timeout(Duration::from_secs(10), run).await {
+        Ok(Err(e)) if is_test_done(&e) => {}
+        other => panic!("expected test done, got {other:?}"),
+    }
+
+    assert_eq!(*texts.lock().unwrap(), vec![r#"{"n":1}"#, r#"{"n":2}"#]);
+    assert_eq!(metrics.errors_total.get(), 1);
+    assert!(hook.disconnects.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_local_ws_ping_rtt_recorded_and_unanswered_ping_times_out() -> AppResult<()> {
+    use crate::ingest::metrics::IngestMetrics;
+
+    // Echo server: tungstenite answers pings with a pong carrying the same payload
+    let echo_addr =
+        spawn_ws_server(|mut ws| async move { while let Some(Ok(_)) = ws.next().await {} }).await?;
+
+    // Silent server: reads the subscribe, then never reads (so never pongs) again
+    let silent_addr = spawn_ws_server(serve_silent).await?;
+
+    let mut cfg = binance_test_cfg(echo_addr)?;
+    cfg.ws_read_idle_timeout_seconds = 0;
+    cfg.ws_heartbeat_type = Some("ping".into());
+    cfg.ws_heartbeat_frame = None; // protocol pings
+    cfg.ws_heartbeat_timeout_seconds = Some(2); // ping every 1s
+    let stream = cfg.ws.get("trades").expect("missing [ws.trades]").clone();
+
+    let metrics = Arc::new(IngestMetrics::new()?);
+
+    let client = WsClient::new(
+        "binance_linear",
+        cfg.clone(),
+        Some(Arc::clone(&metrics)),
+        None,
+    );
+    let stop_on_pong = |ev| {
+        Box::pin(async move {
+            match ev {
+                WsEvent::Pong(_) => Err(AppError::Internal("__TEST_DONE__".into())),
+                _ => Ok(()),
+            }
+        }) as EventFuture
+    };
+    let run = client.run_stream(None, &stream, mk_ctx_btc(), stop_on_pong, None, None);
+    match tokio::time::timeout(Duration::from_secs(10), run).await {
+        Ok(Err(e)) if is_test_done(&e) => {}
+        other => panic!("expected a pong, got {other:?}"),
+    }
+    let rtt = metrics
+        .ws_ping_rtt_seconds
+        .with_label_values(&["binance_linear"]);
+    assert_eq!(rtt.get_sample_count(), 1);
+    assert!(rtt.get_sample_sum() < 1.0);
+
+    // No pong within ws_heartbeat_timeout_seconds: the connection is dropped
+    cfg.ws_base_url = format!("ws://{silent_addr}");
+    let client = WsClient::new("binance_linear", cfg, Some(Arc::clone(&metrics)), None);
+    let mut hook = WsTestHook {
+        max_reconnect_attempts: Some(1),
+        ..Default::default()
+    };
+    let run = client.run_stream(
+        None,
+        &stream,
+        mk_ctx_btc(),
+        |_ev| Box::pin(async { Ok(()) }),
+        Some(&mut hook),
+        None,
+    );
+    tokio::time::timeout(Duration::from_secs(10), run)
+        .await
+        .map_err(|_| AppError::Internal("pong timeout test timed out".into()))??;
+    assert_eq!(hook.disconnects, vec![Some("pong timeout".to_string())]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_local_ws_lifecycle_events_bracket_each_connection() -> AppResult<()> {
+    let local_addr = spawn_ws_server(|ws| {
+        serve_ping_close(
+            ws,
+            Arc::new(AtomicUsize::new(0)),
+            Arc::new(AtomicUsize::new(0)),
+        )
+    })
+    .await?;
+
+    let cfg = binance_test_cfg(local_addr)?;
+    let stream = cfg.ws.get("trades").expect("missing [ws.trades]").clone();
+
+    let client = WsClient::new("binance_linear", cfg, None, None);
+
+    // Everything but data/heartbeat frames, in arrival order
+    let events = Arc::new(std::sync::Mutex::new(Vec::<String>::new()));
+    let seen = Arc::clone(&events);
+    let on_event = move |ev| {
+        let seen = Arc::clone(&seen);
+        Box::pin(async move {
+            let label = match ev {
+                WsEvent::Connected { attempt } => format!("connected {attempt}"),
+                WsEvent::Close(_) => "close".to_string(),
+                WsEvent::Disconnected { reason, .. } => format!("disconnected {reason:?}"),
+                _ => return Ok(()),
+            };
+            seen.lock().unwrap().push(label);
+            Ok(())
+        }) as EventFuture
+    };
+
+    let mut hook = WsTestHook {
+        max_reconnect_attempts: Some(2),
+        skip_reconnect_sleep: true,
+        ..Default::default()
+    };
+    let run = client.run_stream(None, &stream, mk_ctx_btc(), on_event, Some(&mut hook), None);
+    tokio::time::timeout(Duration::from_secs(10), run)
+        .await
+        .map_err(|_| AppError::Internal("lifecycle test timed out".into()))??;
+
+    // The server closes every connection; the hook and the event see the same reason
+    assert_eq!(hook.disconnects.len(), 2);
+    assert!(
+        hook.disconnects
+            .iter()
+            .all(|r| r.as_deref().is_some_and(|r| r.starts_with("close:")))
+    );
+    // Close(None) from the server: no status
+    assert!(
+        hook.closes
+            .iter()
+            .all(|c| c.as_ref().is_some_and(|c| c.code == 1005))
+    );
+    let disconnected = |i: usize| format!("disconnected {:?}", hook.disconnects[i]);
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec![
+            "connected 1".to_string(),
+            "close".to_string(),
+            disconnected(0),
+            "connected 2".to_string(),
+            "close".to_string(),
+            disconnected(1),
+        ]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_local_ws_failing_disconnected_handler_ends_the_run() -> AppResult<()> {
+    let local_addr = spawn_ws_server(|ws| {
+        serve_ping_close(
+            ws,
+            Arc::new(AtomicUsize::new(0)),
+            Arc::new(AtomicUsize::new(0)),
+        )
+    })
+    .await?;
+
+    let cfg = binance_test_cfg(local_addr)?;
+    let stream = cfg.ws.get("trades").expect("missing [ws.trades]").clone();
+
+    let client = WsClient::new("binance_linear", cfg, None, None);
+
+    // Same handling as a failing `Connected`: the error ends the run, no reconnect
+    let on_event = |ev| {
+        Box::pin(async move {
+            match ev {
+                WsEvent::Disconnected { .. } => Err(AppError::Internal("__TEST_DONE__".into())),
+                _ => Ok(()),
+            }
+        }) as EventFuture
+    };
+
+    let mut hook = WsTestHook {
+        max_reconnect_attempts: Some(3),
+        skip_reconnect_sleep: true,
+        ..Default::default()
+    };
+    let run = client.run_stream(None, &stream, mk_ctx_btc(), on_event, Some(&mut hook), None);
+    let err = tokio::time::timeout(Duration::from_secs(10), run)
+        .await
+        .map_err(|_| AppError::Internal("disconnected handler test timed out".into()))?
+        .expect_err("the handler error ends the run");
+    assert!(is_test_done(&err), "{err}");
+    assert_eq!(hook.disconnects.len(), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_local_ws_close_code_and_reason_reach_hook_and_event() -> AppResult<()> {
+    use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+    use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+
+    // Acks the subscribe, then closes like a rate-limiting venue
+    let local_addr = spawn_ws_server(|ws| async move {
+        let (mut write, mut read) = ws.split();
+        let _ = tokio::time::timeout(Duration::from_secs(2), read.next()).await;
+        let _ = write
+            .send(Message::Text(r#"{"result":null,"id":"1"}"#.into()))
+            .await;
+        let _ = write
+            .send(Message::Close(Some(CloseFrame {
+                code: CloseCode::Policy,
+                reason: "too many requests".into(),
+            })))
+            .await;
+        while let Some(Ok(_)) = read.next().await {}
+    })
+    .await?;
+
+    let cfg = binance_test_cfg(local_addr)?;
+    let stream = cfg.ws.get("trades").expect("missing [ws.trades]").clone();
+
+    let client = WsClient::new("binance_linear", cfg, None, None);
+
+    let closes = Arc::new(std::sync::Mutex::new(Vec::<Option<WsCloseInfo>>::new()));
+    let seen = Arc::clone(&closes);
+    let on_event = move |ev| {
+        let seen = Arc::clone(&seen);
+        Box::pin(async move {
+            if let WsEvent::Disconnected { close, .. } = ev {
+                seen.lock().unwrap().push(close);
+            }
+            Ok(())
+        }) as EventFuture
+    };
+
+    let mut hook = WsTestHook {
+        max_reconnect_attempts: Some(1),
+        skip_reconnect_sleep: true,
+        ..Default::default()
+    };
+    let run = client.run_stream(None, &stream, mk_ctx_btc(), on_event, Some(&mut hook), None);
+    tokio::time::timeout(Duration::from_secs(10), run)
+        .await
+        .map_err(|_| AppError::Internal("close code test timed out".into()))??;
+
+    let expected = WsCloseInfo {
+        code: 1008,
+        reason: "too many requests".into(),
+    };
+    assert!(expected.is_throttle());
+    assert_eq!(hook.closes, vec![Some(expected.clone())]);
+    assert_eq!(
+        hook.disconnects,
+        vec![Some("close: 1008 too many requests".to_string())]
+    );
+    assert_eq!(*closes.lock().unwrap(), vec![Some(expected)]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_local_ws_throttle_close_waits_the_max_backoff() -> AppResult<()> {
+    use crate::ingest::metrics::IngestMetrics;
+    use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+    use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+
+    // Acks the subscribe, then closes with 1013 (try again later)
+    let local_addr = spawn_ws_server(|ws| async move {
+        let (mut write, mut read) = ws.split();
+        let _ = tokio::time::timeout(Duration::from_secs(2), read.next()).await;
+        let _ = write
+            .send(Message::Text(r#"{"result":null,"id":"1"}"#.into()))
+            .await;
+        let _ = write
+            .send(Message::Close(Some(CloseFrame {
+                code: CloseCode::Again,
+                reason: "".into(),
+            })))
+            .await;
+        while let Some(Ok(_)) = read.next().await {}
+    })
+    .await?;
+
+    let mut cfg = binance_test_cfg(local_addr)?;
+    cfg.ws_reconnect_backoff_ms = Some(20);
+    cfg.ws_reconnect_backoff_max_ms = Some(400);
+    let stream = cfg.ws.get("trades").expect("missing [ws.trades]").clone();
+
+    let metrics = Arc::new(IngestMetrics::new()?);
+    let client = WsClient::new("binance_linear", cfg, Some(Arc::clone(&metrics)), None);
+    let mut hook = WsTestHook {
+        max_reconnect_attempts: Some(1),
+        ..Default::default()
+    };
+    let run = client.run_stream(
+        None,
+        &stream,
+        mk_ctx_btc(),
+        |_ev| Box::pin(async { Ok(()) }),
+        Some(&mut hook),
+        None,
+    );
+    tokio::time::timeout(Duration::from_secs(10), run)
+        .await
+        .map_err(|_| AppError::Internal("throttle backoff test timed out".into()))??;
+
+    // First failure, yet the wait is the max backoff (400ms -20% jitter), not the initial 20ms
+    assert_eq!(hook.closes.len(), 1);
+    assert!(
+        hook.closes[0]
+            .as_ref()
+            .is_some_and(WsCloseInfo::is_throttle)
+    );
+    assert_eq!(metrics.ws_reconnect_wait_seconds.get_sample_count(), 1);
+    assert!(metrics.ws_reconnect_wait_seconds.get_sample_sum() >= 0.32);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_local_ws_handler_failure_dumps_recent_frames() -> AppResult<()> {
+    // Every connection gets {"n":1}..{"n":5}
+    let local_addr = spawn_ws_server(|ws| async move {
+        let (mut write, mut read) = ws.split();
+        let _ = tokio::time::timeout(Duration::from_secs(2), read.next()).await;
+        for n in 1..=5 {
+            let frame = format!(r#"{{"n":{n}}}"#);
+            write.send(Message::Text(frame.into())).await.ok();
+        }
+        futures_util::future::pending::<()>().await;
+    })
+    .await?;
+
+    let cfg = binance_test_cfg(local_addr)?;
+    let stream = cfg.ws.get("trades").expect("missing [ws.trades]").clone();
+
+    let dir = std::env::temp_dir().join(format!("ws_frame_dump_test_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let client = Arc::new(
+        WsClient::new("binance_linear", cfg, None, None).with_frame_ring(3, Some(dir.clone())),
+    );
+    let dumps = |dir: &std::path::Path| -> Vec<String> {
+        let mut paths: Vec<_> = std::fs::read_dir(dir)
+            .map(|d| d.flatten().map(|e| e.path()).collect())
+            .unwrap_or_default();
+        paths.sort();
+        paths
+            .iter()
+            .map(|p| std::fs::read_to_string(p).unwrap())
+            .collect()
+    };
+
+    // Handler error on the 5th frame: the last 3 frames are written out, the error returned
+    let seen = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&seen);
+    let fail_on_fifth = move |ev| {
+        let counter = Arc::clone(&counter);
+        Box::pin(async move {
+            if let WsEvent::Text(_) = ev
+                && counter.fetch_add(1, Ordering::SeqCst) + 1 == 5
+            {
+                return Err(AppError::Internal("boom".into()));
+            }
+            Ok(())
+        }) as EventFuture
+    };
+    let run = client.run_stream(None, &stream, mk_ctx_btc(), fail_on_fifth, None, None);
+    match tokio::time::timeout(Duration::from_secs(10), run).await {
+        Ok(Err(AppError::Internal(m))) if m == "boom" => {}
+        other => panic!("expected the handler error, got {other:?}"),
+    }
+    let written = dumps(&dir);
+    assert_eq!(written.len(), 1);
+    let lines: Vec<&str> = written[0].lines().collect();
+    assert!(lines[0].starts_with("# binance_linear ") && lines[0].ends_with("boom"));
+    assert_eq!(&lines[1..], [r#"{"n":3}"#, r#"{"n":4}"#, r#"{"n":5}"#]);
+
+    // Handler panic on the 2nd frame: dumped, then the panic goes on
+    let _ = std::fs::remove_dir_all(&dir);
+    let task_client = Arc::clone(&client);
+    let task = tokio::spawn(async move {
+        let seen = Arc::new(AtomicUsize::new(0));
+        let panic_on_second = move |ev| {
+            let seen = Arc::clone(&seen);
+            Box::pin(async move {
+                if let WsEvent::Text(_) = ev
+                    && seen.fetch_add(1, Ordering::SeqCst) + 1 == 2
+                {
+                    panic!("handler bug");
+                }
+                Ok(())
+            }) as EventFuture
+        };
+        task_client
+            .run_stream(None, &stream, mk_ctx_btc(), panic_on_second, None, None)
+            .await
+    });
+    let join = tokio::time::timeout(Duration::from_secs(10), task)
+        .await
+        .map_err(|_| AppError::Internal("panic test timed out".into()))?;
+    assert!(join.is_err_and(|e| e.is_panic()));
+    let written = dumps(&dir);
+    assert_eq!(written.len(), 1);
+    let lines: Vec<&str> = written[0].lines().collect();
+    assert!(lines[0].ends_with("panic: handler bug"));
+    assert_eq!(&lines[1..], [r#"{"n":1}"#, r#"{"n":2}"#]);
+
+    let _ = std::fs::remove_dir_all(&dir);
+    Ok(())
+}
+
+#[test]
+fn subscribe_ack_matching_follows_pointer_pairs() {
+    use crate::ingest::ws::ws_client::is_subscribe_ack;
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    let binance: BTreeMap<String, String> = [("/id".into(), "/id".into())].into();
+    let sub = json!({"method": "SUBSCRIBE", "params": ["btcusdt@aggTrade"], "id": 7});
+    assert!(is_subscribe_ack(
+        &json!({"result": null, "id": 7}),
+        &sub,
+        &binance
+    ));
+    assert!(!is_subscribe_ack(
+        &json!({"result": null, "id": 8}),
+        &sub,
+        &binance
+    ));
+    assert!(!is_subscribe_ack(&json!({"e": "aggTrade"}), &sub, &binance));
+
+    let hyperliquid: BTreeMap<String, String> = [
+        (
+            "/data/subscription/type".into(),
+            "/subscription/type".into(),
+        ),
+        (
+            "/data/subscription/coin".into(),
+            "/subscription/coin".into(),
+        ),
+    ]
+    .into();
+    let sub = json!({"method": "subscribe", "subscription": {"type": "trades", "coin": "BTC"}});
+    let ack = |coin: &str| {
+        json!({"channel": "subscriptionResponse",
+               "data": {"method": "subscribe", "subscription": {"type": "trades", "coin": coin}}})
+    };
+    assert!(is_subscribe_ack(&ack("BTC"), &sub, &hyperliquid));
+    assert!(!is_subscribe_ack(&ack("ETH"), &sub, &hyperliquid));
+    assert!(!is_subscribe_ack(
+        &json!({"channel": "trades", "data": [{"coin": "BTC"}]}),
+        &sub,
+        &hyperliquid
+    ));
+    // Nothing to compare against in the subscribe message: never an ack
+    assert!(!is_subscribe_ack(&json!({}), &json!({}), &binance));
+}
+
+/// Sends a data frame, the ack of the subscribe (its id echoed) when `ack`, another data frame.
+async fn serve_acking(ws: WebSocketStream<TcpStream>, ack: bool) {
+    let (mut write, mut read) = ws.split();
+    let Ok(Some(Ok(Message::Text(sub)))) =
+        tokio::time::timeout(Duration::from_secs(2), read.next()).await
+    else {
+        return;
+    };
+    let id = serde_json::from_str::<serde_json::Value>(&sub).unwrap()["id"].clone();
+    write
+        .send(Message::Text(r#"{"e":"aggTrade","n":1}"#.into()))
+        .await
+        .ok();
+    if ack {
+        let ack = serde_json::json!({"result": null, "id": id}).to_string();
+        write.send(Message::Text(ack.into())).await.ok();
+    }
+    write
+        .send(Message::Text(r#"{"e":"aggTrade","n":2}"#.into()))
+        .await
+        .ok();
+    futures_util::future::pending::<()>().await;
+}
+
+#[tokio::test]
+async fn test_local_ws_missing_subscribe_ack_fails_the_subscribe() -> AppResult<()> {
+    use crate::ingest::metrics::IngestMetrics;
+
+    let acking = spawn_ws_server(|ws| serve_acking(ws, true)).await?;
+    let silent = spawn_ws_server(|ws| serve_acking(ws, false)).await?;
+
+    let mut cfg = binance_test_cfg(acking)?;
+    cfg.ws_subscribe_ack_timeout_seconds = 1;
+    cfg.ws_subscribe_ack_match = [("/id".to_string(), "/id".to_string())].into();
+    cfg.ws_subscribe_ack_error_pointer = Some("/error".into());
+    let stream = cfg.ws.get("trades").expect("missing [ws.trades]").clone();
+
+    let texts = Arc::new(std::sync::Mutex::new(Vec::<String>::new()));
+    let on_event = {
+        let texts = Arc::clone(&texts);
+        move |ev| {
+            let texts = Arc::clone(&texts);
+            Box::pin(async move {
+                if let WsEvent::Text(s) = ev {
+                    let mut v = texts.lock().unwrap();
+                    v.push(s);
+                    if v.len() == 2 {
+                        return Err(AppError::Internal("__TEST_DONE__".into()));
+                    }
+                }
+                Ok(())
+            }) as EventFuture
+        }
+    };
+
+    // Acked: the data frame sent before the ack still reaches the handler, the ack does not
+    let client = WsClient::new("binance_linear", cfg.clone(), None, None);
+    let run = client.run_stream(None, &stream, mk_ctx_btc(), on_event.clone(), None, None);
+    match tokio::time::timeout(Duration::from_secs(10), run).await {
+        Ok(Err(e)) if is_test_done(&e) => {}
+        other => panic!("expected test done, got {other:?}"),
+    }
+    assert_eq!(
+        *texts.lock().unwrap(),
+        vec![r#"{"e":"aggTrade","n":1}"#, r#"{"e":"aggTrade","n":2}"#]
+    );
+
+    // Never acked: every attempt is a failed subscribe (counted); its data frames still reach
+    // the handler while waiting
+    texts.lock().unwrap().clear();
+    let on_event = {
+        let texts = Arc::clone(&texts);
+        move |ev| {
+            let texts = Arc::clone(&texts);
+            Box::pin(async move {
+                if let WsEvent::Text(s) = ev {
+                    texts.lock().unwrap().push(s);
+                }
+                Ok(())
+            }) as EventFuture
+        }
+    };
+    cfg.ws_base_url = format!("ws://{silent}");
+    let metrics = Arc::new(IngestMetrics::new()?);
+    let client = WsClient::new("binance_linear", cfg, Some(Arc::clone(&metrics)), None);
+    let mut hook = WsTestHook {
+        max_reconnect_attempts: Some(2),
+        skip_reconnect_sleep: true,
+        ..Default::default()
+    };
+    let run = client.run_stream(None, &stream, mk_ctx_btc(), on_event, Some(&mut hook), None);
+    tokio::time::timeout(Duration::from_secs(10), run)
+        .await
+        .map_err(|_| AppError::Internal("ack test timed out".into()))??;
+    assert_eq!(metrics.errors_total.get(), 2);
+    assert_eq!(texts.lock().unwrap().len(), 4);
+    assert!(hook.disconnects.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_local_ws_gzip_subscribe_ack_is_decoded() -> AppResult<()> {
+    use crate::ingest::metrics::IngestMetrics;
+
+    // Everything gzip'd: data, the ack (its id echoed), data
+    let local_addr = spawn_ws_server(|ws| async move {
+        let (mut write, mut read) = ws.split();
+        let Ok(Some(Ok(Message::Text(sub)))) =
+            tokio::time::timeout(Duration::from_secs(2), read.next()).await
+        else {
+            return;
+        };
+        let id = serde_json::from_str::<serde_json::Value>(&sub).unwrap()["id"].clone();
+        let ack = serde_json::json!({"result": null, "id": id}).to_string();
+        for frame in [
+            r#"{"e":"aggTrade","n":1}"#,
+            &ack,
+            r#"{"e":"aggTrade","n":2}"#,
+        ] {
+            write.send(Message::Binary(gzip(frame).into())).await.ok();
+        }
+        futures_util::future::pending::<()>().await;
+    })
+    .await?;
+
+    let mut cfg = binance_test_cfg(local_addr)?;
+    cfg.ws_compression = Some("gzip".into());
+    cfg.ws_subscribe_ack_timeout_seconds = 2;
+    cfg.ws_subscribe_ack_match = [("/id".to_string(), "/id".to_string())].into();
+    let stream = cfg.ws.get("trades").expect("missing [ws.trades]").clone();
+
+    let metrics = Arc::new(IngestMetrics::new()?);
+    let client = WsClient::new("binance_linear", cfg, Some(Arc::clone(&metrics)), None);
+    let texts = Arc::new(std::sync::Mutex::new(Vec::<String>::new()));
+    let seen = Arc::clone(&texts);
+    let on_event = move |ev| {
+        let seen = Arc::clone(&seen);
+        Box::pin(async move {
+            if let WsEvent::Text(s) = ev {
+                let mut v = seen.lock().unwrap();
+                v.push(s);
+                if v.len() == 2 {
+                    return Err(AppError::Internal("__TEST_DONE__".into()));
+                }
+            }
+            Ok(())
+        }) as EventFuture
+    };
+
+    // Acked on the first connection; the data frame sent before the ack is delivered
+    let mut hook = WsTestHook {
+        max_reconnect_attempts: Some(1),
+        ..Default::default()
+    };
+    let run = client.run_stream(None, &stream, mk_ctx_btc(), on_event, Some(&mut hook), None);
+    match tokio::time::timeout(Duration::from_secs(10), run).await {
+        Ok(Err(e)) if is_test_done(&e) => {}
+        other => panic!("expected test done, got {other:?}"),
+    }
+    assert_eq!(
+        *texts.lock().unwrap(),
+        vec![r#"{"e":"aggTrade","n":1}"#, r#"{"e":"aggTrade","n":2}"#]
+    );
+    // Counted as on the live connection: every frame in, all but the one whose handler
+    // ended the run processed (the ack included)
+    assert_eq!(metrics.in_total.get(), 3);
+    assert_eq!(metrics.processed_total.get(), 2);
+    assert_eq!(metrics.errors_total.get(), 0);
+    assert!(hook.disconnects.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_local_ws_venue_reported_subscribe_budget_throttles_the_limiter() -> AppResult<()> {
+    use crate::ingest::ws::ws_client::ack_remaining;
+    use serde_json::json;
+
+    assert_eq!(ack_remaining(&json!(3)), Some(3));
+    assert_eq!(ack_remaining(&json!(" 7 ")), Some(7));
+    assert_eq!(ack_remaining(&json!(-1)), None);
+    assert_eq!(ack_remaining(&json!(null)), None);
+
+    // Acks the subscribe reporting 1 subscribe left, then sends a data frame
+    let local_addr = spawn_ws_server(|ws| async move {
+        let (mut write, mut read) = ws.split();
+        let Some(Ok(Message::Text(sub))) = read.next().await else {
+            return;
+        };
+        let id = serde_json::from_str::<serde_json::Value>(&sub).unwrap()["id"].clone();
+        let ack = json!({"result": null, "id": id, "rateLimit": {"remaining": "1"}});
+        write.send(Message::Text(ack.to_string().into())).await.ok();
+        write
+            .send(Message::Text(r#"{"e":"aggTrade"}"#.into()))
             .await
-            .map_err(|e| AppError::Internal(format!("ws test server accept error: {e}")))?;
+            .ok();
+        futures_util::future::pending::<()>().await;
+    })
+    .await?;
 
-        let accept_count = accept_count.clone();
-        let pong_count = pong_count.clone();
+    let appcfg = load_app_config(false, 0)?;
+    let mut ex = ExchangeConfigs::new(&appcfg, false, 0)?;
+    let binance = ex
+        .binance_linear
+        .as_mut()
+        .expect("binance_linear config must exist");
+    // A window long enough not to reset during the test: 10 subscribes / minute
+    binance.ws_subscribe_attempt_limit = 10;
+    binance.ws_subscribe_attempts_reset_seconds = 60;
+    let ws_limiters = WsLimiterRegistry::new(&appcfg, &ex, None)?;
+
+    let mut cfg = binance_test_cfg(local_addr)?;
+    cfg.ws_subscribe_attempt_limit = 10;
+    cfg.ws_subscribe_attempts_reset_seconds = 60;
+    cfg.ws_subscribe_ack_timeout_seconds = 2;
+    cfg.ws_subscribe_ack_match = [("/id".to_string(), "/id".to_string())].into();
+    cfg.ws_subscribe_ack_remaining_pointer = Some("/rateLimit/remaining".into());
+    let stream = cfg.ws.get("trades").expect("missing [ws.trades]").clone();
+
+    let client = WsClient::new("binance_linear", cfg, None, None);
+    let on_event = stop_after_n_text_messages(1).await;
+    let run = client.run_stream(
+        Some(&ws_limiters),
+        &stream,
+        mk_ctx_btc(),
+        on_event,
+        None,
+        None,
+    );
+    match tokio::time::timeout(Duration::from_secs(10), run).await {
+        Ok(Err(e)) if is_test_done(&e) => {}
+        other => panic!("expected test done, got {other:?}"),
+    }
 
-        tokio::spawn(async move {
-            let ws = accept_async(tcp).await.expect("accept_async");
+    // 1 of 10 used locally, but the venue said 1 left: that is all the limiter grants now
+    assert_eq!(
+        ws_limiters
+            .get_remaining_subscribe_attempts("binance_linear")
+            .await?,
+        1
+    );
+    ws_limiters.acquire_subscribe("binance_linear").await?;
+    let throttled = tokio::time::timeout(
+        Duration::from_millis(200),
+        ws_limiters.acquire_subscribe("binance_linear"),
+    )
+    .await;
+    assert!(throttled.is_err(), "limiter should be exhausted");
+
+    // A venue reporting more than the local budget never raises it
+    assert_eq!(
+        ws_limiters
+            .observe_subscribe_remaining("binance_linear", 50)
+            .await?,
+        0
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_local_ws_run_streams_resubscribes_every_stream_on_reconnect() -> AppResult<()> {
+    // Each connection: collect the subscribes, send one frame, close
+    let subscribes = Arc::new(std::sync::Mutex::new(Vec::<Vec<String>>::new()));
+    let server_subs = Arc::clone(&subscribes);
+    let local_addr = spawn_ws_server(move |ws| {
+        let server_subs = Arc::clone(&server_subs);
+        async move {
             let (mut write, mut read) = ws.split();
+            let mut got = Vec::new();
+            while let Ok(Some(Ok(Message::Text(t)))) =
+                tokio::time::timeout(Duration::from_millis(300), read.next()).await
+            {
+                got.push(t.to_string());
+            }
+            server_subs.lock().unwrap().push(got);
+            write
+                .send(Message::Text(r#"{"e":"aggTrade"}"#.into()))
+                .await
+                .ok();
+            write.send(Message::Close(None)).await.ok();
+        }
+    })
+    .await?;
 
-            accept_count.fetch_add(1, Ordering::SeqCst);
+    let cfg = binance_test_cfg(local_addr)?;
+    let trades = cfg.ws.get("trades").expect("missing [ws.trades]").clone();
 
-            // Read the client's subscribe message (Binance-style JSON)
-            let _ = tokio::time::timeout(Duration::from_secs(2), read.next()).await;
+    let client = WsClient::new("binance_linear", cfg, None, None);
 
-            // Ping + expect Pong payload match
-            let payload = b"binance_ping".to_vec();
-            write.send(Message::Ping(payload.clone().into())).await.ok();
+    let mut eth = Ctx::new();
+    eth.insert("symbol".to_string(), "ethusdt".to_string());
+    let texts = Arc::new(AtomicUsize::new(0));
+    let seen = Arc::clone(&texts);
+    let on_event = move |ev| {
+        let seen = Arc::clone(&seen);
+        Box::pin(async move {
+            if let WsEvent::Text(_) = ev {
+                seen.fetch_add(1, Ordering::SeqCst);
+            }
+            Ok(())
+        }) as EventFuture
+    };
+
+    let mut hook = WsTestHook {
+        max_reconnect_attempts: Some(2),
+        skip_reconnect_sleep: true,
+        ..Default::default()
+    };
+    let run = client.run_streams(
+        None,
+        vec![(&trades, mk_ctx_btc()), (&trades, eth)],
+        on_event,
+        Some(&mut hook),
+        None,
+    );
+    tokio::time::timeout(Duration::from_secs(10), run)
+        .await
+        .map_err(|_| AppError::Internal("run_streams test timed out".into()))??;
+
+    // Both connections subscribed both symbols; all frames reached the one handler
+    let subscribes = subscribes.lock().unwrap();
+    assert_eq!(subscribes.len(), 2);
+    for conn in subscribes.iter() {
+        let all = conn.join("\n");
+        assert!(
+            all.contains("btcusdt@") && all.contains("ethusdt@"),
+            "{all}"
+        );
+    }
+    assert_eq!(texts.load(Ordering::SeqCst), 2);
+    Ok(())
+}
 
-            if let Ok(Some(Ok(Message::Pong(p)))) =
+#[tokio::test]
+async fn test_local_ws_multi_step_subscribe_sends_frames_in_order() -> AppResult<()> {
+    // Collects the control frames, acks the one carrying an id, then sends a data frame
+    let frames = Arc::new(std::sync::Mutex::new(Vec::<serde_json::Value>::new()));
+    let server_frames = Arc::clone(&frames);
+    let local_addr = spawn_ws_server(move |ws| {
+        let server_frames = Arc::clone(&server_frames);
+        async move {
+            let (mut write, mut read) = ws.split();
+            while let Ok(Some(Ok(Message::Text(t)))) =
                 tokio::time::timeout(Duration::from_secs(2), read.next()).await
             {
-                if p == payload {
-                    pong_count.fetch_add(1, Ordering::SeqCst);
+                let v: serde_json::Value = serde_json::from_str(&t).unwrap();
+                server_frames.lock().unwrap().push(v.clone());
+                if !v["id"].is_null() {
+                    let ack = serde_json::json!({"result": null, "id": v["id"]}).to_string();
+                    write.send(Message::Text(ack.into())).await.ok();
+                    write
+                        .send(Message::Text(r#"{"e":"aggTrade"}"#.into()))
+                        .await
+                        .ok();
                 }
             }
+        }
+    })
+    .await?;
+
+    let mut cfg = binance_test_cfg(local_addr)?;
+    cfg.ws_subscribe_msg = Some(
+        toml::from_str::<toml::Value>(
+            r#"steps = [
+                { method = "AUTH", key = "k" },
+                { method = "SUBSCRIBE", params = ["<stream_title>"], id = "<stream_id>" },
+            ]"#,
+        )?["steps"]
+            .clone(),
+    );
+    cfg.ws_subscribe_step_delay_ms = 100;
+    // Only the SUBSCRIBE step has `/id`: the AUTH step is not awaited
+    cfg.ws_subscribe_ack_timeout_seconds = 2;
+    cfg.ws_subscribe_ack_match = [("/id".to_string(), "/id".to_string())].into();
+    let stream = cfg.ws.get("trades").expect("missing [ws.trades]").clone();
 
-            // Send Binance-style subscribe ACK
-            let _ = write
-                .send(Message::Text(r#"{"result":null,"id":"1"}"#.into()))
-                .await;
+    let client = WsClient::new("binance_linear", cfg, None, None);
+    let on_event = stop_after_n_text_messages(1).await;
+    let started = std::time::Instant::now();
+    let run = client.run_stream(None, &stream, mk_ctx_btc(), on_event, None, None);
+    match tokio::time::timeout(Duration::from_secs(10), run).await {
+        Ok(Err(e)) if is_test_done(&e) => {}
+        other => panic!("expected test done, got {other:?}"),
+    }
+    assert!(started.elapsed() >= Duration::from_millis(100));
+
+    let frames = frames.lock().unwrap();
+    assert_eq!(frames.len(), 2, "{frames:?}");
+    assert_eq!(frames[0]["method"], "AUTH");
+    assert_eq!(frames[1]["method"], "SUBSCRIBE");
+    assert_eq!(frames[1]["params"][0], "btcusdt@aggTrade");
+    Ok(())
+}
 
-            // Close to force reconnect
-            let _ = write.send(Message::Close(None)).await;
-        });
+#[tokio::test]
+async fn test_local_ws_multi_step_subscribe_awaits_each_step_ack_before_the_next() -> AppResult<()>
+{
+    // Acks every frame 300ms after it arrived, reading on meanwhile
+    let frames = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let server_frames = Arc::clone(&frames);
+    let local_addr = spawn_ws_server(move |ws| {
+        let server_frames = Arc::clone(&server_frames);
+        async move {
+            let (mut write, mut read) = ws.split();
+            let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<serde_json::Value>();
+            tokio::spawn(async move {
+                while let Some(v) = rx.recv().await {
+                    tokio::time::sleep(Duration::from_millis(300)).await;
+                    let ack = serde_json::json!({"result": null, "id": v["id"]}).to_string();
+                    write.send(Message::Text(ack.into())).await.ok();
+                    if v["method"] == "SUBSCRIBE" {
+                        write
+                            .send(Message::Text(r#"{"e":"aggTrade"}"#.into()))
+                            .await
+                            .ok();
+                    }
+                }
+            });
+            while let Ok(Some(Ok(Message::Text(t)))) =
+                tokio::time::timeout(Duration::from_secs(2), read.next()).await
+            {
+                let v: serde_json::Value = serde_json::from_str(&t).unwrap();
+                server_frames
+                    .lock()
+                    .unwrap()
+                    .push((v["method"].clone(), std::time::Instant::now()));
+                tx.send(v).ok();
+            }
+        }
+    })
+    .await?;
+
+    let mut cfg = binance_test_cfg(local_addr)?;
+    cfg.ws_subscribe_msg = Some(
+        toml::from_str::<toml::Value>(
+            r#"steps = [
+                { method = "LOGIN", id = "login" },
+                { method = "SUBSCRIBE", params = ["<stream_title>"], id = "<stream_id>" },
+            ]"#,
+        )?["steps"]
+            .clone(),
+    );
+    cfg.ws_subscribe_step_delay_ms = 0;
+    cfg.ws_subscribe_ack_timeout_seconds = 2;
+    cfg.ws_subscribe_ack_match = [("/id".to_string(), "/id".to_string())].into();
+    cfg.validate()?;
+    let stream = cfg.ws.get("trades").expect("missing [ws.trades]").clone();
+
+    let client = WsClient::new("binance_linear", cfg, None, None);
+    let on_event = stop_after_n_text_messages(1).await;
+    let run = client.run_stream(None, &stream, mk_ctx_btc(), on_event, None, None);
+    match tokio::time::timeout(Duration::from_secs(10), run).await {
+        Ok(Err(e)) if is_test_done(&e) => {}
+        other => panic!("expected test done, got {other:?}"),
     }
+
+    // The SUBSCRIBE step went out only once the LOGIN step was acknowledged
+    let frames = frames.lock().unwrap();
+    assert_eq!(frames.len(), 2, "{frames:?}");
+    assert_eq!(
+        (&frames[0].0, &frames[1].0),
+        (&"LOGIN".into(), &"SUBSCRIBE".into())
+    );
+    assert!(frames[1].1 - frames[0].1 >= Duration::from_millis(300));
+    Ok(())
 }
 
-// --- Local WS server: accepts WS, reads subscribe, then stays silent forever.
-async fn spawn_local_ws_server_silent_listener(listener: TcpListener) -> AppResult<()> {
-    loop {
-        let (tcp, _peer) = listener
-            .accept()
-            .await
-            .map_err(|e| AppError::Internal(format!("ws test server accept error: {e}")))?;
+#[tokio::test]
+async fn test_local_ws_mass_reconnect_under_limiters_never_wedges() -> AppResult<()> {
+    use crate::ingest::ws::{
+        AttemptLimiterConfig, ReconnectAttemptLimiter, SubscribeAttemptLimiter, WsLimiterOrder,
+    };
 
-        tokio::spawn(async move {
-            let ws = accept_async(tcp).await.expect("accept_async");
-            let (_write, mut read) = ws.split();
+    const STREAMS: usize = 20;
+    const CONNECTS: usize = 2;
 
-            // Read the client's subscribe message (best-effort, so test doesn't hang here)
-            let _ = tokio::time::timeout(Duration::from_secs(2), read.next()).await;
+    let limiters = || {
+        let window = Duration::from_millis(200);
+        let subscribe = || {
+            SubscribeAttemptLimiter::new(
+                AttemptLimiterConfig {
+                    max_attempts: 5,
+                    window,
+                },
+                None,
+            )
+        };
+        let reconnect = || {
+            ReconnectAttemptLimiter::new(
+                AttemptLimiterConfig {
+                    max_attempts: 10,
+                    window,
+                },
+                None,
+            )
+        };
+        WsLimiterRegistry {
+            binance_linear_subscribe: subscribe(),
+            binance_linear_reconnect: reconnect(),
+            hyperliquid_perp_subscribe: subscribe(),
+            hyperliquid_perp_reconnect: reconnect(),
+        }
+    };
 
-            // Now: be completely silent forever (or long enough)
-            futures_util::future::pending::<()>().await;
+    for order in [
+        WsLimiterOrder::ReconnectFirst,
+        WsLimiterOrder::SubscribeFirst,
+        WsLimiterOrder::Together,
+    ] {
+        // Each connection: time from open to its subscribe, then close
+        let idle = Arc::new(std::sync::Mutex::new(Vec::<Duration>::new()));
+        let server_idle = Arc::clone(&idle);
+        let local_addr = spawn_ws_server(move |ws| {
+            let server_idle = Arc::clone(&server_idle);
+            async move {
+                let opened = std::time::Instant::now();
+                let (mut write, mut read) = ws.split();
+                if let Ok(Some(Ok(Message::Text(_)))) =
+                    tokio::time::timeout(Duration::from_secs(5), read.next()).await
+                {
+                    server_idle.lock().unwrap().push(opened.elapsed());
+                }
+                write.send(Message::Close(None)).await.ok();
+            }
+        })
+        .await?;
+
+        let mut cfg = binance_test_cfg(local_addr)?;
+        cfg.ws_limiter_order = order;
+        let stream = cfg.ws.get("trades").expect("missing [ws.trades]").clone();
+        let lims = limiters();
+        let clients: Vec<WsClient> = (0..STREAMS)
+            .map(|_| WsClient::new("binance_linear", cfg.clone(), None, None))
+            .collect();
+        let mut hooks: Vec<WsTestHook> = (0..STREAMS)
+            .map(|_| WsTestHook {
+                max_reconnect_attempts: Some(CONNECTS as u32),
+                skip_reconnect_sleep: true,
+                ..Default::default()
+            })
+            .collect();
+
+        let runs = clients.iter().zip(hooks.iter_mut()).map(|(client, hook)| {
+            client.run_stream(
+                Some(&lims),
+                &stream,
+                mk_ctx_btc(),
+                |_ev| async { Ok(()) },
+                Some(hook),
+                None,
+            )
         });
+        let results = tokio::time::timeout(
+            Duration::from_secs(20),
+            futures_util::future::join_all(runs),
+        )
+        .await
+        .map_err(|_| AppError::Internal(format!("{order:?}: mass reconnect wedged")))?;
+        for r in results {
+            r?;
+        }
+
+        // Subscribes are the tighter limit (8 windows for 40); no socket waited for one
+        let idle = idle.lock().unwrap();
+        assert_eq!(idle.len(), STREAMS * CONNECTS, "{order:?}");
+        let worst = idle.iter().max().copied().unwrap_or_default();
+        assert!(
+            worst < Duration::from_millis(150),
+            "{order:?}: a connection sat idle {worst:?} before subscribing"
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_local_ws_json_heartbeat_expects_pong_within_two_intervals() -> AppResult<()> {
+    use crate::ingest::config::StringOrTable;
+    use crate::ingest::metrics::IngestMetrics;
+    use crate::ingest::ws::ws_client::is_text_pong;
+
+    assert!(is_text_pong("pong") && is_text_pong(r#"{"channel":"pong"}"#));
+    assert!(!is_text_pong(r#"{"channel":"trades","data":[]}"#));
+
+    // Answers every {"op":"ping"} with {"op":"pong"}
+    let answering_addr = spawn_ws_server(|mut ws| async move {
+        while let Some(Ok(msg)) = ws.next().await {
+            if let Message::Text(t) = msg
+                && t.contains(r#""op":"ping""#)
+            {
+                ws.send(Message::Text(r#"{"op":"pong"}"#.into())).await.ok();
+            }
+        }
+    })
+    .await?;
+
+    let silent_addr = spawn_ws_server(serve_silent).await?;
+
+    let mut cfg = binance_test_cfg(answering_addr)?;
+    cfg.ws_read_idle_timeout_seconds = 0;
+    cfg.ws_heartbeat_type = Some("json".into());
+    cfg.ws_heartbeat_frame = Some(StringOrTable::Table(
+        toml::from_str(r#"op = "ping""#).unwrap(),
+    ));
+    cfg.ws_heartbeat_timeout_seconds = Some(2); // heartbeat every 1s
+    cfg.ws_heartbeat_expect_pong = true;
+    cfg.validate()?;
+    let mut ping = cfg.clone();
+    ping.ws_heartbeat_type = Some("ping".into());
+    assert!(
+        ping.validate().is_err(),
+        "expect_pong needs json heartbeats"
+    );
+    let stream = cfg.ws.get("trades").expect("missing [ws.trades]").clone();
+
+    // Answered: pongs reach the handler as Pong (not data), the connection stays up
+    let metrics = Arc::new(IngestMetrics::new()?);
+    let client = WsClient::new(
+        "binance_linear",
+        cfg.clone(),
+        Some(Arc::clone(&metrics)),
+        None,
+    );
+    let pongs = Arc::new(AtomicUsize::new(0));
+    let seen = Arc::clone(&pongs);
+    let on_event = move |ev| {
+        let seen = Arc::clone(&seen);
+        Box::pin(async move {
+            match ev {
+                WsEvent::Pong(p) if p == br#"{"op":"pong"}"# => {
+                    if seen.fetch_add(1, Ordering::SeqCst) + 1 == 3 {
+                        return Err(AppError::Internal("__TEST_DONE__".into()));
+                    }
+                    Ok(())
+                }
+                WsEvent::Text(t) => Err(AppError::Internal(format!("pong as data: {t}"))),
+                _ => Ok(()),
+            }
+        }) as EventFuture
+    };
+    let mut hook = WsTestHook {
+        max_reconnect_attempts: Some(1),
+        ..Default::default()
+    };
+    let run = client.run_stream(None, &stream, mk_ctx_btc(), on_event, Some(&mut hook), None);
+    match tokio::time::timeout(Duration::from_secs(10), run).await {
+        Ok(Err(e)) if is_test_done(&e) => {}
+        other => panic!("expected 3 pongs, got {other:?}"),
     }
+    assert!(hook.disconnects.is_empty());
+    let rtt = metrics
+        .ws_ping_rtt_seconds
+        .with_label_values(&["binance_linear"]);
+    assert_eq!(rtt.get_sample_count(), 3);
+
+    // Unanswered: dropped after two intervals
+    cfg.ws_base_url = format!("ws://{silent_addr}");
+    let client = WsClient::new("binance_linear", cfg, None, None);
+    let mut hook = WsTestHook {
+        max_reconnect_attempts: Some(1),
+        ..Default::default()
+    };
+    let run = client.run_stream(
+        None,
+        &stream,
+        mk_ctx_btc(),
+        |_ev| Box::pin(async { Ok(()) }),
+        Some(&mut hook),
+        None,
+    );
+    tokio::time::timeout(Duration::from_secs(10), run)
+        .await
+        .map_err(|_| AppError::Internal("json pong timeout test timed out".into()))??;
+    assert_eq!(hook.disconnects, vec![Some("pong timeout".to_string())]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_local_ws_shipped_heartbeats_record_ping_rtt() -> AppResult<()> {
+    use crate::ingest::metrics::IngestMetrics;
+
+    let appcfg = load_app_config(false, 0)?;
+    let ex = ExchangeConfigs::new(&appcfg, false, 0)?;
+    let venues = [
+        ("binance_linear", ex.binance_linear.clone()),
+        ("hyperliquid_perp", ex.hyperliquid_perp.clone()),
+    ];
+    for (name, cfg) in venues {
+        let mut cfg = cfg.unwrap_or_else(|| panic!("{name} config must exist"));
+
+        // Venue-like server: protocol pings are answered by tungstenite, Hyperliquid's
+        // {"method":"ping"} with {"channel":"pong"}
+        let addr = spawn_ws_server(|mut ws| async move {
+            while let Some(Ok(msg)) = ws.next().await {
+                if let Message::Text(t) = msg
+                    && t.contains(r#""method":"ping""#)
+                {
+                    ws.send(Message::Text(r#"{"channel":"pong"}"#.into()))
+                        .await
+                        .ok();
+                }
+            }
+        })
+        .await?;
+
+        // Heartbeat type/frame as shipped; only the period is shortened
+        cfg.ws_base_url = format!("ws://{addr}");
+        cfg.ws_connection_timeout_seconds = 0;
+        cfg.ws_read_idle_timeout_seconds = 0;
+        cfg.ws_heartbeat_timeout_seconds = Some(2);
+        let stream = cfg.ws.get("trades").expect("missing [ws.trades]").clone();
+
+        let metrics = Arc::new(IngestMetrics::new()?);
+        let client = WsClient::new(name, cfg, Some(Arc::clone(&metrics)), None);
+        let stop_on_pong = |ev| {
+            Box::pin(async move {
+                match ev {
+                    WsEvent::Pong(_) => Err(AppError::Internal("__TEST_DONE__".into())),
+                    _ => Ok(()),
+                }
+            }) as EventFuture
+        };
+        let run = client.run_stream(None, &stream, mk_ctx_btc(), stop_on_pong, None, None);
+        match tokio::time::timeout(Duration::from_secs(10), run).await {
+            Ok(Err(e)) if is_test_done(&e) => {}
+            other => panic!("{name}: expected a pong, got {other:?}"),
+        }
+        let rtt = metrics.ws_ping_rtt_seconds.with_label_values(&[name]);
+        assert_eq!(
+            rtt.get_sample_count(),
+            1,
+            "{name}: ping round trip not timed"
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_local_ws_cancel_unsubscribes_and_stops_a_stream_waiting_for_limiter() -> AppResult<()>
+{
+    use crate::ingest::ws::{
+        AttemptLimiterConfig, ReconnectAttemptLimiter, SubscribeAttemptLimiter,
+    };
+
+    // Records every text frame the client sends
+    let received = Arc::new(std::sync::Mutex::new(Vec::<String>::new()));
+    let server_received = Arc::clone(&received);
+    let local_addr = spawn_ws_server(move |mut ws| {
+        let server_received = Arc::clone(&server_received);
+        async move {
+            while let Some(Ok(msg)) = ws.next().await {
+                if let Message::Text(t) = msg {
+                    server_received.lock().unwrap().push(t.to_string());
+                }
+            }
+        }
+    })
+    .await?;
+
+    let cfg = binance_test_cfg(local_addr)?;
+    let stream = cfg.ws.get("trades").expect("missing [ws.trades]").clone();
+    let client = WsClient::new("binance_linear", cfg, None, None);
+
+    // Connected stream: cancel leaves it after the unsubscribe, no reconnect
+    let cancel = CancellationToken::new();
+    let mut hook = WsTestHook::default();
+    let run = client.run_stream(
+        None,
+        &stream,
+        mk_ctx_btc(),
+        |_ev| async { Ok(()) },
+        Some(&mut hook),
+        Some(cancel.clone()),
+    );
+    let stop = async {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        cancel.cancel();
+    };
+    let (res, ()) = tokio::time::timeout(Duration::from_secs(5), async { tokio::join!(run, stop) })
+        .await
+        .map_err(|_| AppError::Internal("cancel did not stop the stream".into()))?;
+    res?;
+    assert_eq!(hook.reconnect_attempts, 1);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    {
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 2, "{received:?}");
+        assert!(received[0].contains("\"SUBSCRIBE\""));
+        assert!(received[1].contains("\"UNSUBSCRIBE\""));
+    }
+
+    // Reconnect limiter exhausted for an hour: cancel ends the wait
+    let window = Duration::from_secs(3600);
+    let exhausted = ReconnectAttemptLimiter::new(
+        AttemptLimiterConfig {
+            max_attempts: 0,
+            window,
+        },
+        None,
+    );
+    let subscribe = SubscribeAttemptLimiter::new(
+        AttemptLimiterConfig {
+            max_attempts: 10,
+            window,
+        },
+        None,
+    );
+    let lims = WsLimiterRegistry {
+        binance_linear_subscribe: subscribe.clone(),
+        binance_linear_reconnect: exhausted.clone(),
+        hyperliquid_perp_subscribe: subscribe,
+        hyperliquid_perp_reconnect: exhausted,
+    };
+    let cancel = CancellationToken::new();
+    let run = client.run_stream(
+        Some(&lims),
+        &stream,
+        mk_ctx_btc(),
+        |_ev| async { Ok(()) },
+        None,
+        Some(cancel.clone()),
+    );
+    let stop = async {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        cancel.cancel();
+    };
+    let (res, ()) = tokio::time::timeout(Duration::from_secs(5), async { tokio::join!(run, stop) })
+        .await
+        .map_err(|_| AppError::Internal("cancel did not end the limiter wait".into()))?;
+    res?;
+    assert_eq!(received.lock().unwrap().len(), 2, "never connected");
+    Ok(())
+}
+
+/// Forward proxy on a local port: HTTP `CONNECT` or SOCKS5 (user/pass auth), then a plain
+/// splice. Records the target and the credentials it was given.
+async fn spawn_local_ws_proxy(
+    socks5: bool,
+) -> (std::net::SocketAddr, Arc<std::sync::Mutex<Vec<String>>>) {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let log = Arc::clone(&seen);
+    tokio::spawn(async move {
+        while let Ok((mut client, _)) = listener.accept().await {
+            let log = Arc::clone(&log);
+            tokio::spawn(async move {
+                let target = if socks5 {
+                    let mut hello = [0u8; 2];
+                    client.read_exact(&mut hello).await.unwrap();
+                    let mut methods = vec![0u8; hello[1] as usize];
+                    client.read_exact(&mut methods).await.unwrap();
+                    assert!(methods.contains(&2), "client offers user/pass auth");
+                    client.write_all(&[5, 2]).await.unwrap();
+
+                    let mut ver_ulen = [0u8; 2];
+                    client.read_exact(&mut ver_ulen).await.unwrap();
+                    let mut user = vec![0u8; ver_ulen[1] as usize];
+                    client.read_exact(&mut user).await.unwrap();
+                    let plen = client.read_u8().await.unwrap();
+                    let mut pass = vec![0u8; plen as usize];
+                    client.read_exact(&mut pass).await.unwrap();
+                    log.lock().unwrap().push(format!(
+                        "auth {}:{}",
+                        String::from_utf8_lossy(&user),
+                        String::from_utf8_lossy(&pass)
+                    ));
+                    client.write_all(&[1, 0]).await.unwrap();
+
+                    let mut req = [0u8; 5];
+                    client.read_exact(&mut req).await.unwrap();
+                    assert_eq!(&req[..4], &[5, 1, 0, 3], "CONNECT by domain name");
+                    let mut host = vec![0u8; req[4] as usize];
+                    client.read_exact(&mut host).await.unwrap();
+                    let port = client.read_u16().await.unwrap();
+                    client
+                        .write_all(&[5, 0, 0, 1, 0, 0, 0, 0, 0, 0])
+                        .await
+                        .unwrap();
+                    format!("{}:{port}", String::from_utf8_lossy(&host))
+                } else {
+                    let mut head = Vec::new();
+                    while !head.ends_with(b"\r\n\r\n") {
+                        head.push(client.read_u8().await.unwrap());
+                    }
+                    let head = String::from_utf8(head).unwrap();
+                    let mut lines = head.lines();
+                    let target = lines
+                        .next()
+                        .and_then(|l| l.strip_prefix("CONNECT "))
+                        .and_then(|l| l.strip_suffix(" HTTP/1.1"))
+                        .expect("CONNECT request line")
+                        .to_string();
+                    if let Some(auth) = lines.find_map(|l| l.strip_prefix("Proxy-Authorization: "))
+                    {
+                        log.lock().unwrap().push(format!("auth {auth}"));
+                    }
+                    client
+                        .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+                        .await
+                        .unwrap();
+                    target
+                };
+                log.lock().unwrap().push(format!("connect {target}"));
+
+                let mut upstream = tokio::net::TcpStream::connect(target).await.unwrap();
+                let _ = tokio::io::copy_bidirectional(&mut client, &mut upstream).await;
+            });
+        }
+    });
+    (addr, seen)
+}
+
+#[tokio::test]
+async fn test_local_ws_connects_through_http_and_socks5_proxies() -> AppResult<()> {
+    // Venue: acks the subscribe and sends one data frame
+    let venue = spawn_ws_server(|ws| async move {
+        let (mut write, mut read) = ws.split();
+        let _ = tokio::time::timeout(Duration::from_secs(2), read.next()).await;
+        let _ = write
+            .send(Message::Text(r#"{"result":null,"id":"1"}"#.into()))
+            .await;
+        let _ = write
+            .send(Message::Text(r#"{"e":"aggTrade","s":"BTCUSDT"}"#.into()))
+            .await;
+        while let Some(Ok(_)) = read.next().await {}
+    })
+    .await?;
+
+    unsafe { std::env::set_var("TEST_WS_PROXY_AUTH", "collector:s3cret") };
+
+    for socks5 in [false, true] {
+        let (proxy, seen) = spawn_local_ws_proxy(socks5).await;
+
+        // By host name, so the proxy is asked to resolve it
+        let mut cfg = binance_test_cfg(format!("localhost:{}", venue.port()))?;
+        let scheme = if socks5 { "socks5" } else { "http" };
+        cfg.ws_proxy_url = Some(format!("{scheme}://{proxy}"));
+        cfg.ws_proxy_auth_env = Some("TEST_WS_PROXY_AUTH".into());
+        let stream = cfg.ws.get("trades").expect("missing [ws.trades]").clone();
+
+        let client = WsClient::new("binance_linear", cfg, None, None);
+        let mut hook = WsTestHook {
+            max_reconnect_attempts: Some(1),
+            skip_reconnect_sleep: true,
+            ..Default::default()
+        };
+        let run = client.run_stream(
+            None,
+            &stream,
+            mk_ctx_btc(),
+            stop_after_n_text_messages(1).await,
+            Some(&mut hook),
+            None,
+        );
+        match tokio::time::timeout(Duration::from_secs(10), run).await {
+            Ok(Err(e)) if is_test_done(&e) => {}
+            other => panic!("{scheme} proxy: no frame through the tunnel: {other:?}"),
+        }
+
+        let auth = if socks5 {
+            "auth collector:s3cret".to_string()
+        } else {
+            // base64("collector:s3cret")
+            "auth Basic Y29sbGVjdG9yOnMzY3JldA==".to_string()
+        };
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![auth, format!("connect localhost:{}", venue.port())],
+            "{scheme} proxy"
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn test_decompress_frame_is_capped() {
+    use crate::ingest::ws::ws_client::{decompress_frame, decompress_frame_within};
+
+    let text = "x".repeat(4096);
+    let frame = gzip(&text);
+    assert_eq!(decompress_frame("gzip", &frame).unwrap(), text);
+    assert_eq!(decompress_frame_within("gzip", &frame, 4096).unwrap(), text);
+
+    // A frame inflating past the cap is an error (dropped), not an unbounded buffer
+    let err = decompress_frame_within("gzip", &frame, 4095).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
 }
@@ -1,13 +1,17 @@
 mod cli;
 
 use clap::Parser;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
 use tokio::runtime::Builder;
 use tracing::debug;
 
 use crate::cli::{Cli, ConfigSource, ShutdownAction};
 use mini_fintickstreams::api::server::run_api_server;
 use mini_fintickstreams::app::runtime::AppRuntime;
+use mini_fintickstreams::app::startup::Startup;
 use mini_fintickstreams::error::AppResult;
+use mini_fintickstreams::prometheus::config::PrometheusConfig;
 use mini_fintickstreams::prometheus::server::run_metrics_server;
 use mini_fintickstreams::telemetry::tracing as app_tracing;
 
@@ -35,34 +39,77 @@ fn main() -> AppResult<()> {
         debug!(?cli, "starting");
 
         let from_env = matches!(cli.config, ConfigSource::Env);
-        let runtime = AppRuntime::new(from_env, cli.stream_version).await?;
+        let prom_cfg = PrometheusConfig::load(from_env, cli.stream_version)?;
 
-        // ✅ Restore on startup (your logic)
-        match cli.shutdown_action {
-            ShutdownAction::None => {}
-            ShutdownAction::RestoreStreams => {
-                debug!("shutdown_action=RestoreStreams: running restore");
-                runtime.on_crash().await?;
+        // Subsystems come up in dependency order (app::startup); metrics report the progress
+        let startup = Arc::new(Startup::new());
+        let runtime_slot: Arc<OnceLock<AppRuntime>> = Arc::new(OnceLock::new());
+        let gather = {
+            let startup = startup.clone();
+            let slot = runtime_slot.clone();
+            move || {
+                let mut text = startup.encode_prometheus_text();
+                if let Some(rt) = slot.get() {
+                    text.push_str(&rt.encode_prometheus_text()?);
+                }
+                Ok(text)
             }
+        };
+        let spawn_metrics =
+            move || tokio::spawn(run_metrics_server(gather, from_env, cli.stream_version));
+
+        let mut metrics_task = prom_cfg.start_early.then(spawn_metrics.clone());
+
+        let started = async {
+            let runtime =
+                AppRuntime::new_with_startup(from_env, cli.stream_version, &startup).await?;
+
+            // ✅ Restore on startup (your logic)
+            startup
+                .step("streams", async {
+                    match cli.shutdown_action {
+                        ShutdownAction::None => Ok(()),
+                        ShutdownAction::RestoreStreams => {
+                            debug!("shutdown_action=RestoreStreams: running restore");
+                            runtime.on_crash().await
+                        }
+                    }
+                })
+                .await?;
+            AppResult::Ok(runtime)
         }
+        .await;
 
-        let gather = {
-            let rt = runtime.clone();
-            move || rt.encode_prometheus_text()
+        let runtime = match started {
+            Ok(runtime) => runtime,
+            Err(e) => {
+                let linger = prom_cfg.startup_failure_linger_secs;
+                if metrics_task.is_some() && linger > 0 {
+                    tracing::error!(
+                        error = %e,
+                        linger_secs = linger,
+                        "startup failed; serving metrics before exit"
+                    );
+                    tokio::time::sleep(Duration::from_secs(linger)).await;
+                }
+                return Err(e);
+            }
         };
+        let _ = runtime_slot.set(runtime.clone());
+        let metrics_task = metrics_task.get_or_insert_with(spawn_metrics);
 
         let api_task = run_api_server(runtime.clone(), from_env, cli.stream_version);
-        let metrics_task = run_metrics_server(gather, from_env, cli.stream_version);
 
         // ✅ Keep running until one task errors/exits, or Ctrl+C
         tokio::select! {
             res = api_task => res?,
-            res = metrics_task => res?,
+            res = metrics_task => res??,
             _ = tokio::signal::ctrl_c() => {
                 debug!("shutdown signal received");
             }
         }
 
+        runtime.shutdown().await;
         debug!("exiting");
         Ok(())
     })
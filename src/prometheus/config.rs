@@ -17,6 +17,23 @@ pub struct PrometheusConfig {
 
     #[serde(default)]
     pub targets: TargetsConfig,
+
+    /// Serve metrics before the other subsystems start, so startup progress (and the
+    /// subsystem that failed) can be scraped. Off = serve once startup completed.
+    #[serde(default = "default_start_early")]
+    pub start_early: bool,
+
+    /// After a startup failure, keep serving metrics this long before exiting (0 = exit now).
+    #[serde(default = "default_startup_failure_linger_secs")]
+    pub startup_failure_linger_secs: u64,
+}
+
+fn default_start_early() -> bool {
+    true
+}
+
+fn default_startup_failure_linger_secs() -> u64 {
+    30
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -1,6 +1,7 @@
 use crate::error::{AppError, AppResult};
 use crate::redis::config::RedisConfig;
 use crate::redis::health::poller::RedisProbe;
+use crate::redis::health::types::GroupInfo;
 use crate::redis::manager::RedisStreamPublisher;
 
 use async_trait::async_trait;
@@ -94,22 +95,36 @@ impl RedisProbe for RedisClient {
     async fn ping(&self) -> AppResult<()> {
         let cmd = redis::cmd("PING");
         let pong = self.cmd_string(&cmd).await?;
-        if pong.trim() == "PONG" {
-            Ok(())
-        } else {
-            Err(AppError::RedisLogic(format!("PING returned '{pong}'")))
-        }
+        check_pong(&pong)
     }
 
     async fn memory_info(&self) -> AppResult<(u64, Option<u64>, Option<f64>)> {
         let raw = self.info_memory_raw().await?;
-        let parsed = RedisMemoryInfo::parse(&raw);
+        Ok(RedisMemoryInfo::parse(&raw).as_tuple())
+    }
+
+    /// PING + INFO memory in one pipeline. If the pipeline fails as a whole (e.g. INFO is
+    /// not permitted) the commands are retried one by one to tell the two failures apart.
+    async fn ping_and_memory_info(&self) -> AppResult<AppResult<(u64, Option<u64>, Option<f64>)>> {
+        let mut pipe = redis::pipe();
+        pipe.cmd("PING").cmd("INFO").arg("memory");
+        let res: AppResult<(String, String)> = self
+            .with_timeout(async {
+                let mut conn = self.manager.clone();
+                pipe.query_async(&mut conn).await
+            })
+            .await;
 
-        Ok((
-            parsed.used_memory_bytes,
-            parsed.maxmemory_bytes,
-            parsed.used_memory_pct,
-        ))
+        match res {
+            Ok((pong, raw)) => {
+                check_pong(&pong)?;
+                Ok(Ok(RedisMemoryInfo::parse(&raw).as_tuple()))
+            }
+            Err(_) => {
+                self.ping().await?;
+                Ok(self.memory_info().await)
+            }
+        }
     }
 
     /// App-defined pending/backlog.
@@ -119,10 +134,96 @@ impl RedisProbe for RedisClient {
     async fn pending_total(&self) -> AppResult<u64> {
         Ok(0)
     }
+
+    async fn xinfo_groups(&self, key: &str) -> AppResult<Vec<GroupInfo>> {
+        let mut cmd = redis::cmd("XINFO");
+        cmd.arg("GROUPS").arg(key);
+        match self.cmd_value(cmd).await {
+            Ok(v) => parse_xinfo_groups(&v),
+            // Stream trimmed away / never written
+            Err(e) if e.to_string().contains("no such key") => Ok(Vec::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn xlen(&self, key: &str) -> AppResult<u64> {
+        let mut cmd = redis::cmd("XLEN");
+        cmd.arg(key);
+        self.with_timeout(async {
+            let mut conn = self.manager.clone();
+            cmd.query_async(&mut conn).await
+        })
+        .await
+    }
+}
+
+// ------------------------------------------------------------
+// XINFO GROUPS parser
+// ------------------------------------------------------------
+
+/// XINFO GROUPS reply: one entry per group, each a flat field/value array (RESP2) or a
+/// map (RESP3). Fields other than name/pending/lag are ignored.
+pub(crate) fn parse_xinfo_groups(v: &Value) -> AppResult<Vec<GroupInfo>> {
+    let Value::Array(entries) = v else {
+        return Err(AppError::RedisLogic(format!(
+            "XINFO GROUPS returned unexpected value: {v:?}"
+        )));
+    };
+
+    entries
+        .iter()
+        .map(|entry| {
+            let pairs: Vec<(&Value, &Value)> = match entry {
+                Value::Map(m) => m.iter().map(|(k, v)| (k, v)).collect(),
+                Value::Array(flat) => flat.chunks_exact(2).map(|p| (&p[0], &p[1])).collect(),
+                other => {
+                    return Err(AppError::RedisLogic(format!(
+                        "XINFO GROUPS entry is not a map: {other:?}"
+                    )));
+                }
+            };
+
+            let mut name = None;
+            let mut pending = None;
+            let mut lag = None;
+            for (k, v) in pairs {
+                match value_str(k).as_deref() {
+                    Some("name") => name = value_str(v),
+                    Some("pending") => pending = value_u64(v),
+                    Some("lag") => lag = value_u64(v),
+                    _ => {}
+                }
+            }
+
+            Ok(GroupInfo {
+                name: name.ok_or_else(|| {
+                    AppError::RedisLogic("XINFO GROUPS entry without name".into())
+                })?,
+                pending: pending.unwrap_or(0),
+                lag,
+            })
+        })
+        .collect()
+}
+
+fn value_str(v: &Value) -> Option<String> {
+    match v {
+        Value::BulkString(b) => Some(String::from_utf8_lossy(b).into_owned()),
+        Value::SimpleString(s) => Some(s.clone()),
+        _ => None,
+    }
+}
+
+fn value_u64(v: &Value) -> Option<u64> {
+    match v {
+        Value::Int(n) => u64::try_from(*n).ok(),
+        // `lag` is nil when Redis cannot compute it
+        _ => value_str(v)?.parse().ok(),
+    }
 }
 
 // ------------------------------------------------------------
-// RedisStreamPublisher implementation (XADD)
+// RedisStreamPublisher implementation (XADD + PUBLISH)
 // ------------------------------------------------------------
 #[async_trait]
 impl RedisStreamPublisher for RedisClient {
@@ -151,6 +252,19 @@ impl RedisStreamPublisher for RedisClient {
 
         self.cmd_string(&cmd).await
     }
+
+    async fn publish_channel(&self, channel: &str, payload: &str) -> AppResult<u64> {
+        // PUBLISH channel message -> number of receiving subscribers
+        let mut cmd = redis::cmd("PUBLISH");
+        cmd.arg(channel).arg(payload);
+
+        match self.cmd_value(cmd).await? {
+            Value::Int(n) => Ok(n.max(0) as u64),
+            other => Err(AppError::RedisLogic(format!(
+                "PUBLISH returned unexpected value: {other:?}"
+            ))),
+        }
+    }
 }
 
 // ------------------------------------------------------------
@@ -163,7 +277,23 @@ struct RedisMemoryInfo {
     used_memory_pct: Option<f64>,
 }
 
+fn check_pong(pong: &str) -> AppResult<()> {
+    if pong.trim() == "PONG" {
+        Ok(())
+    } else {
+        Err(AppError::RedisLogic(format!("PING returned '{pong}'")))
+    }
+}
+
 impl RedisMemoryInfo {
+    fn as_tuple(&self) -> (u64, Option<u64>, Option<f64>) {
+        (
+            self.used_memory_bytes,
+            self.maxmemory_bytes,
+            self.used_memory_pct,
+        )
+    }
+
     fn parse(info_memory: &str) -> Self {
         // INFO memory is "key:value" lines.
         // We care about:
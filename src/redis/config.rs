@@ -1,5 +1,8 @@
+use crate::app::config::CheckMode;
 use crate::error::{AppError, AppResult};
+use crate::redis::streams::StreamKind;
 use serde::Deserialize;
+use std::collections::BTreeMap;
 use std::io::ErrorKind;
 use std::{collections::HashMap, fs, path::Path, path::PathBuf};
 
@@ -41,6 +44,19 @@ pub struct ConnectionConfig {
     pub command_timeout_ms: u64,
     pub keepalive_sec: u64,
     pub tcp_nodelay: bool,
+
+    /// Open a second connection used only by health probes (PING / INFO),
+    /// so slow probes never queue behind XADD publishes (and vice versa).
+    #[serde(default)]
+    pub dedicated_probe_connection: bool,
+
+    /// On shutdown, wait this long for in-flight publishes before dropping the connection.
+    #[serde(default = "default_shutdown_drain_timeout_ms")]
+    pub shutdown_drain_timeout_ms: u64,
+}
+
+fn default_shutdown_drain_timeout_ms() -> u64 {
+    2000
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -59,6 +75,36 @@ pub struct CapacityConfig {
     /// Rolling p99 command latency threshold (ms). App-defined.
     pub max_p99_cmd_ms: u64,
     pub redis_publish_latency_window: u64,
+
+    /// Stream keys probed (XINFO GROUPS) per poll for consumer group lag (0 = off).
+    #[serde(default = "default_group_lag_max_keys")]
+    pub group_lag_max_keys: usize,
+
+    /// Publish audit (XLEN vs published count, see `PublishAudit`): every N polls (0 = off).
+    #[serde(default)]
+    pub publish_audit_every_polls: u32,
+    /// Stream keys sampled per audit.
+    #[serde(default = "default_publish_audit_max_keys")]
+    pub publish_audit_max_keys: usize,
+    /// Share of the expected stream length that may be missing before it counts as a mismatch.
+    #[serde(default = "default_publish_audit_tolerance")]
+    pub publish_audit_tolerance: f64,
+
+    /// Send PING and INFO memory as one pipelined round trip per poll instead of two.
+    #[serde(default)]
+    pub pipeline_probes: bool,
+}
+
+fn default_group_lag_max_keys() -> usize {
+    32
+}
+
+fn default_publish_audit_max_keys() -> usize {
+    4
+}
+
+fn default_publish_audit_tolerance() -> f64 {
+    0.1
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -94,14 +140,46 @@ pub struct StreamsConfig {
     pub publish_liquidations: bool,
     pub publish_funding: bool,
     pub publish_open_interest: bool,
+
+    /// Also PUBLISH every event (JSON object of the stream fields) to a pub/sub channel named
+    /// like its stream key. Fire-and-forget: not counted in stream latency/health.
+    #[serde(default)]
+    pub also_publish_pubsub: bool,
+
+    /// Startup check that Redis keys and DB tables spell exchanges alike (see `check_sink_naming`).
+    #[serde(default)]
+    pub naming_check: CheckMode,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct RetentionConfig {
-    pub maxlen: u64,
+    pub maxlen: RetentionMaxLen,
     pub approx: bool,
 }
 
+/// XADD MAXLEN: one cap for every stream (`maxlen = 5_000`), or one per `StreamKind`
+/// (`maxlen = { default = 5_000, depth = 50_000 }`); kinds not listed use `default`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum RetentionMaxLen {
+    All(u64),
+    PerKind(BTreeMap<String, u64>),
+}
+
+impl RetentionConfig {
+    /// MAXLEN of the streams of `kind` (validated: every kind resolves to a cap).
+    pub fn maxlen_for(&self, kind: StreamKind) -> u64 {
+        match &self.maxlen {
+            RetentionMaxLen::All(maxlen) => *maxlen,
+            RetentionMaxLen::PerKind(caps) => caps
+                .get(kind.as_str())
+                .or_else(|| caps.get("default"))
+                .copied()
+                .unwrap_or(0),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct GroupsConfig {
     pub feature_builder: String,
@@ -111,6 +189,13 @@ pub struct GroupsConfig {
     pub ml_infer: Option<String>,
 }
 
+impl GroupsConfig {
+    /// Configured group names.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.feature_builder.as_str()).chain(self.ml_infer.as_deref())
+    }
+}
+
 impl RedisConfig {
     pub fn load_from_file(path: impl AsRef<Path>) -> AppResult<Self> {
         let path = path.as_ref();
@@ -364,6 +449,11 @@ impl RedisConfig {
                 "redis.toml: capacity.redis_publish_latency_window must be >= 100".into(),
             ));
         }
+        if !(0.0..1.0).contains(&self.capacity.publish_audit_tolerance) {
+            return Err(AppError::InvalidConfig(
+                "redis.toml: capacity.publish_audit_tolerance must be in [0, 1)".into(),
+            ));
+        }
 
         // streams
         let fmt = self.streams.key_format.trim();
@@ -381,10 +471,40 @@ impl RedisConfig {
         }
 
         // retention
-        if self.retention.maxlen == 0 {
-            return Err(AppError::InvalidConfig(
-                "redis.toml: retention.maxlen must be > 0".into(),
-            ));
+        match &self.retention.maxlen {
+            RetentionMaxLen::All(0) => {
+                return Err(AppError::InvalidConfig(
+                    "redis.toml: retention.maxlen must be > 0".into(),
+                ));
+            }
+            RetentionMaxLen::All(_) => {}
+            RetentionMaxLen::PerKind(caps) => {
+                for (kind, maxlen) in caps {
+                    if kind != "default" && !StreamKind::ALL.iter().any(|k| k.as_str() == kind) {
+                        let known: Vec<&str> = StreamKind::ALL.iter().map(|k| k.as_str()).collect();
+                        return Err(AppError::InvalidConfig(format!(
+                            "redis.toml: retention.maxlen: unknown stream kind '{kind}' (expected default, {})",
+                            known.join(", ")
+                        )));
+                    }
+                    if *maxlen == 0 {
+                        return Err(AppError::InvalidConfig(format!(
+                            "redis.toml: retention.maxlen.{kind} must be > 0"
+                        )));
+                    }
+                }
+                let uncapped: Vec<&str> = StreamKind::ALL
+                    .iter()
+                    .filter(|k| self.retention.maxlen_for(**k) == 0)
+                    .map(|k| k.as_str())
+                    .collect();
+                if !uncapped.is_empty() {
+                    return Err(AppError::InvalidConfig(format!(
+                        "redis.toml: retention.maxlen needs a `default` for kinds not listed ({})",
+                        uncapped.join(", ")
+                    )));
+                }
+            }
         }
 
         // groups (documentation-only, but still validate basic sanity)
@@ -489,4 +609,50 @@ mod tests {
         println!("Feature builder group: {}", cfg.groups.feature_builder);
         println!("ML infer group: {:?}", cfg.groups.ml_infer);
     }
+
+    #[test]
+    fn retention_maxlen_per_kind_falls_back_to_default() {
+        let mut cfg = RedisConfig::load_default().expect("failed to load redis.toml");
+        let mut set = |maxlen: &str| {
+            cfg.retention = toml::from_str(&format!("maxlen = {maxlen}\napprox = true")).unwrap();
+            cfg.validate().map(|()| cfg.retention.clone())
+        };
+
+        // A scalar caps every kind (the old layout)
+        let retention = set("5_000").unwrap();
+        assert_eq!(retention.maxlen, RetentionMaxLen::All(5_000));
+        assert!(
+            StreamKind::ALL
+                .iter()
+                .all(|k| retention.maxlen_for(*k) == 5_000)
+        );
+
+        let retention = set("{ default = 5_000, depth = 50_000, funding = 500 }").unwrap();
+        assert_eq!(retention.maxlen_for(StreamKind::Depth), 50_000);
+        assert_eq!(retention.maxlen_for(StreamKind::Funding), 500);
+        assert_eq!(retention.maxlen_for(StreamKind::Trades), 5_000);
+
+        // Every kind listed: no default needed
+        let all = "{ trades = 1, depth = 2, liquidations = 3, funding = 4, open_interest = 5 }";
+        assert_eq!(set(all).unwrap().maxlen_for(StreamKind::OpenInterest), 5);
+
+        for (maxlen, why) in [
+            (
+                "{ default = 5_000, l2book = 50_000 }",
+                "unknown stream kind 'l2book'",
+            ),
+            (
+                "{ depth = 50_000 }",
+                "needs a `default` for kinds not listed (trades,",
+            ),
+            (
+                "{ default = 5_000, depth = 0 }",
+                "retention.maxlen.depth must be > 0",
+            ),
+            ("0", "retention.maxlen must be > 0"),
+        ] {
+            let err = set(maxlen).unwrap_err();
+            assert!(format!("{err:?}").contains(why), "{maxlen}: {err:?}");
+        }
+    }
 }
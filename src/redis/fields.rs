@@ -1,3 +1,6 @@
+use crate::db::rows::{
+    DepthDeltaDBRow, FundingDBRow, LiquidationDBRow, OpenInterestDBRow, TradeDBRow,
+};
 use crate::ingest::datamap::event::{
     DepthDeltaRow, FundingRow, LiquidationRow, MarketEvent, OpenInterestRow, TradeRow,
 };
@@ -149,6 +152,121 @@ impl ToRedisPublish for LiquidationRow {
     }
 }
 
+// ------------------------------------------------------------
+// Committed DB rows (writer.publish_persisted_only)
+// ------------------------------------------------------------
+
+/// DB rows published after their batch commits. The exchange comes from the batch key;
+/// fields match the event-row impls above, so consumers see one format either way.
+pub trait PersistedRedisPublish {
+    fn redis_kind(&self) -> StreamKind;
+    fn redis_symbol(&self) -> &str;
+    fn redis_fields(&self) -> RedisFields;
+}
+
+impl PersistedRedisPublish for TradeDBRow {
+    fn redis_kind(&self) -> StreamKind {
+        StreamKind::Trades
+    }
+    fn redis_symbol(&self) -> &str {
+        &self.symbol
+    }
+    fn redis_fields(&self) -> RedisFields {
+        vec![
+            ("time", self.time.to_rfc3339()),
+            ("side", self.side.to_string()),
+            ("price_i", self.price_i.to_string()),
+            ("qty_i", self.qty_i.to_string()),
+            (
+                "trade_id",
+                self.trade_id.map(|x| x.to_string()).unwrap_or_default(),
+            ),
+            (
+                "is_maker",
+                self.is_maker.map(|x| x.to_string()).unwrap_or_default(),
+            ),
+        ]
+    }
+}
+
+impl PersistedRedisPublish for DepthDeltaDBRow {
+    fn redis_kind(&self) -> StreamKind {
+        StreamKind::Depth
+    }
+    fn redis_symbol(&self) -> &str {
+        &self.symbol
+    }
+    fn redis_fields(&self) -> RedisFields {
+        vec![
+            ("time", self.time.to_rfc3339()),
+            ("side", self.side.to_string()),
+            ("price_i", self.price_i.to_string()),
+            ("size_i", self.size_i.to_string()),
+            ("seq", self.seq.map(|x| x.to_string()).unwrap_or_default()),
+        ]
+    }
+}
+
+impl PersistedRedisPublish for OpenInterestDBRow {
+    fn redis_kind(&self) -> StreamKind {
+        StreamKind::OpenInterest
+    }
+    fn redis_symbol(&self) -> &str {
+        &self.symbol
+    }
+    fn redis_fields(&self) -> RedisFields {
+        vec![
+            ("time", self.time.to_rfc3339()),
+            ("oi_i", self.oi_i.to_string()),
+        ]
+    }
+}
+
+impl PersistedRedisPublish for FundingDBRow {
+    fn redis_kind(&self) -> StreamKind {
+        StreamKind::Funding
+    }
+    fn redis_symbol(&self) -> &str {
+        &self.symbol
+    }
+    fn redis_fields(&self) -> RedisFields {
+        vec![
+            ("time", self.time.to_rfc3339()),
+            ("funding_rate", self.funding_rate.to_string()),
+            (
+                "funding_time",
+                self.funding_time
+                    .map(|t| t.to_rfc3339())
+                    .unwrap_or_default(),
+            ),
+        ]
+    }
+}
+
+impl PersistedRedisPublish for LiquidationDBRow {
+    fn redis_kind(&self) -> StreamKind {
+        StreamKind::Liquidations
+    }
+    fn redis_symbol(&self) -> &str {
+        &self.symbol
+    }
+    fn redis_fields(&self) -> RedisFields {
+        vec![
+            ("time", self.time.to_rfc3339()),
+            ("side", self.side.to_string()),
+            (
+                "price_i",
+                self.price_i.map(|x| x.to_string()).unwrap_or_default(),
+            ),
+            ("qty_i", self.qty_i.to_string()),
+            (
+                "liq_id",
+                self.liq_id.map(|x| x.to_string()).unwrap_or_default(),
+            ),
+        ]
+    }
+}
+
 // ------------------------------------------------------------
 // Optional thing: MarketEvent -> Option<(kind, ex, sym, fields)>
 // ------------------------------------------------------------
@@ -0,0 +1,190 @@
+// src/redis/health/audit.rs
+
+use crate::redis::config::RedisConfig;
+use std::collections::BTreeMap;
+
+/// Publish audit: catches data Redis silently discards although every XADD succeeded
+/// (a MAXLEN far below the key's `retention.maxlen` applied by someone else, writes landing
+/// on another node, a consumer deleting entries).
+///
+/// Every `every_polls` health polls, up to `max_keys` stream keys (round-robin) get an XLEN.
+/// Between two samples of a key the stream must have grown by what was published to it,
+/// up to the retention cap of its kind: `XLEN >= min(previous XLEN + published, maxlen)`
+/// (approximate trimming only ever keeps more). A key more than `tolerance` (share of the
+/// expected length) short counts as a mismatch.
+#[derive(Debug)]
+pub struct PublishAudit {
+    every_polls: u32,
+    max_keys: usize,
+    tolerance: f64,
+    polls: u32,
+    // index into the key list of the next key to sample
+    cursor: usize,
+    keys: BTreeMap<String, KeyAudit>,
+}
+
+#[derive(Debug, Default)]
+struct KeyAudit {
+    // successful XADDs since the key was last sampled
+    published: u64,
+    // XLEN at the last sample (None = no baseline yet)
+    last_len: Option<u64>,
+    // MAXLEN the key is published with
+    maxlen: u64,
+}
+
+/// A key that holds fewer entries than published to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AuditMismatch {
+    pub expected_min: u64,
+    pub observed: u64,
+}
+
+impl PublishAudit {
+    /// `every_polls` or `max_keys` of 0 disables the audit.
+    pub fn new(every_polls: u32, max_keys: usize, tolerance: f64) -> Self {
+        Self {
+            every_polls,
+            max_keys,
+            tolerance: tolerance.clamp(0.0, 1.0),
+            polls: 0,
+            cursor: 0,
+            keys: BTreeMap::new(),
+        }
+    }
+
+    pub fn from_config(cfg: &RedisConfig) -> Self {
+        Self::new(
+            cfg.capacity.publish_audit_every_polls,
+            cfg.capacity.publish_audit_max_keys,
+            cfg.capacity.publish_audit_tolerance,
+        )
+    }
+
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.every_polls > 0 && self.max_keys > 0
+    }
+
+    /// One successful XADD to `key` with MAXLEN `maxlen`.
+    pub fn on_published(&mut self, key: &str, maxlen: u64) {
+        match self.keys.get_mut(key) {
+            Some(k) => {
+                k.published += 1;
+                k.maxlen = maxlen;
+            }
+            None => {
+                self.keys.insert(
+                    key.to_string(),
+                    KeyAudit {
+                        published: 1,
+                        last_len: None,
+                        maxlen,
+                    },
+                );
+            }
+        }
+    }
+
+    /// Called once per health poll: keys to sample now (empty between audit polls).
+    pub fn next_keys(&mut self) -> Vec<String> {
+        if !self.is_enabled() || self.keys.is_empty() {
+            return Vec::new();
+        }
+        self.polls = self.polls.wrapping_add(1);
+        if !self.polls.is_multiple_of(self.every_polls) {
+            return Vec::new();
+        }
+        let len = self.keys.len();
+        let n = self.max_keys.min(len);
+        let start = self.cursor % len;
+        self.cursor = (start + n) % len;
+        self.keys
+            .keys()
+            .cycle()
+            .skip(start)
+            .take(n)
+            .cloned()
+            .collect()
+    }
+
+    /// XLEN of `key` sampled just now; Some if it holds fewer entries than expected.
+    pub fn record(&mut self, key: &str, xlen: u64) -> Option<AuditMismatch> {
+        let k = self.keys.get_mut(key)?;
+        let published = std::mem::take(&mut k.published);
+        let last = k.last_len.replace(xlen)?;
+
+        let expected_min = last.saturating_add(published).min(k.maxlen);
+        let allowed_short = (expected_min as f64 * self.tolerance).floor() as u64;
+        (xlen.saturating_add(allowed_short) < expected_min).then_some(AuditMismatch {
+            expected_min,
+            observed: xlen,
+        })
+    }
+
+    /// XLEN of `key` failed: the publishes counted meanwhile can't be checked, start over.
+    pub fn forget(&mut self, key: &str) {
+        if let Some(k) = self.keys.get_mut(key) {
+            *k = KeyAudit {
+                maxlen: k.maxlen,
+                ..KeyAudit::default()
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn growth_below_published_is_a_mismatch() {
+        let key = "stream:binance_linear:BTCUSDT:trades";
+        let mut audit = PublishAudit::new(2, 4, 0.1);
+
+        // Audit polls only every 2nd health poll
+        audit.on_published(key, 1000);
+        assert!(audit.next_keys().is_empty());
+        assert_eq!(audit.next_keys(), [key]);
+        // First sample is the baseline
+        assert_eq!(audit.record(key, 1), None);
+
+        // 100 more published, all there (or within tolerance)
+        (0..100).for_each(|_| audit.on_published(key, 1000));
+        assert_eq!(audit.record(key, 101), None);
+        (0..100).for_each(|_| audit.on_published(key, 1000));
+        assert_eq!(audit.record(key, 185), None);
+
+        // Retention: a full stream stays at maxlen
+        (0..5000).for_each(|_| audit.on_published(key, 1000));
+        assert_eq!(audit.record(key, 1000), None);
+
+        // Entries vanished
+        (0..50).for_each(|_| audit.on_published(key, 1000));
+        assert_eq!(
+            audit.record(key, 3),
+            Some(AuditMismatch {
+                expected_min: 1000,
+                observed: 3
+            })
+        );
+
+        // Each key is held to its own cap (per-kind retention)
+        let depth = "stream:binance_linear:BTCUSDT:depth";
+        audit.on_published(depth, 50_000);
+        assert_eq!(audit.record(depth, 1), None);
+        (0..5000).for_each(|_| audit.on_published(depth, 50_000));
+        assert_eq!(
+            audit.record(depth, 1000),
+            Some(AuditMismatch {
+                expected_min: 5001,
+                observed: 1000
+            })
+        );
+
+        // Unknown keys are never a mismatch; a failed XLEN resets the baseline
+        assert_eq!(audit.record("stream:other", 0), None);
+        audit.forget(key);
+        assert_eq!(audit.record(key, 0), None);
+    }
+}
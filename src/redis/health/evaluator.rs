@@ -65,6 +65,11 @@ mod tests {
             max_pending: 200_000,
             max_p99_cmd_ms: 10,
             redis_publish_latency_window: 2048,
+            group_lag_max_keys: 32,
+            publish_audit_every_polls: 0,
+            publish_audit_max_keys: 4,
+            publish_audit_tolerance: 0.1,
+            pipeline_probes: false,
         }
     }
 
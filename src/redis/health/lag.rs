@@ -0,0 +1,121 @@
+// src/redis/health/lag.rs
+
+use crate::redis::config::RedisConfig;
+use crate::redis::health::types::GroupInfo;
+use std::collections::{HashMap, HashSet};
+
+/// Consumer lag per configured group (`[groups]` in redis.toml), for a dashboard showing
+/// whether a consumer falls behind steadily or only transiently.
+///
+/// Each health poll probes at most `max_keys` stream keys (XINFO GROUPS), continuing from a
+/// cursor kept between polls, so every key is visited in turn however many streams exist.
+/// The per-group figure sums the latest sample of every key.
+#[derive(Debug)]
+pub struct GroupLagSampler {
+    groups: Vec<String>,
+    max_keys: usize,
+    // index into the key list of the next key to probe
+    cursor: usize,
+    // key -> (group -> pending), latest sample
+    seen: HashMap<String, HashMap<String, u64>>,
+}
+
+impl GroupLagSampler {
+    /// `max_keys` of 0 disables sampling.
+    pub fn new(groups: Vec<String>, max_keys: usize) -> Self {
+        Self {
+            groups,
+            max_keys,
+            cursor: 0,
+            seen: HashMap::new(),
+        }
+    }
+
+    pub fn from_config(cfg: &RedisConfig) -> Self {
+        Self::new(
+            cfg.groups.names().map(str::to_string).collect(),
+            cfg.capacity.group_lag_max_keys,
+        )
+    }
+
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.max_keys > 0 && !self.groups.is_empty()
+    }
+
+    /// Keys to probe this poll, continuing where the previous poll stopped.
+    /// Keys no longer in `keys` are forgotten.
+    pub fn next_keys(&mut self, keys: &[String]) -> Vec<String> {
+        let live: HashSet<&str> = keys.iter().map(String::as_str).collect();
+        self.seen.retain(|k, _| live.contains(k.as_str()));
+
+        if keys.is_empty() || !self.is_enabled() {
+            return Vec::new();
+        }
+        let n = self.max_keys.min(keys.len());
+        let start = self.cursor % keys.len();
+        self.cursor = (start + n) % keys.len();
+        (0..n)
+            .map(|i| keys[(start + i) % keys.len()].clone())
+            .collect()
+    }
+
+    /// Latest XINFO GROUPS sample of `key` (groups not configured are ignored).
+    pub fn record(&mut self, key: &str, groups: &[GroupInfo]) {
+        let pending = groups
+            .iter()
+            .filter(|g| self.groups.contains(&g.name))
+            .map(|g| (g.name.clone(), g.pending))
+            .collect();
+        self.seen.insert(key.to_string(), pending);
+    }
+
+    /// Pending entries per configured group, over all keys sampled so far.
+    pub fn pending_by_group(&self) -> Vec<(&str, u64)> {
+        self.groups
+            .iter()
+            .map(|g| {
+                let total = self.seen.values().filter_map(|by| by.get(g)).sum();
+                (g.as_str(), total)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(name: &str, pending: u64) -> GroupInfo {
+        GroupInfo {
+            name: name.into(),
+            pending,
+            lag: None,
+        }
+    }
+
+    #[test]
+    fn cursor_visits_every_key_within_the_bound() {
+        let keys: Vec<String> = (0..5).map(|i| format!("stream:k{i}")).collect();
+        let mut s = GroupLagSampler::new(vec!["cg:features".into()], 2);
+
+        assert_eq!(s.next_keys(&keys), ["stream:k0", "stream:k1"]);
+        assert_eq!(s.next_keys(&keys), ["stream:k2", "stream:k3"]);
+        assert_eq!(s.next_keys(&keys), ["stream:k4", "stream:k0"]);
+
+        for k in &keys {
+            s.record(k, &[group("cg:features", 10), group("cg:other", 99)]);
+        }
+        assert_eq!(s.pending_by_group(), [("cg:features", 50)]);
+
+        // A key that went away stops counting
+        s.next_keys(&keys[1..]);
+        assert_eq!(s.pending_by_group(), [("cg:features", 40)]);
+
+        assert!(
+            GroupLagSampler::new(vec!["cg:features".into()], 0)
+                .next_keys(&keys)
+                .is_empty()
+        );
+    }
+}
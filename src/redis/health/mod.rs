@@ -1,7 +1,11 @@
+pub mod audit;
 pub mod evaluator;
+pub mod lag;
 pub mod poller;
 pub mod types;
 
+pub use audit::*;
 pub use evaluator::*;
+pub use lag::*;
 pub use poller::*;
 pub use types::*;
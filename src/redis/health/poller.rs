@@ -2,7 +2,7 @@
 
 use crate::error::AppResult;
 use crate::redis::config::CapacityConfig;
-use crate::redis::health::types::RedisSnapshot;
+use crate::redis::health::types::{GroupInfo, RedisSnapshot};
 use async_trait::async_trait;
 use std::time::{Duration, Instant, SystemTime};
 
@@ -16,8 +16,23 @@ pub trait RedisProbe: Send + Sync {
     /// used_pct should be None if maxmemory is not configured/known.
     async fn memory_info(&self) -> AppResult<(u64, Option<u64>, Option<f64>)>;
 
+    /// `ping` then `memory_info`: the outer error is the ping, the inner one the memory read.
+    /// Used with `capacity.pipeline_probes`; implementations may send both in one round trip.
+    async fn ping_and_memory_info(&self) -> AppResult<AppResult<(u64, Option<u64>, Option<f64>)>> {
+        self.ping().await?;
+        Ok(self.memory_info().await)
+    }
+
     /// App-defined backlog metric.
     async fn pending_total(&self) -> AppResult<u64>;
+
+    /// Consumer groups of stream `key` (XINFO GROUPS). A missing key has none.
+    async fn xinfo_groups(&self, _key: &str) -> AppResult<Vec<GroupInfo>> {
+        Ok(Vec::new())
+    }
+
+    /// Entries in stream `key` (XLEN). A missing key has none.
+    async fn xlen(&self, key: &str) -> AppResult<u64>;
 }
 
 /// Polls Redis periodically (caller controls scheduling).
@@ -25,12 +40,14 @@ pub trait RedisProbe: Send + Sync {
 #[derive(Debug, Clone)]
 pub struct HealthPoller {
     poll_interval: Duration,
+    pipeline_probes: bool,
 }
 
 impl HealthPoller {
     pub fn from_config(cap: &CapacityConfig) -> Self {
         Self {
             poll_interval: Duration::from_secs(cap.poll_interval_sec),
+            pipeline_probes: cap.pipeline_probes,
         }
     }
 
@@ -48,9 +65,16 @@ impl HealthPoller {
     ) -> RedisSnapshot {
         let ts = SystemTime::now();
 
-        // 1) Ping + RTT
+        // 1) Ping + RTT (pipelined: the RTT covers PING and INFO memory together)
         let t0 = Instant::now();
-        let ping_res = probe.ping().await;
+        let (ping_res, memory_res) = if self.pipeline_probes {
+            match probe.ping_and_memory_info().await {
+                Ok(mem) => (Ok(()), Some(mem)),
+                Err(e) => (Err(e), None),
+            }
+        } else {
+            (probe.ping().await, None)
+        };
         let ping_ok = ping_res.is_ok();
 
         let ping_rtt_ms = if ping_ok {
@@ -74,11 +98,14 @@ impl HealthPoller {
         }
 
         // 2) Memory info (best-effort)
-        let (used_memory_bytes, maxmemory_bytes, used_memory_pct) =
-            match probe.memory_info().await {
-                Ok((used, max, pct)) => (Some(used), max, pct),
-                Err(_) => (None, None, None),
-            };
+        let memory_res = match memory_res {
+            Some(res) => res,
+            None => probe.memory_info().await,
+        };
+        let (used_memory_bytes, maxmemory_bytes, used_memory_pct) = match memory_res {
+            Ok((used, max, pct)) => (Some(used), max, pct),
+            Err(_) => (None, None, None),
+        };
 
         // 3) Pending / backlog (best-effort)
         let pending_total = match probe.pending_total().await {
@@ -99,3 +126,70 @@ impl HealthPoller {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Counts round trips; the pipelined read answers PING + INFO memory in one.
+    #[derive(Default)]
+    struct FakeProbe {
+        round_trips: AtomicU32,
+    }
+
+    #[async_trait]
+    impl RedisProbe for FakeProbe {
+        async fn ping(&self) -> AppResult<()> {
+            self.round_trips.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        }
+
+        async fn memory_info(&self) -> AppResult<(u64, Option<u64>, Option<f64>)> {
+            self.round_trips.fetch_add(1, Ordering::Relaxed);
+            Ok((512, Some(1024), Some(50.0)))
+        }
+
+        async fn ping_and_memory_info(
+            &self,
+        ) -> AppResult<AppResult<(u64, Option<u64>, Option<f64>)>> {
+            self.round_trips.fetch_add(1, Ordering::Relaxed);
+            Ok(Ok((512, Some(1024), Some(50.0))))
+        }
+
+        async fn pending_total(&self) -> AppResult<u64> {
+            Ok(7)
+        }
+
+        async fn xlen(&self, _key: &str) -> AppResult<u64> {
+            Ok(0)
+        }
+    }
+
+    fn poller(pipeline_probes: bool) -> HealthPoller {
+        HealthPoller {
+            poll_interval: Duration::from_secs(2),
+            pipeline_probes,
+        }
+    }
+
+    #[tokio::test]
+    async fn pipelined_probe_matches_sequential_snapshot() {
+        let sequential_io = FakeProbe::default();
+        let pipelined_io = FakeProbe::default();
+
+        let seq = poller(false).poll_once(&sequential_io, Some(3.0)).await;
+        let pip = poller(true).poll_once(&pipelined_io, Some(3.0)).await;
+
+        assert_eq!(sequential_io.round_trips.load(Ordering::Relaxed), 2);
+        assert_eq!(pipelined_io.round_trips.load(Ordering::Relaxed), 1);
+
+        assert!(seq.is_up && pip.is_up);
+        assert!(seq.ping_rtt_ms.is_some() && pip.ping_rtt_ms.is_some());
+        assert_eq!(seq.used_memory_bytes, pip.used_memory_bytes);
+        assert_eq!(seq.maxmemory_bytes, pip.maxmemory_bytes);
+        assert_eq!(seq.used_memory_pct, pip.used_memory_pct);
+        assert_eq!(seq.pending_total, pip.pending_total);
+        assert_eq!(seq.p99_cmd_ms, pip.p99_cmd_ms);
+        assert_eq!(pip.used_memory_bytes, Some(512));
+    }
+}
@@ -87,6 +87,16 @@ impl RedisSnapshot {
     }
 }
 
+/// One consumer group of a stream (an XINFO GROUPS entry).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupInfo {
+    pub name: String,
+    /// Delivered to a consumer but not acknowledged yet.
+    pub pending: u64,
+    /// Entries not delivered to the group yet (Redis >= 7, None when unknown).
+    pub lag: Option<u64>,
+}
+
 /// Evaluated health status: "should we use Redis right now?"
 #[derive(Debug, Clone)]
 pub struct HealthStatus {
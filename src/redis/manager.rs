@@ -1,21 +1,27 @@
 // src/redis/manager.rs
 
 use crate::error::AppResult;
+use crate::ingest::config::SymbolCase;
 use crate::redis::config::RedisConfig;
 use crate::redis::gate::RedisGate;
+use crate::redis::health::audit::PublishAudit;
 use crate::redis::health::evaluator::HealthEvaluator;
+use crate::redis::health::lag::GroupLagSampler;
 use crate::redis::health::poller::{HealthPoller, RedisProbe};
 use crate::redis::health::types::HealthStatus;
 use crate::redis::latency::RedisPublishLatency;
 use crate::redis::metrics::RedisMetrics;
 use crate::redis::streams::{StreamKeyBuilder, StreamKind};
+use crate::telemetry::throttle::log_throttle;
 
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::Instant;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
 use tokio::time::sleep;
 
-/// Minimal interface needed to publish to Redis Streams.
+/// Minimal interface needed to publish to Redis Streams (and optionally pub/sub).
 /// Your future Redis client will implement this.
 #[async_trait::async_trait]
 pub trait RedisStreamPublisher: Send + Sync {
@@ -31,6 +37,9 @@ pub trait RedisStreamPublisher: Send + Sync {
         approx: bool,
         fields: &[(&str, &str)],
     ) -> AppResult<String>;
+
+    /// Fire-and-forget PUBLISH; returns the number of subscribers that received it.
+    async fn publish_channel(&self, channel: &str, payload: &str) -> AppResult<u64>;
 }
 
 /// Result of calling publish: we never want Redis to be “hard required”.
@@ -63,6 +72,13 @@ pub struct RedisManager<T> {
     evaluator: HealthEvaluator,
     gate: Arc<RedisGate>,
 
+    // Consumer group lag: keys published to so far + sampling cursor
+    stream_keys: Mutex<BTreeSet<String>>,
+    group_lag: Mutex<GroupLagSampler>,
+
+    // Publish audit (XLEN vs published); None when off
+    publish_audit: Option<Mutex<PublishAudit>>,
+
     // Metrics + latency
     metrics: RedisMetrics,
     latency: Arc<RedisPublishLatency>,
@@ -70,9 +86,31 @@ pub struct RedisManager<T> {
     // Redis I/O object (future RedisClient)
     io: Arc<T>,
 
+    // Optional dedicated I/O for health probes (falls back to `io`)
+    probe_io: Option<Arc<T>>,
+
     // Producer-side assignment:
     // If a symbol is assigned, we attempt Redis publishing for it (subject to gate.can_publish()).
     assigned_symbols: Mutex<HashSet<(String, String)>>, // (exchange, symbol)
+
+    // Shutdown drain: `shutdown` stops new publishes and awaits the in-flight ones
+    accepting: AtomicBool,
+    in_flight: AtomicUsize,
+    drained: Notify,
+}
+
+/// Marks one publish as in flight; the last one out wakes `shutdown`.
+struct InFlightPublish<'a> {
+    count: &'a AtomicUsize,
+    drained: &'a Notify,
+}
+
+impl Drop for InFlightPublish<'_> {
+    fn drop(&mut self) {
+        if self.count.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.drained.notify_waiters();
+        }
+    }
 }
 
 impl<T> RedisManager<T>
@@ -93,19 +131,60 @@ where
         // Gate
         let gate = Arc::new(RedisGate::new(cfg.failover.clone(), metrics.clone()));
 
+        let group_lag = Mutex::new(GroupLagSampler::from_config(&cfg));
+        let publish_audit = Some(PublishAudit::from_config(&cfg))
+            .filter(PublishAudit::is_enabled)
+            .map(Mutex::new);
+
         Ok(Self {
             cfg,
             keys,
             poller,
             evaluator,
             gate,
+            stream_keys: Mutex::new(BTreeSet::new()),
+            group_lag,
+            publish_audit,
             metrics,
             latency,
             io,
+            probe_io: None,
             assigned_symbols: Mutex::new(HashSet::new()),
+            accepting: AtomicBool::new(true),
+            in_flight: AtomicUsize::new(0),
+            drained: Notify::new(),
         })
     }
 
+    /// Use a separate connection for health probing, so polling never competes
+    /// with the publish path.
+    pub fn with_probe_io(mut self, probe_io: Arc<T>) -> Self {
+        self.probe_io = Some(probe_io);
+        self
+    }
+
+    #[inline]
+    pub fn has_dedicated_probe_io(&self) -> bool {
+        self.probe_io.is_some()
+    }
+
+    /// I/O used by the health loop.
+    #[inline]
+    fn probe_io(&self) -> &T {
+        self.probe_io.as_deref().unwrap_or(self.io.as_ref())
+    }
+
+    /// Apply per-exchange symbol casing to stream keys and symbol onboarding.
+    pub fn with_symbol_cases<'a>(
+        mut self,
+        cases: impl IntoIterator<Item = (&'a str, SymbolCase)>,
+    ) -> Self {
+        for (exchange, case) in cases {
+            self.keys = self.keys.with_symbol_case(exchange, case);
+        }
+        self
+    }
+
     /// Start the health loop in a background task.
     ///
     /// Caller decides where to hold/join the task; this returns the JoinHandle.
@@ -130,7 +209,8 @@ where
                         let p99 = this.latency.p99_ms();
 
                         // 2) Poll backend
-                        let snap = this.poller.poll_once(this.io.as_ref(), p99).await;
+                        let snap = this.poller.poll_once(this.probe_io(), p99).await;
+                        let is_up = snap.is_up;
 
                         // 3) Evaluate thresholds
                         let status = this.evaluator.evaluate(snap);
@@ -138,6 +218,13 @@ where
                         // 4) Apply policy
                         this.gate.apply_health(&status);
 
+                        // 5) Consumer group lag + publish audit (best-effort, bounded
+                        //    number of keys)
+                        if is_up {
+                            this.sample_group_lag().await;
+                            this.audit_publishes().await;
+                        }
+
                         sleep(interval).await;
                     } => {}
                 }
@@ -145,6 +232,97 @@ where
         })
     }
 
+    /// Probe the next batch of stream keys (XINFO GROUPS) and update the per-group
+    /// pending gauges.
+    pub async fn sample_group_lag(&self) {
+        let batch = {
+            let keys: Vec<String> = self
+                .stream_keys
+                .lock()
+                .expect("stream_keys mutex poisoned")
+                .iter()
+                .cloned()
+                .collect();
+            let mut lag = self.group_lag.lock().expect("group_lag mutex poisoned");
+            if !lag.is_enabled() {
+                return;
+            }
+            lag.next_keys(&keys)
+        };
+
+        for key in batch {
+            match self.probe_io().xinfo_groups(&key).await {
+                Ok(groups) => self
+                    .group_lag
+                    .lock()
+                    .expect("group_lag mutex poisoned")
+                    .record(&key, &groups),
+                Err(e) => {
+                    if let Some(suppressed) = log_throttle().allow("redis xinfo groups failed") {
+                        tracing::warn!(
+                            component = "redis",
+                            stream = %key,
+                            suppressed,
+                            error = %e,
+                            "XINFO GROUPS failed"
+                        );
+                    }
+                }
+            }
+        }
+
+        let lag = self.group_lag.lock().expect("group_lag mutex poisoned");
+        for (group, pending) in lag.pending_by_group() {
+            self.metrics.set_group_pending(group, pending);
+        }
+    }
+
+    /// Publish audit poll: XLEN the keys due and count those short of what was published
+    /// to them (see `PublishAudit`).
+    pub async fn audit_publishes(&self) {
+        let Some(audit) = &self.publish_audit else {
+            return;
+        };
+        let batch = audit
+            .lock()
+            .expect("publish_audit mutex poisoned")
+            .next_keys();
+
+        for key in batch {
+            let res = self.probe_io().xlen(&key).await;
+            let mut audit = audit.lock().expect("publish_audit mutex poisoned");
+            match res {
+                Ok(xlen) => {
+                    if let Some(m) = audit.record(&key, xlen) {
+                        self.metrics.inc_publish_audit_mismatch();
+                        if let Some(suppressed) = log_throttle().allow("redis publish audit") {
+                            tracing::warn!(
+                                component = "redis",
+                                stream = %key,
+                                expected_min = m.expected_min,
+                                observed = m.observed,
+                                suppressed,
+                                "stream holds fewer entries than published (check MAXLEN / node)"
+                            );
+                        }
+                    }
+                }
+                Err(e) => {
+                    audit.forget(&key);
+                    if let Some(suppressed) = log_throttle().allow("redis xlen failed") {
+                        tracing::warn!(
+                            component = "redis",
+                            stream = %key,
+                            suppressed,
+                            error = %e,
+                            "XLEN failed"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
     /// Fast query helpers
     #[inline]
     pub fn can_publish(&self) -> bool {
@@ -207,6 +385,15 @@ where
             return Ok(PublishOutcome::Skipped);
         }
 
+        // Shutting down: no new publishes (held until this publish returns)
+        let Some(_in_flight) = self.begin_publish() else {
+            return Ok(PublishOutcome::Skipped);
+        };
+
+        // One canonical casing per symbol (same key + same assignment slot)
+        let symbol = self.keys.canonical_symbol(exchange, symbol);
+        let symbol = symbol.as_str();
+
         // Onboard symbol only if policy allows
         if !self.ensure_assigned(exchange, symbol) {
             // This is your "stop assigning new" behavior
@@ -220,7 +407,7 @@ where
 
         let stream_key = self.keys.key(exchange, symbol, kind);
 
-        let maxlen = self.cfg.retention.maxlen;
+        let maxlen = self.cfg.retention.maxlen_for(kind);
         let approx = self.cfg.retention.approx;
 
         // Measure publish latency
@@ -232,13 +419,38 @@ where
         self.latency.observe_ms(elapsed_ms);
         self.metrics.observe_publish_latency(elapsed_ms / 1000.0);
 
+        // Ephemeral consumers: same event on the pub/sub channel.
+        // Outside the latency window, so it never feeds health/gate decisions.
+        if self.cfg.streams.also_publish_pubsub {
+            self.publish_pubsub(&stream_key, fields).await;
+        }
+
         match res {
             Ok(_id) => {
                 self.metrics.inc_published(1);
+                if let Some(audit) = &self.publish_audit {
+                    audit
+                        .lock()
+                        .expect("publish_audit mutex poisoned")
+                        .on_published(&stream_key, maxlen);
+                }
+                let mut keys = self.stream_keys.lock().expect("stream_keys mutex poisoned");
+                if !keys.contains(&stream_key) {
+                    keys.insert(stream_key);
+                }
                 Ok(PublishOutcome::Published)
             }
-            Err(_e) => {
+            Err(e) => {
                 self.metrics.inc_publish_failure();
+                if let Some(suppressed) = log_throttle().allow("redis publish failed") {
+                    tracing::warn!(
+                        component = "redis",
+                        stream = %stream_key,
+                        suppressed,
+                        error = %e,
+                        "redis publish failed"
+                    );
+                }
 
                 // Optional: if you want to aggressively disable on repeated errors,
                 // do that in a separate module/window. For now: just record failure.
@@ -247,6 +459,91 @@ where
         }
     }
 
+    /// Register an in-flight publish, or None once `shutdown` has started.
+    fn begin_publish(&self) -> Option<InFlightPublish<'_>> {
+        // Count first, then check: `shutdown` either sees this publish or we see its flag
+        self.in_flight.fetch_add(1, Ordering::AcqRel);
+        let guard = InFlightPublish {
+            count: &self.in_flight,
+            drained: &self.drained,
+        };
+        self.accepting.load(Ordering::Acquire).then_some(guard)
+    }
+
+    /// Publishes currently awaiting Redis.
+    #[inline]
+    pub fn in_flight_publishes(&self) -> usize {
+        self.in_flight.load(Ordering::Acquire)
+    }
+
+    /// Drain for shutdown: stop accepting publishes, then await the in-flight ones
+    /// for up to `connection.shutdown_drain_timeout_ms`.
+    ///
+    /// Returns how many were still pending at the deadline (0 = drained cleanly).
+    pub async fn shutdown(&self) -> usize {
+        self.accepting.store(false, Ordering::Release);
+
+        let pending = self.in_flight_publishes();
+        if pending == 0 {
+            tracing::info!(
+                component = "redis",
+                "redis drained (no in-flight publishes)"
+            );
+            return 0;
+        }
+
+        let timeout = Duration::from_millis(self.cfg.connection.shutdown_drain_timeout_ms);
+        tracing::info!(
+            component = "redis",
+            pending,
+            timeout_ms = timeout.as_millis() as u64,
+            "redis draining in-flight publishes"
+        );
+
+        let drain = async {
+            loop {
+                let notified = self.drained.notified();
+                tokio::pin!(notified);
+                notified.as_mut().enable();
+                if self.in_flight_publishes() == 0 {
+                    break;
+                }
+                notified.await;
+            }
+        };
+        let _ = tokio::time::timeout(timeout, drain).await;
+
+        let abandoned = self.in_flight_publishes();
+        if abandoned == 0 {
+            tracing::info!(component = "redis", pending, "redis drained");
+        } else {
+            tracing::warn!(
+                component = "redis",
+                pending,
+                abandoned,
+                "redis drain timed out; in-flight publishes dropped"
+            );
+        }
+        abandoned
+    }
+
+    /// Best-effort PUBLISH of `fields` as a JSON object. Failures are only counted.
+    async fn publish_pubsub(&self, channel: &str, fields: &[(&str, &str)]) {
+        let payload: serde_json::Map<String, serde_json::Value> = fields
+            .iter()
+            .map(|(k, v)| ((*k).to_string(), serde_json::Value::from(*v)))
+            .collect();
+        let payload = serde_json::Value::Object(payload).to_string();
+
+        match self.io.publish_channel(channel, &payload).await {
+            Ok(_receivers) => self.metrics.inc_pubsub_published(),
+            Err(e) => {
+                self.metrics.inc_pubsub_failure();
+                tracing::debug!(channel, error = %e, "redis pub/sub publish failed");
+            }
+        }
+    }
+
     fn kind_enabled(&self, kind: StreamKind) -> bool {
         match kind {
             StreamKind::Trades => self.cfg.streams.publish_trades,
@@ -276,3 +573,383 @@ where
         set.clear();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::redis::health::types::GroupInfo;
+    use tokio_util::sync::CancellationToken;
+
+    /// Counts which commands hit this "connection".
+    #[derive(Debug, Default)]
+    struct CountingIo {
+        probes: AtomicUsize,
+        xadds: AtomicUsize,
+        published: Mutex<Vec<(String, String)>>,
+        xadd_delay_ms: u64,
+        xinfo_reply: Option<redis::Value>,
+        // Stream lengths as a server trimming at `server_maxlen` would report them
+        lens: Mutex<std::collections::HashMap<String, u64>>,
+        server_maxlen: Option<u64>,
+    }
+
+    #[async_trait::async_trait]
+    impl RedisProbe for CountingIo {
+        async fn ping(&self) -> AppResult<()> {
+            self.probes.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        }
+
+        async fn memory_info(&self) -> AppResult<(u64, Option<u64>, Option<f64>)> {
+            self.probes.fetch_add(1, Ordering::Relaxed);
+            Ok((0, None, None))
+        }
+
+        async fn pending_total(&self) -> AppResult<u64> {
+            Ok(0)
+        }
+
+        async fn xinfo_groups(&self, _key: &str) -> AppResult<Vec<GroupInfo>> {
+            match &self.xinfo_reply {
+                Some(v) => crate::redis::client::parse_xinfo_groups(v),
+                None => Ok(Vec::new()),
+            }
+        }
+
+        async fn xlen(&self, key: &str) -> AppResult<u64> {
+            Ok(self.lens.lock().unwrap().get(key).copied().unwrap_or(0))
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl RedisStreamPublisher for CountingIo {
+        async fn xadd(
+            &self,
+            stream_key: &str,
+            maxlen: u64,
+            _approx: bool,
+            _fields: &[(&str, &str)],
+        ) -> AppResult<String> {
+            if self.xadd_delay_ms > 0 {
+                tokio::time::sleep(std::time::Duration::from_millis(self.xadd_delay_ms)).await;
+            }
+            self.xadds.fetch_add(1, Ordering::Relaxed);
+            let cap = self.server_maxlen.unwrap_or(maxlen);
+            let mut lens = self.lens.lock().unwrap();
+            let len = lens.entry(stream_key.to_string()).or_insert(0);
+            *len = (*len + 1).min(cap);
+            Ok("0-1".into())
+        }
+
+        async fn publish_channel(&self, channel: &str, payload: &str) -> AppResult<u64> {
+            self.published
+                .lock()
+                .unwrap()
+                .push((channel.to_string(), payload.to_string()));
+            Ok(1)
+        }
+    }
+
+    /// XINFO GROUPS entry as Redis 7 sends it over RESP2 (flat field/value array).
+    fn xinfo_entry(name: &str, pending: i64, lag: i64) -> redis::Value {
+        use redis::Value::{Array, BulkString, Int};
+        Array(vec![
+            BulkString(b"name".to_vec()),
+            BulkString(name.as_bytes().to_vec()),
+            BulkString(b"consumers".to_vec()),
+            Int(1),
+            BulkString(b"pending".to_vec()),
+            Int(pending),
+            BulkString(b"last-delivered-id".to_vec()),
+            BulkString(b"1700000000000-0".to_vec()),
+            BulkString(b"entries-read".to_vec()),
+            Int(10),
+            BulkString(b"lag".to_vec()),
+            Int(lag),
+        ])
+    }
+
+    fn enabled_cfg() -> RedisConfig {
+        let mut cfg = RedisConfig::load_default().unwrap();
+        cfg.enabled = true;
+        cfg.streams.publish_trades = true;
+        cfg
+    }
+
+    #[tokio::test]
+    async fn health_probes_use_dedicated_connection() {
+        let publish_io = Arc::new(CountingIo::default());
+        let probe_io = Arc::new(CountingIo::default());
+
+        let manager = Arc::new(
+            RedisManager::new(
+                enabled_cfg(),
+                Arc::clone(&publish_io),
+                RedisMetrics::new().unwrap(),
+            )
+            .unwrap()
+            .with_probe_io(Arc::clone(&probe_io)),
+        );
+        assert!(manager.has_dedicated_probe_io());
+
+        let token = CancellationToken::new();
+        let handle = manager.spawn_health_loop(token.clone());
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        token.cancel();
+        handle.await.unwrap();
+
+        let out = manager
+            .publish(
+                "binance_linear",
+                "BTCUSDT",
+                StreamKind::Trades,
+                &[("k", "v")],
+            )
+            .await
+            .unwrap();
+        assert_eq!(out, PublishOutcome::Published);
+
+        // probes only on the probe connection, publishes only on the publish connection
+        assert!(probe_io.probes.load(Ordering::Relaxed) > 0);
+        assert_eq!(probe_io.xadds.load(Ordering::Relaxed), 0);
+        assert_eq!(publish_io.probes.load(Ordering::Relaxed), 0);
+        assert_eq!(publish_io.xadds.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn consumer_group_pending_gauge_is_set_per_configured_group() {
+        let io = Arc::new(CountingIo {
+            xinfo_reply: Some(redis::Value::Array(vec![
+                xinfo_entry("cg:features", 42, 7),
+                xinfo_entry("cg:adhoc", 1000, 0),
+            ])),
+            ..Default::default()
+        });
+        let mut cfg = enabled_cfg();
+        cfg.groups.ml_infer = Some("cg:ml".into());
+        cfg.capacity.group_lag_max_keys = 1;
+        let manager =
+            RedisManager::new(cfg, Arc::clone(&io), RedisMetrics::new().unwrap()).unwrap();
+
+        for symbol in ["BTCUSDT", "ETHUSDT"] {
+            manager
+                .publish("binance_linear", symbol, StreamKind::Trades, &[("k", "v")])
+                .await
+                .unwrap();
+        }
+
+        let parsed =
+            crate::redis::client::parse_xinfo_groups(io.xinfo_reply.as_ref().unwrap()).unwrap();
+        assert_eq!(
+            parsed[0],
+            GroupInfo {
+                name: "cg:features".into(),
+                pending: 42,
+                lag: Some(7)
+            }
+        );
+
+        // One key per poll: the second poll reaches the second stream
+        let pending = |group: &str| {
+            manager
+                .metrics
+                .group_pending
+                .with_label_values(&[group])
+                .get()
+        };
+        manager.sample_group_lag().await;
+        assert_eq!(pending("cg:features"), 42);
+        manager.sample_group_lag().await;
+        assert_eq!(pending("cg:features"), 84);
+        // Configured but absent on every stream; unconfigured groups get no series
+        assert_eq!(pending("cg:ml"), 0);
+        let text = manager.metrics.encode_text().unwrap();
+        assert!(text.contains("redis_consumer_group_pending{group=\"cg:features\"} 84"));
+        assert!(!text.contains("cg:adhoc"));
+    }
+
+    #[tokio::test]
+    async fn publish_audit_detects_a_tiny_maxlen() {
+        let mut cfg = enabled_cfg();
+        cfg.capacity.publish_audit_every_polls = 1;
+        assert_eq!(cfg.retention.maxlen_for(StreamKind::Trades), 5_000);
+
+        async fn publish_burst(manager: &RedisManager<CountingIo>) {
+            for _ in 0..20 {
+                manager
+                    .publish(
+                        "binance_linear",
+                        "BTCUSDT",
+                        StreamKind::Trades,
+                        &[("k", "v")],
+                    )
+                    .await
+                    .unwrap();
+            }
+        }
+        let mismatches =
+            |manager: &RedisManager<CountingIo>| manager.metrics.publish_audit_mismatch_total.get();
+
+        // Healthy server: retention as configured, every entry is there
+        let manager = RedisManager::new(
+            cfg.clone(),
+            Arc::new(CountingIo::default()),
+            RedisMetrics::new().unwrap(),
+        )
+        .unwrap();
+        for _ in 0..3 {
+            publish_burst(&manager).await;
+            manager.audit_publishes().await;
+        }
+        assert_eq!(mismatches(&manager), 0);
+
+        // The stream is really trimmed to MAXLEN 1: every XADD succeeds, data is gone
+        let io = Arc::new(CountingIo {
+            server_maxlen: Some(1),
+            ..Default::default()
+        });
+        let manager =
+            RedisManager::new(cfg, Arc::clone(&io), RedisMetrics::new().unwrap()).unwrap();
+        publish_burst(&manager).await;
+        manager.audit_publishes().await; // baseline
+        assert_eq!(mismatches(&manager), 0);
+        publish_burst(&manager).await;
+        manager.audit_publishes().await;
+        assert_eq!(io.xadds.load(Ordering::Relaxed), 40);
+        assert_eq!(mismatches(&manager), 1);
+        let text = manager.metrics.encode_text().unwrap();
+        assert!(text.contains("redis_publish_audit_mismatch_total 1"));
+    }
+
+    #[tokio::test]
+    async fn health_probes_share_publish_connection_by_default() {
+        let io = Arc::new(CountingIo::default());
+        let manager = Arc::new(
+            RedisManager::new(enabled_cfg(), Arc::clone(&io), RedisMetrics::new().unwrap())
+                .unwrap(),
+        );
+        assert!(!manager.has_dedicated_probe_io());
+
+        let token = CancellationToken::new();
+        let handle = manager.spawn_health_loop(token.clone());
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        token.cancel();
+        handle.await.unwrap();
+
+        assert!(io.probes.load(Ordering::Relaxed) > 0);
+    }
+
+    #[tokio::test]
+    async fn pubsub_mirrors_stream_publishes_when_enabled() {
+        let io = Arc::new(CountingIo::default());
+        let mut cfg = enabled_cfg();
+        cfg.streams.also_publish_pubsub = true;
+        let manager =
+            RedisManager::new(cfg, Arc::clone(&io), RedisMetrics::new().unwrap()).unwrap();
+
+        for px in ["100", "101"] {
+            let out = manager
+                .publish(
+                    "binance_linear",
+                    "BTCUSDT",
+                    StreamKind::Trades,
+                    &[("px", px), ("qty", "1")],
+                )
+                .await
+                .unwrap();
+            assert_eq!(out, PublishOutcome::Published);
+        }
+
+        let stream_key = manager
+            .keys
+            .key("binance_linear", "BTCUSDT", StreamKind::Trades);
+        let published = io.published.lock().unwrap().clone();
+        assert_eq!(io.xadds.load(Ordering::Relaxed), 2);
+        assert_eq!(published.len(), 2);
+        assert!(published.iter().all(|(ch, _)| *ch == stream_key));
+
+        let first: serde_json::Value = serde_json::from_str(&published[0].1).unwrap();
+        assert_eq!(first, serde_json::json!({ "px": "100", "qty": "1" }));
+    }
+
+    #[tokio::test]
+    async fn pubsub_is_off_by_default() {
+        let io = Arc::new(CountingIo::default());
+        let manager =
+            RedisManager::new(enabled_cfg(), Arc::clone(&io), RedisMetrics::new().unwrap())
+                .unwrap();
+
+        manager
+            .publish(
+                "binance_linear",
+                "BTCUSDT",
+                StreamKind::Trades,
+                &[("k", "v")],
+            )
+            .await
+            .unwrap();
+        assert_eq!(io.xadds.load(Ordering::Relaxed), 1);
+        assert!(io.published.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn shutdown_awaits_in_flight_publishes_up_to_timeout() {
+        let publish = |m: Arc<RedisManager<CountingIo>>| {
+            tokio::spawn(async move {
+                m.publish(
+                    "binance_linear",
+                    "BTCUSDT",
+                    StreamKind::Trades,
+                    &[("k", "v")],
+                )
+                .await
+                .unwrap()
+            })
+        };
+
+        // Slow XADD finishes within the drain timeout
+        let io = Arc::new(CountingIo {
+            xadd_delay_ms: 200,
+            ..Default::default()
+        });
+        let mut cfg = enabled_cfg();
+        cfg.connection.shutdown_drain_timeout_ms = 2_000;
+        let manager = Arc::new(
+            RedisManager::new(cfg, Arc::clone(&io), RedisMetrics::new().unwrap()).unwrap(),
+        );
+
+        let task = publish(Arc::clone(&manager));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(manager.in_flight_publishes(), 1);
+
+        assert_eq!(manager.shutdown().await, 0);
+        assert_eq!(io.xadds.load(Ordering::Relaxed), 1);
+        assert_eq!(task.await.unwrap(), PublishOutcome::Published);
+
+        // No new publishes after shutdown
+        assert_eq!(
+            publish(Arc::clone(&manager)).await.unwrap(),
+            PublishOutcome::Skipped
+        );
+        assert_eq!(io.xadds.load(Ordering::Relaxed), 1);
+
+        // Stuck XADD: shutdown gives up at the deadline and reports it
+        let io = Arc::new(CountingIo {
+            xadd_delay_ms: 10_000,
+            ..Default::default()
+        });
+        let mut cfg = enabled_cfg();
+        cfg.connection.shutdown_drain_timeout_ms = 100;
+        let manager = Arc::new(
+            RedisManager::new(cfg, Arc::clone(&io), RedisMetrics::new().unwrap()).unwrap(),
+        );
+
+        let task = publish(Arc::clone(&manager));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let t0 = Instant::now();
+        assert_eq!(manager.shutdown().await, 1);
+        assert!(t0.elapsed() < Duration::from_secs(2));
+        task.abort();
+    }
+}
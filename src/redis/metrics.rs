@@ -1,9 +1,12 @@
+use crate::app::metrics::ConstLabels;
+#[cfg(feature = "metrics")]
+use crate::app::metrics::registry_with_const_labels;
 use crate::error::AppResult;
 
 #[cfg(feature = "metrics")]
 use prometheus::{
-    Encoder, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
-    TextEncoder,
+    Encoder, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts,
+    Registry, TextEncoder,
 };
 
 #[derive(Clone, Debug)]
@@ -16,6 +19,8 @@ pub struct RedisMetrics {
     // --------------------------------------------
     #[cfg(feature = "metrics")]
     pub published_total: IntCounter,
+    #[cfg(feature = "metrics")]
+    pub pubsub_published_total: IntCounter,
 
     // --------------------------------------------
     // Latency
@@ -28,6 +33,8 @@ pub struct RedisMetrics {
     // --------------------------------------------
     #[cfg(feature = "metrics")]
     pub publish_failures_total: IntCounter,
+    #[cfg(feature = "metrics")]
+    pub pubsub_failures_total: IntCounter,
 
     // --------------------------------------------
     // Backpressure-ish (application-side)
@@ -35,6 +42,14 @@ pub struct RedisMetrics {
     #[cfg(feature = "metrics")]
     pub publish_queue_depth: IntGauge,
 
+    /// Pending entries of each configured consumer group (sampled stream keys).
+    #[cfg(feature = "metrics")]
+    pub group_pending: IntGaugeVec,
+
+    /// Audited stream keys holding fewer entries than published to them.
+    #[cfg(feature = "metrics")]
+    pub publish_audit_mismatch_total: IntCounter,
+
     // --------------------------------------------
     // Health gate / optional-Redis signals
     // --------------------------------------------
@@ -53,15 +68,25 @@ pub struct RedisMetrics {
 
 impl RedisMetrics {
     pub fn new() -> AppResult<Self> {
+        Self::with_const_labels(&ConstLabels::new())
+    }
+
+    /// Like `new`, with `const_labels` on every series (`[metrics]` instance labels).
+    pub fn with_const_labels(const_labels: &ConstLabels) -> AppResult<Self> {
         #[cfg(feature = "metrics")]
         {
-            let registry = Registry::new();
+            let registry = registry_with_const_labels(const_labels)?;
 
             let published_total = IntCounter::with_opts(Opts::new(
                 "redis_stream_published_total",
                 "Redis stream messages published total",
             ))?;
 
+            let pubsub_published_total = IntCounter::with_opts(Opts::new(
+                "redis_pubsub_published_total",
+                "Redis pub/sub messages published total (also_publish_pubsub)",
+            ))?;
+
             // Better buckets for Redis publish: sub-ms to 1s range.
             let publish_latency_seconds = Histogram::with_opts(
                 HistogramOpts::new(
@@ -78,11 +103,29 @@ impl RedisMetrics {
                 "Redis publish failures total",
             ))?;
 
+            let pubsub_failures_total = IntCounter::with_opts(Opts::new(
+                "redis_pubsub_failures_total",
+                "Redis pub/sub publish failures total",
+            ))?;
+
             let publish_queue_depth = IntGauge::with_opts(Opts::new(
                 "redis_publish_queue_depth",
                 "Approx publish queue depth (application-side)",
             ))?;
 
+            let group_pending = IntGaugeVec::new(
+                Opts::new(
+                    "redis_consumer_group_pending",
+                    "Pending (delivered, unacked) entries per configured consumer group",
+                ),
+                &["group"],
+            )?;
+
+            let publish_audit_mismatch_total = IntCounter::with_opts(Opts::new(
+                "redis_publish_audit_mismatch_total",
+                "Audited stream keys (XLEN) short of the entries published to them",
+            ))?;
+
             let enabled_state = IntGauge::with_opts(Opts::new(
                 "redis_enabled_state",
                 "Whether Redis is currently used by the app (1=yes, 0=no)",
@@ -97,9 +140,13 @@ impl RedisMetrics {
             )?;
 
             registry.register(Box::new(published_total.clone()))?;
+            registry.register(Box::new(pubsub_published_total.clone()))?;
             registry.register(Box::new(publish_latency_seconds.clone()))?;
             registry.register(Box::new(publish_failures_total.clone()))?;
+            registry.register(Box::new(pubsub_failures_total.clone()))?;
             registry.register(Box::new(publish_queue_depth.clone()))?;
+            registry.register(Box::new(group_pending.clone()))?;
+            registry.register(Box::new(publish_audit_mismatch_total.clone()))?;
             registry.register(Box::new(enabled_state.clone()))?;
             registry.register(Box::new(disable_events_total.clone()))?;
 
@@ -109,9 +156,13 @@ impl RedisMetrics {
             Ok(Self {
                 registry,
                 published_total,
+                pubsub_published_total,
                 publish_latency_seconds,
                 publish_failures_total,
+                pubsub_failures_total,
                 publish_queue_depth,
+                group_pending,
+                publish_audit_mismatch_total,
                 enabled_state,
                 disable_events_total,
             })
@@ -119,6 +170,7 @@ impl RedisMetrics {
 
         #[cfg(not(feature = "metrics"))]
         {
+            let _ = const_labels;
             Ok(Self { _noop: () })
         }
     }
@@ -153,12 +205,38 @@ impl RedisMetrics {
         self.publish_failures_total.inc();
     }
 
+    #[inline]
+    pub fn inc_pubsub_published(&self) {
+        #[cfg(feature = "metrics")]
+        self.pubsub_published_total.inc();
+    }
+
+    #[inline]
+    pub fn inc_pubsub_failure(&self) {
+        #[cfg(feature = "metrics")]
+        self.pubsub_failures_total.inc();
+    }
+
     #[inline]
     pub fn set_queue_depth(&self, _depth: i64) {
         #[cfg(feature = "metrics")]
         self.publish_queue_depth.set(_depth);
     }
 
+    #[inline]
+    pub fn set_group_pending(&self, _group: &str, _pending: u64) {
+        #[cfg(feature = "metrics")]
+        self.group_pending
+            .with_label_values(&[_group])
+            .set(_pending.min(i64::MAX as u64) as i64);
+    }
+
+    #[inline]
+    pub fn inc_publish_audit_mismatch(&self) {
+        #[cfg(feature = "metrics")]
+        self.publish_audit_mismatch_total.inc();
+    }
+
     // ------------------------------------------------------------
     // Optional-Redis / health-gate helpers
     // ------------------------------------------------------------
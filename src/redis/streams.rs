@@ -1,7 +1,10 @@
 // src/redis/streams.rs
 
 use crate::error::{AppError, AppResult};
+use crate::ingest::config::SymbolCase;
+use crate::ingest::datamap::naming::{exchange_token, symbol_token};
 use crate::redis::config::RedisConfig;
+use std::collections::HashMap;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum StreamKind {
@@ -13,6 +16,14 @@ pub enum StreamKind {
 }
 
 impl StreamKind {
+    pub const ALL: [StreamKind; 5] = [
+        StreamKind::Trades,
+        StreamKind::Depth,
+        StreamKind::Liquidations,
+        StreamKind::Funding,
+        StreamKind::OpenInterest,
+    ];
+
     pub fn as_str(&self) -> &'static str {
         match self {
             StreamKind::Trades => "trades",
@@ -27,6 +38,8 @@ impl StreamKind {
 #[derive(Debug, Clone)]
 pub struct StreamKeyBuilder {
     fmt: String,
+    // exchange -> symbol casing (exchanges not listed keep the symbol as-is)
+    symbol_cases: HashMap<String, SymbolCase>,
 }
 
 impl StreamKeyBuilder {
@@ -38,14 +51,29 @@ impl StreamKeyBuilder {
     pub fn from_config(cfg: &RedisConfig) -> AppResult<Self> {
         Ok(Self {
             fmt: cfg.streams.key_format.clone(),
+            symbol_cases: HashMap::new(),
         })
     }
 
+    /// Set the symbol casing policy used for `exchange` keys.
+    pub fn with_symbol_case(mut self, exchange: impl Into<String>, case: SymbolCase) -> Self {
+        self.symbol_cases.insert(exchange.into(), case);
+        self
+    }
+
+    /// Canonical casing of `symbol` for `exchange`.
+    #[inline]
+    pub fn canonical_symbol(&self, exchange: &str, symbol: &str) -> String {
+        let case = self.symbol_cases.get(exchange).copied().unwrap_or_default();
+        symbol_token(case, symbol)
+    }
+
+    /// Stream key; exchange/symbol tokens match the DB tables (see `datamap::naming`).
     #[inline]
     pub fn key(&self, exchange: &str, symbol: &str, kind: StreamKind) -> String {
         self.fmt
-            .replace("{exchange}", exchange)
-            .replace("{symbol}", symbol)
+            .replace("{exchange}", &exchange_token(exchange))
+            .replace("{symbol}", &self.canonical_symbol(exchange, symbol))
             .replace("{kind}", kind.as_str())
     }
 }
@@ -79,4 +107,42 @@ mod tests {
             "stream:bybit:ETHUSDT:open_interest"
         );
     }
+
+    #[test]
+    fn applies_per_exchange_symbol_case() {
+        let cfg = RedisConfig::load_default().unwrap();
+        let builder = StreamKeyBuilder::from_config(&cfg)
+            .unwrap()
+            .with_symbol_case("binance_linear", SymbolCase::Upper)
+            .with_symbol_case("okx", SymbolCase::Lower)
+            .with_symbol_case("hyperliquid_perp", SymbolCase::Preserve);
+
+        // upper: both casings land on the same key
+        assert_eq!(
+            builder.key("binance_linear", "btcusdt", StreamKind::Trades),
+            builder.key("binance_linear", "BTCUSDT", StreamKind::Trades)
+        );
+        assert_eq!(
+            builder.key("binance_linear", "btcUsdt", StreamKind::Trades),
+            "stream:binance_linear:BTCUSDT:trades"
+        );
+
+        // lower
+        assert_eq!(
+            builder.key("okx", "BTC-USDT", StreamKind::Depth),
+            "stream:okx:btc-usdt:depth"
+        );
+
+        // preserve
+        assert_eq!(
+            builder.key("hyperliquid_perp", "kPEPE", StreamKind::Trades),
+            "stream:hyperliquid_perp:kPEPE:trades"
+        );
+
+        // unknown exchange => preserve
+        assert_eq!(
+            builder.key("bybit", "EthUsdt", StreamKind::Funding),
+            "stream:bybit:EthUsdt:funding"
+        );
+    }
 }
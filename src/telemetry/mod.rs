@@ -1,3 +1,7 @@
+pub mod redact;
+pub mod throttle;
 pub mod tracing;
 
+pub use redact::*;
+pub use throttle::*;
 pub use tracing::*;
@@ -0,0 +1,171 @@
+//! telemetry/redact.rs
+//!
+//! Field-level redaction of raw venue payloads before they reach logs or error messages.
+//!
+//! Authenticated streams can carry account identifiers. Every place that surfaces a raw
+//! payload (today: decode errors, which quote the offending message) goes through the
+//! process-wide redactor, configured by `logging.redact_fields`:
+//! - `"user"`: a field name, redacted at any depth;
+//! - `"/data/0/users"`: a JSON pointer (RFC 6901), redacted at exactly that place.
+//!
+//! Redacted values are replaced by `logging.redact_placeholder`, the rest is kept as is.
+
+use arc_swap::ArcSwap;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::sync::{Arc, OnceLock};
+
+/// Default for `logging.redact_placeholder`.
+pub const DEFAULT_REDACT_PLACEHOLDER: &str = "[REDACTED]";
+
+/// Longest payload quoted in a log line or error (chars, after redaction).
+pub const PAYLOAD_PREVIEW_CHARS: usize = 512;
+
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    names: HashSet<String>,
+    pointers: Vec<String>,
+    placeholder: String,
+}
+
+impl Default for Redactor {
+    fn default() -> Self {
+        Self::new(&[], DEFAULT_REDACT_PLACEHOLDER)
+    }
+}
+
+impl Redactor {
+    /// Entries starting with `/` are JSON pointers, the others field names.
+    pub fn new(fields: &[String], placeholder: &str) -> Self {
+        let (pointers, names): (Vec<_>, Vec<_>) = fields
+            .iter()
+            .map(|f| f.trim())
+            .filter(|f| !f.is_empty())
+            .partition(|f| f.starts_with('/'));
+        Self {
+            names: names.into_iter().map(str::to_string).collect(),
+            pointers: pointers.into_iter().map(str::to_string).collect(),
+            placeholder: placeholder.to_string(),
+        }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty() && self.pointers.is_empty()
+    }
+
+    /// Replace every configured field of `v` with the placeholder, in place.
+    pub fn redact_value(&self, v: &mut Value) {
+        if self.is_empty() {
+            return;
+        }
+        for p in &self.pointers {
+            if let Some(slot) = v.pointer_mut(p) {
+                *slot = Value::String(self.placeholder.clone());
+            }
+        }
+        self.redact_names(v);
+    }
+
+    fn redact_names(&self, v: &mut Value) {
+        match v {
+            Value::Object(map) => {
+                for (k, child) in map.iter_mut() {
+                    if self.names.contains(k) {
+                        *child = Value::String(self.placeholder.clone());
+                    } else {
+                        self.redact_names(child);
+                    }
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|c| self.redact_names(c)),
+            _ => {}
+        }
+    }
+
+    /// Redacted copy of a raw text frame. Text that is not JSON cannot be redacted field by
+    /// field and is replaced whole (when any field is configured).
+    pub fn redact_text(&self, raw: &str) -> String {
+        if self.is_empty() {
+            return raw.to_string();
+        }
+        match serde_json::from_str::<Value>(raw) {
+            Ok(mut v) => {
+                self.redact_value(&mut v);
+                v.to_string()
+            }
+            Err(_) => self.placeholder.clone(),
+        }
+    }
+
+    /// Redacted payload, cut to `PAYLOAD_PREVIEW_CHARS`, for a log line or error message.
+    pub fn preview(&self, v: &Value) -> String {
+        let text = if self.is_empty() {
+            v.to_string()
+        } else {
+            let mut v = v.clone();
+            self.redact_value(&mut v);
+            v.to_string()
+        };
+        match text.char_indices().nth(PAYLOAD_PREVIEW_CHARS) {
+            Some((cut, _)) => format!("{}...", &text[..cut]),
+            None => text,
+        }
+    }
+}
+
+static REDACTOR: OnceLock<ArcSwap<Redactor>> = OnceLock::new();
+
+fn slot() -> &'static ArcSwap<Redactor> {
+    REDACTOR.get_or_init(|| ArcSwap::from_pointee(Redactor::default()))
+}
+
+/// Process-wide redactor used wherever a raw payload is surfaced.
+pub fn payload_redactor() -> Arc<Redactor> {
+    slot().load_full()
+}
+
+/// Apply `logging.redact_fields` / `logging.redact_placeholder`.
+pub fn set_payload_redaction(fields: &[String], placeholder: &str) {
+    slot().store(Arc::new(Redactor::new(fields, placeholder)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn configured_fields_are_redacted_by_name_and_pointer() {
+        let r = Redactor::new(&["users".to_string(), "/data/0/hash".to_string()], "<x>");
+        let mut v = json!({
+            "channel": "trades",
+            "data": [
+                {"coin": "BTC", "hash": "0xabc", "users": ["0xa", "0xb"]},
+                {"coin": "BTC", "hash": "0xdef", "users": ["0xc", "0xd"]}
+            ]
+        });
+        r.redact_value(&mut v);
+        assert_eq!(
+            v,
+            json!({
+                "channel": "trades",
+                "data": [
+                    {"coin": "BTC", "hash": "<x>", "users": "<x>"},
+                    {"coin": "BTC", "hash": "0xdef", "users": "<x>"}
+                ]
+            })
+        );
+
+        // Raw text: JSON is redacted field by field, anything else replaced whole
+        let text = r.redact_text(r#"{"users":["0xa"],"px":"1.0"}"#);
+        assert!(!text.contains("0xa") && text.contains("1.0"), "{text}");
+        assert_eq!(r.redact_text("account 0xa"), "<x>");
+
+        // Nothing configured: untouched
+        let none = Redactor::default();
+        assert_eq!(none.redact_text("account 0xa"), "account 0xa");
+        let long = Value::String("a".repeat(2 * PAYLOAD_PREVIEW_CHARS));
+        assert!(none.preview(&long).ends_with("..."));
+    }
+}
@@ -0,0 +1,225 @@
+//! telemetry/throttle.rs
+//!
+//! Rate-limited logging for errors that repeat at line rate (ws read errors, failed
+//! publishes, dropped snapshots).
+//!
+//! The first occurrence per key is logged immediately; later ones within
+//! `logging.repeat_window_secs` are counted, and the next occurrence after the window logs
+//! again with `suppressed = N` (occurrences skipped since the previous line). Keys are a
+//! category plus whatever scopes it (exchange, stream key, ...).
+//!
+//! An error that stops before recurring would never get its summary that way, so the
+//! runtime also calls `flush_due` periodically and logs the counts it returns.
+//!
+//! Call sites:
+//!
+//! ```text
+//! if let Some(suppressed) = log_throttle().allow(&key) {
+//!     warn!(suppressed, error = %e, "redis publish failed");
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Default for `logging.repeat_window_secs`.
+pub const DEFAULT_REPEAT_WINDOW_SECS: u64 = 10;
+
+#[derive(Debug)]
+struct Site {
+    logged_at: Instant,
+    suppressed: u64,
+}
+
+/// Per-key log throttle.
+#[derive(Debug)]
+pub struct LogThrottle {
+    // 0 = log every occurrence
+    window_ms: AtomicU64,
+    sites: Mutex<HashMap<String, Site>>,
+}
+
+impl LogThrottle {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window_ms: AtomicU64::new(window.as_millis() as u64),
+            sites: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn set_window(&self, window: Duration) {
+        self.window_ms
+            .store(window.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    pub fn window(&self) -> Duration {
+        Duration::from_millis(self.window_ms.load(Ordering::Relaxed))
+    }
+
+    /// Some(suppressed) if this occurrence of `key` should be logged, None to skip it.
+    #[inline]
+    pub fn allow(&self, key: &str) -> Option<u64> {
+        self.allow_at(key, Instant::now())
+    }
+
+    pub fn allow_at(&self, key: &str, now: Instant) -> Option<u64> {
+        let window = self.window();
+        if window.is_zero() {
+            return Some(0);
+        }
+
+        let mut sites = self
+            .sites
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let Some(site) = sites.get_mut(key) else {
+            sites.insert(
+                key.to_string(),
+                Site {
+                    logged_at: now,
+                    suppressed: 0,
+                },
+            );
+            return Some(0);
+        };
+
+        if now.saturating_duration_since(site.logged_at) >= window {
+            site.logged_at = now;
+            Some(std::mem::take(&mut site.suppressed))
+        } else {
+            site.suppressed += 1;
+            None
+        }
+    }
+
+    /// `(key, suppressed)` for every key with suppressed occurrences whose window has passed.
+    /// Those counts are reset (as if the summary was logged now); idle keys are dropped.
+    #[inline]
+    pub fn flush_due(&self) -> Vec<(String, u64)> {
+        self.flush_due_at(Instant::now())
+    }
+
+    pub fn flush_due_at(&self, now: Instant) -> Vec<(String, u64)> {
+        let window = self.window();
+        let mut sites = self
+            .sites
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        let mut due = Vec::new();
+        sites.retain(|key, site| {
+            if now.saturating_duration_since(site.logged_at) < window {
+                return true;
+            }
+            if site.suppressed == 0 {
+                // Next occurrence logs immediately either way
+                return false;
+            }
+            due.push((key.clone(), std::mem::take(&mut site.suppressed)));
+            site.logged_at = now;
+            true
+        });
+        due
+    }
+}
+
+static LOG_THROTTLE: OnceLock<LogThrottle> = OnceLock::new();
+
+/// Process-wide throttle used by the hot error paths.
+pub fn log_throttle() -> &'static LogThrottle {
+    LOG_THROTTLE.get_or_init(|| LogThrottle::new(Duration::from_secs(DEFAULT_REPEAT_WINDOW_SECS)))
+}
+
+/// Apply `logging.repeat_window_secs` (0 = log every occurrence).
+pub fn set_log_repeat_window(window: Duration) {
+    log_throttle().set_window(window);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeats_are_suppressed_then_summarized_per_window() {
+        let throttle = LogThrottle::new(Duration::from_secs(10));
+        let t0 = Instant::now();
+        let at = |secs: u64| t0 + Duration::from_secs(secs);
+
+        // First occurrence logs immediately
+        assert_eq!(
+            throttle.allow_at("ws read error:binance_linear", t0),
+            Some(0)
+        );
+
+        // Repeats within the window are counted, not logged
+        for s in 1..=5 {
+            assert_eq!(
+                throttle.allow_at("ws read error:binance_linear", at(s)),
+                None
+            );
+        }
+
+        // Other keys are throttled independently
+        assert_eq!(
+            throttle.allow_at("ws read error:hyperliquid_perp", at(3)),
+            Some(0)
+        );
+
+        // After the window: logs again with the number suppressed meanwhile
+        assert_eq!(
+            throttle.allow_at("ws read error:binance_linear", at(10)),
+            Some(5)
+        );
+        assert_eq!(
+            throttle.allow_at("ws read error:binance_linear", at(11)),
+            None
+        );
+        assert_eq!(
+            throttle.allow_at("ws read error:binance_linear", at(25)),
+            Some(1)
+        );
+
+        // Window 0 disables throttling
+        throttle.set_window(Duration::ZERO);
+        assert_eq!(
+            throttle.allow_at("ws read error:binance_linear", at(25)),
+            Some(0)
+        );
+        assert_eq!(
+            throttle.allow_at("ws read error:binance_linear", at(25)),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn flush_reports_repeats_of_an_error_that_stopped() {
+        let throttle = LogThrottle::new(Duration::from_secs(10));
+        let t0 = Instant::now();
+        let at = |secs: u64| t0 + Duration::from_secs(secs);
+
+        assert_eq!(throttle.allow_at("publish failed:redis", t0), Some(0));
+        for s in 1..=3 {
+            assert_eq!(throttle.allow_at("publish failed:redis", at(s)), None);
+        }
+        assert_eq!(
+            throttle.allow_at("ws read error:binance_linear", at(2)),
+            Some(0)
+        );
+
+        // Nothing due while the window is open
+        assert!(throttle.flush_due_at(at(5)).is_empty());
+
+        // The error stopped: the flush still reports its repeats, once
+        assert_eq!(
+            throttle.flush_due_at(at(12)),
+            vec![("publish failed:redis".to_string(), 3)]
+        );
+        assert!(throttle.flush_due_at(at(30)).is_empty());
+
+        // Idle keys were dropped, so the next occurrence logs immediately
+        assert!(throttle.sites.lock().unwrap().is_empty());
+        assert_eq!(throttle.allow_at("publish failed:redis", at(31)), Some(0));
+    }
+}
@@ -150,6 +150,73 @@ async fn db_registry_insert() {
     println!("[test] registry insert OK");
 }
 
+#[tokio::test]
+async fn db_registry_upsert_many_lands_every_stream() {
+    use crate::db::RegistryUpsert;
+
+    if !db_tests_enabled() {
+        println!("[test] Skipping DB integration test (set RUN_DB_TESTS=1)");
+        return;
+    }
+
+    let (pools, handler) = make_handler().await;
+    let knobs = StreamKnobs {
+        flush_rows: 321,
+        ..StreamKnobs::default()
+    };
+
+    let specs: Vec<StreamSpec> = (0..50)
+        .flat_map(|i| {
+            [
+                StreamSpec {
+                    exchange: "binance_linear",
+                    instrument: format!("BULK{i}USDT"),
+                    kind: StreamKind::Trades,
+                    transport: StreamTransport::Ws,
+                },
+                StreamSpec {
+                    exchange: "hyperliquid_perp",
+                    instrument: format!("BULK{i}"),
+                    kind: StreamKind::L2Book,
+                    transport: StreamTransport::Ws,
+                },
+            ]
+        })
+        .collect();
+    let mut entries: Vec<RegistryUpsert> = specs
+        .iter()
+        .map(|spec| RegistryUpsert {
+            spec: spec.clone(),
+            knobs,
+            enabled: false,
+        })
+        .collect();
+    // A repeat in the same call: the last entry wins
+    entries.push(RegistryUpsert {
+        spec: specs[0].clone(),
+        knobs,
+        enabled: true,
+    });
+
+    let n = handler
+        .upsert_stream_registry_many(&entries)
+        .await
+        .expect("bulk upsert failed");
+    assert_eq!(n, specs.len() as u64);
+
+    for (i, spec) in specs.iter().enumerate() {
+        let row = fetch_registry_row(&pools, spec)
+            .await
+            .unwrap_or_else(|| panic!("row for {} should exist", spec.instrument));
+        assert_eq!(row.2, 321);
+        assert_eq!(row.6, i == 0);
+    }
+
+    for spec in &specs {
+        handler.remove_stream(spec).await.expect("remove failed");
+    }
+}
+
 #[tokio::test]
 async fn db_registry_update_knobs() {
     if !db_tests_enabled() {
@@ -166,6 +233,8 @@ async fn db_registry_update_knobs() {
         flush_interval_ms: 1234,
         chunk_rows: 9,
         hard_cap_rows: 11,
+        emit_bbo: true,
+        emit_microprice: true,
     };
 
     for p in test_cases() {
@@ -3,6 +3,7 @@ use std::time::Duration;
 
 use chrono::{TimeZone, Utc};
 
+use crate::app::stream_types::{StreamKind, StreamTransport};
 use crate::db::config::TimescaleDbConfig;
 use crate::db::metrics::DbMetrics;
 use crate::db::pools::DbPools;
@@ -26,10 +27,20 @@ fn override_dsn_if_present(cfg: &mut TimescaleDbConfig) {
 }
 
 fn key(stream: &str, symbol: &str) -> BatchKey {
+    let (transport, kind) = match stream {
+        "depth" => (StreamTransport::HttpPoll, StreamKind::L2Book),
+        "open_interest" => (StreamTransport::HttpPoll, StreamKind::OpenInterest),
+        "funding" => (StreamTransport::HttpPoll, StreamKind::Funding),
+        "liquidations" => (StreamTransport::Ws, StreamKind::Liquidations),
+        _ => (StreamTransport::Ws, StreamKind::Trades),
+    };
     BatchKey {
         exchange: "binance_linear".to_string(),
+        transport,
+        kind,
         stream: stream.to_string(),
         symbol: symbol.to_string(),
+        derived: None,
     }
 }
 
@@ -216,3 +227,617 @@ async fn write_batches_for_all_row_types() {
 
     println!("[test] ===== DB WRITE INTEGRATION TEST OK =====");
 }
+
+#[tokio::test]
+async fn write_batch_reports_flush_outcome() {
+    if !db_tests_enabled() {
+        println!("[test] Skipping DB integration test (set RUN_DB_TESTS=1)");
+        return;
+    }
+
+    let mut cfg = TimescaleDbConfig::load(false, 0).expect("failed to load config");
+    cfg.writer.batch_size = 3;
+    cfg.writer.flush_interval_ms = 60_000;
+    override_dsn_if_present(&mut cfg);
+
+    let pools = Arc::new(
+        DbPools::new(cfg.clone(), true)
+            .await
+            .expect("failed to create DbPools"),
+    );
+    let metrics = Arc::new(DbMetrics::new().expect("metrics init failed"));
+    let handler = DbHandler::new(pools, cfg.writer.clone(), metrics);
+
+    // Trade inserts are idempotent (`ON CONFLICT DO NOTHING`): a fixed time would make a
+    // rerun against the same database write nothing.
+    let ts = Utc::now();
+    let row = |ms: i64, id: i64| TradeDBRow {
+        time: ts + chrono::Duration::milliseconds(ms),
+        symbol: "BTCUSDT".into(),
+        side: 0,
+        price_i: 42_000_000,
+        qty_i: 1_000,
+        trade_id: Some(id),
+        is_maker: Some(true),
+    };
+
+    // --------------------------------------------------
+    // No flush: below batch_size and interval not elapsed
+    // --------------------------------------------------
+    let mut trades = Batch::new(
+        key("trades", "BTCUSDT"),
+        vec![row(5, 1), row(1, 2)],
+        &cfg.writer,
+    );
+    let out = handler
+        .write_batch(&mut trades)
+        .await
+        .expect("write trades");
+    assert!(!out.flushed);
+    assert_eq!(out.rows_written, 0);
+    assert_eq!(out.watermark, None);
+    assert_eq!(trades.rows.len(), 2, "rows must be kept when not flushed");
+
+    // --------------------------------------------------
+    // Flush: batch_size reached
+    // --------------------------------------------------
+    trades.rows.push(row(3, 3));
+    let out = handler
+        .write_batch(&mut trades)
+        .await
+        .expect("write trades");
+    assert!(out.flushed);
+    assert_eq!(out.rows_written, 3);
+    assert_eq!(
+        out.watermark,
+        Some(ts + chrono::Duration::milliseconds(5)),
+        "watermark is the max event time, not the last row"
+    );
+    assert!(trades.rows.is_empty());
+}
+
+#[tokio::test]
+async fn deduped_trade_is_not_written_nor_kept_for_publish() {
+    if !db_tests_enabled() {
+        println!("[test] Skipping DB integration test (set RUN_DB_TESTS=1)");
+        return;
+    }
+
+    let mut cfg = TimescaleDbConfig::load(false, 0).expect("failed to load config");
+    cfg.writer.batch_size = 3;
+    cfg.writer.flush_interval_ms = 60_000;
+    override_dsn_if_present(&mut cfg);
+
+    let pools = Arc::new(
+        DbPools::new(cfg.clone(), true)
+            .await
+            .expect("failed to create DbPools"),
+    );
+    let metrics = Arc::new(DbMetrics::new().expect("metrics init failed"));
+    let handler = DbHandler::new(pools, cfg.writer.clone(), metrics);
+
+    let ts = Utc::now();
+    let row = |id: i64, price_i: i64| TradeDBRow {
+        time: ts,
+        symbol: "BTCUSDT".into(),
+        side: 0,
+        price_i,
+        qty_i: 1_000,
+        trade_id: Some(id),
+        is_maker: None,
+    };
+
+    // publish_persisted_only: committed rows are kept for the Redis publish
+    let mut trades = Batch::new(key("trades", "BTCUSDT"), vec![], &cfg.writer);
+    trades.track_persisted = true;
+    trades.rows = vec![row(7, 100), row(7, 999), row(8, 101)];
+
+    let out = handler
+        .write_batch(&mut trades)
+        .await
+        .expect("write trades");
+    assert!(out.flushed);
+    assert_eq!((out.rows_written, out.deduped), (2, 1));
+
+    let persisted = trades.take_persisted();
+    assert_eq!(
+        persisted
+            .iter()
+            .map(|r| (r.trade_id, r.price_i))
+            .collect::<Vec<_>>(),
+        vec![(Some(7), 100), (Some(8), 101)],
+        "only the committed rows are handed to the publisher"
+    );
+}
+
+#[tokio::test]
+async fn replayed_trades_land_once() {
+    use crate::db::{BatchInsertRow, quote_table_name};
+
+    if !db_tests_enabled() {
+        println!("[test] Skipping DB integration test (set RUN_DB_TESTS=1)");
+        return;
+    }
+
+    let mut cfg = TimescaleDbConfig::load(false, 0).expect("failed to load config");
+    cfg.writer.batch_size = 1;
+    override_dsn_if_present(&mut cfg);
+
+    let pools = Arc::new(
+        DbPools::new(cfg.clone(), true)
+            .await
+            .expect("failed to create DbPools"),
+    );
+    let metrics = Arc::new(DbMetrics::new().expect("metrics init failed"));
+    let handler = DbHandler::new(Arc::clone(&pools), cfg.writer.clone(), metrics);
+
+    let ts = Utc::now();
+    let row = |id: i64| TradeDBRow {
+        time: ts,
+        symbol: "BTCUSDT".into(),
+        side: 0,
+        price_i: 42_000_000,
+        qty_i: 1_000,
+        trade_id: Some(id),
+        is_maker: None,
+    };
+
+    let mut trades = Batch::new(key("trades", "BTCUSDT"), vec![row(1), row(2)], &cfg.writer);
+    trades.track_persisted = true;
+    let out = handler.write_batch(&mut trades).await.expect("write");
+    assert_eq!(out.rows_written, 2);
+    assert_eq!(trades.take_persisted().len(), 2);
+
+    // Reconnect overlap: the same trades again plus one new
+    trades.rows = vec![row(1), row(2), row(3)];
+    let out = handler.write_batch(&mut trades).await.expect("replay");
+    assert!(out.flushed);
+    assert_eq!(out.rows_written, 1, "only the new trade is applied");
+    assert_eq!(
+        trades
+            .take_persisted()
+            .iter()
+            .map(|r| r.trade_id)
+            .collect::<Vec<_>>(),
+        vec![Some(3)],
+        "rows skipped by the conflict are not published"
+    );
+
+    let shard_id = pools
+        .shard_id_for("binance_linear", "trades", "BTCUSDT")
+        .await
+        .expect("route");
+    let pool = pools.pool_by_id(&shard_id).await.expect("pool");
+    let stored: i64 = sqlx::query_scalar(&format!(
+        "SELECT count(*) FROM {} WHERE time = $1 AND symbol = 'BTCUSDT'",
+        quote_table_name(&row(0).table("binance_linear"))
+    ))
+    .bind(ts)
+    .fetch_one(&pool)
+    .await
+    .expect("count");
+    assert_eq!(stored, 3);
+}
+
+#[tokio::test]
+async fn copy_writes_every_row_once() {
+    use crate::db::{BatchInsertRow, quote_table_name};
+
+    if !db_tests_enabled() {
+        println!("[test] Skipping DB integration test (set RUN_DB_TESTS=1)");
+        return;
+    }
+
+    let mut cfg = TimescaleDbConfig::load(false, 0).expect("failed to load config");
+    cfg.writer.batch_size = 1;
+    cfg.writer.use_copy = true;
+    cfg.writer.chunk_rows = 3; // several sends in the one COPY
+    override_dsn_if_present(&mut cfg);
+
+    let pools = Arc::new(
+        DbPools::new(cfg.clone(), true)
+            .await
+            .expect("failed to create DbPools"),
+    );
+    let metrics = Arc::new(DbMetrics::new().expect("metrics init failed"));
+    let handler = DbHandler::new(Arc::clone(&pools), cfg.writer.clone(), Arc::clone(&metrics));
+
+    // Liquidations have no conflict target: they go through COPY
+    let symbol = format!("COPY{}", Utc::now().timestamp_millis());
+    let ts = Utc::now();
+    let rows: Vec<LiquidationDBRow> = (0..10)
+        .map(|i| LiquidationDBRow {
+            time: ts,
+            symbol: symbol.clone(),
+            side: 1,
+            price_i: Some(42_000_000),
+            qty_i: 1_000 + i,
+            liq_id: Some(i),
+        })
+        .collect();
+    let table = rows[0].table("binance_linear");
+
+    let mut batch = Batch::new(key("liquidations", &symbol), rows, &cfg.writer);
+    let out = handler.write_batch(&mut batch).await.expect("copy write");
+    assert!(out.flushed);
+    assert_eq!(out.rows_written, 10);
+    assert_eq!(metrics.copy_fallback_total.get(), 0, "written by COPY");
+
+    let shard_id = pools
+        .shard_id_for("binance_linear", "liquidations", &symbol)
+        .await
+        .expect("route");
+    let pool = pools.pool_by_id(&shard_id).await.expect("pool");
+    let stored: i64 = sqlx::query_scalar(&format!(
+        "SELECT count(*) FROM {} WHERE symbol = $1",
+        quote_table_name(&table)
+    ))
+    .bind(&symbol)
+    .fetch_one(&pool)
+    .await
+    .expect("count");
+    assert_eq!(stored, 10);
+}
+
+#[tokio::test]
+async fn commit_hook_fires_once_per_commit() {
+    use crate::db::{BatchInsertRow, CommitInfo};
+    use std::sync::Mutex;
+
+    if !db_tests_enabled() {
+        println!("[test] Skipping DB integration test (set RUN_DB_TESTS=1)");
+        return;
+    }
+
+    let mut cfg = TimescaleDbConfig::load(false, 0).expect("failed to load config");
+    cfg.writer.batch_size = 2;
+    cfg.writer.flush_interval_ms = 60_000;
+    override_dsn_if_present(&mut cfg);
+
+    let pools = Arc::new(
+        DbPools::new(cfg.clone(), true)
+            .await
+            .expect("failed to create DbPools"),
+    );
+    let metrics = Arc::new(DbMetrics::new().expect("metrics init failed"));
+
+    let commits: Arc<Mutex<Vec<CommitInfo>>> = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&commits);
+    let handler = DbHandler::new(pools, cfg.writer.clone(), metrics).with_commit_hook(Arc::new(
+        move |info: &CommitInfo| seen.lock().unwrap().push(info.clone()),
+    ));
+
+    let ts = Utc::now();
+    let row = |ms: i64, id: i64| TradeDBRow {
+        time: ts + chrono::Duration::milliseconds(ms),
+        symbol: "BTCUSDT".into(),
+        side: 1,
+        price_i: 42_000_000,
+        qty_i: 1_000,
+        trade_id: Some(id),
+        is_maker: Some(false),
+    };
+
+    let mut trades = Batch::new(key("trades", "BTCUSDT"), vec![row(1, 1)], &cfg.writer);
+
+    // No flush: the hook must not run
+    let out = handler.write_batch(&mut trades).await.expect("write");
+    assert!(!out.flushed);
+    assert!(commits.lock().unwrap().is_empty());
+
+    // Two commits -> two hook calls
+    trades.rows.push(row(7, 2));
+    assert!(
+        handler
+            .write_batch(&mut trades)
+            .await
+            .expect("write")
+            .flushed
+    );
+    trades.rows.extend([row(9, 3), row(8, 4)]);
+    assert!(
+        handler
+            .write_batch(&mut trades)
+            .await
+            .expect("write")
+            .flushed
+    );
+
+    let commits = commits.lock().unwrap();
+    assert_eq!(commits.len(), 2);
+    assert_eq!(commits[0].rows, 2);
+    assert_eq!(
+        commits[0].max_event_time,
+        Some(ts + chrono::Duration::milliseconds(7))
+    );
+    assert_eq!(
+        commits[1].max_event_time,
+        Some(ts + chrono::Duration::milliseconds(9))
+    );
+    assert_eq!(commits[0].table, row(0, 0).table("binance_linear"));
+    assert!(!commits[0].shard_id.is_empty());
+}
+
+#[tokio::test]
+async fn missing_table_is_not_retried() {
+    use crate::db::{BatchInsertRow, CopyLine};
+    use crate::error::AppError;
+    use chrono::DateTime;
+    use sqlx::{Postgres, query_builder::Separated};
+
+    /// Row whose table was never migrated.
+    struct GhostRow {
+        time: DateTime<Utc>,
+    }
+
+    impl BatchInsertRow for GhostRow {
+        const COLUMNS: &'static [&'static str] = &["time"];
+        const COLUMN_TYPES: &'static [&'static str] = &["TIMESTAMPTZ NOT NULL"];
+
+        fn table(&self, exchange: &str) -> String {
+            format!("ex_{exchange}.ghost_rows_never_migrated")
+        }
+
+        fn event_time(&self) -> DateTime<Utc> {
+            self.time
+        }
+
+        fn push_binds(&self, b: &mut Separated<'_, '_, Postgres, &'static str>) {
+            b.push_bind(self.time);
+        }
+
+        fn push_copy_fields(&self, line: &mut CopyLine<'_>) {
+            line.field(&self.time);
+        }
+    }
+
+    if !db_tests_enabled() {
+        println!("[test] Skipping DB integration test (set RUN_DB_TESTS=1)");
+        return;
+    }
+
+    let mut cfg = TimescaleDbConfig::load(false, 0).expect("failed to load config");
+    cfg.writer.batch_size = 1;
+    override_dsn_if_present(&mut cfg);
+
+    let pools = Arc::new(
+        DbPools::new(cfg.clone(), true)
+            .await
+            .expect("failed to create DbPools"),
+    );
+    let metrics = Arc::new(DbMetrics::new().expect("metrics init failed"));
+    let handler = DbHandler::new(pools, cfg.writer.clone(), metrics);
+
+    let mut batch = Batch::new(
+        key("trades", "BTCUSDT"),
+        vec![GhostRow { time: Utc::now() }],
+        &cfg.writer,
+    );
+
+    // A long backoff would make any retry obvious
+    let started = std::time::Instant::now();
+    let res = handler
+        .write_batch_with_retry(&mut batch, 3, Duration::from_secs(5))
+        .await;
+    match res {
+        Err(AppError::MissingTable { table }) => {
+            assert_eq!(table, "ex_binance_linear.ghost_rows_never_migrated")
+        }
+        other => panic!("expected MissingTable, got {other:?}"),
+    }
+    assert!(started.elapsed() < Duration::from_secs(5), "must not retry");
+    assert_eq!(batch.rows.len(), 1, "rows are kept on failure");
+}
+
+#[tokio::test]
+async fn auto_create_tables_creates_table_and_lands_row() {
+    use crate::db::{BatchInsertRow, CopyLine};
+    use chrono::DateTime;
+    use sqlx::{Postgres, query_builder::Separated};
+
+    /// Row targeting a throwaway schema (dropped at the end).
+    struct ProbeRow {
+        schema: String,
+        time: DateTime<Utc>,
+        value: i64,
+    }
+
+    impl BatchInsertRow for ProbeRow {
+        const COLUMNS: &'static [&'static str] = &["time", "symbol", "value_i"];
+        const COLUMN_TYPES: &'static [&'static str] =
+            &["TIMESTAMPTZ NOT NULL", "TEXT NOT NULL", "BIGINT NOT NULL"];
+
+        fn table(&self, _exchange: &str) -> String {
+            format!("{}.probe", self.schema)
+        }
+
+        fn event_time(&self) -> DateTime<Utc> {
+            self.time
+        }
+
+        fn push_binds(&self, b: &mut Separated<'_, '_, Postgres, &'static str>) {
+            b.push_bind(self.time)
+                .push_bind("BTCUSDT")
+                .push_bind(self.value);
+        }
+
+        fn push_copy_fields(&self, line: &mut CopyLine<'_>) {
+            line.field(&self.time).field("BTCUSDT").field(&self.value);
+        }
+    }
+
+    if !db_tests_enabled() {
+        println!("[test] Skipping DB integration test (set RUN_DB_TESTS=1)");
+        return;
+    }
+
+    let mut cfg = TimescaleDbConfig::load(false, 0).expect("failed to load config");
+    cfg.writer.batch_size = 1;
+    cfg.writer.auto_create_tables = true;
+    override_dsn_if_present(&mut cfg);
+
+    let pools = Arc::new(
+        DbPools::new(cfg.clone(), true)
+            .await
+            .expect("failed to create DbPools"),
+    );
+    let metrics = Arc::new(DbMetrics::new().expect("metrics init failed"));
+    let handler = DbHandler::new(Arc::clone(&pools), cfg.writer.clone(), metrics);
+
+    let schema = format!("ex_autocreate_{}", Utc::now().timestamp_millis());
+    let mut batch = Batch::new(
+        key("trades", "BTCUSDT"),
+        vec![ProbeRow {
+            schema: schema.clone(),
+            time: Utc::now(),
+            value: 42,
+        }],
+        &cfg.writer,
+    );
+
+    let out = handler.write_batch(&mut batch).await;
+
+    // Clean up the disposable schema before asserting
+    let shard_id = pools
+        .shard_id_for("binance_linear", "trades", "BTCUSDT")
+        .await
+        .expect("route");
+    let pool = pools.pool_by_id(&shard_id).await.expect("pool");
+    let landed: Option<i64> =
+        sqlx::query_scalar(&format!("SELECT value_i FROM \"{schema}\".probe LIMIT 1"))
+            .fetch_optional(&pool)
+            .await
+            .expect("table was created");
+    sqlx::query(&format!("DROP SCHEMA IF EXISTS \"{schema}\" CASCADE"))
+        .execute(&pool)
+        .await
+        .expect("drop schema");
+
+    let out = out.expect("write after auto-create");
+    assert!(out.flushed);
+    assert_eq!(out.rows_written, 1);
+    assert_eq!(landed, Some(42));
+}
+
+#[tokio::test]
+async fn missing_conflict_index_falls_back_to_plain_insert() {
+    use crate::db::{BatchInsertRow, CopyLine};
+    use chrono::DateTime;
+    use sqlx::{Postgres, query_builder::Separated};
+
+    /// Idempotent row type whose table predates its unique index.
+    struct LegacyRow {
+        schema: String,
+        time: DateTime<Utc>,
+        id: i64,
+    }
+
+    impl BatchInsertRow for LegacyRow {
+        const COLUMNS: &'static [&'static str] = &["time", "symbol", "id"];
+        const COLUMN_TYPES: &'static [&'static str] =
+            &["TIMESTAMPTZ NOT NULL", "TEXT NOT NULL", "BIGINT NULL"];
+        const CONFLICT_TARGET: Option<&'static str> = Some("time, symbol, id");
+        const DEDUP_COLUMN: Option<&'static str> = Some("id");
+
+        fn table(&self, _exchange: &str) -> String {
+            format!("{}.legacy", self.schema)
+        }
+
+        fn event_time(&self) -> DateTime<Utc> {
+            self.time
+        }
+
+        fn dedup_key(&self) -> Option<i64> {
+            Some(self.id)
+        }
+
+        fn push_binds(&self, b: &mut Separated<'_, '_, Postgres, &'static str>) {
+            b.push_bind(self.time)
+                .push_bind("BTCUSDT")
+                .push_bind(self.id);
+        }
+
+        fn push_copy_fields(&self, line: &mut CopyLine<'_>) {
+            line.field(&self.time).field("BTCUSDT").field(&self.id);
+        }
+    }
+
+    if !db_tests_enabled() {
+        println!("[test] Skipping DB integration test (set RUN_DB_TESTS=1)");
+        return;
+    }
+
+    let mut cfg = TimescaleDbConfig::load(false, 0).expect("failed to load config");
+    cfg.writer.batch_size = 1;
+    override_dsn_if_present(&mut cfg);
+
+    let pools = Arc::new(
+        DbPools::new(cfg.clone(), true)
+            .await
+            .expect("failed to create DbPools"),
+    );
+    let shard_id = pools
+        .shard_id_for("binance_linear", "trades", "BTCUSDT")
+        .await
+        .expect("route");
+    let pool = pools.pool_by_id(&shard_id).await.expect("pool");
+
+    // A table as an old deployment has it: no unique index
+    let schema = format!("ex_noconflictidx_{}", Utc::now().timestamp_millis());
+    sqlx::query(&format!("CREATE SCHEMA \"{schema}\""))
+        .execute(&pool)
+        .await
+        .expect("create schema");
+    sqlx::query(&format!(
+        "CREATE TABLE \"{schema}\".legacy (time TIMESTAMPTZ NOT NULL, symbol TEXT NOT NULL, id BIGINT NULL)"
+    ))
+    .execute(&pool)
+    .await
+    .expect("create table");
+
+    let time = Utc::now();
+    let row = || LegacyRow {
+        schema: schema.clone(),
+        time,
+        id: 7,
+    };
+
+    // Startup check reports the table
+    let metrics = Arc::new(DbMetrics::new().expect("metrics init failed"));
+    let checked = DbHandler::new(Arc::clone(&pools), cfg.writer.clone(), Arc::clone(&metrics));
+    let missing = checked.check_conflict_indexes::<LegacyRow>("legacy").await;
+    let first = checked
+        .write_batch(&mut Batch::new(
+            key("trades", "BTCUSDT"),
+            vec![row()],
+            &cfg.writer,
+        ))
+        .await;
+
+    // Without the startup check, the 42P10 of the first insert switches to plain INSERT
+    let unchecked = DbHandler::new(Arc::clone(&pools), cfg.writer.clone(), metrics);
+    let second = unchecked
+        .write_batch(&mut Batch::new(
+            key("trades", "BTCUSDT"),
+            vec![row()],
+            &cfg.writer,
+        ))
+        .await;
+
+    let stored: i64 = sqlx::query_scalar(&format!("SELECT count(*) FROM \"{schema}\".legacy"))
+        .fetch_one(&pool)
+        .await
+        .expect("count");
+    sqlx::query(&format!("DROP SCHEMA IF EXISTS \"{schema}\" CASCADE"))
+        .execute(&pool)
+        .await
+        .expect("drop schema");
+
+    assert!(
+        missing.contains(&format!("{shard_id}:{schema}.legacy")),
+        "{missing:?}"
+    );
+    assert_eq!(first.expect("plain insert").rows_written, 1);
+    assert_eq!(second.expect("fallback insert").rows_written, 1);
+    assert_eq!(stored, 2, "no index: the replay is stored twice");
+}
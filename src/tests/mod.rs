@@ -3,6 +3,7 @@ mod db_writes;
 mod http_deserialize;
 mod redis_failure;
 mod redis_pressure;
+mod redis_pubsub;
 mod redis_retention;
 mod runtime;
 mod ws_deserialize;
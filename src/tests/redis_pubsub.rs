@@ -0,0 +1,108 @@
+/// Integration test:
+/// - enables `streams.also_publish_pubsub`
+/// - subscribes to the channel named like the stream key
+/// - publishes a few events
+/// - verifies each event arrives on the channel AND as a stream entry
+#[tokio::test]
+async fn redis_pubsub_mirrors_stream_entries() {
+    // Imports are scoped to the test: this module is also compiled without cfg(test).
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use futures_util::StreamExt;
+
+    use crate::redis::client::RedisClient;
+    use crate::redis::config::RedisConfig;
+    use crate::redis::manager::{PublishOutcome, RedisManager};
+    use crate::redis::metrics::RedisMetrics;
+    use crate::redis::streams::StreamKind;
+
+    let mut cfg =
+        RedisConfig::load_default().expect("failed to load RedisConfig from default TOML");
+    assert!(cfg.enabled, "Redis must be enabled for this test");
+    cfg.streams.also_publish_pubsub = true;
+    cfg.streams.publish_trades = true;
+
+    let client = Arc::new(
+        RedisClient::connect_from_config(&cfg, false)
+            .await
+            .expect("failed to connect Redis client"),
+    );
+    let manager = RedisManager::new(
+        cfg.clone(),
+        Arc::clone(&client),
+        RedisMetrics::new().expect("failed to create RedisMetrics"),
+    )
+    .expect("failed to create RedisManager");
+
+    let (exchange, symbol, kind) = ("test_pubsub", "BTCUSDT", StreamKind::Trades);
+    let stream_key = manager.keys.key(exchange, symbol, kind);
+
+    // Clean state
+    {
+        let mut conn = client.manager.clone();
+        let _: redis::Value = redis::cmd("DEL")
+            .arg(&stream_key)
+            .query_async(&mut conn)
+            .await
+            .expect("failed to delete existing stream");
+    }
+
+    // Subscribe before publishing (pub/sub has no history)
+    let sub_client = redis::Client::open(cfg.default_uri(false).unwrap()).unwrap();
+    let mut pubsub = sub_client
+        .get_async_pubsub()
+        .await
+        .expect("failed to open pub/sub connection");
+    pubsub
+        .subscribe(&stream_key)
+        .await
+        .expect("SUBSCRIBE failed");
+
+    let n = 3;
+    for i in 0..n {
+        let seq = i.to_string();
+        let fields: [(&str, &str); 2] = [("seq", seq.as_str()), ("price", "100.0")];
+        let outcome = manager
+            .publish(exchange, symbol, kind, &fields)
+            .await
+            .expect("publish returned error");
+        assert_eq!(outcome, PublishOutcome::Published);
+    }
+
+    // Channel: every event, in order
+    let mut messages = pubsub.on_message();
+    for i in 0..n {
+        let msg = tokio::time::timeout(Duration::from_secs(5), messages.next())
+            .await
+            .expect("timed out waiting for pub/sub message")
+            .expect("pub/sub stream closed");
+        assert_eq!(msg.get_channel_name(), stream_key);
+
+        let payload: String = msg.get_payload().expect("non-string payload");
+        let v: serde_json::Value = serde_json::from_str(&payload).expect("payload is not JSON");
+        assert_eq!(v["seq"], i.to_string());
+        assert_eq!(v["price"], "100.0");
+    }
+
+    // Stream: same events are durable
+    let stream_len: u64 = {
+        let mut conn = client.manager.clone();
+        redis::cmd("XLEN")
+            .arg(&stream_key)
+            .query_async(&mut conn)
+            .await
+            .expect("XLEN failed")
+    };
+    assert_eq!(stream_len, n as u64);
+
+    // Cleanup
+    {
+        let mut conn = client.manager.clone();
+        let _: redis::Value = redis::cmd("DEL")
+            .arg(&stream_key)
+            .query_async(&mut conn)
+            .await
+            .expect("failed to cleanup stream");
+    }
+}
@@ -16,18 +16,16 @@ async fn redis_stream_retention_is_enforced() {
     println!("\n[TEST] loading Redis config from TOML");
 
     let cfg = RedisConfig::load_default().expect("failed to load RedisConfig from default TOML");
+    let maxlen = cfg.retention.maxlen_for(StreamKind::Trades);
 
     println!(
         "[TEST] retention.maxlen = {}, approx = {}",
-        cfg.retention.maxlen, cfg.retention.approx
+        maxlen, cfg.retention.approx
     );
     println!("[TEST] redis enabled = {}", cfg.enabled);
 
     assert!(cfg.enabled, "Redis must be enabled for this test");
-    assert!(
-        cfg.retention.maxlen > 0,
-        "retention.maxlen must be > 0 for this test"
-    );
+    assert!(maxlen > 0, "retention.maxlen must be > 0 for this test");
 
     // ------------------------------------------------------------
     // Connect Redis client
@@ -71,11 +69,11 @@ async fn redis_stream_retention_is_enforced() {
     // ------------------------------------------------------------
     // Publish more entries than retention allows
     // ------------------------------------------------------------
-    let publish_count = cfg.retention.maxlen * 2 + 5;
+    let publish_count = maxlen * 2 + 5;
 
     println!(
         "[TEST] publishing {} messages (retention maxlen = {})",
-        publish_count, cfg.retention.maxlen
+        publish_count, maxlen
     );
 
     for i in 0..publish_count {
@@ -114,19 +112,16 @@ async fn redis_stream_retention_is_enforced() {
 
     println!(
         "[TEST] stream length after publishes = {} (expected <= {})",
-        stream_len, cfg.retention.maxlen
+        stream_len, maxlen
     );
 
     if cfg.retention.approx {
         assert!(
-            stream_len <= cfg.retention.maxlen + 5,
+            stream_len <= maxlen + 5,
             "approx retention exceeded tolerance: len={stream_len}"
         );
     } else {
-        assert_eq!(
-            stream_len, cfg.retention.maxlen,
-            "exact retention not enforced"
-        );
+        assert_eq!(stream_len, maxlen, "exact retention not enforced");
     }
 
     // ------------------------------------------------------------